@@ -0,0 +1,188 @@
+use axum::async_trait;
+use redfish_axum::{BackendAdapter, Error, Node};
+use redfish_data::{CollectionType, ResourceSchemaVersion, ResourceType};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::tree::{Collection, Resource};
+
+const CHASSIS_URI: &str = "/redfish/v1/Chassis";
+const SENSORS_URI: &str = "/redfish/v1/Chassis/1/Sensors";
+
+// Returns the output of `ipmitool sdr`, or of something that prints the same format.
+type SdrFn = Box<dyn Fn() -> String + Send + Sync>;
+
+// One line of `ipmitool sdr`, such as "CPU Temp | 45 degrees C | ok".
+struct SensorReading {
+    id: String,
+    name: String,
+    reading: Option<f64>,
+    units: Option<&'static str>,
+    status: String,
+}
+
+impl SensorReading {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('|').map(|field| field.trim()).collect();
+        let (name, value, status) = match fields[..] {
+            [name, value, status] if !name.is_empty() => (name, value, status),
+            _ => return None,
+        };
+        // Readings are like "45 degrees C", or "no reading" for sensors that have none.
+        let (reading, units) = match value.split_once(' ') {
+            Some((reading, units)) => (reading.parse().ok(), units),
+            None => (value.parse().ok(), ""),
+        };
+        let units = match units {
+            "degrees C" => Some("Cel"),
+            "Volts" => Some("V"),
+            "Amps" => Some("A"),
+            "Watts" => Some("W"),
+            "RPM" => Some("RPM"),
+            "percent" => Some("%"),
+            _ => None,
+        };
+        Some(Self {
+            id: name.chars().filter(|c| c.is_ascii_alphanumeric()).collect(),
+            name: String::from(name),
+            reading,
+            units,
+            status: String::from(status),
+        })
+    }
+
+    fn get_status(&self) -> Value {
+        match self.status.as_str() {
+            "ok" => json!({"State": "Enabled", "Health": "OK"}),
+            "nc" => json!({"State": "Enabled", "Health": "Warning"}),
+            "cr" | "nr" => json!({"State": "Enabled", "Health": "Critical"}),
+            _ => json!({"State": "UnavailableOffline"}),
+        }
+    }
+}
+
+// Serves a chassis with the sensors of an IPMI BMC, reading them again for every request.
+pub struct IpmiSensorAdapter {
+    read_sdr: SdrFn,
+}
+
+impl IpmiSensorAdapter {
+    pub fn new(read_sdr: SdrFn) -> Self {
+        Self { read_sdr }
+    }
+
+    fn get_readings(&self) -> Vec<SensorReading> {
+        (self.read_sdr)()
+            .lines()
+            .filter_map(SensorReading::parse)
+            .collect()
+    }
+}
+
+fn get_chassis_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("Chassis"),
+        ResourceSchemaVersion::new(1, 22, 0),
+    )
+}
+
+fn get_sensor_type() -> ResourceType {
+    ResourceType::new_dmtf(String::from("Sensor"), ResourceSchemaVersion::new(1, 7, 0))
+}
+
+#[async_trait]
+impl BackendAdapter for IpmiSensorAdapter {
+    fn get_root(&self) -> &str {
+        CHASSIS_URI
+    }
+
+    async fn materialize(&self, uri: &str) -> Result<Arc<dyn Node + Send + Sync>, Error> {
+        if uri == CHASSIS_URI {
+            return Ok(Arc::new(Collection::new(
+                CHASSIS_URI,
+                String::from("ChassisCollection"),
+                String::from("Chassis Collection"),
+                vec![format!("{}/1", CHASSIS_URI)],
+                None,
+            )));
+        }
+        if uri == format!("{}/1", CHASSIS_URI) {
+            let chassis_type = get_chassis_type();
+            return Ok(Arc::new(Resource::new(
+                uri,
+                chassis_type.name,
+                chassis_type.version,
+                String::from("Chassis"),
+                String::from("IPMI Chassis"),
+                None,
+                None,
+                Some(String::from(CHASSIS_URI)),
+                json!({
+                    "ChassisType": "RackMount",
+                    "Sensors": {"@odata.id": SENSORS_URI},
+                }),
+            )));
+        }
+        if uri == SENSORS_URI {
+            let members = self
+                .get_readings()
+                .iter()
+                .map(|sensor| format!("{}/{}", SENSORS_URI, sensor.id))
+                .collect();
+            return Ok(Arc::new(Collection::new(
+                SENSORS_URI,
+                String::from("SensorCollection"),
+                String::from("Sensor Collection"),
+                members,
+                None,
+            )));
+        }
+        let id = match uri
+            .strip_prefix(SENSORS_URI)
+            .and_then(|id| id.strip_prefix('/'))
+        {
+            Some(id) => id,
+            None => return Err(Error::NotFound),
+        };
+        let sensor = self
+            .get_readings()
+            .into_iter()
+            .find(|sensor| sensor.id == id)
+            .ok_or(Error::NotFound)?;
+        let sensor_type = get_sensor_type();
+        Ok(Arc::new(Resource::new(
+            uri,
+            sensor_type.name,
+            sensor_type.version,
+            String::from("Sensor"),
+            sensor.name.clone(),
+            None,
+            None,
+            Some(String::from(SENSORS_URI)),
+            json!({
+                "Reading": sensor.reading,
+                "ReadingUnits": sensor.units,
+                "Status": sensor.get_status(),
+            }),
+        )))
+    }
+
+    fn get_collection_types(&self) -> Vec<CollectionType> {
+        vec![
+            CollectionType::new_dmtf_v1(String::from("ChassisCollection")),
+            CollectionType::new_dmtf_v1(String::from("SensorCollection")),
+        ]
+    }
+
+    fn get_resource_types(&self) -> Vec<ResourceType> {
+        vec![get_chassis_type(), get_sensor_type()]
+    }
+}
+
+// Run ipmitool against the local BMC. With no BMC, there are no sensors.
+pub fn run_ipmitool_sdr() -> String {
+    match std::process::Command::new("ipmitool").arg("sdr").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(_) => String::new(),
+    }
+}
@@ -1,26 +1,57 @@
-use axum::{Router, ServiceExt};
-use redfish_axum::{Error, Node};
-use redfish_data::{get_uri_id, ResourceSchemaVersion};
+use axum::{async_trait, ServiceExt};
+use bytes::Bytes;
+#[cfg(feature = "fault-injection")]
+use redfish_axum::FaultScenario;
+use redfish_axum::{
+    check_profile, validate, AppBuilder, AuditLog, BackendTree, CachingAdapter, Error,
+    InteropProfile, Node, NodeLink, NumericThresholds, Scaffold, Threshold, ThresholdActivation,
+    Trigger, TriggerAction, UploadWriter,
+};
+use redfish_client::{Auth, Client};
+use redfish_data::{
+    get_uri_id, lint_message_registry, DiagnosticSeverity, PropertyType, ResourceSchemaVersion,
+};
 use serde_json::{json, Map, Value};
-use tower_http::normalize_path::NormalizePath;
-use axum_server::tls_rustls::RustlsConfig;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
+mod ipmi;
+mod tls;
 mod tree;
-use tree::{Collection, MockTree, Resource};
+use ipmi::{run_ipmitool_sdr, IpmiSensorAdapter};
+use tls::CertificateWatcher;
+use tree::{Attachment, Collection, MockTree, Resource};
 
-fn create_session(collection: &Collection, request_body: &Map<String, Value>) -> Result<Resource, Error> {
-    // Look at existing members to see next Id to pick
-    let mut highest = 0;
-    for member in collection.members.iter() {
-        let id = get_uri_id(member.as_str());
-        let id = id.parse().unwrap(); // TODO: Not so catastrophic?
-        if id > highest {
-            highest = id;
+fn create_session(
+    collection: &Collection,
+    request_body: &Map<String, Value>,
+) -> Result<Resource, Error> {
+    let id = match request_body.get("Id").and_then(|id| id.as_str()) {
+        Some(id) => String::from(id),
+        None => {
+            // Look at existing members to see next Id to pick, skipping those clients picked
+            let mut highest = 0;
+            for member in collection.members.iter() {
+                let id = get_uri_id(member.as_str());
+                if let Ok(id) = id.parse() {
+                    if id > highest {
+                        highest = id;
+                    }
+                }
+            }
+            (highest + 1).to_string()
         }
-    }
-    let id = (highest + 1).to_string();
+    };
     let member_uri = format!("{}/{}", collection.get_uri(), id);
+    // The service only checks this for clients that can read the collection
+    if collection.members.contains(&member_uri) {
+        return Err(Error::ResourceAlreadyExists(
+            String::from("Session"),
+            String::from("Id"),
+            id,
+        ));
+    }
 
     // Return new resource
     Ok(Resource::new(
@@ -28,27 +59,80 @@ fn create_session(collection: &Collection, request_body: &Map<String, Value>) ->
         String::from("Session"),
         ResourceSchemaVersion::new(1, 6, 0),
         String::from("Session"),
-        String::from(format!("Session {}", id)),
+        format!("Session {}", id),
         Some(|_| Ok(())),
         None,
         Some(String::from(collection.get_uri())),
         json!({
-            "UserName": request_body.get("UserName").unwrap().as_str(),
+            // The service has checked that it was given.
+            "UserName": request_body["UserName"].as_str(),
             "Password": serde_json::Value::Null,
         }),
     ))
 }
 
-fn patch_session_service(resource: &mut Resource, request_body: &Map<String, Value>) -> Result<(), Error> {
+fn patch_session_service(
+    resource: &mut Resource,
+    request_body: &Map<String, Value>,
+) -> Result<(), Error> {
     // TODO: API for patch handling
     if let Some(timeout) = request_body.get("SessionTimeout") {
         // TODO: Validate the value!
         resource.body["SessionTimeout"] = Value::from(timeout.as_u64().unwrap());
     }
+    if let Some(enabled) = request_body.get("ServiceEnabled") {
+        resource.body.insert(
+            String::from("ServiceEnabled"),
+            Value::from(enabled.as_bool().unwrap()),
+        );
+    }
     // TODO: Error handling of attempts to patch other properties
     Ok(())
 }
 
+// Pretend to flash an uploaded firmware image, by only counting its bytes.
+struct FirmwareImageWriter {
+    size: u64,
+}
+
+#[async_trait]
+impl UploadWriter for FirmwareImageWriter {
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error> {
+        self.size += chunk.len() as u64;
+        Ok(())
+    }
+
+    async fn finish(self: Box<Self>) -> Result<(), Error> {
+        if self.size == 0 {
+            return Err(Error::BadRequest);
+        }
+        Ok(())
+    }
+}
+
+fn get_cpu_temperature_trigger() -> Trigger {
+    let thresholds = NumericThresholds {
+        upper_critical: Some(Threshold::new(95.0, ThresholdActivation::Increasing)),
+        upper_warning: Some(Threshold::new(85.0, ThresholdActivation::Either)),
+        ..Default::default()
+    };
+    Trigger::numeric(
+        "/redfish/v1/TelemetryService/Triggers/CPUTemperature",
+        "CPU Temperature Trigger",
+        thresholds,
+        vec![
+            TriggerAction::RedfishEvent,
+            TriggerAction::RedfishMetricReport,
+        ],
+    )
+    .with_metric_properties(vec![String::from(
+        "/redfish/v1/Chassis/1/Sensors/CPUTemp#/Reading",
+    )])
+    .with_metric_report_definitions(vec![String::from(
+        "/redfish/v1/TelemetryService/MetricReportDefinitions/Thermal",
+    )])
+}
+
 fn get_mock_tree() -> MockTree {
     let mut tree = MockTree::new();
     tree.add_resource(Resource::new(
@@ -64,6 +148,9 @@ fn get_mock_tree() -> MockTree {
             "AccountService": {
                 "@odata.id": "/redfish/v1/AccountService",
             },
+            "EventService": {
+                "@odata.id": "/redfish/v1/EventService",
+            },
             "Links": {
                 "Sessions": {
                     "@odata.id": "/redfish/v1/SessionService/Sessions"
@@ -74,30 +161,37 @@ fn get_mock_tree() -> MockTree {
             },
         }),
     ));
-    tree.add_resource(Resource::new(
-        "/redfish/v1/SessionService",
-        String::from("SessionService"),
-        ResourceSchemaVersion::new(1, 1, 8),
-        String::from("SessionService"),
-        String::from("Session Service"),
-        None,
-        Some(patch_session_service),
-        None,
-        json!({
-            "@Redfish.WriteableProperties": ["SessionTimeout"],
-            "SessionTimeout": 600,
-            "Sessions": {
-                "@odata.id": "/redfish/v1/SessionService/Sessions"
-            },
-        }),
-    ));
-    tree.add_collection(Collection::new(
-        "/redfish/v1/SessionService/Sessions",
-        String::from("SessionCollection"),
-        String::from("Session Collection"),
-        Vec::new(),
-        Some(create_session),
-    ));
+    tree.add_resource(
+        Resource::new(
+            "/redfish/v1/SessionService",
+            String::from("SessionService"),
+            ResourceSchemaVersion::new(1, 1, 8),
+            String::from("SessionService"),
+            String::from("Session Service"),
+            None,
+            Some(patch_session_service),
+            None,
+            json!({
+                "@Redfish.WriteableProperties": ["SessionTimeout"],
+                "SessionTimeout": 600,
+                "Sessions": {
+                    "@odata.id": "/redfish/v1/SessionService/Sessions"
+                },
+            }),
+        )
+        .with_property("SessionTimeout", PropertyType::Integer)
+        .with_property("ServiceEnabled", PropertyType::Boolean),
+    );
+    tree.add_collection(
+        Collection::new(
+            "/redfish/v1/SessionService/Sessions",
+            String::from("SessionCollection"),
+            String::from("Session Collection"),
+            Vec::new(),
+            Some(create_session),
+        )
+        .with_required_properties(&["UserName", "Password"]),
+    );
     tree.add_resource(Resource::new(
         "/redfish/v1/AccountService",
         String::from("AccountService"),
@@ -123,28 +217,48 @@ fn get_mock_tree() -> MockTree {
         vec![String::from("/redfish/v1/AccountService/Accounts/admin")],
         None,
     ));
-    tree.add_resource(Resource::new(
+    tree.add_resource(
+        Resource::new(
+            "/redfish/v1/AccountService/Accounts/admin",
+            String::from("ManagerAccount"),
+            ResourceSchemaVersion::new(1, 10, 0),
+            String::from("ManagerAccount"),
+            String::from("Admin Account"),
+            None,
+            None,
+            Some(String::from("/redfish/v1/AccountService/Accounts")),
+            json!({
+                "@Redfish.WriteableProperties": ["Password"],
+                "AccountTypes": ["Redfish"],
+                "Links": {
+                    "Role": {
+                        "@odata.id": "/redfish/v1/AccountService/Roles/Administrator"
+                    }
+                },
+                "Oem": {
+                    "Contoso": {
+                        "@odata.type": "#ContosoManagerAccount.v1_0_0.ContosoManagerAccount",
+                        "ConsoleAccess": true,
+                    }
+                },
+                "Password": null,
+                "RoleId": "Administrator",
+                "UserName": "admin",
+            }),
+        )
+        // Clients find the schema of the OEM extension from the Link header.
+        .with_link(NodeLink::new(
+            "https://contoso.com/schemas/ContosoManagerAccount.v1_0_0.json"
+                .parse()
+                .unwrap(),
+            "describedby",
+        )),
+    );
+    tree.add_link(
         "/redfish/v1/AccountService/Accounts/admin",
-        String::from("ManagerAccount"),
-        ResourceSchemaVersion::new(1, 10, 0),
-        String::from("ManagerAccount"),
-        String::from("Admin Account"),
-        None,
-        None,
-        Some(String::from("/redfish/v1/AccountService/Accounts")),
-        json!({
-            "@Redfish.WriteableProperties": ["Password"],
-            "AccountTypes": ["Redfish"],
-            "Links": {
-                "Role": {
-                    "@odata.id": "/redfish/v1/AccountService/Roles/Administrator"
-                }
-            },
-            "Password": null,
-            "RoleId": "Administrator",
-            "UserName": "admin",
-        }),
-    ));
+        "Links/Role",
+        "/redfish/v1/AccountService/Roles/Administrator",
+    );
     tree.add_collection(Collection::new(
         "/redfish/v1/AccountService/Roles",
         String::from("RoleCollection"),
@@ -214,23 +328,185 @@ fn get_mock_tree() -> MockTree {
             "RoleId": "ReadOnly",
         }),
     ));
+    tree.add_attachment(Attachment::new(
+        "/redfish/v1/CertificateService/ServerCertificate.pem",
+        "application/x-pem-file",
+        Bytes::from_static(include_bytes!("../cert.pem")),
+    ));
+    tree.add_collection(Collection::new(
+        "/redfish/v1/TelemetryService/Triggers",
+        String::from("TriggersCollection"),
+        String::from("Triggers Collection"),
+        vec![String::from(
+            "/redfish/v1/TelemetryService/Triggers/CPUTemperature",
+        )],
+        None,
+    ));
+    tree.add_trigger(get_cpu_temperature_trigger());
+    tree.add_upload(
+        "/redfish/v1/UpdateService/upload",
+        Box::new(|| Box::new(FirmwareImageWriter { size: 0 })),
+    );
     tree
 }
 
-fn app() -> NormalizePath<Router> {
+// Print the problems in a message registry file. Returns whether it is safe to load.
+fn lint_registry(path: &str) -> bool {
+    let data = std::fs::read_to_string(path).expect("Unable to read file");
+    let diagnostics = lint_message_registry(&data);
+    for diagnostic in diagnostics.iter() {
+        let location = match &diagnostic.message_key {
+            Some(key) => format!("{}: {}", path, key),
+            None => String::from(path),
+        };
+        println!(
+            "{:?}: {}: {}",
+            diagnostic.severity, location, diagnostic.description
+        );
+    }
+    !diagnostics
+        .iter()
+        .any(|d| d.severity == DiagnosticSeverity::Error)
+}
+
+// Print the TreeBuilder setup for the resources an interop profile or OpenAPI document requires.
+fn print_scaffold(kind: &str, path: &str) -> bool {
+    let data = std::fs::read_to_string(path).expect("Unable to read file");
+    let scaffold = match kind {
+        "profile" => Scaffold::from_interop_profile(&data),
+        "openapi" => Scaffold::from_openapi(&data),
+        _ => {
+            println!(
+                "Unknown document kind {}, expected profile or openapi",
+                kind
+            );
+            return false;
+        }
+    };
+    match scaffold {
+        Ok(scaffold) => {
+            print!("{}", scaffold.to_rust());
+            true
+        }
+        Err(error) => {
+            println!("{:?}", error);
+            false
+        }
+    }
+}
+
+// Print what the startup self-check finds wrong with the mock tree, as read by the admin.
+async fn print_validation() -> bool {
     let tree = get_mock_tree();
-    redfish_axum::app(tree)
+    let report = validate(&tree, Some("admin")).await;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report.to_json()).unwrap()
+    );
+    report.is_ok()
+}
+
+// Print how the mock tree, as read by the admin, falls short of an interop profile.
+async fn print_conformance(path: &str) -> bool {
+    let data = std::fs::read_to_string(path).expect("Unable to read file");
+    let profile = match InteropProfile::from_json(&data) {
+        Ok(profile) => profile,
+        Err(error) => {
+            println!("{:?}", error);
+            return false;
+        }
+    };
+    let report = check_profile(&get_mock_tree(), Some("admin"), &profile).await;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report.to_json()).unwrap()
+    );
+    report.is_conformant()
+}
+
+// Print the inventory of the Redfish service at the URL, such as http://127.0.0.1:8000, as read
+// by the user. The client only speaks HTTP, so this service is reached through a proxy that
+// terminates its TLS.
+async fn print_inventory(url: &str, username: &str, password: &str) -> bool {
+    let client = Client::new(url).with_auth(Auth::Basic {
+        username: String::from(username),
+        password: String::from(password),
+    });
+    match client.get_inventory().await {
+        Ok(inventory) => {
+            println!("{}", serde_json::to_string_pretty(&inventory).unwrap());
+            true
+        }
+        Err(error) => {
+            println!("{}", error);
+            false
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let config = RustlsConfig::from_pem_file("example/cert.pem", "example/key.pem")
-        .await
-        .unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "lint-registry" {
+        let ok = lint_registry(&args[2]);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if args.len() == 4 && args[1] == "scaffold" {
+        let ok = print_scaffold(&args[2], &args[3]);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if args.len() == 3 && args[1] == "check-profile" {
+        let ok = print_conformance(&args[2]).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if args.len() == 5 && args[1] == "inventory" {
+        let ok = print_inventory(&args[2], &args[3], &args[4]).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if args.len() == 2 && args[1] == "validate" {
+        let ok = print_validation().await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Serving with the faults of a scenario file makes the service misbehave, for testing
+    // clients against. Only built with the fault-injection feature.
+    #[cfg(feature = "fault-injection")]
+    let faults = match args.get(1).map(String::as_str) {
+        Some("faults") if args.len() == 3 => {
+            let data = std::fs::read_to_string(&args[2]).expect("Unable to read file");
+            Some(FaultScenario::from_json(&data).expect("Invalid fault scenario"))
+        }
+        _ => None,
+    };
+
+    // Installing a new certificate over these files takes effect within a few seconds.
+    let watcher = CertificateWatcher::new(
+        PathBuf::from("example/cert.pem"),
+        PathBuf::from("example/key.pem"),
+    )
+    .await
+    .unwrap();
+    let config = watcher.get_config();
+    tokio::spawn(watcher.watch(Duration::from_secs(5)));
+
+    // Chassis and their sensors come from the local BMC over IPMI, which is slow to query.
+    let adapter = IpmiSensorAdapter::new(Box::new(run_ipmitool_sdr));
+    let adapter = CachingAdapter::new(adapter, Duration::from_secs(5));
+    let audit_log = AuditLog::new("/redfish/v1/Managers/BMC/LogServices/AuditLog", 500);
+    let mut tree = get_mock_tree();
+    tree.add_audit_log(audit_log.clone());
+    let tree = BackendTree::new(tree).with_adapter(adapter);
+    let builder = AppBuilder::new(tree).audit_hook(audit_log);
+    #[cfg(feature = "fault-injection")]
+    let builder = match faults {
+        Some(faults) => builder.fault_scenario(faults),
+        None => builder,
+    };
+    let app = builder.build();
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     axum_server::bind_rustls(addr, config)
-        .serve(app().into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
@@ -240,12 +516,57 @@ mod tests {
     use super::*;
     use axum::{
         body::Body,
+        extract::ConnectInfo,
         http::{Request, StatusCode},
         response::Response,
+        Router,
     };
     use http::{request::Builder, HeaderValue};
+    #[cfg(feature = "fault-injection")]
+    use redfish_axum::FaultKind;
+    use redfish_axum::{
+        current_user, get_inventory, AccountLockouts, AccountServiceTree, ActionVisibility,
+        AppBuilder, ArchiveReader, AuditAction, AuditEntry, AuditHook, AuditLog,
+        AuthThrottleConfig, AuthenticationProvider, BackendAdapter, Clock, ConcurrencyLimitConfig,
+        ConfigurationStore, ConformanceIssue, CreateLimit, CreateLimitResponse, DBusAction,
+        DBusConnection, DBusProperty, DBusSubtree, DBusTree, DeliveryOutcome,
+        DisabledSessionPolicy, DiscreteTrigger, DocumentAccess, ErrorHook, EventFilter,
+        EventSender, EventService, EventServiceSettings, ExternalAccountProvider,
+        ExternalAccountProviderType, FileSessionStore, Lifecycle, LifecyclePhase, LinkRegistry,
+        LocalAccounts, ManagerBuilder, ManualClock, MemorySessionStore, NativeQuerySupport,
+        NetworkProtocol, NetworkRuntime, NodeLink, NodeRef, Outcome, PartialPatch,
+        PropertyValidator, ProtocolSettings, Query, Reference, RequestUser, ResponseStatistics,
+        SessionLimitConfig, SessionLimitPolicy, SessionManager, SessionServiceTree, SessionStore,
+        SessionTokenConfig, StaticTree, Task, TaskLocation, TaskService, Tree, TreeBuilder,
+        ValidationIssue,
+    };
+    use redfish_data::PasswordHashConfig;
+    use redfish_data::{get_datetime_string, Health};
+    use redfish_data::{
+        AllowedMethods, CollectionType, ErrorResponse, Message, MessageRegistry, PrivilegeTarget,
+        ResourceType,
+    };
     use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::num::NonZeroU32;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::time::{Instant, SystemTime};
     use tower::{Service, ServiceExt};
+    use tower_http::normalize_path::NormalizePath;
+
+    fn app() -> NormalizePath<Router> {
+        let tree = get_mock_tree();
+        redfish_axum::app(tree)
+    }
+
+    // The body without its @odata.etag, which changes with the rest of it, for comparing the
+    // rest. Checks that it has one.
+    fn without_etag(mut body: Value) -> Value {
+        let etag = body.as_object_mut().unwrap().remove("@odata.etag");
+        assert!(etag.is_some_and(|etag| etag.as_str().is_some_and(|etag| etag.starts_with('"'))));
+        body
+    }
 
     enum Auth {
         Token(String),
@@ -257,11 +578,11 @@ mod tests {
         match auth {
             Auth::Token(token) => {
                 let headers = req.headers_mut().unwrap();
-                headers.insert("x-auth-token", HeaderValue::from_str(&token).unwrap());
+                headers.insert("x-auth-token", HeaderValue::from_str(token).unwrap());
             }
             Auth::Basic(header_val) => {
                 let headers = req.headers_mut().unwrap();
-                headers.insert("authorization", HeaderValue::from_str(&header_val).unwrap());
+                headers.insert("authorization", HeaderValue::from_str(header_val).unwrap());
             }
             _ => (),
         }
@@ -279,6 +600,19 @@ mod tests {
         app.ready().await.unwrap().call(req).await.unwrap()
     }
 
+    async fn get_from(
+        app: &mut NormalizePath<Router>,
+        uri: &str,
+        auth: &Auth,
+        client: SocketAddr,
+    ) -> Response {
+        let mut req = Request::get(uri);
+        add_auth_headers(&mut req, auth);
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(client));
+        app.ready().await.unwrap().call(req).await.unwrap()
+    }
+
     async fn get_response_json(response: Response) -> Value {
         assert_eq!(get_header(&response, "content-type"), "application/json");
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
@@ -297,7 +631,7 @@ mod tests {
         assert_eq!(get_header(&response, "OData-Version"), "4.0");
         assert_eq!(get_header(&response, "cache-control"), "no-cache");
         for (key, val) in headers {
-            assert_eq!(get_header(&response, *key), *val);
+            assert_eq!(get_header(&response, key), *val);
         }
         get_response_json(response).await
     }
@@ -365,10 +699,128 @@ mod tests {
         app.ready().await.unwrap().call(req).await.unwrap()
     }
 
+    // Object path, method name and args of a D-Bus method call.
+    type MethodCall = (String, String, Vec<Value>);
+
+    // Bus holding properties in memory, and recording the methods called.
+    #[derive(Clone, Default)]
+    struct FakeBus {
+        properties: Arc<Mutex<HashMap<(String, String), Value>>>,
+        calls: Arc<Mutex<Vec<MethodCall>>>,
+    }
+
+    #[async_trait]
+    impl DBusConnection for FakeBus {
+        async fn get_child_paths(&self, object_path: &str) -> Result<Vec<String>, Error> {
+            let prefix = format!("{}/", object_path);
+            let mut paths: Vec<String> = self
+                .properties
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|(path, _)| path.starts_with(&prefix))
+                .map(|(path, _)| path.clone())
+                .collect();
+            paths.sort();
+            paths.dedup();
+            Ok(paths)
+        }
+
+        async fn get_property(
+            &self,
+            object_path: &str,
+            _interface: &str,
+            property: &str,
+        ) -> Result<Value, Error> {
+            let key = (String::from(object_path), String::from(property));
+            self.properties
+                .lock()
+                .unwrap()
+                .get(&key)
+                .cloned()
+                .ok_or(Error::NotFound)
+        }
+
+        async fn set_property(
+            &self,
+            object_path: &str,
+            _interface: &str,
+            property: &str,
+            value: &Value,
+        ) -> Result<(), Error> {
+            let key = (String::from(object_path), String::from(property));
+            self.properties.lock().unwrap().insert(key, value.clone());
+            Ok(())
+        }
+
+        async fn call_method(
+            &self,
+            object_path: &str,
+            _interface: &str,
+            method: &str,
+            args: Vec<Value>,
+        ) -> Result<(), Error> {
+            self.calls.lock().unwrap().push((
+                String::from(object_path),
+                String::from(method),
+                args,
+            ));
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct AuditRecorder {
+        entries: Arc<Mutex<Vec<AuditEntry>>>,
+    }
+
+    impl AuditHook for AuditRecorder {
+        fn record(&self, entry: &AuditEntry) {
+            self.entries.lock().unwrap().push(entry.clone());
+        }
+    }
+
+    // Bytes received so far and total expected, after each chunk.
+    type UploadProgress = Vec<(u64, Option<u64>)>;
+
+    // Upload writer that records what it is given.
+    #[derive(Clone, Default)]
+    struct RecordingWriter {
+        data: Arc<Mutex<Vec<u8>>>,
+        progress: Arc<Mutex<UploadProgress>>,
+        finished: Arc<Mutex<bool>>,
+    }
+
+    #[async_trait]
+    impl UploadWriter for RecordingWriter {
+        async fn write(&mut self, chunk: Bytes) -> Result<(), Error> {
+            self.data.lock().unwrap().extend_from_slice(&chunk);
+            Ok(())
+        }
+
+        fn progress(&mut self, received: u64, total: Option<u64>) {
+            self.progress.lock().unwrap().push((received, total));
+        }
+
+        async fn finish(self: Box<Self>) -> Result<(), Error> {
+            *self.finished.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    struct AdminOnly;
+
+    #[async_trait]
+    impl AuthenticationProvider for AdminOnly {
+        async fn authenticate(&self, username: &str, password: &str) -> bool {
+            username == "admin" && password == "admin"
+        }
+    }
+
     fn validate_unauthorized(response: &Response) {
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
         assert_eq!(
-            get_header(&response, "www-authenticate"),
+            get_header(response, "www-authenticate"),
             "Basic realm=\"simple\""
         );
     }
@@ -414,9 +866,8 @@ mod tests {
                 ("link", "<https://redfish.dmtf.org/schemas/v1/ServiceRoot.v1_15_0.json>; rel=describedby"),
             ]).await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1",
                 "@odata.type": "#ServiceRoot.v1_15_0.ServiceRoot",
                 "Id": "RootService",
@@ -424,6 +875,9 @@ mod tests {
                 "AccountService": {
                     "@odata.id": "/redfish/v1/AccountService",
                 },
+                "EventService": {
+                    "@odata.id": "/redfish/v1/EventService",
+                },
                 "Links": {
                     "Sessions": {
                         "@odata.id": "/redfish/v1/SessionService/Sessions"
@@ -463,6 +917,11 @@ mod tests {
                         "name": "AccountService",
                         "url": "/redfish/v1/AccountService",
                     },
+                    {
+                        "kind": "Singleton",
+                        "name": "EventService",
+                        "url": "/redfish/v1/EventService",
+                    },
                     {
                         "kind": "Singleton",
                         "name": "SessionService",
@@ -473,6 +932,196 @@ mod tests {
         );
     }
 
+    fn get_german_base_registry() -> &'static MessageRegistry {
+        static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(|| {
+            let mut data: Value = serde_json::from_str(include_str!(
+                "../../redfish-data/registries/Base.1.16.0.json"
+            ))
+            .unwrap();
+            data["Language"] = json!("de");
+            data["Messages"]["GeneralError"]["Message"] =
+                json!("Ein allgemeiner Fehler ist aufgetreten.");
+            data["Messages"]["PropertyValueTypeError"]["Message"] =
+                json!("Der Wert '%1' der Eigenschaft %2 hat den falschen Typ.");
+            MessageRegistry::from_json(&data.to_string())
+        })
+    }
+
+    #[tokio::test]
+    async fn localized_error_messages() {
+        async fn patch_in(app: &mut NormalizePath<Router>, accept_language: Option<&str>) -> Value {
+            let body = Body::from(json!({"SessionTimeout": "forever"}).to_string());
+            let mut req = Request::patch("/redfish/v1/SessionService")
+                .header("Content-Type", "application/json");
+            if let Some(accept_language) = accept_language {
+                req = req.header("Accept-Language", accept_language);
+            }
+            add_auth_headers(&mut req, &admin_admin_basic_auth());
+            let response = app
+                .ready()
+                .await
+                .unwrap()
+                .call(req.body(body).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            get_response_json(response).await["error"].clone()
+        }
+
+        let mut localized = AppBuilder::new(get_mock_tree())
+            .localized_registries(Some(vec![get_german_base_registry()]))
+            .build();
+        for accept_language in ["de", "de-DE", "fr;q=0.5, de-AT;q=0.8", "de, *;q=0.5"] {
+            let error = patch_in(&mut localized, Some(accept_language)).await;
+            assert_eq!(error["code"], "Base.1.16.GeneralError");
+            assert_eq!(error["message"], "Ein allgemeiner Fehler ist aufgetreten.");
+            let info = &error["@Message.ExtendedInfo"][0];
+            assert_eq!(info["MessageId"], "Base.1.16.PropertyValueTypeError");
+            assert_eq!(
+                info["Message"],
+                "Der Wert 'forever' der Eigenschaft SessionTimeout hat den falschen Typ."
+            );
+            assert_eq!(info["Language"], "de");
+        }
+
+        // Languages without a registry fall back to the default one.
+        for accept_language in [None, Some("fr"), Some("*, de;q=0.5"), Some("de;q=0")] {
+            let error = patch_in(&mut localized, accept_language).await;
+            assert!(error["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("A general error"));
+            assert_eq!(error["@Message.ExtendedInfo"][0]["Language"], "en");
+        }
+
+        // Without localized registries, messages do not give their language.
+        let mut app = app();
+        let error = patch_in(&mut app, Some("de")).await;
+        assert!(error["@Message.ExtendedInfo"][0].get("Language").is_none());
+    }
+
+    fn get_contoso_registry() -> &'static MessageRegistry {
+        static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(|| {
+            let data = json!({
+                "Language": "en",
+                "RegistryPrefix": "Contoso",
+                "RegistryVersion": "1.0.0",
+                "Messages": {
+                    "ContactSupport": {
+                        "Message": "Contact Contoso support about error %1.",
+                        "MessageSeverity": "Critical",
+                        "NumberOfArgs": 1,
+                        "Resolution": "None.",
+                    },
+                },
+            });
+            MessageRegistry::from_json(&data.to_string())
+        })
+    }
+
+    // Points clients to the support page of each error.
+    struct SupportInfo;
+
+    impl ErrorHook for SupportInfo {
+        fn extend(&self, error: &mut ErrorResponse) {
+            let code = error.get_code().rsplit('.').next().unwrap().to_string();
+            let url = format!("https://support.contoso.com/{}", code);
+            error.insert_property(String::from("Oem"), json!({"Contoso": {"SupportUrl": url}}));
+            let message = Message::from_registry(
+                get_contoso_registry(),
+                "ContactSupport",
+                ResourceSchemaVersion::new(1, 1, 2),
+                vec![code],
+                Vec::new(),
+            )
+            .unwrap();
+            error.add_extended_info(message);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_hook() {
+        let mut service = AppBuilder::new(get_mock_tree())
+            .error_hook(SupportInfo)
+            .build();
+        let data = json!({"UserName": "Obiwan", "Password": "n/a", "Context": 7});
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            data,
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.GeneralError");
+        assert_eq!(
+            body["error"]["Oem"]["Contoso"]["SupportUrl"],
+            "https://support.contoso.com/GeneralError"
+        );
+        let info = body["error"]["@Message.ExtendedInfo"].as_array().unwrap();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0]["MessageId"], "Base.1.16.PropertyValueTypeError");
+        assert_eq!(info[1]["MessageId"], "Contoso.1.0.ContactSupport");
+
+        // Errors made outside of the handlers are extended too
+        let request = Request::get("/redfish/v1")
+            .header("OData-Version", "4.1")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["Oem"]["Contoso"]["SupportUrl"],
+            "https://support.contoso.com/HeaderInvalid"
+        );
+    }
+
+    #[tokio::test]
+    async fn odata_context() {
+        let mut with_context = AppBuilder::new(get_mock_tree()).odata_context(true).build();
+        let body =
+            get_response_json(get(&mut with_context, "/redfish/v1", &Auth::None).await).await;
+        assert_eq!(
+            body["@odata.context"],
+            "/redfish/v1/$metadata#ServiceRoot.ServiceRoot"
+        );
+        let uri = "/redfish/v1/SessionService/Sessions";
+        let body =
+            get_response_json(get(&mut with_context, uri, &admin_admin_basic_auth()).await).await;
+        assert_eq!(
+            body["@odata.context"],
+            "/redfish/v1/$metadata#SessionCollection.SessionCollection"
+        );
+
+        let mut app = app();
+        let body = get_response_json(get(&mut app, "/redfish/v1", &Auth::None).await).await;
+        assert!(body.get("@odata.context").is_none());
+    }
+
+    #[tokio::test]
+    async fn document_access() {
+        let mut public = app();
+        let mut authenticated = AppBuilder::new(get_mock_tree())
+            .document_access(DocumentAccess::Authenticated)
+            .build();
+        let bad_token = Auth::Token(String::from("bogus"));
+        for uri in ["/redfish/v1/$metadata", "/redfish/v1/odata"] {
+            let response = get(&mut public, uri, &Auth::None).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = get(&mut public, uri, &bad_token).await;
+            validate_unauthorized(&response);
+
+            let response = get(&mut authenticated, uri, &Auth::None).await;
+            validate_unauthorized(&response);
+            let response = get(&mut authenticated, uri, &admin_admin_basic_auth()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
     #[tokio::test]
     async fn get_odata_metadata_doc() {
         let mut app = app();
@@ -497,6 +1146,9 @@ mod tests {
   <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/RoleCollection_v1.xml">
     <edmx:Include Namespace="RoleCollection" />
   </edmx:Reference>
+  <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/TriggersCollection_v1.xml">
+    <edmx:Include Namespace="TriggersCollection" />
+  </edmx:Reference>
   <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/ServiceRoot_v1.xml">
     <edmx:Include Namespace="ServiceRoot" />
     <edmx:Include Namespace="ServiceRoot.v1_15_0" />
@@ -517,6 +1169,14 @@ mod tests {
     <edmx:Include Namespace="Role" />
     <edmx:Include Namespace="Role.v1_3_1" />
   </edmx:Reference>
+  <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/Triggers_v1.xml">
+    <edmx:Include Namespace="Triggers" />
+    <edmx:Include Namespace="Triggers.v1_3_0" />
+  </edmx:Reference>
+  <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/EventService_v1.xml">
+    <edmx:Include Namespace="EventService" />
+    <edmx:Include Namespace="EventService.v1_10_0" />
+  </edmx:Reference>
   <edmx:Reference Uri="http://redfish.dmtf.org/schemas/v1/RedfishExtensions_v1.xml">
     <edmx:Include Namespace="RedfishExtensions.v1_0_0" Alias="Redfish"/>
   </edmx:Reference>
@@ -570,6 +1230,81 @@ mod tests {
         assert_eq!(body, "");
     }
 
+    #[tokio::test]
+    async fn node_links() {
+        let uri = "/redfish/v1/Systems/Linked";
+        let mut tree = get_mock_tree();
+        let oem_schema = "https://contoso.com/schemas/ContosoComputerSystem.v1_0_0.json";
+        let action_info = "/redfish/v1/Systems/Linked/ResetActionInfo";
+        tree.add_resource(
+            Resource::new(
+                uri,
+                String::from("ComputerSystem"),
+                ResourceSchemaVersion::new(1, 20, 0),
+                String::from("ComputerSystem"),
+                String::from("Linked System"),
+                None,
+                None,
+                None,
+                json!({}),
+            )
+            .with_link(NodeLink::new(oem_schema.parse().unwrap(), "describedby"))
+            .with_link(NodeLink::new(
+                action_info.parse().unwrap(),
+                "https://contoso.com/rels/action-info",
+            ))
+            .with_link(NodeLink::new(action_info.parse().unwrap(), "broken\nrel"))
+            // The same relation is only sent once.
+            .with_link(NodeLink::new(oem_schema.parse().unwrap(), "describedby"))
+            .with_link(NodeLink::new(
+                "https://redfish.dmtf.org/schemas/v1/ComputerSystem.v1_20_0.json"
+                    .parse()
+                    .unwrap(),
+                "describedby",
+            )),
+        );
+        let mut app = redfish_axum::app(tree);
+        let expected = format!(
+            "<https://redfish.dmtf.org/schemas/v1/ComputerSystem.v1_20_0.json>; rel=describedby, <{}>; rel=describedby, <{}>; rel=\"https://contoso.com/rels/action-info\"",
+            oem_schema, action_info
+        );
+
+        let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_header(&response, "Link"), expected);
+
+        let mut req = Request::head(uri);
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(get_header(&response, "Link"), expected);
+    }
+
+    #[tokio::test]
+    async fn created_headers() {
+        let mut app = app();
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let uri = "/redfish/v1/SessionService/Sessions";
+        let created = post(&mut app, uri, data, &Auth::None).await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let location = get_header(&created, "location").to_string();
+        let auth = Auth::Token(get_header(&created, "x-auth-token").to_string());
+
+        // The new session is described by the same headers it is read with later, each
+        // sent once.
+        let read = get(&mut app, &location, &auth).await;
+        for name in ["allow", "link", "odata-version", "cache-control", "etag"] {
+            let values: Vec<_> = created.headers().get_all(name).iter().collect();
+            assert_eq!(values.len(), 1, "{}", name);
+            assert_eq!(Some(values[0]), read.headers().get(name), "{}", name);
+        }
+    }
+
     #[tokio::test]
     async fn get_session_service() {
         let mut app = app();
@@ -579,13 +1314,11 @@ mod tests {
             &[
                 ("allow", "GET,HEAD,PATCH"),
                 ("link", "<https://redfish.dmtf.org/schemas/v1/SessionService.v1_1_8.json>; rel=describedby"),
-                ("etag", "\"HARDCODED_ETAG\""),
             ],
         ).await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService",
                 "@odata.type": "#SessionService.v1_1_8.SessionService",
                 "@Redfish.WriteableProperties": ["SessionTimeout"],
@@ -611,14 +1344,12 @@ mod tests {
                     "link",
                     "<https://redfish.dmtf.org/schemas/v1/SessionCollection.json>; rel=describedby",
                 ),
-                ("etag", "\"HARDCODED_ETAG\""),
             ],
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions",
                 "@odata.type": "#SessionCollection.SessionCollection",
                 "Name": "Session Collection",
@@ -641,9 +1372,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/AccountService/Roles/Administrator",
                 "@odata.type": "#Role.v1_3_1.Role",
                 "Id": "Administrator",
@@ -674,9 +1404,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/AccountService/Roles/Operator",
                 "@odata.type": "#Role.v1_3_1.Role",
                 "Id": "Operator",
@@ -705,9 +1434,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/AccountService/Roles/ReadOnly",
                 "@odata.type": "#Role.v1_3_1.Role",
                 "Id": "ReadOnly",
@@ -784,13 +1512,12 @@ mod tests {
             get_header(&response, "Link"),
             "<https://redfish.dmtf.org/schemas/v1/SessionService.v1_1_8.json>; rel=describedby"
         );
-        assert_eq!(get_header(&response, "etag"), "\"HARDCODED_ETAG\"");
+        assert!(response.headers().contains_key("etag"));
 
         let body = get_response_json(response).await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService",
                 "@odata.type": "#SessionService.v1_1_8.SessionService",
                 "@Redfish.WriteableProperties": ["SessionTimeout"],
@@ -810,9 +1537,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService",
                 "@odata.type": "#SessionService.v1_1_8.SessionService",
                 "@Redfish.WriteableProperties": ["SessionTimeout"],
@@ -842,10 +1568,83 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn empty_patch() {
+    async fn session_context_and_oem() {
+        let sessions = SessionManager::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_manager(sessions.clone())
+            .build();
+        let uri = "/redfish/v1/SessionService/Sessions";
+
+        // Sessions without a Context do not get one.
+        let (token, session_uri) = login(&mut app).await;
+        let body = get_response_json(get(&mut app, &session_uri, &token).await).await;
+        assert!(body.get("Context").is_none());
+
+        let data = json!({"UserName": "Obiwan", "Password": "n/a", "Context": 7});
+        let response = post(&mut app, uri, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let info = &body["error"]["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageId"], "Base.1.16.PropertyValueTypeError");
+        assert_eq!(info["RelatedProperties"], json!(["#/Context"]));
+
+        let data = json!({"UserName": "Obiwan", "Password": "n/a", "Context": "ticket-42"});
+        let response = post(&mut app, uri, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let session_uri = get_header(&response, "Location").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["Context"], "ticket-42");
+        assert!(body.get("Oem").is_none());
+        assert_eq!(
+            sessions.get_context(&session_uri).as_deref(),
+            Some("ticket-42")
+        );
+
+        let oem = json!({"Contoso": {"Console": "serial"}});
+        sessions
+            .set_oem(&session_uri, oem.as_object().unwrap().clone())
+            .unwrap();
+        assert!(sessions.set_oem(&format!("{}/9", uri), Map::new()).is_err());
+        let body = get_response_json(get(&mut app, &session_uri, &token).await).await;
+        assert_eq!(body["Context"], "ticket-42");
+        assert_eq!(body["Oem"], oem);
+        assert_eq!(body["UserName"], "Obiwan");
+    }
+
+    #[tokio::test]
+    async fn patch_property_value_errors() {
         let mut app = app();
         let (token, _) = login(&mut app).await;
-        let data = json!({});
+
+        // Nothing valid to apply
+        let data = json!({"SessionTimeout": "forever"});
+        let response = patch(&mut app, "/redfish/v1/SessionService", data, &token).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.GeneralError");
+        let info = &body["error"]["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageId"], "Base.1.16.PropertyValueTypeError");
+        assert_eq!(info["MessageArgs"], json!(["forever", "SessionTimeout"]));
+        assert_eq!(info["RelatedProperties"], json!(["#/SessionTimeout"]));
+
+        // The valid property is applied, and the invalid one is reported
+        let data = json!({"SessionTimeout": 300, "ServiceEnabled": 1});
+        let response = patch(&mut app, "/redfish/v1/SessionService", data, &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(body["SessionTimeout"], 300);
+        let info = &body["@Message.ExtendedInfo"];
+        assert_eq!(info.as_array().unwrap().len(), 1);
+        assert_eq!(info[0]["MessageId"], "Base.1.16.PropertyValueTypeError");
+        assert_eq!(info[0]["MessageArgs"], json!(["1", "ServiceEnabled"]));
+    }
+
+    #[tokio::test]
+    async fn empty_patch() {
+        let mut app = app();
+        let (token, _) = login(&mut app).await;
+        let data = json!({});
 
         let response = patch(&mut app, "/redfish/v1/SessionService", data, &token).await;
         assert_eq!(response.status(), StatusCode::OK);
@@ -855,13 +1654,12 @@ mod tests {
             get_header(&response, "Link"),
             "<https://redfish.dmtf.org/schemas/v1/SessionService.v1_1_8.json>; rel=describedby"
         );
-        assert_eq!(get_header(&response, "etag"), "\"HARDCODED_ETAG\"");
+        assert!(response.headers().contains_key("etag"));
 
         let body = get_response_json(response).await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService",
                 "@odata.type": "#SessionService.v1_1_8.SessionService",
                 "@Redfish.WriteableProperties": ["SessionTimeout"],
@@ -897,7 +1695,7 @@ mod tests {
             get_header(&response, "Link"),
             "<https://redfish.dmtf.org/schemas/v1/Session.v1_6_0.json>; rel=describedby"
         );
-        assert_eq!(get_header(&response, "etag"), "\"HARDCODED_ETAG\"");
+        assert!(response.headers().contains_key("etag"));
         let token1 = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
 
         // Create session 2
@@ -920,14 +1718,13 @@ mod tests {
             get_header(&response, "Link"),
             "<https://redfish.dmtf.org/schemas/v1/Session.v1_6_0.json>; rel=describedby"
         );
-        assert_eq!(get_header(&response, "etag"), "\"HARDCODED_ETAG\"");
+        assert!(response.headers().contains_key("etag"));
         let token2 = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
 
         let body = get_response_json(response).await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions/2",
                 "@odata.type": "#Session.v1_6_0.Session",
                 "Id": "2",
@@ -947,9 +1744,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions/1",
                 "@odata.type": "#Session.v1_6_0.Session",
                 "Id": "1",
@@ -968,9 +1764,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions/2",
                 "@odata.type": "#Session.v1_6_0.Session",
                 "Id": "2",
@@ -989,9 +1784,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions",
                 "@odata.type": "#SessionCollection.SessionCollection",
                 "Name": "Session Collection",
@@ -1026,9 +1820,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions",
                 "@odata.type": "#SessionCollection.SessionCollection",
                 "Name": "Session Collection",
@@ -1051,9 +1844,8 @@ mod tests {
         )
         .await;
         assert_eq!(
-            body,
+            without_etag(body),
             json!({
-                "@odata.etag": "\"HARDCODED_ETAG\"",
                 "@odata.id": "/redfish/v1/SessionService/Sessions/2",
                 "@odata.type": "#Session.v1_6_0.Session",
                 "Id": "2",
@@ -1109,6 +1901,94 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn post_missing_properties() {
+        let mut app = app();
+        let uri = "/redfish/v1/SessionService/Sessions";
+        let response = post(&mut app, uri, json!({"UserName": "Obiwan"}), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let info = body["error"]["@Message.ExtendedInfo"].as_array().unwrap();
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0]["MessageId"], "Base.1.16.PropertyMissing");
+        assert_eq!(info[0]["MessageArgs"], json!(["Password"]));
+        assert_eq!(info[0]["RelatedProperties"], json!(["#/Password"]));
+
+        let response = post(&mut app, uri, json!({}), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let info = body["error"]["@Message.ExtendedInfo"].as_array().unwrap();
+        assert_eq!(info.len(), 2);
+
+        // No session was made
+        let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+        let body = get_response_json(response).await;
+        assert_eq!(body["Members@odata.count"], 0);
+    }
+
+    #[tokio::test]
+    async fn post_with_id() {
+        let mut app = app();
+        let uri = "/redfish/v1/SessionService/Sessions";
+        let data = json!({"UserName": "Obiwan", "Password": "n/a", "Id": "obiwan"});
+        let response = post(&mut app, uri, data.clone(), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            get_header(&response, "Location"),
+            "/redfish/v1/SessionService/Sessions/obiwan"
+        );
+
+        // Numbering carries on around Ids that clients picked
+        let (token, session_uri) = login(&mut app).await;
+        assert_eq!(session_uri, "/redfish/v1/SessionService/Sessions/1");
+
+        // An Id that is taken, from a client that can see it is
+        let response = post(&mut app, uri, data.clone(), &token).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.ResourceAlreadyExists");
+        let info = &body["error"]["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageArgs"], json!(["Session", "Id", "obiwan"]));
+        assert_eq!(info["RelatedProperties"], json!(["#/Id"]));
+
+        // And from one that cannot
+        let response = post(&mut app, uri, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        for id in [json!(7), json!(""), json!("a/b")] {
+            let data = json!({"UserName": "Obiwan", "Password": "n/a", "Id": id});
+            let response = post(&mut app, uri, data, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_members() {
+        let mut app = app();
+        let (token, _) = login(&mut app).await;
+        let collection = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts",
+            StatusCode::OK,
+            &token,
+            &[],
+        )
+        .await;
+        let members = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts/Members",
+            StatusCode::OK,
+            &token,
+            &[],
+        )
+        .await;
+        assert_eq!(members, collection);
+
+        // Only collections have Members
+        let response = get(&mut app, "/redfish/v1/AccountService/Members", &token).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn post_not_found() {
         let mut app = app();
@@ -1119,6 +1999,77 @@ mod tests {
         assert_eq!(body, "");
     }
 
+    #[tokio::test]
+    async fn delete_collection() {
+        let mut service = AppBuilder::new(get_mock_tree())
+            .collection_delete(true)
+            .build();
+        let auth = admin_admin_basic_auth();
+        let (token, _) = login(&mut service).await;
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            data,
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = delete(&mut service, "/redfish/v1/SessionService/Sessions", &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        let info = body["@Message.ExtendedInfo"].as_array().unwrap();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0]["MessageId"], "ResourceEvent.1.1.ResourceRemoved");
+        assert_eq!(
+            info[0]["MessageArgs"],
+            json!(["/redfish/v1/SessionService/Sessions/1"])
+        );
+        assert_eq!(
+            info[1]["MessageArgs"],
+            json!(["/redfish/v1/SessionService/Sessions/2"])
+        );
+        let body = jget(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members@odata.count"], 0);
+        let response = get(&mut service, "/redfish/v1", &token).await;
+        validate_unauthorized(&response);
+
+        // Members that cannot be deleted are kept
+        let response = delete(&mut service, "/redfish/v1/AccountService/Accounts", &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        let info = &body["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageId"], "Base.1.16.ResourceCannotBeDeleted");
+        assert_eq!(info["RelatedProperties"], json!(["#/Members/0"]));
+        let body = jget(
+            &mut service,
+            "/redfish/v1/AccountService/Accounts",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members@odata.count"], 1);
+
+        // Resources are deleted as they would be otherwise
+        let (_, session_uri) = login(&mut service).await;
+        let response = delete(&mut service, &session_uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Collections cannot be deleted by default
+        let mut app = app();
+        let response = delete(&mut app, "/redfish/v1/SessionService/Sessions", &auth).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
     #[tokio::test]
     async fn delete_not_found() {
         let mut app = app();
@@ -1171,6 +2122,65 @@ mod tests {
             .unwrap();
         let response = app.ready().await.unwrap().call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.HeaderInvalid");
+        let info = &body["error"]["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageArgs"], json!(["OData-Version: 4.1"]));
+        assert_eq!(
+            info["Resolution"],
+            "Resubmit the request with an OData-Version of 4.0, or without one."
+        );
+
+        // Newer versions can be accepted too.
+        let mut newer = AppBuilder::new(get_mock_tree())
+            .odata_versions(vec![String::from("4.0"), String::from("4.1")])
+            .build();
+        for version in ["4.0", "4.1"] {
+            let request = Request::get("/redfish/v1")
+                .header("OData-Version", version)
+                .body(Body::empty())
+                .unwrap();
+            let response = newer.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let request = Request::get("/redfish/v1")
+            .header("OData-Version", "5.0")
+            .body(Body::empty())
+            .unwrap();
+        let response = newer.ready().await.unwrap().call(request).await.unwrap();
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["Resolution"],
+            "Resubmit the request with an OData-Version of 4.0 or 4.1, or without one."
+        );
+    }
+
+    #[tokio::test]
+    async fn odata_version_before_changes() {
+        let mut service = AppBuilder::new(get_mock_tree())
+            .odata_versions(vec![String::from("4.1")])
+            .build();
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/SessionService/Sessions";
+
+        // Only the versions configured are accepted, so 4.0 is refused, and no session is
+        // created for the refused request.
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let request = Request::post(uri)
+            .header("Content-Type", "application/json")
+            .header("OData-Version", "4.0")
+            .body(Body::from(data.to_string()))
+            .unwrap();
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert!(response.headers().get("x-auth-token").is_none());
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["Resolution"],
+            "Resubmit the request with an OData-Version of 4.1, or without one."
+        );
+        let body = jget(&mut service, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["Members@odata.count"], 0);
     }
 
     #[tokio::test]
@@ -1183,12 +2193,13 @@ mod tests {
             .unwrap();
         let response = app.ready().await.unwrap().call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
+        let etag = get_header(&response, "etag").to_string();
         let body = get_response_json(response).await;
-        assert_eq!(&body["@odata.etag"], "\"HARDCODED_ETAG\"");
+        assert_eq!(body["@odata.etag"], etag.as_str());
 
         // use if-none-match with matching etag. should get NOT_MODIFIED.
         let request = Request::get("/redfish/v1")
-            .header("if-none-match", "\"HARDCODED_ETAG\"")
+            .header("if-none-match", etag.as_str())
             .body(Body::empty())
             .unwrap();
         let response = app.ready().await.unwrap().call(request).await.unwrap();
@@ -1211,6 +2222,72 @@ mod tests {
         assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
     }
 
+    #[tokio::test]
+    async fn patch_content_type() {
+        let mut app = app();
+        let data = json!({"SessionTimeout": 300});
+        for (content_type, status) in [
+            ("application/json", StatusCode::OK),
+            ("application/json; charset=utf-8", StatusCode::OK),
+            ("Application/JSON;charset=UTF-8", StatusCode::OK),
+            (
+                "application/json; charset=iso-8859-1",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            ("text/plain", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ("json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ] {
+            let body = Body::from(serde_json::to_vec(&data).unwrap());
+            let mut request =
+                Request::patch("/redfish/v1/SessionService").header("Content-Type", content_type);
+            add_auth_headers(&mut request, &admin_admin_basic_auth());
+            let request = request.body(body).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status, "{}", content_type);
+            if status == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                assert_eq!(get_header(&response, "OData-Version"), "4.0");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn patch_body_encoding() {
+        let mut app = app();
+        let json = r#"{"SessionTimeout": 300}"#;
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(json.encode_utf16().flat_map(|unit| unit.to_le_bytes()))
+            .collect();
+        for (body, status) in [
+            (
+                [&b"\xEF\xBB\xBF"[..], json.as_bytes()].concat(),
+                StatusCode::OK,
+            ),
+            (utf16, StatusCode::BAD_REQUEST),
+            (
+                b"{\"SessionTimeout\": \"\xE9\"}".to_vec(),
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let mut request = Request::patch("/redfish/v1/SessionService")
+                .header("Content-Type", "application/json");
+            add_auth_headers(&mut request, &admin_admin_basic_auth());
+            let request = request.body(Body::from(body)).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status);
+            let body = get_response_json(response).await;
+            if status == StatusCode::BAD_REQUEST {
+                assert_eq!(body["error"]["code"], "Base.1.16.MalformedJSON");
+                assert_eq!(
+                    body["error"]["@Message.ExtendedInfo"][0]["Resolution"],
+                    "Encode the request body in UTF-8 and resubmit the request."
+                );
+            } else {
+                assert_eq!(body["SessionTimeout"], 300);
+            }
+        }
+    }
+
     #[tokio::test]
     async fn post_bad_odata_version() {
         let mut app = app();
@@ -1235,4 +2312,6581 @@ mod tests {
         let response = app.ready().await.unwrap().call(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
     }
+
+    #[tokio::test]
+    async fn session_lifecycle_events() {
+        let audit = AuditRecorder::default();
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service)
+            .audit_hook(audit.clone())
+            .build();
+
+        // Create a session from a known client address
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let mut req = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&data).unwrap()))
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 168, 0, 7], 50000))));
+        let response = app.ready().await.unwrap().call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "SessionEvent.1.0.SessionCreated"
+        );
+        assert_eq!(
+            event["Events"][0]["Message"],
+            "A session for user Obiwan was created from 192.168.0.7."
+        );
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"],
+            json!({"@odata.id": "/redfish/v1/SessionService/Sessions/1"})
+        );
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceCreated"
+        );
+
+        // Terminate it, from an unknown address
+        let response = delete(&mut app, "/redfish/v1/SessionService/Sessions/1", &token).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "SessionEvent.1.0.SessionTerminated"
+        );
+        assert_eq!(
+            event["Events"][0]["MessageArgs"],
+            json!(["Obiwan", "an unknown address"])
+        );
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceRemoved"
+        );
+        assert!(events.try_recv().is_err());
+
+        let entries = audit.entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, AuditAction::SessionCreated);
+        assert_eq!(entries[0].username, "Obiwan");
+        assert_eq!(entries[0].uri, "/redfish/v1/SessionService/Sessions/1");
+        assert_eq!(entries[0].source, Some([192, 168, 0, 7].into()));
+        assert_eq!(entries[1].action, AuditAction::SessionTerminated);
+        assert_eq!(entries[1].source, None);
+    }
+
+    #[tokio::test]
+    async fn request_id() {
+        let audit = AuditRecorder::default();
+        let mut service = AppBuilder::new(get_mock_tree())
+            .audit_hook(audit.clone())
+            .request_id_header(true)
+            .build();
+
+        // An ID sent by the client is kept, and recorded with what the request does
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let request = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/json")
+            .header("X-Request-Id", "client-42")
+            .body(Body::from(serde_json::to_vec(&data).unwrap()))
+            .unwrap();
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(get_header(&response, "X-Request-Id"), "client-42");
+        let entries = audit.entries.lock().unwrap().clone();
+        assert_eq!(entries[0].request_id.as_deref(), Some("client-42"));
+
+        // Other requests are given a new one each, errors included
+        let response = get(&mut service, "/redfish/v1/notfound", &Auth::None).await;
+        validate_unauthorized(&response);
+        let first = get_header(&response, "X-Request-Id").to_string();
+        assert_eq!(first.len(), 32);
+        let response = get(&mut service, "/redfish/v1", &Auth::None).await;
+        assert_ne!(get_header(&response, "X-Request-Id"), first);
+
+        // The header is left out by default
+        let response = get(&mut app(), "/redfish/v1", &Auth::None).await;
+        assert!(response.headers().get("X-Request-Id").is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_account_terminates_sessions() {
+        let mut tree = get_mock_tree();
+        let account = "/redfish/v1/AccountService/Accounts/obiwan";
+        tree.add_resource(Resource::new(
+            account,
+            String::from("ManagerAccount"),
+            ResourceSchemaVersion::new(1, 10, 0),
+            String::from("ManagerAccount"),
+            String::from("Obiwan's Account"),
+            Some(|_| Ok(())),
+            None,
+            Some(String::from("/redfish/v1/AccountService/Accounts")),
+            json!({"RoleId": "Operator", "UserName": "Obiwan"}),
+        ));
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(tree)
+            .event_service(event_service.clone())
+            .build();
+
+        let (first, first_uri) = login(&mut app).await;
+        let response = login_as(&mut app, "Obiwan").await;
+        let second = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let second_uri = get_header(&response, "Location").to_string();
+        let response = login_as(&mut app, "Leia").await;
+        let other = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let other_uri = get_header(&response, "Location").to_string();
+
+        let mut events = event_service.subscribe();
+        let response = delete(&mut app, account, &other).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        for token in [&first, &second] {
+            let response = get(&mut app, "/redfish/v1/SessionService", token).await;
+            validate_unauthorized(&response);
+        }
+        let response = get(&mut app, &first_uri, &other).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = get(&mut app, &other_uri, &other).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut removed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            let event = event.to_json();
+            let record = &event["Events"][0];
+            if record["MessageId"] == "ResourceEvent.1.1.ResourceRemoved" {
+                removed.push(record["MessageArgs"][0].as_str().unwrap().to_string());
+            }
+        }
+        assert_eq!(removed, vec![first_uri, second_uri, String::from(account)]);
+    }
+
+    #[tokio::test]
+    async fn audit_log() {
+        let uri = "/redfish/v1/Managers/BMC/LogServices/AuditLog";
+        let audit_log = AuditLog::new(uri, 3);
+        let mut tree = get_mock_tree();
+        tree.add_audit_log(audit_log.clone());
+        let mut app = AppBuilder::new(tree).audit_hook(audit_log).build();
+
+        let (token, session_uri) = login(&mut app).await;
+        login_as(&mut app, "Leia").await;
+        let response = delete(&mut app, &session_uri, &token).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let auth = admin_admin_basic_auth();
+
+        let body = get_response_json(get(&mut app, uri, &auth).await).await;
+        assert_eq!(body["@odata.type"], "#LogService.v1_5_0.LogService");
+        assert_eq!(body["Entries"]["@odata.id"], format!("{}/Entries", uri));
+        assert_eq!(body["MaxNumberOfRecords"], 3);
+
+        let entries_uri = format!("{}/Entries", uri);
+        let body = get_response_json(get(&mut app, &entries_uri, &auth).await).await;
+        assert_eq!(body["Members@odata.count"], 3);
+        let body =
+            get_response_json(get(&mut app, &format!("{}/3", entries_uri), &auth).await).await;
+        assert_eq!(body["@odata.type"], "#LogEntry.v1_15_0.LogEntry");
+        assert_eq!(body["MessageId"], "SessionEvent.1.0.SessionTerminated");
+        assert_eq!(body["Originator"], "Obiwan");
+        assert_eq!(body["Links"]["OriginOfCondition"]["@odata.id"], session_uri);
+
+        // Filter by user.
+        let filtered = format!("{}?$filter=Originator%20eq%20'Leia'", entries_uri);
+        let body = get_response_json(get(&mut app, &filtered, &auth).await).await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": format!("{}/2", entries_uri)}])
+        );
+
+        // Filter by time range.
+        let hour = Duration::from_secs(3600);
+        let since = redfish_data::get_datetime_string(SystemTime::now() - hour);
+        let until = redfish_data::get_datetime_string(SystemTime::now() + hour);
+        let filtered = format!(
+            "{}?$filter=Created%20ge%20'{}'%20and%20Created%20le%20'{}'",
+            entries_uri, since, until
+        )
+        .replace('+', "%2B");
+        let body = get_response_json(get(&mut app, &filtered, &auth).await).await;
+        assert_eq!(body["Members@odata.count"], 3);
+        let filtered =
+            format!("{}?$filter=Created%20ge%20'{}'", entries_uri, until).replace('+', "%2B");
+        let body = get_response_json(get(&mut app, &filtered, &auth).await).await;
+        assert_eq!(body["Members@odata.count"], 0);
+
+        // Once full, the oldest entries are overwritten.
+        login_as(&mut app, "Leia").await;
+        let body = get_response_json(get(&mut app, &entries_uri, &auth).await).await;
+        assert_eq!(
+            body["Members"][0]["@odata.id"],
+            format!("{}/2", entries_uri)
+        );
+        let response = get(&mut app, &format!("{}/1", entries_uri), &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn login_as(app: &mut NormalizePath<Router>, username: &str) -> Response {
+        let data = json!({"UserName": username, "Password": "n/a"});
+        post(
+            app,
+            "/redfish/v1/SessionService/Sessions",
+            data,
+            &Auth::None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn post_prefer_minimal() {
+        let mut app = app();
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let request = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/json")
+            .header("Prefer", "respond-async, return=minimal")
+            .body(Body::from(serde_json::to_vec(&data).unwrap()))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            get_header(&response, "Preference-Applied"),
+            "return=minimal"
+        );
+        let uri = get_header(&response, "Location").to_string();
+        let token = get_header(&response, "X-Auth-Token").to_string();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "");
+
+        // The session works, and its resource is at the Location.
+        let response = get(&mut app, &uri, &Auth::Token(token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(body["UserName"], "Obiwan");
+    }
+
+    #[tokio::test]
+    async fn session_limit_reject() {
+        let config = SessionLimitConfig {
+            max_sessions_per_user: 2,
+            policy: SessionLimitPolicy::Reject,
+        };
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_limit(Some(config))
+            .build();
+        for _ in 0..2 {
+            let response = login_as(&mut app, "Obiwan").await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+        let response = login_as(&mut app, "Obiwan").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.SessionLimitExceeded");
+        let body = jget(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions",
+            StatusCode::OK,
+            &admin_admin_basic_auth(),
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members@odata.count"], 2);
+
+        // Other users have their own limit
+        let response = login_as(&mut app, "Yoda").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Logging out makes room for another session
+        let response = delete(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions/1",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = login_as(&mut app, "Obiwan").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn session_limit_evict_oldest() {
+        let config = SessionLimitConfig {
+            max_sessions_per_user: 2,
+            policy: SessionLimitPolicy::EvictOldest,
+        };
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_limit(Some(config))
+            .build();
+        let mut tokens = Vec::new();
+        for _ in 0..3 {
+            let response = login_as(&mut app, "Obiwan").await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            tokens.push(Auth::Token(
+                get_header(&response, "X-Auth-Token").to_string(),
+            ));
+        }
+
+        // The oldest session was terminated to make room for the newest
+        let response = get(&mut app, "/redfish/v1/SessionService", &tokens[0]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions/1",
+            &tokens[2],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        for token in &tokens[1..] {
+            jget(
+                &mut app,
+                "/redfish/v1/SessionService",
+                StatusCode::OK,
+                token,
+                &[],
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn session_token_binding() {
+        let config = SessionTokenConfig {
+            bind_to_client_ip: true,
+            token_ttl: Some(Duration::from_millis(200)),
+        };
+        let clock = ManualClock::new();
+        let mut service = AppBuilder::new(get_mock_tree())
+            .session_tokens(config)
+            .clock(clock.clone())
+            .build();
+        let client = SocketAddr::from(([192, 168, 0, 7], 50000));
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let mut req = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&data).unwrap()))
+            .unwrap();
+        req.extensions_mut().insert(ConnectInfo(client));
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let uri = "/redfish/v1/SessionService/Sessions/1";
+
+        // The token works from the client that logged in, on a new connection too
+        let response = get_from(&mut service, uri, &token, client).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let other_port = SocketAddr::from(([192, 168, 0, 7], 50001));
+        let response = get_from(&mut service, uri, &token, other_port).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Not from any other address, or from an unknown one
+        let other_client = SocketAddr::from(([192, 168, 0, 8], 50000));
+        let response = get_from(&mut service, uri, &token, other_client).await;
+        validate_unauthorized(&response);
+        let response = get(&mut service, uri, &token).await;
+        validate_unauthorized(&response);
+
+        // Nor once it has expired, though the session goes on
+        clock.advance(Duration::from_millis(300));
+        let response = get_from(&mut service, uri, &token, client).await;
+        validate_unauthorized(&response);
+        let response = get(&mut service, uri, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Tokens that are not visible ASCII are refused, not choked on
+        let req = Request::get(uri)
+            .header(
+                "X-Auth-Token",
+                HeaderValue::from_bytes(b"\xfftoken").unwrap(),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        validate_unauthorized(&response);
+    }
+
+    #[tokio::test]
+    async fn basic_auth_throttling() {
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(AdminOnly)
+            .auth_throttle(Some(AuthThrottleConfig {
+                max_failures: 3,
+                initial_delay: Duration::from_millis(20),
+                max_delay: Duration::from_millis(30),
+                block_duration: Duration::from_secs(3600),
+                failure_window: Duration::from_secs(3600),
+            }))
+            .build();
+        let attacker = SocketAddr::from(([10, 0, 0, 1], 40000));
+        let bystander = SocketAddr::from(([10, 0, 0, 2], 40000));
+        // admin:wrong
+        let bad_auth = Auth::Basic(String::from("Basic YWRtaW46d3Jvbmc="));
+        let uri = "/redfish/v1/SessionService";
+
+        // Each failure is delayed longer than the last, up to the max
+        let start = Instant::now();
+        let response = get_from(&mut app, uri, &bad_auth, attacker).await;
+        validate_unauthorized(&response);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let start = Instant::now();
+        let response = get_from(&mut app, uri, &bad_auth, attacker).await;
+        validate_unauthorized(&response);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // Good credentials reset the count of failures
+        let response = get_from(&mut app, uri, &admin_admin_basic_auth(), attacker).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for _ in 0..3 {
+            let response = get_from(&mut app, uri, &bad_auth, attacker).await;
+            validate_unauthorized(&response);
+        }
+
+        // Now blocked, so even good credentials do not work from that address
+        let response = get_from(&mut app, uri, &admin_admin_basic_auth(), attacker).await;
+        validate_unauthorized(&response);
+
+        // But they still do from elsewhere
+        let response = get_from(&mut app, uri, &admin_admin_basic_auth(), bystander).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn log_entry_sse() {
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+
+        let response = get(&mut app, "/redfish/v1/EventService/SSE", &Auth::None).await;
+        validate_unauthorized(&response);
+
+        let response = get(
+            &mut app,
+            "/redfish/v1/EventService/SSE",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_header(&response, "content-type"), "text/event-stream");
+        let mut body = response.into_body();
+
+        event_service.log_entry_added(
+            "/redfish/v1/Managers/BMC/LogServices/Log",
+            "/redfish/v1/Managers/BMC/LogServices/Log/Entries/1",
+        );
+        let chunk = hyper::body::HttpBody::data(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data:"))
+            .unwrap();
+        let event: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "LogEvent.1.0.LogEntryAdded"
+        );
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"],
+            json!({"@odata.id": "/redfish/v1/Managers/BMC/LogServices/Log"})
+        );
+        assert_eq!(
+            event["Events"][0]["LogEntry"],
+            json!({"@odata.id": "/redfish/v1/Managers/BMC/LogServices/Log/Entries/1"})
+        );
+        assert!(chunk.contains(&format!("id:{}", event["Id"].as_str().unwrap())));
+    }
+
+    #[tokio::test]
+    async fn event_service_settings() {
+        let event_service = EventService::new();
+        let mut receiver = event_service.subscribe();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let uri = "/redfish/v1/EventService";
+
+        let response = get(&mut app, uri, &Auth::None).await;
+        validate_unauthorized(&response);
+        let response = patch(&mut app, uri, json!({"ServiceEnabled": false}), &Auth::None).await;
+        validate_unauthorized(&response);
+
+        let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_header(&response, "allow"), "GET,HEAD,PATCH");
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.type"], "#EventService.v1_10_0.EventService");
+        assert_eq!(body["ServiceEnabled"], true);
+        assert_eq!(body["DeliveryRetryAttempts"], 3);
+        assert_eq!(body["ServerSentEventUri"], "/redfish/v1/EventService/SSE");
+        assert_eq!(body["EventFormatTypes"], json!(["Event", "MetricReport"]));
+
+        // Nothing that can be written, so nothing is applied.
+        let req = json!({"ServerSentEventUri": "/sse", "Bogus": 1, "DeliveryRetryAttempts": -1});
+        let response = patch(&mut app, uri, req, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let mut ids: Vec<&str> = body["error"]["@Message.ExtendedInfo"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["MessageId"].as_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![
+                "Base.1.16.PropertyNotWritable",
+                "Base.1.16.PropertyUnknown",
+                "Base.1.16.PropertyValueOutOfRange"
+            ]
+        );
+
+        let req = json!({"ServiceEnabled": false, "DeliveryRetryIntervalSeconds": 30});
+        let response = patch(&mut app, uri, req, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(body["ServiceEnabled"], false);
+        assert_eq!(body["DeliveryRetryIntervalSeconds"], 30);
+        assert_eq!(body["Status"]["State"], "Disabled");
+        assert!(!event_service.get_settings().service_enabled);
+
+        // A disabled service drops events, and refuses new streams.
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        assert!(receiver.try_recv().is_err());
+        let response = get(
+            &mut app,
+            "/redfish/v1/EventService/SSE",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.ServiceDisabled"
+        );
+
+        let req = json!({"ServiceEnabled": true});
+        let response = patch(&mut app, uri, req, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn task_events() {
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        let task = "/redfish/v1/TaskService/Tasks/7";
+
+        event_service.task_started(task);
+        event_service.task_progress_changed(task, 40);
+        event_service.task_completed(task, Health::OK);
+        event_service.task_completed(task, Health::Warning);
+        event_service.task_completed(task, Health::Critical);
+        for (message_id, args, severity) in [
+            ("TaskEvent.1.0.TaskStarted", json!(["7"]), "OK"),
+            (
+                "TaskEvent.1.0.TaskProgressChanged",
+                json!(["7", "40"]),
+                "OK",
+            ),
+            ("TaskEvent.1.0.TaskCompletedOK", json!(["7"]), "OK"),
+            (
+                "TaskEvent.1.0.TaskCompletedWarning",
+                json!(["7"]),
+                "Warning",
+            ),
+            ("TaskEvent.1.0.TaskAborted", json!(["7"]), "Critical"),
+        ] {
+            let event = events.try_recv().unwrap().to_json();
+            assert_eq!(event["Events"][0]["MessageId"], message_id);
+            assert_eq!(event["Events"][0]["MessageArgs"], args);
+            assert_eq!(event["Events"][0]["MessageSeverity"], severity);
+            assert_eq!(
+                event["Events"][0]["OriginOfCondition"],
+                json!({"@odata.id": task})
+            );
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn get_trigger() {
+        let mut app = app();
+        let response = jget(
+            &mut app,
+            "/redfish/v1/TelemetryService/Triggers/CPUTemperature",
+            StatusCode::OK,
+            &admin_admin_basic_auth(),
+            &[("allow", "GET,HEAD")],
+        )
+        .await;
+        assert_eq!(response["@odata.type"], "#Triggers.v1_3_0.Triggers");
+        assert_eq!(response["MetricType"], "Numeric");
+        assert_eq!(
+            response["NumericThresholds"],
+            json!({
+                "UpperCritical": {"Reading": 95.0, "Activation": "Increasing"},
+                "UpperWarning": {"Reading": 85.0, "Activation": "Either"},
+            })
+        );
+        assert_eq!(
+            response["TriggerActions"],
+            json!(["RedfishEvent", "RedfishMetricReport"])
+        );
+        assert_eq!(
+            response["Links"]["MetricReportDefinitions"],
+            json!([{"@odata.id": "/redfish/v1/TelemetryService/MetricReportDefinitions/Thermal"}])
+        );
+    }
+
+    #[test]
+    fn numeric_trigger() {
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        let mut trigger = get_cpu_temperature_trigger();
+
+        assert!(trigger.update_numeric(60.0, &event_service).is_empty());
+        assert!(events.try_recv().is_err());
+
+        // Rising past the warning threshold fires it, and asks for the metric report
+        let reports = trigger.update_numeric(88.0, &event_service).to_vec();
+        assert_eq!(
+            reports,
+            vec!["/redfish/v1/TelemetryService/MetricReportDefinitions/Thermal"]
+        );
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "TriggerEvent.1.0.UpperWarningThresholdCrossed"
+        );
+        assert_eq!(
+            event["Events"][0]["MessageArgs"],
+            json!(["CPUTemperature", "88", "85"])
+        );
+        assert_eq!(event["Events"][0]["MessageSeverity"], "Warning");
+
+        // Staying above it does not fire again
+        assert!(trigger.update_numeric(90.0, &event_service).is_empty());
+        assert!(events.try_recv().is_err());
+
+        // Critical wins when both are crossed at once
+        trigger.update_numeric(99.0, &event_service);
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "TriggerEvent.1.0.UpperCriticalThresholdCrossed"
+        );
+        assert_eq!(event["Events"][0]["MessageSeverity"], "Critical");
+
+        // Falling back below the critical threshold only fires thresholds activated by decreasing
+        trigger.update_numeric(80.0, &event_service);
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "TriggerEvent.1.0.UpperWarningThresholdCrossed"
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn discrete_trigger() {
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        let mut trigger = Trigger::discrete(
+            "/redfish/v1/TelemetryService/Triggers/PowerState",
+            "Power State Trigger",
+            vec![DiscreteTrigger::new("PoweredOff", "Off", Health::Critical)],
+            vec![TriggerAction::RedfishEvent],
+        );
+
+        trigger.update_discrete("On", &event_service);
+        assert!(events.try_recv().is_err());
+        // No metric report is asked for when that is not one of the trigger's actions
+        assert!(trigger.update_discrete("Off", &event_service).is_empty());
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "TriggerEvent.1.0.DiscreteTriggerMatched"
+        );
+        assert_eq!(
+            event["Events"][0]["Message"],
+            "The value Off for trigger PowerState matched the discrete trigger PoweredOff."
+        );
+        assert_eq!(event["Events"][0]["MessageSeverity"], "Critical");
+        trigger.update_discrete("Off", &event_service);
+        assert!(events.try_recv().is_err());
+    }
+
+    fn get_test_profile() -> String {
+        json!({
+            "ProfileName": "Test",
+            "Resources": {
+                "ComputerSystemCollection": {
+                    "URIs": ["/redfish/v1/Systems"],
+                    "CreateResource": true,
+                },
+                "ComputerSystem": {
+                    "MinVersion": "1.6.0",
+                    "URIs": ["/redfish/v1/Systems/{ComputerSystemId}"],
+                    "DeleteResource": true,
+                    "PropertyRequirements": {
+                        "AssetTag": {"WriteRequirement": "Mandatory"},
+                        "SerialNumber": {},
+                    },
+                },
+                "Chassis": {
+                    "ReadRequirement": "IfImplemented",
+                    "URIs": ["/redfish/v1/Chassis/{ChassisId}"],
+                },
+            },
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn scaffold_from_interop_profile() {
+        let scaffold = Scaffold::from_interop_profile(&get_test_profile()).unwrap();
+        let uris: Vec<&str> = scaffold
+            .nodes
+            .iter()
+            .map(|node| node.uri.as_str())
+            .collect();
+        assert_eq!(uris, vec!["/redfish/v1/Systems/1", "/redfish/v1/Systems"]);
+        assert_eq!(
+            scaffold.nodes[0].allowed_methods,
+            AllowedMethods {
+                delete: true,
+                get: true,
+                patch: true,
+                post: false
+            }
+        );
+        assert!(scaffold.to_rust().contains(
+            "    .collection(
+        \"/redfish/v1/Systems\",
+        \"ComputerSystemCollection\",
+        CollectionType::new_dmtf_v1(String::from(\"ComputerSystemCollection\")),
+        vec![String::from(\"/redfish/v1/Systems/1\")],
+        Some(ResourceType::new_dmtf(String::from(\"ComputerSystem\"), ResourceSchemaVersion::new(1, 6, 0))),
+    )
+"
+        ));
+
+        // The tree it describes works as is
+        let tree = scaffold.apply(TreeBuilder::new()).build();
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD,POST")],
+        )
+        .await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/Systems/1"}])
+        );
+        let response = post(
+            &mut app,
+            "/redfish/v1/Systems",
+            json!({"AssetTag": "new"}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(get_header(&response, "location"), "/redfish/v1/Systems/2");
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems/2",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD,DELETE,PATCH")],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#ComputerSystem.v1_6_0.ComputerSystem");
+        assert_eq!(body["AssetTag"], "new");
+        let data = json!({"AssetTag": "patched"});
+        let response = patch(&mut app, "/redfish/v1/Systems/1", data, &auth).await;
+        assert_eq!(get_response_json(response).await["AssetTag"], "patched");
+        let response = delete(&mut app, "/redfish/v1/Systems/1", &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let body = jget(&mut app, "/redfish/v1/Systems", StatusCode::OK, &auth, &[]).await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/Systems/2"}])
+        );
+    }
+
+    #[test]
+    fn scaffold_from_openapi() {
+        let openapi = json!({
+            "openapi": "3.0.1",
+            "paths": {
+                "/redfish/v1/AccountService/Accounts": {
+                    "get": {"responses": {"200": {"content": {"application/json": {"schema": {
+                        "$ref": "http://redfish.dmtf.org/schemas/v1/ManagerAccountCollection.yaml#/components/schemas/ManagerAccountCollection_ManagerAccountCollection"
+                    }}}}}},
+                    "post": {},
+                },
+                "/redfish/v1/AccountService/Accounts/{ManagerAccountId}": {
+                    "get": {"responses": {"200": {"content": {"application/json": {"schema": {
+                        "$ref": "http://redfish.dmtf.org/schemas/v1/ManagerAccount.v1_10_0.yaml#/components/schemas/ManagerAccount_v1_10_0_ManagerAccount"
+                    }}}}}},
+                    "patch": {},
+                    "delete": {},
+                },
+                "/redfish/v1/Systems/{ComputerSystemId}/Actions/ComputerSystem.Reset": {
+                    "post": {},
+                },
+            },
+        })
+        .to_string();
+        let scaffold = Scaffold::from_openapi(&openapi).unwrap();
+        assert_eq!(scaffold.nodes.len(), 2);
+        assert_eq!(scaffold.nodes[0].uri, "/redfish/v1/AccountService/Accounts");
+        assert_eq!(scaffold.nodes[0].version, None);
+        assert!(scaffold.nodes[0].allowed_methods.post);
+        assert_eq!(
+            scaffold.nodes[1].uri,
+            "/redfish/v1/AccountService/Accounts/1"
+        );
+        assert_eq!(scaffold.nodes[1].schema, "ManagerAccount");
+        assert_eq!(
+            scaffold.nodes[1].version,
+            Some(ResourceSchemaVersion::new(1, 10, 0))
+        );
+        assert!(Scaffold::from_openapi("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn virtual_collection() {
+        let session_type =
+            ResourceType::new_dmtf(String::from("Session"), ResourceSchemaVersion::new(1, 6, 0));
+        let allowed_methods = AllowedMethods {
+            delete: true,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let mut builder = TreeBuilder::new();
+        for uri in [
+            "/redfish/v1/SessionService/Sessions/2",
+            "/redfish/v1/SessionService/Sessions/1",
+        ] {
+            builder = builder.resource(
+                uri,
+                "User Session",
+                session_type.clone(),
+                allowed_methods,
+                json!({}),
+            );
+        }
+        let tree = builder
+            .resource(
+                "/redfish/v1/SessionService",
+                "Session Service",
+                ResourceType::new_dmtf(
+                    String::from("SessionService"),
+                    ResourceSchemaVersion::new(1, 1, 8),
+                ),
+                allowed_methods,
+                json!({}),
+            )
+            .virtual_collection(
+                "/redfish/v1/SessionService/Sessions",
+                "Session Collection",
+                CollectionType::new_dmtf_v1(String::from("SessionCollection")),
+                |node| {
+                    node.get_resource_type()
+                        .is_some_and(|t| t.name == "Session")
+                },
+            )
+            .build();
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD")],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#SessionCollection.SessionCollection");
+        assert_eq!(
+            body["Members"],
+            json!([
+                {"@odata.id": "/redfish/v1/SessionService/Sessions/1"},
+                {"@odata.id": "/redfish/v1/SessionService/Sessions/2"},
+            ])
+        );
+
+        // Members follow the resources of the tree
+        let response = delete(&mut app, "/redfish/v1/SessionService/Sessions/1", &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let body = jget(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/SessionService/Sessions/2"}])
+        );
+        assert_eq!(body["Members@odata.count"], 1);
+
+        // Virtual collections are read-only
+        let response = post(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions",
+            json!({}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let response = delete(&mut app, "/redfish/v1/SessionService/Sessions", &auth).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    fn get_systems_tree() -> StaticTree {
+        let system_type = ResourceType::new_dmtf(
+            String::from("ComputerSystem"),
+            ResourceSchemaVersion::new(1, 20, 0),
+        );
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let mut builder = TreeBuilder::new();
+        let mut members = Vec::new();
+        for (id, power_state, health) in [
+            ("1", "On", "OK"),
+            ("2", "Off", "OK"),
+            ("3", "On", "Critical"),
+        ] {
+            let uri = format!("/redfish/v1/Systems/{}", id);
+            let body = json!({
+                "PowerState": power_state,
+                "Status": {"Health": health},
+                "Links": {"Chassis": [{"@odata.id": "/redfish/v1/Chassis/1"}]},
+            });
+            builder = builder.resource(&uri, "System", system_type.clone(), allowed_methods, body);
+            members.push(uri);
+        }
+        builder
+            .resource(
+                "/redfish/v1/Chassis/1",
+                "Chassis",
+                ResourceType::new_dmtf(
+                    String::from("Chassis"),
+                    ResourceSchemaVersion::new(1, 22, 0),
+                ),
+                allowed_methods,
+                json!({}),
+            )
+            .collection(
+                "/redfish/v1/Systems",
+                "Computer System Collection",
+                CollectionType::new_dmtf_v1(String::from("ComputerSystemCollection")),
+                members,
+                None,
+            )
+            .build()
+    }
+
+    fn get_member_ids(body: &Value) -> Vec<&str> {
+        body["Members"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| {
+                member["@odata.id"]
+                    .as_str()
+                    .unwrap()
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn query_parameters() {
+        let mut app = redfish_axum::app(get_systems_tree());
+        let auth = admin_admin_basic_auth();
+
+        let uri = "/redfish/v1/Systems?$filter=PowerState%20eq%20'On'";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["1", "3"]);
+        assert_eq!(body["Members@odata.count"], 2);
+        let uri = "/redfish/v1/Systems?$filter=not%20(PowerState%20eq%20'On'%20and%20Status/Health%20eq%20'OK')";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["2", "3"]);
+
+        let uri = "/redfish/v1/Systems?$skip=1&$top=1";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["2"]);
+        assert_eq!(body["Members@odata.count"], 3);
+
+        // Members are subordinate, so they are expanded by . but not by ~
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems?$expand=.",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members"][0]["PowerState"], "On");
+        assert_eq!(
+            body["Members"][0]["Links"]["Chassis"][0],
+            json!({"@odata.id": "/redfish/v1/Chassis/1"})
+        );
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems?$expand=~",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["Members"][0],
+            json!({"@odata.id": "/redfish/v1/Systems/1"})
+        );
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems?$expand=*($levels=2)",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["Members"][0]["Links"]["Chassis"][0]["@odata.type"],
+            "#Chassis.v1_22_0.Chassis"
+        );
+
+        for uri in [
+            "/redfish/v1/Systems?$top=many",
+            "/redfish/v1/Systems?$filter=PowerState%20eq",
+            "/redfish/v1/Systems?$expand=all",
+        ] {
+            let response = get(&mut app, uri, &auth).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn next_link() {
+        let mut app = redfish_axum::app(get_systems_tree());
+        let auth = admin_admin_basic_auth();
+
+        // Following the links visits every member once
+        let mut uri = String::from("/redfish/v1/Systems?$top=2");
+        let mut ids = Vec::new();
+        loop {
+            let body = jget(&mut app, &uri, StatusCode::OK, &auth, &[]).await;
+            ids.extend(get_member_ids(&body).into_iter().map(String::from));
+            match body["Members@odata.nextLink"].as_str() {
+                Some(next) => uri = String::from(next),
+                None => break,
+            }
+        }
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert_eq!(uri, "/redfish/v1/Systems?$top=2&$skip=2");
+
+        // Links keep the other parameters, encoded
+        let uri = "/redfish/v1/Systems?$filter=Status/Health%20eq%20'OK'&$top=1";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["1"]);
+        let next = body["Members@odata.nextLink"].as_str().unwrap().to_string();
+        assert_eq!(
+            next,
+            "/redfish/v1/Systems?$filter=Status/Health%20eq%20'OK'&$top=1&$skip=1"
+        );
+        let body = jget(&mut app, &next, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["2"]);
+        assert!(body.get("Members@odata.nextLink").is_none());
+
+        // Pages that reach the end have none
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems?$top=3",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert!(body.get("Members@odata.nextLink").is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_token() {
+        let mut app = AppBuilder::new(get_systems_tree())
+            .page_size(Some(2))
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        let body = jget(&mut app, "/redfish/v1/Systems", StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["1", "2"]);
+        assert_eq!(body["Members@odata.count"], 3);
+        let next = body["Members@odata.nextLink"].as_str().unwrap().to_string();
+        let token = next
+            .strip_prefix("/redfish/v1/Systems?$skiptoken=")
+            .unwrap();
+        assert_eq!(token.len(), 32);
+        let body = jget(&mut app, &next, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["3"]);
+        assert!(body.get("Members@odata.nextLink").is_none());
+
+        // Tokens are opaque, so the service only takes those it gave, for the members it gave
+        // them for
+        let mut tampered = String::from(token);
+        tampered.replace_range(15..16, "1");
+        let stale = "/redfish/v1/Systems?$filter=PowerState%20ne%20'Off'&$skiptoken=";
+        for uri in [
+            String::from("/redfish/v1/Systems?$skiptoken=2"),
+            format!("/redfish/v1/Systems?$skiptoken={}", tampered),
+            format!("{}{}", stale, token),
+        ] {
+            let response = get(&mut app, &uri, &auth).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+
+        // Pages the client asks for are split too, then carry on with the next one it asked for
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Systems?$top=3&$filter=PowerState%20ne%20'Off'",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(get_member_ids(&body), vec!["1", "3"]);
+        assert!(body.get("Members@odata.nextLink").is_none());
+        let mut uri = String::from("/redfish/v1/Systems?$skip=0&$top=3");
+        let mut links = Vec::new();
+        loop {
+            let body = jget(&mut app, &uri, StatusCode::OK, &auth, &[]).await;
+            match body["Members@odata.nextLink"].as_str() {
+                Some(next) => uri = String::from(next),
+                None => break,
+            }
+            links.push(uri.clone());
+        }
+        // The links give the parameters in their canonical order
+        assert_eq!(links.len(), 1);
+        assert!(links[0].starts_with("/redfish/v1/Systems?$top=3&$skip=0&$skiptoken="));
+
+        let response = get(&mut app, "/redfish/v1/Systems?$skiptoken=next", &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn select_properties() {
+        let mut app = app();
+        let auth = admin_admin_basic_auth();
+        let uri =
+            "/redfish/v1/AccountService/Accounts/admin?$select=UserName,Oem/Contoso/ConsoleAccess";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(
+            without_etag(body),
+            json!({
+                "@odata.id": "/redfish/v1/AccountService/Accounts/admin",
+                "@odata.type": "#ManagerAccount.v1_10_0.ManagerAccount",
+                "UserName": "admin",
+                "Oem": {
+                    "Contoso": {
+                        "@odata.type": "#ContosoManagerAccount.v1_0_0.ContosoManagerAccount",
+                        "ConsoleAccess": true,
+                    }
+                },
+            })
+        );
+
+        // Annotations of selected properties are kept with them
+        let uri = "/redfish/v1/AccountService/Accounts?$select=Members";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["Members@odata.count"], 1);
+        assert!(body.get("Name").is_none());
+
+        for uri in [
+            "/redfish/v1/AccountService/Accounts/admin?$select=",
+            "/redfish/v1/AccountService/Accounts/admin?$select=UserName,",
+            "/redfish/v1/AccountService/Accounts/admin?$select=Oem//Contoso",
+        ] {
+            let response = get(&mut app, uri, &auth).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    // Handles $top and $skip itself, recording the query parameters it is given.
+    struct NativePagingTree {
+        tree: StaticTree,
+        queries: Arc<Mutex<Vec<Query>>>,
+    }
+
+    #[async_trait]
+    impl Tree for NativePagingTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        fn get_native_query_support(&self) -> NativeQuerySupport {
+            NativeQuerySupport {
+                top_skip: true,
+                ..Default::default()
+            }
+        }
+
+        async fn get_with_query(
+            &self,
+            uri: &str,
+            username: Option<&str>,
+            query: &Query,
+        ) -> Result<NodeRef<'_>, Error> {
+            self.queries.lock().unwrap().push(query.clone());
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn native_query_parameters() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let tree = NativePagingTree {
+            tree: get_systems_tree(),
+            queries: queries.clone(),
+        };
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+
+        // Paging is left to the tree, which ignores it here, but filtering is not
+        let uri = "/redfish/v1/Systems?$top=1&$filter=Status/Health%20ne%20'OK'";
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(get_member_ids(&body), vec!["3"]);
+        assert_eq!(queries.lock().unwrap().len(), 1);
+        assert_eq!(queries.lock().unwrap()[0].top, Some(1));
+        assert_eq!(queries.lock().unwrap()[0].filter, None);
+
+        // Requests with nothing to forward use get()
+        jget(
+            &mut app,
+            "/redfish/v1/Systems?$filter=PowerState%20eq%20'On'",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(queries.lock().unwrap().len(), 1);
+    }
+
+    // Gives a fixed list of sessions for the collection, whatever is created or deleted.
+    struct StaleSessionsTree {
+        tree: MockTree,
+        sessions: Collection,
+    }
+
+    #[async_trait]
+    impl Tree for StaleSessionsTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            match uri == self.sessions.get_uri() {
+                true => Ok(NodeRef::from(&self.sessions)),
+                false => self.tree.get(uri, username).await,
+            }
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn check_membership() {
+        let auth = admin_admin_basic_auth();
+        let sessions = |members: Vec<&str>| {
+            Collection::new(
+                "/redfish/v1/SessionService/Sessions",
+                String::from("SessionCollection"),
+                String::from("Session Collection"),
+                members.into_iter().map(String::from).collect(),
+                None,
+            )
+        };
+        let body = json!({"UserName": "Administrator", "Password": "password"});
+
+        // Membership is not checked by default
+        let tree = StaleSessionsTree {
+            tree: get_mock_tree(),
+            sessions: sessions(vec![]),
+        };
+        let mut service = redfish_axum::app(tree);
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            body.clone(),
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // A created session missing from Members
+        let tree = StaleSessionsTree {
+            tree: get_mock_tree(),
+            sessions: sessions(vec![]),
+        };
+        let mut service = AppBuilder::new(tree).check_membership(true).build();
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            body.clone(),
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // A deleted session still in Members
+        let uri = "/redfish/v1/SessionService/Sessions/1";
+        let tree = StaleSessionsTree {
+            tree: get_mock_tree(),
+            sessions: sessions(vec![uri]),
+        };
+        let mut service = AppBuilder::new(tree).check_membership(true).build();
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            body.clone(),
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = delete(&mut service, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // A tree that keeps Members up to date passes
+        let mut service = AppBuilder::new(get_mock_tree())
+            .check_membership(true)
+            .build();
+        let response = post(
+            &mut service,
+            "/redfish/v1/SessionService/Sessions",
+            body.clone(),
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = delete(&mut service, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn dbus_tree() {
+        let bus = FakeBus::default();
+        let path = "/xyz/openbmc_project/inventory/system/chassis/chassis0";
+        for (property, value) in [
+            ("PrettyName", json!("Main Chassis")),
+            ("AssetTag", json!("")),
+        ] {
+            bus.properties
+                .lock()
+                .unwrap()
+                .insert((String::from(path), String::from(property)), value);
+        }
+        let chassis = DBusSubtree::new(
+            "/redfish/v1/Chassis",
+            "Chassis Collection",
+            CollectionType::new_dmtf_v1(String::from("ChassisCollection")),
+            "/xyz/openbmc_project/inventory/system/chassis",
+            ResourceType::new_dmtf(
+                String::from("Chassis"),
+                ResourceSchemaVersion::new(1, 22, 0),
+            ),
+        )
+        .with_property(DBusProperty::new(
+            "Model",
+            "xyz.openbmc_project.Inventory.Item",
+            "PrettyName",
+        ))
+        .with_property(
+            DBusProperty::new(
+                "AssetTag",
+                "xyz.openbmc_project.Inventory.Decorator.AssetTag",
+                "AssetTag",
+            )
+            .writable(),
+        )
+        .with_action(DBusAction::new(
+            "Chassis.Reset",
+            "xyz.openbmc_project.State.Chassis",
+            "Reset",
+            vec![String::from("ResetType")],
+        ));
+        let tree = DBusTree::new(bus.clone(), get_mock_tree()).with_subtree(chassis);
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+
+        // Nodes outside the subtree come from the fallback tree
+        jget(&mut app, "/redfish/v1", StatusCode::OK, &Auth::None, &[]).await;
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD")],
+        )
+        .await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/Chassis/chassis0"}])
+        );
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/chassis0",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD,PATCH")],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#Chassis.v1_22_0.Chassis");
+        assert_eq!(body["Model"], "Main Chassis");
+        assert_eq!(
+            body["Actions"]["#Chassis.Reset"]["target"],
+            "/redfish/v1/Chassis/chassis0/Actions/Chassis.Reset"
+        );
+        let response = get(&mut app, "/redfish/v1/Chassis/chassis1", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // PATCH writes the D-Bus property
+        let data = json!({"AssetTag": "rack-4"});
+        let response = patch(&mut app, "/redfish/v1/Chassis/chassis0", data, &auth).await;
+        assert_eq!(get_response_json(response).await["AssetTag"], "rack-4");
+        let key = (String::from(path), String::from("AssetTag"));
+        assert_eq!(bus.properties.lock().unwrap()[&key], "rack-4");
+
+        // Nothing is written if any property cannot be
+        let data = json!({"AssetTag": "rack-5", "Model": "Other"});
+        let response = patch(&mut app, "/redfish/v1/Chassis/chassis0", data, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyNotWritable"
+        );
+        assert_eq!(bus.properties.lock().unwrap()[&key], "rack-4");
+
+        // Actions call the D-Bus method
+        let data = json!({"ResetType": "PowerCycle"});
+        let response = post(
+            &mut app,
+            "/redfish/v1/Chassis/chassis0/Actions/Chassis.Reset",
+            data,
+            &auth,
+        )
+        .await;
+        assert!(response.status().is_success());
+        assert_eq!(
+            *bus.calls.lock().unwrap(),
+            vec![(
+                String::from(path),
+                String::from("Reset"),
+                vec![json!("PowerCycle")]
+            )]
+        );
+    }
+
+    // A resource, such as a chassis, whose backend has gone away.
+    struct UnreachableNode(&'static str);
+
+    impl Node for UnreachableNode {
+        fn get_uri(&self) -> &str {
+            self.0
+        }
+
+        fn get_body(&self) -> Value {
+            panic!("The service should only call try_get_body()")
+        }
+
+        fn try_get_body(&self) -> Result<Value, Error> {
+            Err(Error::InternalError)
+        }
+
+        fn get_allowed_methods(&self) -> AllowedMethods {
+            AllowedMethods {
+                delete: false,
+                get: true,
+                patch: false,
+                post: false,
+            }
+        }
+
+        fn described_by(&self) -> Option<&redfish_data::SchemaUrl> {
+            None
+        }
+
+        fn get_etag(&self) -> Option<etag::EntityTag> {
+            None
+        }
+    }
+
+    // A chassis collection whose backend has gone away, though its type is still known.
+    struct UnreachableChassis(CollectionType);
+
+    impl Node for UnreachableChassis {
+        fn get_uri(&self) -> &str {
+            "/redfish/v1/Chassis"
+        }
+
+        fn get_body(&self) -> Value {
+            panic!("The service should only call try_get_body()")
+        }
+
+        fn try_get_body(&self) -> Result<Value, Error> {
+            Err(Error::InternalError)
+        }
+
+        fn get_allowed_methods(&self) -> AllowedMethods {
+            AllowedMethods {
+                delete: false,
+                get: true,
+                patch: false,
+                post: false,
+            }
+        }
+
+        fn described_by(&self) -> Option<&redfish_data::SchemaUrl> {
+            Some(&self.0.described_by)
+        }
+
+        fn get_etag(&self) -> Option<etag::EntityTag> {
+            None
+        }
+    }
+
+    struct UnreachableAdapter;
+
+    #[async_trait]
+    impl BackendAdapter for UnreachableAdapter {
+        fn get_root(&self) -> &str {
+            "/redfish/v1/Chassis"
+        }
+
+        async fn materialize(&self, _uri: &str) -> Result<Arc<dyn Node + Send + Sync>, Error> {
+            let collection_type = CollectionType::new_dmtf_v1(String::from("ChassisCollection"));
+            Ok(Arc::new(UnreachableChassis(collection_type)))
+        }
+
+        fn get_collection_types(&self) -> Vec<CollectionType> {
+            Vec::new()
+        }
+
+        fn get_resource_types(&self) -> Vec<ResourceType> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_node_body() {
+        let tree = BackendTree::new(get_mock_tree()).with_adapter(UnreachableAdapter);
+        let mut app = redfish_axum::app(tree);
+        for uri in ["/redfish/v1/Chassis", "/redfish/v1/Chassis?$top=1"] {
+            let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let body = get_response_json(response).await;
+            assert_eq!(
+                body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+                "Base.1.16.InternalError"
+            );
+        }
+
+        // HEAD only needs the metadata of the node, so it does not fail.
+        let mut req = Request::head("/redfish/v1/Chassis");
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_header(&response, "allow"), "GET,HEAD");
+        assert_eq!(get_header(&response, "content-type"), "application/json");
+
+        // Nor when authorizing it by the privilege registry, which takes the entity from the
+        // schema of the node.
+        let tree = BackendTree::new(get_mock_tree()).with_adapter(UnreachableAdapter);
+        let mut app = AppBuilder::new(tree).authentication_provider(Roles).build();
+        // Obiwan:x and Leia:x
+        let obiwan = Auth::Basic(String::from("Basic T2Jpd2FuOng="));
+        let leia = Auth::Basic(String::from("Basic TGVpYTp4"));
+        for auth in [admin_admin_basic_auth(), obiwan] {
+            let mut req = Request::head("/redfish/v1/Chassis");
+            add_auth_headers(&mut req, &auth);
+            let request = req.body(Body::empty()).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = post(&mut app, "/redfish/v1/Chassis", json!({}), &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    // Serves the SessionService and AccountService from a backend that has gone away.
+    struct UnreachableServicesTree {
+        tree: MockTree,
+    }
+
+    #[async_trait]
+    impl Tree for UnreachableServicesTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            match uri {
+                "/redfish/v1/SessionService" => Ok(NodeRef::Shared(Arc::new(UnreachableNode(
+                    "/redfish/v1/SessionService",
+                )))),
+                "/redfish/v1/AccountService" => Ok(NodeRef::Shared(Arc::new(UnreachableNode(
+                    "/redfish/v1/AccountService",
+                )))),
+                _ => self.tree.get(uri, username).await,
+            }
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_service_bodies() {
+        let tree = UnreachableServicesTree {
+            tree: get_mock_tree(),
+        };
+        let mut app = AppBuilder::new(tree)
+            .authentication_provider(AdminOnly)
+            .disabled_sessions(DisabledSessionPolicy::Suspend)
+            .build();
+        let sessions = "/redfish/v1/SessionService/Sessions";
+
+        // A SessionService that cannot be read is taken to be enabled
+        let data = json!({"UserName": "admin", "Password": "admin"});
+        let response = post(&mut app, sessions, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        jget(&mut app, sessions, StatusCode::OK, &token, &[]).await;
+        let response = get(&mut app, "/redfish/v1/SessionService", &token).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // Nor does an AccountService that cannot be read lock accounts
+        // admin:wrong
+        let bad_auth = Auth::Basic(String::from("Basic YWRtaW46d3Jvbmc="));
+        validate_unauthorized(&get(&mut app, sessions, &bad_auth).await);
+        jget(
+            &mut app,
+            sessions,
+            StatusCode::OK,
+            &admin_admin_basic_auth(),
+            &[],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn ipmi_backend_tree() {
+        let sdr = || {
+            String::from(
+                "CPU Temp         | 45 degrees C      | ok\n\
+                 Fan 1            | 3600 RPM          | nc\n\
+                 PSU Status       | no reading        | ns\n",
+            )
+        };
+        let adapter = IpmiSensorAdapter::new(Box::new(sdr));
+        let tree = BackendTree::new(get_mock_tree()).with_adapter(adapter);
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+
+        // Nodes outside the adapter's subtree come from the fallback tree
+        jget(&mut app, "/redfish/v1", StatusCode::OK, &Auth::None, &[]).await;
+
+        let response = get(&mut app, "/redfish/v1/Chassis", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/1",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#Chassis.v1_22_0.Chassis");
+        assert_eq!(
+            body["Sensors"]["@odata.id"],
+            "/redfish/v1/Chassis/1/Sensors"
+        );
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors",
+            StatusCode::OK,
+            &auth,
+            &[("allow", "GET,HEAD")],
+        )
+        .await;
+        assert_eq!(
+            body["Members"],
+            json!([
+                {"@odata.id": "/redfish/v1/Chassis/1/Sensors/CPUTemp"},
+                {"@odata.id": "/redfish/v1/Chassis/1/Sensors/Fan1"},
+                {"@odata.id": "/redfish/v1/Chassis/1/Sensors/PSUStatus"},
+            ])
+        );
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors/CPUTemp",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#Sensor.v1_7_0.Sensor");
+        assert_eq!(body["Name"], "CPU Temp");
+        assert_eq!(body["Reading"], 45.0);
+        assert_eq!(body["ReadingUnits"], "Cel");
+        assert_eq!(body["Status"]["Health"], "OK");
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors/Fan1",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Status"]["Health"], "Warning");
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors/PSUStatus",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Reading"], Value::Null);
+        assert_eq!(body["Status"]["State"], "UnavailableOffline");
+
+        let response = get(&mut app, "/redfish/v1/Chassis/1/Sensors/Missing", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // The adapter's nodes are read-only
+        let data = json!({"Reading": 0});
+        let response = patch(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors/CPUTemp",
+            data,
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn caching_adapter() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let counter = reads.clone();
+        let sdr = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            String::from("CPU Temp | 45 degrees C | ok\n")
+        };
+        let adapter = CachingAdapter::new(
+            IpmiSensorAdapter::new(Box::new(sdr)),
+            Duration::from_secs(60),
+        );
+        let cache = adapter.get_cache();
+        let events = EventService::new();
+        cache.invalidate_on_events(&events);
+        let tree = BackendTree::new(get_mock_tree()).with_adapter(adapter);
+        let mut app = redfish_axum::app(tree);
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/Chassis/1/Sensors/CPUTemp";
+
+        // The backend is only read on first access
+        for _ in 0..3 {
+            jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+        jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+        // Invalidating a node also invalidates its collection
+        cache.invalidate(uri);
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        jget(
+            &mut app,
+            "/redfish/v1/Chassis/1/Sensors",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+
+        // Change notifications invalidate the nodes they are about
+        events.resource_changed(uri);
+        while reads.load(Ordering::SeqCst) == 4 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 5);
+
+        // Nodes older than the TTL are read again
+        let reads = Arc::new(AtomicUsize::new(0));
+        let counter = reads.clone();
+        let sdr = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            String::from("CPU Temp | 45 degrees C | ok\n")
+        };
+        let clock = ManualClock::new();
+        let adapter = CachingAdapter::new(
+            IpmiSensorAdapter::new(Box::new(sdr)),
+            Duration::from_secs(60),
+        )
+        .with_clock(clock.clone());
+        let tree = BackendTree::new(get_mock_tree()).with_adapter(adapter);
+        let mut app = redfish_axum::app(tree);
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        clock.advance(Duration::from_secs(59));
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+        clock.advance(Duration::from_secs(1));
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn lint_builtin_registries() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../redfish-axum/registries");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let data = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert_eq!(lint_message_registry(&data), vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_raw_body() {
+        let mut app = app();
+        let response = get(
+            &mut app,
+            "/redfish/v1/CertificateService/ServerCertificate.pem",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_header(&response, "content-type"),
+            "application/x-pem-file"
+        );
+        assert_eq!(get_header(&response, "allow"), "GET,HEAD");
+        assert_eq!(get_header(&response, "OData-Version"), "4.0");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, &include_bytes!("../cert.pem")[..]);
+    }
+
+    // Start an upload that stays in progress until the returned sender is dropped.
+    async fn start_upload(
+        app: &NormalizePath<Router>,
+        recorder: &RecordingWriter,
+    ) -> (hyper::body::Sender, tokio::task::JoinHandle<Response>) {
+        let (mut sender, body) = Body::channel();
+        let mut req = Request::post("/redfish/v1/UpdateService/test-upload")
+            .header("Content-Type", "application/octet-stream");
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let mut uploader = app.clone();
+        let upload = tokio::spawn(async move {
+            let req = req.body(body).unwrap();
+            uploader.ready().await.unwrap().call(req).await.unwrap()
+        });
+        sender
+            .send_data(Bytes::from_static(b"first"))
+            .await
+            .unwrap();
+        while recorder.data.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        (sender, upload)
+    }
+
+    #[tokio::test]
+    async fn certificate_rotation() {
+        let dir = std::env::temp_dir().join(format!("redfish-certs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, include_bytes!("../cert.pem")).unwrap();
+        std::fs::write(&key_path, include_bytes!("../key.pem")).unwrap();
+        let mut watcher = CertificateWatcher::new(cert_path.clone(), key_path.clone())
+            .await
+            .unwrap();
+        let config = watcher.get_config();
+        let original = config.get_inner();
+        assert!(!watcher.check().await.unwrap());
+
+        // Installing a bad certificate keeps the old one.
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::write(&cert_path, "not a certificate").unwrap();
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&cert_path)
+            .unwrap();
+        file.set_modified(later).unwrap();
+        assert!(watcher.check().await.is_err());
+        assert!(Arc::ptr_eq(&config.get_inner(), &original));
+
+        // Installing a good one swaps it into the config the server already has.
+        std::fs::write(&cert_path, include_bytes!("../cert.pem")).unwrap();
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&cert_path)
+            .unwrap();
+        file.set_modified(later + Duration::from_secs(10)).unwrap();
+        assert!(watcher.check().await.unwrap());
+        assert!(!Arc::ptr_eq(&config.get_inner(), &original));
+        assert!(!watcher.check().await.unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn allowed_hosts() {
+        let hosts = vec![
+            String::from("bmc.example.com"),
+            String::from("10.0.0.5:8443"),
+        ];
+        let mut app = AppBuilder::new(get_mock_tree())
+            .allowed_hosts(Some(hosts))
+            .build();
+        for (host, status) in [
+            (Some("bmc.example.com"), StatusCode::OK),
+            (Some("BMC.Example.com:443"), StatusCode::OK),
+            (Some("10.0.0.5:8443"), StatusCode::OK),
+            (Some("10.0.0.5"), StatusCode::BAD_REQUEST),
+            (Some("attacker.example.com"), StatusCode::BAD_REQUEST),
+            (None, StatusCode::BAD_REQUEST),
+        ] {
+            let mut request = Request::get("/redfish/v1");
+            if let Some(host) = host {
+                request = request.header("Host", host);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status, "{:?}", host);
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit() {
+        let app_with_limit = |recorder: &RecordingWriter, config| {
+            let mut tree = get_mock_tree();
+            let writer = recorder.clone();
+            tree.add_upload(
+                "/redfish/v1/UpdateService/test-upload",
+                Box::new(move || Box::new(writer.clone())),
+            );
+            AppBuilder::new(tree)
+                .concurrency_limit(Some(config))
+                .build()
+        };
+        let auth = admin_admin_basic_auth();
+
+        // Requests are refused while the limit is reached
+        let recorder = RecordingWriter::default();
+        let config = ConcurrencyLimitConfig {
+            max_requests: 1,
+            max_requests_per_method: HashMap::new(),
+            retry_after: Duration::from_millis(2500),
+        };
+        let mut app = app_with_limit(&recorder, config);
+        let (sender, upload) = start_upload(&app, &recorder).await;
+        let response = get(&mut app, "/redfish/v1/SessionService", &auth).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(get_header(&response, "retry-after"), "3");
+        assert_eq!(get_header(&response, "OData-Version"), "4.0");
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["code"],
+            "Base.1.16.ServiceTemporarilyUnavailable"
+        );
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageArgs"],
+            json!(["3"])
+        );
+
+        // They are accepted again once requests finish
+        drop(sender);
+        assert_eq!(upload.await.unwrap().status(), StatusCode::NO_CONTENT);
+        jget(
+            &mut app,
+            "/redfish/v1/SessionService",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+
+        // Limits per method only apply to requests with that method
+        let recorder = RecordingWriter::default();
+        let config = ConcurrencyLimitConfig {
+            max_requests: 4,
+            max_requests_per_method: HashMap::from([(http::Method::POST, 1)]),
+            ..Default::default()
+        };
+        let mut app = app_with_limit(&recorder, config);
+        let (sender, upload) = start_upload(&app, &recorder).await;
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let response = post(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions",
+            data,
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(get_header(&response, "retry-after"), "1");
+        jget(
+            &mut app,
+            "/redfish/v1/SessionService",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        drop(sender);
+        assert_eq!(upload.await.unwrap().status(), StatusCode::NO_CONTENT);
+        login(&mut app).await;
+    }
+
+    #[tokio::test]
+    async fn raw_upload() {
+        let recorder = RecordingWriter::default();
+        let mut tree = get_mock_tree();
+        let writer = recorder.clone();
+        tree.add_upload(
+            "/redfish/v1/UpdateService/test-upload",
+            Box::new(move || Box::new(writer.clone())),
+        );
+        let mut app = redfish_axum::app(tree);
+
+        // Stream the body in a few chunks
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            for chunk in [&b"first,"[..], b"second,", b"third"] {
+                sender.send_data(Bytes::from_static(chunk)).await.unwrap();
+            }
+        });
+        let mut req = Request::post("/redfish/v1/UpdateService/test-upload")
+            .header("Content-Type", "application/octet-stream")
+            .header("Content-Length", "18");
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(body).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(get_header(&response, "OData-Version"), "4.0");
+        assert_eq!(*recorder.data.lock().unwrap(), b"first,second,third");
+        assert_eq!(
+            *recorder.progress.lock().unwrap(),
+            vec![(6, Some(18)), (13, Some(18)), (18, Some(18))]
+        );
+        assert!(*recorder.finished.lock().unwrap());
+
+        // Binary bodies are not accepted by other resources
+        let mut req = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/octet-stream");
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(Body::from("data")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        // The demo firmware upload works too
+        let mut req = Request::post("/redfish/v1/UpdateService/upload")
+            .header("Content-Type", "application/octet-stream");
+        add_auth_headers(&mut req, &admin_admin_basic_auth());
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(Body::from("image")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    // A tree that has to be told when it may finish starting, and records its shutdown.
+    struct WarmingTree {
+        tree: MockTree,
+        warmed: Arc<tokio::sync::Notify>,
+        shut_down: Arc<Mutex<bool>>,
+    }
+
+    #[async_trait]
+    impl Tree for WarmingTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+
+        async fn on_start(&mut self) -> Result<(), Error> {
+            self.warmed.notified().await;
+            Ok(())
+        }
+
+        async fn on_shutdown(&mut self) {
+            *self.shut_down.lock().unwrap() = true;
+        }
+    }
+
+    #[tokio::test]
+    async fn lifecycle() {
+        let warmed = Arc::new(tokio::sync::Notify::new());
+        let shut_down = Arc::new(Mutex::new(false));
+        let tree = WarmingTree {
+            tree: get_mock_tree(),
+            warmed: warmed.clone(),
+            shut_down: shut_down.clone(),
+        };
+        let lifecycle = Lifecycle::new();
+        let mut app = AppBuilder::new(tree).lifecycle(lifecycle.clone()).build();
+        assert_eq!(lifecycle.get_phase(), LifecyclePhase::Starting);
+
+        // Only /redfish is served while starting
+        let response = get(&mut app, "/redfish", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(&mut app, "/redfish/v1", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(get_header(&response, "retry-after"), "5");
+        let response = get(
+            &mut app,
+            "/redfish/v1/SessionService",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Everything is served once started
+        warmed.notify_one();
+        assert!(lifecycle.wait_started().await);
+        assert_eq!(lifecycle.get_phase(), LifecyclePhase::Running);
+        let response = get(&mut app, "/redfish/v1", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(
+            &mut app,
+            "/redfish/v1/SessionService",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Shutting down lets the tree clean up, and stops serving it
+        assert!(!*shut_down.lock().unwrap());
+        lifecycle.shutdown().await;
+        assert!(*shut_down.lock().unwrap());
+        assert_eq!(lifecycle.get_phase(), LifecyclePhase::Stopped);
+        let response = get(&mut app, "/redfish/v1", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!lifecycle.wait_started().await);
+
+        // A tree that fails to start is not served
+        struct FailingTree(MockTree);
+        #[async_trait]
+        impl Tree for FailingTree {
+            async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+                self.0.get(uri, username).await
+            }
+
+            async fn create(
+                &mut self,
+                uri: &str,
+                request_body: &Map<String, Value>,
+                username: Option<&str>,
+            ) -> Result<&dyn Node, Error> {
+                self.0.create(uri, request_body, username).await
+            }
+
+            async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+                self.0.delete(uri, username).await
+            }
+
+            async fn patch(
+                &mut self,
+                uri: &str,
+                request_body: &Map<String, Value>,
+                username: Option<&str>,
+            ) -> Result<&dyn Node, Error> {
+                self.0.patch(uri, request_body, username).await
+            }
+
+            fn get_collection_types(&self) -> &[CollectionType] {
+                self.0.get_collection_types()
+            }
+
+            fn get_resource_types(&self) -> &[ResourceType] {
+                self.0.get_resource_types()
+            }
+
+            async fn on_start(&mut self) -> Result<(), Error> {
+                Err(Error::InternalError)
+            }
+        }
+        let lifecycle = Lifecycle::new();
+        let mut app = AppBuilder::new(FailingTree(get_mock_tree()))
+            .lifecycle(lifecycle.clone())
+            .build();
+        assert!(!lifecycle.wait_started().await);
+        assert_eq!(lifecycle.get_phase(), LifecyclePhase::Failed);
+        let response = get(&mut app, "/redfish/v1", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let response = get(&mut app, "/redfish", &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn validate_tree() {
+        // The mock tree passes
+        let report = validate(&get_mock_tree(), Some("admin")).await;
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert!(report
+            .checked
+            .contains(&String::from("/redfish/v1/AccountService/Accounts/admin")));
+
+        // Static trees have no ETags, and this one links to a missing system
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let tree = TreeBuilder::new()
+            .resource(
+                "/redfish/v1",
+                "Root Service",
+                ResourceType::new_dmtf(
+                    String::from("ServiceRoot"),
+                    ResourceSchemaVersion::new(1, 15, 0),
+                ),
+                allowed_methods,
+                json!({"Systems": {"@odata.id": "/redfish/v1/Systems/1#/Status"}}),
+            )
+            .build();
+        let report = validate(&tree, Some("admin")).await;
+        assert_eq!(report.checked, vec![String::from("/redfish/v1")]);
+        assert_eq!(
+            report.issues,
+            vec![
+                ValidationIssue::MissingEtag {
+                    uri: String::from("/redfish/v1")
+                },
+                ValidationIssue::MissingServiceRootLink(String::from("Sessions")),
+                ValidationIssue::DanglingLink {
+                    from: String::from("/redfish/v1"),
+                    to: String::from("/redfish/v1/Systems/1"),
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_json()["Issues"][2],
+            json!({"Issue": "DanglingLink", "Uri": "/redfish/v1", "Link": "/redfish/v1/Systems/1"})
+        );
+
+        // A resource whose type the tree does not declare is missing from $metadata
+        let tree = StaleSessionsTree {
+            tree: get_mock_tree(),
+            sessions: Collection::new(
+                "/redfish/v1/SessionService/Sessions",
+                String::from("UndeclaredCollection"),
+                String::from("Session Collection"),
+                Vec::new(),
+                None,
+            ),
+        };
+        let report = validate(&tree, Some("admin")).await;
+        assert_eq!(
+            report.issues,
+            vec![ValidationIssue::MissingMetadata {
+                uri: String::from("/redfish/v1/SessionService/Sessions")
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn static_tree_bodies() {
+        let uri = "/redfish/v1/Chassis/1";
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: true,
+            post: false,
+        };
+        let body = json!({
+            "AssetTag": null,
+            "Location": {"PartLocation": {"ServiceLabel": "Slot 1", "LocationOrdinalValue": 0}},
+            "PowerCapacityWatts": 1.5e3,
+            "Serial": u64::MAX,
+            "Manufacturer": "Contoso \u{2013} Ünited",
+            "Sensors": [{"Reading": -1}, [true, false], "OK"],
+        });
+        let mut tree = TreeBuilder::new()
+            .resource(
+                uri,
+                "Chassis",
+                ResourceType::new_dmtf(
+                    String::from("Chassis"),
+                    ResourceSchemaVersion::new(1, 23, 0),
+                ),
+                allowed_methods,
+                body.clone(),
+            )
+            .build();
+
+        // The body is held compactly, but read back whole
+        let node = tree.get(uri, Some("admin")).await.unwrap();
+        let mut read = node.get_body();
+        for added in ["@odata.id", "@odata.type", "Id", "Name"] {
+            read.as_object_mut().unwrap().remove(added);
+        }
+        assert_eq!(read, body);
+
+        // Patches replace properties rather than repeating them, and nested ones whole
+        let data = json!({"AssetTag": "Rack 4", "Location": {"Placement": {"Row": "B"}}});
+        tree.patch(uri, data.as_object().unwrap(), Some("admin"))
+            .await
+            .unwrap();
+        let node = tree.get(uri, Some("admin")).await.unwrap();
+        let read = node.get_body();
+        assert_eq!(read["AssetTag"], "Rack 4");
+        assert_eq!(read["Location"], json!({"Placement": {"Row": "B"}}));
+        assert_eq!(read["Serial"], u64::MAX);
+        assert_eq!(
+            read.as_object().unwrap().len(),
+            body.as_object().unwrap().len() + 4
+        );
+    }
+
+    #[tokio::test]
+    async fn only() {
+        let mut app = redfish_axum::app(get_mock_tree());
+        let auth = admin_admin_basic_auth();
+
+        // The only member is returned instead of its collection
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts?only",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["@odata.id"],
+            "/redfish/v1/AccountService/Accounts/admin"
+        );
+        assert_eq!(body["UserName"], "admin");
+
+        // Collections without exactly one member cannot be narrowed to it
+        let body = jget(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions?only",
+            StatusCode::BAD_REQUEST,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["error"]["code"], "Base.1.16.QueryParameterOutOfRange");
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["Message"],
+            "The value '0' for the query parameter only is out of range 1."
+        );
+
+        // Nor can resources, or other query parameters be given with it
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService?only",
+            StatusCode::BAD_REQUEST,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["error"]["code"],
+            "Base.1.16.QueryNotSupportedOnResource"
+        );
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts?only&$top=1",
+            StatusCode::BAD_REQUEST,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["error"]["code"], "Base.1.16.QueryCombinationInvalid");
+        let response = get(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts?only=1",
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn response_statistics() {
+        let statistics = ResponseStatistics::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .response_statistics(statistics.clone())
+            .build();
+        let auth = admin_admin_basic_auth();
+        jget(&mut app, "/redfish/v1", StatusCode::OK, &Auth::None, &[]).await;
+        jget(
+            &mut app,
+            "/redfish/v1/AccountService",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        let response = get(&mut app, "/redfish/v1/AccountService", &auth).await;
+        let length = get_response_json(response).await.to_string().len();
+        jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        let response = patch(
+            &mut app,
+            "/redfish/v1/SessionService",
+            json!({"SessionTimeout": "long"}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Requests that do not reach a resource are not counted
+        let response = get(&mut app, "/redfish/v1/notfound", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        validate_unauthorized(&get(&mut app, "/redfish/v1/AccountService", &Auth::None).await);
+
+        let names: Vec<String> = statistics
+            .get_all()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "AccountService",
+                "ManagerAccountCollection",
+                "ServiceRoot",
+                "SessionService"
+            ]
+        );
+        let account_service = statistics.get("AccountService").unwrap();
+        assert_eq!(account_service.requests, 2);
+        assert_eq!(account_service.errors, 0);
+        assert_eq!(account_service.get_average_body_size(), length as f64);
+        let session_service = statistics.get("SessionService").unwrap();
+        assert_eq!(session_service.requests, 1);
+        assert_eq!(session_service.get_error_rate(), 1.0);
+
+        statistics.reset();
+        assert!(statistics.get("ServiceRoot").is_none());
+    }
+
+    #[tokio::test]
+    async fn unsupported_query_parameters() {
+        let mut app = redfish_axum::app(get_mock_tree());
+        let auth = admin_admin_basic_auth();
+
+        // Parameters defined by the spec that the service does not support are refused
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts?$count=true",
+            StatusCode::NOT_IMPLEMENTED,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["error"]["code"], "Base.1.16.QueryParameterUnsupported");
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageArgs"],
+            json!(["$count"])
+        );
+        let body = jget(
+            &mut app,
+            "/redfish/v1/EventService?$levels=1",
+            StatusCode::NOT_IMPLEMENTED,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["error"]["code"], "Base.1.16.QueryParameterUnsupported");
+        let response = delete(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions/1?$count=true",
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+
+        // Others are ignored
+        jget(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts?vendor=1",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+
+        // Those that only apply to GET cannot be used with other operations
+        let response = patch(
+            &mut app,
+            "/redfish/v1/SessionService?$select=SessionTimeout",
+            json!({"SessionTimeout": 60}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["code"],
+            "Base.1.16.QueryNotSupportedOnOperation"
+        );
+        let response = post(
+            &mut app,
+            "/redfish/v1/SessionService/Sessions?$top=1",
+            json!({"UserName": "admin", "Password": "admin"}),
+            &Auth::None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = patch(
+            &mut app,
+            "/redfish/v1/SessionService?vendor=1",
+            json!({"SessionTimeout": 60}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn link_registry() {
+        let account = "/redfish/v1/AccountService/Accounts/admin";
+        let session = "/redfish/v1/SessionService/Sessions/1";
+        let tree = get_mock_tree();
+        let links: LinkRegistry = tree.get_link_registry().unwrap().clone();
+        let role = Reference {
+            from: String::from(account),
+            property: String::from("Links/Role"),
+            to: String::from("/redfish/v1/AccountService/Roles/Administrator"),
+        };
+        assert_eq!(
+            links.get_dependents("/redfish/v1/AccountService/Roles/Administrator"),
+            vec![role.clone()]
+        );
+        assert_eq!(links.get_references(account), vec![role.clone()]);
+
+        // Links from and to a resource are forgotten when it is deleted through the service
+        let mut app = redfish_axum::app(tree);
+        let (auth, _) = login(&mut app).await;
+        links.add(session, "Oem/Contoso/Account", account);
+        links.add(account, "Oem/Contoso/LastSession", session);
+        assert_eq!(links.get_dependents(session).len(), 1);
+        let response = delete(&mut app, session, &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(links.get_references(session).is_empty());
+        assert!(links.get_dependents(session).is_empty());
+        assert_eq!(links.get_references(account), vec![role.clone()]);
+
+        // Links to moved resources are updated, and returned for repairing the bodies linking
+        let repaired = links.rename(
+            "/redfish/v1/AccountService/Roles",
+            "/redfish/v1/AccountService/AllRoles",
+        );
+        let moved = Reference {
+            to: String::from("/redfish/v1/AccountService/AllRoles/Administrator"),
+            ..role
+        };
+        assert_eq!(repaired, vec![moved.clone()]);
+        assert_eq!(links.get_references(account), vec![moved]);
+        assert!(links
+            .rename("/redfish/v1/AccountService/Role", "/redfish/v1/Other")
+            .is_empty());
+
+        // Only one link is kept per property
+        links.add(
+            account,
+            "Links/Role",
+            "/redfish/v1/AccountService/AllRoles/ReadOnly",
+        );
+        assert_eq!(links.get_references(account).len(), 1);
+        links.remove(account, "Links/Role");
+        assert!(links.get_references(account).is_empty());
+    }
+
+    #[tokio::test]
+    async fn max_response_size() {
+        let auth = admin_admin_basic_auth();
+        let mut app = redfish_axum::app(get_mock_tree());
+        let response = get(&mut app, "/redfish/v1/AccountService", &auth).await;
+        let length = get_response_json(response).await.to_string().len();
+
+        // Bodies up to the limit are sent
+        let mut app = AppBuilder::new(get_mock_tree())
+            .max_response_size(Some(length))
+            .build();
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService",
+            StatusCode::OK,
+            &auth,
+            &[("content-type", "application/json")],
+        )
+        .await;
+        assert_eq!(body.to_string().len(), length);
+
+        // Larger ones are refused, telling the client how to get less
+        let mut app = AppBuilder::new(get_mock_tree())
+            .max_response_size(Some(length - 1))
+            .build();
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService",
+            StatusCode::INSUFFICIENT_STORAGE,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["error"]["code"], "Base.1.16.InsufficientStorage");
+        let resolution = body["error"]["@Message.ExtendedInfo"][0]["Resolution"]
+            .as_str()
+            .unwrap();
+        assert!(resolution.contains("$top"));
+        jget(
+            &mut app,
+            "/redfish/v1/AccountService?$select=Accounts",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn resource_events() {
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let auth = admin_admin_basic_auth();
+        let mut events = event_service.subscribe();
+
+        // Changes through the service are announced
+        let response = patch(
+            &mut app,
+            "/redfish/v1/SessionService",
+            json!({"SessionTimeout": 60}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let event = events.try_recv().unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceChanged"
+        );
+        assert_eq!(
+            event["Events"][0]["MessageArgs"],
+            json!(["/redfish/v1/SessionService"])
+        );
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"],
+            json!({"@odata.id": "/redfish/v1/SessionService"})
+        );
+
+        // Failed ones are not
+        let response = patch(
+            &mut app,
+            "/redfish/v1/AccountService",
+            json!({"Bogus": 1}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(events.try_recv().is_err());
+
+        // Nor are any while the event service is disabled
+        event_service.set_settings(EventServiceSettings {
+            service_enabled: false,
+            ..event_service.get_settings()
+        });
+        let response = patch(
+            &mut app,
+            "/redfish/v1/SessionService",
+            json!({"SessionTimeout": 90}),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(events.try_recv().is_err());
+    }
+
+    // A tree that counts how many of its resources are being read at once.
+    struct ConcurrentReadsTree {
+        tree: MockTree,
+        reading: Arc<std::sync::atomic::AtomicUsize>,
+        most_reading: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Tree for ConcurrentReadsTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            use std::sync::atomic::Ordering;
+            let reading = self.reading.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_reading.fetch_max(reading, Ordering::SeqCst);
+            // Like a backend, reads take a while.
+            tokio::task::yield_now().await;
+            self.reading.fetch_sub(1, Ordering::SeqCst);
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn expand_concurrently() {
+        let most_reading = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut app = redfish_axum::app(ConcurrentReadsTree {
+            tree: get_mock_tree(),
+            reading: Default::default(),
+            most_reading: most_reading.clone(),
+        });
+        let body = jget(
+            &mut app,
+            "/redfish/v1/AccountService/Roles?$expand=.",
+            StatusCode::OK,
+            &admin_admin_basic_auth(),
+            &[],
+        )
+        .await;
+
+        // The members are read together, and kept in order
+        assert_eq!(most_reading.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let ids: Vec<&str> = body["Members"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member["Id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["Administrator", "Operator", "ReadOnly"]);
+    }
+
+    #[tokio::test]
+    async fn event_delivery_retries() {
+        // Retries back off exponentially from the interval
+        let settings = EventServiceSettings {
+            delivery_retry_attempts: 3,
+            delivery_retry_interval_seconds: 60,
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.get_retry_delays(),
+            vec![
+                Duration::from_secs(60),
+                Duration::from_secs(120),
+                Duration::from_secs(240)
+            ]
+        );
+        let settings = EventServiceSettings {
+            delivery_retry_attempts: 20,
+            ..settings
+        };
+        assert_eq!(settings.get_retry_delays()[19], Duration::from_secs(3600));
+
+        let event_service = EventService::new();
+        event_service.set_settings(EventServiceSettings {
+            delivery_retry_attempts: 2,
+            delivery_retry_interval_seconds: 0,
+            ..Default::default()
+        });
+        let attempts = Mutex::new(0);
+        let attempt = || async {
+            *attempts.lock().unwrap() += 1;
+            false
+        };
+        assert_eq!(
+            event_service.deliver(attempt).await,
+            DeliveryOutcome::RetriesExhausted
+        );
+        assert_eq!(*attempts.lock().unwrap(), 3);
+
+        // Delivery stops once an attempt succeeds
+        *attempts.lock().unwrap() = 0;
+        let attempt = || async {
+            let mut attempts = attempts.lock().unwrap();
+            *attempts += 1;
+            *attempts == 2
+        };
+        assert_eq!(
+            event_service.deliver(attempt).await,
+            DeliveryOutcome::Delivered
+        );
+        assert_eq!(*attempts.lock().unwrap(), 2);
+    }
+
+    // Passes on what it is given to send, which is accepted while it is told to accept.
+    #[derive(Clone)]
+    struct ChannelSender {
+        sent: tokio::sync::mpsc::UnboundedSender<(String, Value)>,
+        accept: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl EventSender for ChannelSender {
+        async fn send(&self, destination: &str, payload: &Value) -> bool {
+            self.sent
+                .send((String::from(destination), payload.clone()))
+                .unwrap();
+            self.accept.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn event_subscriptions() {
+        let (sent, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let accept = Arc::new(AtomicBool::new(true));
+        let sender = ChannelSender {
+            sent,
+            accept: accept.clone(),
+        };
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(Roles)
+            .event_service(event_service.clone())
+            .event_sender(sender)
+            .build();
+        let admin = admin_admin_basic_auth();
+        // Leia:x
+        let leia = Auth::Basic(String::from("Basic TGVpYTp4"));
+        let uri = "/redfish/v1/EventService/Subscriptions";
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/EventService",
+            StatusCode::OK,
+            &admin,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Subscriptions"], json!({"@odata.id": uri}));
+        let body = jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(body["Members@odata.count"], 0);
+
+        // Subscribing needs ConfigureManager or ConfigureComponents
+        let req = json!({
+            "Destination": "https://receiver.example/events",
+            "Protocol": "Redfish",
+            "Context": "Hello",
+        });
+        let response = post(&mut app, uri, req.clone(), &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Destinations have to be HTTP URIs, and unknown properties are refused
+        let bad = json!({"Destination": "receiver", "Protocol": "Redfish", "Bogus": 1});
+        let response = post(&mut app, uri, bad, &admin).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let mut ids: Vec<&str> = body["error"]["@Message.ExtendedInfo"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["MessageId"].as_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![
+                "Base.1.16.PropertyUnknown",
+                "Base.1.16.PropertyValueFormatError"
+            ]
+        );
+        let response = post(&mut app, uri, json!({"Destination": "https://a"}), &admin).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post(&mut app, uri, req, &admin).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let subscription = get_header(&response, "location").to_string();
+        assert_eq!(subscription, "/redfish/v1/EventService/Subscriptions/1");
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["@odata.type"],
+            "#EventDestination.v1_13_0.EventDestination"
+        );
+        assert_eq!(body["Destination"], "https://receiver.example/events");
+        assert_eq!(body["Context"], "Hello");
+
+        // Its own creation is the first event it is pushed, with its Context
+        let (destination, event) = received.recv().await.unwrap();
+        assert_eq!(destination, "https://receiver.example/events");
+        assert_eq!(event["Context"], "Hello");
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"]["@odata.id"],
+            subscription
+        );
+        let body = jget(&mut app, &subscription, StatusCode::OK, &leia, &[]).await;
+        let status = &body["Oem"]["RedfishAxum"];
+        assert_eq!(status["ConsecutiveDeliveryFailures"], 0);
+        assert!(status["LastDelivery"].is_string());
+        assert_eq!(body["Status"]["Health"], "OK");
+
+        // Broken destinations show in how delivery has gone
+        accept.store(false, Ordering::SeqCst);
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        received.recv().await.unwrap();
+        let body = jget(&mut app, &subscription, StatusCode::OK, &leia, &[]).await;
+        let status = &body["Oem"]["RedfishAxum"];
+        assert_eq!(status["ConsecutiveDeliveryFailures"], 1);
+        assert!(status["LastDeliveryAttempt"].is_string());
+        assert_eq!(body["Status"]["Health"], "Warning");
+
+        // Once deleted, it is pushed nothing more
+        let response = delete(&mut app, &subscription, &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = delete(&mut app, &subscription, &admin).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = get(&mut app, &subscription, &admin).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        tokio::task::yield_now().await;
+        assert!(received.try_recv().is_err());
+
+        // Those whose retries are exhausted are terminated, or suspended if they ask to be
+        event_service.set_settings(EventServiceSettings {
+            delivery_retry_attempts: 1,
+            delivery_retry_interval_seconds: 0,
+            ..Default::default()
+        });
+        accept.store(true, Ordering::SeqCst);
+        let req = json!({"Destination": "https://a.example", "Protocol": "Redfish"});
+        let response = post(&mut app, uri, req, &admin).await;
+        let terminated = get_header(&response, "location").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["DeliveryRetryPolicy"], "TerminateAfterRetries");
+        received.recv().await.unwrap();
+        let req = json!({
+            "Destination": "https://b.example",
+            "Protocol": "Redfish",
+            "DeliveryRetryPolicy": "SuspendRetries",
+        });
+        let response = post(&mut app, uri, req, &admin).await;
+        let suspended = get_header(&response, "location").to_string();
+        for _ in 0..2 {
+            received.recv().await.unwrap();
+        }
+        accept.store(false, Ordering::SeqCst);
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        let mut destinations = Vec::new();
+        for _ in 0..4 {
+            destinations.push(received.recv().await.unwrap().0);
+        }
+        destinations.sort();
+        assert_eq!(
+            destinations,
+            vec![
+                "https://a.example",
+                "https://a.example",
+                "https://b.example",
+                "https://b.example"
+            ]
+        );
+        let response = get(&mut app, &terminated, &admin).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = jget(&mut app, &suspended, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["Status"]["State"], "Disabled");
+        assert_eq!(body["Oem"]["RedfishAxum"]["ConsecutiveDeliveryFailures"], 2);
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        tokio::task::yield_now().await;
+        assert!(received.try_recv().is_err());
+
+        let req = json!({
+            "Destination": "https://c.example",
+            "Protocol": "Redfish",
+            "DeliveryRetryPolicy": "RetryForever",
+        });
+        let response = post(&mut app, uri, req, &admin).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Those of MetricReports are pushed reports instead of events, with their Context
+        accept.store(true, Ordering::SeqCst);
+        let req = json!({
+            "Destination": "https://d.example",
+            "Protocol": "Redfish",
+            "EventFormatType": "Bogus",
+        });
+        let response = post(&mut app, uri, req, &admin).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyValueNotInList"
+        );
+        let req = json!({
+            "Destination": "https://d.example",
+            "Protocol": "Redfish",
+            "Context": "Reports",
+            "EventFormatType": "MetricReport",
+        });
+        let response = post(&mut app, uri, req, &admin).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = get_response_json(response).await;
+        assert_eq!(body["EventFormatType"], "MetricReport");
+        event_service.resource_changed("/redfish/v1/Systems/1");
+        let report_uri = "/redfish/v1/TelemetryService/MetricReports/Power";
+        event_service.send_metric_report(json!({"@odata.id": report_uri}));
+        let (destination, report) = received.recv().await.unwrap();
+        assert_eq!(destination, "https://d.example");
+        assert_eq!(
+            report,
+            json!({"@odata.id": report_uri, "Context": "Reports"})
+        );
+        assert!(received.try_recv().is_err());
+
+        // Events can have the bodies of their origins, as the subscriber reads them when pushed
+        let req = json!({
+            "Destination": "https://e.example",
+            "Protocol": "Redfish",
+            "IncludeOriginOfCondition": true,
+        });
+        let response = post(&mut app, uri, req, &admin).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = get_response_json(response).await;
+        assert_eq!(body["IncludeOriginOfCondition"], true);
+        received.recv().await.unwrap();
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let (_, event) = received.recv().await.unwrap();
+        let origin = &event["Events"][0]["OriginOfCondition"];
+        assert_eq!(
+            origin["@odata.id"],
+            "/redfish/v1/AccountService/Accounts/admin"
+        );
+        assert_eq!(origin["UserName"], "admin");
+    }
+
+    // Two systems, one of them in a chassis, and firmware on a manager and in the inventory.
+    fn get_inventory_tree() -> StaticTree {
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let resource_type = |name: &str| {
+            ResourceType::new_dmtf(String::from(name), ResourceSchemaVersion::new(1, 0, 0))
+        };
+        TreeBuilder::new()
+            .resource(
+                "/redfish/v1",
+                "Root Service",
+                resource_type("ServiceRoot"),
+                allowed_methods,
+                json!({
+                    "Systems": {"@odata.id": "/redfish/v1/Systems"},
+                    "Managers": {"@odata.id": "/redfish/v1/Managers/BMC"},
+                    "Firmware": {"@odata.id": "/redfish/v1/UpdateService/FirmwareInventory/BIOS"},
+                }),
+            )
+            .collection(
+                "/redfish/v1/Systems",
+                "Computer System Collection",
+                CollectionType::new_dmtf_v1(String::from("ComputerSystemCollection")),
+                vec![
+                    String::from("/redfish/v1/Systems/2"),
+                    String::from("/redfish/v1/Systems/1"),
+                ],
+                None,
+            )
+            .resource(
+                "/redfish/v1/Systems/1",
+                "System",
+                resource_type("ComputerSystem"),
+                allowed_methods,
+                json!({
+                    "Manufacturer": "Contoso",
+                    "PowerState": "On",
+                    "Status": {"Health": "OK"},
+                    "Links": {"Chassis": [{"@odata.id": "/redfish/v1/Chassis/1"}]},
+                }),
+            )
+            .resource(
+                "/redfish/v1/Systems/2",
+                "System",
+                resource_type("ComputerSystem"),
+                allowed_methods,
+                json!({}),
+            )
+            .resource(
+                "/redfish/v1/Chassis/1",
+                "Chassis",
+                resource_type("Chassis"),
+                allowed_methods,
+                json!({"ChassisType": "RackMount"}),
+            )
+            .resource(
+                "/redfish/v1/Managers/BMC",
+                "Manager",
+                resource_type("Manager"),
+                allowed_methods,
+                json!({"FirmwareVersion": "1.2.3"}),
+            )
+            .resource(
+                "/redfish/v1/UpdateService/FirmwareInventory/BIOS",
+                "BIOS",
+                resource_type("SoftwareInventory"),
+                allowed_methods,
+                json!({"Version": "P80", "Updateable": true}),
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn inventory() {
+        let tree = get_inventory_tree();
+
+        let inventory = get_inventory(&tree, Some("admin")).await;
+        assert_eq!(
+            inventory["Systems"][0],
+            json!({
+                "Uri": "/redfish/v1/Systems/1",
+                "Id": "1",
+                "Name": "System",
+                "Manufacturer": "Contoso",
+                "Model": null,
+                "SerialNumber": null,
+                "PowerState": "On",
+                "BiosVersion": null,
+                "Health": "OK",
+            })
+        );
+        assert_eq!(inventory["Systems"][1]["Uri"], "/redfish/v1/Systems/2");
+        assert_eq!(inventory["Chassis"].as_array().unwrap().len(), 1);
+        assert_eq!(inventory["Chassis"][0]["ChassisType"], "RackMount");
+        assert_eq!(
+            inventory["Firmware"],
+            json!([
+                {"Uri": "/redfish/v1/Managers/BMC", "Name": "Manager", "Version": "1.2.3", "Updateable": null},
+                {"Uri": "/redfish/v1/UpdateService/FirmwareInventory/BIOS", "Name": "BIOS", "Version": "P80", "Updateable": true},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_inventory() {
+        // Served over HTTP a member at a time, so that the crawl has to follow the nextLinks.
+        let app = AppBuilder::new(get_inventory_tree())
+            .page_size(Some(1))
+            .build();
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let app = app.clone();
+            async move { Ok::<_, std::convert::Infallible>(app) }
+        });
+        let server =
+            hyper::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        // It is what the service would read in process, as the same user.
+        let admin = Client::new(&url).with_auth(redfish_client::Auth::Basic {
+            username: String::from("admin"),
+            password: String::from("admin"),
+        });
+        let inventory = admin.get_inventory().await.unwrap();
+        assert_eq!(
+            inventory,
+            get_inventory(&get_inventory_tree(), Some("admin")).await
+        );
+        assert_eq!(inventory["Systems"].as_array().unwrap().len(), 2);
+        assert_eq!(inventory["Systems"][0]["Manufacturer"], "Contoso");
+        assert_eq!(inventory["Chassis"][0]["Uri"], "/redfish/v1/Chassis/1");
+        assert_eq!(inventory["Firmware"].as_array().unwrap().len(), 2);
+
+        // Without credentials, only the ServiceRoot can be read, which has none of it.
+        let anonymous = Client::new(&url).get_inventory().await.unwrap();
+        assert_eq!(
+            anonymous,
+            json!({"Systems": [], "Chassis": [], "Firmware": []})
+        );
+    }
+
+    #[tokio::test]
+    async fn event_filters() {
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        event_service.resource_created("/redfish/v1/Systems/1");
+        event_service.log_entry_added(
+            "/redfish/v1/Managers/BMC/LogServices/Log",
+            "/redfish/v1/Managers/BMC/LogServices/Log/Entries/1",
+        );
+        let created = events.try_recv().unwrap();
+        let created = &created.get_records()[0];
+        let log_entry = events.try_recv().unwrap();
+        let log_entry = &log_entry.get_records()[0];
+
+        let filter = EventFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches(created, None));
+        let filter = EventFilter {
+            registry_prefixes: vec![String::from("ResourceEvent")],
+            ..Default::default()
+        };
+        assert!(filter.matches(created, None));
+        assert!(!filter.matches(log_entry, None));
+        let filter = EventFilter {
+            resource_types: vec![String::from("ComputerSystem")],
+            ..Default::default()
+        };
+        assert!(filter.matches(created, Some("ComputerSystem")));
+        assert!(!filter.matches(created, Some("Chassis")));
+        assert!(!filter.matches(created, None));
+        let filter = EventFilter {
+            origin_resources: vec![String::from("/redfish/v1/Managers/BMC/LogServices/Log")],
+            message_ids: vec![
+                String::from("LogEvent.LogEntryAdded"),
+                String::from("ResourceEvent.1.1.ResourceRemoved"),
+            ],
+            ..Default::default()
+        };
+        assert!(filter.matches(log_entry, None));
+        assert!(!filter.matches(created, None));
+
+        // The SSE stream takes the same filters as $filter.
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let uri = "/redfish/v1/EventService/SSE?$top=1";
+        let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let uri = "/redfish/v1/EventService/SSE?$filter=RegistryPrefix%20eq%20%27ResourceEvent%27%20and%20ResourceType%20eq%20%27ManagerAccount%27";
+        let response = get(&mut app, uri, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+
+        event_service.log_entry_added(
+            "/redfish/v1/Managers/BMC/LogServices/Log",
+            "/redfish/v1/Managers/BMC/LogServices/Log/Entries/2",
+        );
+        event_service.resource_changed("/redfish/v1/AccountService/Roles/Administrator");
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let chunk = hyper::body::HttpBody::data(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data:"))
+            .unwrap();
+        let event: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(event["Events"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceChanged"
+        );
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"],
+            json!({"@odata.id": "/redfish/v1/AccountService/Accounts/admin"})
+        );
+
+        // Subscriptions take them as properties of their EventDestinations.
+        let (sent, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let sender = ChannelSender {
+            sent,
+            accept: Arc::new(AtomicBool::new(true)),
+        };
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .event_sender(sender)
+            .build();
+        let uri = "/redfish/v1/EventService/Subscriptions";
+        let req = json!({
+            "Destination": "https://receiver.example",
+            "Protocol": "Redfish",
+            "MessageIds": "ResourceEvent.1.1.ResourceChanged",
+        });
+        let response = post(&mut app, uri, req, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let req = json!({
+            "Destination": "https://receiver.example",
+            "Protocol": "Redfish",
+            "RegistryPrefixes": ["ResourceEvent"],
+            "ResourceTypes": ["ManagerAccount"],
+            "OriginResources": [{"@odata.id": "/redfish/v1/AccountService/Accounts/admin"}],
+        });
+        let response = post(&mut app, uri, req, &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = get_response_json(response).await;
+        assert_eq!(body["ResourceTypes"], json!(["ManagerAccount"]));
+        assert_eq!(
+            body["OriginResources"],
+            json!([{"@odata.id": "/redfish/v1/AccountService/Accounts/admin"}])
+        );
+        assert_eq!(body["MessageIds"], json!([]));
+
+        event_service.log_entry_added(
+            "/redfish/v1/Managers/BMC/LogServices/Log",
+            "/redfish/v1/Managers/BMC/LogServices/Log/Entries/3",
+        );
+        event_service.resource_changed("/redfish/v1/AccountService/Roles/Administrator");
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let (_, event) = received.recv().await.unwrap();
+        assert_eq!(event["Events"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceChanged"
+        );
+        assert_eq!(
+            event["Events"][0]["OriginOfCondition"],
+            json!({"@odata.id": "/redfish/v1/AccountService/Accounts/admin"})
+        );
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn interop_profile() {
+        assert!(InteropProfile::from_json("{}").is_err());
+        let profile = InteropProfile::from_json(
+            r#"{
+                "ProfileName": "Test",
+                "ProfileVersion": "1.0.0",
+                "Resources": {
+                    "ComputerSystem": {
+                        "MinVersion": "1.1.0",
+                        "PropertyRequirements": {
+                            "PowerState": {"Comparison": "AnyOf", "Values": ["On", "Off"]},
+                            "SerialNumber": {},
+                            "AssetTag": {"ReadRequirement": "Recommended"},
+                            "Oem": {"Comparison": "Absent"},
+                            "Boot": {
+                                "PropertyRequirements": {
+                                    "BootSourceOverrideTarget@Redfish.AllowableValues": {
+                                        "Comparison": "AllOf",
+                                        "Values": ["Pxe", "Hdd"]
+                                    }
+                                }
+                            },
+                            "ProcessorSummary": {
+                                "ReadRequirement": "Optional",
+                                "PropertyRequirements": {
+                                    "Count": {"Comparison": "GreaterThanOrEqual", "Values": [2]}
+                                }
+                            }
+                        }
+                    },
+                    "Chassis": {},
+                    "Manager": {"ReadRequirement": "Recommended"}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let tree = TreeBuilder::new()
+            .resource(
+                "/redfish/v1",
+                "Root Service",
+                ResourceType::new_dmtf(String::from("ServiceRoot"), ResourceSchemaVersion::new(1, 0, 0)),
+                allowed_methods,
+                json!({"Systems": [{"@odata.id": "/redfish/v1/Systems/1"}, {"@odata.id": "/redfish/v1/Systems/2"}]}),
+            )
+            .resource(
+                "/redfish/v1/Systems/1",
+                "System",
+                ResourceType::new_dmtf(String::from("ComputerSystem"), ResourceSchemaVersion::new(1, 20, 0)),
+                allowed_methods,
+                json!({
+                    "PowerState": "On",
+                    "SerialNumber": "1234",
+                    "Boot": {"BootSourceOverrideTarget@Redfish.AllowableValues": ["None", "Pxe", "Hdd"]},
+                    "ProcessorSummary": {"Count": 2},
+                }),
+            )
+            .resource(
+                "/redfish/v1/Systems/2",
+                "System",
+                ResourceType::new_dmtf(String::from("ComputerSystem"), ResourceSchemaVersion::new(1, 0, 0)),
+                allowed_methods,
+                json!({
+                    "PowerState": "Paused",
+                    "Oem": {},
+                    "Boot": {"BootSourceOverrideTarget@Redfish.AllowableValues": ["Pxe"]},
+                    "ProcessorSummary": {"Count": 1},
+                }),
+            )
+            .build();
+
+        let report = check_profile(&tree, Some("admin"), &profile).await;
+        assert_eq!(report.profile, "Test 1.0.0");
+        assert_eq!(
+            report.checked,
+            vec!["/redfish/v1/Systems/1", "/redfish/v1/Systems/2"]
+        );
+        let uri = String::from("/redfish/v1/Systems/2");
+        assert_eq!(
+            report.issues,
+            vec![
+                ConformanceIssue::VersionTooOld {
+                    uri: uri.clone(),
+                    version: String::from("1.0.0"),
+                    min_version: String::from("1.1.0"),
+                },
+                ConformanceIssue::UnexpectedValue {
+                    uri: uri.clone(),
+                    property: String::from("Boot/BootSourceOverrideTarget@Redfish.AllowableValues"),
+                    value: json!(["Pxe"]),
+                },
+                ConformanceIssue::ForbiddenProperty {
+                    uri: uri.clone(),
+                    property: String::from("Oem")
+                },
+                ConformanceIssue::UnexpectedValue {
+                    uri: uri.clone(),
+                    property: String::from("PowerState"),
+                    value: json!("Paused"),
+                },
+                ConformanceIssue::UnexpectedValue {
+                    uri: uri.clone(),
+                    property: String::from("ProcessorSummary/Count"),
+                    value: json!(1),
+                },
+                ConformanceIssue::MissingProperty {
+                    uri: uri.clone(),
+                    property: String::from("SerialNumber")
+                },
+                ConformanceIssue::MissingResource(String::from("Chassis")),
+            ]
+        );
+        assert!(!report.is_conformant());
+        assert_eq!(
+            report.to_json()["Issues"][6],
+            json!({"Issue": "MissingResource", "ResourceType": "Chassis"})
+        );
+    }
+
+    // Patches and deletes take long, so carry on as tasks.
+    struct SlowTree {
+        tree: MockTree,
+        tasks: TaskService,
+        started: Arc<Mutex<Option<Task>>>,
+    }
+
+    #[async_trait]
+    impl Tree for SlowTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        async fn patch_deferred(
+            &mut self,
+            _uri: &str,
+            _request_body: &Map<String, Value>,
+            _username: Option<&str>,
+        ) -> Result<Outcome<&dyn Node>, Error> {
+            let task = self.tasks.start("Account Update");
+            *self.started.lock().unwrap() = Some(task.clone());
+            Ok(Outcome::Async(task))
+        }
+
+        async fn delete_deferred(
+            &mut self,
+            _uri: &str,
+            _username: Option<&str>,
+        ) -> Result<Outcome<()>, Error> {
+            Ok(Outcome::Async(self.tasks.start("Account Removal")))
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn task_service() {
+        // Without a TaskService, its URIs are left to the tree.
+        let mut app = app();
+        let response = get(
+            &mut app,
+            "/redfish/v1/TaskService",
+            &admin_admin_basic_auth(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let tasks = TaskService::new();
+        let event_service = EventService::new();
+        let mut events = event_service.subscribe();
+        let started = Arc::new(Mutex::new(None));
+        let tree = SlowTree {
+            tree: get_mock_tree(),
+            tasks: tasks.clone(),
+            started: started.clone(),
+        };
+        let mut app = AppBuilder::new(tree)
+            .event_service(event_service)
+            .task_service(tasks)
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        let response = get(&mut app, "/redfish/v1/TaskService", &Auth::None).await;
+        validate_unauthorized(&response);
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["@odata.type"], "#TaskService.v1_2_0.TaskService");
+        assert_eq!(
+            body["Tasks"],
+            json!({"@odata.id": "/redfish/v1/TaskService/Tasks"})
+        );
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService/Tasks",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members"], json!([]));
+
+        let uri = "/redfish/v1/AccountService/Accounts/admin";
+        let response = patch(&mut app, uri, json!({"Password": "new"}), &auth).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            get_header(&response, "location"),
+            "/redfish/v1/TaskService/TaskMonitors/1"
+        );
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.id"], "/redfish/v1/TaskService/Tasks/1");
+        assert_eq!(body["Name"], "Account Update");
+        assert_eq!(body["TaskState"], "Running");
+        assert_eq!(body["PercentComplete"], 0);
+        let task = started.lock().unwrap().clone().unwrap();
+        assert_eq!(task.get_uri(), "/redfish/v1/TaskService/Tasks/1");
+
+        let monitor = "/redfish/v1/TaskService/TaskMonitors/1";
+        let response = get(&mut app, monitor, &Auth::None).await;
+        validate_unauthorized(&response);
+        let body = jget(&mut app, monitor, StatusCode::ACCEPTED, &auth, &[]).await;
+        assert_eq!(body["TaskState"], "Running");
+        task.set_percent_complete(150);
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService/Tasks/1",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["PercentComplete"], 100);
+        assert!(body.get("EndTime").is_none());
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService/Tasks",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/TaskService/Tasks/1"}])
+        );
+        let response = get(&mut app, "/redfish/v1/TaskService/Tasks/2", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = get(&mut app, "/redfish/v1/TaskService/TaskMonitors/2", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Once complete, the monitor gives what the task completed with.
+        task.complete(Health::OK, Some(json!({"Result": "Done"})));
+        task.set_percent_complete(10);
+        let body = jget(&mut app, monitor, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body, json!({"Result": "Done"}));
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService/Tasks/1",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["TaskState"], "Completed");
+        assert_eq!(body["TaskStatus"], "OK");
+        assert_eq!(body["PercentComplete"], 100);
+        assert!(body["EndTime"].is_string());
+
+        let ids: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| String::from(event.get_records()[0].get_message_id()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "TaskEvent.1.0.TaskStarted",
+                "TaskEvent.1.0.TaskProgressChanged",
+                "TaskEvent.1.0.TaskCompletedOK"
+            ]
+        );
+
+        // Deleting carries on as a task too, and nothing is removed meanwhile.
+        let response = delete(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            get_header(&response, "location"),
+            "/redfish/v1/TaskService/TaskMonitors/2"
+        );
+        let body = get_response_json(response).await;
+        assert_eq!(body["Name"], "Account Removal");
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            events.try_recv().unwrap().get_records()[0].get_message_id(),
+            "TaskEvent.1.0.TaskStarted"
+        );
+        assert!(events.try_recv().is_err());
+
+        let response = get(&mut app, "/redfish/v1/$metadata", &auth).await;
+        let body = String::from_utf8(
+            hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(body.contains("Task_v1.xml"));
+        assert!(body.contains("TaskCollection_v1.xml"));
+    }
+
+    #[tokio::test]
+    async fn deferred_session_delete() {
+        let tasks = TaskService::new();
+        let tree = SlowTree {
+            tree: get_mock_tree(),
+            tasks: tasks.clone(),
+            started: Arc::new(Mutex::new(None)),
+        };
+        let mut app = AppBuilder::new(tree).task_service(tasks).build();
+        let (auth, session_uri) = login(&mut app).await;
+
+        // Logging out carries on as a task, and the service only forgets the session once
+        // the tree has deleted it, so the token keeps working meanwhile.
+        let response = delete(&mut app, &session_uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let monitor = get_header(&response, "location").to_string();
+        let body = jget(&mut app, &session_uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["UserName"], "Obiwan");
+        let response = get(&mut app, &monitor, &auth).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body = get_response_json(response).await;
+        assert_eq!(body["Name"], "Account Removal");
+        assert_eq!(body["TaskState"], "Running");
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[tokio::test]
+    async fn fault_injection() {
+        assert!(FaultScenario::from_json(r#"{"Faults": [{"Kind": "Bogus"}]}"#).is_err());
+        assert!(FaultScenario::from_json(r#"{"Faults": [{"Kind": "Delay"}]}"#).is_err());
+        assert!(FaultScenario::from_json(
+            r#"{"Faults": [{"Kind": "DropEtag", "Probability": 2}]}"#
+        )
+        .is_err());
+        let scenario = FaultScenario::from_json(
+            r#"{"Faults": [
+                {"Kind": "InternalError", "Uri": "/redfish/v1/SessionService", "Methods": ["GET"]},
+                {"Kind": "InternalError", "Probability": 0},
+                {"Kind": "Delay", "DelayMilliseconds": 50, "Uri": "/redfish/v1/AccountService"},
+                {"Kind": "DropEtag", "Uri": "/redfish/v1/AccountService/Accounts"},
+                {"Kind": "ExpireSession", "Uri": "/redfish/v1/AccountService/Roles"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.faults.len(), 5);
+        assert_eq!(
+            scenario.faults[2].kind,
+            FaultKind::Delay(Duration::from_millis(50))
+        );
+        assert_eq!(scenario.faults[1].probability, 0.0);
+        assert_eq!(scenario.faults[0].probability, 1.0);
+
+        let mut app = AppBuilder::new(get_mock_tree())
+            .fault_scenario(scenario)
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        // Faults only apply to the URIs and methods they are for.
+        let response = get(&mut app, "/redfish/v1/SessionService", &auth).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let response = get(&mut app, "/redfish/v1/SessionService/Sessions", &auth).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let response = get(&mut app, "/redfish/v1/SessionServices", &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = patch(&mut app, "/redfish/v1/SessionService", json!({}), &auth).await;
+        assert_ne!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let start = Instant::now();
+        let response = get(&mut app, "/redfish/v1/AccountService/Accounts/admin", &auth).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("etag").is_none());
+        let response = get(
+            &mut app,
+            "/redfish/v1/AccountService/Roles/Administrator",
+            &auth,
+        )
+        .await;
+        assert!(response.headers().get("etag").is_some());
+        let start = Instant::now();
+        get(&mut app, "/redfish", &Auth::None).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // Sessions look expired, but logging in still works.
+        let (token, _) = login(&mut app).await;
+        let response = get(
+            &mut app,
+            "/redfish/v1/AccountService/Roles/Administrator",
+            &token,
+        )
+        .await;
+        validate_unauthorized(&response);
+        let response = get(
+            &mut app,
+            "/redfish/v1/AccountService/Roles/Administrator",
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn respond_async() {
+        async fn patch_async(app: &mut NormalizePath<Router>, uri: &str, req: Value) -> Response {
+            let body = Body::from(serde_json::to_vec(&req).unwrap());
+            let mut req = Request::patch(uri)
+                .header("Content-Type", "application/json")
+                .header("Prefer", "respond-async");
+            add_auth_headers(&mut req, &admin_admin_basic_auth());
+            let req = req.body(body).unwrap();
+            app.ready().await.unwrap().call(req).await.unwrap()
+        }
+        let uri = "/redfish/v1/SessionService";
+        let req = json!({"SessionTimeout": 600});
+
+        // Without a TaskService, the preference is not applied.
+        let mut app = app();
+        let response = patch_async(&mut app, uri, req.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("preference-applied").is_none());
+
+        let mut app = AppBuilder::new(get_mock_tree())
+            .task_service(TaskService::new())
+            .build();
+        let auth = admin_admin_basic_auth();
+        let response = patch_async(&mut app, uri, req).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(get_header(&response, "preference-applied"), "respond-async");
+        let monitor = get_header(&response, "location").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["Name"], "PATCH /redfish/v1/SessionService");
+        assert_eq!(body["TaskState"], "Completed");
+        let body = jget(&mut app, &monitor, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["@odata.id"], "/redfish/v1/SessionService");
+        assert_eq!(body["SessionTimeout"], 600);
+
+        // Failures are responded to as they are.
+        let response = patch_async(&mut app, "/redfish/v1/SessionServices", json!({})).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get("preference-applied").is_none());
+
+        // Logging in is not carried out as a task, so that the client gets the token.
+        let data = json!({"UserName": "Obiwan", "Password": "n/a"});
+        let request = Request::post("/redfish/v1/SessionService/Sessions")
+            .header("Content-Type", "application/json")
+            .header("Prefer", "respond-async")
+            .body(Body::from(serde_json::to_vec(&data).unwrap()))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get("x-auth-token").is_some());
+
+        // Operations the tree defers to tasks itself are not tasks twice.
+        let tasks = TaskService::new();
+        let started = Arc::new(Mutex::new(None));
+        let tree = SlowTree {
+            tree: get_mock_tree(),
+            tasks: tasks.clone(),
+            started,
+        };
+        let mut app = AppBuilder::new(tree).task_service(tasks).build();
+        let response = patch_async(&mut app, uri, json!({"SessionTimeout": 600})).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(get_header(&response, "preference-applied"), "respond-async");
+        let body = get_response_json(response).await;
+        assert_eq!(body["Name"], "Account Update");
+        let body = jget(
+            &mut app,
+            "/redfish/v1/TaskService/Tasks",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["Members@odata.count"], 1);
+    }
+
+    // Accounts have the ChangePassword action, which records the new passwords.
+    struct ActionTree {
+        tree: MockTree,
+        passwords: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Tree for ActionTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        async fn action(
+            &mut self,
+            uri: &str,
+            action: &str,
+            request_body: &Map<String, Value>,
+            _username: Option<&str>,
+        ) -> Result<Outcome<Option<Value>>, Error> {
+            if !uri.starts_with("/redfish/v1/AccountService/Accounts/")
+                || action != "ManagerAccount.ChangePassword"
+            {
+                return Err(Error::ActionNotSupported(String::from(action)));
+            }
+            match request_body.get("NewPassword").and_then(|p| p.as_str()) {
+                Some(password) => {
+                    self.passwords.lock().unwrap().push(String::from(password));
+                    Ok(Outcome::Done(None))
+                }
+                None => Err(Error::BadRequest),
+            }
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn actions() {
+        let target =
+            "/redfish/v1/AccountService/Accounts/admin/Actions/ManagerAccount.ChangePassword";
+        let data = json!({"NewPassword": "hunter2"});
+
+        // Resources have no actions unless the tree carries them out.
+        let mut app = app();
+        let response = post(&mut app, target, data.clone(), &admin_admin_basic_auth()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.ActionNotSupported");
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["Message"],
+            "The action ManagerAccount.ChangePassword is not supported by the resource."
+        );
+
+        let passwords = Arc::new(Mutex::new(Vec::new()));
+        let tree = ActionTree {
+            tree: get_mock_tree(),
+            passwords: passwords.clone(),
+        };
+        let mut app = AppBuilder::new(tree).build();
+        let auth = admin_admin_basic_auth();
+
+        let response = post(&mut app, target, data.clone(), &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(*passwords.lock().unwrap(), vec![String::from("hunter2")]);
+
+        // Targets are checked the way the resource is.
+        let response = post(&mut app, target, data.clone(), &Auth::None).await;
+        validate_unauthorized(&response);
+        let response = post(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts/nobody/Actions/ManagerAccount.ChangePassword",
+            data.clone(),
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = post(
+            &mut app,
+            "/redfish/v1/AccountService/Accounts/admin/Actions/ManagerAccount.Reset",
+            data,
+            &auth,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post(&mut app, target, json!({}), &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(passwords.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tree_builder_behaviors() {
+        let system = "/redfish/v1/Systems/1";
+        let chassis = "/redfish/v1/Chassis/1";
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: true,
+            post: false,
+        };
+        let tree = TreeBuilder::new()
+            .resource(
+                system,
+                "System",
+                ResourceType::new_dmtf(
+                    String::from("ComputerSystem"),
+                    ResourceSchemaVersion::new(1, 20, 0),
+                ),
+                allowed_methods,
+                json!({
+                    "PowerState": "On",
+                    "AssetTag": "",
+                    "Actions": {
+                        "#ComputerSystem.Reset": {
+                            "target": "/redfish/v1/Systems/1/Actions/ComputerSystem.Reset"
+                        },
+                        "#ComputerSystem.SetDefaultBootOrder": {
+                            "target": "/redfish/v1/Systems/1/Actions/ComputerSystem.SetDefaultBootOrder"
+                        },
+                    },
+                }),
+            )
+            .resource(
+                chassis,
+                "Chassis",
+                ResourceType::new_dmtf(
+                    String::from("Chassis"),
+                    ResourceSchemaVersion::new(1, 22, 0),
+                ),
+                allowed_methods,
+                json!({"PowerState": "On", "AssetTag": ""}),
+            )
+            // Resetting powers the system and its chassis off or on.
+            .on_action(
+                system,
+                "ComputerSystem.Reset",
+                move |resources, parameters| {
+                    let state = match parameters.get("ResetType").and_then(|t| t.as_str()) {
+                        Some("ForceOff") => "Off",
+                        Some("On") => "On",
+                        _ => return Err(Error::BadRequest),
+                    };
+                    resources.set(system, "PowerState", json!(state))?;
+                    resources.set(chassis, "PowerState", json!(state))?;
+                    Ok(None)
+                },
+            )
+            // Responds with the order it set.
+            .on_action(system, "ComputerSystem.SetDefaultBootOrder", |_, _| {
+                Ok(Some(json!({"BootOrder": ["Pxe", "Hdd"]})))
+            })
+            // The asset tag of the system is that of its chassis.
+            .on_patch(system, move |resources, uri, request_body| {
+                if let Some(tag) = request_body.get("AssetTag") {
+                    resources.set(uri, "AssetTag", tag.clone())?;
+                    resources.set(chassis, "AssetTag", tag.clone())?;
+                }
+                Ok(())
+            })
+            .build();
+        let mut app = AppBuilder::new(tree).build();
+        let auth = admin_admin_basic_auth();
+
+        let target = "/redfish/v1/Systems/1/Actions/ComputerSystem.Reset";
+        let response = post(&mut app, target, json!({"ResetType": "ForceOff"}), &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let body = jget(&mut app, system, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["PowerState"], "Off");
+        let body = jget(&mut app, chassis, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["PowerState"], "Off");
+        let response = post(&mut app, target, json!({"ResetType": "Nmi"}), &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // Bodies actions respond with have the headers of any other response.
+        let target = "/redfish/v1/Systems/1/Actions/ComputerSystem.SetDefaultBootOrder";
+        let response = post(&mut app, target, json!({}), &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_header(&response, "OData-Version"), "4.0");
+        assert_eq!(get_header(&response, "Cache-Control"), "no-cache");
+        assert_eq!(get_header(&response, "Allow"), "POST");
+        let body = get_response_json(response).await;
+        assert_eq!(body["BootOrder"], json!(["Pxe", "Hdd"]));
+
+        // Only the actions given behaviors are supported.
+        let target = "/redfish/v1/Chassis/1/Actions/Chassis.Reset";
+        let response = post(&mut app, target, json!({"ResetType": "On"}), &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.ActionNotSupported");
+
+        let data = json!({"AssetTag": "rack-7"});
+        let response = patch(&mut app, system, data, &auth).await;
+        assert_eq!(get_response_json(response).await["AssetTag"], "rack-7");
+        let body = jget(&mut app, chassis, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["AssetTag"], "rack-7");
+
+        // Resources without a behavior are patched as usual.
+        let data = json!({"AssetTag": "rack-8"});
+        let response = patch(&mut app, chassis, data, &auth).await;
+        assert_eq!(get_response_json(response).await["AssetTag"], "rack-8");
+        let body = jget(&mut app, system, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["AssetTag"], "rack-7");
+    }
+
+    #[tokio::test]
+    async fn manual_clock() {
+        let clock = ManualClock::new();
+        let tasks = TaskService::new();
+        let events = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(AdminOnly)
+            .auth_throttle(Some(AuthThrottleConfig {
+                max_failures: 1,
+                initial_delay: Duration::ZERO,
+                max_delay: Duration::ZERO,
+                block_duration: Duration::from_secs(300),
+                failure_window: Duration::from_secs(600),
+            }))
+            .task_service(tasks.clone())
+            .event_service(events.clone())
+            .clock(clock.clone())
+            .build();
+        let client = SocketAddr::from(([10, 0, 0, 1], 40000));
+        let bad_auth = Auth::Basic(String::from("Basic YWRtaW46d3Jvbmc="));
+        let uri = "/redfish/v1/SessionService";
+
+        // Blocks last until the clock says they are over
+        let response = get_from(&mut app, uri, &bad_auth, client).await;
+        validate_unauthorized(&response);
+        clock.advance(Duration::from_secs(299));
+        let response = get_from(&mut app, uri, &admin_admin_basic_auth(), client).await;
+        validate_unauthorized(&response);
+        clock.advance(Duration::from_secs(1));
+        let response = get_from(&mut app, uri, &admin_admin_basic_auth(), client).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Tasks are timestamped by it
+        let start_time = clock.system_time();
+        let task = tasks.start("Firmware Update");
+        clock.advance(Duration::from_secs(3600));
+        task.complete(Health::OK, None);
+        let body = jget(
+            &mut app,
+            &task.get_uri(),
+            StatusCode::OK,
+            &admin_admin_basic_auth(),
+            &[],
+        )
+        .await;
+        assert_eq!(body["StartTime"], get_datetime_string(start_time));
+        assert_eq!(
+            body["EndTime"],
+            get_datetime_string(start_time + Duration::from_secs(3600))
+        );
+
+        // And so are events
+        let mut receiver = events.subscribe();
+        events.resource_created("/redfish/v1/Systems/1");
+        let event = receiver.recv().await.unwrap().to_json();
+        assert_eq!(
+            event["Events"][0]["EventTimestamp"],
+            get_datetime_string(clock.system_time())
+        );
+    }
+
+    #[tokio::test]
+    async fn metric_report_sse() {
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        let uri = "/redfish/v1/EventService/SSE?$filter=EventFormatType%20eq%20%27Alert%27";
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.QueryParameterValueError"
+        );
+
+        let uri = "/redfish/v1/EventService/SSE?$filter=EventFormatType%20eq%20%27MetricReport%27";
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let report = json!({
+            "@odata.id": "/redfish/v1/TelemetryService/MetricReports/CPUTemperature",
+            "@odata.type": "#MetricReport.v1_5_0.MetricReport",
+            "Id": "CPUTemperature",
+            "MetricReportDefinition": {
+                "@odata.id": "/redfish/v1/TelemetryService/MetricReportDefinitions/CPUTemperature"
+            },
+            "MetricValues": [{"MetricId": "Temperature", "MetricValue": "91"}],
+        });
+        event_service.send_metric_report(report.clone());
+        let chunk = hyper::body::HttpBody::data(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data:"))
+            .unwrap();
+        assert_eq!(serde_json::from_str::<Value>(data).unwrap(), report);
+        // Its Id follows that of the event it was sent after.
+        assert!(chunk.lines().any(|line| line == "id:2"));
+
+        // Streams for events do not get them.
+        let uri = "/redfish/v1/EventService/SSE?$filter=EventFormatType%20eq%20%27Event%27";
+        let response = get(&mut app, uri, &auth).await;
+        let mut body = response.into_body();
+        event_service.send_metric_report(report);
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let chunk = hyper::body::HttpBody::data(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data:"))
+            .unwrap();
+        let event: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(
+            event["Events"][0]["MessageId"],
+            "ResourceEvent.1.1.ResourceChanged"
+        );
+    }
+
+    #[tokio::test]
+    async fn include_origin_of_condition() {
+        let event_service = EventService::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        let uri = "/redfish/v1/EventService/SSE?includeoriginofcondition=maybe";
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let uri = "/redfish/v1/EventService/SSE?includeoriginofcondition=true";
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        let chunk = hyper::body::HttpBody::data(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data:"))
+            .unwrap();
+        let event: Value = serde_json::from_str(data).unwrap();
+        let origin = &event["Events"][0]["OriginOfCondition"];
+        assert_eq!(
+            origin["@odata.id"],
+            "/redfish/v1/AccountService/Accounts/admin"
+        );
+        assert_eq!(origin["UserName"], "admin");
+    }
+
+    #[tokio::test]
+    async fn event_coalescing() {
+        let event_service = EventService::new().with_coalescing_window(Duration::from_millis(50));
+        let mut app = AppBuilder::new(get_mock_tree())
+            .event_service(event_service.clone())
+            .build();
+        let auth = admin_admin_basic_auth();
+
+        let response = get(&mut app, "/redfish/v1/EventService/SSE", &auth).await;
+        let mut body = response.into_body();
+        for _ in 0..3 {
+            event_service.resource_changed("/redfish/v1/AccountService/Accounts/admin");
+        }
+        // Changes to resources that are then removed are not sent.
+        event_service.resource_changed("/redfish/v1/AccountService/Accounts/user");
+        event_service.resource_removed("/redfish/v1/AccountService/Accounts/user");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        event_service.resource_created("/redfish/v1/AccountService/Accounts/user");
+
+        let mut records = Vec::new();
+        while records.len() < 3 {
+            let chunk = hyper::body::HttpBody::data(&mut body)
+                .await
+                .unwrap()
+                .unwrap();
+            let chunk = std::str::from_utf8(&chunk).unwrap();
+            for data in chunk.lines().filter_map(|line| line.strip_prefix("data:")) {
+                let event: Value = serde_json::from_str(data).unwrap();
+                let record = &event["Events"][0];
+                records.push((
+                    record["MessageId"].as_str().unwrap().to_string(),
+                    record["OriginOfCondition"]["@odata.id"].clone(),
+                ));
+            }
+        }
+        assert_eq!(
+            records,
+            vec![
+                (
+                    String::from("ResourceEvent.1.1.ResourceRemoved"),
+                    json!("/redfish/v1/AccountService/Accounts/user")
+                ),
+                (
+                    String::from("ResourceEvent.1.1.ResourceChanged"),
+                    json!("/redfish/v1/AccountService/Accounts/admin")
+                ),
+                (
+                    String::from("ResourceEvent.1.1.ResourceCreated"),
+                    json!("/redfish/v1/AccountService/Accounts/user")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn session_timeout() {
+        let clock = ManualClock::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .clock(clock.clone())
+            .build();
+        let (auth, _) = login(&mut app).await;
+        let response = login_as(&mut app, "Leia").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let idle_auth = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let uri = "/redfish/v1/SessionService/Sessions";
+
+        // Using a session keeps it from timing out
+        clock.advance(Duration::from_secs(599));
+        let response = get(&mut app, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Those idle for the SessionTimeout expire, and are removed from the tree too
+        clock.advance(Duration::from_secs(1));
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/SessionService/Sessions/1"}])
+        );
+        let response = get(&mut app, uri, &idle_auth).await;
+        validate_unauthorized(&response);
+
+        clock.advance(Duration::from_secs(600));
+        let response = get(&mut app, uri, &auth).await;
+        validate_unauthorized(&response);
+    }
+
+    #[tokio::test]
+    async fn session_sweep() {
+        let clock = ManualClock::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .clock(clock.clone())
+            .session_sweep(Some(Duration::from_millis(10)))
+            .build();
+        let (_, session_uri) = login(&mut app).await;
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/SessionService/Sessions";
+
+        // Nothing expires before the SessionTimeout
+        clock.advance(Duration::from_secs(599));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["Members@odata.count"], 1);
+
+        // Then the session is removed without any token being presented
+        clock.advance(Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["Members"], json!([]));
+        let response = get(&mut app, &session_uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[derive(Clone, Default)]
+    struct ExternalProviders {
+        configured: Arc<Mutex<Vec<(ExternalAccountProviderType, ExternalAccountProvider)>>>,
+    }
+
+    #[async_trait]
+    impl AuthenticationProvider for ExternalProviders {
+        async fn authenticate(&self, _username: &str, _password: &str) -> bool {
+            true
+        }
+
+        async fn configure_external_provider(
+            &self,
+            provider_type: ExternalAccountProviderType,
+            provider: &ExternalAccountProvider,
+        ) {
+            self.configured
+                .lock()
+                .unwrap()
+                .push((provider_type, provider.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn external_account_providers() {
+        let providers = ExternalProviders::default();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(providers.clone())
+            .external_account_providers(&[
+                ExternalAccountProviderType::LDAP,
+                ExternalAccountProviderType::ActiveDirectory,
+            ])
+            .build();
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/AccountService";
+
+        let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["LDAP"]["ServiceEnabled"], false);
+        assert_eq!(body["ActiveDirectory"]["ServiceAddresses"], json!([]));
+        assert_eq!(
+            body["Roles"]["@odata.id"],
+            "/redfish/v1/AccountService/Roles"
+        );
+
+        let data = json!({"LDAP": {
+            "ServiceEnabled": true,
+            "ServiceAddresses": ["ldaps://ldap.example.com"],
+            "Authentication": {
+                "AuthenticationType": "UsernameAndPassword",
+                "Username": "cn=bmc,dc=example,dc=com",
+                "Password": "secret",
+            },
+            "RemoteRoleMapping": [{"RemoteGroup": "admins", "LocalRole": "Administrator"}],
+        }});
+        let response = patch(&mut app, uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["LDAP"]["ServiceAddresses"],
+            json!(["ldaps://ldap.example.com"])
+        );
+        assert_eq!(
+            body["LDAP"]["Authentication"]["Username"],
+            "cn=bmc,dc=example,dc=com"
+        );
+        assert_eq!(body["LDAP"]["Authentication"]["Password"], Value::Null);
+        assert_eq!(
+            body["LDAP"]["RemoteRoleMapping"],
+            json!([{"RemoteGroup": "admins", "RemoteUser": null, "LocalRole": "Administrator"}])
+        );
+        assert_eq!(body["ActiveDirectory"]["ServiceEnabled"], false);
+        {
+            let configured = providers.configured.lock().unwrap();
+            assert_eq!(configured.len(), 1);
+            assert_eq!(configured[0].0, ExternalAccountProviderType::LDAP);
+            assert!(configured[0].1.service_enabled);
+            assert_eq!(configured[0].1.password.as_deref(), Some("secret"));
+        }
+
+        // What is not mentioned is left as it was
+        let data = json!({"LDAP": {"ServiceEnabled": false}});
+        let body = get_response_json(patch(&mut app, uri, data, &auth).await).await;
+        assert_eq!(body["LDAP"]["ServiceEnabled"], false);
+        assert_eq!(
+            body["LDAP"]["ServiceAddresses"],
+            json!(["ldaps://ldap.example.com"])
+        );
+
+        // Patches of the wrong shape change nothing
+        let data = json!({"ActiveDirectory": {"ServiceAddresses": "ad.example.com"}});
+        let response = patch(&mut app, uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyValueTypeError"
+        );
+        let data = json!({"LDAP": {"RemoteRoleMapping": [{"RemoteGroup": "admins"}]}});
+        let response = patch(&mut app, uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(providers.configured.lock().unwrap().len(), 2);
+    }
+
+    #[derive(Clone, Default)]
+    struct Listeners {
+        configured: Arc<Mutex<Vec<(NetworkProtocol, ProtocolSettings)>>>,
+    }
+
+    impl NetworkRuntime for Listeners {
+        // SSH cannot be enabled.
+        fn configure(
+            &self,
+            protocol: NetworkProtocol,
+            settings: &ProtocolSettings,
+        ) -> Result<(), Error> {
+            if protocol == NetworkProtocol::SSH && settings.enabled {
+                return Err(Error::InternalError);
+            }
+            self.configured
+                .lock()
+                .unwrap()
+                .push((protocol, settings.clone()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn manager_network_protocol() {
+        let listeners = Listeners::default();
+        let manager = ManagerBuilder::new("BMC", listeners.clone())
+            .protocol(
+                NetworkProtocol::HTTPS,
+                ProtocolSettings {
+                    enabled: true,
+                    port: 443,
+                },
+            )
+            .protocol(
+                NetworkProtocol::SSH,
+                ProtocolSettings {
+                    enabled: false,
+                    port: 22,
+                },
+            );
+        let uri = format!("{}/NetworkProtocol", manager.get_uri());
+        let tree = manager.add_to(TreeBuilder::new()).build();
+        let mut app = AppBuilder::new(tree).build();
+        let auth = admin_admin_basic_auth();
+
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Managers/BMC",
+            StatusCode::OK,
+            &auth,
+            &[],
+        )
+        .await;
+        assert_eq!(body["ManagerType"], "BMC");
+        assert_eq!(body["NetworkProtocol"]["@odata.id"], uri);
+        let body = jget(&mut app, &uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["HTTPS"], json!({"ProtocolEnabled": true, "Port": 443}));
+
+        // PATCHes call back into the runtime
+        let data = json!({"HTTPS": {"Port": 8443}, "HostName": "bmc"});
+        let response = patch(&mut app, &uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["HTTPS"],
+            json!({"ProtocolEnabled": true, "Port": 8443})
+        );
+        assert_eq!(body["HostName"], "bmc");
+        assert_eq!(
+            *listeners.configured.lock().unwrap(),
+            vec![(
+                NetworkProtocol::HTTPS,
+                ProtocolSettings {
+                    enabled: true,
+                    port: 8443
+                }
+            )]
+        );
+
+        // Those it refuses, and those of the wrong type, are not applied
+        let data = json!({"SSH": {"ProtocolEnabled": true}});
+        let response = patch(&mut app, &uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let data = json!({"HTTPS": {"Port": 70000}});
+        let response = patch(&mut app, &uri, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = jget(&mut app, &uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["SSH"], json!({"ProtocolEnabled": false, "Port": 22}));
+        assert_eq!(body["HTTPS"]["Port"], 8443);
+        assert_eq!(listeners.configured.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shared_session_store() {
+        // Like two processes whose sessions are kept in the same database
+        let store = MemorySessionStore::new();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_manager(SessionManager::with_store(store.clone()))
+            .build();
+        let mut other_app = AppBuilder::new(get_mock_tree())
+            .session_manager(SessionManager::with_store(store.clone()))
+            .build();
+
+        let (auth, session_uri) = login(&mut app).await;
+        let sessions = store.list();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].uri, session_uri);
+        assert_eq!(sessions[0].username, "Obiwan");
+
+        let response = get(&mut other_app, "/redfish/v1/SessionService", &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = delete(&mut app, &session_uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(store.list().is_empty());
+        let response = get(&mut other_app, "/redfish/v1/SessionService", &auth).await;
+        validate_unauthorized(&response);
+    }
+
+    #[tokio::test]
+    async fn file_session_store() {
+        let path = std::env::temp_dir().join(format!("sessions-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = FileSessionStore::open(&path).unwrap();
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_manager(SessionManager::with_store(store))
+            .build();
+        let (auth, session_uri) = login(&mut app).await;
+        let token = match &auth {
+            Auth::Token(token) => token.clone(),
+            _ => panic!("not logged in with a token"),
+        };
+        // The file is written in the background
+        let data = loop {
+            match std::fs::read_to_string(&path) {
+                Ok(data) if data.contains(&session_uri) => break data,
+                _ => tokio::time::sleep(Duration::from_millis(1)).await,
+            }
+        };
+        assert!(!data.contains(&token));
+
+        // As after a restart
+        let store = FileSessionStore::open(&path).unwrap();
+        assert_eq!(store.list()[0].username, "Obiwan");
+        let mut app = AppBuilder::new(get_mock_tree())
+            .session_manager(SessionManager::with_store(store))
+            .build();
+        // Activity alone is not written on every request.
+        std::fs::remove_file(&path).unwrap();
+        let response = get(&mut app, "/redfish/v1/SessionService", &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!path.exists());
+        let response = get(
+            &mut app,
+            "/redfish/v1/SessionService",
+            &Auth::Token(String::from("1234")),
+        )
+        .await;
+        validate_unauthorized(&response);
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(FileSessionStore::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Only admin may invoke the actions of its resources.
+    struct AdminActionsTree {
+        tree: StaticTree,
+    }
+
+    #[async_trait]
+    impl Tree for AdminActionsTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        async fn action(
+            &mut self,
+            uri: &str,
+            action: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<Outcome<Option<Value>>, Error> {
+            self.tree.action(uri, action, request_body, username).await
+        }
+
+        fn can_invoke_action(&self, _uri: &str, _action: &str, username: Option<&str>) -> bool {
+            username == Some("admin")
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn action_privileges() {
+        let system = "/redfish/v1/Systems/1";
+        let target = "/redfish/v1/Systems/1/Actions/ComputerSystem.Reset";
+        let get_tree = || {
+            let tree = TreeBuilder::new()
+                .resource(
+                    system,
+                    "System",
+                    ResourceType::new_dmtf(
+                        String::from("ComputerSystem"),
+                        ResourceSchemaVersion::new(1, 20, 0),
+                    ),
+                    AllowedMethods {
+                        delete: false,
+                        get: true,
+                        patch: false,
+                        post: false,
+                    },
+                    json!({
+                        "PowerState": "On",
+                        "Actions": {
+                            "#ComputerSystem.Reset": {
+                                "target": target,
+                                "ResetType@Redfish.AllowableValues": ["On", "ForceOff"],
+                            },
+                            "#ComputerSystem.SetDefaultBootOrder": {
+                                "target": "/redfish/v1/Systems/1/Actions/ComputerSystem.SetDefaultBootOrder",
+                            },
+                            "Oem": {},
+                        },
+                    }),
+                )
+                .on_action(system, "ComputerSystem.Reset", move |resources, _| {
+                    resources.set(system, "PowerState", json!("Off"))?;
+                    Ok(None)
+                })
+                .build();
+            AdminActionsTree { tree }
+        };
+        let operator = Auth::Basic(String::from("Basic b3BlcmF0b3I6b3BlcmF0b3I="));
+        let data = json!({"ResetType": "ForceOff"});
+
+        // Those who cannot invoke them do not see them, and are refused if they try anyway.
+        let mut app = AppBuilder::new(get_tree()).build();
+        let body = jget(&mut app, system, StatusCode::OK, &operator, &[]).await;
+        assert_eq!(body["Actions"], json!({"Oem": {}}));
+        let response = post(&mut app, target, data.clone(), &operator).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.InsufficientPrivilege");
+        let body = jget(&mut app, system, StatusCode::OK, &operator, &[]).await;
+        assert_eq!(body["PowerState"], "On");
+
+        let auth = admin_admin_basic_auth();
+        let body = jget(&mut app, system, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(
+            body["Actions"]["#ComputerSystem.Reset"]["ResetType@Redfish.AllowableValues"],
+            json!(["On", "ForceOff"])
+        );
+        assert!(body["Actions"]["#ComputerSystem.SetDefaultBootOrder"].is_object());
+        let response = post(&mut app, target, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Or see those with parameters as having nothing they may invoke them with.
+        let mut app = AppBuilder::new(get_tree())
+            .action_visibility(ActionVisibility::Annotated)
+            .build();
+        let body = jget(&mut app, system, StatusCode::OK, &operator, &[]).await;
+        assert_eq!(
+            body["Actions"],
+            json!({
+                "#ComputerSystem.Reset": {
+                    "target": target,
+                    "ResetType@Redfish.AllowableValues": [],
+                },
+                "Oem": {},
+            })
+        );
+    }
+
+    // Gives admin every privilege, Obiwan those of an operator, and anyone else only Login.
+    struct Roles;
+
+    #[async_trait]
+    impl AuthenticationProvider for Roles {
+        async fn authenticate(&self, _username: &str, _password: &str) -> bool {
+            true
+        }
+
+        async fn get_privileges(&self, username: &str) -> Option<Vec<String>> {
+            let privileges = match username {
+                "admin" => vec![
+                    "Login",
+                    "ConfigureManager",
+                    "ConfigureUsers",
+                    "ConfigureComponents",
+                ],
+                "Obiwan" => vec!["Login", "ConfigureComponents", "ConfigureSelf"],
+                _ => vec!["Login"],
+            };
+            Some(privileges.into_iter().map(String::from).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn task_readers_and_retention() {
+        async fn patch_async(app: &mut NormalizePath<Router>, auth: &Auth) -> String {
+            let body = Body::from(serde_json::to_vec(&json!({"SessionTimeout": 600})).unwrap());
+            let mut req = Request::patch("/redfish/v1/SessionService")
+                .header("Content-Type", "application/json")
+                .header("Prefer", "respond-async");
+            add_auth_headers(&mut req, auth);
+            let req = req.body(body).unwrap();
+            let response = app.ready().await.unwrap().call(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            get_header(&response, "location").to_string()
+        }
+        let clock = ManualClock::new();
+        let tasks = TaskService::new()
+            .with_max_completed_tasks(2)
+            .with_completed_task_ttl(Duration::from_secs(60));
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(Roles)
+            .privilege_registry(None)
+            .task_service(tasks)
+            .clock(clock.clone())
+            .build();
+        let admin = admin_admin_basic_auth();
+        // Obiwan:x and Leia:x
+        let obiwan = Auth::Basic(String::from("Basic T2Jpd2FuOng="));
+        let leia = Auth::Basic(String::from("Basic TGVpYTp4"));
+
+        // Tasks are read by those who started them, and those who configure the manager
+        let monitor = patch_async(&mut app, &obiwan).await;
+        let task = "/redfish/v1/TaskService/Tasks/1";
+        for uri in [&monitor, task] {
+            jget(&mut app, uri, StatusCode::OK, &obiwan, &[]).await;
+            jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+            let response = get(&mut app, uri, &leia).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        let uri = "/redfish/v1/TaskService/Tasks";
+        let body = jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(body["Members"], json!([]));
+        let body = jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["Members@odata.count"], 1);
+
+        // Only so many completed tasks are kept, and for only so long
+        patch_async(&mut app, &admin).await;
+        clock.advance(Duration::from_secs(30));
+        patch_async(&mut app, &admin).await;
+        let body = jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(
+            body["Members"],
+            json!([
+                {"@odata.id": "/redfish/v1/TaskService/Tasks/2"},
+                {"@odata.id": "/redfish/v1/TaskService/Tasks/3"},
+            ])
+        );
+        let response = get(&mut app, task, &admin).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        clock.advance(Duration::from_secs(45));
+        let body = jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/TaskService/Tasks/3"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn privilege_registry() {
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(Roles)
+            .build();
+        let admin = admin_admin_basic_auth();
+        let response = login_as(&mut app, "Obiwan").await;
+        let obiwan = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let obiwan_uri = get_header(&response, "Location").to_string();
+        let response = login_as(&mut app, "Leia").await;
+        let leia = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let leia_uri = get_header(&response, "Location").to_string();
+
+        // Reading the SessionService needs Login, and patching it ConfigureManager.
+        let uri = "/redfish/v1/SessionService";
+        jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        let response = patch(&mut app, uri, json!({"SessionTimeout": 600}), &obiwan).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = get_response_json(response).await;
+        assert_eq!(body["error"]["code"], "Base.1.16.InsufficientPrivilege");
+        let response = patch(&mut app, uri, json!({"SessionTimeout": 600}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Accounts can only be read by those who configure them.
+        let uri = "/redfish/v1/AccountService/Accounts/admin";
+        let response = get(&mut app, uri, &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        // Nor can queries of the collection read them for anyone else.
+        let uri = "/redfish/v1/AccountService/Accounts?$expand=.";
+        let body = jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(
+            body["Members"][0],
+            json!({"@odata.id": "/redfish/v1/AccountService/Accounts/admin"})
+        );
+        let body = jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["Members"][0]["UserName"], "admin");
+        let uri = "/redfish/v1/AccountService/Accounts?$filter=UserName%20eq%20'admin'";
+        let body = jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(body["Members"], json!([]));
+        let body = jget(&mut app, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["Members@odata.count"], 1);
+
+        // ConfigureSelf lets users end their own sessions, but not those of others.
+        let response = delete(&mut app, &leia_uri, &obiwan).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = delete(&mut app, &obiwan_uri, &obiwan).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // So too are requests for the resources the service serves itself.
+        let uri = "/redfish/v1/EventService";
+        jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        let response = patch(&mut app, uri, json!({"DeliveryRetryAttempts": 5}), &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = patch(&mut app, uri, json!({"DeliveryRetryAttempts": 5}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Uploads need the privileges to POST to the resource they are under.
+        let upload = |auth: &Auth| {
+            let mut req = Request::post("/redfish/v1/UpdateService/upload")
+                .header("Content-Type", "application/octet-stream");
+            add_auth_headers(&mut req, auth);
+            req.body(Body::from("image")).unwrap()
+        };
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(upload(&leia))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(upload(&admin))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Clients can read the registry the service authorizes requests by.
+        let uri = "/redfish/v1/Registries/Redfish_1.5.0_PrivilegeRegistry";
+        let body = jget(&mut app, uri, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(body["Registry"], "Redfish_1.5.0_PrivilegeRegistry");
+        let location = body["Location"][0]["Uri"].as_str().unwrap().to_string();
+        let body = jget(&mut app, &location, StatusCode::OK, &leia, &[]).await;
+        assert_eq!(
+            body["@odata.type"],
+            "#PrivilegeRegistry.v1_1_4.PrivilegeRegistry"
+        );
+        let response = get(&mut app, uri, &Auth::None).await;
+        validate_unauthorized(&response);
+        let target = PrivilegeTarget::new(
+            "ManagerAccount",
+            "/redfish/v1/AccountService/Accounts/admin",
+        );
+        assert_eq!(
+            redfish_axum::privilege_registry().get_required_privileges(&target, "PATCH"),
+            Some(&[vec![String::from("ConfigureUsers")]][..])
+        );
+
+        // Without a registry, what users may do is left to the tree.
+        let mut app = AppBuilder::new(get_mock_tree())
+            .authentication_provider(Roles)
+            .privilege_registry(None)
+            .build();
+        let reader = Auth::Basic(String::from("Basic b3BlcmF0b3I6b3BlcmF0b3I="));
+        let uri = "/redfish/v1/SessionService";
+        let response = patch(&mut app, uri, json!({"SessionTimeout": 600}), &reader).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let uri = "/redfish/v1/Registries/Redfish_1.5.0_PrivilegeRegistry";
+        let response = get(&mut app, uri, &reader).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    // Refuses asset tags longer than 8 characters, and booting from floppies.
+    struct SystemLimits;
+
+    #[async_trait]
+    impl PropertyValidator for SystemLimits {
+        async fn validate(
+            &self,
+            _uri: &str,
+            name: &str,
+            value: &Value,
+            _username: Option<&str>,
+        ) -> Option<Message> {
+            let (key, path, value) = match (name, value) {
+                ("AssetTag", Value::String(tag)) if tag.len() > 8 => {
+                    ("PropertyValueOutOfRange", "AssetTag", tag.clone())
+                }
+                ("Boot", boot) if boot["BootSourceOverrideTarget"] == "Floppy" => (
+                    "PropertyValueNotInList",
+                    "Boot/BootSourceOverrideTarget",
+                    String::from("Floppy"),
+                ),
+                _ => return None,
+            };
+            let message = Message::from_registry(
+                redfish_axum::base_registry(),
+                key,
+                ResourceSchemaVersion::new(1, 1, 2),
+                vec![value, String::from(path)],
+                vec![format!("#/{}", path)],
+            );
+            Some(message.unwrap())
+        }
+    }
+
+    // Records the properties it is given to validate, and accepts them all.
+    struct SeenProperties(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl PropertyValidator for SeenProperties {
+        async fn validate(
+            &self,
+            _uri: &str,
+            name: &str,
+            _value: &Value,
+            _username: Option<&str>,
+        ) -> Option<Message> {
+            self.0.lock().unwrap().push(String::from(name));
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn partial_patch() {
+        let system = "/redfish/v1/Systems/1";
+        let get_tree = || {
+            TreeBuilder::new()
+                .resource(
+                    system,
+                    "System",
+                    ResourceType::new_dmtf(
+                        String::from("ComputerSystem"),
+                        ResourceSchemaVersion::new(1, 20, 0),
+                    ),
+                    AllowedMethods {
+                        delete: false,
+                        get: true,
+                        patch: true,
+                        post: false,
+                    },
+                    json!({
+                        "AssetTag": "",
+                        "PowerState": "On",
+                        "Boot": {"BootSourceOverrideTarget": "None"},
+                    }),
+                )
+                .build()
+        };
+        let auth = admin_admin_basic_auth();
+        let data = json!({
+            "AssetTag": "rack-7",
+            "Boot": {"BootSourceOverrideTarget": "Floppy"},
+        });
+
+        // The properties the validators accept are applied, and the messages about the others
+        // annotate the resource.
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut app = AppBuilder::new(get_tree())
+            .property_validator(SystemLimits)
+            .property_validator(SeenProperties(seen.clone()))
+            .build();
+        let response = patch(&mut app, system, data.clone(), &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(body["AssetTag"], "rack-7");
+        assert_eq!(body["Boot"]["BootSourceOverrideTarget"], "None");
+        let info = &body["@Message.ExtendedInfo"];
+        assert_eq!(info.as_array().unwrap().len(), 1);
+        assert_eq!(info[0]["MessageId"], "Base.1.16.PropertyValueNotInList");
+        assert_eq!(*seen.lock().unwrap(), vec![String::from("AssetTag")]);
+        let bad_tag = json!({"AssetTag": "rack-7-slot-42"});
+        let response = patch(&mut app, system, bad_tag, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        let info = &body["error"]["@Message.ExtendedInfo"][0];
+        assert_eq!(info["MessageId"], "Base.1.16.PropertyValueOutOfRange");
+
+        // Or each annotates the property it is about, if the resource has it.
+        let mut app = AppBuilder::new(get_tree())
+            .property_validator(SystemLimits)
+            .partial_patch(PartialPatch::PerProperty)
+            .build();
+        let data = json!({
+            "AssetTag": "rack-7-slot-42",
+            "PowerState": "Off",
+            "Boot": {"BootSourceOverrideTarget": "Floppy"},
+        });
+        let response = patch(&mut app, system, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = get_response_json(response).await;
+        assert_eq!(body["PowerState"], "Off");
+        assert!(body.get("@Message.ExtendedInfo").is_none());
+        assert_eq!(
+            body["AssetTag@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyValueOutOfRange"
+        );
+        let boot = &body["Boot"];
+        assert_eq!(boot["BootSourceOverrideTarget"], "None");
+        assert_eq!(
+            boot["BootSourceOverrideTarget@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyValueNotInList"
+        );
+
+        // Or none are applied.
+        let mut app = AppBuilder::new(get_tree())
+            .property_validator(SystemLimits)
+            .partial_patch(PartialPatch::Refused)
+            .build();
+        let data = json!({
+            "AssetTag": "rack-7",
+            "Boot": {"BootSourceOverrideTarget": "Floppy"},
+        });
+        let response = patch(&mut app, system, data, &auth).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = jget(&mut app, system, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(body["AssetTag"], "");
+    }
+
+    #[tokio::test]
+    async fn account_lockout() {
+        let tree = TreeBuilder::new()
+            .resource(
+                "/redfish/v1/AccountService",
+                "Account Service",
+                ResourceType::new_dmtf(
+                    String::from("AccountService"),
+                    ResourceSchemaVersion::new(1, 12, 0),
+                ),
+                AllowedMethods {
+                    delete: false,
+                    get: true,
+                    patch: false,
+                    post: false,
+                },
+                json!({
+                    "AccountLockoutThreshold": 3,
+                    "AccountLockoutDuration": 60,
+                    "AccountLockoutCounterResetAfter": 30,
+                }),
+            )
+            .build();
+        let clock = ManualClock::new();
+        let lockouts = AccountLockouts::new();
+        let mut app = AppBuilder::new(tree)
+            .authentication_provider(AdminOnly)
+            .auth_throttle(None)
+            .account_lockouts(lockouts.clone())
+            .clock(clock.clone())
+            .build();
+        let uri = "/redfish/v1/AccountService";
+        let auth = admin_admin_basic_auth();
+        // admin:wrong
+        let bad_auth = Auth::Basic(String::from("Basic YWRtaW46d3Jvbmc="));
+
+        // Failures are counted, until the account authenticates
+        for _ in 0..2 {
+            let response = get(&mut app, uri, &bad_auth).await;
+            validate_unauthorized(&response);
+        }
+        assert_eq!(lockouts.get_failed_attempts("admin"), 2);
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+        assert_eq!(lockouts.get_failed_attempts("admin"), 0);
+
+        // Or until it goes long enough without failing
+        for _ in 0..2 {
+            let response = get(&mut app, uri, &bad_auth).await;
+            validate_unauthorized(&response);
+        }
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(lockouts.get_failed_attempts("admin"), 0);
+
+        // Reaching the threshold locks the account, even for the right credentials
+        for _ in 0..2 {
+            let response = get(&mut app, uri, &bad_auth).await;
+            validate_unauthorized(&response);
+        }
+        assert!(!lockouts.is_locked("admin"));
+        for auth in [&bad_auth, &auth] {
+            let response = get(&mut app, uri, auth).await;
+            validate_unauthorized(&response);
+            let body = get_response_json(response).await;
+            assert_eq!(body["error"]["code"], "Base.1.16.NoValidSession");
+        }
+        assert!(lockouts.is_locked("admin"));
+        assert_eq!(lockouts.get_failed_attempts("admin"), 3);
+
+        // Until the lockout has expired
+        clock.advance(Duration::from_secs(59));
+        let response = get(&mut app, uri, &auth).await;
+        validate_unauthorized(&response);
+        clock.advance(Duration::from_secs(1));
+        assert!(!lockouts.is_locked("admin"));
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+
+        // Or the account is unlocked
+        for _ in 0..3 {
+            let response = get(&mut app, uri, &bad_auth).await;
+            validate_unauthorized(&response);
+        }
+        assert!(lockouts.is_locked("admin"));
+        lockouts.unlock("admin");
+        jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+    }
+
+    #[tokio::test]
+    async fn document_caching() {
+        let mut service = AppBuilder::new(get_mock_tree())
+            .document_max_age(Some(Duration::from_secs(3600)))
+            .build();
+        let send = |uri: &str, headers: &[(&str, &str)]| {
+            let mut request = Request::get(uri);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        for uri in ["/redfish/v1/$metadata", "/redfish/v1/odata"] {
+            let response = service
+                .ready()
+                .await
+                .unwrap()
+                .call(send(uri, &[]))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(get_header(&response, "cache-control"), "max-age=3600");
+            let etag = get_header(&response, "etag").to_string();
+            let last_modified = get_header(&response, "last-modified").to_string();
+
+            // A current copy is not sent again, by either validator
+            for headers in [
+                [("if-none-match", etag.as_str())],
+                [("if-none-match", "\"other\", *")],
+                [("if-modified-since", last_modified.as_str())],
+            ] {
+                let request = send(uri, &headers);
+                let response = service.ready().await.unwrap().call(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+                assert_eq!(get_header(&response, "etag"), etag);
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                assert_eq!(body, "");
+            }
+
+            // An outdated one is
+            for headers in [
+                [("if-none-match", "\"other\"")],
+                [("if-modified-since", "Mon, 01 Jan 2001 00:00:00 GMT")],
+            ] {
+                let request = send(uri, &headers);
+                let response = service.ready().await.unwrap().call(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+        }
+
+        // Clients revalidate every time by default
+        let mut app = app();
+        let response = get(&mut app, "/redfish/v1/odata", &Auth::None).await;
+        assert_eq!(get_header(&response, "cache-control"), "no-cache");
+    }
+
+    #[tokio::test]
+    async fn session_service_disabled() {
+        let uri = "/redfish/v1/SessionService";
+        let admin = admin_admin_basic_auth();
+        for policy in [
+            DisabledSessionPolicy::Keep,
+            DisabledSessionPolicy::Suspend,
+            DisabledSessionPolicy::Terminate,
+        ] {
+            let mut app = AppBuilder::new(get_mock_tree())
+                .disabled_sessions(policy)
+                .build();
+            let (token, session_uri) = login(&mut app).await;
+            let response = patch(&mut app, uri, json!({"ServiceEnabled": false}), &admin).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // Logins are refused
+            let response = login_as(&mut app, "Obiwan").await;
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let body = get_response_json(response).await;
+            assert_eq!(
+                body["error"]["@Message.ExtendedInfo"][0]["MessageArgs"],
+                json!(["SessionService"])
+            );
+
+            // Existing sessions go on as the policy says
+            let response = get(&mut app, &session_uri, &token).await;
+            match policy {
+                DisabledSessionPolicy::Keep => assert_eq!(response.status(), StatusCode::OK),
+                _ => validate_unauthorized(&response),
+            }
+            let response = get(&mut app, &session_uri, &admin).await;
+            match policy {
+                DisabledSessionPolicy::Terminate => {
+                    assert_eq!(response.status(), StatusCode::NOT_FOUND)
+                }
+                _ => assert_eq!(response.status(), StatusCode::OK),
+            }
+
+            // Until the service is enabled again
+            let response = patch(&mut app, uri, json!({"ServiceEnabled": true}), &admin).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = get(&mut app, &session_uri, &token).await;
+            match policy {
+                DisabledSessionPolicy::Terminate => validate_unauthorized(&response),
+                _ => assert_eq!(response.status(), StatusCode::OK),
+            }
+            let response = login_as(&mut app, "Obiwan").await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+    }
+
+    #[tokio::test]
+    async fn local_accounts() {
+        let hashing = PasswordHashConfig {
+            iterations: NonZeroU32::new(1).unwrap(),
+            ..Default::default()
+        };
+        let accounts = LocalAccounts::new().with_password_hashing(hashing);
+        accounts
+            .add_account("admin", "password1", "Administrator")
+            .unwrap();
+        let tree = AccountServiceTree::new(get_mock_tree(), accounts.clone());
+        let mut service = AppBuilder::new(tree)
+            .authentication_provider(accounts.clone())
+            .build();
+        let collection_uri = "/redfish/v1/AccountService/Accounts";
+        let bob_uri = "/redfish/v1/AccountService/Accounts/bob";
+        // admin:password1
+        let admin = Auth::Basic(String::from("Basic YWRtaW46cGFzc3dvcmQx"));
+        // bob:password2
+        let bob = Auth::Basic(String::from("Basic Ym9iOnBhc3N3b3JkMg=="));
+
+        // Only authenticated users can read the accounts
+        let response = get(&mut service, collection_uri, &Auth::None).await;
+        validate_unauthorized(&response);
+        let body = jget(&mut service, collection_uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(
+            body["Members"],
+            json!([{"@odata.id": "/redfish/v1/AccountService/Accounts/admin"}])
+        );
+        let uri = "/redfish/v1/AccountService/Roles/ReadOnly";
+        let body = jget(&mut service, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(
+            body["AssignedPrivileges"],
+            json!(["Login", "ConfigureSelf"])
+        );
+        assert_eq!(body["IsPredefined"], true);
+
+        // Creating an account
+        let data = json!({"UserName": "bob", "Password": "password2", "RoleId": "ReadOnly"});
+        let response = post(&mut service, collection_uri, data.clone(), &admin).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(get_header(&response, "Location"), bob_uri);
+        let response = post(&mut service, collection_uri, data, &admin).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        for data in [
+            json!({"UserName": "carol", "Password": "short", "RoleId": "ReadOnly"}),
+            json!({"UserName": "carol", "Password": "password4", "RoleId": "Nobody"}),
+            json!({"UserName": "carol", "RoleId": "ReadOnly"}),
+        ] {
+            let response = post(&mut service, collection_uri, data, &admin).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+        let body = jget(&mut service, bob_uri, StatusCode::OK, &bob, &[]).await;
+        assert_eq!(body["RoleId"], "ReadOnly");
+        assert_eq!(body["Password"], Value::Null);
+        assert_eq!(body["Enabled"], true);
+
+        // Users without ConfigureUsers may only read their own account
+        let admin_uri = "/redfish/v1/AccountService/Accounts/admin";
+        let response = get(&mut service, admin_uri, &bob).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        jget(&mut service, admin_uri, StatusCode::OK, &admin, &[]).await;
+        // Users without accounts are refused as those with the wrong password are
+        // (nobody:password2 and bob:wrong)
+        for credentials in ["bm9ib2R5OnBhc3N3b3JkMg==", "Ym9iOndyb25n"] {
+            let auth = Auth::Basic(format!("Basic {}", credentials));
+            validate_unauthorized(&get(&mut service, bob_uri, &auth).await);
+        }
+
+        // Users without ConfigureUsers may only change their own password
+        let data = json!({"UserName": "carol", "Password": "password4", "RoleId": "ReadOnly"});
+        let response = post(&mut service, collection_uri, data, &bob).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let data = json!({"RoleId": "Administrator"});
+        let response = patch(&mut service, bob_uri, data, &bob).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let data = json!({"Password": "password3"});
+        let response = patch(&mut service, bob_uri, data, &bob).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        validate_unauthorized(&get(&mut service, bob_uri, &bob).await);
+        // bob:password3
+        let bob = Auth::Basic(String::from("Basic Ym9iOnBhc3N3b3JkMw=="));
+        jget(&mut service, bob_uri, StatusCode::OK, &bob, &[]).await;
+
+        // Disabled accounts lose their sessions, and cannot log in
+        let data = json!({"UserName": "bob", "Password": "password3"});
+        let uri = "/redfish/v1/SessionService/Sessions";
+        let response = post(&mut service, uri, data.clone(), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        jget(&mut service, bob_uri, StatusCode::OK, &token, &[]).await;
+        let response = patch(&mut service, bob_uri, json!({"Enabled": false}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        validate_unauthorized(&get(&mut service, bob_uri, &token).await);
+        let response = post(&mut service, uri, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Deleted accounts are gone
+        let response = delete(&mut service, bob_uri, &admin).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = get(&mut service, bob_uri, &admin).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(accounts.list().len(), 1);
+    }
+
+    // Records who each request it is given is from, as the service resolved them.
+    struct CurrentUserTree {
+        tree: MockTree,
+        seen: Arc<Mutex<Vec<Option<RequestUser>>>>,
+    }
+
+    #[async_trait]
+    impl Tree for CurrentUserTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.seen.lock().unwrap().push(current_user());
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.seen.lock().unwrap().push(current_user());
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn current_request_user() {
+        let hashing = PasswordHashConfig {
+            iterations: NonZeroU32::new(1).unwrap(),
+            ..Default::default()
+        };
+        let accounts = LocalAccounts::new().with_password_hashing(hashing);
+        accounts
+            .add_account("admin", "password1", "Administrator")
+            .unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tree = CurrentUserTree {
+            tree: get_mock_tree(),
+            seen: seen.clone(),
+        };
+        let mut service = AppBuilder::new(tree)
+            .authentication_provider(accounts)
+            .build();
+        // admin:password1
+        let admin = Auth::Basic(String::from("Basic YWRtaW46cGFzc3dvcmQx"));
+
+        // Trees are given the role and privileges of the user as well as who they are
+        let uri = "/redfish/v1/SessionService";
+        jget(&mut service, uri, StatusCode::OK, &admin, &[]).await;
+        let response = patch(&mut service, uri, json!({"SessionTimeout": 600}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let seen = std::mem::take(&mut *seen.lock().unwrap());
+        assert!(!seen.is_empty());
+        for user in seen {
+            let user = user.unwrap();
+            assert_eq!(user.username, "admin");
+            assert_eq!(user.role.as_deref(), Some("Administrator"));
+            assert!(user.has_privilege("ConfigureManager"));
+        }
+        // Only while handling requests
+        assert_eq!(current_user(), None);
+    }
+
+    // Logs clients in through an OEM collection of sessions, as well as the usual one.
+    struct OemSessionsTree {
+        tree: MockTree,
+    }
+
+    const OEM_SESSIONS_URI: &str = "/redfish/v1/Oem/Contoso/Sessions";
+
+    #[async_trait]
+    impl Tree for OemSessionsTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            // The service has checked the credentials of logins, which the mock only lets
+            // through for the usual collection.
+            let username = match uri == OEM_SESSIONS_URI {
+                true => request_body.get("UserName").and_then(Value::as_str),
+                false => username,
+            };
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn is_session_collection(&self, uri: &str) -> bool {
+            uri == OEM_SESSIONS_URI || uri == "/redfish/v1/SessionService/Sessions"
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn oem_session_collection() {
+        let mut tree = get_mock_tree();
+        tree.add_collection(Collection::new(
+            OEM_SESSIONS_URI,
+            String::from("SessionCollection"),
+            String::from("Contoso Session Collection"),
+            Vec::new(),
+            Some(create_session),
+        ));
+        let mut service = AppBuilder::new(OemSessionsTree { tree })
+            .authentication_provider(AdminOnly)
+            .task_service(TaskService::new())
+            .build();
+
+        // Logins are checked, and get a token, as they are for the usual collection
+        let data = json!({"UserName": "admin", "Password": "wrong"});
+        let response = post(&mut service, OEM_SESSIONS_URI, data, &Auth::None).await;
+        validate_unauthorized(&response);
+        let data = json!({"UserName": "admin", "Password": "admin"});
+        let response = post(&mut service, OEM_SESSIONS_URI, data.clone(), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let session_uri = get_header(&response, "Location").to_string();
+        assert_eq!(session_uri, format!("{}/1", OEM_SESSIONS_URI));
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let body = jget(&mut service, &session_uri, StatusCode::OK, &token, &[]).await;
+        assert_eq!(body["UserName"], "admin");
+
+        // Nor are they carried out as tasks
+        let body = Body::from(serde_json::to_vec(&data).unwrap());
+        let req = Request::post(OEM_SESSIONS_URI)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "respond-async")
+            .body(body)
+            .unwrap();
+        let response = service.ready().await.unwrap().call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get("X-Auth-Token").is_some());
+
+        // The session goes with its token
+        let response = delete(&mut service, &session_uri, &token).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        validate_unauthorized(&get(&mut service, &session_uri, &token).await);
+    }
+
+    #[tokio::test]
+    async fn session_body_without_username() {
+        // Sessions of OEM types need not echo the UserName they were made with
+        let mut tree = get_mock_tree();
+        tree.add_collection(Collection::new(
+            OEM_SESSIONS_URI,
+            String::from("SessionCollection"),
+            String::from("Contoso Session Collection"),
+            Vec::new(),
+            Some(|collection, request_body| {
+                let mut session = create_session(collection, request_body)?;
+                session.body.remove("UserName");
+                Ok(session)
+            }),
+        ));
+        let mut service = AppBuilder::new(OemSessionsTree { tree })
+            .authentication_provider(AdminOnly)
+            .build();
+
+        let data = json!({"UserName": "admin", "Password": "admin"});
+        let response = post(&mut service, OEM_SESSIONS_URI, data, &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let response = get(&mut service, "/redfish/v1/AccountService/Accounts", &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn session_service_tree() {
+        let sessions = SessionManager::new();
+        let tree = SessionServiceTree::new(get_mock_tree(), sessions.clone())
+            .with_session_timeout(Duration::from_secs(60));
+        let clock = ManualClock::new();
+        let mut service = AppBuilder::new(tree)
+            .session_manager(sessions.clone())
+            .authentication_provider(AdminOnly)
+            .clock(clock.clone())
+            .build();
+        let uri = "/redfish/v1/SessionService";
+        let collection_uri = "/redfish/v1/SessionService/Sessions";
+        let admin = admin_admin_basic_auth();
+
+        // Only authenticated users can read the service
+        validate_unauthorized(&get(&mut service, uri, &Auth::None).await);
+        let body = jget(&mut service, uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["SessionTimeout"], 60);
+        assert_eq!(body["ServiceEnabled"], true);
+
+        // Logging in makes a session of the manager
+        let data = json!({"UserName": "admin", "Password": "wrong"});
+        let response = post(&mut service, collection_uri, data, &Auth::None).await;
+        validate_unauthorized(&response);
+        let data = json!({"UserName": "admin", "Password": "admin", "Context": "ticket-7"});
+        let response = post(&mut service, collection_uri, data.clone(), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let first_uri = get_header(&response, "Location").to_string();
+        assert_eq!(first_uri, "/redfish/v1/SessionService/Sessions/1");
+        let first = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        assert_eq!(
+            sessions.get_context(&first_uri).as_deref(),
+            Some("ticket-7")
+        );
+        let body = jget(&mut service, &first_uri, StatusCode::OK, &first, &[]).await;
+        assert_eq!(body["UserName"], "admin");
+        assert_eq!(body["Context"], "ticket-7");
+        let response = post(&mut service, collection_uri, data.clone(), &Auth::None).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let second_uri = get_header(&response, "Location").to_string();
+        let second = Auth::Token(get_header(&response, "X-Auth-Token").to_string());
+        let body = jget(&mut service, collection_uri, StatusCode::OK, &first, &[]).await;
+        assert_eq!(body["Members@odata.count"], 2);
+
+        // Logging out deletes it, and Ids are not reused
+        let response = delete(&mut service, &first_uri, &first).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        validate_unauthorized(&get(&mut service, &first_uri, &first).await);
+        let body = jget(&mut service, collection_uri, StatusCode::OK, &second, &[]).await;
+        assert_eq!(body["Members"], json!([{"@odata.id": second_uri}]));
+        let response = post(&mut service, collection_uri, data, &Auth::None).await;
+        assert_eq!(
+            get_header(&response, "Location"),
+            "/redfish/v1/SessionService/Sessions/3"
+        );
+
+        // The timeout is checked, and applies to the sessions
+        let response = patch(&mut service, uri, json!({"SessionTimeout": 10}), &admin).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PropertyValueOutOfRange"
+        );
+        let response = patch(&mut service, uri, json!({"SessionTimeout": 120}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        clock.advance(Duration::from_secs(90));
+        jget(&mut service, &second_uri, StatusCode::OK, &second, &[]).await;
+        clock.advance(Duration::from_secs(120));
+        validate_unauthorized(&get(&mut service, &second_uri, &second).await);
+    }
+
+    // Creates systems as tasks, which the test completes.
+    struct DeferredCreateTree {
+        tree: MockTree,
+        tasks: TaskService,
+        started: Arc<Mutex<Option<Task>>>,
+    }
+
+    #[async_trait]
+    impl Tree for DeferredCreateTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn create_deferred(
+            &mut self,
+            _uri: &str,
+            _request_body: &Map<String, Value>,
+            _username: Option<&str>,
+        ) -> Result<Outcome<&dyn Node>, Error> {
+            let task = self.tasks.start("System Creation");
+            *self.started.lock().unwrap() = Some(task.clone());
+            Ok(Outcome::Async(task))
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn task_polling() {
+        let auth = admin_admin_basic_auth();
+        let task_uri = "/redfish/v1/TaskService/Tasks/1";
+        let monitor = "/redfish/v1/TaskService/TaskMonitors/1";
+        for location in [TaskLocation::Monitor, TaskLocation::Task] {
+            let tasks = TaskService::new();
+            let started = Arc::new(Mutex::new(None));
+            let tree = DeferredCreateTree {
+                tree: get_mock_tree(),
+                tasks: tasks.clone(),
+                started: started.clone(),
+            };
+            let mut service = AppBuilder::new(tree)
+                .task_service(tasks)
+                .task_location(location)
+                .build();
+
+            // The client is told where to poll, and that the body is the task
+            let data = json!({"Name": "New System"});
+            let response = post(&mut service, "/redfish/v1/Systems", data, &auth).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            let expected = match location {
+                TaskLocation::Monitor => monitor,
+                TaskLocation::Task => task_uri,
+            };
+            assert_eq!(get_header(&response, "Location"), expected);
+            assert_eq!(get_header(&response, "Content-Location"), task_uri);
+
+            // The monitor gives the task while it runs
+            let response = get(&mut service, monitor, &auth).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            assert_eq!(get_header(&response, "Content-Location"), task_uri);
+            let body = get_response_json(response).await;
+            assert_eq!(body["TaskState"], "Running");
+
+            // And then what was created, as the create would have
+            let task = started.lock().unwrap().clone().unwrap();
+            task.set_created_resource("/redfish/v1/Systems/2");
+            let response = get(&mut service, monitor, &auth).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            task.complete(
+                Health::OK,
+                Some(json!({"@odata.id": "/redfish/v1/Systems/2"})),
+            );
+            let response = get(&mut service, monitor, &auth).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(get_header(&response, "Location"), "/redfish/v1/Systems/2");
+            assert!(response.headers().get("Content-Location").is_none());
+            let body = get_response_json(response).await;
+            assert_eq!(body["@odata.id"], "/redfish/v1/Systems/2");
+            let body = jget(&mut service, task_uri, StatusCode::OK, &auth, &[]).await;
+            assert_eq!(body["TaskState"], "Completed");
+        }
+
+        // Creates carried out as tasks because the client preferred it are the same
+        let mut tree = get_mock_tree();
+        tree.add_collection(Collection::new(
+            OEM_SESSIONS_URI,
+            String::from("SessionCollection"),
+            String::from("Contoso Session Collection"),
+            Vec::new(),
+            Some(create_session),
+        ));
+        let mut service = AppBuilder::new(tree)
+            .task_service(TaskService::new())
+            .build();
+        let data = json!({"UserName": "admin", "Password": "admin"});
+        let body = Body::from(serde_json::to_vec(&data).unwrap());
+        let mut req = Request::post(OEM_SESSIONS_URI)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "respond-async");
+        add_auth_headers(&mut req, &auth);
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(req.body(body).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(get_header(&response, "Location"), monitor);
+        let response = get(&mut service, monitor, &auth).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let member_uri = format!("{}/1", OEM_SESSIONS_URI);
+        assert_eq!(get_header(&response, "Location"), member_uri);
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.id"], member_uri);
+    }
+
+    #[tokio::test]
+    async fn conditional_get() {
+        let mut app = app();
+        let response = get(&mut app, "/redfish/v1", &Auth::None).await;
+        let etag = get_header(&response, "etag").to_string();
+        // CURRENT stands for the ETag of the resource.
+        let cases: &[(&[(&str, &str)], StatusCode)] = &[
+            // If-None-Match takes a list, matched weakly, or * for any
+            (
+                &[("if-none-match", "\"other\", CURRENT")],
+                StatusCode::NOT_MODIFIED,
+            ),
+            (&[("if-none-match", "W/CURRENT")], StatusCode::NOT_MODIFIED),
+            (&[("if-none-match", "*")], StatusCode::NOT_MODIFIED),
+            (
+                &[("if-none-match", "\"other\", \"CURR,ENT\"")],
+                StatusCode::OK,
+            ),
+            // Fields of the same header are one list
+            (
+                &[("if-none-match", "\"other\""), ("if-none-match", "CURRENT")],
+                StatusCode::NOT_MODIFIED,
+            ),
+            // If-Match takes a list too, but matched strongly
+            (&[("if-match", "\"other\", CURRENT")], StatusCode::OK),
+            (&[("if-match", "*")], StatusCode::OK),
+            (
+                &[("if-match", "W/CURRENT")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                &[("if-match", "\"other\"")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                &[("if-match", "not a tag")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            // If-Match is evaluated first
+            (
+                &[("if-match", "\"other\""), ("if-none-match", "CURRENT")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                &[("if-match", "CURRENT"), ("if-none-match", "CURRENT")],
+                StatusCode::NOT_MODIFIED,
+            ),
+            (
+                &[("if-match", "CURRENT"), ("if-none-match", "\"other\"")],
+                StatusCode::OK,
+            ),
+        ];
+        for (headers, status) in cases {
+            for method in [http::Method::GET, http::Method::HEAD] {
+                let mut request = Request::builder().method(method.clone()).uri("/redfish/v1");
+                for (name, value) in headers.iter() {
+                    request = request.header(*name, value.replace("CURRENT", &etag));
+                }
+                let request = request.body(Body::empty()).unwrap();
+                let response = app.ready().await.unwrap().call(request).await.unwrap();
+                assert_eq!(response.status(), *status, "{} {:?}", method, headers);
+                if *status == StatusCode::NOT_MODIFIED {
+                    assert_eq!(get_header(&response, "etag"), etag);
+                }
+                if *status == StatusCode::PRECONDITION_FAILED && method == http::Method::GET {
+                    let body = get_response_json(response).await;
+                    assert_eq!(
+                        body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+                        "Base.1.16.PreconditionFailed"
+                    );
+                }
+            }
+        }
+
+        // The documents take the same rules, and If-None-Match over If-Modified-Since
+        let response = get(&mut app, "/redfish/v1/$metadata", &Auth::None).await;
+        let last_modified = get_header(&response, "last-modified").to_string();
+        let cases: &[(&[(&str, &str)], StatusCode)] = &[
+            (
+                &[("if-match", "\"other\"")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                &[("if-match", "*"), ("if-none-match", "*")],
+                StatusCode::NOT_MODIFIED,
+            ),
+            (
+                &[
+                    ("if-none-match", "\"other\""),
+                    ("if-modified-since", last_modified.as_str()),
+                ],
+                StatusCode::OK,
+            ),
+        ];
+        for (headers, status) in cases {
+            let mut request = Request::get("/redfish/v1/$metadata");
+            for (name, value) in headers.iter() {
+                request = request.header(*name, *value);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), *status, "{:?}", headers);
+        }
+    }
+
+    #[tokio::test]
+    async fn computed_etags() {
+        let scaffold = Scaffold::from_interop_profile(&get_test_profile()).unwrap();
+        let tree = scaffold.apply(TreeBuilder::new()).build();
+        let mut service = AppBuilder::new(tree).computed_etags(true).build();
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/Systems/1";
+
+        // Nodes without an ETag get one from their body, in the header and the body alike
+        let response = get(&mut service, uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = get_header(&response, "etag").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.etag"], etag.as_str());
+        for method in [http::Method::GET, http::Method::HEAD] {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", "Basic YWRtaW46YWRtaW4=")
+                .body(Body::empty())
+                .unwrap();
+            let response = service.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(get_header(&response, "etag"), etag);
+        }
+        let response = get(&mut service, "/redfish/v1/Systems", &auth).await;
+        let collection_etag = get_header(&response, "etag").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.etag"], collection_etag.as_str());
+        assert_ne!(collection_etag, etag);
+
+        // Clients can revalidate by it
+        let request = Request::get(uri)
+            .header("Authorization", "Basic YWRtaW46YWRtaW4=")
+            .header("If-None-Match", etag.as_str())
+            .body(Body::empty())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(get_header(&response, "etag"), etag);
+
+        // It changes with the body
+        let response = patch(&mut service, uri, json!({"AssetTag": "new"}), &auth).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let patched_etag = get_header(&response, "etag").to_string();
+        assert_ne!(patched_etag, etag);
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.etag"], patched_etag.as_str());
+        let response = get(&mut service, uri, &auth).await;
+        assert_eq!(get_header(&response, "etag"), patched_etag);
+        let request = Request::get(uri)
+            .header("Authorization", "Basic YWRtaW46YWRtaW4=")
+            .header("If-None-Match", etag.as_str())
+            .body(Body::empty())
+            .unwrap();
+        let response = service.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The ETags of nodes that have their own are kept
+        let mut service = AppBuilder::new(get_mock_tree())
+            .computed_etags(true)
+            .build();
+        let response = get(&mut service, "/redfish/v1", &auth).await;
+        let own = get(&mut app(), "/redfish/v1", &auth).await;
+        assert_eq!(get_header(&response, "etag"), get_header(&own, "etag"));
+
+        // And none are computed by default
+        let tree = scaffold.apply(TreeBuilder::new()).build();
+        let mut service = AppBuilder::new(tree).build();
+        let response = get(&mut service, uri, &auth).await;
+        assert!(response.headers().get("etag").is_none());
+        let body = get_response_json(response).await;
+        assert!(body.get("@odata.etag").is_none());
+    }
+
+    #[tokio::test]
+    async fn create_limits() {
+        let auth = admin_admin_basic_auth();
+        let data = json!({"Name": "New"});
+        let responses = [
+            (
+                CreateLimitResponse::Conflict,
+                StatusCode::CONFLICT,
+                "ResourceInUse",
+            ),
+            (
+                CreateLimitResponse::ServiceUnavailable(Duration::from_secs(3)),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "ServiceTemporarilyUnavailable",
+            ),
+        ];
+        for (limit_response, status, message_key) in responses {
+            let tasks = TaskService::new();
+            let started = Arc::new(Mutex::new(None));
+            let tree = DeferredCreateTree {
+                tree: get_mock_tree(),
+                tasks: tasks.clone(),
+                started: started.clone(),
+            };
+            let limit = CreateLimit {
+                max_pending: 1,
+                response: limit_response,
+            };
+            let mut service = AppBuilder::new(tree)
+                .task_service(tasks)
+                .create_limit("/redfish/v1/Systems", limit)
+                .build();
+
+            // Other collections are not limited
+            for _ in 0..2 {
+                let response = post(&mut service, "/redfish/v1/Chassis", data.clone(), &auth).await;
+                assert_eq!(response.status(), StatusCode::ACCEPTED);
+            }
+
+            // Creates carried out as tasks are pending until the task completes
+            let response = post(&mut service, "/redfish/v1/Systems", data.clone(), &auth).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            let task = started.lock().unwrap().take().unwrap();
+            let response = post(&mut service, "/redfish/v1/Systems", data.clone(), &auth).await;
+            assert_eq!(response.status(), status);
+            if status == StatusCode::SERVICE_UNAVAILABLE {
+                assert_eq!(get_header(&response, "Retry-After"), "3");
+            }
+            let body = get_response_json(response).await;
+            assert_eq!(
+                body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+                format!("Base.1.16.{}", message_key)
+            );
+            task.complete(Health::OK, None);
+            let response = post(&mut service, "/redfish/v1/Systems", data.clone(), &auth).await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+    }
+
+    // A request to change the resource, given the If-Match or If-None-Match headers to send.
+    fn get_conditional_request(
+        method: http::Method,
+        uri: &str,
+        auth: &Auth,
+        headers: &[(&str, &str)],
+        data: Option<Value>,
+    ) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(uri);
+        add_auth_headers(&mut request, auth);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        match data {
+            Some(data) => request
+                .header("Content-Type", "application/json")
+                .body(Body::from(serde_json::to_vec(&data).unwrap()))
+                .unwrap(),
+            None => request.body(Body::empty()).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn conditional_changes() {
+        let mut app = app();
+        let auth = admin_admin_basic_auth();
+        let uri = "/redfish/v1/SessionService";
+        // CURRENT stands for the ETag the resource has before each request.
+        let cases: &[(&[(&str, &str)], StatusCode)] = &[
+            (
+                &[("If-Match", "\"other\"")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            // Compared strongly, as for GET
+            (
+                &[("If-Match", "W/CURRENT")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            // The resource exists, so If-None-Match: * fails, and never with 304
+            (&[("If-None-Match", "*")], StatusCode::PRECONDITION_FAILED),
+            (
+                &[("If-None-Match", "CURRENT")],
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (&[("If-Match", "\"other\", CURRENT")], StatusCode::OK),
+            (&[("If-Match", "*")], StatusCode::OK),
+            (&[("If-None-Match", "\"other\"")], StatusCode::OK),
+        ];
+        let first_etag = get_header(&get(&mut app, uri, &auth).await, "etag").to_string();
+        for (timeout, (headers, status)) in (301..).zip(cases.iter()) {
+            let data = json!({"SessionTimeout": timeout});
+            let etag = get_header(&get(&mut app, uri, &auth).await, "etag").to_string();
+            let headers: Vec<(&str, String)> = headers
+                .iter()
+                .map(|(name, value)| (*name, value.replace("CURRENT", &etag)))
+                .collect();
+            let headers: Vec<(&str, &str)> = headers
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .collect();
+            let request =
+                get_conditional_request(http::Method::PATCH, uri, &auth, &headers, Some(data));
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), *status, "{:?}", headers);
+            let body = jget(&mut app, uri, StatusCode::OK, &auth, &[]).await;
+            if *status == StatusCode::PRECONDITION_FAILED {
+                // Nothing was changed
+                assert_ne!(body["SessionTimeout"], timeout);
+            } else {
+                assert_eq!(body["SessionTimeout"], timeout);
+            }
+        }
+        // Changes give the resource a new ETag, so that clients can tell they missed them.
+        let request = get_conditional_request(
+            http::Method::PATCH,
+            uri,
+            &auth,
+            &[("If-Match", first_etag.as_str())],
+            Some(json!({"SessionTimeout": 300})),
+        );
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let request = get_conditional_request(
+            http::Method::PATCH,
+            uri,
+            &auth,
+            &[("If-Match", "\"other\"")],
+            Some(json!({"SessionTimeout": 300})),
+        );
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        let body = get_response_json(response).await;
+        assert_eq!(
+            body["error"]["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.PreconditionFailed"
+        );
+
+        // Deleting is conditional too
+        let (session_auth, session_uri) = login(&mut app).await;
+        let response = get(&mut app, &session_uri, &session_auth).await;
+        let session_etag = get_header(&response, "etag").to_string();
+        for (headers, status) in [
+            ([("If-Match", "\"other\"")], StatusCode::PRECONDITION_FAILED),
+            (
+                [("If-Match", session_etag.as_str())],
+                StatusCode::NO_CONTENT,
+            ),
+        ] {
+            let request = get_conditional_request(
+                http::Method::DELETE,
+                &session_uri,
+                &session_auth,
+                &headers,
+                None,
+            );
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+        let response = get(&mut app, &session_uri, &auth).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // Even If-Match: * is false of what is not there, before it is found missing
+        for (headers, status) in [
+            (&[("If-Match", "*")][..], StatusCode::PRECONDITION_FAILED),
+            (&[("If-None-Match", "*")][..], StatusCode::NOT_FOUND),
+        ] {
+            let request =
+                get_conditional_request(http::Method::DELETE, &session_uri, &auth, headers, None);
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+
+        // Accounts have ETags of their own, so stale changes to them are refused
+        let hashing = PasswordHashConfig {
+            iterations: NonZeroU32::new(1).unwrap(),
+            ..Default::default()
+        };
+        let accounts = LocalAccounts::new().with_password_hashing(hashing);
+        accounts
+            .add_account("admin", "password1", "Administrator")
+            .unwrap();
+        accounts
+            .add_account("bob", "password2", "ReadOnly")
+            .unwrap();
+        let tree = AccountServiceTree::new(get_mock_tree(), accounts.clone());
+        let mut service = AppBuilder::new(tree)
+            .authentication_provider(accounts)
+            .privilege_registry(None)
+            .build();
+        // admin:password1
+        let admin = Auth::Basic(String::from("Basic YWRtaW46cGFzc3dvcmQx"));
+        let bob_uri = "/redfish/v1/AccountService/Accounts/bob";
+        let response = get(&mut service, bob_uri, &admin).await;
+        let etag = get_header(&response, "etag").to_string();
+        let body = get_response_json(response).await;
+        assert_eq!(body["@odata.etag"], etag.as_str());
+        for (data, status) in [
+            (json!({"RoleId": "Operator"}), StatusCode::OK),
+            (
+                json!({"RoleId": "Administrator"}),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+        ] {
+            let headers = [("If-Match", etag.as_str())];
+            let request =
+                get_conditional_request(http::Method::PATCH, bob_uri, &admin, &headers, Some(data));
+            let response = service.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+        let body = jget(&mut service, bob_uri, StatusCode::OK, &admin, &[]).await;
+        assert_eq!(body["RoleId"], "Operator");
+        assert_ne!(body["@odata.etag"], etag.as_str());
+    }
+
+    // Configuration store that keeps the configuration, and the archive last uploaded, in memory.
+    // Archives are the configuration as it is, which only restores if it starts with "cfg:".
+    #[derive(Clone, Default)]
+    struct MemoryConfiguration {
+        config: Arc<Mutex<Vec<u8>>>,
+        received: Arc<Mutex<Vec<u8>>>,
+    }
+
+    // Reads the archive a few bytes at a time.
+    struct ChunkReader(Vec<u8>);
+
+    #[async_trait]
+    impl ArchiveReader for ChunkReader {
+        async fn read(&mut self) -> Result<Option<Bytes>, Error> {
+            if self.0.is_empty() {
+                return Ok(None);
+            }
+            let rest = self.0.split_off(self.0.len().min(4));
+            Ok(Some(Bytes::from(std::mem::replace(&mut self.0, rest))))
+        }
+    }
+
+    #[async_trait]
+    impl ConfigurationStore for MemoryConfiguration {
+        async fn snapshot(&self) -> Result<Box<dyn ArchiveReader>, Error> {
+            Ok(Box::new(ChunkReader(self.config.lock().unwrap().clone())))
+        }
+
+        async fn receive(&self) -> Result<Box<dyn UploadWriter>, Error> {
+            self.received.lock().unwrap().clear();
+            Ok(Box::new(RecordingWriter {
+                data: self.received.clone(),
+                ..Default::default()
+            }))
+        }
+
+        async fn restore(&self) -> Result<(), Error> {
+            let received = self.received.lock().unwrap().clone();
+            if !received.starts_with(b"cfg:") {
+                return Err(Error::BadRequest);
+            }
+            *self.config.lock().unwrap() = received;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn configuration_backup() {
+        let store = MemoryConfiguration::default();
+        *store.config.lock().unwrap() = b"cfg:hostname=bmc".to_vec();
+        let manager = ManagerBuilder::new("BMC", Listeners::default());
+        let tree = manager.add_to(TreeBuilder::new()).build();
+        let mut app = AppBuilder::new(tree)
+            .authentication_provider(Roles)
+            .task_service(TaskService::new())
+            .configuration_backup("/redfish/v1/Managers/BMC", store.clone())
+            .build();
+        let admin = admin_admin_basic_auth();
+        // Leia:x
+        let leia = Auth::Basic(String::from("Basic TGVpYTp4"));
+        let backup = "/redfish/v1/Managers/BMC/Actions/Oem/Manager.BackupConfiguration";
+        let restore = "/redfish/v1/Managers/BMC/Actions/Oem/Manager.RestoreConfiguration";
+
+        // The manager lists the actions
+        let body = jget(
+            &mut app,
+            "/redfish/v1/Managers/BMC",
+            StatusCode::OK,
+            &admin,
+            &[],
+        )
+        .await;
+        assert_eq!(
+            body["Actions"]["Oem"]["#Manager.BackupConfiguration"],
+            json!({"target": backup})
+        );
+        assert_eq!(
+            body["Actions"]["Oem"]["#Manager.RestoreConfiguration"],
+            json!({"target": restore})
+        );
+
+        // Backing up needs ConfigureManager, and downloads the archive
+        let response = post(&mut app, backup, json!({}), &leia).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post(&mut app, backup, json!({}), &admin).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_header(&response, "Content-Type"),
+            "application/octet-stream"
+        );
+        let archive = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&archive[..], b"cfg:hostname=bmc");
+
+        // Restoring takes the archive as a binary upload, and is carried out as a task
+        let upload = |archive: &'static [u8], auth: &Auth| {
+            let mut req = Request::post(restore).header("Content-Type", "application/octet-stream");
+            add_auth_headers(&mut req, auth);
+            req.body(Body::from(archive)).unwrap()
+        };
+        let response = app
+            .ready()
+            .await
+            .unwrap()
+            .call(upload(b"cfg:hostname=spare", &leia))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post(&mut app, restore, json!({}), &admin).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        for (archive, state) in [
+            (&b"cfg:hostname=spare"[..], "Completed"),
+            (&b"garbage"[..], "Exception"),
+        ] {
+            let response = app
+                .ready()
+                .await
+                .unwrap()
+                .call(upload(archive, &admin))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            let task_uri = get_header(&response, "Content-Location").to_owned();
+            let body = get_response_json(response).await;
+            assert_eq!(body["Name"], "Restore Configuration");
+            tokio::task::yield_now().await;
+            let body = jget(&mut app, &task_uri, StatusCode::OK, &admin, &[]).await;
+            assert_eq!(body["TaskState"], state);
+        }
+        assert_eq!(*store.config.lock().unwrap(), b"cfg:hostname=spare");
+    }
 }
@@ -0,0 +1,70 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Serves HTTPS with the certificate and key in the given files, and picks up new ones when
+// they are installed, such as by certbot or a CertificateService action. Only connections
+// made after that get the new certificate, so nothing is dropped, and sessions carry on.
+pub struct CertificateWatcher {
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    modified: (SystemTime, SystemTime),
+}
+
+impl CertificateWatcher {
+    pub async fn new(cert_path: PathBuf, key_path: PathBuf) -> io::Result<Self> {
+        let modified = get_modified(&cert_path, &key_path)?;
+        let config = RustlsConfig::from_pem_file(&cert_path, &key_path).await?;
+        Ok(Self {
+            config,
+            cert_path,
+            key_path,
+            modified,
+        })
+    }
+
+    // The config to serve with. It changes in place as certificates are reloaded.
+    pub fn get_config(&self) -> RustlsConfig {
+        self.config.clone()
+    }
+
+    // Reload the certificate if either file has changed since it was last loaded.
+    // Returns whether it was reloaded. If the new files are bad, such as while only one of
+    // them has been written, the old certificate is kept, and they are tried again next time.
+    pub async fn check(&mut self) -> io::Result<bool> {
+        let modified = get_modified(&self.cert_path, &self.key_path)?;
+        if modified == self.modified {
+            return Ok(false);
+        }
+        let cert = std::fs::read(&self.cert_path)?;
+        let key = std::fs::read(&self.key_path)?;
+        // An empty chain would be loaded without complaint, and then fail every handshake.
+        if rustls_pemfile::certs(&mut cert.as_slice())?.is_empty() {
+            let message = format!("No certificates in {}", self.cert_path.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        self.config.reload_from_pem(cert, key).await?;
+        self.modified = modified;
+        Ok(true)
+    }
+
+    // Check for new certificates every interval, for as long as the service runs.
+    pub async fn watch(mut self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            match self.check().await {
+                Ok(true) => println!("Reloaded certificate {}", self.cert_path.display()),
+                Ok(false) => (),
+                Err(error) => println!("Keeping old certificate: {}", error),
+            }
+        }
+    }
+}
+
+fn get_modified(cert_path: &Path, key_path: &Path) -> io::Result<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(cert_path)?.modified()?;
+    let key = std::fs::metadata(key_path)?.modified()?;
+    Ok((cert, key))
+}
@@ -1,12 +1,23 @@
 use axum::async_trait;
+use bytes::Bytes;
 use etag::EntityTag;
-use redfish_axum::{Error, Node, Tree};
+use redfish_axum::{
+    compute_etag, AuditLog, Error, LinkRegistry, Node, NodeLink, NodeRef, RawBody, Tree, Trigger,
+    UploadWriter,
+};
 use redfish_data::{
-    get_uri_id, AllowedMethods, CollectionType, ResourceSchemaVersion, ResourceType,
+    get_uri_id, AllowedMethods, CollectionType, PropertyType, ResourceSchemaVersion, ResourceType,
+    SchemaUrl,
 };
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 
+type PostFn = fn(&Collection, &Map<String, Value>) -> Result<Resource, Error>;
+type PatchFn = fn(&mut Resource, &Map<String, Value>) -> Result<(), Error>;
+type DeleteFn = fn(&Resource) -> Result<(), Error>;
+// Returns a new writer for each upload to a URI.
+type UploadFn = Box<dyn Fn() -> Box<dyn UploadWriter> + Send + Sync>;
+
 pub struct Collection {
     uri: String,
     resource_type: CollectionType,
@@ -15,7 +26,9 @@ pub struct Collection {
     // if user should not be able to POST to collection, this should be None
     // else, it should be a function that returns new Resource generated from Request
     // that function should *not* add the resource to the collection's members vector.
-    post: Option<fn(&Collection, &Map<String, Value>) -> Result<Resource, Error>>,
+    post: Option<PostFn>,
+    // Properties that POST requests must give, checked by the service before post is called.
+    required_properties: Vec<String>,
 }
 
 impl Collection {
@@ -24,7 +37,7 @@ impl Collection {
         schema_name: String,
         name: String,
         members: Vec<String>,
-        post: Option<fn(&Collection, &Map<String, Value>) -> Result<Resource, Error>>,
+        post: Option<PostFn>,
     ) -> Self {
         Self {
             uri: String::from(uri),
@@ -32,30 +45,44 @@ impl Collection {
             name,
             members,
             post,
+            required_properties: Vec::new(),
         }
     }
-}
 
-impl Node for Collection {
-    fn get_uri(&self) -> &str {
-        self.uri.as_str()
+    pub fn with_required_properties(mut self, names: &[&str]) -> Self {
+        self.required_properties = names.iter().map(|name| String::from(*name)).collect();
+        self
     }
 
-    fn get_body(&self) -> Value {
+    // The body without its @odata.etag, which is computed from the rest.
+    fn get_untagged_body(&self) -> Map<String, Value> {
         let mut member_list = Vec::new();
         for member in self.members.iter() {
             let mut member_obj = HashMap::new();
             member_obj.insert(String::from("@odata.id"), member);
             member_list.push(member_obj);
         }
-        json!({
+        let body = json!({
             "@odata.id": self.uri,
-            "@odata.etag": self.get_etag().unwrap().to_string(),
             "@odata.type": format!("#{}.{}", self.resource_type.name, self.resource_type.name),
             "Name": self.name,
             "Members": member_list,
             "Members@odata.count": self.members.len(),
-        })
+        });
+        body.as_object().unwrap().clone()
+    }
+}
+
+impl Node for Collection {
+    fn get_uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    fn get_body(&self) -> Value {
+        let mut body = self.get_untagged_body();
+        let etag = compute_etag(&body);
+        body.insert(String::from("@odata.etag"), json!(etag.to_string()));
+        Value::Object(body)
     }
 
     fn get_allowed_methods(&self) -> AllowedMethods {
@@ -67,12 +94,16 @@ impl Node for Collection {
         }
     }
 
-    fn described_by(&self) -> Option<&str> {
-        Some(self.resource_type.described_by.as_str())
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
     }
 
     fn get_etag(&self) -> Option<EntityTag> {
-        Some(EntityTag::strong("HARDCODED_ETAG"))
+        Some(compute_etag(&self.get_untagged_body()))
+    }
+
+    fn get_required_create_properties(&self) -> Vec<String> {
+        self.required_properties.clone()
     }
 }
 
@@ -83,27 +114,28 @@ pub struct Resource {
     collection: Option<String>,
     // if user should not be able to PATCH this resource, this should be None
     // else, it should be a function that applies the patch.
-    patch: Option<fn(&mut Resource, &Map<String, Value>) -> Result<(), Error>>,
+    patch: Option<PatchFn>,
     // if use should not be able to DELETE this resource, this should be None.
     // else, it should be a function that performs any extra logic associated with deleting the resource.
-    delete: Option<fn(&Resource) -> Result<(), Error>>,
+    delete: Option<DeleteFn>,
+    links: Vec<NodeLink>,
 }
 
 impl Resource {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         uri: &str,
         schema_name: String,
         schema_version: ResourceSchemaVersion,
         term_name: String,
         name: String,
-        delete: Option<fn(&Resource) -> Result<(), Error>>,
-        patch: Option<fn(&mut Resource, &Map<String, Value>) -> Result<(), Error>>,
+        delete: Option<DeleteFn>,
+        patch: Option<PatchFn>,
         collection: Option<String>,
         rest: Value,
     ) -> Self {
         let mut body = rest.as_object().unwrap().clone();
         body.insert(String::from("@odata.id"), json!(uri));
-        body.insert(String::from("@odata.etag"), json!("\"HARDCODED_ETAG\""));
         body.insert(
             String::from("@odata.type"),
             json!(format!(
@@ -124,8 +156,21 @@ impl Resource {
             delete,
             patch,
             collection,
+            links: Vec::new(),
         }
     }
+
+    // Declare the type of a writable property, so that PATCH requests are validated against it.
+    pub fn with_property(mut self, name: &str, property_type: PropertyType) -> Self {
+        self.resource_type = self.resource_type.with_property(name, property_type);
+        self
+    }
+
+    // Add a relation to the Link header of the resource, such as to the ActionInfo of an action.
+    pub fn with_link(mut self, link: NodeLink) -> Self {
+        self.links.push(link);
+        self
+    }
 }
 
 impl Node for Resource {
@@ -134,7 +179,10 @@ impl Node for Resource {
     }
 
     fn get_body(&self) -> Value {
-        Value::Object(self.body.clone())
+        let mut body = self.body.clone();
+        let etag = compute_etag(&self.body);
+        body.insert(String::from("@odata.etag"), json!(etag.to_string()));
+        Value::Object(body)
     }
 
     fn get_allowed_methods(&self) -> AllowedMethods {
@@ -146,20 +194,84 @@ impl Node for Resource {
         }
     }
 
-    fn described_by(&self) -> Option<&str> {
-        Some(self.resource_type.described_by.as_str())
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
     }
 
     fn get_etag(&self) -> Option<EntityTag> {
-        Some(EntityTag::strong("HARDCODED_ETAG"))
+        Some(compute_etag(&self.body))
+    }
+
+    fn get_resource_type(&self) -> Option<&ResourceType> {
+        Some(&self.resource_type)
+    }
+
+    fn get_links(&self) -> Vec<NodeLink> {
+        self.links.clone()
+    }
+}
+
+// A node with a non-JSON body, such as a certificate export or log dump.
+pub struct Attachment {
+    uri: String,
+    content_type: String,
+    data: Bytes,
+}
+
+impl Attachment {
+    pub fn new(uri: &str, content_type: &str, data: Bytes) -> Self {
+        Self {
+            uri: String::from(uri),
+            content_type: String::from(content_type),
+            data,
+        }
+    }
+}
+
+impl Node for Attachment {
+    fn get_uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    fn get_body(&self) -> Value {
+        Value::Null
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        }
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        None
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+
+    fn get_raw_body(&self) -> Option<RawBody> {
+        Some(RawBody {
+            content_type: self.content_type.clone(),
+            data: self.data.clone(),
+        })
     }
 }
 
 pub struct MockTree {
     resources: HashMap<String, Resource>,
     collections: HashMap<String, Collection>,
+    attachments: HashMap<String, Attachment>,
+    triggers: HashMap<String, Trigger>,
+    audit_log: Option<AuditLog>,
+    uploads: HashMap<String, UploadFn>,
     collection_types: Vec<CollectionType>,
     resource_types: Vec<ResourceType>,
+    links: LinkRegistry,
 }
 
 impl MockTree {
@@ -167,11 +279,20 @@ impl MockTree {
         Self {
             resources: HashMap::new(),
             collections: HashMap::new(),
+            attachments: HashMap::new(),
+            triggers: HashMap::new(),
+            audit_log: None,
+            uploads: HashMap::new(),
             collection_types: Vec::new(),
             resource_types: Vec::new(),
+            links: LinkRegistry::new(),
         }
     }
 
+    pub fn add_link(&mut self, from: &str, property: &str, to: &str) {
+        self.links.add(from, property, to);
+    }
+
     pub fn add_resource(&mut self, resource: Resource) {
         let resource_type = resource.resource_type.clone();
         self.resources.insert(resource.uri.clone(), resource);
@@ -180,6 +301,37 @@ impl MockTree {
         }
     }
 
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        self.attachments.insert(attachment.uri.clone(), attachment);
+    }
+
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        let resource_type = trigger.get_resource_type().clone();
+        self.triggers
+            .insert(String::from(trigger.get_uri()), trigger);
+        if !self.resource_types.contains(&resource_type) {
+            self.resource_types.push(resource_type);
+        }
+    }
+
+    pub fn add_audit_log(&mut self, audit_log: AuditLog) {
+        for resource_type in audit_log.get_resource_types() {
+            if !self.resource_types.contains(&resource_type) {
+                self.resource_types.push(resource_type);
+            }
+        }
+        for collection_type in audit_log.get_collection_types() {
+            if !self.collection_types.contains(&collection_type) {
+                self.collection_types.push(collection_type);
+            }
+        }
+        self.audit_log = Some(audit_log);
+    }
+
+    pub fn add_upload(&mut self, uri: &str, upload: UploadFn) {
+        self.uploads.insert(String::from(uri), upload);
+    }
+
     pub fn add_collection(&mut self, collection: Collection) {
         let collection_type = collection.resource_type.clone();
         self.collections.insert(collection.uri.clone(), collection);
@@ -191,15 +343,24 @@ impl MockTree {
 
 #[async_trait]
 impl Tree for MockTree {
-    async fn get(&self, uri: &str, username: Option<&str>) -> Result<&dyn Node, Error> {
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
         if uri != "/redfish/v1" && username.is_none() {
             return Err(Error::Unauthorized);
         }
         if let Some(resource) = self.resources.get(uri) {
-            return Ok(resource);
+            return Ok(resource.into());
         }
         if let Some(collection) = self.collections.get(uri) {
-            return Ok(collection);
+            return Ok(collection.into());
+        }
+        if let Some(attachment) = self.attachments.get(uri) {
+            return Ok(attachment.into());
+        }
+        if let Some(trigger) = self.triggers.get(uri) {
+            return Ok(trigger.into());
+        }
+        if let Some(node) = self.audit_log.as_ref().and_then(|log| log.get_node(uri)) {
+            return Ok(node);
         }
         Err(Error::NotFound)
     }
@@ -286,6 +447,23 @@ impl Tree for MockTree {
         }
     }
 
+    async fn upload(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        match self.uploads.get(uri) {
+            Some(upload) => Ok(upload()),
+            None => match self.get(uri, username).await {
+                Ok(_) => Err(Error::UnsupportedMediaType),
+                Err(error) => Err(error),
+            },
+        }
+    }
+
     fn get_collection_types(&self) -> &[CollectionType] {
         &self.collection_types
     }
@@ -293,4 +471,8 @@ impl Tree for MockTree {
     fn get_resource_types(&self) -> &[ResourceType] {
         &self.resource_types
     }
+
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        Some(&self.links)
+    }
 }
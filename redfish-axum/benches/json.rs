@@ -0,0 +1,80 @@
+// Times PATCH requests with large bodies, to compare the default JSON body parsing with that
+// of the fast-json feature. Run it both ways and compare:
+// cargo bench -p redfish-axum --bench json
+// cargo bench -p redfish-axum --bench json --features fast-json
+use axum::body::Body;
+use http::Request;
+use redfish_axum::TreeBuilder;
+use redfish_data::{AllowedMethods, ResourceSchemaVersion, ResourceType};
+use serde_json::{json, Map, Value};
+use std::time::{Duration, Instant};
+use tower::{Service, ServiceExt};
+
+const ITERATIONS: u32 = 200;
+
+// A body like that of a bulk import, with many members of a few properties each.
+fn get_large_body(members: usize) -> Vec<u8> {
+    let members: Vec<Value> = (0..members)
+        .map(|i| {
+            json!({
+                "UserName": format!("user{}", i),
+                "RoleId": "ReadOnly",
+                "Enabled": i % 2 == 0,
+                "Description": "An account imported from the directory of another service",
+            })
+        })
+        .collect();
+    let mut body = Map::new();
+    body.insert(String::from("Oem"), json!({"Import": members}));
+    serde_json::to_vec(&body).unwrap()
+}
+
+async fn time_patches(body: &[u8]) -> Duration {
+    let tree = TreeBuilder::new()
+        .resource(
+            "/redfish/v1/AccountService",
+            "Account Service",
+            ResourceType::new_dmtf(
+                String::from("AccountService"),
+                ResourceSchemaVersion::new(1, 12, 0),
+            ),
+            AllowedMethods {
+                delete: false,
+                get: true,
+                patch: true,
+                post: false,
+            },
+            json!({}),
+        )
+        .build();
+    let mut app = redfish_axum::app(tree);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let request = Request::patch("/redfish/v1/AccountService")
+            .header("Content-Type", "application/json")
+            .header("Authorization", "Basic YWRtaW46YWRtaW4=")
+            .body(Body::from(body.to_vec()))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert!(response.status().is_success());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+#[tokio::main]
+async fn main() {
+    let feature = match cfg!(feature = "fast-json") {
+        true => "fast-json",
+        false => "default",
+    };
+    for members in [10, 1000, 10000] {
+        let body = get_large_body(members);
+        let elapsed = time_patches(&body).await;
+        println!(
+            "{}: PATCH of {} bytes took {:?} on average",
+            feature,
+            body.len(),
+            elapsed
+        );
+    }
+}
@@ -0,0 +1,1002 @@
+use crate::accounts::{get_string, unknown, PatchError, ACCOUNT_SERVICE_URI};
+use crate::registries::base_registry;
+use crate::{
+    compute_etag, AccountLockouts, AuthenticationProvider, Error, LinkRegistry, Node, NodeRef,
+    Outcome, Tree, UploadWriter,
+};
+use async_trait::async_trait;
+use etag::EntityTag;
+use redfish_data::{
+    get_resource_odata_type, AllowedMethods, CollectionType, Message, PasswordHash,
+    PasswordHashConfig, ResourceSchemaVersion, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+
+const ACCOUNTS_URI: &str = "/redfish/v1/AccountService/Accounts";
+const ROLES_URI: &str = "/redfish/v1/AccountService/Roles";
+
+// The properties of the AccountService that clients may set, all in seconds but the threshold.
+const LOCKOUT_PROPERTIES: [&str; 3] = [
+    "AccountLockoutThreshold",
+    "AccountLockoutDuration",
+    "AccountLockoutCounterResetAfter",
+];
+
+// A role that accounts can be given, with the privileges it gives them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Role {
+    pub id: String,
+    pub privileges: Vec<String>,
+    // Whether Redfish predefines the role, as it does Administrator, Operator and ReadOnly.
+    pub predefined: bool,
+}
+
+impl Role {
+    // A custom role, such as one for those who may only configure components.
+    pub fn new(id: &str, privileges: &[&str]) -> Self {
+        Self {
+            id: String::from(id),
+            privileges: privileges
+                .iter()
+                .map(|privilege| String::from(*privilege))
+                .collect(),
+            predefined: false,
+        }
+    }
+
+    fn get_predefined() -> Vec<Self> {
+        let administrator = [
+            "Login",
+            "ConfigureManager",
+            "ConfigureUsers",
+            "ConfigureSelf",
+            "ConfigureComponents",
+        ];
+        let operator = ["Login", "ConfigureSelf", "ConfigureComponents"];
+        let read_only = ["Login", "ConfigureSelf"];
+        [
+            ("Administrator", &administrator[..]),
+            ("Operator", &operator[..]),
+            ("ReadOnly", &read_only[..]),
+        ]
+        .into_iter()
+        .map(|(id, privileges)| Self {
+            predefined: true,
+            ..Self::new(id, privileges)
+        })
+        .collect()
+    }
+
+    fn get_uri(&self) -> String {
+        format!("{}/{}", ROLES_URI, self.id)
+    }
+
+    fn get_body(&self) -> Value {
+        let resource_type = get_role_type();
+        json!({
+            "@odata.id": self.get_uri(),
+            "@odata.type": get_resource_odata_type("Role", &resource_type.version, "Role"),
+            "Id": self.id,
+            "Name": format!("{} Role", self.id),
+            "RoleId": self.id,
+            "AssignedPrivileges": self.privileges,
+            "IsPredefined": self.predefined,
+        })
+    }
+}
+
+// An account that LocalAccounts keeps, with only a hash of its password.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalAccount {
+    pub username: String,
+    pub password: PasswordHash,
+    pub role_id: String,
+    pub enabled: bool,
+}
+
+struct Directory {
+    accounts: Vec<LocalAccount>,
+    roles: Vec<Role>,
+    password_hashing: PasswordHashConfig,
+    // A hash of no one's password, made with the configuration when first needed. Users without
+    // accounts are checked against it, so that they take as long to refuse as those with them.
+    dummy_password: Option<PasswordHash>,
+    min_password_length: usize,
+    max_password_length: usize,
+    // The lockout properties of the AccountService, as clients last set them.
+    lockout: Map<String, Value>,
+}
+
+impl Directory {
+    fn get_account(&self, username: &str) -> Option<&LocalAccount> {
+        self.accounts
+            .iter()
+            .find(|account| account.username == username)
+    }
+
+    fn get_role(&self, role_id: &str) -> Option<&Role> {
+        self.roles.iter().find(|role| role.id == role_id)
+    }
+
+    fn hash_password(&self, value: &Value) -> Result<PasswordHash, Error> {
+        let password = get_patch_string(value, "Password")?;
+        let length = password.chars().count();
+        if length < self.min_password_length || length > self.max_password_length {
+            return Err(get_password_error(
+                self.min_password_length,
+                self.max_password_length,
+            ));
+        }
+        Ok(PasswordHash::new(&password, &self.password_hashing))
+    }
+
+    fn check_role(&self, value: &Value) -> Result<String, Error> {
+        let role_id = get_patch_string(value, "RoleId")?;
+        match self.get_role(&role_id) {
+            Some(_) => Ok(role_id),
+            None => Err(get_patch_error("PropertyValueNotInList", "RoleId", value)),
+        }
+    }
+}
+
+fn get_patch_error(key: &'static str, path: &str, value: &Value) -> Error {
+    let error = PatchError {
+        key,
+        path: String::from(path),
+        value: value.clone(),
+    };
+    Error::InvalidProperties(vec![error.into_message()])
+}
+
+fn get_patch_string(value: &Value, path: &str) -> Result<String, Error> {
+    get_string(value, String::from(path))
+        .map_err(|error| Error::InvalidProperties(vec![error.into_message()]))
+}
+
+fn get_patch_bool(value: &Value, path: &str) -> Result<bool, Error> {
+    value
+        .as_bool()
+        .ok_or_else(|| get_patch_error("PropertyValueTypeError", path, value))
+}
+
+// Passwords are not given back to clients, even in messages about them.
+fn get_password_error(min_length: usize, max_length: usize) -> Error {
+    let message = Message::from_registry(
+        base_registry(),
+        "PropertyValueFormatError",
+        ResourceSchemaVersion::new(1, 1, 2),
+        vec![String::from("(hidden)"), String::from("Password")],
+        vec![String::from("#/Password")],
+    )
+    .unwrap()
+    .with_resolution(format!(
+        "Give a password of {} to {} characters.",
+        min_length, max_length
+    ));
+    Error::InvalidProperties(vec![message])
+}
+
+// User names are the Ids of their accounts, so they have to be fit for URIs.
+fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._".contains(c))
+}
+
+// Keeps the accounts of the service and the roles they have, as the AccountService that an
+// AccountServiceTree serves. It is also the AuthenticationProvider for them, which gives each
+// user the privileges of their role. Cloning gives another handle to the same accounts.
+#[derive(Clone)]
+pub struct LocalAccounts {
+    directory: Arc<RwLock<Directory>>,
+    lockouts: Option<AccountLockouts>,
+}
+
+impl Default for LocalAccounts {
+    fn default() -> Self {
+        let directory = Directory {
+            accounts: Vec::new(),
+            roles: Role::get_predefined(),
+            password_hashing: PasswordHashConfig::default(),
+            dummy_password: None,
+            min_password_length: 8,
+            max_password_length: 64,
+            lockout: LOCKOUT_PROPERTIES
+                .iter()
+                .map(|name| (String::from(*name), json!(0)))
+                .collect(),
+        };
+        Self {
+            directory: Arc::new(RwLock::new(directory)),
+            lockouts: None,
+        }
+    }
+}
+
+impl LocalAccounts {
+    // No accounts, and the roles Redfish predefines. Accounts are not locked out until a
+    // client sets the AccountLockoutThreshold and AccountLockoutDuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_role(self, role: Role) -> Self {
+        {
+            let mut directory = self.directory.write().unwrap();
+            directory.roles.retain(|existing| existing.id != role.id);
+            directory.roles.push(role);
+        }
+        self
+    }
+
+    // Hash new passwords with the configuration. Passwords hashed more weakly are hashed again
+    // as their accounts next authenticate.
+    pub fn with_password_hashing(self, config: PasswordHashConfig) -> Self {
+        {
+            let mut directory = self.directory.write().unwrap();
+            directory.password_hashing = config;
+            directory.dummy_password = None;
+        }
+        self
+    }
+
+    // The lengths of password clients may give, which are 8 to 64 characters by default.
+    pub fn with_password_length(self, min_length: usize, max_length: usize) -> Self {
+        {
+            let mut directory = self.directory.write().unwrap();
+            directory.min_password_length = min_length;
+            directory.max_password_length = max_length;
+        }
+        self
+    }
+
+    // Serve the Locked property of accounts from the handle the service locks them with, for
+    // clients to unlock them by setting it to false. See AppBuilder::account_lockouts().
+    pub fn with_lockouts(mut self, lockouts: AccountLockouts) -> Self {
+        self.lockouts = Some(lockouts);
+        self
+    }
+
+    // Add an account, such as the first administrator's, as a client would create it.
+    pub fn add_account(&self, username: &str, password: &str, role_id: &str) -> Result<(), Error> {
+        let mut body = Map::new();
+        body.insert(String::from("UserName"), json!(username));
+        body.insert(String::from("Password"), json!(password));
+        body.insert(String::from("RoleId"), json!(role_id));
+        self.create(&body, None).map(|_| ())
+    }
+
+    // Add an account as it was kept, such as when reloading those list() gave, replacing any
+    // with its user name.
+    pub fn insert(&self, account: LocalAccount) {
+        let mut directory = self.directory.write().unwrap();
+        directory
+            .accounts
+            .retain(|existing| existing.username != account.username);
+        directory.accounts.push(account);
+    }
+
+    // Every account, for keeping them across restarts.
+    pub fn list(&self) -> Vec<LocalAccount> {
+        self.directory.read().unwrap().accounts.clone()
+    }
+
+    fn get_account(&self, username: &str) -> Option<LocalAccount> {
+        self.directory
+            .read()
+            .unwrap()
+            .get_account(username)
+            .cloned()
+    }
+
+    // Users without accounts here are left to the privilege registry.
+    fn may_configure_users(&self, username: Option<&str>) -> bool {
+        let directory = self.directory.read().unwrap();
+        let account = match username.and_then(|username| directory.get_account(username)) {
+            Some(account) => account,
+            None => return true,
+        };
+        directory
+            .get_role(&account.role_id)
+            .is_some_and(|role| role.privileges.iter().any(|p| p == "ConfigureUsers"))
+    }
+
+    fn is_locked(&self, username: &str) -> bool {
+        self.lockouts
+            .as_ref()
+            .is_some_and(|lockouts| lockouts.is_locked(username))
+    }
+
+    fn get_account_body(&self, account: &LocalAccount) -> Value {
+        let resource_type = get_account_type();
+        let uri = format!("{}/{}", ACCOUNTS_URI, account.username);
+        json!({
+            "@odata.id": uri,
+            "@odata.type": get_resource_odata_type("ManagerAccount", &resource_type.version, "ManagerAccount"),
+            "Id": account.username,
+            "Name": "User Account",
+            "UserName": account.username,
+            "Password": null,
+            "RoleId": account.role_id,
+            "Enabled": account.enabled,
+            "Locked": self.is_locked(&account.username),
+            "Locked@Redfish.AllowableValues": ["false"],
+            "AccountTypes": ["Redfish"],
+            "Links": {
+                "Role": {"@odata.id": format!("{}/{}", ROLES_URI, account.role_id)},
+            },
+        })
+    }
+
+    fn get_node(&self, uri: &str) -> Option<AccountNode> {
+        let directory = self.directory.read().unwrap();
+        let read_only = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        if uri == ACCOUNT_SERVICE_URI {
+            let resource_type = get_account_service_type();
+            let mut body = json!({
+                "@odata.id": ACCOUNT_SERVICE_URI,
+                "@odata.type": get_resource_odata_type("AccountService", &resource_type.version, "AccountService"),
+                "Id": "AccountService",
+                "Name": "Account Service",
+                "ServiceEnabled": true,
+                "MinPasswordLength": directory.min_password_length,
+                "MaxPasswordLength": directory.max_password_length,
+                "Accounts": {"@odata.id": ACCOUNTS_URI},
+                "Roles": {"@odata.id": ROLES_URI},
+                "Status": {"State": "Enabled", "Health": "OK"},
+            });
+            body.as_object_mut()
+                .unwrap()
+                .extend(directory.lockout.clone());
+            let allowed_methods = AllowedMethods {
+                patch: true,
+                ..read_only
+            };
+            return Some(AccountNode::new(
+                uri,
+                body,
+                allowed_methods,
+                resource_type.described_by,
+            ));
+        }
+        if uri == ACCOUNTS_URI {
+            let members: Vec<String> = directory
+                .accounts
+                .iter()
+                .map(|account| format!("{}/{}", ACCOUNTS_URI, account.username))
+                .collect();
+            let allowed_methods = AllowedMethods {
+                post: true,
+                ..read_only
+            };
+            let mut node = AccountNode::new(
+                uri,
+                get_collection_body(uri, "ManagerAccountCollection", "Accounts", &members),
+                allowed_methods,
+                get_account_collection_type().described_by,
+            );
+            node.required = ["UserName", "Password", "RoleId"]
+                .iter()
+                .map(|name| String::from(*name))
+                .collect();
+            return Some(node);
+        }
+        if uri == ROLES_URI {
+            let members: Vec<String> = directory.roles.iter().map(Role::get_uri).collect();
+            return Some(AccountNode::new(
+                uri,
+                get_collection_body(uri, "RoleCollection", "Roles", &members),
+                read_only,
+                get_role_collection_type().described_by,
+            ));
+        }
+        if let Some(username) = get_member_id(uri, ACCOUNTS_URI) {
+            let account = directory.get_account(username)?;
+            let allowed_methods = AllowedMethods {
+                delete: true,
+                get: true,
+                patch: true,
+                post: false,
+            };
+            return Some(AccountNode::new(
+                uri,
+                self.get_account_body(account),
+                allowed_methods,
+                get_account_type().described_by,
+            ));
+        }
+        let role_id = get_member_id(uri, ROLES_URI)?;
+        let role = directory.get_role(role_id)?;
+        Some(AccountNode::new(
+            uri,
+            role.get_body(),
+            read_only,
+            get_role_type().described_by,
+        ))
+    }
+
+    // The properties are checked before any account is added, so that a request with any that
+    // are not valid adds none.
+    fn create(&self, body: &Map<String, Value>, username: Option<&str>) -> Result<String, Error> {
+        if !self.may_configure_users(username) {
+            return Err(Error::Forbidden);
+        }
+        let mut directory = self.directory.write().unwrap();
+        let mut account_username = None;
+        let mut password = None;
+        let mut role_id = None;
+        let mut enabled = true;
+        for (name, value) in body {
+            match name.as_str() {
+                "UserName" => {
+                    let name = get_patch_string(value, "UserName")?;
+                    if !is_valid_username(&name) {
+                        return Err(get_patch_error(
+                            "PropertyValueFormatError",
+                            "UserName",
+                            value,
+                        ));
+                    }
+                    if directory.get_account(&name).is_some() {
+                        return Err(Error::ResourceAlreadyExists(
+                            String::from("ManagerAccount"),
+                            String::from("UserName"),
+                            name,
+                        ));
+                    }
+                    account_username = Some(name);
+                }
+                "Password" => password = Some(directory.hash_password(value)?),
+                "RoleId" => role_id = Some(directory.check_role(value)?),
+                "Enabled" => enabled = get_patch_bool(value, "Enabled")?,
+                _ => {
+                    let error = unknown(name.clone());
+                    return Err(Error::InvalidProperties(vec![error.into_message()]));
+                }
+            }
+        }
+        let missing = |name: &str| {
+            let error = PatchError {
+                key: "PropertyMissing",
+                path: String::from(name),
+                value: Value::Null,
+            };
+            Error::InvalidProperties(vec![error.into_message()])
+        };
+        let account = LocalAccount {
+            username: account_username.ok_or_else(|| missing("UserName"))?,
+            password: password.ok_or_else(|| missing("Password"))?,
+            role_id: role_id.ok_or_else(|| missing("RoleId"))?,
+            enabled,
+        };
+        let uri = format!("{}/{}", ACCOUNTS_URI, account.username);
+        directory.accounts.push(account);
+        Ok(uri)
+    }
+
+    // Users who may not configure users may still change their own password.
+    fn patch_account(
+        &self,
+        account_username: &str,
+        body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<(), Error> {
+        if !self.may_configure_users(username)
+            && (username != Some(account_username) || body.keys().any(|name| name != "Password"))
+        {
+            return Err(Error::Forbidden);
+        }
+        let mut directory = self.directory.write().unwrap();
+        let mut account = directory
+            .get_account(account_username)
+            .ok_or(Error::NotFound)?
+            .clone();
+        let mut unlock = false;
+        for (name, value) in body {
+            match name.as_str() {
+                "Password" => account.password = directory.hash_password(value)?,
+                "RoleId" => account.role_id = directory.check_role(value)?,
+                "Enabled" => account.enabled = get_patch_bool(value, "Enabled")?,
+                // Only the service locks accounts.
+                "Locked" => match value {
+                    Value::Bool(false) => unlock = true,
+                    Value::Bool(true) => {
+                        return Err(get_patch_error("PropertyValueNotInList", "Locked", value))
+                    }
+                    value => {
+                        return Err(get_patch_error("PropertyValueTypeError", "Locked", value))
+                    }
+                },
+                "UserName" | "Id" | "AccountTypes" => {
+                    return Err(get_patch_error("PropertyNotWritable", name, value))
+                }
+                _ => {
+                    let error = unknown(name.clone());
+                    return Err(Error::InvalidProperties(vec![error.into_message()]));
+                }
+            }
+        }
+        if let Some(existing) = directory
+            .accounts
+            .iter_mut()
+            .find(|existing| existing.username == account_username)
+        {
+            *existing = account;
+        }
+        drop(directory);
+        if let (true, Some(lockouts)) = (unlock, &self.lockouts) {
+            lockouts.unlock(account_username);
+        }
+        Ok(())
+    }
+
+    fn patch_service(
+        &self,
+        body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<(), Error> {
+        if !self.may_configure_users(username) {
+            return Err(Error::Forbidden);
+        }
+        let mut lockout = Map::new();
+        for (name, value) in body {
+            if LOCKOUT_PROPERTIES.contains(&name.as_str()) {
+                if !value.is_u64() {
+                    return Err(get_patch_error("PropertyValueTypeError", name, value));
+                }
+                lockout.insert(name.clone(), value.clone());
+                continue;
+            }
+            let is_read_only = self
+                .get_node(ACCOUNT_SERVICE_URI)
+                .is_some_and(|node| node.body.get(name).is_some() || name.starts_with("@odata."));
+            let key = match is_read_only {
+                true => "PropertyNotWritable",
+                false => "PropertyUnknown",
+            };
+            let path = String::from(name);
+            let error = PatchError {
+                key,
+                path,
+                value: Value::Null,
+            };
+            return Err(Error::InvalidProperties(vec![error.into_message()]));
+        }
+        self.directory.write().unwrap().lockout.extend(lockout);
+        Ok(())
+    }
+
+    fn delete(&self, account_username: &str, username: Option<&str>) -> Result<(), Error> {
+        if !self.may_configure_users(username) {
+            return Err(Error::Forbidden);
+        }
+        let mut directory = self.directory.write().unwrap();
+        let index = directory
+            .accounts
+            .iter()
+            .position(|account| account.username == account_username)
+            .ok_or(Error::NotFound)?;
+        directory.accounts.remove(index);
+        drop(directory);
+        // An account made again with the name starts afresh.
+        if let Some(lockouts) = &self.lockouts {
+            lockouts.unlock(account_username);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuthenticationProvider for LocalAccounts {
+    // Hashing is slow by design, so it is done off the async runtime. Unknown and disabled users
+    // are refused only once a password has been checked, so that how long it takes does not
+    // tell clients which user names have accounts.
+    async fn authenticate(&self, username: &str, password: &str) -> bool {
+        let account = self.get_account(username).filter(|account| account.enabled);
+        let (config, dummy) = {
+            let directory = self.directory.read().unwrap();
+            let dummy = directory.dummy_password.clone();
+            (directory.password_hashing.clone(), dummy)
+        };
+        let dummy = match dummy {
+            Some(dummy) => dummy,
+            None => {
+                let hashing = config.clone();
+                let dummy = tokio::task::spawn_blocking(move || PasswordHash::new("", &hashing))
+                    .await
+                    .unwrap();
+                let mut directory = self.directory.write().unwrap();
+                if directory.password_hashing == config {
+                    directory.dummy_password = Some(dummy.clone());
+                }
+                dummy
+            }
+        };
+        let password = String::from(password);
+        let rehashed = tokio::task::spawn_blocking(move || {
+            let account = match account {
+                Some(account) => account,
+                None => {
+                    dummy.verify(&password);
+                    return None;
+                }
+            };
+            if !account.password.verify(&password) {
+                return None;
+            }
+            match account.password.needs_rehash(&config) {
+                true => Some(Some(PasswordHash::new(&password, &config))),
+                false => Some(None),
+            }
+        })
+        .await
+        .ok()
+        .flatten();
+        match rehashed {
+            None => false,
+            Some(None) => true,
+            Some(Some(hash)) => {
+                let mut directory = self.directory.write().unwrap();
+                if let Some(account) = directory
+                    .accounts
+                    .iter_mut()
+                    .find(|account| account.username == username)
+                {
+                    account.password = hash;
+                }
+                true
+            }
+        }
+    }
+
+    async fn get_privileges(&self, username: &str) -> Option<Vec<String>> {
+        let directory = self.directory.read().unwrap();
+        let account = directory.get_account(username)?;
+        let role = directory.get_role(&account.role_id)?;
+        Some(role.privileges.clone())
+    }
+
+    async fn get_role(&self, username: &str) -> Option<String> {
+        let directory = self.directory.read().unwrap();
+        Some(directory.get_account(username)?.role_id.clone())
+    }
+}
+
+fn get_member_id<'a>(uri: &'a str, collection_uri: &str) -> Option<&'a str> {
+    let id = uri.strip_prefix(collection_uri)?.strip_prefix('/')?;
+    match id.contains('/') {
+        true => None,
+        false => Some(id),
+    }
+}
+
+fn get_collection_body(uri: &str, collection_type: &str, name: &str, members: &[String]) -> Value {
+    let members: Vec<Value> = members
+        .iter()
+        .map(|member| json!({"@odata.id": member}))
+        .collect();
+    json!({
+        "@odata.id": uri,
+        "@odata.type": format!("#{}.{}", collection_type, collection_type),
+        "Name": name,
+        "Members@odata.count": members.len(),
+        "Members": members,
+    })
+}
+
+fn get_account_service_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("AccountService"),
+        ResourceSchemaVersion::new(1, 12, 0),
+    )
+}
+
+fn get_account_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("ManagerAccount"),
+        ResourceSchemaVersion::new(1, 10, 0),
+    )
+}
+
+fn get_role_type() -> ResourceType {
+    ResourceType::new_dmtf(String::from("Role"), ResourceSchemaVersion::new(1, 3, 1))
+}
+
+fn get_account_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("ManagerAccountCollection"))
+}
+
+fn get_role_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("RoleCollection"))
+}
+
+// The AccountService, one of its collections, or one of their members, as of when it was made.
+// Its ETag is a hash of its body, so that clients can make conditional PATCH requests of
+// accounts, as the spec requires services to support.
+pub(crate) struct AccountNode {
+    uri: String,
+    body: Value,
+    etag: EntityTag,
+    allowed_methods: AllowedMethods,
+    described_by: SchemaUrl,
+    required: Vec<String>,
+}
+
+impl AccountNode {
+    fn new(
+        uri: &str,
+        mut body: Value,
+        allowed_methods: AllowedMethods,
+        described_by: SchemaUrl,
+    ) -> Self {
+        let etag = compute_etag(body.as_object().unwrap());
+        body["@odata.etag"] = Value::String(etag.to_string());
+        Self {
+            uri: String::from(uri),
+            body,
+            etag,
+            allowed_methods,
+            described_by,
+            required: Vec::new(),
+        }
+    }
+}
+
+impl Node for AccountNode {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        self.allowed_methods
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        Some(self.etag.clone())
+    }
+
+    fn get_required_create_properties(&self) -> Vec<String> {
+        self.required.clone()
+    }
+}
+
+// A tree that serves the AccountService, with its accounts and roles, from LocalAccounts, and
+// everything else from a fallback tree. Clients create accounts by POST to the collection of
+// accounts, change their Password, RoleId and Enabled by PATCH, and remove them by DELETE, as
+// well as setting the lockout properties of the AccountService. The accounts should also be
+// the AuthenticationProvider of the service, for their users to log in. Only authenticated
+// users may read any of it. The service root of the fallback should link to the AccountService.
+pub struct AccountServiceTree<T: Tree + Send + Sync> {
+    fallback: T,
+    accounts: LocalAccounts,
+    collection_types: Vec<CollectionType>,
+    resource_types: Vec<ResourceType>,
+    // The last node made for create() or patch(), which is lent to the caller.
+    last_node: Option<Arc<dyn Node + Send + Sync>>,
+}
+
+impl<T: Tree + Send + Sync> AccountServiceTree<T> {
+    pub fn new(fallback: T, accounts: LocalAccounts) -> Self {
+        let mut tree = Self {
+            fallback,
+            accounts,
+            collection_types: Vec::new(),
+            resource_types: Vec::new(),
+            last_node: None,
+        };
+        tree.update_types();
+        tree
+    }
+
+    fn update_types(&mut self) {
+        self.collection_types = self.fallback.get_collection_types().to_vec();
+        self.resource_types = self.fallback.get_resource_types().to_vec();
+        for collection_type in [get_account_collection_type(), get_role_collection_type()] {
+            if !self.collection_types.contains(&collection_type) {
+                self.collection_types.push(collection_type);
+            }
+        }
+        for resource_type in [
+            get_account_service_type(),
+            get_account_type(),
+            get_role_type(),
+        ] {
+            if !self
+                .resource_types
+                .iter()
+                .any(|existing| existing.name == resource_type.name)
+            {
+                self.resource_types.push(resource_type);
+            }
+        }
+    }
+
+    fn is_served(uri: &str) -> bool {
+        match uri.strip_prefix(ACCOUNT_SERVICE_URI) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+
+    // The error for a method that the node at the URI does not allow, if there is one.
+    fn refuse(&self, uri: &str) -> Error {
+        match self.accounts.get_node(uri) {
+            Some(node) => Error::MethodNotAllowed(node.allowed_methods),
+            None => Error::NotFound,
+        }
+    }
+
+    fn lend(&mut self, uri: &str) -> Result<&dyn Node, Error> {
+        let node = self.accounts.get_node(uri).ok_or(Error::NotFound)?;
+        self.last_node = Some(Arc::new(node));
+        Ok(self.last_node.as_deref().unwrap())
+    }
+}
+
+#[async_trait]
+impl<T: Tree + Send + Sync> Tree for AccountServiceTree<T> {
+    async fn on_start(&mut self) -> Result<(), Error> {
+        self.fallback.on_start().await?;
+        // Starting may have added types to the fallback.
+        self.update_types();
+        Ok(())
+    }
+
+    async fn on_shutdown(&mut self) {
+        self.fallback.on_shutdown().await
+    }
+
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        self.fallback.get_link_registry()
+    }
+
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.get(uri, username).await;
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        let node = self.accounts.get_node(uri).ok_or(Error::NotFound)?;
+        // Those who may not configure users may only read their own account.
+        if let Some(account_username) = get_member_id(uri, ACCOUNTS_URI) {
+            if account_username != username.unwrap() && !self.accounts.may_configure_users(username)
+            {
+                return Err(Error::Forbidden);
+            }
+        }
+        Ok(NodeRef::Shared(Arc::new(node)))
+    }
+
+    async fn create(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if Self::is_served(uri) {
+            if username.is_none() {
+                return Err(Error::Unauthorized);
+            }
+            if uri != ACCOUNTS_URI {
+                return Err(self.refuse(uri));
+            }
+            let member_uri = self.accounts.create(request_body, username)?;
+            return self.lend(&member_uri);
+        }
+        let node = self.fallback.create(uri, request_body, username).await?;
+        let member_uri = String::from(node.get_uri());
+        // Creating may have added types to the fallback. Sessions are read as the user logging
+        // in, who is not yet authenticated.
+        self.update_types();
+        let reader = username.or(request_body.get("UserName").and_then(Value::as_str));
+        match self.fallback.get(&member_uri, reader).await? {
+            NodeRef::Borrowed(node) => Ok(node),
+            NodeRef::Shared(node) => {
+                self.last_node = Some(node);
+                Ok(self.last_node.as_deref().unwrap())
+            }
+        }
+    }
+
+    async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        if !Self::is_served(uri) {
+            self.fallback.delete(uri, username).await?;
+            self.update_types();
+            return Ok(());
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        match get_member_id(uri, ACCOUNTS_URI) {
+            Some(account_username) => self.accounts.delete(account_username, username),
+            None => Err(self.refuse(uri)),
+        }
+    }
+
+    async fn patch(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.patch(uri, request_body, username).await;
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        if uri == ACCOUNT_SERVICE_URI {
+            self.accounts.patch_service(request_body, username)?;
+        } else if let Some(account_username) = get_member_id(uri, ACCOUNTS_URI) {
+            self.accounts
+                .patch_account(account_username, request_body, username)?;
+        } else {
+            return Err(self.refuse(uri));
+        }
+        self.lend(uri)
+    }
+
+    async fn action(
+        &mut self,
+        uri: &str,
+        action: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        if Self::is_served(uri) {
+            return match username {
+                None => Err(Error::Unauthorized),
+                Some(_) => Err(Error::ActionNotSupported(String::from(action))),
+            };
+        }
+        self.fallback
+            .action(uri, action, request_body, username)
+            .await
+    }
+
+    fn can_invoke_action(&self, uri: &str, action: &str, username: Option<&str>) -> bool {
+        match Self::is_served(uri) {
+            false => self.fallback.can_invoke_action(uri, action, username),
+            true => true,
+        }
+    }
+
+    fn is_session_collection(&self, uri: &str) -> bool {
+        !Self::is_served(uri) && self.fallback.is_session_collection(uri)
+    }
+
+    async fn upload(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        if Self::is_served(uri) {
+            return Err(Error::UnsupportedMediaType);
+        }
+        self.fallback.upload(uri, username).await
+    }
+
+    fn get_collection_types(&self) -> &[CollectionType] {
+        &self.collection_types
+    }
+
+    fn get_resource_types(&self) -> &[ResourceType] {
+        &self.resource_types
+    }
+}
@@ -0,0 +1,303 @@
+use crate::registries::base_registry;
+use crate::{AuthenticationProvider, Error};
+use redfish_data::{Message, ResourceSchemaVersion};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use strum::{Display, EnumString};
+
+pub(crate) const ACCOUNT_SERVICE_URI: &str = "/redfish/v1/AccountService";
+
+// An external account provider of the AccountService, by the name of its property there.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum ExternalAccountProviderType {
+    LDAP,
+    ActiveDirectory,
+}
+
+// Gives the users of an external account provider, or those in one of its groups, a local
+// role, such as Operator.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoleMapping {
+    pub remote_group: Option<String>,
+    pub remote_user: Option<String>,
+    pub local_role: String,
+}
+
+impl RoleMapping {
+    fn to_json(&self) -> Value {
+        json!({
+            "RemoteGroup": self.remote_group,
+            "RemoteUser": self.remote_user,
+            "LocalRole": self.local_role,
+        })
+    }
+}
+
+// The configuration of an external account provider, such as an LDAP server, as the LDAP and
+// ActiveDirectory properties of the AccountService have it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExternalAccountProvider {
+    pub service_enabled: bool,
+    // Such as ldaps://ldap.example.com:636.
+    pub service_addresses: Vec<String>,
+    // Such as UsernameAndPassword, with those to bind to the provider with.
+    pub authentication_type: Option<String>,
+    pub username: Option<String>,
+    // Clients may set this, and never read it back.
+    pub password: Option<String>,
+    pub remote_role_mapping: Vec<RoleMapping>,
+}
+
+// Why a patch of an external account provider, or of a local account, was refused: the key of
+// the Base message, and the path and value of the property it is about, such as
+// LDAP/ServiceAddresses.
+pub(crate) struct PatchError {
+    pub(crate) key: &'static str,
+    pub(crate) path: String,
+    pub(crate) value: Value,
+}
+
+impl PatchError {
+    fn type_error(path: String, value: &Value) -> Self {
+        Self {
+            key: "PropertyValueTypeError",
+            path,
+            value: value.clone(),
+        }
+    }
+
+    pub(crate) fn into_message(self) -> Message {
+        let value = match self.value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        let args = match self.key {
+            "PropertyValueTypeError"
+            | "PropertyValueNotInList"
+            | "PropertyValueFormatError"
+            | "PropertyValueOutOfRange" => {
+                vec![value, self.path.clone()]
+            }
+            _ => vec![self.path.clone()],
+        };
+        Message::from_registry(
+            base_registry(),
+            self.key,
+            ResourceSchemaVersion::new(1, 1, 2),
+            args,
+            vec![format!("#/{}", self.path)],
+        )
+        .unwrap()
+    }
+}
+
+pub(crate) fn get_string(value: &Value, path: String) -> Result<String, PatchError> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        value => Err(PatchError::type_error(path, value)),
+    }
+}
+
+// Null clears the property.
+fn get_optional_string(value: &Value, path: String) -> Result<Option<String>, PatchError> {
+    match value {
+        Value::Null => Ok(None),
+        value => get_string(value, path).map(Some),
+    }
+}
+
+fn get_object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>, PatchError> {
+    value
+        .as_object()
+        .ok_or_else(|| PatchError::type_error(String::from(path), value))
+}
+
+fn get_array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, PatchError> {
+    value
+        .as_array()
+        .ok_or_else(|| PatchError::type_error(String::from(path), value))
+}
+
+pub(crate) fn unknown(path: String) -> PatchError {
+    PatchError {
+        key: "PropertyUnknown",
+        path,
+        value: Value::Null,
+    }
+}
+
+fn get_role_mapping(value: &Value, path: &str) -> Result<RoleMapping, PatchError> {
+    let mut mapping = RoleMapping::default();
+    let mut has_local_role = false;
+    for (name, value) in get_object(value, path)? {
+        let property = format!("{}/{}", path, name);
+        match name.as_str() {
+            "RemoteGroup" => mapping.remote_group = get_optional_string(value, property)?,
+            "RemoteUser" => mapping.remote_user = get_optional_string(value, property)?,
+            "LocalRole" => {
+                mapping.local_role = get_string(value, property)?;
+                has_local_role = true;
+            }
+            _ => return Err(unknown(property)),
+        }
+    }
+    match has_local_role {
+        true => Ok(mapping),
+        false => Err(PatchError {
+            key: "PropertyMissing",
+            path: format!("{}/LocalRole", path),
+            value: Value::Null,
+        }),
+    }
+}
+
+impl ExternalAccountProvider {
+    // The property of the AccountService for the provider. The Password is always null.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "ServiceEnabled": self.service_enabled,
+            "ServiceAddresses": self.service_addresses,
+            "Authentication": {
+                "AuthenticationType": self.authentication_type,
+                "Username": self.username,
+                "Password": null,
+            },
+            "RemoteRoleMapping": self
+                .remote_role_mapping
+                .iter()
+                .map(RoleMapping::to_json)
+                .collect::<Vec<Value>>(),
+        })
+    }
+
+    // Apply the value a client patched the property of the provider with, such as
+    // {"ServiceAddresses": ["ldaps://ldap.example.com"]}, leaving what it does not mention as
+    // it was. Arrays are replaced whole.
+    fn patch(&mut self, patch: &Value, path: &str) -> Result<(), PatchError> {
+        for (name, value) in get_object(patch, path)? {
+            let property = format!("{}/{}", path, name);
+            match name.as_str() {
+                "ServiceEnabled" => {
+                    self.service_enabled = value
+                        .as_bool()
+                        .ok_or_else(|| PatchError::type_error(property, value))?;
+                }
+                "ServiceAddresses" => {
+                    self.service_addresses = get_array(value, &property)?
+                        .iter()
+                        .enumerate()
+                        .map(|(index, address)| {
+                            get_string(address, format!("{}/{}", property, index))
+                        })
+                        .collect::<Result<Vec<String>, PatchError>>()?;
+                }
+                "Authentication" => self.patch_authentication(value, &property)?,
+                "RemoteRoleMapping" => {
+                    self.remote_role_mapping = get_array(value, &property)?
+                        .iter()
+                        .enumerate()
+                        .map(|(index, mapping)| {
+                            get_role_mapping(mapping, &format!("{}/{}", property, index))
+                        })
+                        .collect::<Result<Vec<RoleMapping>, PatchError>>()?;
+                }
+                _ => return Err(unknown(property)),
+            }
+        }
+        Ok(())
+    }
+
+    fn patch_authentication(&mut self, patch: &Value, path: &str) -> Result<(), PatchError> {
+        for (name, value) in get_object(patch, path)? {
+            let property = format!("{}/{}", path, name);
+            match name.as_str() {
+                "AuthenticationType" => {
+                    self.authentication_type = get_optional_string(value, property)?
+                }
+                "Username" => self.username = get_optional_string(value, property)?,
+                "Password" => self.password = get_optional_string(value, property)?,
+                _ => return Err(unknown(property)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// The external account providers the service models in the AccountService, for clients to
+// configure by patching it. Cloning gives another handle to the same providers.
+#[derive(Clone, Default)]
+pub(crate) struct ExternalAccounts {
+    providers: Arc<RwLock<Vec<(ExternalAccountProviderType, ExternalAccountProvider)>>>,
+}
+
+impl ExternalAccounts {
+    pub(crate) fn new(provider_types: &[ExternalAccountProviderType]) -> Self {
+        let providers = provider_types
+            .iter()
+            .map(|provider_type| (*provider_type, ExternalAccountProvider::default()))
+            .collect();
+        Self {
+            providers: Arc::new(RwLock::new(providers)),
+        }
+    }
+
+    // Add the properties of the providers to the body of the AccountService, if it is that.
+    pub(crate) fn add_properties(&self, uri: &str, body: &mut Map<String, Value>) {
+        if uri != ACCOUNT_SERVICE_URI {
+            return;
+        }
+        for (provider_type, provider) in self.providers.read().unwrap().iter() {
+            body.insert(provider_type.to_string(), provider.to_json());
+        }
+    }
+
+    // Take the properties of the providers out of a patch of the resource at the URI, and
+    // return the providers as they would be with them applied. Nothing is applied yet, so
+    // that a patch the tree refuses leaves them as they were.
+    pub(crate) fn take_patches(
+        &self,
+        uri: &str,
+        payload: &mut Map<String, Value>,
+    ) -> Result<Vec<(ExternalAccountProviderType, ExternalAccountProvider)>, Error> {
+        if uri != ACCOUNT_SERVICE_URI {
+            return Ok(Vec::new());
+        }
+        let mut patched = Vec::new();
+        for (provider_type, provider) in self.providers.read().unwrap().iter() {
+            let name = provider_type.to_string();
+            if let Some(patch) = payload.remove(&name) {
+                let mut provider = provider.clone();
+                provider
+                    .patch(&patch, &name)
+                    .map_err(|error| Error::InvalidProperties(vec![error.into_message()]))?;
+                patched.push((*provider_type, provider));
+            }
+        }
+        Ok(patched)
+    }
+
+    // Apply the providers take_patches() returned, once the rest of the patch has been.
+    pub(crate) fn apply(&self, patched: &[(ExternalAccountProviderType, ExternalAccountProvider)]) {
+        let mut providers = self.providers.write().unwrap();
+        for (provider_type, provider) in patched {
+            if let Some((_, existing)) = providers
+                .iter_mut()
+                .find(|(existing_type, _)| existing_type == provider_type)
+            {
+                *existing = provider.clone();
+            }
+        }
+    }
+}
+
+// Give the authentication provider the providers that were applied, to use from then on.
+pub(crate) async fn configure_external_providers(
+    authentication_provider: &dyn AuthenticationProvider,
+    applied: Vec<(ExternalAccountProviderType, ExternalAccountProvider)>,
+) {
+    for (provider_type, provider) in applied {
+        authentication_provider
+            .configure_external_provider(provider_type, &provider)
+            .await;
+    }
+}
@@ -0,0 +1,56 @@
+use crate::Tree;
+use serde_json::{json, Map, Value};
+
+// How the service shows the actions of a resource that the user cannot invoke, as
+// Tree::can_invoke_action() decides, so that clients do not offer them only to be refused.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ActionVisibility {
+    // Leave them out of its Actions.
+    #[default]
+    Hidden,
+    // Keep them, with empty AllowableValues for each of their parameters that has any, so that
+    // clients see there is nothing they may invoke them with. Those without are still hidden.
+    Annotated,
+}
+
+// Empty the AllowableValues of the parameters of the action, and return whether it had any.
+fn annotate(action: &mut Map<String, Value>) -> bool {
+    let mut annotated = false;
+    for (name, value) in action.iter_mut() {
+        if name.ends_with("@Redfish.AllowableValues") {
+            *value = json!([]);
+            annotated = true;
+        }
+    }
+    annotated
+}
+
+// Hide or annotate the actions in the body of the resource at the URI that the user cannot
+// invoke, which is all of them if their privileges do not allow POST to it. Members of the
+// Actions that are not actions, such as Oem, are left as they are.
+pub(crate) fn restrict_actions(
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    username: Option<&str>,
+    may_post: bool,
+    visibility: ActionVisibility,
+    body: &mut Value,
+) {
+    let actions = match body.get_mut("Actions").and_then(Value::as_object_mut) {
+        Some(actions) => actions,
+        None => return,
+    };
+    actions.retain(|name, action| {
+        let name = match name.strip_prefix('#') {
+            Some(name) => name,
+            None => return true,
+        };
+        if may_post && tree.can_invoke_action(uri, name, username) {
+            return true;
+        }
+        match (visibility, action.as_object_mut()) {
+            (ActionVisibility::Annotated, Some(action)) => annotate(action),
+            _ => false,
+        }
+    });
+}
@@ -0,0 +1,316 @@
+use crate::registries::session_event_registry;
+use crate::{Node, NodeRef};
+use etag::EntityTag;
+use redfish_data::{
+    get_datetime_string, get_resource_odata_type, get_uri_id, AllowedMethods, CollectionType,
+    Message, ResourceSchemaVersion, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditAction {
+    SessionCreated,
+    SessionTerminated,
+    SessionExpired,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub timestamp: SystemTime,
+    pub action: AuditAction,
+    // The user the audited action applies to.
+    pub username: String,
+    // The URI of the resource the audited action applies to.
+    pub uri: String,
+    // The IP address of the client that caused the action, if known.
+    pub source: Option<IpAddr>,
+    // The ID of the request that caused the action, if it was caused by one.
+    pub request_id: Option<String>,
+}
+
+// Receives an entry for every security-relevant action taken by the service.
+pub trait AuditHook: Send + Sync {
+    fn record(&self, entry: &AuditEntry);
+}
+
+// The message describing an action on a session, from the SessionEvent registry.
+pub(crate) fn get_session_message(
+    action: AuditAction,
+    username: &str,
+    source: Option<IpAddr>,
+) -> Message {
+    let key = match action {
+        AuditAction::SessionCreated => "SessionCreated",
+        AuditAction::SessionTerminated => "SessionTerminated",
+        AuditAction::SessionExpired => "SessionExpired",
+    };
+    let source_name = match source {
+        Some(ip) => ip.to_string(),
+        None => String::from("an unknown address"),
+    };
+    Message::from_registry(
+        session_event_registry(),
+        key,
+        ResourceSchemaVersion::new(1, 1, 2),
+        vec![String::from(username), source_name],
+        Vec::new(),
+    )
+    .unwrap()
+}
+
+struct AuditLogEntries {
+    // Increases with every entry, so that the Ids of entries are never reused.
+    next_id: u64,
+    entries: VecDeque<(u64, AuditEntry)>,
+}
+
+// An audit hook that keeps the audit trail as a LogService, such as at
+// /redfish/v1/Managers/BMC/LogServices/AuditLog, holding the newest max_entries entries.
+// The tree serves its resources by returning them from get_node(), and clients can use
+// $filter on its entries, such as Created ge '2024-01-01T00:00:00+00:00' for a time range,
+// or Originator eq 'admin' for the actions on a user's sessions.
+// Cloning gives another handle to the same log.
+#[derive(Clone)]
+pub struct AuditLog {
+    uri: Arc<str>,
+    max_entries: usize,
+    entries: Arc<Mutex<AuditLogEntries>>,
+}
+
+impl AuditLog {
+    pub fn new(uri: &str, max_entries: usize) -> Self {
+        Self {
+            uri: Arc::from(uri),
+            max_entries,
+            entries: Arc::new(Mutex::new(AuditLogEntries {
+                next_id: 1,
+                entries: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    // The LogService, its Entries collection, or one of its entries, if the URI is of one.
+    pub fn get_node(&self, uri: &str) -> Option<NodeRef<'static>> {
+        let node: Arc<dyn Node + Send + Sync> = if uri == &*self.uri {
+            Arc::new(AuditLogService {
+                uri: String::from(uri),
+                entries_uri: self.get_entries_uri(),
+                max_entries: self.max_entries,
+                resource_type: get_log_service_type(),
+            })
+        } else if uri == self.get_entries_uri() {
+            let log = self.entries.lock().unwrap();
+            let members = log
+                .entries
+                .iter()
+                .map(|(id, _)| format!("{}/{}", uri, id))
+                .collect();
+            Arc::new(AuditLogEntryCollection {
+                uri: String::from(uri),
+                members,
+                collection_type: get_log_entry_collection_type(),
+            })
+        } else {
+            let id = uri
+                .strip_prefix(&self.get_entries_uri())?
+                .strip_prefix('/')?
+                .parse::<u64>()
+                .ok()?;
+            let log = self.entries.lock().unwrap();
+            let (_, entry) = log.entries.iter().find(|(entry_id, _)| *entry_id == id)?;
+            Arc::new(AuditLogEntry {
+                uri: String::from(uri),
+                entry: entry.clone(),
+                resource_type: get_log_entry_type(),
+            })
+        };
+        Some(NodeRef::Shared(node))
+    }
+
+    // The types of the log's resources, for trees to include in $metadata.
+    pub fn get_resource_types(&self) -> Vec<ResourceType> {
+        vec![get_log_service_type(), get_log_entry_type()]
+    }
+
+    pub fn get_collection_types(&self) -> Vec<CollectionType> {
+        vec![get_log_entry_collection_type()]
+    }
+
+    fn get_entries_uri(&self) -> String {
+        format!("{}/Entries", self.uri)
+    }
+}
+
+impl AuditHook for AuditLog {
+    fn record(&self, entry: &AuditEntry) {
+        let mut log = self.entries.lock().unwrap();
+        let id = log.next_id;
+        log.next_id += 1;
+        log.entries.push_back((id, entry.clone()));
+        // The oldest entries are overwritten once the log is full.
+        while log.entries.len() > self.max_entries {
+            log.entries.pop_front();
+        }
+    }
+}
+
+fn get_log_service_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("LogService"),
+        ResourceSchemaVersion::new(1, 5, 0),
+    )
+}
+
+fn get_log_entry_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("LogEntry"),
+        ResourceSchemaVersion::new(1, 15, 0),
+    )
+}
+
+fn get_log_entry_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("LogEntryCollection"))
+}
+
+fn get_read_only_methods() -> AllowedMethods {
+    AllowedMethods {
+        delete: false,
+        get: true,
+        patch: false,
+        post: false,
+    }
+}
+
+struct AuditLogService {
+    uri: String,
+    entries_uri: String,
+    max_entries: usize,
+    resource_type: ResourceType,
+}
+
+impl Node for AuditLogService {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        json!({
+            "@odata.id": self.uri,
+            "@odata.type": get_resource_odata_type("LogService", &self.resource_type.version, "LogService"),
+            "Id": get_uri_id(&self.uri),
+            "Name": "Audit Log",
+            "Description": "Actions taken on the sessions of the service.",
+            "Entries": { "@odata.id": self.entries_uri },
+            "LogEntryType": "Event",
+            "MaxNumberOfRecords": self.max_entries,
+            "OverWritePolicy": "WrapsWhenFull",
+            "ServiceEnabled": true,
+            "Status": { "State": "Enabled", "Health": "OK" },
+        })
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        get_read_only_methods()
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
+
+struct AuditLogEntryCollection {
+    uri: String,
+    members: Vec<String>,
+    collection_type: CollectionType,
+}
+
+impl Node for AuditLogEntryCollection {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        let members: Vec<Value> = self
+            .members
+            .iter()
+            .map(|member| json!({ "@odata.id": member }))
+            .collect();
+        json!({
+            "@odata.id": self.uri,
+            "@odata.type": "#LogEntryCollection.LogEntryCollection",
+            "Name": "Audit Log Entries",
+            "Members@odata.count": members.len(),
+            "Members": members,
+        })
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        get_read_only_methods()
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.collection_type.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
+
+struct AuditLogEntry {
+    uri: String,
+    entry: AuditEntry,
+    resource_type: ResourceType,
+}
+
+impl Node for AuditLogEntry {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        let entry = &self.entry;
+        let message = get_session_message(entry.action, &entry.username, entry.source).to_json();
+        json!({
+            "@odata.id": self.uri,
+            "@odata.type": get_resource_odata_type("LogEntry", &self.resource_type.version, "LogEntry"),
+            "Id": get_uri_id(&self.uri),
+            "Name": "Audit Log Entry",
+            "EntryType": "Event",
+            "Created": get_datetime_string(entry.timestamp),
+            "Severity": message["MessageSeverity"],
+            "Message": message["Message"],
+            "MessageId": message["MessageId"],
+            "MessageArgs": message["MessageArgs"],
+            "Originator": entry.username,
+            "OriginatorType": "Client",
+            "Links": {
+                "OriginOfCondition": { "@odata.id": entry.uri },
+            },
+        })
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        get_read_only_methods()
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
@@ -0,0 +1,456 @@
+use crate::lockout::get_lockout_policy;
+use crate::privileges::set_current_user;
+use crate::sessions::Session;
+use crate::{
+    record_session_action, AppState, AuditAction, Clock, Error, ExternalAccountProvider,
+    ExternalAccountProviderType, SESSION_SERVICE_URI,
+};
+use async_trait::async_trait;
+use http::HeaderMap;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Checks the credentials a client presents with Basic authentication, or the UserName and
+// Password it logs in with. Providers that keep accounts themselves can keep their passwords
+// as a redfish_data::PasswordHash, as LocalAccounts does.
+#[async_trait]
+pub trait AuthenticationProvider: Send + Sync {
+    // Return true if the password is correct for the given user.
+    async fn authenticate(&self, username: &str, password: &str) -> bool;
+
+    // Given the configuration of an external account provider, such as an LDAP server, each
+    // time a client changes it through the AccountService, for authenticating its users with.
+    // See AppBuilder::external_account_providers().
+    async fn configure_external_provider(
+        &self,
+        _provider_type: ExternalAccountProviderType,
+        _provider: &ExternalAccountProvider,
+    ) {
+    }
+
+    // The privileges of the user, such as Login and ConfigureComponents, as their role gives
+    // them, for the service to authorize their requests by its privilege registry. None, as
+    // by default, leaves what the user may do to the tree. See AppBuilder::privilege_registry().
+    async fn get_privileges(&self, _username: &str) -> Option<Vec<String>> {
+        None
+    }
+
+    // The Id of the user's role, such as Administrator, for trees to read from current_user().
+    async fn get_role(&self, _username: &str) -> Option<String> {
+        None
+    }
+}
+
+// Used when no AuthenticationProvider is configured.
+// TODO: Stop accepting any credentials by default?
+pub(crate) struct AcceptAllCredentials;
+
+#[async_trait]
+impl AuthenticationProvider for AcceptAllCredentials {
+    async fn authenticate(&self, _username: &str, _password: &str) -> bool {
+        true
+    }
+}
+
+// Limits how quickly a client address can guess Basic auth credentials.
+// Every failed attempt from an address delays the response to it, doubling each time.
+// Once an address reaches max_failures it is blocked: all of its Basic auth attempts fail
+// until block_duration has passed, even with the right credentials.
+#[derive(Clone, Debug)]
+pub struct AuthThrottleConfig {
+    pub max_failures: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub block_duration: Duration,
+    // Failures are forgotten once an address has gone this long without failing.
+    pub failure_window: Duration,
+}
+
+impl Default for AuthThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            block_duration: Duration::from_secs(300),
+            failure_window: Duration::from_secs(600),
+        }
+    }
+}
+
+// Who may read the service documents, $metadata and the OData service document.
+// Redfish requires them to be public, so that clients can find out about the service before
+// logging in, but closed deployments may not want to reveal the schemas they implement.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DocumentAccess {
+    #[default]
+    Public,
+    Authenticated,
+}
+
+// Return the user reading a service document, or Error::Unauthorized if they may not.
+// Credentials are checked even when anyone may read it, as for any other request.
+pub(crate) async fn check_document_access(
+    headers: &HeaderMap,
+    state: &AppState,
+    source: Option<IpAddr>,
+) -> Result<Option<String>, Error> {
+    let user = get_request_username(headers, state, source).await?;
+    if user.is_none() && state.document_access == DocumentAccess::Authenticated {
+        return Err(Error::Unauthorized);
+    }
+    Ok(user)
+}
+
+// What to do when a user who already has the most sessions they may have logs in again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionLimitPolicy {
+    // Refuse the login with SessionLimitExceeded.
+    Reject,
+    // Let the login through, and terminate the user's oldest session.
+    EvictOldest,
+}
+
+// What becomes of the sessions clients already have when one disables the SessionService, by
+// patching its ServiceEnabled to false. Logins are refused with ServiceDisabled either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DisabledSessionPolicy {
+    // Their tokens keep working.
+    #[default]
+    Keep,
+    // Their tokens are refused until the SessionService is enabled again, so clients have to
+    // use Basic authentication to enable it.
+    Suspend,
+    // They are terminated.
+    Terminate,
+}
+
+// The ServiceEnabled of the SessionService, as the user reads it. A SessionService without
+// one, or that the user cannot read, is enabled.
+pub(crate) async fn is_session_service_enabled(state: &AppState, username: Option<&str>) -> bool {
+    let tree = state.tree.read().await;
+    let enabled = match tree.get(SESSION_SERVICE_URI, username).await {
+        Ok(node) => node
+            .try_get_body()
+            .ok()
+            .and_then(|body| body.get("ServiceEnabled").cloned()),
+        Err(_) => None,
+    };
+    enabled != Some(Value::Bool(false))
+}
+
+// Restricts how the token of a session may be used, beyond it being a session's.
+// By default any client may use a token for as long as its session lasts.
+// TODO: Also allow binding tokens to the TLS session they were issued over. The service is
+// not told about TLS, so the TLS layer would have to give it something to identify that by.
+#[derive(Clone, Debug, Default)]
+pub struct SessionTokenConfig {
+    // Only accept the token from the IP address of the client that logged in.
+    pub bind_to_client_ip: bool,
+    // Stop accepting the token once this long has passed since logging in, even while its
+    // session goes on.
+    pub token_ttl: Option<Duration>,
+}
+
+// Limits how many sessions a single user may have open at once.
+#[derive(Clone, Debug)]
+pub struct SessionLimitConfig {
+    pub max_sessions_per_user: usize,
+    pub policy: SessionLimitPolicy,
+}
+
+// Return the URIs of the sessions to terminate for the user to log in again, oldest first,
+// or Error::SessionLimitExceeded if they may not.
+pub(crate) fn check_session_limit(state: &AppState, username: &str) -> Result<Vec<String>, Error> {
+    let config = match &state.session_limit {
+        Some(config) => config,
+        None => return Ok(Vec::new()),
+    };
+    // Sessions are kept in the order they were created.
+    let sessions: Vec<String> = state
+        .sessions
+        .store()
+        .list()
+        .iter()
+        .filter(|session| session.username == username)
+        .map(|session| session.uri.clone())
+        .collect();
+    let excess = (sessions.len() + 1).saturating_sub(config.max_sessions_per_user);
+    if excess == 0 {
+        return Ok(Vec::new());
+    }
+    match config.policy {
+        SessionLimitPolicy::Reject => Err(Error::SessionLimitExceeded),
+        SessionLimitPolicy::EvictOldest => Ok(sessions.into_iter().take(excess).collect()),
+    }
+}
+
+struct FailureRecord {
+    count: u32,
+    last_failure: Instant,
+    blocked_until: Option<Instant>,
+}
+
+// Clients whose address is unknown share a single record.
+pub(crate) struct AuthThrottle {
+    config: AuthThrottleConfig,
+    failures: Mutex<HashMap<Option<IpAddr>, FailureRecord>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AuthThrottle {
+    pub(crate) fn new(config: AuthThrottleConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    fn is_blocked(&self, source: Option<IpAddr>) -> bool {
+        match self.failures.lock().unwrap().get(&source) {
+            Some(FailureRecord {
+                blocked_until: Some(until),
+                ..
+            }) => self.clock.now() < *until,
+            _ => false,
+        }
+    }
+
+    // Record a failed attempt and return how long to delay the response to it.
+    fn record_failure(&self, source: Option<IpAddr>) -> Duration {
+        let now = self.clock.now();
+        let mut failures = self.failures.lock().unwrap();
+        // Forget about addresses that stopped failing, so that this cannot grow without bound.
+        failures.retain(|_, record| {
+            now.duration_since(record.last_failure) < self.config.failure_window
+                || record.blocked_until.is_some_and(|until| now < until)
+        });
+        let record = failures.entry(source).or_insert(FailureRecord {
+            count: 0,
+            last_failure: now,
+            blocked_until: None,
+        });
+        if record.blocked_until.is_some_and(|until| now >= until) {
+            record.count = 0;
+            record.blocked_until = None;
+        }
+        record.count += 1;
+        record.last_failure = now;
+        if record.count >= self.config.max_failures {
+            record.blocked_until = Some(now + self.config.block_duration);
+        }
+        let factor = 2u32.saturating_pow(record.count - 1);
+        self.config
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.config.max_delay)
+    }
+
+    fn record_success(&self, source: Option<IpAddr>) {
+        self.failures.lock().unwrap().remove(&source);
+    }
+}
+
+// The SessionTimeout of the SessionService, as the user reads it, if the tree has one.
+async fn get_session_timeout(state: &AppState, username: &str) -> Option<Duration> {
+    let tree = state.tree.read().await;
+    let node = tree.get(SESSION_SERVICE_URI, Some(username)).await.ok()?;
+    let timeout = node.try_get_body().ok()?.get("SessionTimeout")?.as_u64()?;
+    Some(Duration::from_secs(timeout))
+}
+
+// Remove the sessions that have been idle for the timeout or longer, from the tree too.
+async fn expire_sessions(state: &AppState, timeout: Duration) {
+    let now = state.clock.system_time();
+    let expired: Vec<Session> = state.sessions.remove_where(|session| {
+        now.duration_since(session.last_activity)
+            .is_ok_and(|idle| idle >= timeout)
+    });
+    if expired.is_empty() {
+        return;
+    }
+    let mut tree = state.tree.write().await;
+    for session in expired {
+        // The session is gone for the service even if the tree cannot delete its resource.
+        tree.delete(&session.uri, Some(&session.username))
+            .await
+            .ok();
+        record_session_action(state, AuditAction::SessionExpired, &session, None);
+        state.events.resource_removed(&session.uri);
+    }
+}
+
+// How often the sessions idle for the SessionTimeout are removed, by default, when no tokens
+// are presented to remove them sooner.
+pub(crate) const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+// Remove the expired sessions at every interval, so that they do not linger in the tree and
+// the store while no tokens are presented. It only runs within a tokio runtime, and then for
+// as long as the runtime does.
+pub(crate) fn sweep_sessions(state: AppState, interval: Duration) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    runtime.spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            // The service has no user of its own, so the SessionTimeout is read as the user of
+            // a session.
+            let Some(session) = state.sessions.store().list().into_iter().next() else {
+                continue;
+            };
+            if let Some(timeout) = get_session_timeout(&state, &session.username).await {
+                expire_sessions(&state, timeout).await;
+            }
+        }
+    });
+}
+
+// Accepting the token counts as activity of its session.
+fn get_token_user(token: String, state: &AppState, source: Option<IpAddr>) -> Option<String> {
+    let mut session = state.sessions.store().find_by_token(&token)?;
+    let config = &state.session_tokens;
+    if config.bind_to_client_ip && (source.is_none() || session.source != source) {
+        return None;
+    }
+    if let Some(ttl) = config.token_ttl {
+        let now = state.clock.system_time();
+        if now
+            .duration_since(session.created)
+            .is_ok_and(|age| age >= ttl)
+        {
+            return None;
+        }
+    }
+    session.last_activity = state.clock.system_time();
+    let username = session.username.clone();
+    state.sessions.store().update(session);
+    Some(username)
+}
+
+// Check the Basic auth credentials in an authorization header, applying the throttle if any.
+async fn get_basic_auth_user(
+    header_val: &str,
+    state: &AppState,
+    source: Option<IpAddr>,
+) -> Result<String, Error> {
+    match http_auth_basic::Credentials::from_header(header_val.to_string()) {
+        Ok(credentials) => {
+            check_credentials(state, &credentials.user_id, &credentials.password, source).await?;
+            Ok(credentials.user_id)
+        }
+        Err(_) => {
+            if let Some(throttle) = &state.auth_throttle {
+                if !throttle.is_blocked(source) {
+                    tokio::time::sleep(throttle.record_failure(source)).await;
+                }
+            }
+            Err(Error::Unauthorized)
+        }
+    }
+}
+
+// Check the credentials of a user, such as those of Basic auth or of a login, applying the
+// throttle if any. Accounts that are locked out fail even with the right credentials. The tree
+// must not be locked, as the lockout policy is read from it.
+pub(crate) async fn check_credentials(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    source: Option<IpAddr>,
+) -> Result<(), Error> {
+    if let Some(throttle) = &state.auth_throttle {
+        if throttle.is_blocked(source) {
+            return Err(Error::Unauthorized);
+        }
+    }
+    if state.lockouts.is_locked(username) {
+        return Err(Error::AccountLocked);
+    }
+    if state
+        .authentication_provider
+        .authenticate(username, password)
+        .await
+    {
+        if let Some(throttle) = &state.auth_throttle {
+            throttle.record_success(source);
+        }
+        state.lockouts.record_success(username);
+        return Ok(());
+    }
+    let mut error = Error::Unauthorized;
+    if let Some(policy) = get_lockout_policy(state, username).await {
+        if state.lockouts.record_failure(username, &policy) {
+            tracing::warn!("locked account {} after failing to authenticate", username);
+            error = Error::AccountLocked;
+        }
+    }
+    if let Some(throttle) = &state.auth_throttle {
+        tokio::time::sleep(throttle.record_failure(source)).await;
+    }
+    Err(error)
+}
+
+// Parse credentials from request. If bad credentials, return Error.
+// If no credentials, return Ok(None).
+// If credentials check out, return Ok(Some(username)).
+// The source is the IP address of the client, if known.
+// This may take a while if the client has been failing to authenticate,
+// so it must not be called while holding a lock on the tree.
+// The user is then the current_user() of the request.
+pub(crate) async fn get_request_username(
+    headers: &HeaderMap,
+    state: &AppState,
+    source: Option<IpAddr>,
+) -> Result<Option<String>, Error> {
+    let username = authenticate_request(headers, state, source).await?;
+    if let Some(username) = &username {
+        set_current_user(state, username).await;
+    }
+    Ok(username)
+}
+
+async fn authenticate_request(
+    headers: &HeaderMap,
+    state: &AppState,
+    source: Option<IpAddr>,
+) -> Result<Option<String>, Error> {
+    match headers.get("x-auth-token") {
+        Some(token) => {
+            let token = token.to_str().map_err(|_| Error::Unauthorized)?.to_string();
+            let username = state
+                .sessions
+                .store()
+                .find_by_token(&token)
+                .map(|session| session.username);
+            // Expired sessions are collected whenever a token is presented, those of other
+            // users included.
+            if let Some(username) = username {
+                if let Some(timeout) = get_session_timeout(state, &username).await {
+                    expire_sessions(state, timeout).await;
+                }
+                if state.disabled_sessions == DisabledSessionPolicy::Suspend
+                    && !is_session_service_enabled(state, Some(&username)).await
+                {
+                    return Err(Error::Unauthorized);
+                }
+            }
+            match get_token_user(token, state, source) {
+                None => Err(Error::Unauthorized),
+                Some(user) => Ok(Some(user)),
+            }
+        }
+        None => match headers.get("authorization") {
+            None => Ok(None),
+            Some(header_val) => match header_val.to_str() {
+                Err(_) => Err(Error::Unauthorized),
+                Ok(header_val) => Ok(Some(get_basic_auth_user(header_val, state, source).await?)),
+            },
+        },
+    }
+}
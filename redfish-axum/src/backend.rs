@@ -0,0 +1,205 @@
+use crate::{Error, LinkRegistry, Node, NodeRef, Outcome, Tree, UploadWriter};
+use async_trait::async_trait;
+use redfish_data::{CollectionType, ResourceType};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+// Serves a read-only subtree from a source that does not speak Redfish, such as IPMI,
+// by materializing each node only when a request asks for it.
+#[async_trait]
+pub trait BackendAdapter: Send + Sync {
+    // URI of the root of the subtree, such as /redfish/v1/Chassis.
+    fn get_root(&self) -> &str;
+
+    // Make the node at the given URI, which is the root or below it, from the backend.
+    // Return Error::NotFound if the backend has nothing there.
+    async fn materialize(&self, uri: &str) -> Result<Arc<dyn Node + Send + Sync>, Error>;
+
+    fn get_collection_types(&self) -> Vec<CollectionType>;
+
+    fn get_resource_types(&self) -> Vec<ResourceType>;
+}
+
+// A tree that serves the subtrees of its adapters, and everything else from a fallback tree.
+// Only authenticated users may read the subtrees of adapters, and nobody may modify them.
+pub struct BackendTree<T: Tree + Send + Sync> {
+    fallback: T,
+    adapters: Vec<Box<dyn BackendAdapter>>,
+    collection_types: Vec<CollectionType>,
+    resource_types: Vec<ResourceType>,
+    // The last node the fallback made for create(), which it lends to the caller.
+    last_node: Option<Arc<dyn Node + Send + Sync>>,
+}
+
+impl<T: Tree + Send + Sync> BackendTree<T> {
+    pub fn new(fallback: T) -> Self {
+        let mut tree = Self {
+            fallback,
+            adapters: Vec::new(),
+            collection_types: Vec::new(),
+            resource_types: Vec::new(),
+            last_node: None,
+        };
+        tree.update_types();
+        tree
+    }
+
+    pub fn with_adapter(mut self, adapter: impl BackendAdapter + 'static) -> Self {
+        self.adapters.push(Box::new(adapter));
+        self.update_types();
+        self
+    }
+
+    fn update_types(&mut self) {
+        self.collection_types = self.fallback.get_collection_types().to_vec();
+        self.resource_types = self.fallback.get_resource_types().to_vec();
+        for adapter in self.adapters.iter() {
+            for collection_type in adapter.get_collection_types() {
+                if !self.collection_types.contains(&collection_type) {
+                    self.collection_types.push(collection_type);
+                }
+            }
+            for resource_type in adapter.get_resource_types() {
+                if !self.resource_types.contains(&resource_type) {
+                    self.resource_types.push(resource_type);
+                }
+            }
+        }
+    }
+
+    fn get_adapter(&self, uri: &str) -> Option<&dyn BackendAdapter> {
+        self.adapters
+            .iter()
+            .find(|adapter| {
+                let root = adapter.get_root();
+                match uri.strip_prefix(root) {
+                    Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                    None => false,
+                }
+            })
+            .map(|adapter| adapter.as_ref())
+    }
+
+    // Nodes of adapters can only be read.
+    async fn check_read_only(&self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        let node = self.get(uri, username).await?;
+        Err(Error::MethodNotAllowed(node.get_meta().allowed_methods))
+    }
+}
+
+#[async_trait]
+impl<T: Tree + Send + Sync> Tree for BackendTree<T> {
+    async fn on_start(&mut self) -> Result<(), Error> {
+        self.fallback.on_start().await?;
+        // Starting may have added types to the fallback.
+        self.update_types();
+        Ok(())
+    }
+
+    async fn on_shutdown(&mut self) {
+        self.fallback.on_shutdown().await
+    }
+
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        self.fallback.get_link_registry()
+    }
+
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+        match self.get_adapter(uri) {
+            None => self.fallback.get(uri, username).await,
+            Some(_) if username.is_none() => Err(Error::Unauthorized),
+            Some(adapter) => Ok(NodeRef::Shared(adapter.materialize(uri).await?)),
+        }
+    }
+
+    async fn create(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if self.get_adapter(uri).is_some() {
+            self.check_read_only(uri, username).await?;
+        }
+        let node = self.fallback.create(uri, request_body, username).await?;
+        let member_uri = String::from(node.get_uri());
+        // Creating may have added types to the fallback.
+        self.update_types();
+        match self.fallback.get(&member_uri, username).await? {
+            NodeRef::Borrowed(node) => Ok(node),
+            NodeRef::Shared(node) => {
+                self.last_node = Some(node);
+                Ok(self.last_node.as_deref().unwrap())
+            }
+        }
+    }
+
+    async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        if self.get_adapter(uri).is_some() {
+            self.check_read_only(uri, username).await?;
+        }
+        self.fallback.delete(uri, username).await?;
+        self.update_types();
+        Ok(())
+    }
+
+    async fn patch(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if self.get_adapter(uri).is_some() {
+            self.check_read_only(uri, username).await?;
+        }
+        self.fallback.patch(uri, request_body, username).await
+    }
+
+    async fn action(
+        &mut self,
+        uri: &str,
+        action: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        if self.get_adapter(uri).is_some() {
+            return match username {
+                None => Err(Error::Unauthorized),
+                Some(_) => Err(Error::ActionNotSupported(String::from(action))),
+            };
+        }
+        self.fallback
+            .action(uri, action, request_body, username)
+            .await
+    }
+
+    fn can_invoke_action(&self, uri: &str, action: &str, username: Option<&str>) -> bool {
+        match self.get_adapter(uri) {
+            None => self.fallback.can_invoke_action(uri, action, username),
+            Some(_) => true,
+        }
+    }
+
+    fn is_session_collection(&self, uri: &str) -> bool {
+        self.get_adapter(uri).is_none() && self.fallback.is_session_collection(uri)
+    }
+
+    async fn upload(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        if self.get_adapter(uri).is_some() {
+            return Err(Error::UnsupportedMediaType);
+        }
+        self.fallback.upload(uri, username).await
+    }
+
+    fn get_collection_types(&self) -> &[CollectionType] {
+        &self.collection_types
+    }
+
+    fn get_resource_types(&self) -> &[ResourceType] {
+        &self.resource_types
+    }
+}
@@ -0,0 +1,131 @@
+use crate::clock::SystemClock;
+use crate::{BackendAdapter, Clock, Error, EventService, Node};
+use async_trait::async_trait;
+use redfish_data::{CollectionType, ResourceType};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+type Entries = Mutex<HashMap<String, (Instant, Arc<dyn Node + Send + Sync>)>>;
+
+// Handle to the nodes cached by a CachingAdapter, for invalidating them when they change.
+// Cloning gives another handle to the same cache.
+#[derive(Clone)]
+pub struct NodeCache {
+    entries: Arc<Entries>,
+}
+
+impl NodeCache {
+    // Forget the node at the given URI, the nodes below it, and the collection above it,
+    // whose members may have changed along with it.
+    pub fn invalidate(&self, uri: &str) {
+        invalidate(&self.entries, uri);
+    }
+
+    pub fn invalidate_all(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    // Invalidate the OriginOfCondition of every event sent through the service, such as
+    // those of EventService::resource_changed(). This has to be called within a tokio runtime,
+    // and keeps listening for as long as the cache exists.
+    pub fn invalidate_on_events(&self, events: &EventService) {
+        let weak = Arc::downgrade(&self.entries);
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let event = receiver.recv().await;
+                let entries = match weak.upgrade() {
+                    Some(entries) => entries,
+                    None => return,
+                };
+                match event {
+                    Ok(event) => {
+                        for record in event.get_records() {
+                            if let Some(origin) = record.get_origin_of_condition() {
+                                invalidate(&entries, origin);
+                            }
+                        }
+                    }
+                    // Some events were missed, so anything may have changed.
+                    Err(RecvError::Lagged(_)) => entries.lock().unwrap().clear(),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+fn invalidate(entries: &Entries, uri: &str) {
+    let below = format!("{}/", uri);
+    let above = uri.rsplit_once('/').map(|(parent, _)| parent);
+    entries.lock().unwrap().retain(|cached, _| {
+        cached != uri && !cached.starts_with(&below) && Some(cached.as_str()) != above
+    });
+}
+
+// Wraps an adapter whose backend is expensive to query, such as for PCIe inventory,
+// so that each node is only materialized on first access and then again once it is
+// older than the TTL, or has been invalidated through the cache.
+pub struct CachingAdapter<A: BackendAdapter> {
+    adapter: A,
+    ttl: Duration,
+    cache: NodeCache,
+    clock: Arc<dyn Clock>,
+}
+
+impl<A: BackendAdapter> CachingAdapter<A> {
+    pub fn new(adapter: A, ttl: Duration) -> Self {
+        Self {
+            adapter,
+            ttl,
+            cache: NodeCache {
+                entries: Arc::new(Mutex::new(HashMap::new())),
+            },
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Nodes age by the system's clock by default. Give the service's, such as a ManualClock,
+    // for TTLs to expire with it.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn get_cache(&self) -> NodeCache {
+        self.cache.clone()
+    }
+}
+
+#[async_trait]
+impl<A: BackendAdapter> BackendAdapter for CachingAdapter<A> {
+    fn get_root(&self) -> &str {
+        self.adapter.get_root()
+    }
+
+    async fn materialize(&self, uri: &str) -> Result<Arc<dyn Node + Send + Sync>, Error> {
+        if let Some((fetched, node)) = self.cache.entries.lock().unwrap().get(uri) {
+            if self.clock.now().duration_since(*fetched) < self.ttl {
+                return Ok(node.clone());
+            }
+        }
+        // Errors such as NotFound are not cached, so that new nodes show up straight away.
+        let node = self.adapter.materialize(uri).await?;
+        self.cache
+            .entries
+            .lock()
+            .unwrap()
+            .insert(String::from(uri), (self.clock.now(), node.clone()));
+        Ok(node)
+    }
+
+    fn get_collection_types(&self) -> Vec<CollectionType> {
+        self.adapter.get_collection_types()
+    }
+
+    fn get_resource_types(&self) -> Vec<ResourceType> {
+        self.adapter.get_resource_types()
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// The time as the service sees it, for expiring session tokens, blocking clients that fail to
+// authenticate, and timestamping tasks, events and audit entries. Services use the system's
+// clock by default. Tests can use a ManualClock instead, to move time along without sleeping.
+pub trait Clock: Send + Sync {
+    // For measuring how long has passed, such as since logging in.
+    fn now(&self) -> Instant;
+    // For timestamps that clients see, such as the StartTime of a task.
+    fn system_time(&self) -> SystemTime;
+}
+
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// A clock that stands still from when it was made until it is advanced.
+// Cloning gives another handle to the same clock.
+#[derive(Clone)]
+pub struct ManualClock {
+    instant: Instant,
+    system_time: SystemTime,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self {
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.instant + *self.elapsed.lock().unwrap()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.system_time + *self.elapsed.lock().unwrap()
+    }
+}
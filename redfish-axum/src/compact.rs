@@ -0,0 +1,106 @@
+use serde_json::{Map, Number, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Object keys are interned, so that the nodes of a big tree share one copy of each
+// property name, such as @odata.id or Status, rather than each holding its own.
+// Interned keys are never freed, as there are only so many property names.
+type Key = Arc<str>;
+
+fn intern(key: &str) -> Key {
+    static KEYS: OnceLock<Mutex<HashSet<Key>>> = OnceLock::new();
+    let mut keys = KEYS.get_or_init(Default::default).lock().unwrap();
+    if let Some(key) = keys.get(key) {
+        return key.clone();
+    }
+    let key: Key = Arc::from(key);
+    keys.insert(key.clone());
+    key
+}
+
+// Like a serde_json::Value, but smaller, and without the spare capacity of growing it.
+#[derive(Clone, Debug, PartialEq)]
+enum CompactValue {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(Box<str>),
+    Array(Box<[CompactValue]>),
+    Object(CompactBody),
+}
+
+impl From<Value> for CompactValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(value) => Self::Bool(value),
+            Value::Number(value) => Self::Number(value),
+            Value::String(value) => Self::String(value.into_boxed_str()),
+            Value::Array(values) => Self::Array(values.into_iter().map(Self::from).collect()),
+            Value::Object(object) => Self::Object(CompactBody::from(object)),
+        }
+    }
+}
+
+impl From<&CompactValue> for Value {
+    fn from(value: &CompactValue) -> Self {
+        match value {
+            CompactValue::Null => Value::Null,
+            CompactValue::Bool(value) => Value::Bool(*value),
+            CompactValue::Number(value) => Value::Number(value.clone()),
+            CompactValue::String(value) => Value::String(String::from(&**value)),
+            CompactValue::Array(values) => Value::Array(values.iter().map(Value::from).collect()),
+            CompactValue::Object(object) => Value::Object(object.to_map()),
+        }
+    }
+}
+
+// A JSON object for holding the bodies of many nodes in memory, as on constrained devices.
+// It is converted to and from a Map at the edges, when the node is read or patched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactBody {
+    properties: Box<[(Key, CompactValue)]>,
+}
+
+impl CompactBody {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.properties
+            .iter()
+            .find(|(name, _)| &**name == key)
+            .map(|(_, value)| Value::from(value))
+    }
+
+    // Set a property, replacing any value it already has.
+    pub fn insert(&mut self, key: &str, value: Value) {
+        let value = CompactValue::from(value);
+        if let Some((_, existing)) = self.properties.iter_mut().find(|(name, _)| &**name == key) {
+            *existing = value;
+            return;
+        }
+        let mut properties = std::mem::take(&mut self.properties).into_vec();
+        properties.push((intern(key), value));
+        self.properties = properties.into_boxed_slice();
+    }
+
+    pub fn to_map(&self) -> Map<String, Value> {
+        self.properties
+            .iter()
+            .map(|(key, value)| (String::from(&**key), Value::from(value)))
+            .collect()
+    }
+}
+
+impl From<Map<String, Value>> for CompactBody {
+    fn from(object: Map<String, Value>) -> Self {
+        Self {
+            properties: object
+                .into_iter()
+                .map(|(key, value)| (intern(&key), CompactValue::from(value)))
+                .collect(),
+        }
+    }
+}
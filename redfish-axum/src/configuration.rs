@@ -0,0 +1,121 @@
+use crate::{Error, Task, UploadWriter};
+use async_trait::async_trait;
+use axum::body::StreamBody;
+use bytes::Bytes;
+use futures_util::stream::{self, Stream};
+use redfish_data::Health;
+use serde_json::{json, Map, Value};
+use std::io;
+use std::sync::Arc;
+
+pub(crate) const BACKUP_ACTION: &str = "Manager.BackupConfiguration";
+pub(crate) const RESTORE_ACTION: &str = "Manager.RestoreConfiguration";
+
+// Hands out an archive of the configuration a chunk at a time, as it is made, so that the whole
+// archive is never held in memory at once.
+#[async_trait]
+pub trait ArchiveReader: Send {
+    // The next chunk of the archive, or None once all of it has been read.
+    async fn read(&mut self) -> Result<Option<Bytes>, Error>;
+}
+
+// Where the configuration of the service is snapshotted and restored from, for the
+// BackupConfiguration and RestoreConfiguration actions of its manager. Archives are in the
+// store's own format, which clients only download and upload again as they are.
+#[async_trait]
+pub trait ConfigurationStore: Send + Sync {
+    // A snapshot of the configuration as it is now.
+    async fn snapshot(&self) -> Result<Box<dyn ArchiveReader>, Error>;
+
+    // Where an uploaded archive is written as it arrives. Its finish() can refuse an archive
+    // the store cannot restore, before anything is changed.
+    async fn receive(&self) -> Result<Box<dyn UploadWriter>, Error>;
+
+    // Replace the configuration with that of the archive last received.
+    async fn restore(&self) -> Result<(), Error>;
+}
+
+// The actions of one manager, and the store they are carried out with.
+#[derive(Clone)]
+pub(crate) struct ConfigurationBackup {
+    manager_uri: String,
+    store: Arc<dyn ConfigurationStore>,
+}
+
+impl ConfigurationBackup {
+    pub(crate) fn new(manager_uri: &str, store: Arc<dyn ConfigurationStore>) -> Self {
+        Self {
+            manager_uri: String::from(manager_uri),
+            store,
+        }
+    }
+
+    pub(crate) fn get_manager_uri(&self) -> &str {
+        &self.manager_uri
+    }
+
+    pub(crate) fn get_target(&self, action: &str) -> String {
+        format!("{}/Actions/Oem/{}", self.manager_uri, action)
+    }
+
+    // List the actions in the Oem actions of the manager's body. The tree does not know of them,
+    // as the service carries them out itself.
+    pub(crate) fn add_actions(&self, uri: &str, body: &mut Map<String, Value>) {
+        if uri != self.manager_uri {
+            return;
+        }
+        let actions = body
+            .entry("Actions")
+            .or_insert_with(|| json!({}))
+            .as_object_mut();
+        let oem = match actions {
+            Some(actions) => actions.entry("Oem").or_insert_with(|| json!({})),
+            None => return,
+        };
+        if let Value::Object(oem) = oem {
+            for action in [BACKUP_ACTION, RESTORE_ACTION] {
+                let target = json!({"target": self.get_target(action)});
+                oem.insert(format!("#{}", action), target);
+            }
+        }
+    }
+
+    // The body of the download of a snapshot, streamed from the store as it reads it. An error
+    // partway through can only be reported by cutting the download short.
+    pub(crate) async fn snapshot(
+        &self,
+    ) -> Result<StreamBody<impl Stream<Item = Result<Bytes, io::Error>>>, Error> {
+        let reader = self.store.snapshot().await?;
+        let chunks = stream::unfold(Some(reader), |reader| async move {
+            let mut reader = reader?;
+            match reader.read().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(reader))),
+                Ok(None) => None,
+                Err(error) => {
+                    let error = io::Error::other(format!("{:?}", error));
+                    Some((Err(error), None))
+                }
+            }
+        });
+        Ok(StreamBody::new(chunks))
+    }
+
+    pub(crate) async fn receive(&self) -> Result<Box<dyn UploadWriter>, Error> {
+        self.store.receive().await
+    }
+
+    // Restore the archive received, completing the task with how it went.
+    pub(crate) fn restore_as_task(&self, task: Task) {
+        let store = self.store.clone();
+        tokio::spawn(async move {
+            match store.restore().await {
+                Ok(()) => task.complete(Health::OK, None),
+                Err(_) => task.complete(Health::Critical, None),
+            }
+        });
+    }
+
+    pub(crate) async fn restore(&self) -> Result<(), Error> {
+        self.store.restore().await
+    }
+}
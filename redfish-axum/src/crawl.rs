@@ -0,0 +1,80 @@
+use crate::events::{EVENT_SERVICE_URI, SSE_URI};
+use crate::tasks::TASK_SERVICE_URI;
+use crate::{Error, NodeMeta, Tree};
+use redfish_data::get_resource_links;
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+
+pub(crate) const SERVICE_ROOT_URI: &str = "/redfish/v1";
+
+// URIs that the service serves itself, so links to them are not looked up in the tree.
+const SERVICE_URIS: [&str; 5] = [
+    "/redfish/v1/$metadata",
+    "/redfish/v1/odata",
+    EVENT_SERVICE_URI,
+    SSE_URI,
+    TASK_SERVICE_URI,
+];
+
+pub(crate) enum Crawled {
+    Found {
+        uri: String,
+        meta: NodeMeta,
+        body: Value,
+    },
+    // The resource at `from` links to `uri`, which the tree does not have. For the ServiceRoot,
+    // which nothing links to, `from` is empty.
+    Missing {
+        from: String,
+        uri: String,
+    },
+}
+
+// Walks the resources of a tree the way a client would, from the ServiceRoot through the links
+// in each body, as read by the user. Resources the user cannot read, or that fail to read, are
+// passed over, as are non-JSON ones.
+pub(crate) struct Crawler<'a> {
+    tree: &'a (dyn Tree + Send + Sync),
+    username: Option<&'a str>,
+    seen: HashSet<String>,
+    queue: VecDeque<(String, String)>,
+}
+
+impl<'a> Crawler<'a> {
+    pub(crate) fn new(tree: &'a (dyn Tree + Send + Sync), username: Option<&'a str>) -> Self {
+        Self {
+            tree,
+            username,
+            seen: HashSet::from([String::from(SERVICE_ROOT_URI)]),
+            queue: VecDeque::from([(String::from(SERVICE_ROOT_URI), String::new())]),
+        }
+    }
+
+    pub(crate) async fn next(&mut self) -> Option<Crawled> {
+        while let Some((uri, from)) = self.queue.pop_front() {
+            let node = match self.tree.get(&uri, self.username).await {
+                Ok(node) => node,
+                Err(Error::NotFound) => return Some(Crawled::Missing { from, uri }),
+                Err(_) => continue,
+            };
+            if node.get_raw_body().is_some() {
+                continue;
+            }
+            let body = match node.try_get_body() {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+            let meta = node.get_meta();
+            for link in get_resource_links(&body) {
+                if link == uri || SERVICE_URIS.contains(&link.as_str()) || self.seen.contains(&link)
+                {
+                    continue;
+                }
+                self.seen.insert(link.clone());
+                self.queue.push_back((link, uri.clone()));
+            }
+            return Some(Crawled::Found { uri, meta, body });
+        }
+        None
+    }
+}
@@ -0,0 +1,471 @@
+use crate::registries::base_registry;
+use crate::tree_builder::StaticNode;
+use crate::{Error, LinkRegistry, Node, NodeRef, Outcome, Tree, UploadWriter};
+use async_trait::async_trait;
+use redfish_data::{AllowedMethods, CollectionType, Message, ResourceSchemaVersion, ResourceType};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+// The calls the adapter makes on the bus. Implement it on top of a D-Bus library to front
+// an existing, OpenBMC-style, BMC stack. Values are D-Bus variants, represented as JSON.
+#[async_trait]
+pub trait DBusConnection: Send + Sync {
+    // Paths of the objects directly below the given one, as from the ObjectMapper.
+    async fn get_child_paths(&self, object_path: &str) -> Result<Vec<String>, Error>;
+
+    async fn get_property(
+        &self,
+        object_path: &str,
+        interface: &str,
+        property: &str,
+    ) -> Result<Value, Error>;
+
+    async fn set_property(
+        &self,
+        object_path: &str,
+        interface: &str,
+        property: &str,
+        value: &Value,
+    ) -> Result<(), Error>;
+
+    async fn call_method(
+        &self,
+        object_path: &str,
+        interface: &str,
+        method: &str,
+        args: Vec<Value>,
+    ) -> Result<(), Error>;
+}
+
+// A Redfish property backed by a D-Bus property.
+#[derive(Clone)]
+pub struct DBusProperty {
+    pub name: String,
+    pub interface: String,
+    pub property: String,
+    pub writable: bool,
+}
+
+impl DBusProperty {
+    pub fn new(name: &str, interface: &str, property: &str) -> Self {
+        Self {
+            name: String::from(name),
+            interface: String::from(interface),
+            property: String::from(property),
+            writable: false,
+        }
+    }
+
+    // Let PATCH requests set the D-Bus property.
+    pub fn writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+}
+
+// A Redfish action, such as ComputerSystem.Reset, backed by a D-Bus method.
+// The method is called with the values of the action's parameters, in the order given.
+#[derive(Clone)]
+pub struct DBusAction {
+    pub name: String,
+    pub interface: String,
+    pub method: String,
+    pub parameters: Vec<String>,
+}
+
+impl DBusAction {
+    pub fn new(name: &str, interface: &str, method: &str, parameters: Vec<String>) -> Self {
+        Self {
+            name: String::from(name),
+            interface: String::from(interface),
+            method: String::from(method),
+            parameters,
+        }
+    }
+}
+
+// Maps a collection onto the children of a D-Bus object path. Each child object is a
+// member of the collection, whose Id is the last segment of the object's path.
+pub struct DBusSubtree {
+    collection_uri: String,
+    collection_name: String,
+    collection_type: CollectionType,
+    object_path: String,
+    resource_type: ResourceType,
+    properties: Vec<DBusProperty>,
+    actions: Vec<DBusAction>,
+}
+
+impl DBusSubtree {
+    pub fn new(
+        collection_uri: &str,
+        collection_name: &str,
+        collection_type: CollectionType,
+        object_path: &str,
+        resource_type: ResourceType,
+    ) -> Self {
+        Self {
+            collection_uri: String::from(collection_uri),
+            collection_name: String::from(collection_name),
+            collection_type,
+            object_path: String::from(object_path),
+            resource_type,
+            properties: Vec::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn with_property(mut self, property: DBusProperty) -> Self {
+        self.properties.push(property);
+        self
+    }
+
+    pub fn with_action(mut self, action: DBusAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    fn get_member_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: self.properties.iter().any(|property| property.writable),
+            post: false,
+        }
+    }
+
+    fn get_object_path(&self, id: &str) -> String {
+        format!("{}/{}", self.object_path, id)
+    }
+}
+
+enum DBusTarget<'a> {
+    Collection(&'a DBusSubtree),
+    Member(&'a DBusSubtree, &'a str),
+}
+
+fn get_base_message(key: &str, message_args: Vec<String>, related: &str) -> Message {
+    Message::from_registry(
+        base_registry(),
+        key,
+        ResourceSchemaVersion::new(1, 1, 2),
+        message_args,
+        vec![format!("#/{}", related)],
+    )
+    .unwrap()
+}
+
+// A tree that serves the subtrees mapped onto D-Bus by reading and writing the bus on every
+// request, and everything else, such as the service root, from a fallback tree.
+// GET reads properties, PATCH writes them, and POST to a member's Actions calls methods.
+pub struct DBusTree<T: Tree + Send + Sync> {
+    connection: Arc<dyn DBusConnection>,
+    subtrees: Vec<DBusSubtree>,
+    fallback: T,
+    collection_types: Vec<CollectionType>,
+    resource_types: Vec<ResourceType>,
+    // The last node made by create() or patch(), which they lend to the caller.
+    last_node: Option<Arc<dyn Node + Send + Sync>>,
+}
+
+impl<T: Tree + Send + Sync> DBusTree<T> {
+    pub fn new(connection: impl DBusConnection + 'static, fallback: T) -> Self {
+        let mut tree = Self {
+            connection: Arc::new(connection),
+            subtrees: Vec::new(),
+            fallback,
+            collection_types: Vec::new(),
+            resource_types: Vec::new(),
+            last_node: None,
+        };
+        tree.update_types();
+        tree
+    }
+
+    pub fn with_subtree(mut self, subtree: DBusSubtree) -> Self {
+        self.subtrees.push(subtree);
+        self.update_types();
+        self
+    }
+
+    fn update_types(&mut self) {
+        self.collection_types = self.fallback.get_collection_types().to_vec();
+        self.resource_types = self.fallback.get_resource_types().to_vec();
+        for subtree in self.subtrees.iter() {
+            if !self.collection_types.contains(&subtree.collection_type) {
+                self.collection_types.push(subtree.collection_type.clone());
+            }
+            if !self.resource_types.contains(&subtree.resource_type) {
+                self.resource_types.push(subtree.resource_type.clone());
+            }
+        }
+    }
+
+    fn get_target<'a>(&'a self, uri: &'a str) -> Option<DBusTarget<'a>> {
+        for subtree in self.subtrees.iter() {
+            if uri == subtree.collection_uri {
+                return Some(DBusTarget::Collection(subtree));
+            }
+            let rest = match uri.strip_prefix(&format!("{}/", subtree.collection_uri)) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            return match rest.contains('/') || rest.is_empty() {
+                true => None,
+                false => Some(DBusTarget::Member(subtree, rest)),
+            };
+        }
+        None
+    }
+
+    fn lend(&mut self, node: Arc<dyn Node + Send + Sync>) -> Result<&dyn Node, Error> {
+        self.last_node = Some(node);
+        Ok(self.last_node.as_deref().unwrap())
+    }
+
+    async fn read_collection(&self, subtree: &DBusSubtree) -> Result<StaticNode, Error> {
+        let mut members = Vec::new();
+        for path in self
+            .connection
+            .get_child_paths(&subtree.object_path)
+            .await?
+        {
+            if let Some(id) = path.rsplit('/').next() {
+                members.push(format!("{}/{}", subtree.collection_uri, id));
+            }
+        }
+        Ok(StaticNode::collection(
+            &subtree.collection_uri,
+            &subtree.collection_name,
+            subtree.collection_type.clone(),
+            members,
+            None,
+        ))
+    }
+
+    async fn read_member(&self, subtree: &DBusSubtree, id: &str) -> Result<StaticNode, Error> {
+        let uri = format!("{}/{}", subtree.collection_uri, id);
+        let object_path = subtree.get_object_path(id);
+        let mut body = Map::new();
+        for property in subtree.properties.iter() {
+            let value = self
+                .connection
+                .get_property(&object_path, &property.interface, &property.property)
+                .await?;
+            body.insert(property.name.clone(), value);
+        }
+        if !subtree.actions.is_empty() {
+            let mut actions = Map::new();
+            for action in subtree.actions.iter() {
+                actions.insert(
+                    format!("#{}", action.name),
+                    json!({ "target": format!("{}/Actions/{}", uri, action.name) }),
+                );
+            }
+            body.insert(String::from("Actions"), Value::Object(actions));
+        }
+        Ok(StaticNode::resource(
+            &uri,
+            &format!("{} {}", subtree.resource_type.name, id),
+            subtree.resource_type.clone(),
+            subtree.get_member_allowed_methods(),
+            body,
+        ))
+    }
+}
+
+#[async_trait]
+impl<T: Tree + Send + Sync> Tree for DBusTree<T> {
+    async fn on_start(&mut self) -> Result<(), Error> {
+        self.fallback.on_start().await
+    }
+
+    async fn on_shutdown(&mut self) {
+        self.fallback.on_shutdown().await
+    }
+
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        self.fallback.get_link_registry()
+    }
+
+    fn is_session_collection(&self, uri: &str) -> bool {
+        self.get_target(uri).is_none() && self.fallback.is_session_collection(uri)
+    }
+
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+        let node = match self.get_target(uri) {
+            None => return self.fallback.get(uri, username).await,
+            Some(_) if username.is_none() => return Err(Error::Unauthorized),
+            Some(DBusTarget::Collection(subtree)) => self.read_collection(subtree).await?,
+            Some(DBusTarget::Member(subtree, id)) => self.read_member(subtree, id).await?,
+        };
+        Ok(NodeRef::Shared(Arc::new(node)))
+    }
+
+    async fn create(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        match self.get_target(uri) {
+            None => (),
+            Some(_) if username.is_none() => return Err(Error::Unauthorized),
+            Some(DBusTarget::Collection(_)) => {
+                return Err(Error::MethodNotAllowed(AllowedMethods {
+                    delete: false,
+                    get: true,
+                    patch: false,
+                    post: false,
+                }))
+            }
+            Some(DBusTarget::Member(subtree, _)) => {
+                return Err(Error::MethodNotAllowed(
+                    subtree.get_member_allowed_methods(),
+                ))
+            }
+        }
+        let node = self.fallback.create(uri, request_body, username).await?;
+        let member_uri = String::from(node.get_uri());
+        // Creating may have added types to the fallback.
+        self.update_types();
+        match self.fallback.get(&member_uri, username).await? {
+            NodeRef::Borrowed(node) => Ok(node),
+            NodeRef::Shared(node) => {
+                self.last_node = Some(node);
+                Ok(self.last_node.as_deref().unwrap())
+            }
+        }
+    }
+
+    async fn action(
+        &mut self,
+        uri: &str,
+        action: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        let (subtree, id) = match self.get_target(uri) {
+            None => {
+                return self
+                    .fallback
+                    .action(uri, action, request_body, username)
+                    .await
+            }
+            Some(_) if username.is_none() => return Err(Error::Unauthorized),
+            Some(DBusTarget::Member(subtree, id)) => (subtree, id),
+            Some(DBusTarget::Collection(_)) => {
+                return Err(Error::ActionNotSupported(String::from(action)))
+            }
+        };
+        let action = match subtree.actions.iter().find(|a| a.name == action) {
+            Some(action) => action,
+            None => return Err(Error::ActionNotSupported(String::from(action))),
+        };
+        let mut args = Vec::new();
+        let mut messages = Vec::new();
+        for parameter in action.parameters.iter() {
+            match request_body.get(parameter) {
+                Some(value) => args.push(value.clone()),
+                None => messages.push(get_base_message(
+                    "ActionParameterMissing",
+                    vec![action.name.clone(), parameter.clone()],
+                    parameter,
+                )),
+            }
+        }
+        if !messages.is_empty() {
+            return Err(Error::InvalidProperties(messages));
+        }
+        self.connection
+            .call_method(
+                &subtree.get_object_path(id),
+                &action.interface,
+                &action.method,
+                args,
+            )
+            .await?;
+        Ok(Outcome::Done(None))
+    }
+
+    async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        match self.get_target(uri) {
+            None => {
+                self.fallback.delete(uri, username).await?;
+                self.update_types();
+                Ok(())
+            }
+            Some(_) if username.is_none() => Err(Error::Unauthorized),
+            Some(DBusTarget::Member(subtree, _)) => Err(Error::MethodNotAllowed(
+                subtree.get_member_allowed_methods(),
+            )),
+            Some(_) => Err(Error::NotFound),
+        }
+    }
+
+    async fn patch(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        let (subtree, id) = match self.get_target(uri) {
+            None => return self.fallback.patch(uri, request_body, username).await,
+            Some(_) if username.is_none() => return Err(Error::Unauthorized),
+            Some(DBusTarget::Member(subtree, id)) => (subtree, id),
+            Some(_) => return Err(Error::NotFound),
+        };
+        // Check every property before writing any, so a bad request changes nothing.
+        let mut writes = Vec::new();
+        let mut messages = Vec::new();
+        for (name, value) in request_body {
+            match subtree
+                .properties
+                .iter()
+                .find(|property| &property.name == name)
+            {
+                Some(property) if property.writable => writes.push((property, value)),
+                Some(_) => messages.push(get_base_message(
+                    "PropertyNotWritable",
+                    vec![name.clone()],
+                    name,
+                )),
+                None => messages.push(get_base_message(
+                    "PropertyUnknown",
+                    vec![name.clone()],
+                    name,
+                )),
+            }
+        }
+        if !messages.is_empty() {
+            return Err(Error::InvalidProperties(messages));
+        }
+        let object_path = subtree.get_object_path(id);
+        for (property, value) in writes {
+            self.connection
+                .set_property(&object_path, &property.interface, &property.property, value)
+                .await?;
+        }
+        let node = self.read_member(subtree, id).await?;
+        self.lend(Arc::new(node))
+    }
+
+    async fn upload(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        match self.get_target(uri) {
+            None => self.fallback.upload(uri, username).await,
+            Some(_) => Err(Error::UnsupportedMediaType),
+        }
+    }
+
+    fn get_collection_types(&self) -> &[CollectionType] {
+        &self.collection_types
+    }
+
+    fn get_resource_types(&self) -> &[ResourceType] {
+        &self.resource_types
+    }
+}
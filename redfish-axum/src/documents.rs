@@ -0,0 +1,87 @@
+use crate::preconditions::{evaluate_preconditions, Precondition};
+use crate::Error;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use etag::EntityTag;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How clients may cache the service documents, $metadata and the OData service document.
+// Their responses carry an ETag and a Last-Modified for clients to revalidate them with
+// If-None-Match or If-Modified-Since, which the service answers with 304 Not Modified while
+// their copy is current.
+pub(crate) struct DocumentCaching {
+    // The documents rarely change while the service runs, so they are taken as last modified
+    // when it started. Clients that revalidate by ETag notice any change regardless.
+    last_modified: SystemTime,
+    // How long clients may use a document without revalidating it. None makes them revalidate
+    // every time.
+    max_age: Option<Duration>,
+}
+
+fn get_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+impl DocumentCaching {
+    pub(crate) fn new(last_modified: SystemTime, max_age: Option<Duration>) -> Self {
+        Self {
+            last_modified,
+            max_age,
+        }
+    }
+
+    // If-Modified-Since is only considered without If-None-Match, and to the second, as HTTP
+    // dates are.
+    fn is_unmodified_since(&self, headers: &HeaderMap) -> bool {
+        if headers.contains_key(header::IF_NONE_MATCH) {
+            return false;
+        }
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+            .is_some_and(|since| get_seconds(self.last_modified) <= get_seconds(since))
+    }
+
+    // The response with the document, or 304 Not Modified if the client's copy is current.
+    // Documents are only read, so a failed If-Match is the only way to get 412.
+    pub(crate) fn get_response(
+        &self,
+        headers: &HeaderMap,
+        content_type: &'static str,
+        body: Vec<u8>,
+    ) -> Response {
+        let etag = EntityTag::from_data(&body);
+        let cache_control = match self.max_age {
+            Some(max_age) => format!("max-age={}", max_age.as_secs()),
+            None => String::from("no-cache"),
+        };
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert("OData-Version", HeaderValue::from_static("4.0"));
+        // Each is made of characters that are valid in headers.
+        for (name, value) in [
+            (header::CACHE_CONTROL, cache_control),
+            (header::ETAG, etag.to_string()),
+            (
+                header::LAST_MODIFIED,
+                httpdate::fmt_http_date(self.last_modified),
+            ),
+        ] {
+            response_headers.insert(name, HeaderValue::from_str(&value).unwrap());
+        }
+        match evaluate_preconditions(headers, &Method::GET, Some(&etag)) {
+            Precondition::Passed if !self.is_unmodified_since(headers) => {}
+            Precondition::Passed | Precondition::NotModified => {
+                return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+            }
+            Precondition::Failed => return Error::PreconditionFailed.into_response(),
+        }
+        response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response_headers.insert(header::ALLOW, HeaderValue::from_static("GET,HEAD"));
+        (StatusCode::OK, response_headers, body).into_response()
+    }
+}
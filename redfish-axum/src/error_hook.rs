@@ -0,0 +1,34 @@
+use axum::{extract::State, http::Request, middleware::Next, response::Response};
+use redfish_data::ErrorResponse;
+use std::sync::Arc;
+
+// Extends the bodies of the error responses the service generates, such as with OEM messages
+// in their @Message.ExtendedInfo, or with properties like internal error codes or support URLs.
+// The code of the error tells which one it is, such as "Base.1.16.GeneralError".
+pub trait ErrorHook: Send + Sync {
+    fn extend(&self, error: &mut ErrorResponse);
+}
+
+tokio::task_local! {
+    static ERROR_HOOK: Arc<dyn ErrorHook>;
+}
+
+// Handle the request with the hook extending the errors the service responds to it with.
+pub(crate) async fn extend_errors<B>(
+    State(hook): State<Arc<dyn ErrorHook>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    ERROR_HOOK.scope(hook, next.run(request)).await
+}
+
+// The error as the hook of the service extends it, if it has one.
+pub(crate) fn extend_error(error: &ErrorResponse) -> Option<ErrorResponse> {
+    ERROR_HOOK
+        .try_with(|hook| {
+            let mut error = error.clone();
+            hook.extend(&mut error);
+            error
+        })
+        .ok()
+}
@@ -0,0 +1,471 @@
+use crate::clock::SystemClock;
+use crate::registries::{log_event_registry, resource_event_registry, task_event_registry};
+use crate::subscriptions::SUBSCRIPTIONS_URI;
+use crate::{Clock, Node};
+use etag::EntityTag;
+use redfish_data::{
+    get_resource_odata_type, get_uri_id, AllowedMethods, Event, EventRecord, Health, Message,
+    PropertyType, ResourceSchemaVersion, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, RwLock,
+};
+use std::time::{Duration, SystemTime};
+use strum::{Display, EnumString};
+use tokio::sync::broadcast;
+
+pub(crate) const EVENT_SERVICE_URI: &str = "/redfish/v1/EventService";
+pub(crate) const SSE_URI: &str = "/redfish/v1/EventService/SSE";
+
+// How many undelivered events a slow subscriber may fall behind before it starts missing events.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+// Retries back off exponentially from the retry interval, up to this long apart.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+// The settings of the event service that clients may change by patching the EventService resource.
+#[derive(Clone, Debug)]
+pub struct EventServiceSettings {
+    // While this is false, events are dropped and the SSE stream cannot be opened.
+    pub service_enabled: bool,
+    // How often, and how far apart, delivery of an event to a subscriber is attempted.
+    // EventService::deliver(), which delivers to EventDestinations, retries as these say.
+    pub delivery_retry_attempts: u32,
+    pub delivery_retry_interval_seconds: u32,
+}
+
+impl EventServiceSettings {
+    // How long to wait before each retry: the retry interval, doubling with each retry after it.
+    pub fn get_retry_delays(&self) -> Vec<Duration> {
+        let interval = Duration::from_secs(self.delivery_retry_interval_seconds.into());
+        (0..self.delivery_retry_attempts)
+            .map(|retry| {
+                let factor = 2u32.saturating_pow(retry);
+                interval.saturating_mul(factor).min(MAX_RETRY_DELAY)
+            })
+            .collect()
+    }
+}
+
+// What came of delivering an event to a subscriber.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    // Every attempt failed. EventDestinations are then terminated, or suspended, as their
+    // DeliveryRetryPolicy says.
+    RetriesExhausted,
+}
+
+// What a subscriber is sent: events, or the MetricReports of the TelemetryService.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum EventFormatType {
+    Event,
+    MetricReport,
+}
+
+// The formats the service can send, as its EventFormatTypes property lists them.
+pub(crate) const EVENT_FORMAT_TYPES: [EventFormatType; 2] =
+    [EventFormatType::Event, EventFormatType::MetricReport];
+
+// Which events a subscriber gets, as the RegistryPrefixes, ResourceTypes, OriginResources and
+// MessageIds properties of its EventDestination say. A record matches if it matches every list
+// that is not empty, by matching any entry of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventFilter {
+    pub registry_prefixes: Vec<String>,
+    // Schema names, such as ComputerSystem, of the resources the events originate from.
+    pub resource_types: Vec<String>,
+    pub origin_resources: Vec<String>,
+    // Such as ResourceEvent.ResourceCreated. Ids with versions match regardless of the version.
+    pub message_ids: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // The resource type is that of the origin of the record, if it is known.
+    pub fn matches(&self, record: &EventRecord, resource_type: Option<&str>) -> bool {
+        let properties = get_record_properties(record, resource_type);
+        let matches = |list: &[String], property: &str| {
+            list.is_empty()
+                || properties[property]
+                    .as_str()
+                    .is_some_and(|value| list.iter().any(|entry| entry == value))
+        };
+        let message_ids: Vec<String> = self
+            .message_ids
+            .iter()
+            .map(|message_id| strip_message_id_version(message_id))
+            .collect();
+        matches(&self.registry_prefixes, "RegistryPrefix")
+            && matches(&self.resource_types, "ResourceType")
+            && matches(&self.origin_resources, "OriginResource")
+            && matches(&message_ids, "MessageId")
+    }
+}
+
+// Such as ResourceEvent.ResourceCreated for ResourceEvent.1.1.ResourceCreated.
+fn strip_message_id_version(message_id: &str) -> String {
+    let mut parts = message_id.split('.');
+    match (parts.next(), parts.next_back()) {
+        (Some(prefix), Some(key)) => format!("{}.{}", prefix, key),
+        _ => String::from(message_id),
+    }
+}
+
+// The properties of a record that subscribers filter by, by the names $filter gives them on the
+// SSE stream. The MessageId is without its version.
+pub(crate) fn get_record_properties(record: &EventRecord, resource_type: Option<&str>) -> Value {
+    let message_id = record.get_message_id();
+    json!({
+        "EventFormatType": EventFormatType::Event.to_string(),
+        "RegistryPrefix": message_id.split('.').next(),
+        "MessageId": strip_message_id_version(message_id),
+        "OriginResource": record.get_origin_of_condition(),
+        "ResourceType": resource_type,
+    })
+}
+
+// Likewise for a MetricReport, whose origin is the report itself.
+pub(crate) fn get_metric_report_properties(report: &Value) -> Value {
+    json!({
+        "EventFormatType": EventFormatType::MetricReport.to_string(),
+        "MetricReportDefinition": report.pointer("/MetricReportDefinition/@odata.id"),
+        "OriginResource": report.get("@odata.id"),
+        "ResourceType": "MetricReport",
+    })
+}
+
+impl Default for EventServiceSettings {
+    fn default() -> Self {
+        Self {
+            service_enabled: true,
+            delivery_retry_attempts: 3,
+            delivery_retry_interval_seconds: 60,
+        }
+    }
+}
+
+// Fans out events generated by the service to any number of subscribers.
+// Cloning gives another handle to the same stream of events.
+#[derive(Clone)]
+pub struct EventService {
+    sender: broadcast::Sender<Event>,
+    // MetricReports, with the same kind of Id as events.
+    report_sender: broadcast::Sender<(String, Value)>,
+    last_id: Arc<AtomicU64>,
+    settings: Arc<RwLock<EventServiceSettings>>,
+    coalescing_window: Option<Duration>,
+    // The URIs of the resources whose ResourceChanged events are waiting out the window.
+    pending_changes: Arc<Mutex<HashSet<String>>>,
+    // Events are timestamped by the service's clock, once the EventService is given to one.
+    clock: Arc<RwLock<Arc<dyn Clock>>>,
+}
+
+impl EventService {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        let (report_sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self {
+            sender,
+            report_sender,
+            last_id: Arc::new(AtomicU64::new(0)),
+            settings: Arc::new(RwLock::new(EventServiceSettings::default())),
+            coalescing_window: None,
+            pending_changes: Arc::new(Mutex::new(HashSet::new())),
+            clock: Arc::new(RwLock::new(Arc::new(SystemClock))),
+        }
+    }
+
+    // Merge bursts of changes to a resource, such as during bulk updates, into one
+    // ResourceChanged event, sent once the window after the first of them has passed. Changes
+    // waiting out the window are dropped if the resource is removed in the meantime.
+    pub fn with_coalescing_window(mut self, window: Duration) -> Self {
+        self.coalescing_window = Some(window);
+        self
+    }
+
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.write().unwrap() = clock;
+    }
+
+    pub fn get_settings(&self) -> EventServiceSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    // Takes effect for every handle to the service, and for the next event sent.
+    pub fn set_settings(&self, settings: EventServiceSettings) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.read().unwrap().service_enabled
+    }
+
+    // With a link to the collection of subscriptions, if clients can subscribe.
+    pub(crate) fn get_node(&self, has_subscriptions: bool) -> EventServiceNode {
+        EventServiceNode::new(self.get_settings(), has_subscriptions)
+    }
+
+    // Deliver an event to a subscriber by calling the attempt, such as one that POSTs it to the
+    // subscriber's destination, until it returns true. It is retried as the settings at the
+    // start of the delivery say.
+    pub async fn deliver<F, Fut>(&self, mut attempt: F) -> DeliveryOutcome
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        if attempt().await {
+            return DeliveryOutcome::Delivered;
+        }
+        for delay in self.get_settings().get_retry_delays() {
+            tokio::time::sleep(delay).await;
+            if attempt().await {
+                return DeliveryOutcome::Delivered;
+            }
+        }
+        DeliveryOutcome::RetriesExhausted
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    // For subscribers whose EventFormatType is MetricReport. Each report comes with its Id.
+    pub fn subscribe_metric_reports(&self) -> broadcast::Receiver<(String, Value)> {
+        self.report_sender.subscribe()
+    }
+
+    // Trees that generate MetricReports, such as for the MetricReportDefinitions a Trigger
+    // returns when it fires, send the body of each here for subscribers to get.
+    pub fn send_metric_report(&self, report: Value) {
+        if !self.is_enabled() {
+            return;
+        }
+        // It is fine for nobody to be listening.
+        let _ = self.report_sender.send((self.next_id(), report));
+    }
+
+    fn next_id(&self) -> String {
+        (self.last_id.fetch_add(1, Ordering::Relaxed) + 1).to_string()
+    }
+
+    // Send an event containing a single record for the given message.
+    pub fn send(&self, message: Message, origin_of_condition: Option<&str>) {
+        self.send_record(|id, timestamp| {
+            EventRecord::new(
+                id,
+                timestamp,
+                message,
+                origin_of_condition.map(String::from),
+            )
+        });
+    }
+
+    // LogServices that want clients to be able to tail them call this whenever they append an entry.
+    // Subscribers, such as clients of the SSE stream, then get a LogEntryAdded event linking to it.
+    pub fn log_entry_added(&self, log_service_uri: &str, log_entry_uri: &str) {
+        let message = Message::from_registry(
+            log_event_registry(),
+            "LogEntryAdded",
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![String::from(log_service_uri)],
+            Vec::new(),
+        )
+        .unwrap();
+        self.send_record(|id, timestamp| {
+            EventRecord::new(id, timestamp, message, Some(String::from(log_service_uri)))
+                .with_log_entry(String::from(log_entry_uri))
+        });
+    }
+
+    // The service calls these when resources are created, patched or deleted through it. Trees
+    // and backends call them when a resource changes other than through the service, such as
+    // when hardware reports a new state, so that caches of it are invalidated.
+    pub fn resource_created(&self, uri: &str) {
+        self.send_resource_event(uri, "ResourceCreated");
+    }
+
+    pub fn resource_changed(&self, uri: &str) {
+        // Without a runtime to wait on, changes are sent as they come.
+        let (window, runtime) = match (
+            self.coalescing_window,
+            tokio::runtime::Handle::try_current(),
+        ) {
+            (Some(window), Ok(runtime)) => (window, runtime),
+            _ => return self.send_resource_event(uri, "ResourceChanged"),
+        };
+        if !self
+            .pending_changes
+            .lock()
+            .unwrap()
+            .insert(String::from(uri))
+        {
+            return;
+        }
+        let events = self.clone();
+        let uri = String::from(uri);
+        runtime.spawn(async move {
+            tokio::time::sleep(window).await;
+            if events.pending_changes.lock().unwrap().remove(&uri) {
+                events.send_resource_event(&uri, "ResourceChanged");
+            }
+        });
+    }
+
+    pub fn resource_removed(&self, uri: &str) {
+        self.pending_changes.lock().unwrap().remove(uri);
+        self.send_resource_event(uri, "ResourceRemoved");
+    }
+
+    fn send_resource_event(&self, uri: &str, key: &str) {
+        let message = Message::from_registry(
+            resource_event_registry(),
+            key,
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![String::from(uri)],
+            Vec::new(),
+        )
+        .unwrap();
+        self.send(message, Some(uri));
+    }
+
+    // Tasks of the TaskService call these as they progress, so that clients can subscribe instead
+    // of polling them.
+    pub fn task_started(&self, task_uri: &str) {
+        self.send_task_event(task_uri, "TaskStarted", Vec::new());
+    }
+
+    pub fn task_progress_changed(&self, task_uri: &str, percent_complete: u32) {
+        let args = vec![percent_complete.to_string()];
+        self.send_task_event(task_uri, "TaskProgressChanged", args);
+    }
+
+    // The health is that of the task's outcome. The DMTF registry calls a task that
+    // completed critically aborted.
+    pub fn task_completed(&self, task_uri: &str, health: Health) {
+        let key = match health {
+            Health::OK => "TaskCompletedOK",
+            Health::Warning => "TaskCompletedWarning",
+            Health::Critical => "TaskAborted",
+        };
+        self.send_task_event(task_uri, key, Vec::new());
+    }
+
+    fn send_task_event(&self, task_uri: &str, key: &str, args: Vec<String>) {
+        let mut message_args = vec![get_uri_id(task_uri)];
+        message_args.extend(args);
+        let message = Message::from_registry(
+            task_event_registry(),
+            key,
+            ResourceSchemaVersion::new(1, 1, 2),
+            message_args,
+            Vec::new(),
+        )
+        .unwrap();
+        self.send(message, Some(task_uri));
+    }
+
+    fn send_record(&self, record: impl FnOnce(String, SystemTime) -> EventRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+        let id = self.next_id();
+        let timestamp = self.clock.read().unwrap().system_time();
+        let event = Event::new(
+            ResourceSchemaVersion::new(1, 7, 0),
+            id.clone(),
+            vec![record(id, timestamp)],
+        );
+        // It is fine for nobody to be listening.
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for EventService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn get_event_service_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("EventService"),
+        ResourceSchemaVersion::new(1, 10, 0),
+    )
+    .with_property("ServiceEnabled", PropertyType::Boolean)
+    .with_property("DeliveryRetryAttempts", PropertyType::Integer)
+    .with_property("DeliveryRetryIntervalSeconds", PropertyType::Integer)
+}
+
+// The EventService resource, as of the settings when it was made.
+pub(crate) struct EventServiceNode {
+    body: Value,
+    resource_type: ResourceType,
+}
+
+impl EventServiceNode {
+    fn new(settings: EventServiceSettings, has_subscriptions: bool) -> Self {
+        let resource_type = get_event_service_type();
+        let state = match settings.service_enabled {
+            true => "Enabled",
+            false => "Disabled",
+        };
+        let mut body = json!({
+            "@odata.id": EVENT_SERVICE_URI,
+            "@odata.type": get_resource_odata_type("EventService", &resource_type.version, "EventService"),
+            "Id": "EventService",
+            "Name": "Event Service",
+            "ServiceEnabled": settings.service_enabled,
+            "DeliveryRetryAttempts": settings.delivery_retry_attempts,
+            "DeliveryRetryIntervalSeconds": settings.delivery_retry_interval_seconds,
+            "ServerSentEventUri": SSE_URI,
+            "EventFormatTypes": EVENT_FORMAT_TYPES.map(|format| format.to_string()),
+            "Status": {"State": state, "Health": "OK"},
+        });
+        if has_subscriptions {
+            body["Subscriptions"] = json!({"@odata.id": SUBSCRIPTIONS_URI});
+        }
+        Self {
+            body,
+            resource_type,
+        }
+    }
+}
+
+impl Node for EventServiceNode {
+    fn get_uri(&self) -> &str {
+        EVENT_SERVICE_URI
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: true,
+            post: false,
+        }
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+
+    fn get_resource_type(&self) -> Option<&ResourceType> {
+        Some(&self.resource_type)
+    }
+}
@@ -0,0 +1,168 @@
+use crate::Error;
+use axum::{
+    extract::State,
+    http::{header, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum FaultScenarioError {
+    // The document is not JSON, or not shaped like a scenario.
+    InvalidDocument(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FaultKind {
+    // Respond with 500 Internal Server Error instead of handling the request.
+    InternalError,
+    // Handle the request only after this long.
+    Delay(Duration),
+    // Leave the ETag header out of the response.
+    DropEtag,
+    // Refuse the session token of the request with 401, as though its session had expired.
+    // Requests without one are not affected.
+    ExpireSession,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    // Only requests for this URI, and those under it, get the fault, if it is given.
+    pub uri: Option<String>,
+    // Only requests with these methods, such as GET, get the fault, if any are given.
+    pub methods: Vec<String>,
+    // The chance, from 0 to 1, that a request the fault applies to gets it.
+    pub probability: f64,
+}
+
+impl Fault {
+    fn applies_to<B>(&self, request: &Request<B>) -> bool {
+        let uri_matches = self.uri.as_deref().is_none_or(|uri| {
+            request
+                .uri()
+                .path()
+                .strip_prefix(uri)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        let method_matches = self.methods.is_empty()
+            || self
+                .methods
+                .iter()
+                .any(|method| method.eq_ignore_ascii_case(request.method().as_str()));
+        uri_matches && method_matches && get_random_fraction() < self.probability
+    }
+}
+
+// The faults to inject into the responses of a service, for testing how clients cope with
+// services that misbehave. Each applies to a request independently of the others.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultScenario {
+    pub faults: Vec<Fault>,
+}
+
+fn get_fault(fault: &Map<String, Value>) -> Result<Fault, FaultScenarioError> {
+    let invalid = |reason: &str| FaultScenarioError::InvalidDocument(String::from(reason));
+    let kind = match fault.get("Kind").and_then(|kind| kind.as_str()) {
+        Some("InternalError") => FaultKind::InternalError,
+        Some("Delay") => {
+            let delay = fault
+                .get("DelayMilliseconds")
+                .and_then(|delay| delay.as_u64())
+                .ok_or_else(|| invalid("Delay without DelayMilliseconds"))?;
+            FaultKind::Delay(Duration::from_millis(delay))
+        }
+        Some("DropEtag") => FaultKind::DropEtag,
+        Some("ExpireSession") => FaultKind::ExpireSession,
+        Some(kind) => return Err(invalid(&format!("Unknown Kind {}", kind))),
+        None => return Err(invalid("Fault without Kind")),
+    };
+    let uri = fault
+        .get("Uri")
+        .and_then(|uri| uri.as_str())
+        .map(String::from);
+    let methods = fault
+        .get("Methods")
+        .and_then(|methods| methods.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|method| method.as_str())
+        .map(String::from)
+        .collect();
+    let probability = match fault.get("Probability") {
+        None => 1.0,
+        Some(probability) => probability
+            .as_f64()
+            .filter(|probability| (0.0..=1.0).contains(probability))
+            .ok_or_else(|| invalid("Probability not from 0 to 1"))?,
+    };
+    Ok(Fault {
+        kind,
+        uri,
+        methods,
+        probability,
+    })
+}
+
+impl FaultScenario {
+    // Read a scenario such as:
+    // {"Faults": [{"Kind": "InternalError", "Probability": 0.1, "Uri": "/redfish/v1/Systems"},
+    //             {"Kind": "Delay", "DelayMilliseconds": 2000, "Methods": ["PATCH"]}]}
+    // Faults apply to every request unless limited by Uri or Methods, and with a Probability
+    // of 1 unless it says otherwise.
+    pub fn from_json(data: &str) -> Result<Self, FaultScenarioError> {
+        let data: Map<String, Value> = serde_json::from_str(data)
+            .map_err(|error| FaultScenarioError::InvalidDocument(error.to_string()))?;
+        let faults = data
+            .get("Faults")
+            .and_then(|faults| faults.as_array())
+            .ok_or_else(|| FaultScenarioError::InvalidDocument(String::from("No Faults")))?;
+        let faults = faults
+            .iter()
+            .map(|fault| match fault.as_object() {
+                Some(fault) => get_fault(fault),
+                None => Err(FaultScenarioError::InvalidDocument(String::from(
+                    "Fault not an object",
+                ))),
+            })
+            .collect::<Result<Vec<Fault>, FaultScenarioError>>()?;
+        Ok(Self { faults })
+    }
+}
+
+// From 0 up to 1, from the random bits of a v4 UUID.
+fn get_random_fraction() -> f64 {
+    let random_bits = Uuid::new_v4().as_u64_pair().0 >> 16;
+    random_bits as f64 / (1u64 << 48) as f64
+}
+
+pub(crate) async fn inject_faults<B>(
+    State(scenario): State<Arc<FaultScenario>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let faults: Vec<&FaultKind> = scenario
+        .faults
+        .iter()
+        .filter(|fault| fault.applies_to(&request))
+        .map(|fault| &fault.kind)
+        .collect();
+    let has_token = request.headers().contains_key("x-auth-token");
+    for fault in &faults {
+        match fault {
+            FaultKind::Delay(delay) => tokio::time::sleep(*delay).await,
+            FaultKind::InternalError => return Error::InternalError.into_response(),
+            FaultKind::ExpireSession if has_token => return Error::Unauthorized.into_response(),
+            _ => (),
+        }
+    }
+    let mut response = next.run(request).await;
+    if faults.contains(&&FaultKind::DropEtag) {
+        response.headers_mut().remove(header::ETAG);
+    }
+    response
+}
@@ -0,0 +1,63 @@
+use crate::{NodeLink, NodeMeta};
+use http::{header, HeaderMap, HeaderName, HeaderValue};
+
+// Builds the headers of a response. Each header is set once, later values replacing earlier
+// ones, and Link relations are sent once each, however many times they are added. Values that
+// would not make a valid header, such as those with newlines, are left out instead of failing.
+pub(crate) struct ResponseHeaders {
+    headers: HeaderMap,
+    links: Vec<String>,
+}
+
+impl ResponseHeaders {
+    // The headers of every JSON response, with the methods allowed on what it carries.
+    pub(crate) fn new(allow: &str) -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            links: Vec::new(),
+        }
+        .with(header::ALLOW, allow)
+        .with(HeaderName::from_static("odata-version"), "4.0")
+        .with(header::CACHE_CONTROL, "no-cache")
+    }
+
+    // The headers of any response that carries a node.
+    pub(crate) fn for_node(meta: &NodeMeta) -> Self {
+        let mut headers = Self::new(&meta.allowed_methods.to_string());
+        if let Some(described_by) = &meta.described_by {
+            headers = headers.with_link(&NodeLink::new(described_by.clone(), "describedby"));
+        }
+        for link in meta.links.iter() {
+            headers = headers.with_link(link);
+        }
+        if let Some(etag) = &meta.etag {
+            headers = headers.with(header::ETAG, &etag.to_string());
+        }
+        headers
+    }
+
+    pub(crate) fn with(mut self, name: HeaderName, value: &str) -> Self {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    pub(crate) fn with_link(mut self, link: &NodeLink) -> Self {
+        let link = link.to_string();
+        if HeaderValue::from_str(&link).is_ok() && !self.links.contains(&link) {
+            self.links.push(link);
+        }
+        self
+    }
+
+    pub(crate) fn into_header_map(self) -> HeaderMap {
+        let mut headers = self.headers;
+        if !self.links.is_empty() {
+            // Each link is valid, so they are valid together.
+            let links = HeaderValue::from_str(&self.links.join(", ")).unwrap();
+            headers.insert(header::LINK, links);
+        }
+        headers
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Error;
+use axum::{
+    extract::State,
+    http::{header, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+// The hosts clients may address the service by, such as "bmc.example.com" or "10.0.0.5:443".
+// Hosts without a port match the host on any port. Names are matched case-insensitively.
+pub(crate) struct AllowedHosts {
+    hosts: Vec<String>,
+}
+
+impl AllowedHosts {
+    pub(crate) fn new(hosts: Vec<String>) -> Self {
+        Self {
+            hosts: hosts.iter().map(|host| host.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let without_port = strip_port(&host);
+        self.hosts
+            .iter()
+            .any(|allowed| *allowed == host || *allowed == without_port)
+    }
+}
+
+// Strip the port from a host such as "bmc:443" or "[::1]:443", keeping the brackets of IPv6.
+fn strip_port(host: &str) -> &str {
+    let port_start = match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => colon,
+        _ => return host,
+    };
+    // A bare IPv6 address has colons, but no port.
+    if host[..port_start].contains(':') && !host.starts_with('[') {
+        return host;
+    }
+    &host[..port_start]
+}
+
+// Refuse requests for hosts other than the allowed ones with 400 Bad Request, so that a
+// reverse proxy shared with other services cannot be tricked into routing their requests
+// here, such as to poison its cache.
+pub(crate) async fn validate_host<B>(
+    State(allowed): State<Arc<AllowedHosts>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    // HTTP/2 requests give the host in the URI instead of in a Host header.
+    let host = match request.uri().authority() {
+        Some(authority) => Some(authority.as_str()),
+        None => request
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok()),
+    };
+    match host {
+        Some(host) if allowed.allows(host) => next.run(request).await,
+        _ => Error::BadRequest.into_response(),
+    }
+}
@@ -0,0 +1,18 @@
+use crate::crawl::{Crawled, Crawler};
+use crate::Tree;
+use redfish_data::Inventory;
+use serde_json::Value;
+
+// A normalized inventory of the systems, chassis and firmware the tree has, as read by the user.
+// The tree is read in process, as the service would read it. Remote services are read over HTTP
+// with redfish_client::Client::get_inventory() instead.
+pub async fn get_inventory(tree: &(dyn Tree + Send + Sync), username: Option<&str>) -> Value {
+    let mut inventory = Inventory::new();
+    let mut crawler = Crawler::new(tree, username);
+    while let Some(crawled) = crawler.next().await {
+        if let Crawled::Found { uri, body, .. } = crawled {
+            inventory.add(&uri, &body);
+        }
+    }
+    inventory.to_json()
+}
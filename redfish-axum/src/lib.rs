@@ -1,32 +1,176 @@
 use async_trait::async_trait;
 use axum::{
+    body::Body,
     debug_handler,
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Json, Response},
-    routing::get,
+    extract::{ConnectInfo, FromRequest, Path, RawQuery, State},
+    http::{Method, Request, StatusCode},
+    middleware,
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{get, post},
     Router,
 };
+use bytes::Bytes;
 use etag::EntityTag;
 use http::{
     header::{self},
-    HeaderMap, HeaderName, HeaderValue,
+    HeaderMap, HeaderName, HeaderValue, Uri,
 };
-use http_auth_basic;
 use redfish_data::{
-    get_odata_metadata_document, get_odata_service_document, AllowedMethods, CollectionType,
-    ResourceType,
+    add_odata_context, get_odata_metadata_document, get_odata_service_document, get_type_name,
+    AllowedMethods, CollectionType, ErrorResponse, Event, EventRecord, Message, MessageRegistry,
+    PrivilegeRegistry, PropertyValueError, ResourceSchemaVersion, ResourceType, SchemaUrl,
 };
 use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tower::layer::Layer;
 use tower_http::normalize_path::{NormalizePath, NormalizePathLayer};
 use uuid::Uuid;
 
+mod account_service;
+mod accounts;
+mod actions;
+mod audit;
+mod auth;
+mod backend;
+mod cache;
+mod clock;
+mod compact;
+mod configuration;
+mod crawl;
+mod dbus;
+mod documents;
+mod error_hook;
+mod events;
+#[cfg(feature = "fault-injection")]
+mod faults;
+mod headers;
+mod host;
+mod inventory;
 mod json;
-use json::JsonResponse;
+mod lifecycle;
+mod limit;
+mod links;
+mod lockout;
+mod manager;
+mod membership;
+mod patch;
+mod preconditions;
+mod privileges;
+mod profile;
+mod query;
+mod registries;
+mod request_id;
+mod scaffold;
+mod session_service;
+mod sessions;
+mod stats;
+mod subscriptions;
+mod tasks;
+mod telemetry;
+mod tree_builder;
+mod upload;
+mod validate;
+pub use account_service::{AccountServiceTree, LocalAccount, LocalAccounts, Role};
+use accounts::{configure_external_providers, ExternalAccounts};
+pub use accounts::{ExternalAccountProvider, ExternalAccountProviderType, RoleMapping};
+use actions::restrict_actions;
+pub use actions::ActionVisibility;
+use audit::get_session_message;
+pub use audit::{AuditAction, AuditEntry, AuditHook, AuditLog};
+use auth::{
+    check_credentials, check_document_access, check_session_limit, get_request_username,
+    is_session_service_enabled, sweep_sessions, AcceptAllCredentials, AuthThrottle,
+    SESSION_SWEEP_INTERVAL,
+};
+pub use auth::{
+    AuthThrottleConfig, AuthenticationProvider, DisabledSessionPolicy, DocumentAccess,
+    SessionLimitConfig, SessionLimitPolicy, SessionTokenConfig,
+};
+pub use backend::{BackendAdapter, BackendTree};
+pub use cache::{CachingAdapter, NodeCache};
+use clock::SystemClock;
+pub use clock::{Clock, ManualClock};
+pub use compact::CompactBody;
+pub use configuration::{ArchiveReader, ConfigurationStore};
+use configuration::{ConfigurationBackup, BACKUP_ACTION, RESTORE_ACTION};
+pub use dbus::{DBusAction, DBusConnection, DBusProperty, DBusSubtree, DBusTree};
+use documents::DocumentCaching;
+use error_hook::extend_errors;
+pub use error_hook::ErrorHook;
+use events::{
+    get_event_service_type, get_metric_report_properties, get_record_properties, EventServiceNode,
+    EVENT_FORMAT_TYPES, EVENT_SERVICE_URI, SSE_URI,
+};
+pub use events::{
+    DeliveryOutcome, EventFilter, EventFormatType, EventService, EventServiceSettings,
+};
+#[cfg(feature = "fault-injection")]
+use faults::inject_faults;
+#[cfg(feature = "fault-injection")]
+pub use faults::{Fault, FaultKind, FaultScenario, FaultScenarioError};
+use headers::ResponseHeaders;
+use host::{validate_host, AllowedHosts};
+pub use inventory::get_inventory;
+use json::{get_media_type, JsonBody, JsonResponse};
+use lifecycle::check_lifecycle;
+pub use lifecycle::{Lifecycle, LifecyclePhase};
+use limit::{limit_concurrency, ConcurrencyLimiter, CreateLimiter};
+pub use limit::{ConcurrencyLimitConfig, CreateLimit, CreateLimitResponse};
+pub use links::{LinkRegistry, Reference};
+pub use lockout::AccountLockouts;
+pub use manager::{ManagerBuilder, NetworkProtocol, NetworkRuntime, ProtocolSettings};
+use patch::{annotate_patched, run_validators};
+pub use patch::{PartialPatch, PropertyValidator};
+use preconditions::{evaluate_preconditions, Precondition};
+use privileges::{
+    add_registry_member, authorize, get_ancestor_entities, get_user_privileges, track_request_user,
+    OperationScope, RegistryNode, REGISTRIES_URI,
+};
+pub use privileges::{current_user, RequestUser};
+pub use profile::{
+    check_profile, ConformanceIssue, ConformanceReport, InteropProfile, ProfileError,
+};
+pub use query::NativeQuerySupport;
+use query::{
+    apply_query, check_query_not_given, get_query_pairs, parse_query, split_query, Paging, Reader,
+};
+pub use redfish_data::{Expand, ExpandKind, Filter, FilterOp, Query};
+pub use registries::{base_registry, privilege_registry};
+use registries::{get_error_body, resource_event_registry, select_language, LocalizedRegistries};
+use request_id::assign_request_id;
+pub use request_id::current_request_id;
+pub use scaffold::{Scaffold, ScaffoldError, ScaffoldNode};
+pub use session_service::SessionServiceTree;
+pub use sessions::{
+    FileSessionStore, MemorySessionStore, Session, SessionManager, SessionStore, SessionStoreError,
+};
+use stats::{record_node_type, record_statistics};
+pub use stats::{ResponseStatistics, TypeStatistics};
+pub use subscriptions::EventSender;
+use subscriptions::{NewSubscription, SubscriptionNode, Subscriptions, SUBSCRIPTIONS_URI};
+use tasks::{
+    get_task_collection_type, get_task_service_type, get_task_type, respond_async, TASKS_URI,
+    TASK_MONITORS_URI, TASK_SERVICE_URI,
+};
+pub use tasks::{Task, TaskLocation, TaskService, TaskState};
+pub use telemetry::{
+    DiscreteTrigger, NumericThresholds, Threshold, ThresholdActivation, Trigger, TriggerAction,
+};
+pub use tree_builder::{StaticResources, StaticTree, TreeBuilder};
+use upload::stream_upload;
+pub use upload::UploadWriter;
+pub use validate::{validate, ValidationIssue, ValidationReport};
 
 // TODO: In doc, clarify that this has to be run via https not http
 // TODO: Is this a better fit for redfish-data?
@@ -36,15 +180,188 @@ pub enum Error {
     NotFound,
     Unauthorized,
     MethodNotAllowed(AllowedMethods),
-    BadODataVersion,
+    // The OData-Version the request asked for, and the versions the service supports.
+    BadODataVersion(String, Vec<String>),
+    BadRequest,
+    UnsupportedMediaType,
+    // The body of the request is not in UTF-8, such as one in UTF-16.
+    NotUtf8,
+    // The request set properties to values they cannot take. The messages say which and why.
+    InvalidProperties(Vec<Message>),
+    // The service is too busy to handle the request. Clients should retry after the duration.
+    ServiceUnavailable(Duration),
+    // The user already has as many sessions as they may have.
+    SessionLimitExceeded,
+    // The account is locked, as it failed to authenticate too often.
+    AccountLocked,
+    // The resource is in use or in transition, such as a collection that already has as many
+    // creates pending as it allows.
+    ResourceInUse,
+    // The resource could not be created, as one of the given type already has the property
+    // with the value, such as another member of the collection with the requested Id.
+    ResourceAlreadyExists(String, String, String),
+    // The query parameters of the request cannot be applied. The key of the Base message
+    // saying why, such as QueryNotSupportedOnResource, and its arguments.
+    InvalidQuery(String, Vec<String>),
+    // The request has a query parameter starting with $ that the service does not support.
+    UnsupportedQueryParameter(String),
+    // The resource does not have the named action, such as ComputerSystem.Reset.
+    ActionNotSupported(String),
+    // The user lacks the privileges for the request, such as to invoke an action.
+    Forbidden,
+    // The named service, such as EventService, has been disabled by a client.
+    ServiceDisabled(String),
+    // The body of the response would be larger than the service allows, such as that of a large
+    // collection requested without paging.
+    ResponseTooLarge,
+    // The request's If-Match or If-None-Match header does not hold for the resource as it is.
+    PreconditionFailed,
+    // Something went wrong that the client cannot do anything about, such as a backend failing.
+    InternalError,
 }
 
 pub trait Node {
     fn get_uri(&self) -> &str; // TODO: Stricter type? Ensure abspath? Don't allow trailing / ???
     fn get_body(&self) -> Value;
     fn get_allowed_methods(&self) -> AllowedMethods;
-    fn described_by(&self) -> Option<&str>; // TODO: Stricter URL type???
-    fn get_etag(&self) -> Option<EntityTag>;
+    fn described_by(&self) -> Option<&SchemaUrl>;
+
+    // The ETag of the node as it is. Nodes without one can be given one made from their body
+    // by AppBuilder::computed_etags() instead.
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+
+    // Nodes whose body comes from a source that can fail, such as a backend that is down,
+    // implement this instead, and return the error to respond with, like Error::InternalError.
+    // The service only calls this. get_body() is then only called by whoever made the node.
+    fn try_get_body(&self) -> Result<Value, Error> {
+        Ok(self.get_body())
+    }
+
+    // Nodes whose body is not JSON, such as certificate exports or log dumps, return it here.
+    // When this is Some, responses carry this body instead of the one from get_body().
+    fn get_raw_body(&self) -> Option<RawBody> {
+        None
+    }
+
+    // Nodes that return their type here get the values of PATCH requests checked against
+    // the property types it declares, before the tree sees them.
+    fn get_resource_type(&self) -> Option<&ResourceType> {
+        None
+    }
+
+    // Link relations to send along with the describedby link of described_by(), such as to
+    // the schema of an OEM extension, or the ActionInfo of one of the node's actions.
+    fn get_links(&self) -> Vec<NodeLink> {
+        Vec::new()
+    }
+
+    // Collections that clients can POST to return the properties that requests to create a
+    // member must give. Requests missing any are refused with PropertyMissing messages before
+    // the tree sees them.
+    fn get_required_create_properties(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // What the service needs to know about the node without its body, such as for HEAD
+    // and conditional requests. Nodes whose body is expensive to make, such as those loaded
+    // lazily from a backend, should be able to return this without making it.
+    fn get_meta(&self) -> NodeMeta {
+        NodeMeta {
+            etag: self.get_etag(),
+            described_by: self.described_by().cloned(),
+            allowed_methods: self.get_allowed_methods(),
+            content_length: None,
+            expensive_body: false,
+            links: self.get_links(),
+        }
+    }
+}
+
+pub struct NodeMeta {
+    pub etag: Option<EntityTag>,
+    pub described_by: Option<SchemaUrl>,
+    pub allowed_methods: AllowedMethods,
+    // The length of the JSON body, if it is known without making it. HEAD responses carry it
+    // as their Content-Length, so it must be exact.
+    pub content_length: Option<u64>,
+    // Whether the body is expensive to make. If so, it is left out of responses that need not
+    // carry it, such as to creating the node, where only the Location is sent.
+    pub expensive_body: bool,
+    pub links: Vec<NodeLink>,
+}
+
+// A relation for the Link header of a node, such as one with the rel of "describedby".
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeLink {
+    pub target: SchemaUrl,
+    pub rel: String,
+}
+
+impl NodeLink {
+    pub fn new(target: SchemaUrl, rel: &str) -> Self {
+        Self {
+            target,
+            rel: String::from(rel),
+        }
+    }
+}
+
+impl fmt::Display for NodeLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Relation types that are not tokens, such as URLs, have to be quoted.
+        let is_token = self
+            .rel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._".contains(c));
+        match is_token && !self.rel.is_empty() {
+            true => write!(f, "<{}>; rel={}", self.target, self.rel),
+            false => write!(
+                f,
+                "<{}>; rel=\"{}\"",
+                self.target,
+                self.rel.replace('"', "")
+            ),
+        }
+    }
+}
+
+pub struct RawBody {
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+// A node returned by Tree::get(). Trees that hold their nodes lend them, and trees that
+// make them per request, such as by reading a backend, hand out a shared one.
+pub enum NodeRef<'a> {
+    Borrowed(&'a dyn Node),
+    Shared(Arc<dyn Node + Send + Sync>),
+}
+
+impl<'a> Deref for NodeRef<'a> {
+    type Target = dyn Node + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            NodeRef::Borrowed(node) => *node,
+            NodeRef::Shared(node) => node.as_ref(),
+        }
+    }
+}
+
+impl<'a, T: Node> From<&'a T> for NodeRef<'a> {
+    fn from(node: &'a T) -> Self {
+        NodeRef::Borrowed(node)
+    }
+}
+
+// What came of an operation that may take too long for the client to wait for.
+pub enum Outcome<T> {
+    Done(T),
+    // The operation carries on as the task, such as a firmware update. The client is given the
+    // task monitor to poll until it completes.
+    Async(Task),
 }
 
 #[async_trait]
@@ -53,7 +370,27 @@ pub trait Tree {
     // If the request successfully provided credentials as a user, the username is given.
     // If the request did not attempt to authenticate, the username is None.
     // If the requested URI requires authentication, and the username is None, you must return Error::Unauthorized.
-    async fn get(&self, uri: &str, username: Option<&str>) -> Result<&dyn Node, Error>;
+    // The service refuses requests the user lacks the privileges for by its privilege registry,
+    // before calling the tree. See AppBuilder::privilege_registry(). For finer checks, the role
+    // and privileges of the user are given by current_user().
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error>;
+
+    // The query parameters this tree applies itself, such as by pushing them into a
+    // database query. The service applies the others to the bodies of the nodes it returns.
+    fn get_native_query_support(&self) -> NativeQuerySupport {
+        NativeQuerySupport::default()
+    }
+
+    // Like get(), for requests with any of the query parameters the tree applies itself.
+    // Only those are given. The body of the node returned must already reflect them.
+    async fn get_with_query(
+        &self,
+        uri: &str,
+        username: Option<&str>,
+        _query: &Query,
+    ) -> Result<NodeRef<'_>, Error> {
+        self.get(uri, username).await
+    }
 
     // Create a resource, given the collction URI and JSON input.
     // Return Ok(Node) of the new resource, or Err.
@@ -86,84 +423,1518 @@ pub trait Tree {
         username: Option<&str>,
     ) -> Result<&dyn Node, Error>;
 
+    // Like create(), patch() and delete(), which these call by default, except that the tree may
+    // start a task for the operation and return Outcome::Async with it, instead of finishing it
+    // before returning. The service calls these rather than those. It only holds the tree locked
+    // until they return, so the work of the task has to carry on elsewhere, such as in a spawned
+    // Tokio task that completes it.
+    async fn create_deferred(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<&dyn Node>, Error> {
+        self.create(uri, request_body, username)
+            .await
+            .map(Outcome::Done)
+    }
+
+    async fn patch_deferred(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<&dyn Node>, Error> {
+        self.patch(uri, request_body, username)
+            .await
+            .map(Outcome::Done)
+    }
+
+    async fn delete_deferred(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Outcome<()>, Error> {
+        self.delete(uri, username).await.map(Outcome::Done)
+    }
+
+    // Carry out the named action, such as ComputerSystem.Reset, of the resource at the URI,
+    // given the parameters of the request to its target, such as
+    // /redfish/v1/Systems/1/Actions/ComputerSystem.Reset. The service calls this only once the
+    // user can read the resource. Return the body to respond with, if the action has one, or
+    // Outcome::Async if it carries on as a task. By default, resources have no actions.
+    // If the request successfully provided credentials as a user, the username is given.
+    // If the request did not attempt to authenticate, the username is None.
+    async fn action(
+        &mut self,
+        _uri: &str,
+        action: &str,
+        _request_body: &Map<String, Value>,
+        _username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        Err(Error::ActionNotSupported(String::from(action)))
+    }
+
+    // Return false for the actions of the resource at the URI that the user lacks the
+    // privileges to invoke. Requests to invoke them are refused with 403 Forbidden, and
+    // the body of the resource shows them as AppBuilder::action_visibility() says.
+    // By default, whoever can read the resource can invoke its actions.
+    fn can_invoke_action(&self, _uri: &str, _action: &str, _username: Option<&str>) -> bool {
+        true
+    }
+
+    // Whether the collection at the URI is one of sessions, which clients log in by creating
+    // members of, such as one of an OEM type. The service checks the UserName and Password of
+    // each login with its AuthenticationProvider, and gives the X-Auth-Token of the session
+    // created with the response. By default, only /redfish/v1/SessionService/Sessions is.
+    fn is_session_collection(&self, uri: &str) -> bool {
+        uri == SESSIONS_URI
+    }
+
+    // Begin a raw binary upload (an application/octet-stream POST) to the given URI,
+    // such as an UpdateService push URI. Return the writer to stream the request body into.
+    // The tree is not locked while the body is being streamed.
+    // If the request successfully provided credentials as a user, the username is given.
+    // If the request did not attempt to authenticate, the username is None.
+    // If the requested URI requires authentication, and the username is None, you must return Error::Unauthorized.
+    async fn upload(
+        &mut self,
+        _uri: &str,
+        _username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        Err(Error::UnsupportedMediaType)
+    }
+
     fn get_collection_types(&self) -> &[CollectionType];
 
     fn get_resource_types(&self) -> &[ResourceType];
+
+    // Trees that keep the links between their resources return them here. The service then
+    // forgets the links from and to each resource deleted through it.
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        None
+    }
+
+    // Called once when a service with a Lifecycle is built, such as to scan the hardware
+    // inventory. Until it returns, requests other than for /redfish are answered with
+    // 503 Service Unavailable, and if it fails, with 500 Internal Server Error.
+    async fn on_start(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Called when the Lifecycle of the service is shut down, once it has stopped handling
+    // requests.
+    async fn on_shutdown(&mut self) {}
+}
+
+// TODO: Better way to declare tree type???
+pub fn app<T: Tree + Send + Sync + 'static>(tree: T) -> NormalizePath<Router> {
+    AppBuilder::new(tree).build()
+}
+
+// Builds the service for a tree, for when the defaults of app() are not enough.
+pub struct AppBuilder<T: Tree + Send + Sync + 'static> {
+    tree: T,
+    events: EventService,
+    event_sender: Option<Arc<dyn EventSender>>,
+    sessions: SessionManager,
+    lockouts: AccountLockouts,
+    audit_hook: Option<Arc<dyn AuditHook>>,
+    error_hook: Option<Arc<dyn ErrorHook>>,
+    authentication_provider: Arc<dyn AuthenticationProvider>,
+    external_account_providers: Vec<ExternalAccountProviderType>,
+    auth_throttle: Option<AuthThrottleConfig>,
+    concurrency_limit: Option<ConcurrencyLimitConfig>,
+    create_limits: HashMap<String, CreateLimit>,
+    session_limit: Option<SessionLimitConfig>,
+    session_tokens: SessionTokenConfig,
+    disabled_sessions: DisabledSessionPolicy,
+    session_sweep: Option<Duration>,
+    allowed_hosts: Option<Vec<String>>,
+    document_access: DocumentAccess,
+    document_max_age: Option<Duration>,
+    odata_context: bool,
+    computed_etags: bool,
+    localized_registries: Option<Vec<&'static MessageRegistry>>,
+    odata_versions: Vec<String>,
+    check_membership: bool,
+    request_id_header: bool,
+    collection_delete: bool,
+    action_visibility: ActionVisibility,
+    privilege_registry: Option<&'static PrivilegeRegistry>,
+    property_validators: Vec<Arc<dyn PropertyValidator>>,
+    partial_patch: PartialPatch,
+    page_size: Option<usize>,
+    max_response_size: Option<usize>,
+    lifecycle: Option<Lifecycle>,
+    statistics: Option<ResponseStatistics>,
+    tasks: Option<TaskService>,
+    task_location: TaskLocation,
+    configuration_backup: Option<ConfigurationBackup>,
+    clock: Arc<dyn Clock>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<FaultScenario>,
+}
+
+impl<T: Tree + Send + Sync + 'static> AppBuilder<T> {
+    pub fn new(tree: T) -> Self {
+        Self {
+            tree,
+            events: EventService::new(),
+            event_sender: None,
+            sessions: SessionManager::new(),
+            lockouts: AccountLockouts::new(),
+            audit_hook: None,
+            error_hook: None,
+            authentication_provider: Arc::new(AcceptAllCredentials),
+            external_account_providers: Vec::new(),
+            auth_throttle: Some(AuthThrottleConfig::default()),
+            concurrency_limit: None,
+            create_limits: HashMap::new(),
+            session_limit: None,
+            session_tokens: SessionTokenConfig::default(),
+            disabled_sessions: DisabledSessionPolicy::Keep,
+            session_sweep: Some(SESSION_SWEEP_INTERVAL),
+            allowed_hosts: None,
+            document_access: DocumentAccess::Public,
+            document_max_age: None,
+            odata_context: false,
+            computed_etags: false,
+            localized_registries: None,
+            odata_versions: vec![String::from("4.0")],
+            check_membership: false,
+            request_id_header: false,
+            collection_delete: false,
+            action_visibility: ActionVisibility::Hidden,
+            privilege_registry: Some(privilege_registry()),
+            property_validators: Vec::new(),
+            partial_patch: PartialPatch::Annotated,
+            page_size: None,
+            max_response_size: None,
+            lifecycle: None,
+            statistics: None,
+            tasks: None,
+            task_location: TaskLocation::default(),
+            configuration_backup: None,
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+
+    // Use the given handle for the events the service generates, so that the tree can share it
+    // to generate its own events, and so that callers can subscribe before building.
+    pub fn event_service(mut self, events: EventService) -> Self {
+        self.events = events;
+        self
+    }
+
+    // Let clients subscribe to have events pushed to them, by creating EventDestinations under
+    // the EventService, which the sender then delivers their events to.
+    pub fn event_sender(mut self, sender: impl EventSender + 'static) -> Self {
+        self.event_sender = Some(Arc::new(sender));
+        self
+    }
+
+    // Keep sessions in the given handle, so that callers can attach OEM data to them, or keep
+    // them in a SessionStore of their own with SessionManager::with_store().
+    pub fn session_manager(mut self, sessions: SessionManager) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    // Count failed Basic auth attempts in the given handle, so that the tree can share it to
+    // serve the Locked property of accounts, and to unlock them. Accounts are only locked if
+    // the tree's AccountService has an AccountLockoutThreshold and AccountLockoutDuration.
+    pub fn account_lockouts(mut self, lockouts: AccountLockouts) -> Self {
+        self.lockouts = lockouts;
+        self
+    }
+
+    pub fn audit_hook(mut self, hook: impl AuditHook + 'static) -> Self {
+        self.audit_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn error_hook(mut self, hook: impl ErrorHook + 'static) -> Self {
+        self.error_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn authentication_provider(
+        mut self,
+        provider: impl AuthenticationProvider + 'static,
+    ) -> Self {
+        self.authentication_provider = Arc::new(provider);
+        self
+    }
+
+    // Model the given external account providers, such as LDAP, as properties of the
+    // AccountService that clients configure by patching it. The AuthenticationProvider is
+    // given each configuration as it changes. The tree's AccountService should not have them.
+    pub fn external_account_providers(
+        mut self,
+        provider_types: &[ExternalAccountProviderType],
+    ) -> Self {
+        self.external_account_providers = Vec::from(provider_types);
+        self
+    }
+
+    // Configure throttling of failed Basic auth attempts, or turn it off with None.
+    pub fn auth_throttle(mut self, config: Option<AuthThrottleConfig>) -> Self {
+        self.auth_throttle = config;
+        self
+    }
+
+    // Limit how many requests are handled at once, or stop limiting them with None.
+    pub fn concurrency_limit(mut self, config: Option<ConcurrencyLimitConfig>) -> Self {
+        self.concurrency_limit = config;
+        self
+    }
+
+    // Limit how many creates the collection at the URI has pending at once. Creates are not
+    // limited by default.
+    pub fn create_limit(mut self, uri: &str, limit: CreateLimit) -> Self {
+        self.create_limits.insert(String::from(uri), limit);
+        self
+    }
+
+    // Limit how many sessions each user may have open at once, or stop limiting them with None.
+    pub fn session_limit(mut self, config: Option<SessionLimitConfig>) -> Self {
+        self.session_limit = config;
+        self
+    }
+
+    // Restrict where and for how long session tokens are accepted.
+    pub fn session_tokens(mut self, config: SessionTokenConfig) -> Self {
+        self.session_tokens = config;
+        self
+    }
+
+    // Decide what becomes of existing sessions when a client disables the SessionService. They
+    // are kept by default.
+    pub fn disabled_sessions(mut self, policy: DisabledSessionPolicy) -> Self {
+        self.disabled_sessions = policy;
+        self
+    }
+
+    // Set how often sessions idle for the SessionTimeout are removed while no tokens are being
+    // presented, or only remove them when tokens are presented with None.
+    pub fn session_sweep(mut self, interval: Option<Duration>) -> Self {
+        self.session_sweep = interval;
+        self
+    }
+
+    // Only serve requests whose Host is one of the given hosts, or serve any host with None.
+    // Hosts without a port, such as "bmc.example.com", match the host on any port.
+    pub fn allowed_hosts(mut self, hosts: Option<Vec<String>>) -> Self {
+        self.allowed_hosts = hosts;
+        self
+    }
+
+    // Decide who may read $metadata and the OData service document. They are public by default.
+    pub fn document_access(mut self, access: DocumentAccess) -> Self {
+        self.document_access = access;
+        self
+    }
+
+    // Let clients use $metadata and the OData service document for this long before
+    // revalidating them, or make them revalidate every time with None, as by default.
+    pub fn document_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.document_max_age = max_age;
+        self
+    }
+
+    // Add an @odata.context to the bodies of resources and collections, for OData clients
+    // that require it. It is left out by default, as some other clients reject it.
+    pub fn odata_context(mut self, enabled: bool) -> Self {
+        self.odata_context = enabled;
+        self
+    }
+
+    // Give nodes without an ETag of their own one made from a hash of their body, sent in the
+    // ETag header and as the @odata.etag of the body, so that trees need not keep them. The
+    // body is then made for HEAD and conditional requests too, so nodes whose body is
+    // expensive to make are better off giving their own.
+    pub fn computed_etags(mut self, enabled: bool) -> Self {
+        self.computed_etags = enabled;
+        self
+    }
+
+    // Give error responses in the language of the request's Accept-Language, from these
+    // translations of the Base registry, or from the default English one if there is none for
+    // it. Their messages then say which Language they are in.
+    pub fn localized_registries(
+        mut self,
+        registries: Option<Vec<&'static MessageRegistry>>,
+    ) -> Self {
+        self.localized_registries = registries;
+        self
+    }
+
+    // The OData versions clients may ask for with the OData-Version header. Requests for
+    // others are refused with 412 Precondition Failed. Only "4.0" is accepted by default.
+    pub fn odata_versions(mut self, versions: Vec<String>) -> Self {
+        self.odata_versions = versions;
+        self
+    }
+
+    // After each create and delete, check that the collection's Members reflect it, and respond
+    // with 500 Internal Server Error if they do not. This is for catching bugs in trees during
+    // development, as each check costs another get from the tree.
+    pub fn check_membership(mut self, enabled: bool) -> Self {
+        self.check_membership = enabled;
+        self
+    }
+
+    // Start the tree with its on_start() when the service is built, and shut it down with its
+    // on_shutdown() through the handle. The service then has to be built within a Tokio runtime.
+    pub fn lifecycle(mut self, lifecycle: Lifecycle) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
+    // Keep statistics of the responses to requests for each type of resource, for finding
+    // those that are requested the most, fail the most, or have the largest bodies.
+    pub fn response_statistics(mut self, statistics: ResponseStatistics) -> Self {
+        self.statistics = Some(statistics);
+        self
+    }
+
+    // Serve the TaskService from the given handle, so that the tree can share it to start tasks
+    // for its long-running operations. Their events go to the service's EventService. Requests
+    // that prefer respond-async are then carried out as tasks too.
+    pub fn task_service(mut self, tasks: TaskService) -> Self {
+        self.tasks = Some(tasks);
+        self
+    }
+
+    // Give the manager at the URI BackupConfiguration and RestoreConfiguration actions, which
+    // download a snapshot of the configuration from the store, and upload one to restore. With
+    // a TaskService, restores are carried out as tasks.
+    pub fn configuration_backup(
+        mut self,
+        manager_uri: &str,
+        store: impl ConfigurationStore + 'static,
+    ) -> Self {
+        self.configuration_backup = Some(ConfigurationBackup::new(manager_uri, Arc::new(store)));
+        self
+    }
+
+    // Which URI the Location of 202 Accepted responses gives for clients to poll, which is the
+    // task monitor by default.
+    pub fn task_location(mut self, location: TaskLocation) -> Self {
+        self.task_location = location;
+        self
+    }
+
+    // Tell the time by the clock instead of the system's, such as by a ManualClock in tests of
+    // when session tokens expire.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // Inject the faults of the scenario into the responses of the service, so that it can stand
+    // in for a misbehaving one when testing clients. Not for services in production.
+    #[cfg(feature = "fault-injection")]
+    pub fn fault_scenario(mut self, scenario: FaultScenario) -> Self {
+        self.faults = Some(scenario);
+        self
+    }
+
+    // Return at most this many members of a collection at once, linking to the rest with a
+    // $skiptoken in Members@odata.nextLink, or return them all with None.
+    pub fn page_size(mut self, page_size: Option<usize>) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    // Refuse to send the bodies of resources that would be larger than this many bytes, such as
+    // those of large collections, telling clients to page them instead. This bounds the memory
+    // a response can take on small devices.
+    pub fn max_response_size(mut self, max_response_size: Option<usize>) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    // Let DELETE requests for a collection delete each of its members that can be deleted. The
+    // response lists which were, with a message for each. Otherwise collections are deleted like
+    // any other node, which trees usually refuse.
+    pub fn collection_delete(mut self, enabled: bool) -> Self {
+        self.collection_delete = enabled;
+        self
+    }
+
+    // Whether the actions of a resource that the user cannot invoke are left out of its body,
+    // as they are by default, or annotated. See Tree::can_invoke_action().
+    pub fn action_visibility(mut self, visibility: ActionVisibility) -> Self {
+        self.action_visibility = visibility;
+        self
+    }
+
+    // The registry of which privileges allow each operation on each entity, by which the
+    // service refuses requests with 403 Forbidden, given the privileges the
+    // AuthenticationProvider gives the user. Clients can read it under /redfish/v1/Registries.
+    // It is that of privilege_registry() by default, and None authorizes nothing by privilege.
+    pub fn privilege_registry(mut self, registry: Option<&'static PrivilegeRegistry>) -> Self {
+        self.privilege_registry = registry;
+        self
+    }
+
+    // Add a validator of the properties of PATCH requests, after any added before it.
+    pub fn property_validator(mut self, validator: impl PropertyValidator + 'static) -> Self {
+        self.property_validators.push(Arc::new(validator));
+        self
+    }
+
+    // Whether PATCH requests with properties that cannot be applied, by the schema or the
+    // validators, apply the others, as they do by default, or are refused whole.
+    pub fn partial_patch(mut self, mode: PartialPatch) -> Self {
+        self.partial_patch = mode;
+        self
+    }
+
+    // Give the ID of each request back in the X-Request-Id header of its response. Requests
+    // sent with an X-Request-Id keep theirs, and others are given a new one.
+    pub fn request_id_header(mut self, enabled: bool) -> Self {
+        self.request_id_header = enabled;
+        self
+    }
+
+    pub fn build(self) -> NormalizePath<Router> {
+        let state = AppState {
+            tree: Arc::new(tokio::sync::RwLock::new(self.tree)),
+            sessions: self.sessions,
+            lockouts: self.lockouts,
+            events: self.events,
+            subscriptions: self
+                .event_sender
+                .map(|sender| Subscriptions::new(sender, self.clock.clone())),
+            audit_hook: self.audit_hook,
+            error_hook: self.error_hook,
+            authentication_provider: self.authentication_provider,
+            external_accounts: ExternalAccounts::new(&self.external_account_providers),
+            auth_throttle: self
+                .auth_throttle
+                .map(|config| Arc::new(AuthThrottle::new(config, self.clock.clone()))),
+            concurrency_limiter: self
+                .concurrency_limit
+                .map(|config| Arc::new(ConcurrencyLimiter::new(config))),
+            create_limiter: Arc::new(CreateLimiter::new(self.create_limits)),
+            session_limit: self.session_limit,
+            session_tokens: self.session_tokens,
+            disabled_sessions: self.disabled_sessions,
+            allowed_hosts: self
+                .allowed_hosts
+                .map(|hosts| Arc::new(AllowedHosts::new(hosts))),
+            document_access: self.document_access,
+            documents: Arc::new(DocumentCaching::new(
+                self.clock.system_time(),
+                self.document_max_age,
+            )),
+            odata_context: self.odata_context,
+            computed_etags: self.computed_etags,
+            localized_registries: self
+                .localized_registries
+                .map(|registries| Arc::new(LocalizedRegistries::new(registries))),
+            odata_versions: Arc::from(self.odata_versions),
+            check_membership: self.check_membership,
+            request_id_header: self.request_id_header,
+            collection_delete: self.collection_delete,
+            action_visibility: self.action_visibility,
+            privilege_registry: self.privilege_registry,
+            property_validators: Arc::from(self.property_validators),
+            partial_patch: self.partial_patch,
+            page_size: self.page_size,
+            max_response_size: self.max_response_size,
+            lifecycle: self.lifecycle,
+            statistics: self.statistics,
+            tasks: self.tasks,
+            configuration_backup: self.configuration_backup,
+            clock: self.clock,
+            #[cfg(feature = "fault-injection")]
+            faults: self.faults.map(Arc::new),
+        };
+        state.lockouts.set_clock(state.clock.clone());
+        state.events.set_clock(state.clock.clone());
+        if let Some(tasks) = &state.tasks {
+            tasks.set_event_service(state.events.clone());
+            tasks.set_clock(state.clock.clone());
+            tasks.set_location(self.task_location);
+        }
+        if let Some(lifecycle) = &state.lifecycle {
+            lifecycle.start(state.tree.clone());
+        }
+        if let Some(interval) = self.session_sweep {
+            sweep_sessions(state.clone(), interval);
+        }
+        router(state)
+    }
+}
+
+fn router(state: AppState) -> NormalizePath<Router> {
+    let mut app = Router::new()
+        .route("/redfish", get(get_redfish))
+        .route("/redfish/v1/$metadata", get(get_odata_metadata_doc))
+        .route("/redfish/v1/odata", get(get_odata_service_doc))
+        .route(
+            EVENT_SERVICE_URI,
+            get(get_event_service).patch(patch_event_service),
+        )
+        .route(SSE_URI, get(get_event_stream))
+        .route(
+            "/redfish/*path",
+            get(getter).post(poster).delete(deleter).patch(patcher),
+        );
+    if state.tasks.is_some() {
+        app = app
+            .route(TASK_SERVICE_URI, get(get_task_service))
+            .route(TASKS_URI, get(get_task_service))
+            .route(&format!("{}/:id", TASKS_URI), get(get_task_service))
+            .route(&format!("{}/:id", TASK_MONITORS_URI), get(get_task_monitor));
+    }
+    if state.subscriptions.is_some() {
+        app = app
+            .route(
+                SUBSCRIPTIONS_URI,
+                get(get_subscriptions).post(post_subscription),
+            )
+            .route(
+                &format!("{}/:id", SUBSCRIPTIONS_URI),
+                get(get_subscriptions).delete(delete_subscription),
+            );
+    }
+    if let Some(backup) = &state.configuration_backup {
+        app = app
+            .route(&backup.get_target(BACKUP_ACTION), post(post_backup))
+            .route(&backup.get_target(RESTORE_ACTION), post(post_restore));
+    }
+    if let Some(registry) = state.privilege_registry {
+        let file_uri = format!("{}/{}", REGISTRIES_URI, registry.get_id());
+        let location = format!("{}/{}.json", file_uri, registry.get_id());
+        app = app
+            .route(&file_uri, get(get_privilege_registry))
+            .route(&location, get(get_privilege_registry));
+    }
+    // Innermost, so that the faults look to everything else like the service's own responses.
+    #[cfg(feature = "fault-injection")]
+    if let Some(scenario) = state.faults.clone() {
+        app = app.layer(middleware::from_fn_with_state(scenario, inject_faults));
+    }
+    if let Some(tasks) = state.tasks.clone() {
+        app = app.layer(middleware::from_fn_with_state(tasks, respond_async));
+    }
+    if let Some(statistics) = state.statistics.clone() {
+        app = app.layer(middleware::from_fn_with_state(
+            statistics,
+            record_statistics,
+        ));
+    }
+    if let Some(lifecycle) = state.lifecycle.clone() {
+        app = app.layer(middleware::from_fn_with_state(lifecycle, check_lifecycle));
+    }
+    if let Some(limiter) = state.concurrency_limiter.clone() {
+        app = app.layer(middleware::from_fn_with_state(limiter, limit_concurrency));
+    }
+    if let Some(registries) = state.localized_registries.clone() {
+        app = app.layer(middleware::from_fn_with_state(registries, select_language));
+    }
+    if let Some(hook) = state.error_hook.clone() {
+        app = app.layer(middleware::from_fn_with_state(hook, extend_errors));
+    }
+    app = app.layer(middleware::from_fn(track_request_user));
+    app = app.layer(middleware::from_fn_with_state(
+        state.request_id_header,
+        assign_request_id,
+    ));
+    // Outermost, so that requests for other hosts are refused before anything else.
+    if let Some(allowed) = state.allowed_hosts.clone() {
+        app = app.layer(middleware::from_fn_with_state(allowed, validate_host));
+    }
+    let app = app.with_state(state);
+
+    NormalizePathLayer::trim_trailing_slash().layer(app)
+}
+
+#[derive(Clone)]
+struct AppState {
+    tree: Arc<tokio::sync::RwLock<dyn Tree + Send + Sync>>,
+    sessions: SessionManager,
+    lockouts: AccountLockouts,
+    events: EventService,
+    subscriptions: Option<Subscriptions>,
+    audit_hook: Option<Arc<dyn AuditHook>>,
+    error_hook: Option<Arc<dyn ErrorHook>>,
+    authentication_provider: Arc<dyn AuthenticationProvider>,
+    external_accounts: ExternalAccounts,
+    auth_throttle: Option<Arc<AuthThrottle>>,
+    concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    create_limiter: Arc<CreateLimiter>,
+    session_limit: Option<SessionLimitConfig>,
+    session_tokens: SessionTokenConfig,
+    disabled_sessions: DisabledSessionPolicy,
+    allowed_hosts: Option<Arc<AllowedHosts>>,
+    document_access: DocumentAccess,
+    documents: Arc<DocumentCaching>,
+    odata_context: bool,
+    computed_etags: bool,
+    localized_registries: Option<Arc<LocalizedRegistries>>,
+    odata_versions: Arc<[String]>,
+    check_membership: bool,
+    request_id_header: bool,
+    collection_delete: bool,
+    action_visibility: ActionVisibility,
+    privilege_registry: Option<&'static PrivilegeRegistry>,
+    property_validators: Arc<[Arc<dyn PropertyValidator>]>,
+    partial_patch: PartialPatch,
+    page_size: Option<usize>,
+    max_response_size: Option<usize>,
+    lifecycle: Option<Lifecycle>,
+    statistics: Option<ResponseStatistics>,
+    tasks: Option<TaskService>,
+    configuration_backup: Option<ConfigurationBackup>,
+    clock: Arc<dyn Clock>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<FaultScenario>>,
+}
+
+// Emit the event and audit entry for a change in the lifecycle of a session.
+// The source is the IP address of the client responsible for the change.
+fn record_session_action(
+    state: &AppState,
+    action: AuditAction,
+    session: &Session,
+    source: Option<IpAddr>,
+) {
+    let message = get_session_message(action, &session.username, source);
+    state.events.send(message, Some(session.uri.as_str()));
+    if let Some(hook) = &state.audit_hook {
+        hook.record(&AuditEntry {
+            timestamp: state.clock.system_time(),
+            action,
+            username: session.username.clone(),
+            uri: session.uri.clone(),
+            source,
+            request_id: current_request_id(),
+        });
+    }
+}
+
+fn get_client_ip(connect_info: Option<ConnectInfo<SocketAddr>>) -> Option<IpAddr> {
+    connect_info.map(|ConnectInfo(addr)| addr.ip())
+}
+
+// Who a request for a resource the service serves itself is from, such as for the
+// EventService, with what the service needs to authorize it as it does those of the tree.
+struct ServedUser {
+    username: String,
+    privileges: Option<Vec<String>>,
+    ancestors: Vec<String>,
+}
+
+impl ServedUser {
+    // Refuse the request for the resource at the URI unless it is from an authenticated user.
+    async fn authenticate(
+        state: &AppState,
+        headers: &HeaderMap,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        uri: &str,
+    ) -> Result<Self, Error> {
+        let username = get_request_username(headers, state, get_client_ip(connect_info))
+            .await?
+            .ok_or(Error::Unauthorized)?;
+        let privileges = get_user_privileges(state, Some(&username)).await;
+        let ancestors = {
+            let tree = state.tree.read().await;
+            get_ancestor_entities(state, &*tree, uri, Some(&username)).await
+        };
+        Ok(Self {
+            username,
+            privileges,
+            ancestors,
+        })
+    }
+
+    // Refuse the operation on the node, which writes the properties, if any, unless the user's
+    // privileges allow it.
+    fn authorize(
+        &self,
+        state: &AppState,
+        node: &dyn Node,
+        operation: &Method,
+        properties: Vec<String>,
+    ) -> Result<(), Error> {
+        let scope = OperationScope::new(&self.ancestors).with_properties(properties);
+        authorize(
+            state,
+            node,
+            operation,
+            Some(&self.username),
+            self.privileges.as_deref(),
+            &scope,
+        )
+    }
+}
+
+// The EventService resource is served from the settings of the running event service,
+// rather than from the tree, so that patching it reconfigures the service.
+async fn get_event_service(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    // Its body is not queried, but parameters the service does not support are still refused.
+    parse_query(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, EVENT_SERVICE_URI).await?;
+    let node = get_event_service_node(&state);
+    user.authorize(&state, &node, &Method::GET, Vec::new())?;
+    get_node_get_response(&state, &node)
+}
+
+fn get_event_service_node(state: &AppState) -> EventServiceNode {
+    state.events.get_node(state.subscriptions.is_some())
+}
+
+// The collection of EventDestinations that clients have subscribed with, and each of them.
+async fn get_subscriptions(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    uri: Uri,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    parse_query(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, uri.path()).await?;
+    let node = get_subscription_node(&state, uri.path())?;
+    user.authorize(&state, &node, &Method::GET, Vec::new())?;
+    get_node_get_response(&state, &node)
+}
+
+fn get_subscription_node(state: &AppState, uri: &str) -> Result<SubscriptionNode, Error> {
+    let subscriptions = state.subscriptions.as_ref().ok_or(Error::NotFound)?;
+    if uri == SUBSCRIPTIONS_URI {
+        return Ok(subscriptions.get_collection_node());
+    }
+    uri.strip_prefix(SUBSCRIPTIONS_URI)
+        .and_then(|id| id.strip_prefix('/'))
+        .and_then(|id| subscriptions.get_node(id))
+        .ok_or(Error::NotFound)
+}
+
+async fn post_subscription(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+    JsonBody(payload): JsonBody,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, SUBSCRIPTIONS_URI).await?;
+    let collection = get_subscription_node(&state, SUBSCRIPTIONS_URI)?;
+    user.authorize(&state, &collection, &Method::POST, Vec::new())?;
+    let required = [String::from("Destination"), String::from("Protocol")];
+    check_required_properties(&payload, &required)?;
+    let subscription = NewSubscription::parse(payload)?;
+    // Only present once the service lets clients subscribe.
+    let subscriptions = state.subscriptions.as_ref().unwrap();
+    let id = subscriptions.create(&state, subscription, &user.username);
+    let node = subscriptions.get_node(&id).ok_or(Error::InternalError)?;
+    state.events.resource_created(node.get_uri());
+    let mut response_headers = ResponseHeaders::for_node(&get_node_meta(&state, &node)?);
+    let body = match prefers_minimal_response(&headers) {
+        true => {
+            response_headers = response_headers.with(
+                HeaderName::from_static("preference-applied"),
+                "return=minimal",
+            );
+            None
+        }
+        false => Some(get_response_body(&state, &node)?),
+    };
+    Ok(get_node_created_response(
+        response_headers,
+        node.get_uri(),
+        body,
+    ))
+}
+
+async fn delete_subscription(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+    let uri = format!("{}/{}", SUBSCRIPTIONS_URI, id);
+    let user = ServedUser::authenticate(&state, &headers, connect_info, &uri).await?;
+    let node = get_subscription_node(&state, &uri)?;
+    user.authorize(&state, &node, &Method::DELETE, Vec::new())?;
+    // Only present once the service lets clients subscribe.
+    if state.subscriptions.as_ref().unwrap().remove(&id) {
+        state.events.resource_removed(&uri);
+    }
+    Ok((StatusCode::NO_CONTENT, [("Cache-Control", "no-cache")]).into_response())
+}
+
+// Authorize the user to invoke an action of the manager the configuration is backed up from,
+// as they would have to be for the actions of the tree.
+async fn authorize_configuration_backup(
+    state: &AppState,
+    headers: &HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+) -> Result<ConfigurationBackup, Error> {
+    // Only present once the service backs up its configuration.
+    let backup = state.configuration_backup.clone().unwrap();
+    let uri = backup.get_manager_uri();
+    let user = ServedUser::authenticate(state, headers, connect_info, uri).await?;
+    let tree = state.tree.read().await;
+    let node = tree.get(uri, Some(&user.username)).await?;
+    user.authorize(state, &*node, &Method::POST, Vec::new())?;
+    Ok(backup)
+}
+
+// Download a snapshot of the configuration, as the archive the store makes of it.
+async fn post_backup(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+    let backup = authorize_configuration_backup(&state, &headers, connect_info).await?;
+    let body = backup.snapshot().await?;
+    Ok((
+        StatusCode::OK,
+        [(
+            header::CONTENT_TYPE,
+            mime::APPLICATION_OCTET_STREAM.as_ref(),
+        )],
+        COMMON_RESPONSE_HEADERS,
+        body,
+    )
+        .into_response())
+}
+
+// Upload an archive downloaded from BackupConfiguration, and restore the configuration from
+// it. The archive is in the body of the request, as application/octet-stream.
+async fn post_restore(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+    request: Request<Body>,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+    let backup = authorize_configuration_backup(&state, &headers, connect_info).await?;
+    if !get_media_type(&headers).is_some_and(|media_type| {
+        media_type.essence_str() == mime::APPLICATION_OCTET_STREAM.essence_str()
+    }) {
+        return Err(Error::UnsupportedMediaType);
+    }
+    let total = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse().ok());
+    stream_upload(request.into_body(), backup.receive().await?, total).await?;
+    match &state.tasks {
+        Some(tasks) => {
+            let task = tasks.start("Restore Configuration");
+            let response = get_task_started_response(&task);
+            backup.restore_as_task(task);
+            Ok(response)
+        }
+        None => {
+            backup.restore().await?;
+            Ok((StatusCode::NO_CONTENT, COMMON_RESPONSE_HEADERS).into_response())
+        }
+    }
+}
+
+// The TaskService, its collection of tasks, and each task.
+async fn get_task_service(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    uri: Uri,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    // Their bodies are not queried, but parameters the service does not support are still
+    // refused.
+    parse_query(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, uri.path()).await?;
+    let tasks = state.tasks.as_ref().ok_or(Error::NotFound)?;
+    let reader = current_user();
+    let node = match uri.path() {
+        TASK_SERVICE_URI => tasks.get_service_node(),
+        TASKS_URI => tasks.get_collection_node(reader.as_ref()),
+        path => {
+            let id = path
+                .strip_prefix(TASKS_URI)
+                .and_then(|id| id.strip_prefix('/'))
+                .ok_or(Error::NotFound)?;
+            check_task_reader(tasks, id, reader.as_ref())?;
+            tasks.get_task_node(id).ok_or(Error::NotFound)?
+        }
+    };
+    user.authorize(&state, &node, &Method::GET, Vec::new())?;
+    get_node_get_response(&state, &node)
+}
+
+fn check_task_reader(
+    tasks: &TaskService,
+    id: &str,
+    reader: Option<&RequestUser>,
+) -> Result<(), Error> {
+    match tasks.may_read(id, reader) {
+        Some(true) => Ok(()),
+        Some(false) => Err(Error::Forbidden),
+        None => Err(Error::NotFound),
+    }
+}
+
+// The MessageRegistryFile of the privilege registry, and the registry it locates.
+async fn get_privilege_registry(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    uri: Uri,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    parse_query(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, uri.path()).await?;
+    let registry = state.privilege_registry.ok_or(Error::NotFound)?;
+    let node = RegistryNode::get(registry, uri.path()).ok_or(Error::NotFound)?;
+    user.authorize(&state, &node, &Method::GET, Vec::new())?;
+    get_node_get_response(&state, &node)
+}
+
+// 202 Accepted with the task while it runs, and once it is complete, 200 OK with the response
+// it completed with, or else the task.
+async fn get_task_monitor(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    let task_uri = format!("{}/{}", TASKS_URI, id);
+    let user = ServedUser::authenticate(&state, &headers, connect_info, &task_uri).await?;
+    let tasks = state.tasks.as_ref().ok_or(Error::NotFound)?;
+    // Monitors are read as the tasks they are of are.
+    check_task_reader(tasks, &id, current_user().as_ref())?;
+    let node = tasks.get_task_node(&id).ok_or(Error::NotFound)?;
+    user.authorize(&state, &node, &Method::GET, Vec::new())?;
+    let response = tasks.get_monitor_response(&id).ok_or(Error::NotFound)?;
+    let mut response_headers = HeaderMap::new();
+    let status = match (response.state, response.created) {
+        (TaskState::Running, _) => StatusCode::ACCEPTED,
+        (_, Some(created)) => {
+            let created = HeaderValue::from_str(&created).map_err(|_| Error::InternalError)?;
+            response_headers.insert(header::LOCATION, created);
+            StatusCode::CREATED
+        }
+        (_, None) => StatusCode::OK,
+    };
+    if let Some(task_uri) = response.task_uri {
+        // Made of characters that are valid in headers.
+        response_headers.insert(
+            header::CONTENT_LOCATION,
+            HeaderValue::from_str(&task_uri).unwrap(),
+        );
+    }
+    Ok((
+        COMMON_RESPONSE_HEADERS,
+        JsonResponse::new(status, response_headers, response.body),
+    )
+        .into_response())
+}
+
+async fn patch_event_service(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+    JsonBody(payload): JsonBody,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+    let user = ServedUser::authenticate(&state, &headers, connect_info, EVENT_SERVICE_URI).await?;
+    let node = get_event_service_node(&state);
+    user.authorize(
+        &state,
+        &node,
+        &Method::PATCH,
+        payload.keys().cloned().collect(),
+    )?;
+    let (payload, mut messages) = validate_properties(&get_event_service_type(), payload);
+    // Only the properties of the settings can be written. Anything else in the body is either
+    // read-only, or not a property of the resource at all.
+    let body = node.get_body();
+    let mut settings = state.events.get_settings();
+    let mut written = false;
+    for (name, value) in payload {
+        let count = value.as_u64().and_then(|count| u32::try_from(count).ok());
+        let (key, args) = match (name.as_str(), &value, count) {
+            ("ServiceEnabled", Value::Bool(enabled), _) => {
+                settings.service_enabled = *enabled;
+                written = true;
+                continue;
+            }
+            ("DeliveryRetryAttempts", _, Some(count)) => {
+                settings.delivery_retry_attempts = count;
+                written = true;
+                continue;
+            }
+            ("DeliveryRetryIntervalSeconds", _, Some(count)) => {
+                settings.delivery_retry_interval_seconds = count;
+                written = true;
+                continue;
+            }
+            // Integers that are negative, or too large.
+            ("DeliveryRetryAttempts" | "DeliveryRetryIntervalSeconds", _, None) => (
+                "PropertyValueOutOfRange",
+                vec![value.to_string(), name.clone()],
+            ),
+            _ if body.get(&name).is_some() => ("PropertyNotWritable", vec![name.clone()]),
+            _ => ("PropertyUnknown", vec![name.clone()]),
+        };
+        let message = Message::from_registry(
+            base_registry(),
+            key,
+            ResourceSchemaVersion::new(1, 1, 2),
+            args,
+            vec![format!("#/{}", name)],
+        )
+        .unwrap();
+        messages.push(message);
+    }
+    if (!written || state.partial_patch == PartialPatch::Refused) && !messages.is_empty() {
+        return Err(Error::InvalidProperties(messages));
+    }
+    state.events.set_settings(settings);
+    get_patched_response(&state, &get_event_service_node(&state), messages)
+}
+
+// Stream every event the service generates to the client, until it disconnects, or only the
+// records of those that $filter matches, by the properties get_record_properties() gives them.
+// Events that a slow client falls too far behind on are skipped.
+async fn get_event_stream(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    let pairs = get_query_pairs(query.as_deref())?;
+    let mut query = Query::from_pairs(&pairs)?;
+    let filter = query.filter.take();
+    if !query.is_empty() {
+        return Err(Error::InvalidQuery(
+            String::from("QueryNotSupportedOnResource"),
+            Vec::new(),
+        ));
+    }
+    if filter
+        .as_ref()
+        .is_some_and(|filter| !has_supported_formats(filter))
+    {
+        return Err(Error::InvalidQuery(
+            String::from("QueryParameterValueError"),
+            vec![String::from("$filter")],
+        ));
+    }
+    // Like the IncludeOriginOfCondition of an EventDestination.
+    let include_origins = match pairs
+        .iter()
+        .find(|(key, _)| key == "includeoriginofcondition")
+    {
+        None => false,
+        Some((_, value)) => match value.as_str() {
+            "true" => true,
+            "false" => false,
+            _ => return Err(Error::BadRequest),
+        },
+    };
+    let user = ServedUser::authenticate(&state, &headers, connect_info, SSE_URI).await?;
+    // The stream is read as the EventService it is of is.
+    user.authorize(
+        &state,
+        &get_event_service_node(&state),
+        &Method::GET,
+        Vec::new(),
+    )?;
+    let username = user.username;
+    if !state.events.is_enabled() {
+        return Err(Error::ServiceDisabled(String::from("EventService")));
+    }
+    // Both events and MetricReports, unless the filter picks one by its EventFormatType.
+    let receivers = (
+        state.events.subscribe(),
+        state.events.subscribe_metric_reports(),
+    );
+    let stream = futures_util::stream::unfold(receivers, move |(mut events, mut reports)| {
+        let filter = filter.clone();
+        let state = state.clone();
+        let username = username.clone();
+        async move {
+            loop {
+                let sse_event = tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(mut event) => {
+                            if let Some(filter) = &filter {
+                                let matches = |record: &EventRecord, resource_type: Option<&str>| {
+                                    filter.matches(&get_record_properties(record, resource_type))
+                                };
+                                filter_event(&mut event, matches, &state, &username).await;
+                                if event.get_records().is_empty() {
+                                    continue;
+                                }
+                            }
+                            let mut body = event.to_json();
+                            if include_origins {
+                                include_origins_of_condition(&mut body, &state, &username).await;
+                            }
+                            SseEvent::default()
+                                .id(event.get_id())
+                                .data(Value::Object(body).to_string())
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    },
+                    report = reports.recv() => match report {
+                        Ok((id, report)) => {
+                            let properties = get_metric_report_properties(&report);
+                            if filter.as_ref().is_some_and(|f| !f.matches(&properties)) {
+                                continue;
+                            }
+                            SseEvent::default().id(id).data(report.to_string())
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    },
+                };
+                return Some((Ok::<_, Infallible>(sse_event), (events, reports)));
+            }
+        }
+    });
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response())
 }
 
-// TODO: Better way to declare tree type???
-pub fn app<T: Tree + Send + Sync + 'static>(tree: T) -> NormalizePath<Router> {
-    let state = AppState {
-        tree: Arc::new(tokio::sync::RwLock::new(tree)),
-        sessions: Arc::new(std::sync::RwLock::new(Vec::new())),
+// Replace the link to the OriginOfCondition of each record of the event with the body of the
+// resource, as the user reads it at the time. Links to those the user cannot read are kept.
+async fn include_origins_of_condition(
+    event: &mut Map<String, Value>,
+    state: &AppState,
+    username: &str,
+) {
+    let records = match event
+        .get_mut("Events")
+        .and_then(|records| records.as_array_mut())
+    {
+        Some(records) => records,
+        None => return,
     };
+    for record in records {
+        let origin = match record
+            .pointer("/OriginOfCondition/@odata.id")
+            .and_then(|origin| origin.as_str())
+        {
+            Some(origin) => String::from(origin),
+            None => continue,
+        };
+        if let Ok(body) = read_origin_body(state, &origin, username).await {
+            record["OriginOfCondition"] = body;
+        }
+    }
+}
 
-    let app = Router::new()
-        .route("/redfish", get(get_redfish))
-        .route("/redfish/v1/$metadata", get(get_odata_metadata_doc))
-        .route("/redfish/v1/odata", get(get_odata_service_doc))
-        .route(
-            "/redfish/*path",
-            get(getter).post(poster).delete(deleter).patch(patcher),
-        )
-        .with_state(state);
-
-    NormalizePathLayer::trim_trailing_slash().layer(app)
+// Whether every EventFormatType the filter compares with is one the service can send.
+fn has_supported_formats(filter: &Filter) -> bool {
+    match filter {
+        Filter::And(left, right) | Filter::Or(left, right) => {
+            has_supported_formats(left) && has_supported_formats(right)
+        }
+        Filter::Not(filter) => has_supported_formats(filter),
+        Filter::Compare(property, _, value) if property == "EventFormatType" => EVENT_FORMAT_TYPES
+            .iter()
+            .any(|format| value.as_str() == Some(&format.to_string())),
+        Filter::Compare(..) => true,
+    }
 }
 
-struct Session {
-    token: String,
-    username: String,
-    uri: String,
+// The body of the origin of an event for its subscriber, as they would GET it now.
+async fn read_origin_body(state: &AppState, origin: &str, username: &str) -> Result<Value, Error> {
+    let privileges = get_user_privileges(state, Some(username)).await;
+    let tree = state.tree.read().await;
+    read_node_body(state, &*tree, origin, Some(username), privileges.as_deref()).await
 }
 
-#[derive(Clone)]
-struct AppState {
-    tree: Arc<tokio::sync::RwLock<dyn Tree + Send + Sync>>,
-    sessions: Arc<std::sync::RwLock<Vec<Session>>>,
+// Drop the records of the event that do not match, given the ResourceType of each. That is the
+// type of its origin as the user reads it now, so there is none for resources since removed,
+// or for those the user cannot read.
+async fn filter_event(
+    event: &mut Event,
+    matches: impl Fn(&EventRecord, Option<&str>) -> bool,
+    state: &AppState,
+    username: &str,
+) {
+    let mut kept = Vec::new();
+    for record in event.get_records() {
+        let resource_type = match record.get_origin_of_condition() {
+            Some(origin) => read_origin_body(state, origin, username)
+                .await
+                .ok()
+                .and_then(|body| get_type_name(&body).map(String::from)),
+            None => None,
+        };
+        kept.push(matches(record, resource_type.as_deref()));
+    }
+    let mut kept = kept.into_iter();
+    event.retain_records(|_| kept.next().unwrap_or(false));
 }
 
-fn validate_odata_version(headers: &HeaderMap) -> Result<(), Error> {
+fn validate_odata_version(headers: &HeaderMap, state: &AppState) -> Result<(), Error> {
     if let Some(odata_version) = headers.get("odata-version") {
-        if odata_version != "4.0" {
-            return Err(Error::BadODataVersion);
+        let supported = &state.odata_versions;
+        if !supported
+            .iter()
+            .any(|version| odata_version == version.as_str())
+        {
+            let requested = String::from_utf8_lossy(odata_version.as_bytes()).into_owned();
+            return Err(Error::BadODataVersion(requested, supported.to_vec()));
         }
     }
     Ok(())
 }
 
+// The URI of the node a request for the given path is for, and whether the path was for the
+// Members of a collection. OData addresses those as "<collection>/Members", which is the
+// collection itself.
+fn get_request_uri(path: &str) -> (String, bool) {
+    let uri = "/redfish/".to_owned() + path;
+    match uri.strip_suffix("/Members") {
+        Some(collection) => (collection.to_string(), true),
+        None => (uri, false),
+    }
+}
+
+fn is_collection(tree: &(dyn Tree + Send + Sync), meta: &NodeMeta) -> bool {
+    let described_by = match &meta.described_by {
+        Some(described_by) => described_by,
+        None => return false,
+    };
+    tree.get_collection_types()
+        .iter()
+        .any(|collection_type| collection_type.described_by == *described_by)
+}
+
+// Respond to a GET of a collection with ?only with its member, if it has exactly one.
+async fn get_only_member(
+    state: &AppState,
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    username: Option<&str>,
+    privileges: Option<&[String]>,
+    method: &Method,
+) -> Result<Response, Error> {
+    let member_uri = {
+        let node = tree.get(uri, username).await?;
+        if !is_collection(tree, &node.get_meta()) {
+            return Err(Error::InvalidQuery(
+                String::from("QueryNotSupportedOnResource"),
+                Vec::new(),
+            ));
+        }
+        let body = node.try_get_body()?;
+        let members = body["Members"].as_array().cloned().unwrap_or_default();
+        match &members[..] {
+            [member] => match member.get("@odata.id").and_then(Value::as_str) {
+                Some(member_uri) => String::from(member_uri),
+                None => return Err(Error::InternalError),
+            },
+            _ => {
+                let args = vec![
+                    members.len().to_string(),
+                    String::from("only"),
+                    String::from("1"),
+                ];
+                return Err(Error::InvalidQuery(
+                    String::from("QueryParameterOutOfRange"),
+                    args,
+                ));
+            }
+        }
+    };
+    let ancestors = get_ancestor_entities(state, tree, &member_uri, username).await;
+    let scope = OperationScope::new(&ancestors);
+    let node = tree.get(&member_uri, username).await?;
+    record_node_type(tree, &node.get_meta());
+    authorize(state, &*node, method, username, privileges, &scope)?;
+    if *method == Method::HEAD && node.get_raw_body().is_none() {
+        return Ok(get_node_head_response(&get_node_meta(state, &*node)?));
+    }
+    if node.get_raw_body().is_some() {
+        return get_node_get_response(state, &*node);
+    }
+    let may_post = authorize(state, &*node, &Method::POST, username, privileges, &scope).is_ok();
+    let mut body = get_response_body(state, &*node)?;
+    restrict_actions(
+        tree,
+        &member_uri,
+        username,
+        may_post,
+        state.action_visibility,
+        &mut body,
+    );
+    let headers = ResponseHeaders::for_node(&get_node_meta(state, &*node)?).into_header_map();
+    Ok(JsonResponse::new(StatusCode::OK, headers, body)
+        .with_max_size(state.max_response_size)
+        .into_response())
+}
+
 #[debug_handler]
 async fn getter(
     headers: HeaderMap,
     Path(path): Path<String>,
     State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    method: Method,
+    RawQuery(query): RawQuery,
 ) -> Result<Response, Error> {
-    validate_odata_version(&headers)?;
-    let uri = "/redfish/".to_owned() + &path;
+    validate_odata_version(&headers, &state)?;
+    let (uri, is_members) = get_request_uri(&path);
+    let query = parse_query(query.as_deref())?;
+    let user = get_request_username(&headers, &state, get_client_ip(connect_info)).await?;
+    let privileges = get_user_privileges(&state, user.as_deref()).await;
     let tree = state.tree.read().await;
-    let user = get_request_username(&headers, &state)?;
-    let node = tree.get(uri.as_str(), user.as_deref()).await?;
-    if let Some(header_etag) = get_etag_from_header(&headers, "if-none-match") {
-        if let Some(node_etag) = node.get_etag() {
-            if (node_etag.weak && node_etag.weak_eq(&header_etag))
-                || node_etag.strong_eq(&header_etag)
-            {
-                return Ok((StatusCode::NOT_MODIFIED, COMMON_RESPONSE_HEADERS).into_response());
-            }
+    if query.only {
+        if query
+            != (Query {
+                only: true,
+                ..Default::default()
+            })
+        {
+            return Err(Error::InvalidQuery(
+                String::from("QueryCombinationInvalid"),
+                Vec::new(),
+            ));
         }
+        return get_only_member(
+            &state,
+            &*tree,
+            &uri,
+            user.as_deref(),
+            privileges.as_deref(),
+            &method,
+        )
+        .await;
     }
-    Ok(get_node_get_response(node).into_response())
+    let (forwarded, remaining) = split_query(query.clone(), tree.get_native_query_support());
+    let ancestors = get_ancestor_entities(&state, &*tree, &uri, user.as_deref()).await;
+    let scope = OperationScope::new(&ancestors);
+    // Nodes may not be Send, so they cannot be held while other nodes are read below.
+    let (response_headers, body) = {
+        let node = match forwarded.is_empty() {
+            true => tree.get(uri.as_str(), user.as_deref()).await?,
+            false => {
+                tree.get_with_query(uri.as_str(), user.as_deref(), &forwarded)
+                    .await?
+            }
+        };
+        let mut meta = node.get_meta();
+        record_node_type(&*tree, &meta);
+        if is_members && !is_collection(&*tree, &meta) {
+            return Err(Error::NotFound);
+        }
+        authorize(
+            &state,
+            &*node,
+            &method,
+            user.as_deref(),
+            privileges.as_deref(),
+            &scope,
+        )?;
+        add_computed_etag(&state, &*node, &mut meta)?;
+        match evaluate_preconditions(&headers, &method, meta.etag.as_ref()) {
+            Precondition::Passed => {}
+            Precondition::NotModified => return Ok(get_not_modified_response(&meta)),
+            Precondition::Failed => return Err(Error::PreconditionFailed),
+        }
+        let is_raw = node.get_raw_body().is_some();
+        // HEAD responses have no body, so there is no need to make one.
+        if method == Method::HEAD && !is_raw {
+            return Ok(get_node_head_response(&meta));
+        }
+        if is_raw {
+            return get_node_get_response(&state, &*node);
+        }
+        let may_post = authorize(
+            &state,
+            &*node,
+            &Method::POST,
+            user.as_deref(),
+            privileges.as_deref(),
+            &scope,
+        )
+        .is_ok();
+        let mut body = get_response_body(&state, &*node)?;
+        restrict_actions(
+            &*tree,
+            &uri,
+            user.as_deref(),
+            may_post,
+            state.action_visibility,
+            &mut body,
+        );
+        let response_headers = ResponseHeaders::for_node(&meta).into_header_map();
+        // Collections may have to be split into pages even when the client asks for none.
+        if remaining.is_empty() && state.page_size.is_none() {
+            return Ok(JsonResponse::new(StatusCode::OK, response_headers, body)
+                .with_max_size(state.max_response_size)
+                .into_response());
+        }
+        (response_headers, body)
+    };
+    let paging = Paging {
+        uri: &uri,
+        query: &query,
+        page_size: state.page_size,
+    };
+    let reader = Reader {
+        state: &state,
+        tree: &*tree,
+        username: user.as_deref(),
+        privileges: privileges.as_deref(),
+    };
+    let body = apply_query(&remaining, &reader, body, &paging).await?;
+    Ok(JsonResponse::new(StatusCode::OK, response_headers, body)
+        .with_max_size(state.max_response_size)
+        .into_response())
 }
 
-fn get_etag_from_header(headers: &HeaderMap, header_name: &str) -> Option<EntityTag> {
-    let etag = match headers.get(header_name)?.to_str() {
-        Ok(etag) => etag,
-        _ => return None,
-    };
-    match EntityTag::from_str(etag) {
-        Ok(etag) => Some(etag),
-        _ => None,
+// The response to a conditional GET or HEAD when the client's copy is current. It carries the
+// ETag, for the client to know which copy that is.
+fn get_not_modified_response(meta: &NodeMeta) -> Response {
+    let mut response = (StatusCode::NOT_MODIFIED, COMMON_RESPONSE_HEADERS).into_response();
+    if let Some(etag) = &meta.etag {
+        if let Ok(value) = HeaderValue::from_str(&etag.to_string()) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
     }
+    response
 }
 
 #[debug_handler]
@@ -171,21 +1942,229 @@ async fn deleter(
     headers: HeaderMap,
     Path(path): Path<String>,
     State(state): State<AppState>,
-) -> Result<impl IntoResponse, Error> {
-    validate_odata_version(&headers)?;
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
     let uri = "/redfish/".to_owned() + &path;
+    let source = get_client_ip(connect_info);
+    let user = get_request_username(&headers, &state, source).await?;
+    let privileges = get_user_privileges(&state, user.as_deref()).await;
+    let privileges = privileges.as_deref();
     let mut tree = state.tree.write().await;
-    let user = get_request_username(&headers, &state)?;
+    check_preconditions(
+        &state,
+        &*tree,
+        &uri,
+        user.as_deref(),
+        privileges,
+        &headers,
+        &Method::DELETE,
+    )
+    .await?;
+    if state.collection_delete {
+        if let Some(members) = get_collection_members(&*tree, &uri, user.as_deref()).await {
+            let messages = delete_members(
+                &state,
+                &mut *tree,
+                members,
+                user.as_deref(),
+                privileges,
+                source,
+            )
+            .await;
+            let body = json!({ "@Message.ExtendedInfo": messages });
+            return Ok((StatusCode::OK, COMMON_RESPONSE_HEADERS, Json(body)).into_response());
+        }
+    }
+    match delete_node(
+        &state,
+        &mut *tree,
+        &uri,
+        user.as_deref(),
+        privileges,
+        source,
+    )
+    .await?
+    {
+        Outcome::Done(()) => {
+            Ok((StatusCode::NO_CONTENT, [("Cache-Control", "no-cache")]).into_response())
+        }
+        Outcome::Async(task) => Ok(get_task_started_response(&task)),
+    }
+}
 
-    tree.delete(uri.as_str(), user.as_deref()).await?;
-    let mut sessions = state.sessions.write().unwrap();
-    for index in 0..sessions.len() {
-        if sessions[index].uri == uri {
-            sessions.swap_remove(index);
-            break;
+// Refuse a request to change the node at the URI, such as a PATCH or DELETE, if its If-Match
+// or If-None-Match header does not hold for the node as it is. Nodes that cannot be read are
+// left to the request to fail for, such as with 404. The ETag is part of what the user reads,
+// so they have to be allowed to read the node for it to be checked.
+async fn check_preconditions(
+    state: &AppState,
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    user: Option<&str>,
+    privileges: Option<&[String]>,
+    headers: &HeaderMap,
+    method: &Method,
+) -> Result<(), Error> {
+    if !headers.contains_key(header::IF_MATCH) && !headers.contains_key(header::IF_NONE_MATCH) {
+        return Ok(());
+    }
+    let ancestors = get_ancestor_entities(state, tree, uri, user).await;
+    let node = match tree.get(uri, user).await {
+        Ok(node) => node,
+        // Without a current representation, If-Match is false even for *, by RFC 9110
+        // section 13.1.1, and that is answered before that the resource is missing.
+        Err(Error::NotFound) if headers.contains_key(header::IF_MATCH) => {
+            return Err(Error::PreconditionFailed)
+        }
+        // The operation itself is refused as it would be without the headers.
+        Err(_) => return Ok(()),
+    };
+    let scope = OperationScope::new(&ancestors);
+    authorize(state, &*node, &Method::GET, user, privileges, &scope)?;
+    let meta = get_node_meta(state, &*node)?;
+    // Only GET and HEAD are answered with 304, so these are only ever refused with 412.
+    match evaluate_preconditions(headers, method, meta.etag.as_ref()) {
+        Precondition::Passed => Ok(()),
+        Precondition::NotModified | Precondition::Failed => Err(Error::PreconditionFailed),
+    }
+}
+
+// Delete the node at the URI, and what the service keeps about it, such as its session. If the
+// tree deletes it as a task, nothing the service keeps about it is removed.
+async fn delete_node(
+    state: &AppState,
+    tree: &mut (dyn Tree + Send + Sync),
+    uri: &str,
+    user: Option<&str>,
+    privileges: Option<&[String]>,
+    source: Option<IpAddr>,
+) -> Result<Outcome<()>, Error> {
+    // Read before it is deleted, as the sessions of an account are found by its UserName.
+    let ancestors = get_ancestor_entities(state, tree, uri, user).await;
+    let account_username = match tree.get(uri, user).await {
+        Ok(node) => {
+            record_node_type(tree, &node.get_meta());
+            let scope = OperationScope::new(&ancestors);
+            authorize(state, &*node, &Method::DELETE, user, privileges, &scope)?;
+            get_account_username(&*node)
         }
+        Err(_) => None,
+    };
+
+    if let Outcome::Async(task) = tree.delete_deferred(uri, user).await? {
+        return Ok(Outcome::Async(task));
+    }
+    if let Some(links) = tree.get_link_registry() {
+        links.remove_resource(uri);
+    }
+    if state.check_membership {
+        membership::check_deleted(tree, uri, user).await?;
+    }
+    if let Some(session) = state.sessions.store().remove(uri) {
+        record_session_action(state, AuditAction::SessionTerminated, &session, source);
+    }
+    // The tree stays locked until the account's sessions are gone too, so that no request
+    // sees them without it.
+    if let Some(username) = account_username {
+        terminate_sessions(state, tree, |session| session.username == username, source).await;
+    }
+    state.events.resource_removed(uri);
+    Ok(Outcome::Done(()))
+}
+
+// Remove the sessions the predicate returns true for, along with their resources.
+async fn terminate_sessions(
+    state: &AppState,
+    tree: &mut (dyn Tree + Send + Sync),
+    predicate: impl Fn(&Session) -> bool,
+    source: Option<IpAddr>,
+) {
+    for session in state.sessions.remove_where(predicate) {
+        // The session is gone for the service even if the tree cannot delete its resource.
+        tree.delete(&session.uri, Some(&session.username))
+            .await
+            .ok();
+        record_session_action(state, AuditAction::SessionTerminated, &session, source);
+        state.events.resource_removed(&session.uri);
+    }
+}
+
+// The URIs of the members of the collection at the URI, or None if it is not a collection.
+async fn get_collection_members(
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    user: Option<&str>,
+) -> Option<Vec<String>> {
+    let node = tree.get(uri, user).await.ok()?;
+    let meta = node.get_meta();
+    if !is_collection(tree, &meta) {
+        return None;
+    }
+    record_node_type(tree, &meta);
+    let body = node.try_get_body().ok()?;
+    let members = body.get("Members")?.as_array()?;
+    let uris = members
+        .iter()
+        .filter_map(|member| member.get("@odata.id")?.as_str())
+        .map(String::from)
+        .collect();
+    Some(uris)
+}
+
+// Delete each of the members that can be deleted, all under the same lock on the tree, and
+// return a message for each, saying whether it was removed. Those that were not are referred to
+// by their place in the Members of the collection.
+async fn delete_members(
+    state: &AppState,
+    tree: &mut (dyn Tree + Send + Sync),
+    members: Vec<String>,
+    user: Option<&str>,
+    privileges: Option<&[String]>,
+    source: Option<IpAddr>,
+) -> Vec<Value> {
+    let mut messages = Vec::new();
+    for (index, member) in members.iter().enumerate() {
+        let deletable = match tree.get(member, user).await {
+            Ok(node) => node.get_allowed_methods().delete,
+            Err(_) => false,
+        };
+        let deleted = deletable
+            && matches!(
+                delete_node(state, tree, member, user, privileges, source).await,
+                Ok(Outcome::Done(()))
+            );
+        let message = match deleted {
+            true => Message::from_registry(
+                resource_event_registry(),
+                "ResourceRemoved",
+                ResourceSchemaVersion::new(1, 1, 2),
+                vec![member.clone()],
+                Vec::new(),
+            ),
+            false => Message::from_registry(
+                base_registry(),
+                "ResourceCannotBeDeleted",
+                ResourceSchemaVersion::new(1, 1, 2),
+                Vec::new(),
+                vec![format!("#/Members/{}", index)],
+            ),
+        };
+        messages.push(Value::Object(message.unwrap().to_json()));
+    }
+    messages
+}
+
+// The UserName of the node if it is a ManagerAccount.
+fn get_account_username(node: &dyn Node) -> Option<String> {
+    let body = node.try_get_body().ok()?;
+    let odata_type = body.get("@odata.type")?.as_str()?;
+    if !odata_type.starts_with("#ManagerAccount.") {
+        return None;
     }
-    Ok((StatusCode::NO_CONTENT, [("Cache-Control", "no-cache")]))
+    Some(String::from(body.get("UserName")?.as_str()?))
 }
 
 #[debug_handler]
@@ -193,42 +2172,268 @@ async fn poster(
     headers: HeaderMap,
     Path(path): Path<String>,
     State(state): State<AppState>,
-    Json(payload): Json<Map<String, Value>>,
-) -> Result<impl IntoResponse, Error> {
-    validate_odata_version(&headers)?;
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+    request: Request<Body>,
+) -> Result<Response, Error> {
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
+
+    let (uri, _) = get_request_uri(&path);
+    let source = get_client_ip(connect_info);
+    let user = get_request_username(&headers, &state, source).await?;
+
+    if get_media_type(&headers).is_some_and(|media_type| {
+        media_type.essence_str() == mime::APPLICATION_OCTET_STREAM.essence_str()
+    }) {
+        return upload(&state, uri.as_str(), user.as_deref(), &headers, request).await;
+    }
+    let payload = match JsonBody::from_request(request, &state).await {
+        Ok(JsonBody(payload)) => payload,
+        Err(rejection) => return Ok(rejection),
+    };
 
-    let mut uri = "/redfish/".to_owned() + &path;
-    if let Some(stripped) = uri.strip_suffix("/Members") {
-        uri = stripped.to_string();
+    if let Some((resource_uri, action)) = split_action_uri(uri.as_str()) {
+        return post_action(&state, resource_uri, action, &payload, user.as_deref()).await;
     }
 
+    let is_session_collection = state.tree.read().await.is_session_collection(&uri);
+    // Read as the user logging in, when they are not otherwise authenticated.
+    if is_session_collection {
+        let username = payload.get("UserName").and_then(Value::as_str);
+        if !is_session_service_enabled(&state, user.as_deref().or(username)).await {
+            return Err(Error::ServiceDisabled(String::from("SessionService")));
+        }
+        // Logins missing either are refused for it once the tree is locked.
+        if let (Some(username), Some(password)) = (payload.get("UserName"), payload.get("Password"))
+        {
+            match (username.as_str(), password.as_str()) {
+                (Some(username), Some(password)) => {
+                    check_credentials(&state, username, password, source).await?
+                }
+                _ => return Err(Error::Unauthorized),
+            }
+        }
+    }
+    let privileges = get_user_privileges(&state, user.as_deref()).await;
     let mut tree = state.tree.write().await;
-    let user = get_request_username(&headers, &state)?;
+    // Collections that do not allow POST are left to the tree's create() to refuse with 405.
+    // Those the user cannot read, such as that of sessions before logging in, are checked
+    // only for what the service requires, and any Id requested is left to the tree.
+    let ancestors = get_ancestor_entities(&state, &*tree, &uri, user.as_deref()).await;
+    let required = match tree.get(uri.as_str(), user.as_deref()).await {
+        Ok(node) => {
+            record_node_type(&*tree, &node.get_meta());
+            authorize(
+                &state,
+                &*node,
+                &Method::POST,
+                user.as_deref(),
+                privileges.as_deref(),
+                &OperationScope::new(&ancestors),
+            )?;
+            match node.get_allowed_methods().post {
+                true => {
+                    if let Some(id) = payload.get("Id") {
+                        check_requested_id(&*node, id)?;
+                    }
+                    Some(node.get_required_create_properties())
+                }
+                false => None,
+            }
+        }
+        Err(_) => Some(Vec::new()),
+    };
+    if let Some(mut required) = required {
+        // The service makes sessions from these, whatever the tree requires.
+        if is_session_collection {
+            for name in ["UserName", "Password"] {
+                if !required.iter().any(|required| required == name) {
+                    required.push(String::from(name));
+                }
+            }
+        }
+        check_required_properties(&payload, &required)?;
+    }
+    // Checked with the tree locked, so that concurrent logins cannot both get under the limit.
+    let evicted = match payload.get("UserName").and_then(|name| name.as_str()) {
+        Some(username) if is_session_collection => check_session_limit(&state, username)?,
+        _ => Vec::new(),
+    };
+
+    let context = match payload.get("Context") {
+        Some(context) if is_session_collection => Some(get_session_context(context)?),
+        _ => None,
+    };
 
-    let node = tree.create(uri.as_str(), &payload, user.as_deref()).await?;
-    let mut additional_headers = HeaderMap::new();
-    // TODO: Would it be better to inspect node to see if it's a Session?
-    if uri == "/redfish/v1/SessionService/Sessions" {
+    // Held until the create is done, which for those carried out as tasks is when they complete.
+    let permit = state.create_limiter.try_acquire(&uri)?;
+    let node = match tree
+        .create_deferred(uri.as_str(), &payload, user.as_deref())
+        .await?
+    {
+        Outcome::Done(node) => node,
+        Outcome::Async(task) => {
+            if let Some(permit) = permit {
+                task.hold_until_complete(permit);
+            }
+            return Ok(get_task_started_response(&task));
+        }
+    };
+    let mut meta = node.get_meta();
+    let minimal = meta.expensive_body || prefers_minimal_response(&headers);
+    // Sessions are made from their bodies, so those are always needed.
+    let mut body = match minimal && !is_session_collection {
+        true => None,
+        false => Some(get_response_body(&state, node)?),
+    };
+    if body.is_some() {
+        add_computed_etag(&state, node, &mut meta)?;
+    }
+    let mut response_headers = ResponseHeaders::for_node(&meta);
+    if let (true, Some(body)) = (is_session_collection, &mut body) {
         let token = Uuid::new_v4().as_simple().to_string();
-        let username = node
-            .get_body()
-            .as_object()
-            .unwrap()
+        // Checked to be present by check_required_properties(), and a string by the login.
+        let username = payload
             .get("UserName")
-            .unwrap()
-            .as_str()
-            .unwrap()
+            .and_then(Value::as_str)
+            .ok_or(Error::InternalError)?
             .to_string();
+        let now = state.clock.system_time();
         let session = Session {
             token: token.clone(),
             username,
             uri: node.get_uri().to_string(),
+            context,
+            oem: None,
+            source,
+            created: now,
+            last_activity: now,
+        };
+        record_session_action(&state, AuditAction::SessionCreated, &session, source);
+        state.sessions.store().insert(session);
+        // It was not yet a session when the body was read.
+        if let Value::Object(body) = body {
+            state.sessions.add_session_properties(node.get_uri(), body);
+        }
+        response_headers = response_headers.with(HeaderName::from_static("x-auth-token"), &token);
+    }
+    if minimal && prefers_minimal_response(&headers) {
+        response_headers = response_headers.with(
+            HeaderName::from_static("preference-applied"),
+            "return=minimal",
+        );
+    }
+    let body = body.filter(|_| !minimal);
+    let created_uri = node.get_uri().to_string();
+    let mut response = get_node_created_response(response_headers, &created_uri, body);
+    if is_session_collection {
+        response.extensions_mut().insert(LoginResponse);
+    }
+    // For when the collection could not be read, such as when logging in.
+    record_node_type(&*tree, &meta);
+    if state.check_membership {
+        membership::check_created(&*tree, uri.as_str(), &created_uri, user.as_deref()).await?;
+    }
+    state.events.resource_created(&created_uri);
+    for uri in evicted {
+        let session = match state.sessions.store().remove(&uri) {
+            Some(session) => session,
+            None => continue,
         };
-        state.sessions.write().unwrap().push(session);
-        let header_val = HeaderValue::from_str(token.as_str()).unwrap();
-        additional_headers.insert("x-auth-token", header_val);
+        // The session is gone for the service even if the tree cannot delete its resource.
+        tree.delete(&uri, Some(&session.username)).await.ok();
+        record_session_action(&state, AuditAction::SessionTerminated, &session, source);
+        state.events.resource_removed(&uri);
+    }
+    Ok(response)
+}
+
+// The URI of the resource and the name of the action that the URI is the target of, if it is
+// one, such as /redfish/v1/Systems/1 and ComputerSystem.Reset for
+// /redfish/v1/Systems/1/Actions/ComputerSystem.Reset.
+fn split_action_uri(uri: &str) -> Option<(&str, &str)> {
+    let (resource_uri, action) = uri.rsplit_once("/Actions/")?;
+    match !resource_uri.is_empty() && !action.contains('/') && action.contains('.') {
+        true => Some((resource_uri, action)),
+        false => None,
+    }
+}
+
+async fn post_action(
+    state: &AppState,
+    resource_uri: &str,
+    action: &str,
+    payload: &Map<String, Value>,
+    user: Option<&str>,
+) -> Result<Response, Error> {
+    let privileges = get_user_privileges(state, user).await;
+    let mut tree = state.tree.write().await;
+    let ancestors = get_ancestor_entities(state, &*tree, resource_uri, user).await;
+    {
+        let node = tree.get(resource_uri, user).await?;
+        let scope = OperationScope::new(&ancestors);
+        authorize(
+            state,
+            &*node,
+            &Method::POST,
+            user,
+            privileges.as_deref(),
+            &scope,
+        )?;
+    }
+    if !tree.can_invoke_action(resource_uri, action, user) {
+        return Err(Error::Forbidden);
+    }
+    match tree.action(resource_uri, action, payload, user).await? {
+        // The target of an action only allows POST.
+        Outcome::Done(Some(body)) => {
+            Ok(get_non_node_json_response(StatusCode::OK, body, "POST").into_response())
+        }
+        Outcome::Done(None) => {
+            Ok((StatusCode::NO_CONTENT, COMMON_RESPONSE_HEADERS).into_response())
+        }
+        Outcome::Async(task) => Ok(get_task_started_response(&task)),
     }
-    Ok(get_node_created_response(node, additional_headers))
+}
+
+async fn upload(
+    state: &AppState,
+    uri: &str,
+    user: Option<&str>,
+    headers: &HeaderMap,
+    request: Request<Body>,
+) -> Result<Response, Error> {
+    let privileges = get_user_privileges(state, user).await;
+    let mut tree = state.tree.write().await;
+    // Push URIs, such as that of the UpdateService, are often not resources themselves. Those
+    // are authorized as POSTs to the resource they are under.
+    let mut target = uri;
+    while tree.get(target, user).await.is_err() {
+        match target.rsplit_once('/') {
+            Some((parent, _)) if parent != "/redfish" => target = parent,
+            _ => break,
+        }
+    }
+    let ancestors = get_ancestor_entities(state, &*tree, target, user).await;
+    if let Ok(node) = tree.get(target, user).await {
+        authorize(
+            state,
+            &*node,
+            &Method::POST,
+            user,
+            privileges.as_deref(),
+            &OperationScope::new(&ancestors),
+        )?;
+    }
+    let writer = tree.upload(uri, user).await?;
+    drop(tree);
+    let total = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.parse().ok());
+    stream_upload(request.into_body(), writer, total).await?;
+    Ok((StatusCode::NO_CONTENT, COMMON_RESPONSE_HEADERS).into_response())
 }
 
 #[debug_handler]
@@ -236,19 +2441,193 @@ async fn patcher(
     headers: HeaderMap,
     Path(path): Path<String>,
     State(state): State<AppState>,
-    Json(payload): Json<Map<String, Value>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    RawQuery(query): RawQuery,
+    JsonBody(payload): JsonBody,
 ) -> Result<impl IntoResponse, Error> {
-    validate_odata_version(&headers)?;
+    validate_odata_version(&headers, &state)?;
+    check_query_not_given(query.as_deref())?;
     let uri = "/redfish/".to_owned() + &path;
+    let source = get_client_ip(connect_info);
+    let user = get_request_username(&headers, &state, source).await?;
+    let privileges = get_user_privileges(&state, user.as_deref()).await;
     let mut tree = state.tree.write().await;
-    let user = get_request_username(&headers, &state)?;
+    check_preconditions(
+        &state,
+        &*tree,
+        &uri,
+        user.as_deref(),
+        privileges.as_deref(),
+        &headers,
+        &Method::PATCH,
+    )
+    .await?;
+
+    let ancestors = get_ancestor_entities(&state, &*tree, &uri, user.as_deref()).await;
+    let (payload, mut messages) = {
+        let node = tree.get(uri.as_str(), user.as_deref()).await?;
+        record_node_type(&*tree, &node.get_meta());
+        // Properties such as the Password of an account may be patched with privileges that do
+        // not allow patching the rest of it.
+        let scope =
+            OperationScope::new(&ancestors).with_properties(payload.keys().cloned().collect());
+        authorize(
+            &state,
+            &*node,
+            &Method::PATCH,
+            user.as_deref(),
+            privileges.as_deref(),
+            &scope,
+        )?;
+        match node.get_resource_type() {
+            Some(resource_type) => validate_properties(resource_type, payload),
+            None => (payload, Vec::new()),
+        }
+    };
+    let (mut payload, invalid) =
+        run_validators(&state.property_validators, &uri, user.as_deref(), payload).await;
+    messages.extend(invalid);
+    if !messages.is_empty() && state.partial_patch == PartialPatch::Refused {
+        return Err(Error::InvalidProperties(messages));
+    }
+    let providers = state.external_accounts.take_patches(&uri, &mut payload)?;
+    if payload.is_empty() && !providers.is_empty() {
+        // Only what the service models was patched, so the tree is not asked to.
+        state.external_accounts.apply(&providers);
+        state.events.resource_changed(&uri);
+        let response = {
+            let node = tree.get(uri.as_str(), user.as_deref()).await?;
+            get_patched_response(&state, &*node, messages)
+        };
+        configure_external_providers(&*state.authentication_provider, providers).await;
+        return response;
+    }
+    if payload.is_empty() && !messages.is_empty() {
+        return Err(Error::InvalidProperties(messages));
+    }
+    let node = match tree
+        .patch_deferred(uri.as_str(), &payload, user.as_deref())
+        .await?
+    {
+        Outcome::Done(node) => node,
+        Outcome::Async(task) => return Ok(get_task_started_response(&task)),
+    };
+    let body = node.try_get_body().ok();
+    let terminates_sessions = uri == SESSION_SERVICE_URI
+        && state.disabled_sessions == DisabledSessionPolicy::Terminate
+        && body.as_ref().and_then(|body| body.get("ServiceEnabled")) == Some(&Value::Bool(false));
+    // Disabled accounts cannot log in, so they keep no sessions either.
+    let disabled_account = get_account_username(node).filter(|_| {
+        body.as_ref().and_then(|body| body.get("Enabled")) == Some(&Value::Bool(false))
+    });
+    state.external_accounts.apply(&providers);
+    state.events.resource_changed(&uri);
+    let response = get_patched_response(&state, node, messages);
+    if terminates_sessions {
+        terminate_sessions(&state, &mut *tree, |_| true, source).await;
+    }
+    if let Some(username) = disabled_account {
+        terminate_sessions(
+            &state,
+            &mut *tree,
+            |session| session.username == username,
+            source,
+        )
+        .await;
+    }
+    configure_external_providers(&*state.authentication_provider, providers).await;
+    response
+}
+
+// 202 Accepted, with the task of the operation, and the URI to poll.
+fn get_task_started_response(task: &Task) -> Response {
+    (
+        StatusCode::ACCEPTED,
+        [
+            (header::LOCATION, task.get_poll_uri()),
+            (header::CONTENT_LOCATION, task.get_uri()),
+        ],
+        COMMON_RESPONSE_HEADERS,
+        Json(task.get_body()),
+    )
+        .into_response()
+}
+
+// The response to a PATCH, given messages about any properties that were not applied.
+fn get_patched_response(
+    state: &AppState,
+    node: &dyn Node,
+    messages: Vec<Message>,
+) -> Result<Response, Error> {
+    if messages.is_empty() {
+        return get_node_get_response(state, node);
+    }
+    // Some properties were applied, so annotate the resource with why the others were not.
+    let headers = ResponseHeaders::for_node(&get_node_meta(state, node)?).into_header_map();
+    let mut body = get_response_body(state, node)?;
+    if let Value::Object(body) = &mut body {
+        annotate_patched(body, messages, state.partial_patch);
+    }
+    Ok(JsonResponse::new(StatusCode::OK, headers, body)
+        .with_max_size(state.max_response_size)
+        .into_response())
+}
+
+// Split the payload into the properties whose values are valid for the resource type,
+// and messages for those that are not.
+fn validate_properties(
+    resource_type: &ResourceType,
+    payload: Map<String, Value>,
+) -> (Map<String, Value>, Vec<Message>) {
+    let mut valid = Map::new();
+    let mut messages = Vec::new();
+    for (name, value) in payload {
+        let key = match resource_type.properties.get(&name) {
+            None => None,
+            Some(property_type) => match property_type.validate(&value) {
+                Ok(()) => None,
+                Err(PropertyValueError::TypeError) => Some("PropertyValueTypeError"),
+                Err(PropertyValueError::NotInList) => Some("PropertyValueNotInList"),
+            },
+        };
+        match key {
+            None => {
+                valid.insert(name, value);
+            }
+            Some(key) => {
+                let value_string = match &value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                let message = Message::from_registry(
+                    base_registry(),
+                    key,
+                    ResourceSchemaVersion::new(1, 1, 2),
+                    vec![value_string, name.clone()],
+                    vec![format!("#/{}", name)],
+                )
+                .unwrap();
+                messages.push(message);
+            }
+        }
+    }
+    (valid, messages)
+}
 
-    let node = tree.patch(uri.as_str(), &payload, user.as_deref()).await?;
-    Ok(get_node_get_response(node))
+fn get_messages_json(messages: &[Message]) -> Value {
+    Value::Array(
+        messages
+            .iter()
+            .map(|message| Value::Object(message.to_json()))
+            .collect(),
+    )
 }
 
-async fn get_redfish(headers: HeaderMap) -> Result<impl IntoResponse, Error> {
-    validate_odata_version(&headers)?;
+async fn get_redfish(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    validate_odata_version(&headers, &state)?;
     Ok(get_non_node_json_response(
         StatusCode::OK,
         json!({ "v1": "/redfish/v1/" }),
@@ -259,101 +2638,339 @@ async fn get_redfish(headers: HeaderMap) -> Result<impl IntoResponse, Error> {
 async fn get_odata_metadata_doc(
     headers: HeaderMap,
     State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
 ) -> Result<impl IntoResponse, Error> {
-    validate_odata_version(&headers)?;
+    validate_odata_version(&headers, &state)?;
+    check_document_access(&headers, &state, get_client_ip(connect_info)).await?;
     let tree = state.tree.read().await;
-    let body = get_odata_metadata_document(tree.get_collection_types(), tree.get_resource_types());
-    Ok((
-        [(header::CONTENT_TYPE, "application/xml")],
-        [(header::ALLOW, "GET,HEAD")],
-        COMMON_RESPONSE_HEADERS,
-        body,
-    ))
+    // The service serves the EventService resource itself, so its type is declared too, as are
+    // those of the TaskService when it serves that.
+    let mut resource_types = tree.get_resource_types().to_vec();
+    let mut collection_types = tree.get_collection_types().to_vec();
+    let mut served_types = vec![get_event_service_type()];
+    if state.tasks.is_some() {
+        served_types.extend([get_task_service_type(), get_task_type()]);
+        let task_collection_type = get_task_collection_type();
+        if !collection_types
+            .iter()
+            .any(|t| t.name == task_collection_type.name)
+        {
+            collection_types.push(task_collection_type);
+        }
+    }
+    for served_type in served_types {
+        if !resource_types.iter().any(|t| t.name == served_type.name) {
+            resource_types.push(served_type);
+        }
+    }
+    let body = get_odata_metadata_document(&collection_types, &resource_types);
+    Ok(state
+        .documents
+        .get_response(&headers, "application/xml", body.into_bytes()))
 }
 
-async fn get_odata_service_doc(State(state): State<AppState>) -> impl IntoResponse {
+async fn get_odata_service_doc(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+) -> Result<impl IntoResponse, Error> {
+    validate_odata_version(&headers, &state)?;
+    let user = check_document_access(&headers, &state, get_client_ip(connect_info)).await?;
     let tree = state.tree.read().await;
-    let service_root = tree.get("/redfish/v1", None).await;
-    //TODO: Handle better than unwrap()
-    let doc = get_odata_service_document(service_root.unwrap().get_body().as_object().unwrap());
-    get_non_node_json_response(StatusCode::OK, Value::Object(doc), "GET,HEAD")
+    // The service root is read as the user, in case the tree shows them more of it.
+    let service_root = tree
+        .get("/redfish/v1", user.as_deref())
+        .await?
+        .try_get_body()?;
+    let doc = get_odata_service_document(service_root.as_object().ok_or(Error::InternalError)?);
+    let body = serde_json::to_vec(&doc).map_err(|_| Error::InternalError)?;
+    Ok(state
+        .documents
+        .get_response(&headers, mime::APPLICATION_JSON.as_ref(), body))
+}
+
+// The body of a node, as sent to clients.
+fn get_response_body(state: &AppState, node: &dyn Node) -> Result<Value, Error> {
+    let mut body = node.try_get_body()?;
+    if let Value::Object(body) = &mut body {
+        state.sessions.add_session_properties(node.get_uri(), body);
+        state.external_accounts.add_properties(node.get_uri(), body);
+        if let Some(registry) = state.privilege_registry {
+            add_registry_member(registry, node.get_uri(), body);
+        }
+        if let Some(backup) = &state.configuration_backup {
+            backup.add_actions(node.get_uri(), body);
+        }
+        if state.odata_context {
+            add_odata_context(body);
+        }
+        if state.computed_etags && !body.contains_key("@odata.etag") {
+            let etag = node.get_etag().unwrap_or_else(|| compute_etag(body));
+            body.insert(String::from("@odata.etag"), Value::String(etag.to_string()));
+        }
+    }
+    Ok(body)
 }
 
-fn node_to_allow(node: &dyn Node) -> String {
-    node.get_allowed_methods().to_string()
+// The body of the node at the URI as a GET of it by the user would return it, for reading nodes
+// on their behalf while answering another request, such as to expand references to them. Nodes
+// the user could not GET are refused as they would be then.
+async fn read_node_body(
+    state: &AppState,
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    user: Option<&str>,
+    privileges: Option<&[String]>,
+) -> Result<Value, Error> {
+    let ancestors = get_ancestor_entities(state, tree, uri, user).await;
+    let scope = OperationScope::new(&ancestors);
+    let node = tree.get(uri, user).await?;
+    authorize(state, &*node, &Method::GET, user, privileges, &scope)?;
+    let may_post = authorize(state, &*node, &Method::POST, user, privileges, &scope).is_ok();
+    let mut body = get_response_body(state, &*node)?;
+    restrict_actions(
+        tree,
+        uri,
+        user,
+        may_post,
+        state.action_visibility,
+        &mut body,
+    );
+    Ok(body)
 }
 
-fn get_described_by_header_value(node: &dyn Node) -> Option<HeaderValue> {
-    if let Some(described_by) = node.described_by() {
-        let val = format!("<{}>; rel=describedby", described_by);
-        if let Ok(val) = HeaderValue::from_str(val.as_str()) {
-            return Some(val);
+// The ETag of a body, hashed from its JSON with the properties of each object in order, so
+// that it does not change with the order the node gives them in. Nodes may give it as their
+// own ETag, so that they have one whether or not the service computes them.
+pub fn compute_etag(body: &Map<String, Value>) -> EntityTag {
+    fn canonicalize(value: &Value) -> Value {
+        match value {
+            Value::Object(object) => {
+                let mut keys: Vec<&String> = object.keys().collect();
+                keys.sort();
+                let object = keys
+                    .into_iter()
+                    .map(|key| (key.clone(), canonicalize(&object[key])))
+                    .collect();
+                Value::Object(object)
+            }
+            Value::Array(values) => Value::Array(values.iter().map(canonicalize).collect()),
+            value => value.clone(),
         }
     }
-    None
+    let body = canonicalize(&Value::Object(body.clone()));
+    EntityTag::from_data(body.to_string().as_bytes())
+}
+
+// Give the meta of a node without an ETag the one computed for its body, if the service
+// computes them. Raw bodies are hashed as they are.
+fn add_computed_etag(state: &AppState, node: &dyn Node, meta: &mut NodeMeta) -> Result<(), Error> {
+    if !state.computed_etags || meta.etag.is_some() {
+        return Ok(());
+    }
+    meta.etag = match node.get_raw_body() {
+        Some(raw) => Some(EntityTag::from_data(&raw.data)),
+        None => get_response_body(state, node)?
+            .get("@odata.etag")
+            .and_then(Value::as_str)
+            .and_then(|etag| EntityTag::from_str(etag).ok()),
+    };
+    Ok(())
+}
+
+// The meta of a node as sent to clients, with any computed ETag.
+fn get_node_meta(state: &AppState, node: &dyn Node) -> Result<NodeMeta, Error> {
+    let mut meta = node.get_meta();
+    add_computed_etag(state, node, &mut meta)?;
+    Ok(meta)
+}
+
+// Refuse the body of a request to create a resource if it is missing any of the required
+// properties, with a PropertyMissing message for each.
+fn check_required_properties(body: &Map<String, Value>, required: &[String]) -> Result<(), Error> {
+    let messages: Vec<Message> = required
+        .iter()
+        .filter(|name| !body.contains_key(name.as_str()))
+        .map(|name| {
+            Message::from_registry(
+                base_registry(),
+                "PropertyMissing",
+                ResourceSchemaVersion::new(1, 1, 2),
+                vec![name.clone()],
+                vec![format!("#/{}", name)],
+            )
+            .unwrap()
+        })
+        .collect();
+    match messages.is_empty() {
+        true => Ok(()),
+        false => Err(Error::InvalidProperties(messages)),
+    }
+}
+
+// A message from the Base registry about the value of a property in the request body, such as
+// PropertyValueTypeError.
+fn get_property_value_message(key: &str, value: &Value, name: &str) -> Message {
+    let value_string = match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    Message::from_registry(
+        base_registry(),
+        key,
+        ResourceSchemaVersion::new(1, 1, 2),
+        vec![value_string, String::from(name)],
+        vec![format!("#/{}", name)],
+    )
+    .unwrap()
 }
 
-fn get_node_etag_header_value(node: &dyn Node) -> Option<HeaderValue> {
-    let etag = node.get_etag()?;
-    if let Ok(val) = HeaderValue::from_str(&etag.to_string()) {
-        return Some(val);
+// The Context of a new session, which has to be a string.
+fn get_session_context(context: &Value) -> Result<String, Error> {
+    match context {
+        Value::String(context) => Ok(context.clone()),
+        context => {
+            let message = get_property_value_message("PropertyValueTypeError", context, "Context");
+            Err(Error::InvalidProperties(vec![message]))
+        }
     }
-    None
 }
 
-fn add_node_headers(headers: &mut HeaderMap, node: &dyn Node) -> () {
-    if let Some(described_by) = get_described_by_header_value(node) {
-        headers.insert(header::LINK, described_by);
+// Check the Id requested for a new member of the collection. It has to be a string that can be
+// the last segment of its URI, which no other member has yet.
+fn check_requested_id(collection: &dyn Node, id: &Value) -> Result<(), Error> {
+    let id_string = match id {
+        Value::String(id) => id,
+        id => {
+            let message = get_property_value_message("PropertyValueTypeError", id, "Id");
+            return Err(Error::InvalidProperties(vec![message]));
+        }
+    };
+    if id_string.is_empty() || id_string.contains(['/', '?', '#', '%']) {
+        let message = get_property_value_message("PropertyValueFormatError", id, "Id");
+        return Err(Error::InvalidProperties(vec![message]));
     }
-    if let Some(etag) = get_node_etag_header_value(node) {
-        headers.insert(header::ETAG, etag);
+    let body = collection.try_get_body()?;
+    let member_uri = format!("{}/{}", collection.get_uri(), id_string);
+    let exists = body
+        .get("Members")
+        .and_then(Value::as_array)
+        .is_some_and(|members| {
+            members
+                .iter()
+                .any(|member| member.get("@odata.id").and_then(Value::as_str) == Some(&member_uri))
+        });
+    if !exists {
+        return Ok(());
     }
-    if let Some(described_by) = node.described_by() {
-        let val = format!("<{}>; rel=describedby", described_by);
-        let val = HeaderValue::from_str(val.as_str()).unwrap();
-        headers.insert(header::LINK, val);
+    // Members of a ManagerAccountCollection are ManagerAccounts.
+    let resource_type = body
+        .get("@odata.type")
+        .and_then(Value::as_str)
+        .and_then(|odata_type| odata_type.trim_start_matches('#').split('.').next())
+        .map(|name| name.strip_suffix("Collection").unwrap_or(name))
+        .unwrap_or("Resource");
+    Err(Error::ResourceAlreadyExists(
+        String::from(resource_type),
+        String::from("Id"),
+        id_string.clone(),
+    ))
+}
+
+fn get_node_get_response(state: &AppState, node: &dyn Node) -> Result<Response, Error> {
+    let headers = ResponseHeaders::for_node(&get_node_meta(state, node)?);
+    match node.get_raw_body() {
+        Some(raw) => {
+            let headers = headers.with(header::CONTENT_TYPE, &raw.content_type);
+            Ok((StatusCode::OK, headers.into_header_map(), raw.data).into_response())
+        }
+        None => {
+            let body = get_response_body(state, node)?;
+            Ok(
+                JsonResponse::new(StatusCode::OK, headers.into_header_map(), body)
+                    .with_max_size(state.max_response_size)
+                    .into_response(),
+            )
+        }
     }
 }
 
-fn get_node_get_response(node: &dyn Node) -> impl IntoResponse {
-    let mut headers = get_standard_headers(node_to_allow(node).as_str());
-    add_node_headers(&mut headers, node);
-    JsonResponse::new(StatusCode::OK, headers, node.get_body())
+fn get_node_head_response(meta: &NodeMeta) -> Response {
+    let mut headers =
+        ResponseHeaders::for_node(meta).with(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+    if let Some(content_length) = meta.content_length {
+        headers = headers.with(header::CONTENT_LENGTH, &content_length.to_string());
+    }
+    (StatusCode::OK, headers.into_header_map()).into_response()
 }
 
-fn get_node_created_response(node: &dyn Node, additional_headers: HeaderMap) -> impl IntoResponse {
-    let mut headers = get_standard_headers(node_to_allow(node).as_str());
-    headers.extend(additional_headers);
-    add_node_headers(&mut headers, node);
-    headers.insert(
-        header::LOCATION,
-        HeaderValue::from_str(node.get_uri()).unwrap(),
-    );
-    JsonResponse::new(StatusCode::CREATED, headers, node.get_body())
+// Without a body, the client can follow the Location to get the new resource.
+fn get_node_created_response(headers: ResponseHeaders, uri: &str, body: Option<Value>) -> Response {
+    let headers = headers.with(header::LOCATION, uri).into_header_map();
+    match body {
+        Some(body) => JsonResponse::new(StatusCode::CREATED, headers, body).into_response(),
+        None => (StatusCode::CREATED, headers).into_response(),
+    }
 }
 
-fn get_non_node_json_response(status: StatusCode, data: Value, allow: &str) -> impl IntoResponse {
-    JsonResponse::new(status, get_standard_headers(allow), data)
+// Whether the client asked for responses without the resource, with Prefer: return=minimal.
+fn prefers_minimal_response(headers: &HeaderMap) -> bool {
+    has_preference(headers, "return=minimal")
 }
 
-fn get_standard_headers(allow: &str) -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert(header::ALLOW, HeaderValue::from_str(allow).unwrap());
-    // TODO: Use COMMON_RESPONSE_HEADERS
-    headers.insert(
-        HeaderName::from_static("odata-version"),
-        HeaderValue::from_static("4.0"),
-    );
-    headers.insert(
-        HeaderName::from_static("cache-control"),
-        HeaderValue::from_static("no-cache"),
-    );
+// Whether the Prefer headers of the request include the preference.
+fn has_preference(headers: &HeaderMap, preference: &str) -> bool {
     headers
+        .get_all("prefer")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|given| given.trim().eq_ignore_ascii_case(preference))
 }
 
-const COMMON_RESPONSE_HEADERS: ([(&str, &str); 1], [(&str, &str); 1]) =
+fn get_non_node_json_response(status: StatusCode, data: Value, allow: &str) -> impl IntoResponse {
+    JsonResponse::new(status, ResponseHeaders::new(allow).into_header_map(), data)
+}
+
+type CommonResponseHeaders = (
+    [(&'static str, &'static str); 1],
+    [(&'static str, &'static str); 1],
+);
+
+const SESSION_SERVICE_URI: &str = "/redfish/v1/SessionService";
+const SESSIONS_URI: &str = "/redfish/v1/SessionService/Sessions";
+
+// Marks the response to a login, which carries the token of the session it created.
+#[derive(Clone, Copy)]
+pub(crate) struct LoginResponse;
+
+const COMMON_RESPONSE_HEADERS: CommonResponseHeaders =
     ([("OData-Version", "4.0")], [("Cache-Control", "no-cache")]);
 
+// Respond with the status, and an error whose only message is the one with the key in the Base
+// registry. The resolution, if given, replaces the one of the registry.
+fn get_base_error_response(
+    status: StatusCode,
+    key: &str,
+    args: Vec<String>,
+    related_properties: Vec<String>,
+    resolution: Option<&str>,
+) -> Response {
+    let mut message = Message::from_registry(
+        base_registry(),
+        key,
+        ResourceSchemaVersion::new(1, 1, 2),
+        args.clone(),
+        related_properties,
+    )
+    .unwrap();
+    if let Some(resolution) = resolution {
+        message = message.with_resolution(String::from(resolution));
+    }
+    let body = ErrorResponse::from_registry(base_registry(), key, &args, vec![message]);
+    (status, COMMON_RESPONSE_HEADERS, Json(get_error_body(&body))).into_response()
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
@@ -364,46 +2981,155 @@ impl IntoResponse for Error {
                 [("www-authenticate", "Basic realm=\"simple\"")],
             )
                 .into_response(),
+            Error::AccountLocked => {
+                let mut response = get_base_error_response(
+                    StatusCode::UNAUTHORIZED,
+                    "NoValidSession",
+                    Vec::new(),
+                    Vec::new(),
+                    Some(
+                        "The account is locked after failing to authenticate too many times. \
+                         Retry once the lockout has expired, or have an administrator unlock \
+                         the account.",
+                    ),
+                );
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"simple\""),
+                );
+                response
+            }
             Error::MethodNotAllowed(allowed) => (
                 StatusCode::METHOD_NOT_ALLOWED,
                 [(header::ALLOW, allowed.to_string())],
                 COMMON_RESPONSE_HEADERS,
             )
                 .into_response(),
-            Error::BadODataVersion => {
-                (StatusCode::PRECONDITION_FAILED, COMMON_RESPONSE_HEADERS).into_response()
+            Error::BadODataVersion(requested, supported) => get_base_error_response(
+                StatusCode::PRECONDITION_FAILED,
+                "HeaderInvalid",
+                vec![format!("OData-Version: {}", requested)],
+                Vec::new(),
+                Some(&format!(
+                    "Resubmit the request with an OData-Version of {}, or without one.",
+                    supported.join(" or ")
+                )),
+            ),
+            Error::BadRequest => (StatusCode::BAD_REQUEST, COMMON_RESPONSE_HEADERS).into_response(),
+            Error::UnsupportedMediaType => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, COMMON_RESPONSE_HEADERS).into_response()
+            }
+            Error::NotUtf8 => get_base_error_response(
+                StatusCode::BAD_REQUEST,
+                "MalformedJSON",
+                Vec::new(),
+                Vec::new(),
+                Some("Encode the request body in UTF-8 and resubmit the request."),
+            ),
+            Error::InvalidProperties(messages) => {
+                let body =
+                    ErrorResponse::from_registry(base_registry(), "GeneralError", &[], messages);
+                (
+                    StatusCode::BAD_REQUEST,
+                    COMMON_RESPONSE_HEADERS,
+                    Json(get_error_body(&body)),
+                )
+                    .into_response()
+            }
+            Error::SessionLimitExceeded => get_base_error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "SessionLimitExceeded",
+                Vec::new(),
+                Vec::new(),
+                None,
+            ),
+            Error::ResourceAlreadyExists(resource_type, property, value) => {
+                let related_properties = vec![format!("#/{}", property)];
+                get_base_error_response(
+                    StatusCode::CONFLICT,
+                    "ResourceAlreadyExists",
+                    vec![resource_type, property, value],
+                    related_properties,
+                    None,
+                )
+            }
+            Error::InvalidQuery(key, args) => {
+                get_base_error_response(StatusCode::BAD_REQUEST, &key, args, Vec::new(), None)
+            }
+            Error::UnsupportedQueryParameter(name) => get_base_error_response(
+                StatusCode::NOT_IMPLEMENTED,
+                "QueryParameterUnsupported",
+                vec![name],
+                Vec::new(),
+                None,
+            ),
+            Error::ResponseTooLarge => get_base_error_response(
+                StatusCode::INSUFFICIENT_STORAGE,
+                "InsufficientStorage",
+                Vec::new(),
+                Vec::new(),
+                Some(
+                    "Request the resource in pages with $top and $skip, or only the properties \
+                     needed with $select, and resubmit the request.",
+                ),
+            ),
+            Error::InternalError => get_base_error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "InternalError",
+                Vec::new(),
+                Vec::new(),
+                None,
+            ),
+            Error::ActionNotSupported(action) => get_base_error_response(
+                StatusCode::BAD_REQUEST,
+                "ActionNotSupported",
+                vec![action],
+                Vec::new(),
+                None,
+            ),
+            Error::PreconditionFailed => get_base_error_response(
+                StatusCode::PRECONDITION_FAILED,
+                "PreconditionFailed",
+                Vec::new(),
+                Vec::new(),
+                None,
+            ),
+            Error::ResourceInUse => get_base_error_response(
+                StatusCode::CONFLICT,
+                "ResourceInUse",
+                Vec::new(),
+                Vec::new(),
+                None,
+            ),
+            Error::Forbidden => get_base_error_response(
+                StatusCode::FORBIDDEN,
+                "InsufficientPrivilege",
+                Vec::new(),
+                Vec::new(),
+                None,
+            ),
+            Error::ServiceDisabled(service) => get_base_error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "ServiceDisabled",
+                vec![service],
+                Vec::new(),
+                None,
+            ),
+            Error::ServiceUnavailable(retry_after) => {
+                // Round up, so that clients do not retry before the service expects them to.
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                let mut response = get_base_error_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "ServiceTemporarilyUnavailable",
+                    vec![seconds.to_string()],
+                    Vec::new(),
+                    None,
+                );
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+                response
             }
         }
     }
 }
-
-fn get_token_user(token: String, state: &AppState) -> Option<String> {
-    for session in state.sessions.read().unwrap().iter() {
-        if session.token == token {
-            return Some(session.username.clone());
-        }
-    }
-    None
-}
-
-// Parse credentials from request. If bad credentials, return Erroror.
-// If no credentials, return Ok(None).
-// If credentials check out, return Ok(Some(username)).
-fn get_request_username(headers: &HeaderMap, state: &AppState) -> Result<Option<String>, Error> {
-    match headers.get("x-auth-token") {
-        Some(token) => match get_token_user(token.to_str().unwrap().to_string(), &state) {
-            None => Err(Error::Unauthorized),
-            Some(user) => Ok(Some(user)),
-        },
-        None => match headers.get("authorization") {
-            None => Ok(None),
-            Some(header_val) => match http_auth_basic::Credentials::from_header(
-                header_val.to_str().unwrap().to_string(),
-            ) {
-                Err(_) => Err(Error::Unauthorized),
-                // TODO: Actually validate credentials!
-                Ok(credentials) => Ok(Some(credentials.user_id)),
-            },
-        },
-    }
-}
@@ -0,0 +1,112 @@
+use crate::{Error, Tree};
+use axum::{
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{watch, RwLock};
+
+// How long clients are told to wait before retrying while the tree is starting.
+const STARTING_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+type SharedTree = Arc<RwLock<dyn Tree + Send + Sync>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifecyclePhase {
+    // The tree's on_start() has not returned yet.
+    Starting,
+    Running,
+    // The tree's on_start() failed, so the service cannot serve it.
+    Failed,
+    // The service has been shut down.
+    Stopped,
+}
+
+// Handle to the lifecycle of a service, for starting and shutting down its tree.
+// Cloning gives another handle to the same service.
+#[derive(Clone)]
+pub struct Lifecycle {
+    phase: Arc<watch::Sender<LifecyclePhase>>,
+    tree: Arc<OnceLock<SharedTree>>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            phase: Arc::new(watch::channel(LifecyclePhase::Starting).0),
+            tree: Arc::new(OnceLock::new()),
+        }
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_phase(&self) -> LifecyclePhase {
+        *self.phase.borrow()
+    }
+
+    // Wait for the tree to finish starting, and return whether it is running.
+    pub async fn wait_started(&self) -> bool {
+        let mut phase = self.phase.subscribe();
+        while *phase.borrow_and_update() == LifecyclePhase::Starting {
+            if phase.changed().await.is_err() {
+                break;
+            }
+        }
+        self.get_phase() == LifecyclePhase::Running
+    }
+
+    // Stop handling requests, and let the tree clean up once those being handled are done.
+    pub async fn shutdown(&self) {
+        self.phase.send_replace(LifecyclePhase::Stopped);
+        if let Some(tree) = self.tree.get() {
+            tree.write().await.on_shutdown().await;
+        }
+    }
+
+    // Start the tree of a service being built. This has to be called within a Tokio runtime.
+    pub(crate) fn start(&self, tree: SharedTree) {
+        if self.tree.set(tree.clone()).is_err() {
+            panic!("A Lifecycle can only be used for one service");
+        }
+        let phase = self.phase.clone();
+        tokio::spawn(async move {
+            let started = tree.write().await.on_start().await;
+            // It stays Stopped if the service was shut down while starting.
+            phase.send_if_modified(|phase| match *phase {
+                LifecyclePhase::Starting => {
+                    *phase = match started {
+                        Ok(()) => LifecyclePhase::Running,
+                        Err(_) => LifecyclePhase::Failed,
+                    };
+                    true
+                }
+                _ => false,
+            });
+        });
+    }
+}
+
+// Refuse requests while the tree is not running, except for /redfish, which does not need it.
+pub(crate) async fn check_lifecycle<B>(
+    State(lifecycle): State<Lifecycle>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if request.uri().path() == "/redfish" {
+        return next.run(request).await;
+    }
+    match lifecycle.get_phase() {
+        LifecyclePhase::Running => next.run(request).await,
+        LifecyclePhase::Starting | LifecyclePhase::Stopped => {
+            Error::ServiceUnavailable(STARTING_RETRY_AFTER).into_response()
+        }
+        LifecyclePhase::Failed => Error::InternalError.into_response(),
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Error;
+use axum::{
+    extract::State,
+    http::{Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Limits how many requests the service handles at once. Requests beyond the limits are not
+// queued, but refused straight away with 503 Service Unavailable, and told when to retry.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimitConfig {
+    pub max_requests: usize,
+    // Lower limits for some methods, such as for PATCH and POST to backends that are slow to write.
+    pub max_requests_per_method: HashMap<Method, usize>,
+    // Sent to refused clients in the Retry-After header, rounded up to whole seconds.
+    pub retry_after: Duration,
+}
+
+impl Default for ConcurrencyLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_requests: 64,
+            max_requests_per_method: HashMap::new(),
+            retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+pub(crate) struct ConcurrencyLimiter {
+    retry_after: Duration,
+    requests: Arc<Semaphore>,
+    requests_per_method: HashMap<Method, Arc<Semaphore>>,
+}
+
+// Held for as long as a request is being handled.
+struct Permits {
+    _request: OwnedSemaphorePermit,
+    _method: Option<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(config: ConcurrencyLimitConfig) -> Self {
+        Self {
+            retry_after: config.retry_after,
+            requests: Arc::new(Semaphore::new(config.max_requests)),
+            requests_per_method: config
+                .max_requests_per_method
+                .into_iter()
+                .map(|(method, max)| (method, Arc::new(Semaphore::new(max))))
+                .collect(),
+        }
+    }
+
+    fn try_acquire(&self, method: &Method) -> Result<Permits, Error> {
+        let saturated = |_| Error::ServiceUnavailable(self.retry_after);
+        let method = match self.requests_per_method.get(method) {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().map_err(saturated)?),
+            None => None,
+        };
+        let request = self
+            .requests
+            .clone()
+            .try_acquire_owned()
+            .map_err(saturated)?;
+        Ok(Permits {
+            _request: request,
+            _method: method,
+        })
+    }
+}
+
+pub(crate) async fn limit_concurrency<B>(
+    State(limiter): State<Arc<ConcurrencyLimiter>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let _permits = match limiter.try_acquire(request.method()) {
+        Ok(permits) => permits,
+        Err(error) => return error.into_response(),
+    };
+    next.run(request).await
+}
+
+// What clients are told when they ask to create a member of a collection that already has as
+// many creates pending as it allows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CreateLimitResponse {
+    // 503 Service Unavailable, to retry after the duration, for limits that soon clear up on
+    // their own, such as those on how many slow creates a backend can take at once.
+    ServiceUnavailable(Duration),
+    // 409 Conflict with a ResourceInUse message, for operations that must not overlap, such as
+    // one firmware update at a time, which the client should not just retry.
+    Conflict,
+}
+
+// Limits how many creates a collection has pending at once. Those carried out as tasks are
+// pending until the task completes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CreateLimit {
+    pub max_pending: usize,
+    pub response: CreateLimitResponse,
+}
+
+pub(crate) struct CreateLimiter {
+    collections: HashMap<String, (Arc<Semaphore>, CreateLimitResponse)>,
+}
+
+impl CreateLimiter {
+    pub(crate) fn new(limits: HashMap<String, CreateLimit>) -> Self {
+        let collections = limits
+            .into_iter()
+            .map(|(uri, limit)| {
+                let semaphore = Arc::new(Semaphore::new(limit.max_pending));
+                (uri, (semaphore, limit.response))
+            })
+            .collect();
+        Self { collections }
+    }
+
+    // A permit to create a member of the collection at the URI, to hold until the create is no
+    // longer pending, or None if its creates are not limited.
+    pub(crate) fn try_acquire(&self, uri: &str) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        let (semaphore, response) = match self.collections.get(uri) {
+            Some(collection) => collection,
+            None => return Ok(None),
+        };
+        match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Ok(Some(permit)),
+            Err(_) => Err(match response {
+                CreateLimitResponse::ServiceUnavailable(retry_after) => {
+                    Error::ServiceUnavailable(*retry_after)
+                }
+                CreateLimitResponse::Conflict => Error::ResourceInUse,
+            }),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, RwLock};
+
+// A link from a property of one resource to another, such as from the Links/Role of a
+// ManagerAccount to its Role.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub from: String,
+    // The path of the property within the body of the resource linking, such as Links/Role.
+    pub property: String,
+    pub to: String,
+}
+
+// Handle to the links between the resources of a tree, kept so that the resources depending on
+// one can be found without reading every body, such as to update them when it is deleted or
+// moved. The links are weak: they do not stop the resources they point to from being deleted.
+// Cloning gives another handle to the same links.
+#[derive(Clone, Default)]
+pub struct LinkRegistry {
+    references: Arc<RwLock<Vec<Reference>>>,
+}
+
+// Whether the URI is that of the resource, or of one under it.
+fn is_at_or_under(uri: &str, resource_uri: &str) -> bool {
+    uri.strip_prefix(resource_uri)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+impl LinkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record that the property of the resource links to the other. A property can only link to
+    // one resource, so this replaces any link it had.
+    pub fn add(&self, from: &str, property: &str, to: &str) {
+        let mut references = self.references.write().unwrap();
+        references.retain(|reference| reference.from != from || reference.property != property);
+        references.push(Reference {
+            from: String::from(from),
+            property: String::from(property),
+            to: String::from(to),
+        });
+    }
+
+    // Forget the link of the property, such as when a PATCH clears it.
+    pub fn remove(&self, from: &str, property: &str) {
+        let mut references = self.references.write().unwrap();
+        references.retain(|reference| reference.from != from || reference.property != property);
+    }
+
+    // The links from the resource.
+    pub fn get_references(&self, from: &str) -> Vec<Reference> {
+        let references = self.references.read().unwrap();
+        references
+            .iter()
+            .filter(|reference| reference.from == from)
+            .cloned()
+            .collect()
+    }
+
+    // The links to the resource, from the resources that depend on it.
+    pub fn get_dependents(&self, to: &str) -> Vec<Reference> {
+        let references = self.references.read().unwrap();
+        references
+            .iter()
+            .filter(|reference| reference.to == to)
+            .cloned()
+            .collect()
+    }
+
+    // Forget the links from the resource and those under it, which are gone with it, and the
+    // links to them. Returns the links to them from other resources, which are now dangling.
+    pub fn remove_resource(&self, uri: &str) -> Vec<Reference> {
+        let mut references = self.references.write().unwrap();
+        references.retain(|reference| !is_at_or_under(&reference.from, uri));
+        let (dangling, kept) = references
+            .drain(..)
+            .partition(|reference| is_at_or_under(&reference.to, uri));
+        *references = kept;
+        dangling
+    }
+
+    // Move the links from and to the resource and those under it to its new URI. Returns the
+    // links to them, with their new targets, for repairing the bodies of the resources linking.
+    pub fn rename(&self, old_uri: &str, new_uri: &str) -> Vec<Reference> {
+        let rename = |uri: &mut String| {
+            if is_at_or_under(uri, old_uri) {
+                *uri = format!("{}{}", new_uri, &uri[old_uri.len()..]);
+            }
+        };
+        let mut references = self.references.write().unwrap();
+        let mut repaired = Vec::new();
+        for reference in references.iter_mut() {
+            rename(&mut reference.from);
+            if is_at_or_under(&reference.to, old_uri) {
+                rename(&mut reference.to);
+                repaired.push(reference.clone());
+            }
+        }
+        repaired
+    }
+}
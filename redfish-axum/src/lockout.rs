@@ -0,0 +1,176 @@
+use crate::accounts::ACCOUNT_SERVICE_URI;
+use crate::clock::SystemClock;
+use crate::{AppState, Clock};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// How the AccountService says to lock accounts that fail to authenticate too often.
+pub(crate) struct LockoutPolicy {
+    threshold: u32,
+    // None locks accounts until they are unlocked, as when AccountLockoutCounterResetEnabled is
+    // false.
+    duration: Option<Duration>,
+    // Failures are forgotten once an account has gone this long without failing. None only
+    // forgets them when the account authenticates, or is unlocked.
+    counter_reset_after: Option<Duration>,
+}
+
+// The policy of the AccountService, as the user reads it, if it locks accounts at all. A
+// threshold of 0, or a duration of 0 while the counter resets, never locks them.
+pub(crate) async fn get_lockout_policy(state: &AppState, username: &str) -> Option<LockoutPolicy> {
+    let tree = state.tree.read().await;
+    let node = tree.get(ACCOUNT_SERVICE_URI, Some(username)).await.ok()?;
+    let body = node.try_get_body().ok()?;
+    let get_seconds = |name: &str| Some(Duration::from_secs(body.get(name)?.as_u64()?));
+    let threshold = body.get("AccountLockoutThreshold")?.as_u64()?;
+    let threshold = u32::try_from(threshold).unwrap_or(u32::MAX);
+    if threshold == 0 {
+        return None;
+    }
+    let reset_enabled = body
+        .get("AccountLockoutCounterResetEnabled")
+        .and_then(|enabled| enabled.as_bool())
+        .unwrap_or(true);
+    if !reset_enabled {
+        return Some(LockoutPolicy {
+            threshold,
+            duration: None,
+            counter_reset_after: None,
+        });
+    }
+    let duration = get_seconds("AccountLockoutDuration").filter(|duration| !duration.is_zero())?;
+    Some(LockoutPolicy {
+        threshold,
+        duration: Some(duration),
+        counter_reset_after: get_seconds("AccountLockoutCounterResetAfter"),
+    })
+}
+
+struct LockoutRecord {
+    failed_attempts: u32,
+    last_failure: Instant,
+    locked: bool,
+    // None while locked means until unlocked.
+    locked_until: Option<Instant>,
+    counter_reset_after: Option<Duration>,
+}
+
+impl LockoutRecord {
+    fn is_locked(&self, now: Instant) -> bool {
+        self.locked && self.locked_until.is_none_or(|until| now < until)
+    }
+
+    // Whether the failures have been forgotten, or the lock has expired.
+    fn is_stale(&self, now: Instant) -> bool {
+        match self.locked {
+            true => !self.is_locked(now),
+            false => self
+                .counter_reset_after
+                .is_some_and(|after| now.duration_since(self.last_failure) >= after),
+        }
+    }
+}
+
+struct Lockouts {
+    accounts: HashMap<String, LockoutRecord>,
+    // The service's, once the handle is given to one.
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Lockouts {
+    fn default() -> Self {
+        Self {
+            accounts: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Lockouts {
+    fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    fn get(&self, username: &str) -> Option<&LockoutRecord> {
+        let now = self.now();
+        self.accounts
+            .get(username)
+            .filter(|record| !record.is_stale(now))
+    }
+}
+
+// Handle to the counts of failed Basic auth attempts of each account, which the service keeps
+// to lock accounts as the AccountLockoutThreshold and AccountLockoutDuration of the
+// AccountService say. The AccountService itself, and the Locked property of its accounts, are
+// left to the tree, which can share the handle to serve them and to unlock accounts.
+// Cloning gives another handle to the same counts.
+#[derive(Clone, Default)]
+pub struct AccountLockouts {
+    lockouts: Arc<Mutex<Lockouts>>,
+}
+
+impl AccountLockouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Accounts are locked by the service's clock.
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.lockouts.lock().unwrap().clock = clock;
+    }
+
+    // The failed attempts since the account last authenticated, was unlocked, or went long
+    // enough without failing for them to be forgotten.
+    pub fn get_failed_attempts(&self, username: &str) -> u32 {
+        let lockouts = self.lockouts.lock().unwrap();
+        lockouts
+            .get(username)
+            .map_or(0, |record| record.failed_attempts)
+    }
+
+    pub fn is_locked(&self, username: &str) -> bool {
+        let lockouts = self.lockouts.lock().unwrap();
+        let now = lockouts.now();
+        lockouts
+            .get(username)
+            .is_some_and(|record| record.is_locked(now))
+    }
+
+    // Unlock the account and forget its failed attempts, such as when a client sets its Locked
+    // property to false.
+    pub fn unlock(&self, username: &str) {
+        self.lockouts.lock().unwrap().accounts.remove(username);
+    }
+
+    // Record a failed attempt to authenticate as the account, and return whether it locked it.
+    pub(crate) fn record_failure(&self, username: &str, policy: &LockoutPolicy) -> bool {
+        let mut lockouts = self.lockouts.lock().unwrap();
+        let now = lockouts.now();
+        // Forget about accounts that stopped failing, so that this cannot grow without bound.
+        lockouts.accounts.retain(|_, record| !record.is_stale(now));
+        let record = lockouts
+            .accounts
+            .entry(String::from(username))
+            .or_insert(LockoutRecord {
+                failed_attempts: 0,
+                last_failure: now,
+                locked: false,
+                locked_until: None,
+                counter_reset_after: policy.counter_reset_after,
+            });
+        record.failed_attempts += 1;
+        record.last_failure = now;
+        record.counter_reset_after = policy.counter_reset_after;
+        if record.locked || record.failed_attempts < policy.threshold {
+            return false;
+        }
+        record.locked = true;
+        record.locked_until = policy.duration.map(|duration| now + duration);
+        true
+    }
+
+    pub(crate) fn record_success(&self, username: &str) {
+        self.unlock(username);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::registries::base_registry;
+use crate::{Error, StaticResources, TreeBuilder};
+use redfish_data::{AllowedMethods, Message, ResourceSchemaVersion, ResourceType};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use strum::{Display, EnumString};
+
+// A protocol a manager serves or responds to, by the name of its property in the
+// ManagerNetworkProtocol.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum NetworkProtocol {
+    HTTP,
+    HTTPS,
+    SSDP,
+    SSH,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolSettings {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl ProtocolSettings {
+    fn to_json(&self) -> Value {
+        json!({"ProtocolEnabled": self.enabled, "Port": self.port})
+    }
+
+    // The property of the protocol with these settings, keeping its other properties, such as
+    // the NotifyTTL of SSDP.
+    fn apply(&self, property: Option<Value>) -> Value {
+        let mut property = match property {
+            Some(Value::Object(property)) => property,
+            _ => Map::new(),
+        };
+        property.insert(String::from("ProtocolEnabled"), json!(self.enabled));
+        property.insert(String::from("Port"), json!(self.port));
+        Value::Object(property)
+    }
+}
+
+// The server runtime of the service, which starts and stops its listeners and discovery
+// responders as clients change the network protocols of the manager.
+pub trait NetworkRuntime: Send + Sync {
+    // Serve the protocol as the settings now say, such as by stopping the SSH listener or
+    // moving HTTPS to another port. Return an error to refuse them, which leaves the protocol
+    // as it was in the ManagerNetworkProtocol, and the rest of the patch unapplied.
+    fn configure(
+        &self,
+        protocol: NetworkProtocol,
+        settings: &ProtocolSettings,
+    ) -> Result<(), Error>;
+}
+
+fn get_type_error(path: String, value: &Value) -> Error {
+    let value = match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    let message = Message::from_registry(
+        base_registry(),
+        "PropertyValueTypeError",
+        ResourceSchemaVersion::new(1, 1, 2),
+        vec![value, path.clone()],
+        vec![format!("#/{}", path)],
+    )
+    .unwrap();
+    Error::InvalidProperties(vec![message])
+}
+
+// The settings a patch of the property of the protocol, such as {"ProtocolEnabled": false},
+// makes of the current ones.
+fn patch_settings(
+    settings: &ProtocolSettings,
+    protocol: NetworkProtocol,
+    patch: &Value,
+) -> Result<ProtocolSettings, Error> {
+    let path = protocol.to_string();
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| get_type_error(path.clone(), patch))?;
+    let mut settings = settings.clone();
+    if let Some(enabled) = patch.get("ProtocolEnabled") {
+        settings.enabled = enabled
+            .as_bool()
+            .ok_or_else(|| get_type_error(format!("{}/ProtocolEnabled", path), enabled))?;
+    }
+    if let Some(port) = patch.get("Port") {
+        settings.port = port
+            .as_u64()
+            .and_then(|port| u16::try_from(port).ok())
+            .ok_or_else(|| get_type_error(format!("{}/Port", path), port))?;
+    }
+    Ok(settings)
+}
+
+fn get_settings(
+    resources: &StaticResources,
+    uri: &str,
+    protocol: NetworkProtocol,
+) -> Option<ProtocolSettings> {
+    let property = resources.get(uri, &protocol.to_string())?;
+    Some(ProtocolSettings {
+        enabled: property.get("ProtocolEnabled")?.as_bool()?,
+        port: property.get("Port")?.as_u64()?.try_into().ok()?,
+    })
+}
+
+// Adds a Manager, and its ManagerNetworkProtocol, to the tree a TreeBuilder builds. PATCHes
+// of the protocols it is given call back into the runtime to serve them as they say. The
+// Managers collection, with the manager among its members, is left to the builder.
+pub struct ManagerBuilder {
+    id: String,
+    manager_type: String,
+    protocols: Vec<(NetworkProtocol, ProtocolSettings)>,
+    runtime: Arc<dyn NetworkRuntime>,
+}
+
+impl ManagerBuilder {
+    pub fn new(id: &str, runtime: impl NetworkRuntime + 'static) -> Self {
+        Self {
+            id: String::from(id),
+            manager_type: String::from("BMC"),
+            protocols: Vec::new(),
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    // Such as BMC, which it is by default, or EnclosureManager.
+    pub fn manager_type(mut self, manager_type: &str) -> Self {
+        self.manager_type = String::from(manager_type);
+        self
+    }
+
+    // The protocol, with the settings the runtime serves it with when the service starts.
+    pub fn protocol(mut self, protocol: NetworkProtocol, settings: ProtocolSettings) -> Self {
+        self.protocols.push((protocol, settings));
+        self
+    }
+
+    pub fn get_uri(&self) -> String {
+        format!("/redfish/v1/Managers/{}", self.id)
+    }
+
+    pub fn add_to(self, builder: TreeBuilder) -> TreeBuilder {
+        let uri = self.get_uri();
+        let network_protocol_uri = format!("{}/NetworkProtocol", uri);
+        let mut network_protocol = Map::new();
+        for (protocol, settings) in &self.protocols {
+            network_protocol.insert(protocol.to_string(), settings.to_json());
+        }
+        let protocols: Vec<NetworkProtocol> = self
+            .protocols
+            .iter()
+            .map(|(protocol, _)| *protocol)
+            .collect();
+        let runtime = self.runtime;
+        builder
+            .resource(
+                &uri,
+                &format!("Manager {}", self.id),
+                ResourceType::new_dmtf(
+                    String::from("Manager"),
+                    ResourceSchemaVersion::new(1, 19, 0),
+                ),
+                AllowedMethods {
+                    delete: false,
+                    get: true,
+                    patch: false,
+                    post: false,
+                },
+                json!({
+                    "ManagerType": self.manager_type,
+                    "NetworkProtocol": {"@odata.id": network_protocol_uri},
+                    "Status": {"State": "Enabled", "Health": "OK"},
+                }),
+            )
+            .resource(
+                &network_protocol_uri,
+                "Manager Network Protocol",
+                ResourceType::new_dmtf(
+                    String::from("ManagerNetworkProtocol"),
+                    ResourceSchemaVersion::new(1, 10, 0),
+                ),
+                AllowedMethods {
+                    delete: false,
+                    get: true,
+                    patch: true,
+                    post: false,
+                },
+                Value::Object(network_protocol),
+            )
+            // Every protocol in the patch is checked before the runtime is told of any. Other
+            // properties are replaced, as they would be without the behavior.
+            .on_patch(
+                &network_protocol_uri,
+                move |resources, uri, request_body| {
+                    let mut patched = Vec::new();
+                    for protocol in &protocols {
+                        let patch = match request_body.get(&protocol.to_string()) {
+                            Some(patch) => patch,
+                            None => continue,
+                        };
+                        let settings =
+                            get_settings(resources, uri, *protocol).ok_or(Error::InternalError)?;
+                        patched.push((*protocol, patch_settings(&settings, *protocol, patch)?));
+                    }
+                    for (protocol, settings) in patched {
+                        runtime.configure(protocol, &settings)?;
+                        let name = protocol.to_string();
+                        let property = settings.apply(resources.get(uri, &name));
+                        resources.set(uri, &name, property)?;
+                    }
+                    for (name, value) in request_body {
+                        if !protocols
+                            .iter()
+                            .any(|protocol| protocol.to_string() == *name)
+                        {
+                            resources.set(uri, name, value.clone())?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+    }
+}
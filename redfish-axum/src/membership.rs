@@ -0,0 +1,50 @@
+use crate::{Error, Tree};
+use serde_json::Value;
+
+// Checks, after the tree has created or deleted a member, that its collection lists the
+// change, for catching trees that forget to update Members. A collection that the user cannot
+// get, or a parent that is not a collection, is not checked.
+
+pub(crate) async fn check_created(
+    tree: &(dyn Tree + Send + Sync),
+    collection_uri: &str,
+    member_uri: &str,
+    username: Option<&str>,
+) -> Result<(), Error> {
+    match lists_member(tree, collection_uri, member_uri, username).await {
+        Some(false) => Err(Error::InternalError),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) async fn check_deleted(
+    tree: &(dyn Tree + Send + Sync),
+    member_uri: &str,
+    username: Option<&str>,
+) -> Result<(), Error> {
+    let collection_uri = match member_uri.rsplit_once('/') {
+        Some((collection_uri, _)) => collection_uri,
+        None => return Ok(()),
+    };
+    match lists_member(tree, collection_uri, member_uri, username).await {
+        Some(true) => Err(Error::InternalError),
+        _ => Ok(()),
+    }
+}
+
+// Whether the collection at the URI lists the member, or None if it cannot be read as one.
+async fn lists_member(
+    tree: &(dyn Tree + Send + Sync),
+    collection_uri: &str,
+    member_uri: &str,
+    username: Option<&str>,
+) -> Option<bool> {
+    let node = tree.get(collection_uri, username).await.ok()?;
+    let body = node.try_get_body().ok()?;
+    let members = body.get("Members")?.as_array()?;
+    Some(
+        members
+            .iter()
+            .any(|member| member.get("@odata.id").and_then(Value::as_str) == Some(member_uri)),
+    )
+}
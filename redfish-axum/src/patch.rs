@@ -0,0 +1,120 @@
+use crate::get_messages_json;
+use async_trait::async_trait;
+use redfish_data::Message;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+// Checks the properties of PATCH requests before the tree is asked to apply them, such as
+// against limits of the hardware that only a backend knows. Validators run in the order they
+// were given to AppBuilder::property_validator(), once the values have been checked against the
+// schema, and each is only given the properties that those before it accepted.
+#[async_trait]
+pub trait PropertyValidator: Send + Sync {
+    // Return a message saying why the property of the resource at the URI cannot take the value,
+    // such as PropertyValueOutOfRange, with #/ and the name of the property among its
+    // RelatedProperties. Return None to accept it.
+    async fn validate(
+        &self,
+        uri: &str,
+        name: &str,
+        value: &Value,
+        username: Option<&str>,
+    ) -> Option<Message>;
+}
+
+// What comes of a PATCH with some properties that cannot be applied, such as ones of the wrong
+// type, and some that can.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PartialPatch {
+    // The others are applied, and the messages about those that were not are given in the
+    // @Message.ExtendedInfo of the resource.
+    #[default]
+    Annotated,
+    // Like Annotated, except that each message annotates the property it is about, as its
+    // @Message.ExtendedInfo, such as SessionTimeout@Message.ExtendedInfo.
+    PerProperty,
+    // None are applied, and the request is refused with 400 Bad Request.
+    Refused,
+}
+
+// Split the payload into the properties every validator accepts, and messages for the others.
+pub(crate) async fn run_validators(
+    validators: &[Arc<dyn PropertyValidator>],
+    uri: &str,
+    username: Option<&str>,
+    mut payload: Map<String, Value>,
+) -> (Map<String, Value>, Vec<Message>) {
+    let mut messages = Vec::new();
+    for validator in validators {
+        let mut accepted = Map::new();
+        for (name, value) in payload {
+            match validator.validate(uri, &name, &value, username).await {
+                Some(message) => messages.push(message),
+                None => {
+                    accepted.insert(name, value);
+                }
+            }
+        }
+        payload = accepted;
+    }
+    (payload, messages)
+}
+
+// Add the message to the @Message.ExtendedInfo of the property at the path, such as
+// Boot/BootSourceOverrideTarget, in the body. Returns false if the body has no such property.
+fn annotate_property(body: &mut Map<String, Value>, path: &str, message: &Message) -> bool {
+    let (parents, name) = match path.rsplit_once('/') {
+        Some((parents, name)) => (Some(parents), name),
+        None => (None, path),
+    };
+    let mut object = body;
+    for parent in parents.into_iter().flat_map(|parents| parents.split('/')) {
+        object = match object.get_mut(parent).and_then(Value::as_object_mut) {
+            Some(object) => object,
+            None => return false,
+        };
+    }
+    if !object.contains_key(name) {
+        return false;
+    }
+    let annotation = object
+        .entry(format!("{}@Message.ExtendedInfo", name))
+        .or_insert_with(|| Value::Array(Vec::new()));
+    match annotation.as_array_mut() {
+        Some(annotation) => {
+            annotation.push(Value::Object(message.to_json()));
+            true
+        }
+        None => false,
+    }
+}
+
+// Annotate the body of the patched resource with the messages about the properties that were
+// not applied. Messages that are not about a property of the body annotate the resource.
+pub(crate) fn annotate_patched(
+    body: &mut Map<String, Value>,
+    messages: Vec<Message>,
+    mode: PartialPatch,
+) {
+    let mut unrelated = Vec::new();
+    for message in messages {
+        let path = message
+            .get_related_properties()
+            .first()
+            .and_then(|property| property.strip_prefix("#/"))
+            .map(String::from);
+        let annotated = match (mode, path) {
+            (PartialPatch::PerProperty, Some(path)) => annotate_property(body, &path, &message),
+            _ => false,
+        };
+        if !annotated {
+            unrelated.push(message);
+        }
+    }
+    if !unrelated.is_empty() {
+        body.insert(
+            String::from("@Message.ExtendedInfo"),
+            get_messages_json(&unrelated),
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use axum::http::{header, HeaderMap, HeaderName, Method};
+use etag::EntityTag;
+use std::str::FromStr;
+
+// What a conditional request is to get, by the If-Match and If-None-Match headers of
+// RFC 9110 section 13.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Precondition {
+    // The request is carried out as if it had neither header.
+    Passed,
+    // The client's copy is current, so it gets 304 Not Modified instead. Only for GET and HEAD.
+    NotModified,
+    // The request is refused with 412 Precondition Failed.
+    Failed,
+}
+
+// The value of an If-Match or If-None-Match header, with all of its fields combined.
+enum EtagList {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+// The members of a list of entity-tags. Commas are allowed within the quotes of a tag, so the
+// list cannot just be split on them.
+fn split_tags(value: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                tags.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    tags.push(value[start..].trim());
+    tags.into_iter().filter(|tag| !tag.is_empty()).collect()
+}
+
+// The list in the header, or None without it. Members that are not entity-tags are ignored,
+// so a list of only those matches nothing.
+fn get_etag_list(headers: &HeaderMap, name: HeaderName) -> Option<EtagList> {
+    let mut values = headers.get_all(name).iter().peekable();
+    values.peek()?;
+    let mut tags = Vec::new();
+    for value in values {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for tag in split_tags(value) {
+            if tag == "*" {
+                return Some(EtagList::Any);
+            }
+            if let Ok(tag) = EntityTag::from_str(tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    Some(EtagList::Tags(tags))
+}
+
+// If-Match compares tags strongly, so weak tags never match. * matches any current
+// representation, which every resource the service finds has, even one without an ETag.
+fn if_match(list: &EtagList, etag: Option<&EntityTag>) -> bool {
+    match list {
+        EtagList::Any => true,
+        EtagList::Tags(tags) => etag.is_some_and(|etag| tags.iter().any(|tag| tag.strong_eq(etag))),
+    }
+}
+
+// If-None-Match compares tags weakly, and is false when any matches.
+fn if_none_match(list: &EtagList, etag: Option<&EntityTag>) -> bool {
+    match list {
+        EtagList::Any => false,
+        EtagList::Tags(tags) => !etag.is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag))),
+    }
+}
+
+// Evaluates the preconditions of a request of a resource with the given ETag, in the order of
+// RFC 9110 section 13.2.2. A failed If-Match takes precedence over If-None-Match, which fails
+// GET and HEAD requests with 304 Not Modified and others with 412 Precondition Failed.
+// If-Modified-Since is left to the caller, as it only applies when this returns Passed
+// without an If-None-Match header.
+pub(crate) fn evaluate_preconditions(
+    headers: &HeaderMap,
+    method: &Method,
+    etag: Option<&EntityTag>,
+) -> Precondition {
+    if let Some(list) = get_etag_list(headers, header::IF_MATCH) {
+        if !if_match(&list, etag) {
+            return Precondition::Failed;
+        }
+    }
+    if let Some(list) = get_etag_list(headers, header::IF_NONE_MATCH) {
+        if !if_none_match(&list, etag) {
+            return match *method == Method::GET || *method == Method::HEAD {
+                true => Precondition::NotModified,
+                false => Precondition::Failed,
+            };
+        }
+    }
+    Precondition::Passed
+}
@@ -0,0 +1,277 @@
+use crate::{get_account_username, AppState, Error, Node, Tree};
+use axum::{
+    http::{Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use etag::EntityTag;
+use redfish_data::{get_type_name, AllowedMethods, PrivilegeRegistry, PrivilegeTarget, SchemaUrl};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+
+pub(crate) const REGISTRIES_URI: &str = "/redfish/v1/Registries";
+
+// Who the request being handled is from, with the role and privileges the AuthenticationProvider
+// gives them, for trees to make finer, data-dependent checks than the privilege registry does,
+// such as on the properties of one resource. See current_user().
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestUser {
+    pub username: String,
+    // The Id of the user's role, such as Administrator, if the provider gives one.
+    pub role: Option<String>,
+    pub privileges: Option<Vec<String>>,
+}
+
+impl RequestUser {
+    // False if the provider does not give the user's privileges.
+    pub fn has_privilege(&self, privilege: &str) -> bool {
+        self.privileges
+            .as_ref()
+            .is_some_and(|privileges| privileges.iter().any(|p| p == privilege))
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_USER: RefCell<Option<RequestUser>>;
+}
+
+// The user the request being handled is from, once the service has authenticated them, as it
+// has by the time it calls the tree. None outside of requests, and for those without
+// credentials.
+pub fn current_user() -> Option<RequestUser> {
+    REQUEST_USER
+        .try_with(|user| user.borrow().clone())
+        .ok()
+        .flatten()
+}
+
+// Say that the request being handled is from the user, who has been authenticated.
+pub(crate) async fn set_current_user(state: &AppState, username: &str) {
+    if current_user().is_some_and(|user| user.username == username) {
+        return;
+    }
+    let provider = &state.authentication_provider;
+    let user = RequestUser {
+        username: String::from(username),
+        role: provider.get_role(username).await,
+        privileges: provider.get_privileges(username).await,
+    };
+    REQUEST_USER
+        .try_with(|current| *current.borrow_mut() = Some(user))
+        .ok();
+}
+
+// Handle the request with somewhere to keep who it is from, once it is authenticated.
+pub(crate) async fn track_request_user<B>(request: Request<B>, next: Next<B>) -> Response {
+    REQUEST_USER
+        .scope(RefCell::new(None), next.run(request))
+        .await
+}
+
+// The privileges the AuthenticationProvider gives the user, if the service authorizes requests
+// by a privilege registry. None if it does not, or there is no user, in which case the tree
+// alone decides what the request may do.
+pub(crate) async fn get_user_privileges(
+    state: &AppState,
+    username: Option<&str>,
+) -> Option<Vec<String>> {
+    state.privilege_registry?;
+    let username = username?;
+    if let Some(user) = current_user().filter(|user| user.username == username) {
+        return user.privileges;
+    }
+    state.authentication_provider.get_privileges(username).await
+}
+
+// What the service knows of an operation beyond the node it is on, for the overrides of the
+// privilege registry: the entities of the resources above the node, as get_ancestor_entities()
+// finds them, and the properties the operation writes, if it writes any in particular.
+pub(crate) struct OperationScope<'a> {
+    pub(crate) ancestors: &'a [String],
+    pub(crate) properties: Vec<String>,
+}
+
+impl<'a> OperationScope<'a> {
+    pub(crate) fn new(ancestors: &'a [String]) -> Self {
+        Self {
+            ancestors,
+            properties: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_properties(mut self, properties: Vec<String>) -> Self {
+        self.properties = properties;
+        self
+    }
+}
+
+// The entities of the resources above the one at the URI, from the service root down, for the
+// SubordinateOverrides of the privilege registry. Each is a get from the tree, so they are only
+// found if the registry has any. Those the user cannot read are left out.
+pub(crate) async fn get_ancestor_entities(
+    state: &AppState,
+    tree: &(dyn Tree + Send + Sync),
+    uri: &str,
+    username: Option<&str>,
+) -> Vec<String> {
+    if !state
+        .privilege_registry
+        .is_some_and(|registry| registry.has_subordinate_overrides())
+    {
+        return Vec::new();
+    }
+    let mut entities = Vec::new();
+    let mut ancestor = String::from("/redfish");
+    let segments: Vec<&str> = uri
+        .strip_prefix("/redfish/")
+        .unwrap_or_default()
+        .split('/')
+        .collect();
+    for segment in &segments[..segments.len().saturating_sub(1)] {
+        ancestor = format!("{}/{}", ancestor, segment);
+        let entity = match tree.get(&ancestor, username).await {
+            Ok(node) => get_entity(&*node).ok().filter(|entity| !entity.is_empty()),
+            Err(_) => None,
+        };
+        entities.extend(entity);
+    }
+    entities
+}
+
+// The entity of the node, such as ComputerSystem, from the resource type or schema it gives in
+// its metadata, so that authorizing HEAD and conditional requests need not make its body. Only
+// nodes that give neither have their body made, for its @odata.type.
+fn get_entity(node: &dyn Node) -> Result<String, Error> {
+    if let Some(resource_type) = node.get_resource_type() {
+        return Ok(resource_type.name.clone());
+    }
+    let schema = node.described_by().and_then(|url| {
+        let file = url.as_str().rsplit('/').next()?;
+        file.split('.').next().filter(|name| !name.is_empty())
+    });
+    if let Some(schema) = schema {
+        return Ok(String::from(schema));
+    }
+    let body = node.try_get_body()?;
+    Ok(String::from(get_type_name(&body).unwrap_or_default()))
+}
+
+// Refuse the operation on the node with Error::Forbidden unless the privileges allow it for
+// its entity, such as ComputerSystem, as the overrides of the registry for the node and the
+// scope of the operation say. ConfigureSelf only counts for the user's own account and
+// sessions.
+pub(crate) fn authorize(
+    state: &AppState,
+    node: &dyn Node,
+    operation: &Method,
+    username: Option<&str>,
+    privileges: Option<&[String]>,
+    scope: &OperationScope,
+) -> Result<(), Error> {
+    let (registry, privileges) = match (state.privilege_registry, privileges) {
+        (Some(registry), Some(privileges)) => (registry, privileges),
+        _ => return Ok(()),
+    };
+    let entity = get_entity(node)?;
+    let is_own = || match entity.as_str() {
+        "ManagerAccount" => get_account_username(node).as_deref() == username,
+        "Session" => state
+            .sessions
+            .store()
+            .get(node.get_uri())
+            .is_some_and(|session| Some(session.username.as_str()) == username),
+        _ => false,
+    };
+    let privileges: Vec<String> = match privileges.iter().any(|p| p == "ConfigureSelf") {
+        true if !is_own() => privileges
+            .iter()
+            .filter(|privilege| *privilege != "ConfigureSelf")
+            .cloned()
+            .collect(),
+        _ => privileges.to_vec(),
+    };
+    let target = PrivilegeTarget::new(&entity, node.get_uri())
+        .with_ancestors(scope.ancestors.to_vec())
+        .with_properties(scope.properties.clone());
+    match registry.allows(&target, operation.as_str(), &privileges) {
+        true => Ok(()),
+        false => Err(Error::Forbidden),
+    }
+}
+
+// Add the registry to the members of the Registries collection, if that is the URI.
+pub(crate) fn add_registry_member(
+    registry: &PrivilegeRegistry,
+    uri: &str,
+    body: &mut Map<String, Value>,
+) {
+    if uri != REGISTRIES_URI {
+        return;
+    }
+    let member = json!({"@odata.id": format!("{}/{}", REGISTRIES_URI, registry.get_id())});
+    let count = match body.get_mut("Members").and_then(Value::as_array_mut) {
+        Some(members) => {
+            members.push(member);
+            members.len()
+        }
+        None => return,
+    };
+    body.insert(String::from("Members@odata.count"), json!(count));
+}
+
+// The MessageRegistryFile of the registry, locating it for clients, or the registry itself.
+pub(crate) struct RegistryNode {
+    uri: String,
+    body: Value,
+}
+
+impl RegistryNode {
+    pub(crate) fn get(registry: &PrivilegeRegistry, uri: &str) -> Option<Self> {
+        let file_uri = format!("{}/{}", REGISTRIES_URI, registry.get_id());
+        let location = format!("{}/{}.json", file_uri, registry.get_id());
+        let body = match uri {
+            _ if uri == file_uri => json!({
+                "@odata.id": file_uri,
+                "@odata.type": "#MessageRegistryFile.v1_1_3.MessageRegistryFile",
+                "Id": registry.get_id(),
+                "Name": "Privilege Registry File",
+                "Registry": registry.get_id(),
+                "Languages": ["en"],
+                "Location": [{"Language": "en", "Uri": location}],
+            }),
+            _ if uri == location => registry.get_body().clone(),
+            _ => return None,
+        };
+        Some(Self {
+            uri: String::from(uri),
+            body,
+        })
+    }
+}
+
+impl Node for RegistryNode {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        }
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        None
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
@@ -0,0 +1,320 @@
+use crate::crawl::{Crawled, Crawler};
+use crate::Tree;
+use redfish_data::get_type_name;
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+pub enum ProfileError {
+    // The document is not JSON, or not shaped like an interop profile.
+    InvalidDocument(String),
+}
+
+// A DMTF interoperability profile: the resources, properties and values a service must have.
+#[derive(Clone, Debug)]
+pub struct InteropProfile {
+    pub name: String,
+    pub version: String,
+    // The requirements of each resource type, by schema name, as the profile states them.
+    resources: Map<String, Value>,
+}
+
+impl InteropProfile {
+    pub fn from_json(data: &str) -> Result<Self, ProfileError> {
+        let data: Map<String, Value> = serde_json::from_str(data)
+            .map_err(|error| ProfileError::InvalidDocument(error.to_string()))?;
+        let get_str = |key| {
+            data.get(key)
+                .and_then(|value| value.as_str())
+                .map(String::from)
+                .ok_or_else(|| ProfileError::InvalidDocument(format!("No {}", key)))
+        };
+        let name = get_str("ProfileName")?;
+        let version = get_str("ProfileVersion")?;
+        let resources = data
+            .get("Resources")
+            .and_then(|resources| resources.as_object())
+            .cloned()
+            .ok_or_else(|| ProfileError::InvalidDocument(String::from("No Resources")))?;
+        Ok(Self {
+            name,
+            version,
+            resources,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConformanceIssue {
+    // The profile requires a resource of this type, and the tree has none.
+    MissingResource(String),
+    // The resource is of an older version of its schema than the profile's MinVersion.
+    VersionTooOld {
+        uri: String,
+        version: String,
+        min_version: String,
+    },
+    // The profile requires the property, and the resource lacks it. Properties are paths such
+    // as Status/Health.
+    MissingProperty {
+        uri: String,
+        property: String,
+    },
+    // The profile requires the property to be absent, and the resource has it.
+    ForbiddenProperty {
+        uri: String,
+        property: String,
+    },
+    // The value of the property does not compare to the profile's Values as it requires.
+    UnexpectedValue {
+        uri: String,
+        property: String,
+        value: Value,
+    },
+}
+
+impl ConformanceIssue {
+    fn to_json(&self) -> Value {
+        match self {
+            ConformanceIssue::MissingResource(resource_type) => {
+                json!({ "Issue": "MissingResource", "ResourceType": resource_type })
+            }
+            ConformanceIssue::VersionTooOld {
+                uri,
+                version,
+                min_version,
+            } => json!({
+                "Issue": "VersionTooOld",
+                "Uri": uri,
+                "Version": version,
+                "MinVersion": min_version,
+            }),
+            ConformanceIssue::MissingProperty { uri, property } => {
+                json!({ "Issue": "MissingProperty", "Uri": uri, "Property": property })
+            }
+            ConformanceIssue::ForbiddenProperty { uri, property } => {
+                json!({ "Issue": "ForbiddenProperty", "Uri": uri, "Property": property })
+            }
+            ConformanceIssue::UnexpectedValue {
+                uri,
+                property,
+                value,
+            } => json!({
+                "Issue": "UnexpectedValue",
+                "Uri": uri,
+                "Property": property,
+                "Value": value,
+            }),
+        }
+    }
+}
+
+// What check_profile() found, in the order it found it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConformanceReport {
+    pub profile: String,
+    // The URIs of the resources whose type the profile has requirements for.
+    pub checked: Vec<String>,
+    pub issues: Vec<ConformanceIssue>,
+}
+
+impl ConformanceReport {
+    pub fn is_conformant(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "Profile": self.profile,
+            "Checked": self.checked.len(),
+            "Conformant": self.is_conformant(),
+            "Issues": self.issues.iter().map(ConformanceIssue::to_json).collect::<Vec<Value>>(),
+        })
+    }
+}
+
+// Only Mandatory and Supported requirements, and those that do not say, which are Mandatory,
+// must be met. The Values of others are still compared when the property is present.
+// ConditionalRequirements are not considered.
+fn is_required(requirements: &Value) -> bool {
+    match requirements
+        .get("ReadRequirement")
+        .and_then(|requirement| requirement.as_str())
+    {
+        None => true,
+        Some(requirement) => requirement == "Mandatory" || requirement == "Supported",
+    }
+}
+
+// The version of the schema of a @odata.type such as #ComputerSystem.v1_20_0.ComputerSystem,
+// or of a MinVersion such as 1.20.0 or 1.20.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let parts: Vec<u32> = version
+        .split(['.', '_'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    match parts[..] {
+        [major, minor] => Some((major, minor, 0)),
+        [major, minor, build] => Some((major, minor, build)),
+        _ => None,
+    }
+}
+
+fn get_odata_type_version(body: &Value) -> Option<&str> {
+    let odata_type = body.get("@odata.type")?.as_str()?;
+    odata_type.split('.').nth(1)?.strip_prefix('v')
+}
+
+// Values of different types do not compare, except that anything equals itself.
+fn compare(value: &Value, expected: &Value) -> Option<Ordering> {
+    match (value, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+// Whether the value compares to the profile's Values as the Comparison says, which is AnyOf if
+// it does not say. Arrays match AnyOf if any of their values is one of those, and AllOf if they
+// contain them all. Comparisons the checker does not know, such as LinkToResource, match.
+fn matches_values(value: &Value, comparison: &str, values: &[Value]) -> bool {
+    let elements = match value {
+        Value::Array(elements) => elements.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    let first = values.first().unwrap_or(&Value::Null);
+    let ordering = compare(value, first);
+    match comparison {
+        "AnyOf" => elements.iter().any(|element| values.contains(element)),
+        "AllOf" => values.iter().all(|expected| elements.contains(expected)),
+        "Equal" => ordering == Some(Ordering::Equal),
+        "NotEqual" => ordering != Some(Ordering::Equal),
+        "GreaterThan" => ordering == Some(Ordering::Greater),
+        "GreaterThanOrEqual" => ordering.is_some_and(|ordering| ordering != Ordering::Less),
+        "LessThan" => ordering == Some(Ordering::Less),
+        "LessThanOrEqual" => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+        _ => true,
+    }
+}
+
+fn check_properties(
+    uri: &str,
+    body: &Value,
+    requirements: &Map<String, Value>,
+    parent: &str,
+    issues: &mut Vec<ConformanceIssue>,
+) {
+    for (name, requirement) in requirements {
+        let property = match parent {
+            "" => name.clone(),
+            parent => format!("{}/{}", parent, name),
+        };
+        let comparison = requirement
+            .get("Comparison")
+            .and_then(|comparison| comparison.as_str());
+        let value = match body.get(name) {
+            Some(value) => value,
+            None => {
+                if is_required(requirement) && comparison != Some("Absent") {
+                    issues.push(ConformanceIssue::MissingProperty {
+                        uri: String::from(uri),
+                        property,
+                    });
+                }
+                continue;
+            }
+        };
+        if comparison == Some("Absent") {
+            issues.push(ConformanceIssue::ForbiddenProperty {
+                uri: String::from(uri),
+                property,
+            });
+            continue;
+        }
+        if let Some(values) = requirement.get("Values").and_then(|v| v.as_array()) {
+            if !matches_values(value, comparison.unwrap_or("AnyOf"), values) {
+                issues.push(ConformanceIssue::UnexpectedValue {
+                    uri: String::from(uri),
+                    property: property.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        // The requirements of the properties of objects, or of each object in an array.
+        if let Some(requirements) = requirement
+            .get("PropertyRequirements")
+            .and_then(|requirements| requirements.as_object())
+        {
+            let elements = match value {
+                Value::Array(elements) => elements.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            for element in elements {
+                check_properties(uri, element, requirements, &property, issues);
+            }
+        }
+    }
+}
+
+// Check that the tree, as read by the user, satisfies the profile. Resources are found the way
+// a client would find them, through the links from the ServiceRoot, and matched to the
+// requirements by the schema of their @odata.type.
+pub async fn check_profile(
+    tree: &(dyn Tree + Send + Sync),
+    username: Option<&str>,
+    profile: &InteropProfile,
+) -> ConformanceReport {
+    let mut report = ConformanceReport {
+        profile: format!("{} {}", profile.name, profile.version),
+        ..Default::default()
+    };
+    let mut found_types = Vec::new();
+    let mut crawler = Crawler::new(tree, username);
+    while let Some(crawled) = crawler.next().await {
+        let (uri, body) = match crawled {
+            Crawled::Found { uri, body, .. } => (uri, body),
+            Crawled::Missing { .. } => continue,
+        };
+        let (resource_type, requirements) =
+            match get_type_name(&body).and_then(|name| profile.resources.get_key_value(name)) {
+                Some(requirements) => requirements,
+                None => continue,
+            };
+        report.checked.push(uri.clone());
+        if !found_types.contains(resource_type) {
+            found_types.push(resource_type.clone());
+        }
+
+        let min_version = requirements
+            .get("MinVersion")
+            .and_then(|version| version.as_str());
+        let version = get_odata_type_version(&body);
+        if let (Some(min_version), Some(version)) = (min_version, version) {
+            let parsed = (parse_version(version), parse_version(min_version));
+            if let (Some(parsed_version), Some(parsed_min_version)) = parsed {
+                if parsed_version < parsed_min_version {
+                    report.issues.push(ConformanceIssue::VersionTooOld {
+                        uri: uri.clone(),
+                        version: version.replace('_', "."),
+                        min_version: String::from(min_version),
+                    });
+                }
+            }
+        }
+        if let Some(properties) = requirements
+            .get("PropertyRequirements")
+            .and_then(|properties| properties.as_object())
+        {
+            check_properties(&uri, &body, properties, "", &mut report.issues);
+        }
+    }
+    for (resource_type, requirements) in profile.resources.iter() {
+        if is_required(requirements) && !found_types.contains(resource_type) {
+            report
+                .issues
+                .push(ConformanceIssue::MissingResource(resource_type.clone()));
+        }
+    }
+    report
+}
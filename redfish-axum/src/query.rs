@@ -0,0 +1,341 @@
+use crate::{read_node_body, AppState, Error, Tree};
+use futures_util::stream::{self, StreamExt};
+use redfish_data::{parse_query_pairs, Expand, ExpandKind, Query, QueryError};
+use serde_json::{Map, Value};
+use std::future::Future;
+use std::pin::Pin;
+
+// What the service needs to page a collection and link to the next page: the URI and query
+// of the request, and the most members to return at once, if it limits them.
+pub(crate) struct Paging<'a> {
+    pub(crate) uri: &'a str,
+    pub(crate) query: &'a Query,
+    pub(crate) page_size: Option<usize>,
+}
+
+// Who the service reads other nodes for while applying a query, such as to expand references to
+// them. It reads them as a GET of each by the user would, so that the query shows the user
+// nothing they could not read themselves.
+pub(crate) struct Reader<'a> {
+    pub(crate) state: &'a AppState,
+    pub(crate) tree: &'a (dyn Tree + Send + Sync),
+    pub(crate) username: Option<&'a str>,
+    pub(crate) privileges: Option<&'a [String]>,
+}
+
+impl Reader<'_> {
+    // The body of the node at the URI, or None if the user cannot read it.
+    async fn read_body(&self, uri: &str) -> Option<Value> {
+        read_node_body(self.state, self.tree, uri, self.username, self.privileges)
+            .await
+            .ok()
+    }
+}
+
+// Where the next page of a collection starts.
+enum NextPage {
+    // The $skip of the next page the client asked for with $top.
+    Skip(usize),
+    // The $skiptoken of the next page of those the service limited to its page size.
+    SkipToken(String),
+}
+
+// The $skiptoken of the page of the members that starts at the offset: the offset and a hash
+// of it and the members, in hex. A token is only good for the members it was made for, so
+// clients cannot make their own, nor carry on paging a collection that has since changed.
+fn get_skip_token(offset: usize, members: &[Value]) -> String {
+    let offset = (offset as u64).to_be_bytes();
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    context.update(&offset);
+    for member in members {
+        context.update(get_uri(member).unwrap_or_default().as_bytes());
+        context.update(b"\n");
+    }
+    let digest = context.finish();
+    offset
+        .iter()
+        .chain(&digest.as_ref()[..8])
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// The offset of the $skiptoken, if it is one the service gave for the members.
+fn parse_skip_token(token: &str, members: &[Value]) -> Option<usize> {
+    if token.len() != 32 || !token.is_ascii() {
+        return None;
+    }
+    let offset = u64::from_str_radix(&token[..16], 16).ok()?;
+    let offset = usize::try_from(offset).ok()?;
+    match get_skip_token(offset, members) == token {
+        true => Some(offset),
+        false => None,
+    }
+}
+
+// The query parameters a tree applies itself, such as by pushing them into a database query.
+// The service applies the others to the body the tree returns.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeQuerySupport {
+    pub filter: bool,
+    pub top_skip: bool,
+    pub expand: bool,
+    pub select: bool,
+}
+
+impl From<QueryError> for Error {
+    fn from(error: QueryError) -> Self {
+        match error {
+            QueryError::Malformed(_) => Error::BadRequest,
+            QueryError::Unsupported(key) => Error::UnsupportedQueryParameter(key),
+        }
+    }
+}
+
+// The decoded pairs of the query string of a request, in the order they were given.
+pub(crate) fn get_query_pairs(raw: Option<&str>) -> Result<Vec<(String, String)>, Error> {
+    Ok(parse_query_pairs(raw.unwrap_or_default())?)
+}
+
+// Parse the query string of a request. Parameters the service does not support are refused
+// if they start with $, as those are defined by the spec, and ignored otherwise.
+pub(crate) fn parse_query(raw: Option<&str>) -> Result<Query, Error> {
+    Ok(Query::parse(raw.unwrap_or_default())?)
+}
+
+// Check the query of a request other than GET, which may not have any parameters the
+// service supports, as they only apply to GET.
+pub(crate) fn check_query_not_given(raw: Option<&str>) -> Result<(), Error> {
+    match parse_query(raw)?.is_empty() {
+        true => Ok(()),
+        false => Err(Error::InvalidQuery(
+            String::from("QueryNotSupportedOnOperation"),
+            Vec::new(),
+        )),
+    }
+}
+
+// Split into the parameters to forward to the tree, and those the service applies itself.
+// The service cannot tell which properties are in excerpts, so excerpt is always forwarded.
+pub(crate) fn split_query(query: Query, native: NativeQuerySupport) -> (Query, Query) {
+    let mut forwarded = Query {
+        excerpt: query.excerpt,
+        ..Default::default()
+    };
+    let mut remaining = Query {
+        excerpt: false,
+        ..query
+    };
+    if native.filter {
+        forwarded.filter = remaining.filter.take();
+    }
+    if native.top_skip {
+        forwarded.top = remaining.top.take();
+        forwarded.skip = remaining.skip.take();
+    }
+    if native.expand {
+        forwarded.expand = remaining.expand.take();
+    }
+    if native.select {
+        forwarded.select = remaining.select.take();
+    }
+    (forwarded, remaining)
+}
+
+// Apply the parameters to the body of a node. Expanding is done first, so that filters
+// can look at the properties of members, then paging, and selecting last, so that members
+// can be filtered by properties that are not selected.
+pub(crate) async fn apply_query(
+    query: &Query,
+    reader: &Reader<'_>,
+    mut body: Value,
+    paging: &Paging<'_>,
+) -> Result<Value, Error> {
+    if let Some(expand) = &query.expand {
+        expand_references(reader, &mut body, *expand, false).await;
+    }
+    if body.get("Members").is_some_and(Value::is_array) {
+        let next_page;
+        (body, next_page) = apply_to_members(query, reader, body, paging.page_size).await?;
+        if let Some(next_page) = next_page {
+            let link = get_page_link(paging.uri, paging.query, next_page);
+            body["Members@odata.nextLink"] = Value::String(link);
+        }
+    }
+    if let (Some(select), Value::Object(object)) = (&query.select, &mut body) {
+        let paths: Vec<Vec<&str>> = select
+            .iter()
+            .map(|path| path.split('/').collect())
+            .collect();
+        select_properties(object, &paths);
+    }
+    Ok(body)
+}
+
+// Filter and page the Members of a collection. The page the client asked for with $skip and
+// $top is split into pages of at most page_size. Also returns where the next page starts,
+// if there are members after this one. Refuses a $skiptoken the service did not give for the
+// members.
+async fn apply_to_members(
+    query: &Query,
+    reader: &Reader<'_>,
+    mut body: Value,
+    page_size: Option<usize>,
+) -> Result<(Value, Option<NextPage>), Error> {
+    let members = body["Members"].as_array_mut().unwrap();
+    if let Some(filter) = &query.filter {
+        let mut kept = Vec::new();
+        for member in members.drain(..) {
+            let matches = match get_uri(&member) {
+                // Members that were not expanded are read to check them. Those the user cannot
+                // read are left out, as their properties are not the user's to compare.
+                Some(uri) => match reader.read_body(uri).await {
+                    Some(body) => filter.matches(&body),
+                    None => false,
+                },
+                None => filter.matches(&member),
+            };
+            if matches {
+                kept.push(member);
+            }
+        }
+        *members = kept;
+    }
+    let count = members.len();
+    let skip = query.skip.unwrap_or(0).min(count);
+    let top = query.top.unwrap_or(count);
+    let paged: Vec<Value> = members.drain(..).skip(skip).take(top).collect();
+    let paged_count = paged.len();
+    let start = match &query.skip_token {
+        Some(token) => parse_skip_token(token, &paged).ok_or(Error::BadRequest)?,
+        None => 0,
+    }
+    .min(paged_count);
+    // Pages of none would never get anywhere.
+    let page_size = page_size.unwrap_or(paged_count).max(1);
+    let next_start = start + page_size.min(paged_count - start);
+    let next_page = if next_start < paged_count {
+        Some(NextPage::SkipToken(get_skip_token(next_start, &paged)))
+    } else if skip + paged_count < count {
+        Some(NextPage::Skip(skip + paged_count))
+    } else {
+        None
+    };
+    *members = paged.into_iter().skip(start).take(page_size).collect();
+    // The count is of all the members the filter matched, not just those on this page.
+    body["Members@odata.count"] = Value::from(count);
+    Ok((body, next_page))
+}
+
+// The URI of the next page of a collection, with the other query parameters of the request for
+// this page, in their canonical form.
+fn get_page_link(uri: &str, query: &Query, next_page: NextPage) -> String {
+    let mut query = query.clone();
+    match next_page {
+        NextPage::Skip(skip) => {
+            query.skip = Some(skip);
+            query.skip_token = None;
+        }
+        NextPage::SkipToken(token) => query.skip_token = Some(token),
+    }
+    format!("{}?{}", uri, query)
+}
+
+// Keep only the properties of the object at the given paths, and the @odata properties every
+// resource has, such as @odata.id. Annotations of kept properties, such as
+// Members@odata.count, are kept with them. Selecting an object keeps all of its properties.
+fn select_properties(object: &mut Map<String, Value>, paths: &[Vec<&str>]) {
+    object.retain(|key, value| {
+        if key.starts_with("@odata.") {
+            return true;
+        }
+        let property = match key.split_once('@') {
+            Some((property, _)) => property,
+            None => key.as_str(),
+        };
+        let selected: Vec<&Vec<&str>> = paths
+            .iter()
+            .filter(|path| path.first() == Some(&property))
+            .collect();
+        if selected.is_empty() {
+            return false;
+        }
+        if property.len() == key.len() && selected.iter().all(|path| path.len() > 1) {
+            if let Value::Object(child) = value {
+                let deeper: Vec<Vec<&str>> =
+                    selected.iter().map(|path| path[1..].to_vec()).collect();
+                select_properties(child, &deeper);
+            }
+        }
+        true
+    });
+}
+
+// Return the URI if the value is a bare reference, like {"@odata.id": "/redfish/v1/Systems/1"}.
+fn get_uri(value: &Value) -> Option<&str> {
+    let object = value.as_object()?;
+    match object.len() {
+        1 => object.get("@odata.id")?.as_str(),
+        _ => None,
+    }
+}
+
+// How many references are read at once when expanding those in an object or array, such as
+// the Members of a collection, so that trees reading slow backends can overlap the reads.
+const EXPAND_CONCURRENCY: usize = 8;
+
+// Replace references in the body with the bodies of the nodes they refer to, to the given depth.
+// References that cannot be read, such as ones the user may not see, are left as they are.
+// This recurses, so the future is boxed.
+fn expand_references<'a>(
+    reader: &'a Reader<'a>,
+    value: &'a mut Value,
+    expand: Expand,
+    in_links: bool,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+    Box::pin(async move {
+        if expand.levels == 0 {
+            return;
+        }
+        if let Some(uri) = get_uri(value) {
+            let wanted = match expand.kind {
+                ExpandKind::All => true,
+                ExpandKind::Links => in_links,
+                ExpandKind::NoLinks => !in_links,
+            };
+            if !wanted {
+                return;
+            }
+            let mut body = match reader.read_body(uri).await {
+                Some(body) => body,
+                None => return,
+            };
+            let deeper = Expand {
+                kind: expand.kind,
+                levels: expand.levels - 1,
+            };
+            expand_references(reader, &mut body, deeper, false).await;
+            *value = body;
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                // Only the references themselves are expanded, not the resource's own id.
+                let children = object.iter_mut().filter(|(key, _)| *key != "@odata.id");
+                stream::iter(children)
+                    .for_each_concurrent(EXPAND_CONCURRENCY, |(key, child)| {
+                        let in_links = in_links || key == "Links";
+                        expand_references(reader, child, expand, in_links)
+                    })
+                    .await;
+            }
+            Value::Array(array) => {
+                stream::iter(array.iter_mut())
+                    .for_each_concurrent(EXPAND_CONCURRENCY, |child| {
+                        expand_references(reader, child, expand, in_links)
+                    })
+                    .await;
+            }
+            _ => (),
+        }
+    })
+}
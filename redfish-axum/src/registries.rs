@@ -0,0 +1,158 @@
+use crate::error_hook::extend_error;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use redfish_data::{ErrorResponse, MessageRegistry, PrivilegeRegistry};
+use serde_json::{Map, Value};
+use std::sync::{Arc, OnceLock};
+
+tokio::task_local! {
+    // The Base registry in the language chosen for the request being handled.
+    static LOCALIZED_BASE_REGISTRY: &'static MessageRegistry;
+}
+
+// The DMTF Base registry, for the standard messages of error and success responses.
+// While handling a request of a service with localized registries, this is the one in the
+// language the client asked for.
+pub fn base_registry() -> &'static MessageRegistry {
+    if let Ok(registry) = LOCALIZED_BASE_REGISTRY.try_with(|registry| *registry) {
+        return registry;
+    }
+    redfish_data::base_registry()
+}
+
+// Translations of the Base registry, from which error responses are given in the language
+// of the request's Accept-Language.
+pub(crate) struct LocalizedRegistries {
+    registries: Vec<&'static MessageRegistry>,
+}
+
+impl LocalizedRegistries {
+    pub(crate) fn new(registries: Vec<&'static MessageRegistry>) -> Self {
+        Self { registries }
+    }
+
+    // The registry for the most preferred language there is one for, or None for the default.
+    fn select(&self, headers: &HeaderMap) -> Option<&'static MessageRegistry> {
+        let accept_language = headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = match parts.find_map(|param| param.trim().strip_prefix("q=")) {
+                    Some(quality) => quality.trim().parse().ok()?,
+                    None => 1.0,
+                };
+                Some((tag, quality))
+            })
+            .filter(|(tag, quality)| !tag.is_empty() && *quality > 0.0)
+            .collect();
+        // Stable, so that ranges of the same quality keep the client's order.
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (tag, _) in ranges {
+            if tag == "*" {
+                return None;
+            }
+            if let Some(registry) = self.lookup(tag) {
+                return Some(registry);
+            }
+        }
+        None
+    }
+
+    // Find the registry for a language range as in RFC 4647 lookup, so that "de-DE" falls
+    // back to "de", dropping subtags from the end until one matches.
+    fn lookup(&self, range: &str) -> Option<&'static MessageRegistry> {
+        let mut range = range;
+        loop {
+            let found = self
+                .registries
+                .iter()
+                .find(|registry| registry.get_language().eq_ignore_ascii_case(range));
+            if let Some(registry) = found {
+                return Some(*registry);
+            }
+            range = &range[..range.rfind('-')?];
+        }
+    }
+}
+
+// Handle the request with the Base registry in the language it asks for, if there is one.
+pub(crate) async fn select_language<B>(
+    State(registries): State<Arc<LocalizedRegistries>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let registry = registries
+        .select(request.headers())
+        .unwrap_or_else(redfish_data::base_registry);
+    LOCALIZED_BASE_REGISTRY
+        .scope(registry, next.run(request))
+        .await
+}
+
+// The JSON of an error response, giving the Language of its messages if they are localized.
+// Any hook the service has for extending errors is applied to it first.
+pub(crate) fn get_error_body(error: &ErrorResponse) -> Map<String, Value> {
+    let extended = extend_error(error);
+    let error = extended.as_ref().unwrap_or(error);
+    match LOCALIZED_BASE_REGISTRY.try_with(|_| ()) {
+        Ok(()) => error.to_localized_json(),
+        Err(_) => error.to_json(),
+    }
+}
+
+// Registry of the messages used for session lifecycle events.
+pub fn session_event_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        MessageRegistry::from_json(include_str!("../registries/SessionEvent.1.0.0.json"))
+    })
+}
+
+// Registry of the messages used for log service events.
+pub fn log_event_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        MessageRegistry::from_json(include_str!("../registries/LogEvent.1.0.0.json"))
+    })
+}
+
+// Registry of the messages used when telemetry triggers fire.
+pub fn trigger_event_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        MessageRegistry::from_json(include_str!("../registries/TriggerEvent.1.0.0.json"))
+    })
+}
+
+// Registry of the privileges that allow each operation on the entities services commonly
+// model, by which services authorize requests by default.
+pub fn privilege_registry() -> &'static PrivilegeRegistry {
+    static REGISTRY: OnceLock<PrivilegeRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        PrivilegeRegistry::from_json(include_str!(
+            "../privileges/Redfish_1.5.0_PrivilegeRegistry.json"
+        ))
+    })
+}
+
+// Registry of the messages used when resources change.
+pub fn resource_event_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        MessageRegistry::from_json(include_str!("../registries/ResourceEvent.1.1.0.json"))
+    })
+}
+
+// Registry of the messages used as tasks progress.
+pub fn task_event_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        MessageRegistry::from_json(include_str!("../registries/TaskEvent.1.0.3.json"))
+    })
+}
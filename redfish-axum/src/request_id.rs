@@ -0,0 +1,61 @@
+use axum::{
+    extract::State,
+    http::{HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// IDs given by clients or proxies are kept if they are no longer than this.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// The ID of the request being handled, for correlating what is logged about it with what the
+// client reports. It is the X-Request-Id the request was sent with, if it had a usable one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn get_request_id<B>(request: &Request<B>) -> String {
+    let given = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN);
+    match given {
+        Some(id) => String::from(id),
+        None => Uuid::new_v4().as_simple().to_string(),
+    }
+}
+
+// Handle the request with an ID, in a tracing span carrying it, and give the ID back in the
+// X-Request-Id of the response if the service is configured to.
+pub(crate) async fn assign_request_id<B>(
+    State(echo): State<bool>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let id = get_request_id(&request);
+    let span = tracing::info_span!(
+        "request",
+        id = %id,
+        method = %request.method(),
+        uri = %request.uri(),
+    );
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request).instrument(span))
+        .await;
+    if echo {
+        // The ID was either made here or read from a valid header, so it is a valid header.
+        response
+            .headers_mut()
+            .insert(X_REQUEST_ID.clone(), HeaderValue::from_str(&id).unwrap());
+    }
+    response
+}
@@ -0,0 +1,283 @@
+use crate::TreeBuilder;
+use redfish_data::{AllowedMethods, CollectionType, ResourceSchemaVersion, ResourceType};
+use serde_json::{json, Map, Value};
+
+#[derive(Debug)]
+pub enum ScaffoldError {
+    // The document is not JSON, or not shaped like the kind of document it was read as.
+    InvalidDocument(String),
+}
+
+// One resource or collection a new service has to implement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScaffoldNode {
+    pub uri: String,
+    pub schema: String,
+    // None for collections, which are all v1.
+    pub version: Option<ResourceSchemaVersion>,
+    pub allowed_methods: AllowedMethods,
+}
+
+impl ScaffoldNode {
+    fn is_collection(&self) -> bool {
+        self.version.is_none()
+    }
+
+    fn get_resource_type(&self) -> Option<ResourceType> {
+        Some(ResourceType::new_dmtf(
+            self.schema.clone(),
+            self.version.clone()?,
+        ))
+    }
+}
+
+// The skeleton of a service: the URIs, types and allowed methods of its resources.
+#[derive(Debug, Default)]
+pub struct Scaffold {
+    pub nodes: Vec<ScaffoldNode>,
+}
+
+// Placeholder Ids replace the {ResourceId} path segments of URI templates.
+fn fill_uri_template(uri: &str) -> String {
+    uri.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "1"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+fn parse_version(version: &str) -> Option<ResourceSchemaVersion> {
+    let parts: Vec<u32> = version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    match parts[..] {
+        [major, minor] => Some(ResourceSchemaVersion::new(major, minor, 0)),
+        [major, minor, build] => Some(ResourceSchemaVersion::new(major, minor, build)),
+        _ => None,
+    }
+}
+
+fn parse_document(data: &str) -> Result<Map<String, Value>, ScaffoldError> {
+    serde_json::from_str(data).map_err(|error| ScaffoldError::InvalidDocument(error.to_string()))
+}
+
+fn is_required(requirement: Option<&Value>) -> bool {
+    match requirement.and_then(|requirement| requirement.as_str()) {
+        None => true,
+        Some(requirement) => requirement == "Mandatory" || requirement == "Recommended",
+    }
+}
+
+fn get_str_property<'a>(data: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    data.get(key).and_then(|value| value.as_str())
+}
+
+impl Scaffold {
+    // Read an interop profile, taking the resources it requires or recommends that list URIs.
+    // Resources whose properties must be writable allow PATCH. Collections allow POST
+    // and resources DELETE when the profile sets CreateResource or DeleteResource.
+    pub fn from_interop_profile(data: &str) -> Result<Self, ScaffoldError> {
+        let data = parse_document(data)?;
+        let resources = data
+            .get("Resources")
+            .and_then(|resources| resources.as_object())
+            .ok_or_else(|| ScaffoldError::InvalidDocument(String::from("No Resources")))?;
+        let mut nodes = Vec::new();
+        for (schema, requirements) in resources {
+            let requirements = match requirements.as_object() {
+                Some(requirements) => requirements,
+                None => continue,
+            };
+            if !is_required(requirements.get("ReadRequirement")) {
+                continue;
+            }
+            let version = match schema.ends_with("Collection") {
+                true => None,
+                false => Some(
+                    get_str_property(requirements, "MinVersion")
+                        .and_then(parse_version)
+                        .unwrap_or(ResourceSchemaVersion::new(1, 0, 0)),
+                ),
+            };
+            let writable = requirements
+                .get("PropertyRequirements")
+                .and_then(|properties| properties.as_object())
+                .is_some_and(|properties| {
+                    properties.values().any(|property| {
+                        property.get("WriteRequirement").is_some()
+                            && is_required(property.get("WriteRequirement"))
+                    })
+                });
+            let flag = |key| requirements.get(key).and_then(|v| v.as_bool()) == Some(true);
+            let allowed_methods = AllowedMethods {
+                delete: flag("DeleteResource"),
+                get: true,
+                patch: writable,
+                post: flag("CreateResource"),
+            };
+            let uris = requirements.get("URIs").and_then(|uris| uris.as_array());
+            for uri in uris.into_iter().flatten().filter_map(|uri| uri.as_str()) {
+                nodes.push(ScaffoldNode {
+                    uri: fill_uri_template(uri),
+                    schema: schema.clone(),
+                    version: version.clone(),
+                    allowed_methods,
+                });
+            }
+        }
+        Ok(Self { nodes })
+    }
+
+    // Read an OpenAPI document in JSON, taking every path whose GET response
+    // refers to a DMTF schema, and the methods listed for it.
+    pub fn from_openapi(data: &str) -> Result<Self, ScaffoldError> {
+        let data = parse_document(data)?;
+        let paths = data
+            .get("paths")
+            .and_then(|paths| paths.as_object())
+            .ok_or_else(|| ScaffoldError::InvalidDocument(String::from("No paths")))?;
+        let mut nodes = Vec::new();
+        for (uri, operations) in paths {
+            let schema_ref = operations
+                .pointer("/get/responses/200/content/application~1json/schema/$ref")
+                .and_then(|schema_ref| schema_ref.as_str());
+            // Schema names look like ComputerSystem_v1_20_0_ComputerSystem,
+            // or ComputerSystemCollection_ComputerSystemCollection.
+            let name = match schema_ref.and_then(|schema_ref| schema_ref.rsplit('/').next()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let parts: Vec<&str> = name.split('_').collect();
+            let (schema, version) = match parts[..] {
+                [schema, _] => (schema, None),
+                [schema, major, minor, build, _] => {
+                    let version = format!("{}.{}.{}", major.trim_start_matches('v'), minor, build);
+                    match parse_version(&version) {
+                        Some(version) => (schema, Some(version)),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            let has = |method| operations.get(method).is_some();
+            nodes.push(ScaffoldNode {
+                uri: fill_uri_template(uri),
+                schema: String::from(schema),
+                version,
+                allowed_methods: AllowedMethods {
+                    delete: has("delete"),
+                    get: true,
+                    patch: has("patch"),
+                    post: has("post"),
+                },
+            });
+        }
+        Ok(Self { nodes })
+    }
+
+    // Members of a collection are the nodes one path segment below it.
+    fn get_members(&self, collection: &ScaffoldNode) -> Vec<&ScaffoldNode> {
+        let prefix = format!("{}/", collection.uri);
+        self.nodes
+            .iter()
+            .filter(|node| match node.uri.strip_prefix(&prefix) {
+                Some(rest) => !rest.is_empty() && !rest.contains('/'),
+                None => false,
+            })
+            .collect()
+    }
+
+    fn get_member_type(&self, collection: &ScaffoldNode) -> Option<ResourceType> {
+        if !collection.allowed_methods.post {
+            return None;
+        }
+        self.get_members(collection)
+            .first()
+            .and_then(|member| member.get_resource_type())
+    }
+
+    // Add the skeleton's resources, with empty bodies, to a tree builder.
+    pub fn apply(&self, mut builder: TreeBuilder) -> TreeBuilder {
+        for node in self.nodes.iter() {
+            builder = match node.get_resource_type() {
+                Some(resource_type) => builder.resource(
+                    &node.uri,
+                    &node.schema,
+                    resource_type,
+                    node.allowed_methods,
+                    json!({}),
+                ),
+                None => builder.collection(
+                    &node.uri,
+                    &node.schema,
+                    CollectionType::new_dmtf_v1(node.schema.clone()),
+                    self.get_members(node)
+                        .iter()
+                        .map(|member| member.uri.clone())
+                        .collect(),
+                    self.get_member_type(node),
+                ),
+            };
+        }
+        builder
+    }
+
+    // Rust source of the TreeBuilder calls that apply() makes, to paste into a new service
+    // and fill in, in place of the empty bodies.
+    pub fn to_rust(&self) -> String {
+        let mut source = String::from("TreeBuilder::new()\n");
+        for node in self.nodes.iter() {
+            if node.is_collection() {
+                let members: Vec<String> = self
+                    .get_members(node)
+                    .iter()
+                    .map(|member| format!("String::from(\"{}\")", member.uri))
+                    .collect();
+                let member_type = match self.get_member_type(node) {
+                    Some(member_type) => format!(
+                        "Some({})",
+                        get_resource_type_source(&member_type.name, &member_type.version)
+                    ),
+                    None => String::from("None"),
+                };
+                source.push_str(&format!(
+                    "    .collection(\n        \"{}\",\n        \"{}\",\n        CollectionType::new_dmtf_v1(String::from(\"{}\")),\n        vec![{}],\n        {},\n    )\n",
+                    node.uri,
+                    node.schema,
+                    node.schema,
+                    members.join(", "),
+                    member_type
+                ));
+            } else {
+                let resource_type = node.get_resource_type().unwrap();
+                let methods = node.allowed_methods;
+                source.push_str(&format!(
+                    "    .resource(\n        \"{}\",\n        \"{}\",\n        {},\n        AllowedMethods {{ delete: {}, get: true, patch: {}, post: {} }},\n        json!({{}}),\n    )\n",
+                    node.uri,
+                    node.schema,
+                    get_resource_type_source(&resource_type.name, &resource_type.version),
+                    methods.delete,
+                    methods.patch,
+                    methods.post
+                ));
+            }
+        }
+        source.push_str("    .build()\n");
+        source
+    }
+}
+
+fn get_resource_type_source(name: &str, version: &ResourceSchemaVersion) -> String {
+    // The version displays as v1_20_0.
+    let numbers = version.to_string()[1..].replace('_', ", ");
+    format!(
+        "ResourceType::new_dmtf(String::from(\"{}\"), ResourceSchemaVersion::new({}))",
+        name, numbers
+    )
+}
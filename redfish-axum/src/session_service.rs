@@ -0,0 +1,480 @@
+use crate::accounts::PatchError;
+use crate::{
+    Error, LinkRegistry, Node, NodeRef, Outcome, SessionManager, Tree, UploadWriter, SESSIONS_URI,
+    SESSION_SERVICE_URI,
+};
+use async_trait::async_trait;
+use etag::EntityTag;
+use redfish_data::{
+    get_resource_odata_type, AllowedMethods, CollectionType, ResourceSchemaVersion, ResourceType,
+    SchemaUrl,
+};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+// The range the schema gives the SessionTimeout, in seconds.
+const MIN_SESSION_TIMEOUT: u64 = 30;
+const MAX_SESSION_TIMEOUT: u64 = 86400;
+
+fn get_session_service_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("SessionService"),
+        ResourceSchemaVersion::new(1, 1, 8),
+    )
+}
+
+fn get_session_type() -> ResourceType {
+    ResourceType::new_dmtf(String::from("Session"), ResourceSchemaVersion::new(1, 6, 0))
+}
+
+fn get_session_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("SessionCollection"))
+}
+
+fn get_patch_error(key: &'static str, path: &str, value: &Value) -> Error {
+    let error = PatchError {
+        key,
+        path: String::from(path),
+        value: value.clone(),
+    };
+    Error::InvalidProperties(vec![error.into_message()])
+}
+
+// The Id of the session at the URI, if it is a member of the collection of sessions.
+fn get_session_id(uri: &str) -> Option<&str> {
+    let id = uri.strip_prefix(SESSIONS_URI)?.strip_prefix('/')?;
+    match id.is_empty() || id.contains('/') {
+        true => None,
+        false => Some(id),
+    }
+}
+
+fn get_session_body(uri: &str, id: &str, username: &str) -> Value {
+    let resource_type = get_session_type();
+    json!({
+        "@odata.id": uri,
+        "@odata.type": get_resource_odata_type("Session", &resource_type.version, "Session"),
+        "Id": id,
+        "Name": "User Session",
+        "UserName": username,
+        "Password": null,
+        "SessionType": "Redfish",
+    })
+}
+
+// The SessionService, its collection of sessions, or one of them, as of when it was made.
+pub(crate) struct SessionServiceNode {
+    uri: String,
+    body: Value,
+    allowed_methods: AllowedMethods,
+    described_by: SchemaUrl,
+    required: Vec<String>,
+}
+
+impl SessionServiceNode {
+    fn new(
+        uri: &str,
+        body: Value,
+        allowed_methods: AllowedMethods,
+        described_by: SchemaUrl,
+    ) -> Self {
+        Self {
+            uri: String::from(uri),
+            body,
+            allowed_methods,
+            described_by,
+            required: Vec::new(),
+        }
+    }
+}
+
+impl Node for SessionServiceNode {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        self.allowed_methods
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+
+    fn get_required_create_properties(&self) -> Vec<String> {
+        self.required.clone()
+    }
+}
+
+// A tree that serves the SessionService, and its collection of sessions, from the sessions of
+// a SessionManager, and everything else from a fallback tree. The manager has to be the one
+// the service keeps sessions in, as given to AppBuilder::session_manager(). Clients log in by
+// creating a session, whose credentials the service has checked with its
+// AuthenticationProvider before the tree is asked to, and log out by deleting it. They may set
+// the SessionTimeout and ServiceEnabled of the SessionService. Only authenticated users may
+// read any of it. The service root of the fallback should link to the SessionService.
+pub struct SessionServiceTree<T: Tree + Send + Sync> {
+    fallback: T,
+    sessions: SessionManager,
+    session_timeout: u64,
+    service_enabled: bool,
+    // Ids are not reused, so that the URI of a session that has gone is not that of another.
+    next_id: u64,
+    collection_types: Vec<CollectionType>,
+    resource_types: Vec<ResourceType>,
+    // The last node made for create() or patch(), which is lent to the caller.
+    last_node: Option<Arc<dyn Node + Send + Sync>>,
+}
+
+impl<T: Tree + Send + Sync> SessionServiceTree<T> {
+    pub fn new(fallback: T, sessions: SessionManager) -> Self {
+        let mut tree = Self {
+            fallback,
+            sessions,
+            session_timeout: 600,
+            service_enabled: true,
+            next_id: 1,
+            collection_types: Vec::new(),
+            resource_types: Vec::new(),
+            last_node: None,
+        };
+        tree.update_types();
+        tree
+    }
+
+    // How long sessions may go unused before they expire, which is 10 minutes by default. It
+    // is kept to the range of 30 seconds to a day that the schema gives it.
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout
+            .as_secs()
+            .clamp(MIN_SESSION_TIMEOUT, MAX_SESSION_TIMEOUT);
+        self
+    }
+
+    fn update_types(&mut self) {
+        self.collection_types = self.fallback.get_collection_types().to_vec();
+        self.resource_types = self.fallback.get_resource_types().to_vec();
+        let collection_type = get_session_collection_type();
+        if !self.collection_types.contains(&collection_type) {
+            self.collection_types.push(collection_type);
+        }
+        for resource_type in [get_session_service_type(), get_session_type()] {
+            if !self
+                .resource_types
+                .iter()
+                .any(|existing| existing.name == resource_type.name)
+            {
+                self.resource_types.push(resource_type);
+            }
+        }
+    }
+
+    fn is_served(uri: &str) -> bool {
+        match uri.strip_prefix(SESSION_SERVICE_URI) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+
+    // The URIs of the sessions, in the order they were created.
+    fn get_members(&self) -> Vec<String> {
+        self.sessions
+            .store()
+            .list()
+            .into_iter()
+            .map(|session| session.uri)
+            .filter(|uri| get_session_id(uri).is_some())
+            .collect()
+    }
+
+    fn get_node(&self, uri: &str) -> Option<SessionServiceNode> {
+        let read_only = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        if uri == SESSION_SERVICE_URI {
+            let resource_type = get_session_service_type();
+            let body = json!({
+                "@odata.id": SESSION_SERVICE_URI,
+                "@odata.type": get_resource_odata_type("SessionService", &resource_type.version, "SessionService"),
+                "Id": "SessionService",
+                "Name": "Session Service",
+                "ServiceEnabled": self.service_enabled,
+                "SessionTimeout": self.session_timeout,
+                "Sessions": {"@odata.id": SESSIONS_URI},
+                "Status": {"State": "Enabled", "Health": "OK"},
+            });
+            let allowed_methods = AllowedMethods {
+                patch: true,
+                ..read_only
+            };
+            return Some(SessionServiceNode::new(
+                uri,
+                body,
+                allowed_methods,
+                resource_type.described_by,
+            ));
+        }
+        if uri == SESSIONS_URI {
+            let members: Vec<Value> = self
+                .get_members()
+                .into_iter()
+                .map(|member| json!({"@odata.id": member}))
+                .collect();
+            let body = json!({
+                "@odata.id": SESSIONS_URI,
+                "@odata.type": "#SessionCollection.SessionCollection",
+                "Name": "Session Collection",
+                "Members@odata.count": members.len(),
+                "Members": members,
+            });
+            let allowed_methods = AllowedMethods {
+                post: true,
+                ..read_only
+            };
+            let mut node = SessionServiceNode::new(
+                uri,
+                body,
+                allowed_methods,
+                get_session_collection_type().described_by,
+            );
+            node.required = vec![String::from("UserName"), String::from("Password")];
+            return Some(node);
+        }
+        let id = get_session_id(uri)?;
+        let session = self.sessions.store().get(uri)?;
+        let allowed_methods = AllowedMethods {
+            delete: true,
+            ..read_only
+        };
+        Some(SessionServiceNode::new(
+            uri,
+            get_session_body(uri, id, &session.username),
+            allowed_methods,
+            get_session_type().described_by,
+        ))
+    }
+
+    // The error for a method that the node at the URI does not allow, if there is one.
+    fn refuse(&self, uri: &str) -> Error {
+        match self.get_node(uri) {
+            Some(node) => Error::MethodNotAllowed(node.allowed_methods),
+            None => Error::NotFound,
+        }
+    }
+
+    fn lend(&mut self, node: SessionServiceNode) -> &dyn Node {
+        self.last_node = Some(Arc::new(node));
+        self.last_node.as_deref().unwrap()
+    }
+
+    // Sessions reloaded from a store, such as a FileSessionStore, keep their Ids.
+    fn take_id(&mut self) -> u64 {
+        let highest = self
+            .get_members()
+            .iter()
+            .filter_map(|uri| get_session_id(uri)?.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        let id = self.next_id.max(highest + 1);
+        self.next_id = id + 1;
+        id
+    }
+
+    // Every property is checked before any is applied.
+    fn patch_service(&mut self, request_body: &Map<String, Value>) -> Result<(), Error> {
+        let mut session_timeout = self.session_timeout;
+        let mut service_enabled = self.service_enabled;
+        for (name, value) in request_body {
+            match name.as_str() {
+                "SessionTimeout" => {
+                    let timeout = value
+                        .as_u64()
+                        .ok_or_else(|| get_patch_error("PropertyValueTypeError", name, value))?;
+                    if !(MIN_SESSION_TIMEOUT..=MAX_SESSION_TIMEOUT).contains(&timeout) {
+                        return Err(get_patch_error("PropertyValueOutOfRange", name, value));
+                    }
+                    session_timeout = timeout;
+                }
+                "ServiceEnabled" => {
+                    service_enabled = value
+                        .as_bool()
+                        .ok_or_else(|| get_patch_error("PropertyValueTypeError", name, value))?;
+                }
+                "Sessions" | "Status" | "Id" | "Name" => {
+                    return Err(get_patch_error("PropertyNotWritable", name, value))
+                }
+                _ => return Err(get_patch_error("PropertyUnknown", name, value)),
+            }
+        }
+        self.session_timeout = session_timeout;
+        self.service_enabled = service_enabled;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Tree + Send + Sync> Tree for SessionServiceTree<T> {
+    async fn on_start(&mut self) -> Result<(), Error> {
+        self.fallback.on_start().await?;
+        // Starting may have added types to the fallback.
+        self.update_types();
+        Ok(())
+    }
+
+    async fn on_shutdown(&mut self) {
+        self.fallback.on_shutdown().await
+    }
+
+    fn get_link_registry(&self) -> Option<&LinkRegistry> {
+        self.fallback.get_link_registry()
+    }
+
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.get(uri, username).await;
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        let node = self.get_node(uri).ok_or(Error::NotFound)?;
+        Ok(NodeRef::Shared(Arc::new(node)))
+    }
+
+    // The session is not kept by the manager until the service has made it from what this
+    // returns, once it has the token.
+    async fn create(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.create(uri, request_body, username).await;
+        }
+        if uri != SESSIONS_URI {
+            return match username {
+                None => Err(Error::Unauthorized),
+                Some(_) => Err(self.refuse(uri)),
+            };
+        }
+        let login = match request_body.get("UserName") {
+            Some(Value::String(login)) => login.clone(),
+            Some(value) => {
+                return Err(get_patch_error("PropertyValueTypeError", "UserName", value))
+            }
+            None => return Err(Error::Unauthorized),
+        };
+        let id = self.take_id().to_string();
+        let member_uri = format!("{}/{}", SESSIONS_URI, id);
+        let allowed_methods = AllowedMethods {
+            delete: true,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let node = SessionServiceNode::new(
+            &member_uri,
+            get_session_body(&member_uri, &id, &login),
+            allowed_methods,
+            get_session_type().described_by,
+        );
+        Ok(self.lend(node))
+    }
+
+    // The service removes the session from the manager once the tree has deleted it.
+    async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.delete(uri, username).await;
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        match get_session_id(uri) {
+            Some(_) if self.sessions.store().get(uri).is_some() => Ok(()),
+            _ => Err(self.refuse(uri)),
+        }
+    }
+
+    async fn patch(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        if !Self::is_served(uri) {
+            return self.fallback.patch(uri, request_body, username).await;
+        }
+        if username.is_none() {
+            return Err(Error::Unauthorized);
+        }
+        if uri != SESSION_SERVICE_URI {
+            return Err(self.refuse(uri));
+        }
+        self.patch_service(request_body)?;
+        let node = self.get_node(uri).ok_or(Error::InternalError)?;
+        Ok(self.lend(node))
+    }
+
+    async fn action(
+        &mut self,
+        uri: &str,
+        action: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        if Self::is_served(uri) {
+            return match username {
+                None => Err(Error::Unauthorized),
+                Some(_) => Err(Error::ActionNotSupported(String::from(action))),
+            };
+        }
+        self.fallback
+            .action(uri, action, request_body, username)
+            .await
+    }
+
+    fn can_invoke_action(&self, uri: &str, action: &str, username: Option<&str>) -> bool {
+        match Self::is_served(uri) {
+            false => self.fallback.can_invoke_action(uri, action, username),
+            true => true,
+        }
+    }
+
+    fn is_session_collection(&self, uri: &str) -> bool {
+        match Self::is_served(uri) {
+            false => self.fallback.is_session_collection(uri),
+            true => uri == SESSIONS_URI,
+        }
+    }
+
+    async fn upload(
+        &mut self,
+        uri: &str,
+        username: Option<&str>,
+    ) -> Result<Box<dyn UploadWriter>, Error> {
+        if Self::is_served(uri) {
+            return Err(Error::UnsupportedMediaType);
+        }
+        self.fallback.upload(uri, username).await
+    }
+
+    fn get_collection_types(&self) -> &[CollectionType] {
+        &self.collection_types
+    }
+
+    fn get_resource_types(&self) -> &[ResourceType] {
+        &self.resource_types
+    }
+}
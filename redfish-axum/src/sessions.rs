@@ -0,0 +1,395 @@
+use crate::Error;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What the service keeps about a session created through it. Times are by the service's
+// clock, as timestamps rather than instants, so that stores can persist them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub token: String,
+    pub username: String,
+    pub uri: String,
+    // Given by the client when logging in, and echoed back in the session's body.
+    pub context: Option<String>,
+    pub oem: Option<Map<String, Value>>,
+    // The IP address of the client that logged in, if known.
+    pub source: Option<IpAddr>,
+    pub created: SystemTime,
+    // When the token was last accepted, for expiring the session once it has been idle for
+    // longer than the SessionTimeout of the SessionService.
+    pub last_activity: SystemTime,
+}
+
+// Where the service keeps its sessions. Stores other than the in-memory default, such as ones
+// backed by Redis, sqlite or a file, let several processes serve the same sessions, and
+// sessions outlive the process. Each session is identified by its URI. Stores may keep a hash
+// of the token of each session in place of the token, as long as find_by_token() finds it.
+pub trait SessionStore: Send + Sync {
+    // Every session, in the order they were created.
+    fn list(&self) -> Vec<Session>;
+
+    fn insert(&self, session: Session);
+
+    // Replace the session with the same URI, if there is one.
+    fn update(&self, session: Session);
+
+    fn remove(&self, uri: &str) -> Option<Session>;
+
+    fn get(&self, uri: &str) -> Option<Session> {
+        self.list().into_iter().find(|session| session.uri == uri)
+    }
+
+    fn find_by_token(&self, token: &str) -> Option<Session> {
+        self.list()
+            .into_iter()
+            .find(|session| session.token == token)
+    }
+}
+
+// Keeps sessions in memory, for as long as the process runs. Cloning gives another handle to
+// the same sessions.
+#[derive(Clone, Default)]
+pub struct MemorySessionStore {
+    sessions: Arc<RwLock<Vec<Session>>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn list(&self) -> Vec<Session> {
+        self.sessions.read().unwrap().clone()
+    }
+
+    fn insert(&self, session: Session) {
+        self.sessions.write().unwrap().push(session);
+    }
+
+    fn update(&self, session: Session) {
+        let mut sessions = self.sessions.write().unwrap();
+        if let Some(existing) = sessions
+            .iter_mut()
+            .find(|existing| existing.uri == session.uri)
+        {
+            *existing = session;
+        }
+    }
+
+    fn remove(&self, uri: &str) -> Option<Session> {
+        let mut sessions = self.sessions.write().unwrap();
+        let index = sessions.iter().position(|session| session.uri == uri)?;
+        Some(sessions.remove(index))
+    }
+
+    fn get(&self, uri: &str) -> Option<Session> {
+        let sessions = self.sessions.read().unwrap();
+        sessions.iter().find(|session| session.uri == uri).cloned()
+    }
+
+    fn find_by_token(&self, token: &str) -> Option<Session> {
+        let sessions = self.sessions.read().unwrap();
+        sessions
+            .iter()
+            .find(|session| session.token == token)
+            .cloned()
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionStoreError {
+    Io(io::Error),
+    // The file is not JSON, or not shaped like the sessions of a FileSessionStore.
+    InvalidDocument(String),
+}
+
+fn get_token_hash(token: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+    digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn get_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+fn session_to_json(session: &Session) -> Value {
+    json!({
+        "TokenHash": session.token,
+        "UserName": session.username,
+        "Uri": session.uri,
+        "Context": session.context,
+        "Oem": session.oem,
+        "Source": session.source.map(|source| source.to_string()),
+        "Created": get_timestamp(session.created),
+        "LastActivity": get_timestamp(session.last_activity),
+    })
+}
+
+fn session_from_json(session: &Value) -> Option<Session> {
+    let get_str = |name: &str| session.get(name)?.as_str().map(String::from);
+    let get_time = |name: &str| {
+        let seconds = session.get(name)?.as_u64()?;
+        Some(UNIX_EPOCH + Duration::from_secs(seconds))
+    };
+    Some(Session {
+        token: get_str("TokenHash")?,
+        username: get_str("UserName")?,
+        uri: get_str("Uri")?,
+        context: get_str("Context"),
+        oem: session.get("Oem").and_then(|oem| oem.as_object()).cloned(),
+        source: get_str("Source").and_then(|source| source.parse().ok()),
+        created: get_time("Created")?,
+        last_activity: get_time("LastActivity")?,
+    })
+}
+
+fn get_latest_activity(sessions: &[Session]) -> Option<SystemTime> {
+    sessions.iter().map(|session| session.last_activity).max()
+}
+
+// Keeps sessions in a file as well as in memory, and reloads them when opened, so that the
+// tokens of clients stay good across restarts of the service. The file holds a hash of each
+// token rather than the token. Expired sessions are reloaded too, and expire as the service
+// next checks them. The service updates the activity of a session on every request it
+// authenticates, so the file is only written for activity about once a minute, and sessions
+// reloaded from it may seem idle for up to a minute longer than they were. Within a tokio
+// runtime the file is written on its blocking threads, after the change is made in memory, so a
+// crash right after a change may lose it. The tree is not told of reloaded sessions, so it has
+// to have their resources again, such as from list(), for clients to read and delete them.
+pub struct FileSessionStore {
+    path: PathBuf,
+    sessions: RwLock<Vec<Session>>,
+    // The latest activity of any session in the file as it was last written.
+    activity_saved: Arc<RwLock<Option<SystemTime>>>,
+    // Saves are numbered as they are made, so that one reaching the file after a later one is
+    // dropped rather than undoing it.
+    last_save: AtomicU64,
+    // The number of the save last written, locked while writing the file.
+    written: Arc<Mutex<u64>>,
+}
+
+// How long a FileSessionStore goes between writing the activity of its sessions to its file.
+const ACTIVITY_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+impl FileSessionStore {
+    // Reload the sessions in the file at the path, if there is one.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SessionStoreError> {
+        let path = path.as_ref().to_path_buf();
+        let sessions = match fs::read_to_string(&path) {
+            Ok(data) => {
+                let data: Value = serde_json::from_str(&data)
+                    .map_err(|error| SessionStoreError::InvalidDocument(error.to_string()))?;
+                data.as_array()
+                    .ok_or_else(|| {
+                        SessionStoreError::InvalidDocument(String::from("Not an array"))
+                    })?
+                    .iter()
+                    .map(|session| {
+                        session_from_json(session).ok_or_else(|| {
+                            SessionStoreError::InvalidDocument(String::from("Invalid session"))
+                        })
+                    })
+                    .collect::<Result<Vec<Session>, SessionStoreError>>()?
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(SessionStoreError::Io(error)),
+        };
+        let activity_saved = get_latest_activity(&sessions);
+        Ok(Self {
+            path,
+            sessions: RwLock::new(sessions),
+            activity_saved: Arc::new(RwLock::new(activity_saved)),
+            last_save: AtomicU64::new(0),
+            written: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    // Written to another file first, and moved over the file, so that a crash while writing
+    // does not leave a partial one.
+    fn save(&self, sessions: &[Session]) {
+        let data = Value::Array(sessions.iter().map(session_to_json).collect()).to_string();
+        let latest_activity = get_latest_activity(sessions);
+        let number = self.last_save.fetch_add(1, Ordering::Relaxed) + 1;
+        let path = self.path.clone();
+        let activity_saved = self.activity_saved.clone();
+        let written = self.written.clone();
+        let write = move || {
+            let mut written = written.lock().unwrap();
+            if *written > number {
+                return;
+            }
+            let mut temporary = path.clone().into_os_string();
+            temporary.push(".tmp");
+            let result = fs::write(&temporary, data).and_then(|()| fs::rename(&temporary, &path));
+            if let Err(error) = result {
+                tracing::warn!("could not save sessions to {}: {}", path.display(), error);
+                return;
+            }
+            *written = number;
+            *activity_saved.write().unwrap() = latest_activity;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(write)),
+            Err(_) => write(),
+        }
+    }
+
+    fn change(&self, change: impl FnOnce(&mut Vec<Session>)) {
+        let mut sessions = self.sessions.write().unwrap();
+        change(&mut sessions);
+        self.save(&sessions);
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn list(&self) -> Vec<Session> {
+        self.sessions.read().unwrap().clone()
+    }
+
+    // A reloaded session the tree no longer has may have the URI of the new one.
+    fn insert(&self, mut session: Session) {
+        session.token = get_token_hash(&session.token);
+        self.change(|sessions| {
+            sessions.retain(|existing| existing.uri != session.uri);
+            sessions.push(session);
+        });
+    }
+
+    fn update(&self, session: Session) {
+        let mut sessions = self.sessions.write().unwrap();
+        let existing = match sessions
+            .iter_mut()
+            .find(|existing| existing.uri == session.uri)
+        {
+            Some(existing) => existing,
+            None => return,
+        };
+        let activity = session.last_activity;
+        let is_activity = Session {
+            last_activity: existing.last_activity,
+            ..session.clone()
+        } == *existing;
+        *existing = session;
+        let saved = *self.activity_saved.read().unwrap();
+        if is_activity
+            && saved.is_some_and(|saved| {
+                activity
+                    .duration_since(saved)
+                    .map_or(true, |since| since < ACTIVITY_SAVE_INTERVAL)
+            })
+        {
+            return;
+        }
+        self.save(&sessions);
+    }
+
+    fn remove(&self, uri: &str) -> Option<Session> {
+        let mut removed = None;
+        self.change(|sessions| {
+            if let Some(index) = sessions.iter().position(|session| session.uri == uri) {
+                removed = Some(sessions.remove(index));
+            }
+        });
+        removed
+    }
+
+    fn find_by_token(&self, token: &str) -> Option<Session> {
+        let hash = get_token_hash(token);
+        let sessions = self.sessions.read().unwrap();
+        sessions
+            .iter()
+            .find(|session| session.token == hash)
+            .cloned()
+    }
+}
+
+// Handle to the sessions created through the service, for attaching OEM data to them.
+// Cloning gives another handle to the same sessions.
+#[derive(Clone)]
+pub struct SessionManager {
+    store: Arc<dyn SessionStore>,
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::with_store(MemorySessionStore::new())
+    }
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keep sessions in the given store, rather than in memory.
+    pub fn with_store(store: impl SessionStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+        }
+    }
+
+    // Set the OEM data of the session at the given URI, which is merged into the Oem
+    // property of its body.
+    pub fn set_oem(&self, uri: &str, oem: Map<String, Value>) -> Result<(), Error> {
+        let mut session = self.store.get(uri).ok_or(Error::NotFound)?;
+        session.oem = Some(oem);
+        self.store.update(session);
+        Ok(())
+    }
+
+    pub fn get_oem(&self, uri: &str) -> Option<Map<String, Value>> {
+        self.store.get(uri)?.oem
+    }
+
+    pub fn get_context(&self, uri: &str) -> Option<String> {
+        self.store.get(uri)?.context
+    }
+
+    pub(crate) fn store(&self) -> &dyn SessionStore {
+        &*self.store
+    }
+
+    // Remove the sessions the predicate returns true for, and return them.
+    pub(crate) fn remove_where(&self, predicate: impl Fn(&Session) -> bool) -> Vec<Session> {
+        self.store
+            .list()
+            .iter()
+            .filter(|session| predicate(session))
+            .filter_map(|session| self.store.remove(&session.uri))
+            .collect()
+    }
+
+    // Add what the service keeps about the session at the given URI to its body, if it is one.
+    pub(crate) fn add_session_properties(&self, uri: &str, body: &mut Map<String, Value>) {
+        let session = match self.store.get(uri) {
+            Some(session) => session,
+            None => return,
+        };
+        if let Some(context) = session.context {
+            body.insert(String::from("Context"), Value::String(context));
+        }
+        if let Some(oem) = session.oem {
+            let existing = body
+                .entry("Oem")
+                .or_insert_with(|| Value::Object(Map::new()));
+            match existing {
+                Value::Object(existing) => existing.extend(oem),
+                existing => *existing = Value::Object(oem),
+            }
+        }
+    }
+}
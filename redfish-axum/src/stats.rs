@@ -0,0 +1,135 @@
+use crate::{NodeMeta, Tree};
+use axum::{body::HttpBody, extract::State, http::Request, middleware::Next, response::Response};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+tokio::task_local! {
+    // The name of the type of the node the request is for, once a handler has read it.
+    static NODE_TYPE: RefCell<Option<String>>;
+}
+
+// What the service has responded with for requests for one type of resource.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TypeStatistics {
+    pub requests: u64,
+    // Requests answered with a 4xx or 5xx status.
+    pub errors: u64,
+    // Responses whose body length was known, and the total of those lengths.
+    pub sized_responses: u64,
+    pub body_bytes: u64,
+}
+
+impl TypeStatistics {
+    pub fn get_error_rate(&self) -> f64 {
+        match self.requests {
+            0 => 0.0,
+            requests => self.errors as f64 / requests as f64,
+        }
+    }
+
+    pub fn get_average_body_size(&self) -> f64 {
+        match self.sized_responses {
+            0 => 0.0,
+            sized => self.body_bytes as f64 / sized as f64,
+        }
+    }
+}
+
+// Handle to the statistics of the responses of a service, by the name of the type of the
+// resource or collection each request was for, such as ComputerSystem. Requests that fail
+// before a resource is read, such as those for unknown URIs or without credentials, are not
+// counted. Cloning gives another handle to the same statistics.
+#[derive(Clone, Default)]
+pub struct ResponseStatistics {
+    types: Arc<Mutex<HashMap<String, TypeStatistics>>>,
+}
+
+impl ResponseStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<TypeStatistics> {
+        self.types.lock().unwrap().get(type_name).copied()
+    }
+
+    // The statistics of every type requested, sorted by name.
+    pub fn get_all(&self) -> Vec<(String, TypeStatistics)> {
+        let mut all: Vec<(String, TypeStatistics)> = self
+            .types
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| (name.clone(), *stats))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    pub fn reset(&self) {
+        self.types.lock().unwrap().clear();
+    }
+
+    fn record(&self, type_name: String, response: &Response) {
+        let mut types = self.types.lock().unwrap();
+        let stats = types.entry(type_name).or_default();
+        stats.requests += 1;
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            stats.errors += 1;
+        }
+        if let Some(length) = response.body().size_hint().exact() {
+            stats.sized_responses += 1;
+            stats.body_bytes += length;
+        }
+    }
+}
+
+// Note the type of the node the request is for, if statistics are kept and none has been
+// noted yet. Types the tree does not declare are named by their schema URL.
+pub(crate) fn record_node_type(tree: &(dyn Tree + Send + Sync), meta: &NodeMeta) {
+    let _ = NODE_TYPE.try_with(|node_type| {
+        let mut node_type = node_type.borrow_mut();
+        if node_type.is_some() {
+            return;
+        }
+        let described_by = match &meta.described_by {
+            Some(described_by) => described_by,
+            None => return,
+        };
+        let resource_type = tree
+            .get_resource_types()
+            .iter()
+            .find(|t| t.described_by == *described_by)
+            .map(|t| t.name.clone());
+        let collection_type = || {
+            tree.get_collection_types()
+                .iter()
+                .find(|t| t.described_by == *described_by)
+                .map(|t| t.name.clone())
+        };
+        *node_type = Some(
+            resource_type
+                .or_else(collection_type)
+                .unwrap_or_else(|| described_by.to_string()),
+        );
+    });
+}
+
+pub(crate) async fn record_statistics<B>(
+    State(stats): State<ResponseStatistics>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (node_type, response) = NODE_TYPE
+        .scope(RefCell::new(None), async {
+            let response = next.run(request).await;
+            (NODE_TYPE.with(|node_type| node_type.take()), response)
+        })
+        .await;
+    if let Some(node_type) = node_type {
+        stats.record(node_type, &response);
+    }
+    response
+}
@@ -0,0 +1,551 @@
+use crate::{
+    base_registry, filter_event, get_property_value_message, include_origins_of_condition,
+    AppState, Clock, DeliveryOutcome, Error, EventFilter, EventFormatType, Node,
+    EVENT_FORMAT_TYPES,
+};
+use async_trait::async_trait;
+use etag::EntityTag;
+use http::Uri;
+use redfish_data::{
+    get_datetime_string, get_resource_odata_type, AllowedMethods, CollectionType, Event,
+    EventRecord, Message, PropertyType, ResourceSchemaVersion, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use strum::{Display, EnumString};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::task::AbortHandle;
+
+pub(crate) const SUBSCRIPTIONS_URI: &str = "/redfish/v1/EventService/Subscriptions";
+
+// Pushes what the service sends to subscribers to the Destination of their EventDestination,
+// such as by POSTing it there. The service has no HTTP client of its own, so clients can only
+// subscribe once the app gives it one with AppBuilder::event_sender().
+#[async_trait]
+pub trait EventSender: Send + Sync {
+    // Whether the destination accepted the payload, such as with a 2XX response.
+    async fn send(&self, destination: &str, payload: &Value) -> bool;
+}
+
+// What becomes of a subscription once every attempt to deliver an event to it has failed, as
+// its DeliveryRetryPolicy says.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq)]
+enum RetryPolicy {
+    // The subscription is deleted.
+    #[default]
+    TerminateAfterRetries,
+    // Nothing more is delivered to the subscription, which stays Disabled until it is deleted.
+    SuspendRetries,
+}
+
+// What a client asked for in creating an EventDestination.
+pub(crate) struct NewSubscription {
+    destination: String,
+    context: Option<String>,
+    retry_policy: RetryPolicy,
+    filter: EventFilter,
+    format: EventFormatType,
+    include_origins: bool,
+}
+
+impl NewSubscription {
+    // Refuse the body of a create, with a message for each property that is not valid, or that
+    // the service does not support.
+    pub(crate) fn parse(payload: Map<String, Value>) -> Result<Self, Error> {
+        let (payload, mut messages) =
+            crate::validate_properties(&get_event_destination_type(), payload);
+        let mut destination = None;
+        let mut context = None;
+        let mut retry_policy = RetryPolicy::default();
+        let mut filter = EventFilter::default();
+        let mut format = EventFormatType::Event;
+        let mut include_origins = false;
+        for (name, value) in payload {
+            let list = match name.as_str() {
+                "RegistryPrefixes" => Some(&mut filter.registry_prefixes),
+                "ResourceTypes" => Some(&mut filter.resource_types),
+                "OriginResources" => Some(&mut filter.origin_resources),
+                "MessageIds" => Some(&mut filter.message_ids),
+                _ => None,
+            };
+            if let Some(list) = list {
+                match get_strings(&value, name == "OriginResources") {
+                    Some(strings) => *list = strings,
+                    None => messages.push(get_property_value_message(
+                        "PropertyValueTypeError",
+                        &value,
+                        &name,
+                    )),
+                }
+                continue;
+            }
+            match (name.as_str(), value) {
+                ("Destination", Value::String(uri)) if is_http_uri(&uri) => destination = Some(uri),
+                ("Destination", value) => messages.push(get_property_value_message(
+                    "PropertyValueFormatError",
+                    &value,
+                    &name,
+                )),
+                ("Context", Value::String(value)) => context = Some(value),
+                // The type of the property only allows the policies there are.
+                ("DeliveryRetryPolicy", Value::String(policy)) => {
+                    retry_policy = policy.parse().unwrap()
+                }
+                // Likewise only the formats the service can send.
+                ("EventFormatType", Value::String(value)) => format = value.parse().unwrap(),
+                ("IncludeOriginOfCondition", Value::Bool(value)) => include_origins = value,
+                ("Protocol" | "SubscriptionType", _) => (),
+                _ => messages.push(
+                    Message::from_registry(
+                        base_registry(),
+                        "PropertyUnknown",
+                        ResourceSchemaVersion::new(1, 1, 2),
+                        vec![name.clone()],
+                        vec![format!("#/{}", name)],
+                    )
+                    .unwrap(),
+                ),
+            }
+        }
+        match (destination, messages.is_empty()) {
+            (Some(destination), true) => Ok(Self {
+                destination,
+                context,
+                retry_policy,
+                filter,
+                format,
+                include_origins,
+            }),
+            _ => Err(Error::InvalidProperties(messages)),
+        }
+    }
+}
+
+// The strings of an array of them, or of the URIs of an array of links, as OriginResources is.
+fn get_strings(value: &Value, links: bool) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|item| match links {
+            true => item.get("@odata.id")?.as_str().map(String::from),
+            false => item.as_str().map(String::from),
+        })
+        .collect()
+}
+
+// Events are pushed to the Redfish protocol's destinations, which are HTTP URIs.
+fn is_http_uri(uri: &str) -> bool {
+    match uri.parse::<Uri>() {
+        Ok(uri) => matches!(uri.scheme_str(), Some("http" | "https")) && uri.authority().is_some(),
+        Err(_) => false,
+    }
+}
+
+// How delivery to a subscriber has gone, for operators to tell when its destination is broken.
+#[derive(Clone, Default)]
+struct DeliveryStatus {
+    last_attempt: Option<SystemTime>,
+    last_delivered: Option<SystemTime>,
+    // Since the last event that was delivered.
+    consecutive_failures: u32,
+}
+
+struct Subscription {
+    id: String,
+    destination: String,
+    context: Option<String>,
+    retry_policy: RetryPolicy,
+    // Which events it is pushed.
+    filter: EventFilter,
+    format: EventFormatType,
+    // Whether the events it is pushed have the bodies of their origins, as the owner reads them
+    // when the events are pushed, in place of links to them.
+    include_origins: bool,
+    // The user who subscribed, as whom the origins of events are read for it.
+    owner: String,
+    // Whether delivery has stopped because its retries were exhausted.
+    suspended: bool,
+    status: DeliveryStatus,
+    // The task delivering to it, which is stopped when it is deleted.
+    delivery: AbortHandle,
+}
+
+impl Subscription {
+    fn get_uri(&self) -> String {
+        format!("{}/{}", SUBSCRIPTIONS_URI, self.id)
+    }
+
+    fn get_body(&self) -> Value {
+        let status = &self.status;
+        let health = match (self.suspended, status.consecutive_failures) {
+            (true, _) => "Critical",
+            (false, 0) => "OK",
+            (false, _) => "Warning",
+        };
+        let state = match self.suspended {
+            true => "Disabled",
+            false => "Enabled",
+        };
+        let mut body = json!({
+            "@odata.id": self.get_uri(),
+            "@odata.type": get_resource_odata_type(
+                "EventDestination",
+                &get_event_destination_type().version,
+                "EventDestination",
+            ),
+            "Id": self.id,
+            "Name": format!("Event Subscription {}", self.id),
+            "Destination": self.destination,
+            "Protocol": "Redfish",
+            "SubscriptionType": "RedfishEvent",
+            "EventFormatType": self.format.to_string(),
+            "IncludeOriginOfCondition": self.include_origins,
+            "DeliveryRetryPolicy": self.retry_policy.to_string(),
+            "RegistryPrefixes": self.filter.registry_prefixes,
+            "ResourceTypes": self.filter.resource_types,
+            "OriginResources": self
+                .filter
+                .origin_resources
+                .iter()
+                .map(|uri| json!({"@odata.id": uri}))
+                .collect::<Vec<Value>>(),
+            "MessageIds": self.filter.message_ids,
+            "Status": {"State": state, "Health": health},
+            // Redfish has no properties for how delivery has gone.
+            "Oem": {
+                "RedfishAxum": {
+                    "LastDeliveryAttempt": status.last_attempt.map(get_datetime_string),
+                    "LastDelivery": status.last_delivered.map(get_datetime_string),
+                    "ConsecutiveDeliveryFailures": status.consecutive_failures,
+                },
+            },
+        });
+        if let Some(context) = &self.context {
+            body["Context"] = json!(context);
+        }
+        body
+    }
+}
+
+#[derive(Default)]
+struct SubscriptionList {
+    subscriptions: Vec<Subscription>,
+    last_id: u64,
+}
+
+// Where a subscriber's events go.
+struct Target {
+    destination: String,
+    context: Option<String>,
+    retry_policy: RetryPolicy,
+    filter: EventFilter,
+    include_origins: bool,
+    owner: String,
+}
+
+// The EventDestinations clients have created, under the EventService, to have the events the
+// service sends pushed to them. Cloning gives another handle to the same subscriptions.
+#[derive(Clone)]
+pub(crate) struct Subscriptions {
+    list: Arc<Mutex<SubscriptionList>>,
+    sender: Arc<dyn EventSender>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Subscriptions {
+    // Delivery is timestamped by the service's clock.
+    pub(crate) fn new(sender: Arc<dyn EventSender>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            list: Arc::new(Mutex::new(SubscriptionList::default())),
+            sender,
+            clock,
+        }
+    }
+
+    // Add the subscription of the user, and start pushing to it the events the service sends
+    // from now on. Returns its Id.
+    pub(crate) fn create(
+        &self,
+        state: &AppState,
+        subscription: NewSubscription,
+        owner: &str,
+    ) -> String {
+        let source = match subscription.format {
+            EventFormatType::Event => Source::Events(state.events.subscribe()),
+            EventFormatType::MetricReport => {
+                Source::Reports(state.events.subscribe_metric_reports())
+            }
+        };
+        let mut list = self.list.lock().unwrap();
+        list.last_id += 1;
+        let id = list.last_id.to_string();
+        let delivery = tokio::spawn(deliver_events(state.clone(), id.clone(), source));
+        list.subscriptions.push(Subscription {
+            id: id.clone(),
+            destination: subscription.destination,
+            context: subscription.context,
+            retry_policy: subscription.retry_policy,
+            filter: subscription.filter,
+            format: subscription.format,
+            include_origins: subscription.include_origins,
+            owner: String::from(owner),
+            suspended: false,
+            status: DeliveryStatus::default(),
+            delivery: delivery.abort_handle(),
+        });
+        id
+    }
+
+    // Remove the subscription, stopping any delivery to it. Returns whether there was one.
+    pub(crate) fn remove(&self, id: &str) -> bool {
+        let mut list = self.list.lock().unwrap();
+        let index = match list.subscriptions.iter().position(|record| record.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        list.subscriptions.remove(index).delivery.abort();
+        true
+    }
+
+    pub(crate) fn get_collection_node(&self) -> SubscriptionNode {
+        let list = self.list.lock().unwrap();
+        let members: Vec<Value> = list
+            .subscriptions
+            .iter()
+            .map(|record| json!({"@odata.id": record.get_uri()}))
+            .collect();
+        let body = json!({
+            "@odata.id": SUBSCRIPTIONS_URI,
+            "@odata.type": "#EventDestinationCollection.EventDestinationCollection",
+            "Name": "Event Subscriptions",
+            "Members@odata.count": members.len(),
+            "Members": members,
+        });
+        SubscriptionNode {
+            uri: String::from(SUBSCRIPTIONS_URI),
+            body,
+            described_by: get_event_destination_collection_type().described_by,
+            allowed_methods: AllowedMethods {
+                delete: false,
+                get: true,
+                patch: false,
+                post: true,
+            },
+        }
+    }
+
+    pub(crate) fn get_node(&self, id: &str) -> Option<SubscriptionNode> {
+        let list = self.list.lock().unwrap();
+        let record = list.subscriptions.iter().find(|record| record.id == id)?;
+        Some(SubscriptionNode {
+            uri: record.get_uri(),
+            body: record.get_body(),
+            described_by: get_event_destination_type().described_by,
+            allowed_methods: AllowedMethods {
+                delete: true,
+                get: true,
+                patch: false,
+                post: false,
+            },
+        })
+    }
+
+    fn get_target(&self, id: &str) -> Option<Target> {
+        let list = self.list.lock().unwrap();
+        let record = list.subscriptions.iter().find(|record| record.id == id)?;
+        Some(Target {
+            destination: record.destination.clone(),
+            context: record.context.clone(),
+            retry_policy: record.retry_policy,
+            filter: record.filter.clone(),
+            include_origins: record.include_origins,
+            owner: record.owner.clone(),
+        })
+    }
+
+    fn suspend(&self, id: &str) {
+        let mut list = self.list.lock().unwrap();
+        if let Some(record) = list.subscriptions.iter_mut().find(|record| record.id == id) {
+            record.suspended = true;
+        }
+    }
+
+    fn record_attempt(&self, id: &str, delivered: bool) {
+        let now = self.clock.system_time();
+        let mut list = self.list.lock().unwrap();
+        let status = match list.subscriptions.iter_mut().find(|record| record.id == id) {
+            Some(record) => &mut record.status,
+            None => return,
+        };
+        status.last_attempt = Some(now);
+        match delivered {
+            true => {
+                status.last_delivered = Some(now);
+                status.consecutive_failures = 0;
+            }
+            false => status.consecutive_failures += 1,
+        }
+    }
+}
+
+// What a subscription is pushed, as its EventFormatType says.
+enum Source {
+    Events(Receiver<Event>),
+    Reports(Receiver<(String, Value)>),
+}
+
+enum Sent {
+    Event(Event),
+    Report(Value),
+}
+
+impl Source {
+    // The next that was sent, or None once nothing more can be. Those the subscriber fell too
+    // far behind on are skipped, as they are on the SSE stream.
+    async fn recv(&mut self) -> Option<Sent> {
+        loop {
+            let sent = match self {
+                Source::Events(events) => events.recv().await.map(Sent::Event),
+                Source::Reports(reports) => {
+                    reports.recv().await.map(|(_, report)| Sent::Report(report))
+                }
+            };
+            match sent {
+                Ok(sent) => return Some(sent),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+// Push each event to the subscriber, or only the records of it that its filter matches, or each
+// MetricReport, one at a time and retrying as the settings of the EventService say, until it is
+// removed, or its retries are exhausted. MetricReports are not filtered, as the filter is of
+// the records of events.
+async fn deliver_events(state: AppState, id: String, mut source: Source) {
+    let subscriptions = match &state.subscriptions {
+        Some(subscriptions) => subscriptions.clone(),
+        None => return,
+    };
+    while let Some(sent) = source.recv().await {
+        let target = match subscriptions.get_target(&id) {
+            Some(target) => target,
+            None => return,
+        };
+        let mut payload = match sent {
+            Sent::Event(mut event) => {
+                if !target.filter.is_empty() {
+                    let matches = |record: &EventRecord, resource_type: Option<&str>| {
+                        target.filter.matches(record, resource_type)
+                    };
+                    filter_event(&mut event, matches, &state, &target.owner).await;
+                    if event.get_records().is_empty() {
+                        continue;
+                    }
+                }
+                let mut payload = event.to_json();
+                if target.include_origins {
+                    include_origins_of_condition(&mut payload, &state, &target.owner).await;
+                }
+                payload
+            }
+            Sent::Report(Value::Object(report)) => report,
+            Sent::Report(_) => continue,
+        };
+        if let Some(context) = target.context {
+            payload.insert(String::from("Context"), Value::String(context));
+        }
+        let payload = Value::Object(payload);
+        let attempt = || async {
+            let delivered = subscriptions
+                .sender
+                .send(&target.destination, &payload)
+                .await;
+            subscriptions.record_attempt(&id, delivered);
+            delivered
+        };
+        if state.events.deliver(attempt).await == DeliveryOutcome::Delivered {
+            continue;
+        }
+        match target.retry_policy {
+            RetryPolicy::TerminateAfterRetries => {
+                subscriptions.remove(&id);
+                state
+                    .events
+                    .resource_removed(&format!("{}/{}", SUBSCRIPTIONS_URI, id));
+            }
+            RetryPolicy::SuspendRetries => subscriptions.suspend(&id),
+        }
+        return;
+    }
+}
+
+pub(crate) fn get_event_destination_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("EventDestination"),
+        ResourceSchemaVersion::new(1, 13, 0),
+    )
+    .with_property("Destination", PropertyType::String)
+    .with_property("Context", PropertyType::String)
+    .with_property(
+        "Protocol",
+        PropertyType::Enum(vec![String::from("Redfish")]),
+    )
+    .with_property(
+        "SubscriptionType",
+        PropertyType::Enum(vec![String::from("RedfishEvent")]),
+    )
+    .with_property(
+        "EventFormatType",
+        PropertyType::Enum(
+            EVENT_FORMAT_TYPES
+                .iter()
+                .map(|format| format.to_string())
+                .collect(),
+        ),
+    )
+    .with_property("IncludeOriginOfCondition", PropertyType::Boolean)
+    .with_property(
+        "DeliveryRetryPolicy",
+        PropertyType::Enum(vec![
+            RetryPolicy::TerminateAfterRetries.to_string(),
+            RetryPolicy::SuspendRetries.to_string(),
+        ]),
+    )
+}
+
+fn get_event_destination_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("EventDestinationCollection"))
+}
+
+// The collection of subscriptions, or one of them, as of when it was made.
+pub(crate) struct SubscriptionNode {
+    uri: String,
+    body: Value,
+    described_by: SchemaUrl,
+    allowed_methods: AllowedMethods,
+}
+
+impl Node for SubscriptionNode {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        self.allowed_methods
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
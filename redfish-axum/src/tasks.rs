@@ -0,0 +1,545 @@
+use crate::clock::SystemClock;
+use crate::{
+    current_user, get_task_started_response, has_preference, Clock, EventService, LoginResponse,
+    Node, RequestUser,
+};
+use axum::{
+    body::HttpBody,
+    extract::State,
+    http::{header, HeaderName, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use etag::EntityTag;
+use redfish_data::{
+    get_datetime_string, get_resource_odata_type, AllowedMethods, CollectionType, Health,
+    ResourceSchemaVersion, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::OwnedSemaphorePermit;
+
+pub(crate) const TASK_SERVICE_URI: &str = "/redfish/v1/TaskService";
+pub(crate) const TASKS_URI: &str = "/redfish/v1/TaskService/Tasks";
+pub(crate) const TASK_MONITORS_URI: &str = "/redfish/v1/TaskService/TaskMonitors";
+
+// How many completed tasks are kept by default, beyond which the oldest are removed.
+const MAX_COMPLETED_TASKS: usize = 100;
+
+// Which URI the Location of a 202 Accepted response gives the client to poll. Either way, its
+// Content-Location is the task, which is what its body is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TaskLocation {
+    // The task monitor, as Redfish says, which responds as the operation would have once the
+    // task is complete.
+    #[default]
+    Monitor,
+    // The task itself, for clients that poll its TaskState instead.
+    Task,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    Running,
+    Completed,
+    // The task completed critically, such as by failing.
+    Exception,
+}
+
+struct TaskRecord {
+    id: String,
+    name: String,
+    state: TaskState,
+    health: Health,
+    percent_complete: u32,
+    start_time: SystemTime,
+    end_time: Option<SystemTime>,
+    // What the task monitor responds with once the task is complete, if not the task itself.
+    response: Option<Value>,
+    // The resource the operation created, which the task monitor gives as its Location once
+    // the task is complete.
+    created: Option<String>,
+    // Held while the task runs, such as for a create that counts against the limit of its
+    // collection until it is done.
+    permit: Option<OwnedSemaphorePermit>,
+    // The user whose request started the task, if one did.
+    owner: Option<String>,
+}
+
+impl TaskRecord {
+    fn get_body(&self) -> Value {
+        let mut body = json!({
+            "@odata.id": format!("{}/{}", TASKS_URI, self.id),
+            "@odata.type": get_resource_odata_type("Task", &get_task_type().version, "Task"),
+            "Id": self.id,
+            "Name": self.name,
+            "TaskState": format!("{:?}", self.state),
+            "TaskStatus": self.health.to_string(),
+            "PercentComplete": self.percent_complete,
+            "StartTime": get_datetime_string(self.start_time),
+            "TaskMonitor": format!("{}/{}", TASK_MONITORS_URI, self.id),
+        });
+        if let Some(end_time) = self.end_time {
+            body["EndTime"] = json!(get_datetime_string(end_time));
+        }
+        body
+    }
+
+    // Tasks can be read by the users who started them, and by those who may configure the
+    // manager. Those not started by a user's request can be read by anyone.
+    fn may_be_read_by(&self, reader: Option<&RequestUser>) -> bool {
+        let owner = match &self.owner {
+            Some(owner) => owner,
+            None => return true,
+        };
+        reader.is_some_and(|reader| {
+            &reader.username == owner || reader.has_privilege("ConfigureManager")
+        })
+    }
+}
+
+struct Tasks {
+    tasks: Vec<TaskRecord>,
+    last_id: u64,
+    events: Option<EventService>,
+    // The service's, once the TaskService is given to one.
+    clock: Arc<dyn Clock>,
+    location: TaskLocation,
+    max_completed: usize,
+    completed_ttl: Option<Duration>,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            last_id: 0,
+            events: None,
+            clock: Arc::new(SystemClock),
+            location: TaskLocation::default(),
+            max_completed: MAX_COMPLETED_TASKS,
+            completed_ttl: None,
+        }
+    }
+}
+
+impl Tasks {
+    fn now(&self) -> SystemTime {
+        self.clock.system_time()
+    }
+
+    // Remove the completed tasks that have been kept for longer than the TTL, if there is one,
+    // and then the oldest of those over the maximum. Running tasks are always kept.
+    fn remove_expired(&mut self) {
+        let now = self.now();
+        if let Some(ttl) = self.completed_ttl {
+            self.tasks.retain(|record| match record.end_time {
+                Some(end_time) => now.duration_since(end_time).map_or(true, |age| age < ttl),
+                None => true,
+            });
+        }
+        let mut completed: Vec<(SystemTime, String)> = self
+            .tasks
+            .iter()
+            .filter_map(|record| Some((record.end_time?, record.id.clone())))
+            .collect();
+        if completed.len() <= self.max_completed {
+            return;
+        }
+        completed.sort();
+        let excess = completed.len() - self.max_completed;
+        let removed: Vec<String> = completed
+            .into_iter()
+            .take(excess)
+            .map(|(_, id)| id)
+            .collect();
+        self.tasks.retain(|record| !removed.contains(&record.id));
+    }
+
+    fn find(&mut self, id: &str) -> Option<&TaskRecord> {
+        self.remove_expired();
+        self.tasks.iter().find(|record| record.id == id)
+    }
+}
+
+// Handle to the tasks of long-running operations, which the service serves under the
+// TaskService. Trees start a task for an operation that takes too long to respond to, and
+// return Outcome::Async with it, so that the client is given its task monitor to poll.
+// The 100 most recently completed tasks are kept by default, and running ones until they
+// complete. Cloning gives another handle to the same tasks.
+#[derive(Clone, Default)]
+pub struct TaskService {
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl TaskService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keep at most this many completed tasks, removing the oldest beyond it.
+    pub fn with_max_completed_tasks(self, max_completed: usize) -> Self {
+        self.tasks.lock().unwrap().max_completed = max_completed;
+        self
+    }
+
+    // Remove completed tasks once this long has passed since they completed, by the service's
+    // clock. By default they are kept until there are too many.
+    pub fn with_completed_task_ttl(self, ttl: Duration) -> Self {
+        self.tasks.lock().unwrap().completed_ttl = Some(ttl);
+        self
+    }
+
+    // Start a task, named for its operation, such as Firmware Update. It runs until completed.
+    // Started while handling a request, it is the task of the user the request is from.
+    pub fn start(&self, name: &str) -> Task {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.remove_expired();
+        tasks.last_id += 1;
+        let id = tasks.last_id.to_string();
+        let start_time = tasks.now();
+        tasks.tasks.push(TaskRecord {
+            id: id.clone(),
+            name: String::from(name),
+            state: TaskState::Running,
+            health: Health::OK,
+            percent_complete: 0,
+            start_time,
+            end_time: None,
+            response: None,
+            created: None,
+            permit: None,
+            owner: current_user().map(|user| user.username),
+        });
+        let task = Task {
+            id,
+            service: self.clone(),
+        };
+        if let Some(events) = &tasks.events {
+            events.task_started(&task.get_uri());
+        }
+        task
+    }
+
+    // The service sends the events of tasks to the subscribers of its EventService.
+    pub(crate) fn set_event_service(&self, events: EventService) {
+        self.tasks.lock().unwrap().events = Some(events);
+    }
+
+    // Tasks are timestamped by the service's clock.
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.tasks.lock().unwrap().clock = clock;
+    }
+
+    pub(crate) fn set_location(&self, location: TaskLocation) {
+        self.tasks.lock().unwrap().location = location;
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        update: impl FnOnce(&mut TaskRecord, Option<&EventService>, SystemTime),
+    ) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let now = tasks.now();
+        let tasks = &mut *tasks;
+        if let Some(record) = tasks.tasks.iter_mut().find(|record| record.id == id) {
+            update(record, tasks.events.as_ref(), now);
+        }
+    }
+
+    pub(crate) fn get_service_node(&self) -> TaskServiceNode {
+        let resource_type = get_task_service_type();
+        let body = json!({
+            "@odata.id": TASK_SERVICE_URI,
+            "@odata.type": get_resource_odata_type("TaskService", &resource_type.version, "TaskService"),
+            "Id": "TaskService",
+            "Name": "Task Service",
+            "ServiceEnabled": true,
+            "Tasks": {"@odata.id": TASKS_URI},
+            "Status": {"State": "Enabled", "Health": "OK"},
+        });
+        TaskServiceNode::new(TASK_SERVICE_URI, body, resource_type.described_by)
+    }
+
+    // Only the tasks the reader may read are members.
+    pub(crate) fn get_collection_node(&self, reader: Option<&RequestUser>) -> TaskServiceNode {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.remove_expired();
+        let members: Vec<Value> = tasks
+            .tasks
+            .iter()
+            .filter(|record| record.may_be_read_by(reader))
+            .map(|record| json!({"@odata.id": format!("{}/{}", TASKS_URI, record.id)}))
+            .collect();
+        let body = json!({
+            "@odata.id": TASKS_URI,
+            "@odata.type": "#TaskCollection.TaskCollection",
+            "Name": "Task Collection",
+            "Members@odata.count": members.len(),
+            "Members": members,
+        });
+        TaskServiceNode::new(TASKS_URI, body, get_task_collection_type().described_by)
+    }
+
+    pub(crate) fn get_task_node(&self, id: &str) -> Option<TaskServiceNode> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let record = tasks.find(id)?;
+        let uri = format!("{}/{}", TASKS_URI, id);
+        Some(TaskServiceNode::new(
+            &uri,
+            record.get_body(),
+            get_task_type().described_by,
+        ))
+    }
+
+    // What the task monitor of the task gives: the task while it runs, and once it is complete
+    // the response it completed with, or else the task.
+    pub(crate) fn get_monitor_response(&self, id: &str) -> Option<MonitorResponse> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let record = tasks.find(id)?;
+        let task_uri = format!("{}/{}", TASKS_URI, record.id);
+        let (body, task_uri) = match (&record.state, &record.response) {
+            (TaskState::Running, _) | (_, None) => (record.get_body(), Some(task_uri)),
+            (_, Some(response)) => (response.clone(), None),
+        };
+        let created = match record.state {
+            TaskState::Completed => record.created.clone(),
+            _ => None,
+        };
+        Some(MonitorResponse {
+            state: record.state,
+            body,
+            task_uri,
+            created,
+        })
+    }
+
+    // Whether the reader may read the task and its monitor, if there is the task.
+    pub(crate) fn may_read(&self, id: &str, reader: Option<&RequestUser>) -> Option<bool> {
+        let mut tasks = self.tasks.lock().unwrap();
+        Some(tasks.find(id)?.may_be_read_by(reader))
+    }
+}
+
+// What the task monitor of a task responds with.
+pub(crate) struct MonitorResponse {
+    pub(crate) state: TaskState,
+    pub(crate) body: Value,
+    // The URI of the task, if the body is the task.
+    pub(crate) task_uri: Option<String>,
+    // The resource the operation created, once the task has completed.
+    pub(crate) created: Option<String>,
+}
+
+// Handle to one task, for the operation it tracks to update as it progresses.
+#[derive(Clone)]
+pub struct Task {
+    id: String,
+    service: TaskService,
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task").field("id", &self.id).finish()
+    }
+}
+
+impl Task {
+    pub fn get_uri(&self) -> String {
+        format!("{}/{}", TASKS_URI, self.id)
+    }
+
+    pub fn get_monitor_uri(&self) -> String {
+        format!("{}/{}", TASK_MONITORS_URI, self.id)
+    }
+
+    // The URI the client is given to poll, as AppBuilder::task_location() says.
+    pub(crate) fn get_poll_uri(&self) -> String {
+        match self.service.tasks.lock().unwrap().location {
+            TaskLocation::Monitor => self.get_monitor_uri(),
+            TaskLocation::Task => self.get_uri(),
+        }
+    }
+
+    // Say that the operation created the resource at the URI, such as the member a create
+    // added to a collection. Once the task has completed, its monitor responds with 201
+    // Created and the URI as the Location, as the operation would have.
+    pub fn set_created_resource(&self, uri: &str) {
+        let uri = String::from(uri);
+        self.service.update(&self.id, |record, _, _| {
+            record.created = Some(uri);
+        });
+    }
+
+    // Hold the permit until the task completes, or drop it now if it already has.
+    pub(crate) fn hold_until_complete(&self, permit: OwnedSemaphorePermit) {
+        self.service.update(&self.id, |record, _, _| {
+            if record.state == TaskState::Running {
+                record.permit = Some(permit);
+            }
+        });
+    }
+
+    pub(crate) fn get_body(&self) -> Value {
+        match self.service.get_task_node(&self.id) {
+            Some(node) => node.body,
+            None => Value::Null,
+        }
+    }
+
+    // Percentages over 100 are taken as 100. Has no effect once the task is complete.
+    pub fn set_percent_complete(&self, percent_complete: u32) {
+        let uri = self.get_uri();
+        self.service.update(&self.id, |record, events, _| {
+            if record.state != TaskState::Running {
+                return;
+            }
+            record.percent_complete = percent_complete.min(100);
+            if let Some(events) = events {
+                events.task_progress_changed(&uri, record.percent_complete);
+            }
+        });
+    }
+
+    // Complete the task, with the health of its outcome. A task that completed critically is in
+    // the Exception state. The response, if any, is what the task monitor then gives, such as
+    // the body the operation would have responded with had it not taken so long.
+    pub fn complete(&self, health: Health, response: Option<Value>) {
+        let uri = self.get_uri();
+        self.service.update(&self.id, |record, events, now| {
+            if record.state != TaskState::Running {
+                return;
+            }
+            record.state = match health {
+                Health::Critical => TaskState::Exception,
+                _ => TaskState::Completed,
+            };
+            record.percent_complete = 100;
+            record.end_time = Some(now);
+            record.permit = None;
+            record.response = response;
+            if let Some(events) = events {
+                events.task_completed(&uri, health.clone());
+            }
+            record.health = health;
+        });
+    }
+}
+
+pub(crate) fn get_task_service_type() -> ResourceType {
+    ResourceType::new_dmtf(
+        String::from("TaskService"),
+        ResourceSchemaVersion::new(1, 2, 0),
+    )
+}
+
+pub(crate) fn get_task_type() -> ResourceType {
+    ResourceType::new_dmtf(String::from("Task"), ResourceSchemaVersion::new(1, 7, 0))
+}
+
+pub(crate) fn get_task_collection_type() -> CollectionType {
+    CollectionType::new_dmtf_v1(String::from("TaskCollection"))
+}
+
+// The TaskService, its collection of tasks, or one of them, as of when it was made.
+pub(crate) struct TaskServiceNode {
+    uri: String,
+    body: Value,
+    described_by: SchemaUrl,
+}
+
+impl TaskServiceNode {
+    fn new(uri: &str, body: Value, described_by: SchemaUrl) -> Self {
+        Self {
+            uri: String::from(uri),
+            body,
+            described_by,
+        }
+    }
+}
+
+impl Node for TaskServiceNode {
+    fn get_uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn get_body(&self) -> Value {
+        self.body.clone()
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        }
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
+
+// Carry out POST, PATCH and DELETE requests that prefer respond-async as tasks, by responding
+// to those that succeed with 202 Accepted and a task that has completed with the response. The
+// task monitor gives the body of the response, and the Location of what it created, though not
+// its other headers. Logins are not carried out as tasks, as the client would not get the token
+// of the session. The operation is not made to run in the background: the 202 is only sent
+// once it is done, so trees that take long should still start tasks of their own and return
+// Outcome::Async. If the body of the response cannot be read in full, the task is completed
+// critically, without it.
+pub(crate) async fn respond_async<B>(
+    State(tasks): State<TaskService>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let method = request.method().clone();
+    let uri = String::from(request.uri().path());
+    let applies = matches!(method, Method::POST | Method::PATCH | Method::DELETE)
+        && has_preference(request.headers(), "respond-async");
+    let mut response = next.run(request).await;
+    let is_login = response.extensions().get::<LoginResponse>().is_some();
+    if !applies || is_login || !response.status().is_success() {
+        return response;
+    }
+    if response.status() != StatusCode::ACCEPTED {
+        let mut data = Vec::new();
+        let mut is_complete = true;
+        let body = response.body_mut();
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) => data.extend_from_slice(&chunk),
+                Err(_) => {
+                    is_complete = false;
+                    break;
+                }
+            }
+        }
+        let task = tasks.start(&format!("{} {}", method, uri));
+        if !is_complete {
+            task.complete(Health::Critical, None);
+        } else {
+            if response.status() == StatusCode::CREATED {
+                if let Some(location) = response.headers().get(header::LOCATION) {
+                    task.set_created_resource(location.to_str().unwrap_or_default());
+                }
+            }
+            task.complete(Health::OK, serde_json::from_slice(&data).ok());
+        }
+        response = get_task_started_response(&task);
+    }
+    // Also when the tree deferred the operation to a task itself.
+    response.headers_mut().insert(
+        HeaderName::from_static("preference-applied"),
+        HeaderValue::from_static("respond-async"),
+    );
+    response
+}
@@ -0,0 +1,355 @@
+use crate::registries::trigger_event_registry;
+use crate::{EventService, Node};
+use etag::EntityTag;
+use redfish_data::{
+    get_resource_odata_type, get_uri_id, AllowedMethods, Health, Message, ResourceSchemaVersion,
+    ResourceType, SchemaUrl,
+};
+use serde_json::{json, Map, Value};
+use strum::Display;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum TriggerAction {
+    RedfishEvent,
+    RedfishMetricReport,
+}
+
+// Which direction a reading has to cross a threshold in for it to fire.
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum ThresholdActivation {
+    Increasing,
+    Decreasing,
+    Either,
+}
+
+#[derive(Clone, Debug)]
+pub struct Threshold {
+    pub reading: f64,
+    pub activation: ThresholdActivation,
+}
+
+impl Threshold {
+    pub fn new(reading: f64, activation: ThresholdActivation) -> Self {
+        Self {
+            reading,
+            activation,
+        }
+    }
+
+    fn is_crossed(&self, previous: f64, reading: f64) -> bool {
+        let increasing = previous < self.reading && reading >= self.reading;
+        let decreasing = previous > self.reading && reading <= self.reading;
+        match self.activation {
+            ThresholdActivation::Increasing => increasing,
+            ThresholdActivation::Decreasing => decreasing,
+            ThresholdActivation::Either => increasing || decreasing,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "Reading": self.reading,
+            "Activation": self.activation.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NumericThresholds {
+    pub upper_critical: Option<Threshold>,
+    pub upper_warning: Option<Threshold>,
+    pub lower_warning: Option<Threshold>,
+    pub lower_critical: Option<Threshold>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiscreteTrigger {
+    pub name: String,
+    pub value: String,
+    pub severity: Health,
+}
+
+impl DiscreteTrigger {
+    pub fn new(name: &str, value: &str, severity: Health) -> Self {
+        Self {
+            name: String::from(name),
+            value: String::from(value),
+            severity,
+        }
+    }
+}
+
+enum TriggerCondition {
+    Numeric(NumericThresholds),
+    Discrete(Vec<DiscreteTrigger>),
+}
+
+// A Triggers resource, which watches the readings of metric properties and fires when they
+// cross a numeric threshold or match a discrete value.
+// The owner of the trigger feeds it readings with update_numeric() or update_discrete().
+pub struct Trigger {
+    uri: String,
+    resource_type: ResourceType,
+    name: String,
+    condition: TriggerCondition,
+    actions: Vec<TriggerAction>,
+    metric_properties: Vec<String>,
+    metric_report_definitions: Vec<String>,
+    last_reading: Option<f64>,
+    last_value: Option<String>,
+}
+
+impl Trigger {
+    pub fn numeric(
+        uri: &str,
+        name: &str,
+        thresholds: NumericThresholds,
+        actions: Vec<TriggerAction>,
+    ) -> Self {
+        Self::new(uri, name, TriggerCondition::Numeric(thresholds), actions)
+    }
+
+    pub fn discrete(
+        uri: &str,
+        name: &str,
+        triggers: Vec<DiscreteTrigger>,
+        actions: Vec<TriggerAction>,
+    ) -> Self {
+        Self::new(uri, name, TriggerCondition::Discrete(triggers), actions)
+    }
+
+    fn new(
+        uri: &str,
+        name: &str,
+        condition: TriggerCondition,
+        actions: Vec<TriggerAction>,
+    ) -> Self {
+        Self {
+            uri: String::from(uri),
+            resource_type: ResourceType::new_dmtf(
+                String::from("Triggers"),
+                ResourceSchemaVersion::new(1, 3, 0),
+            ),
+            name: String::from(name),
+            condition,
+            actions,
+            metric_properties: Vec::new(),
+            metric_report_definitions: Vec::new(),
+            last_reading: None,
+            last_value: None,
+        }
+    }
+
+    // URIs of the properties whose readings this trigger watches.
+    pub fn with_metric_properties(mut self, metric_properties: Vec<String>) -> Self {
+        self.metric_properties = metric_properties;
+        self
+    }
+
+    // URIs of the MetricReportDefinitions to generate reports for when this trigger fires.
+    pub fn with_metric_report_definitions(mut self, definitions: Vec<String>) -> Self {
+        self.metric_report_definitions = definitions;
+        self
+    }
+
+    pub fn get_resource_type(&self) -> &ResourceType {
+        &self.resource_type
+    }
+
+    // Feed a new numeric reading to the trigger. If it crosses a threshold, the trigger fires.
+    // Critical thresholds are checked before warning ones, and only one fires per reading.
+    // Returns the MetricReportDefinitions the caller should generate reports for.
+    pub fn update_numeric(&mut self, reading: f64, events: &EventService) -> &[String] {
+        let thresholds = match &self.condition {
+            TriggerCondition::Numeric(thresholds) => thresholds,
+            TriggerCondition::Discrete(_) => return &[],
+        };
+        // With no earlier reading, assume it was on the normal side of each threshold.
+        let candidates = [
+            (
+                "UpperCriticalThresholdCrossed",
+                &thresholds.upper_critical,
+                true,
+            ),
+            (
+                "LowerCriticalThresholdCrossed",
+                &thresholds.lower_critical,
+                false,
+            ),
+            (
+                "UpperWarningThresholdCrossed",
+                &thresholds.upper_warning,
+                true,
+            ),
+            (
+                "LowerWarningThresholdCrossed",
+                &thresholds.lower_warning,
+                false,
+            ),
+        ];
+        let mut crossed = None;
+        for (key, threshold, upper) in candidates {
+            if let Some(threshold) = threshold {
+                let previous = self.last_reading.unwrap_or(match upper {
+                    true => f64::NEG_INFINITY,
+                    false => f64::INFINITY,
+                });
+                if threshold.is_crossed(previous, reading) {
+                    crossed = Some((key, threshold.reading));
+                    break;
+                }
+            }
+        }
+        self.last_reading = Some(reading);
+        match crossed {
+            Some((key, threshold)) => {
+                let args = vec![
+                    get_uri_id(&self.uri),
+                    reading.to_string(),
+                    threshold.to_string(),
+                ];
+                self.fire(key, args, None, events)
+            }
+            None => &[],
+        }
+    }
+
+    // Feed a new discrete value to the trigger. If it changed to the value of one of the
+    // discrete triggers, the trigger fires with that discrete trigger's severity.
+    // Returns the MetricReportDefinitions the caller should generate reports for.
+    pub fn update_discrete(&mut self, value: &str, events: &EventService) -> &[String] {
+        let triggers = match &self.condition {
+            TriggerCondition::Discrete(triggers) => triggers,
+            TriggerCondition::Numeric(_) => return &[],
+        };
+        if self.last_value.as_deref() == Some(value) {
+            return &[];
+        }
+        self.last_value = Some(String::from(value));
+        match triggers.iter().find(|trigger| trigger.value == value) {
+            Some(trigger) => {
+                let args = vec![
+                    get_uri_id(&self.uri),
+                    String::from(value),
+                    trigger.name.clone(),
+                ];
+                let severity = trigger.severity.clone();
+                self.fire("DiscreteTriggerMatched", args, Some(severity), events)
+            }
+            None => &[],
+        }
+    }
+
+    fn fire(
+        &self,
+        key: &str,
+        args: Vec<String>,
+        severity: Option<Health>,
+        events: &EventService,
+    ) -> &[String] {
+        if self.actions.contains(&TriggerAction::RedfishEvent) {
+            let mut message = Message::from_registry(
+                trigger_event_registry(),
+                key,
+                ResourceSchemaVersion::new(1, 1, 2),
+                args,
+                Vec::new(),
+            )
+            .unwrap();
+            if let Some(severity) = severity {
+                message = message.with_severity(severity);
+            }
+            events.send(message, Some(self.uri.as_str()));
+        }
+        if self.actions.contains(&TriggerAction::RedfishMetricReport) {
+            return &self.metric_report_definitions;
+        }
+        &[]
+    }
+}
+
+impl Node for Trigger {
+    fn get_uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    fn get_body(&self) -> Value {
+        let mut body = Map::new();
+        body.insert(String::from("@odata.id"), json!(self.uri));
+        body.insert(
+            String::from("@odata.type"),
+            json!(get_resource_odata_type(
+                "Triggers",
+                &self.resource_type.version,
+                "Triggers"
+            )),
+        );
+        body.insert(String::from("Id"), json!(get_uri_id(&self.uri)));
+        body.insert(String::from("Name"), json!(self.name));
+        let actions: Vec<String> = self.actions.iter().map(|a| a.to_string()).collect();
+        body.insert(String::from("TriggerActions"), json!(actions));
+        match &self.condition {
+            TriggerCondition::Numeric(thresholds) => {
+                body.insert(String::from("MetricType"), json!("Numeric"));
+                let mut numeric = Map::new();
+                for (name, threshold) in [
+                    ("UpperCritical", &thresholds.upper_critical),
+                    ("UpperWarning", &thresholds.upper_warning),
+                    ("LowerWarning", &thresholds.lower_warning),
+                    ("LowerCritical", &thresholds.lower_critical),
+                ] {
+                    if let Some(threshold) = threshold {
+                        numeric.insert(String::from(name), threshold.to_json());
+                    }
+                }
+                body.insert(String::from("NumericThresholds"), Value::Object(numeric));
+            }
+            TriggerCondition::Discrete(triggers) => {
+                body.insert(String::from("MetricType"), json!("Discrete"));
+                body.insert(String::from("DiscreteTriggerCondition"), json!("Specified"));
+                let triggers: Vec<Value> = triggers
+                    .iter()
+                    .map(|trigger| {
+                        json!({
+                            "Name": trigger.name,
+                            "Value": trigger.value,
+                            "Severity": trigger.severity.to_string(),
+                        })
+                    })
+                    .collect();
+                body.insert(String::from("DiscreteTriggers"), json!(triggers));
+            }
+        }
+        body.insert(
+            String::from("MetricProperties"),
+            json!(self.metric_properties),
+        );
+        let definitions: Vec<Value> = self
+            .metric_report_definitions
+            .iter()
+            .map(|uri| json!({ "@odata.id": uri }))
+            .collect();
+        body.insert(
+            String::from("Links"),
+            json!({ "MetricReportDefinitions": definitions }),
+        );
+        Value::Object(body)
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        }
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        Some(&self.resource_type.described_by)
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{CompactBody, Error, Node, NodeRef, Outcome, Tree};
+use async_trait::async_trait;
+use etag::EntityTag;
+use redfish_data::{
+    get_resource_odata_type, get_uri_id, AllowedMethods, CollectionType, ResourceType, SchemaUrl,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+enum StaticNodeKind {
+    Resource(ResourceType),
+    // Members, and the type of the resources a POST creates, if POST is allowed.
+    Collection(CollectionType, Vec<String>, Option<ResourceType>),
+}
+
+// A resource or collection of a StaticTree, whose body is held in memory.
+pub(crate) struct StaticNode {
+    uri: String,
+    name: String,
+    kind: StaticNodeKind,
+    allowed_methods: AllowedMethods,
+    // Big trees hold many bodies, so they are kept compact.
+    body: CompactBody,
+}
+
+impl StaticNode {
+    pub(crate) fn resource(
+        uri: &str,
+        name: &str,
+        resource_type: ResourceType,
+        allowed_methods: AllowedMethods,
+        body: Map<String, Value>,
+    ) -> Self {
+        Self {
+            uri: String::from(uri),
+            name: String::from(name),
+            kind: StaticNodeKind::Resource(resource_type),
+            allowed_methods,
+            body: CompactBody::from(body),
+        }
+    }
+
+    pub(crate) fn collection(
+        uri: &str,
+        name: &str,
+        collection_type: CollectionType,
+        members: Vec<String>,
+        member_type: Option<ResourceType>,
+    ) -> Self {
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: member_type.is_some(),
+        };
+        Self {
+            uri: String::from(uri),
+            name: String::from(name),
+            kind: StaticNodeKind::Collection(collection_type, members, member_type),
+            allowed_methods,
+            body: CompactBody::new(),
+        }
+    }
+}
+
+impl Node for StaticNode {
+    fn get_uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    fn get_body(&self) -> Value {
+        let mut body = self.body.to_map();
+        body.insert(String::from("@odata.id"), json!(self.uri));
+        body.insert(String::from("Name"), json!(self.name));
+        match &self.kind {
+            StaticNodeKind::Resource(resource_type) => {
+                body.insert(
+                    String::from("@odata.type"),
+                    json!(get_resource_odata_type(
+                        &resource_type.name,
+                        &resource_type.version,
+                        &resource_type.name
+                    )),
+                );
+                body.insert(String::from("Id"), json!(get_uri_id(&self.uri)));
+            }
+            StaticNodeKind::Collection(collection_type, members, _) => {
+                body.insert(
+                    String::from("@odata.type"),
+                    json!(format!(
+                        "#{}.{}",
+                        collection_type.name, collection_type.name
+                    )),
+                );
+                let members: Vec<Value> = members
+                    .iter()
+                    .map(|member| json!({ "@odata.id": member }))
+                    .collect();
+                body.insert(String::from("Members@odata.count"), json!(members.len()));
+                body.insert(String::from("Members"), Value::Array(members));
+            }
+        }
+        Value::Object(body)
+    }
+
+    fn get_allowed_methods(&self) -> AllowedMethods {
+        self.allowed_methods
+    }
+
+    fn described_by(&self) -> Option<&SchemaUrl> {
+        match &self.kind {
+            StaticNodeKind::Resource(resource_type) => Some(&resource_type.described_by),
+            StaticNodeKind::Collection(collection_type, _, _) => {
+                Some(&collection_type.described_by)
+            }
+        }
+    }
+
+    fn get_etag(&self) -> Option<EntityTag> {
+        None
+    }
+
+    fn get_resource_type(&self) -> Option<&ResourceType> {
+        match &self.kind {
+            StaticNodeKind::Resource(resource_type) => Some(resource_type),
+            StaticNodeKind::Collection(..) => None,
+        }
+    }
+}
+
+// Decides whether a node of the tree is a member of a virtual collection.
+type MemberQuery = Box<dyn Fn(&dyn Node) -> bool + Send + Sync>;
+
+// A collection whose members are found by querying the tree each time it is read,
+// so that they cannot drift from the resources the tree actually has.
+struct VirtualCollection {
+    name: String,
+    collection_type: CollectionType,
+    query: MemberQuery,
+}
+
+// The resources of a StaticTree, for its behaviors to read and change.
+pub struct StaticResources<'a> {
+    nodes: &'a mut HashMap<String, StaticNode>,
+}
+
+impl StaticResources<'_> {
+    // A top-level property of the resource at the URI, as it was given or last set.
+    pub fn get(&self, uri: &str, property: &str) -> Option<Value> {
+        self.nodes.get(uri)?.body.get(property)
+    }
+
+    // Set a top-level property of the resource at the URI, as PATCH would.
+    pub fn set(&mut self, uri: &str, property: &str, value: Value) -> Result<(), Error> {
+        let node = self.nodes.get_mut(uri).ok_or(Error::NotFound)?;
+        node.body.insert(property, value);
+        Ok(())
+    }
+}
+
+// Carries out a PATCH in place of the tree, given the URI and the request body.
+type PatchBehavior =
+    Box<dyn Fn(&mut StaticResources, &str, &Map<String, Value>) -> Result<(), Error> + Send + Sync>;
+
+// Carries out an action, given its parameters, and returns the body to respond with, if any.
+type ActionBehavior = Box<
+    dyn Fn(&mut StaticResources, &Map<String, Value>) -> Result<Option<Value>, Error> + Send + Sync,
+>;
+
+// Builds a StaticTree, a tree whose resources are plain JSON held in memory.
+// PATCH replaces top-level properties, POST to a collection adds a member with the
+// properties given, and DELETE removes a resource from the tree and its collection.
+// It is a starting point for new services, before real backends are written. Behaviors
+// given for some URIs make it a simulator of sorts, whose actions and PATCH requests have
+// effects, such as on related resources.
+#[derive(Default)]
+pub struct TreeBuilder {
+    nodes: Vec<StaticNode>,
+    virtual_collections: Vec<(String, VirtualCollection)>,
+    patch_behaviors: HashMap<String, PatchBehavior>,
+    action_behaviors: HashMap<(String, String), ActionBehavior>,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resource(
+        mut self,
+        uri: &str,
+        name: &str,
+        resource_type: ResourceType,
+        allowed_methods: AllowedMethods,
+        body: Value,
+    ) -> Self {
+        self.nodes.push(StaticNode::resource(
+            uri,
+            name,
+            resource_type,
+            allowed_methods,
+            body.as_object().cloned().unwrap_or_default(),
+        ));
+        self
+    }
+
+    // Members are the URIs of the collection's resources. If member_type is Some,
+    // clients may POST to the collection to create more, of that type.
+    pub fn collection(
+        mut self,
+        uri: &str,
+        name: &str,
+        collection_type: CollectionType,
+        members: Vec<String>,
+        member_type: Option<ResourceType>,
+    ) -> Self {
+        self.nodes.push(StaticNode::collection(
+            uri,
+            name,
+            collection_type,
+            members,
+            member_type,
+        ));
+        self
+    }
+
+    // A read-only collection whose members are the nodes the query returns true for,
+    // sorted by URI. For example, all sessions:
+    // |node| node.get_resource_type().is_some_and(|t| t.name == "Session")
+    pub fn virtual_collection(
+        mut self,
+        uri: &str,
+        name: &str,
+        collection_type: CollectionType,
+        query: impl Fn(&dyn Node) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.virtual_collections.push((
+            String::from(uri),
+            VirtualCollection {
+                name: String::from(name),
+                collection_type,
+                query: Box::new(query),
+            },
+        ));
+        self
+    }
+
+    // Carry out PATCH requests of the resource at the URI with the behavior, instead of by
+    // replacing the properties given, such as to also update the resources related to it.
+    // The resource must still allow PATCH.
+    pub fn on_patch(
+        mut self,
+        uri: &str,
+        behavior: impl Fn(&mut StaticResources, &str, &Map<String, Value>) -> Result<(), Error>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.patch_behaviors
+            .insert(String::from(uri), Box::new(behavior));
+        self
+    }
+
+    // Give the resource at the URI the action, such as ComputerSystem.Reset, carried out by the
+    // behavior. For example, one that powers off a system:
+    // |resources, _| {
+    //     resources.set("/redfish/v1/Systems/1", "PowerState", json!("Off"))?;
+    //     Ok(None)
+    // }
+    // The body of the resource should still advertise the action in its Actions.
+    pub fn on_action(
+        mut self,
+        uri: &str,
+        action: &str,
+        behavior: impl Fn(&mut StaticResources, &Map<String, Value>) -> Result<Option<Value>, Error>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.action_behaviors.insert(
+            (String::from(uri), String::from(action)),
+            Box::new(behavior),
+        );
+        self
+    }
+
+    pub fn build(self) -> StaticTree {
+        let mut tree = StaticTree {
+            nodes: HashMap::new(),
+            virtual_collections: HashMap::new(),
+            collection_types: Vec::new(),
+            resource_types: Vec::new(),
+            patch_behaviors: self.patch_behaviors,
+            action_behaviors: self.action_behaviors,
+        };
+        for node in self.nodes {
+            tree.add_node_types(&node);
+            tree.nodes.insert(node.uri.clone(), node);
+        }
+        for (uri, collection) in self.virtual_collections {
+            if !tree.collection_types.contains(&collection.collection_type) {
+                tree.collection_types
+                    .push(collection.collection_type.clone());
+            }
+            tree.virtual_collections.insert(uri, collection);
+        }
+        tree
+    }
+}
+
+pub struct StaticTree {
+    nodes: HashMap<String, StaticNode>,
+    virtual_collections: HashMap<String, VirtualCollection>,
+    collection_types: Vec<CollectionType>,
+    resource_types: Vec<ResourceType>,
+    patch_behaviors: HashMap<String, PatchBehavior>,
+    action_behaviors: HashMap<(String, String), ActionBehavior>,
+}
+
+impl StaticTree {
+    fn get_virtual_collection(&self, uri: &str) -> Option<StaticNode> {
+        let collection = self.virtual_collections.get(uri)?;
+        let mut members: Vec<String> = self
+            .nodes
+            .values()
+            .filter(|node| (collection.query)(*node))
+            .map(|node| node.uri.clone())
+            .collect();
+        members.sort();
+        Some(StaticNode::collection(
+            uri,
+            &collection.name,
+            collection.collection_type.clone(),
+            members,
+            None,
+        ))
+    }
+
+    // Virtual collections can only be read.
+    fn check_not_virtual(&self, uri: &str) -> Result<(), Error> {
+        match self.virtual_collections.contains_key(uri) {
+            true => Err(Error::MethodNotAllowed(AllowedMethods {
+                delete: false,
+                get: true,
+                patch: false,
+                post: false,
+            })),
+            false => Ok(()),
+        }
+    }
+
+    fn add_node_types(&mut self, node: &StaticNode) {
+        match &node.kind {
+            StaticNodeKind::Resource(resource_type) => {
+                if !self.resource_types.contains(resource_type) {
+                    self.resource_types.push(resource_type.clone());
+                }
+            }
+            StaticNodeKind::Collection(collection_type, _, _) => {
+                if !self.collection_types.contains(collection_type) {
+                    self.collection_types.push(collection_type.clone());
+                }
+            }
+        }
+    }
+}
+
+fn check_username(uri: &str, username: Option<&str>) -> Result<(), Error> {
+    // Only the service root may be read without authenticating.
+    if uri != "/redfish/v1" && username.is_none() {
+        return Err(Error::Unauthorized);
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Tree for StaticTree {
+    async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+        check_username(uri, username)?;
+        if let Some(collection) = self.get_virtual_collection(uri) {
+            return Ok(NodeRef::Shared(Arc::new(collection)));
+        }
+        match self.nodes.get(uri) {
+            Some(node) => Ok(node.into()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn create(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        check_username(uri, username)?;
+        self.check_not_virtual(uri)?;
+        let collection = self.nodes.get_mut(uri).ok_or(Error::NotFound)?;
+        let allowed_methods = collection.allowed_methods;
+        let (members, member_type) = match &mut collection.kind {
+            StaticNodeKind::Collection(_, members, Some(member_type)) => (members, member_type),
+            _ => return Err(Error::MethodNotAllowed(allowed_methods)),
+        };
+        let highest = members
+            .iter()
+            .filter_map(|member| get_uri_id(member).parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        let id = (highest + 1).to_string();
+        let member_uri = format!("{}/{}", uri, id);
+        members.push(member_uri.clone());
+        let name = match request_body.get("Name").and_then(|name| name.as_str()) {
+            Some(name) => String::from(name),
+            None => format!("{} {}", member_type.name, id),
+        };
+        let allowed_methods = AllowedMethods {
+            delete: true,
+            get: true,
+            patch: true,
+            post: false,
+        };
+        let member = StaticNode::resource(
+            &member_uri,
+            &name,
+            member_type.clone(),
+            allowed_methods,
+            request_body.clone(),
+        );
+        self.add_node_types(&member);
+        self.nodes.insert(member_uri.clone(), member);
+        Ok(self.nodes.get(&member_uri).unwrap())
+    }
+
+    async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+        check_username(uri, username)?;
+        self.check_not_virtual(uri)?;
+        let node = self.nodes.get(uri).ok_or(Error::NotFound)?;
+        if !node.allowed_methods.delete {
+            return Err(Error::MethodNotAllowed(node.allowed_methods));
+        }
+        self.nodes.remove(uri);
+        for node in self.nodes.values_mut() {
+            if let StaticNodeKind::Collection(_, members, _) = &mut node.kind {
+                members.retain(|member| member != uri);
+            }
+        }
+        Ok(())
+    }
+
+    async fn patch(
+        &mut self,
+        uri: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<&dyn Node, Error> {
+        check_username(uri, username)?;
+        self.check_not_virtual(uri)?;
+        let node = self.nodes.get_mut(uri).ok_or(Error::NotFound)?;
+        if !node.allowed_methods.patch {
+            return Err(Error::MethodNotAllowed(node.allowed_methods));
+        }
+        match self.patch_behaviors.get(uri) {
+            Some(behavior) => {
+                let mut resources = StaticResources {
+                    nodes: &mut self.nodes,
+                };
+                behavior(&mut resources, uri, request_body)?;
+            }
+            None => {
+                for (key, value) in request_body {
+                    node.body.insert(key, value.clone());
+                }
+            }
+        }
+        Ok(self.nodes.get(uri).unwrap())
+    }
+
+    async fn action(
+        &mut self,
+        uri: &str,
+        action: &str,
+        request_body: &Map<String, Value>,
+        username: Option<&str>,
+    ) -> Result<Outcome<Option<Value>>, Error> {
+        check_username(uri, username)?;
+        let key = (String::from(uri), String::from(action));
+        let behavior = match self.action_behaviors.get(&key) {
+            Some(behavior) => behavior,
+            None => return Err(Error::ActionNotSupported(String::from(action))),
+        };
+        let mut resources = StaticResources {
+            nodes: &mut self.nodes,
+        };
+        behavior(&mut resources, request_body).map(Outcome::Done)
+    }
+
+    fn get_collection_types(&self) -> &[CollectionType] {
+        &self.collection_types
+    }
+
+    fn get_resource_types(&self) -> &[ResourceType] {
+        &self.resource_types
+    }
+}
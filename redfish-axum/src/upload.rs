@@ -0,0 +1,33 @@
+use crate::Error;
+use async_trait::async_trait;
+use axum::body::{Body, HttpBody};
+use bytes::Bytes;
+
+// Receives the body of a raw binary upload, such as a firmware image POSTed to an UpdateService
+// push URI, as it arrives. This way the whole image is never held in memory at once.
+#[async_trait]
+pub trait UploadWriter: Send {
+    async fn write(&mut self, chunk: Bytes) -> Result<(), Error>;
+
+    // Called after each chunk is written, with the number of bytes received so far
+    // and the total from the Content-Length header, if the client sent one.
+    fn progress(&mut self, _received: u64, _total: Option<u64>) {}
+
+    // Called once the whole body has been written.
+    async fn finish(self: Box<Self>) -> Result<(), Error>;
+}
+
+pub(crate) async fn stream_upload(
+    mut body: Body,
+    mut writer: Box<dyn UploadWriter>,
+    total: Option<u64>,
+) -> Result<(), Error> {
+    let mut received = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| Error::BadRequest)?;
+        received += chunk.len() as u64;
+        writer.write(chunk).await?;
+        writer.progress(received, total);
+    }
+    writer.finish().await
+}
@@ -0,0 +1,115 @@
+use crate::crawl::{Crawled, Crawler, SERVICE_ROOT_URI};
+use crate::Tree;
+use serde_json::{json, Value};
+
+// Links the ServiceRoot must have, by their name within its Links property.
+const SERVICE_ROOT_LINKS: [&str; 1] = ["Sessions"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationIssue {
+    // The resource at `from` links to `to`, which the tree does not have.
+    DanglingLink { from: String, to: String },
+    // The resource's type is not one the tree declares, so $metadata does not reference it.
+    MissingMetadata { uri: String },
+    // The resource has no ETag, so clients cannot make conditional requests of it.
+    MissingEtag { uri: String },
+    // The ServiceRoot lacks the link of this name in its Links property.
+    MissingServiceRootLink(String),
+}
+
+impl ValidationIssue {
+    fn to_json(&self) -> Value {
+        match self {
+            ValidationIssue::DanglingLink { from, to } => {
+                json!({ "Issue": "DanglingLink", "Uri": from, "Link": to })
+            }
+            ValidationIssue::MissingMetadata { uri } => {
+                json!({ "Issue": "MissingMetadata", "Uri": uri })
+            }
+            ValidationIssue::MissingEtag { uri } => json!({ "Issue": "MissingEtag", "Uri": uri }),
+            ValidationIssue::MissingServiceRootLink(name) => {
+                json!({ "Issue": "MissingServiceRootLink", "Uri": SERVICE_ROOT_URI, "Link": name })
+            }
+        }
+    }
+}
+
+// What validate() found, in the order it found it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    // The URIs of the resources that were checked.
+    pub checked: Vec<String>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "Checked": self.checked.len(),
+            "Issues": self.issues.iter().map(ValidationIssue::to_json).collect::<Vec<Value>>(),
+        })
+    }
+}
+
+// Check the tree the way a service built from it would serve it, such as at boot or in tests.
+// Every resource linked from the ServiceRoot is read as the user, and a resource the user
+// cannot read, or that fails to read, is not checked. Non-JSON resources are not checked.
+pub async fn validate(tree: &(dyn Tree + Send + Sync), username: Option<&str>) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut crawler = Crawler::new(tree, username);
+    while let Some(crawled) = crawler.next().await {
+        let (uri, meta, body) = match crawled {
+            Crawled::Found { uri, meta, body } => (uri, meta, body),
+            Crawled::Missing { from, uri } => {
+                report
+                    .issues
+                    .push(ValidationIssue::DanglingLink { from, to: uri });
+                continue;
+            }
+        };
+        report.checked.push(uri.clone());
+
+        let declared = meta.described_by.as_ref().is_some_and(|described_by| {
+            tree.get_resource_types()
+                .iter()
+                .any(|t| t.described_by == *described_by)
+                || tree
+                    .get_collection_types()
+                    .iter()
+                    .any(|t| t.described_by == *described_by)
+        });
+        if !declared {
+            report
+                .issues
+                .push(ValidationIssue::MissingMetadata { uri: uri.clone() });
+        }
+        // Collections change with their members, so only resources need an ETag.
+        if meta.etag.is_none() && body.get("Members").is_none() {
+            report
+                .issues
+                .push(ValidationIssue::MissingEtag { uri: uri.clone() });
+        }
+        if uri == SERVICE_ROOT_URI {
+            check_service_root_links(&body, &mut report);
+        }
+    }
+    report
+}
+
+fn check_service_root_links(body: &Value, report: &mut ValidationReport) {
+    for name in SERVICE_ROOT_LINKS {
+        let link = body
+            .get("Links")
+            .and_then(|links| links.get(name))
+            .and_then(|link| link.get("@odata.id"));
+        if link.is_none() {
+            report
+                .issues
+                .push(ValidationIssue::MissingServiceRootLink(String::from(name)));
+        }
+    }
+}
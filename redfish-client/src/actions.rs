@@ -0,0 +1,263 @@
+use std::time::{Duration, Instant};
+
+use http::{header, HeaderMap, Method, StatusCode};
+use hyper::Body;
+use redfish_data::{
+    base_registry, validate_action_parameters, ActionParameter, Message, PropertyType,
+    ResourceSchemaVersion,
+};
+use serde_json::{Map, Value};
+
+use crate::{read_body, Client, ClientError};
+
+// An action of a resource, as its Actions property advertises it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Action {
+    // Such as ComputerSystem.Reset, or Contoso.Reset for OEM actions.
+    pub name: String,
+    // The URI to POST to, to invoke it.
+    pub target: String,
+    // The URI of its ActionInfo, which declares its parameters, if it has one.
+    pub action_info: Option<String>,
+    // The values the parameters that are annotated with @Redfish.AllowableValues may take.
+    pub allowable_values: Vec<(String, Vec<String>)>,
+}
+
+// The parameters of an action, as far as the client can tell.
+struct ActionParameters {
+    // Those whose types the client can check.
+    checked: Vec<ActionParameter>,
+    // Every parameter the ActionInfo declares, and whether it is required, or None if the
+    // action has no ActionInfo, so that any parameter may be given.
+    declared: Option<Vec<(String, bool)>>,
+}
+
+impl Client {
+    // The actions the resource at the URI advertises in its Actions, OEM ones included.
+    pub async fn get_actions(&self, uri: &str) -> Result<Vec<Action>, ClientError> {
+        let body = self.get(uri).await?;
+        let mut actions = Vec::new();
+        if let Some(body_actions) = body.get("Actions").and_then(Value::as_object) {
+            add_actions(&mut actions, body_actions);
+            if let Some(oem) = body_actions.get("Oem").and_then(Value::as_object) {
+                add_actions(&mut actions, oem);
+            }
+        }
+        Ok(actions)
+    }
+
+    // The parameters of the action whose types the client can check: those its ActionInfo
+    // declares as strings, numbers or booleans, or arrays of them, with their AllowableValues,
+    // and those annotated with AllowableValues on the action itself.
+    pub async fn get_action_parameters(
+        &self,
+        action: &Action,
+    ) -> Result<Vec<ActionParameter>, ClientError> {
+        Ok(self.get_parameters(action).await?.checked)
+    }
+
+    // Invoke the action with the parameters, once they are checked against those it declares,
+    // and return the body the service responds with, if any. If the service carries it out as
+    // a task, its task monitor is polled until the task completes, and the body it then gives
+    // is returned instead.
+    pub async fn invoke_action(
+        &self,
+        action: &Action,
+        parameters: &Map<String, Value>,
+    ) -> Result<Option<Value>, ClientError> {
+        let messages = check_parameters(action, &self.get_parameters(action).await?, parameters);
+        if !messages.is_empty() {
+            return Err(ClientError::InvalidParameters(messages));
+        }
+        let request_body = Value::Object(parameters.clone());
+        let response = self
+            .send_request(Method::POST, &action.target, &request_body)
+            .await?;
+        if response.status() != StatusCode::ACCEPTED {
+            let status = response.status();
+            let body = read_body(response).await?;
+            if !status.is_success() {
+                return Err(ClientError::Status(status, body));
+            }
+            return Ok(body);
+        }
+        let monitor = get_location(response.headers()).ok_or(ClientError::InvalidBody)?;
+        self.wait_for_task(&monitor).await
+    }
+
+    async fn get_parameters(&self, action: &Action) -> Result<ActionParameters, ClientError> {
+        let mut parameters = ActionParameters {
+            checked: Vec::new(),
+            declared: None,
+        };
+        if let Some(action_info) = &action.action_info {
+            let body = self.get(action_info).await?;
+            let mut declared = Vec::new();
+            let info_parameters = body.get("Parameters").and_then(Value::as_array);
+            for info_parameter in info_parameters.into_iter().flatten() {
+                let Some(name) = info_parameter.get("Name").and_then(Value::as_str) else {
+                    continue;
+                };
+                let required = info_parameter.get("Required") == Some(&Value::Bool(true));
+                declared.push((String::from(name), required));
+                // The AllowableValues of arrays are those of their elements.
+                let allowable_values = get_strings(info_parameter.get("AllowableValues"));
+                let string_type = match allowable_values.is_empty() {
+                    true => PropertyType::String,
+                    false => PropertyType::Enum(allowable_values),
+                };
+                let parameter_type = match info_parameter.get("DataType").and_then(Value::as_str) {
+                    Some("Boolean") => PropertyType::Boolean,
+                    Some("Number") => PropertyType::Number,
+                    Some("String") => string_type,
+                    Some("NumberArray") => PropertyType::Array(Box::new(PropertyType::Number)),
+                    Some("StringArray") => PropertyType::Array(Box::new(string_type)),
+                    // Objects are left for the service to check.
+                    _ => continue,
+                };
+                let mut parameter = ActionParameter::new(name, parameter_type);
+                parameter.required = required;
+                parameters.checked.push(parameter);
+            }
+            parameters.declared = Some(declared);
+        }
+        // Annotations on the action narrow what the ActionInfo allows.
+        for (name, values) in &action.allowable_values {
+            let enum_type = PropertyType::Enum(values.clone());
+            match parameters.checked.iter_mut().find(|p| &p.name == name) {
+                Some(parameter) => match &mut parameter.parameter_type {
+                    PropertyType::Array(element_type) => **element_type = enum_type,
+                    parameter_type => *parameter_type = enum_type,
+                },
+                None => parameters
+                    .checked
+                    .push(ActionParameter::new(name, enum_type)),
+            }
+        }
+        Ok(parameters)
+    }
+
+    // Poll the task monitor until the task it monitors completes, and return the body it then
+    // gives, which is the response to the operation the task carried out, or the task itself.
+    // Polling stops with ClientError::Timeout once the next poll would be past the task timeout.
+    async fn wait_for_task(&self, monitor: &str) -> Result<Option<Value>, ClientError> {
+        let deadline = Instant::now() + self.task_timeout;
+        loop {
+            let request = self
+                .request(Method::GET, monitor)?
+                .body(Body::empty())
+                .map_err(|_| ClientError::InvalidUri)?;
+            let response = self.http.request(request).await?;
+            let status = response.status();
+            if status == StatusCode::ACCEPTED {
+                let delay = response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok()?.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(self.poll_interval);
+                if Instant::now() + delay > deadline {
+                    return Err(ClientError::Timeout);
+                }
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            let body = read_body(response).await?;
+            if !status.is_success() {
+                return Err(ClientError::Status(status, body));
+            }
+            return Ok(body);
+        }
+    }
+}
+
+// Add the actions of an Actions object, or of its Oem property, whose keys are their names
+// prefixed with #.
+fn add_actions(actions: &mut Vec<Action>, body_actions: &Map<String, Value>) {
+    for (key, value) in body_actions {
+        let Some(name) = key.strip_prefix('#') else {
+            continue;
+        };
+        let Some(target) = value.get("target").and_then(Value::as_str) else {
+            continue;
+        };
+        let action_info = value
+            .get("@Redfish.ActionInfo")
+            .and_then(Value::as_str)
+            .map(String::from);
+        let allowable_values = value
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(property, values)| {
+                let parameter = property.strip_suffix("@Redfish.AllowableValues")?;
+                Some((String::from(parameter), get_strings(Some(values))))
+            })
+            .collect();
+        actions.push(Action {
+            name: String::from(name),
+            target: String::from(target),
+            action_info,
+            allowable_values,
+        });
+    }
+}
+
+fn get_strings(values: Option<&Value>) -> Vec<String> {
+    values
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.as_str().map(String::from))
+        .collect()
+}
+
+// The task monitor of a 202 Accepted response, as a URI on the service.
+fn get_location(headers: &HeaderMap) -> Option<String> {
+    let location = headers.get(header::LOCATION)?.to_str().ok()?;
+    // Absolute URLs are made relative to the service they point to.
+    match location.split_once("://") {
+        Some((_, rest)) => Some(String::from(&rest[rest.find('/')?..])),
+        None => Some(String::from(location)),
+    }
+}
+
+// A message for each problem with the parameters given to the action, as the service would
+// give in its error response. Parameters the ActionInfo declares but the client cannot check
+// the types of are only checked to be there if required.
+fn check_parameters(
+    action: &Action,
+    parameters: &ActionParameters,
+    request_body: &Map<String, Value>,
+) -> Vec<Message> {
+    let is_checked = |name: &str| parameters.checked.iter().any(|p| p.name == name);
+    // Leave out those that are declared but not checked, or all that are not checked if the
+    // action declares none, so that they are not taken as unknown.
+    let checked_body: Map<String, Value> = request_body
+        .iter()
+        .filter(|(name, _)| match &parameters.declared {
+            Some(declared) => is_checked(name) || !declared.iter().any(|(n, _)| n == *name),
+            None => is_checked(name),
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let registry = base_registry();
+    let mut messages =
+        validate_action_parameters(registry, &action.name, &parameters.checked, &checked_body)
+            .expect("Base registry is missing action parameter messages");
+    let declared = parameters.declared.iter().flatten();
+    for (name, _) in declared.filter(|(name, required)| {
+        *required && !is_checked(name) && !request_body.contains_key(name)
+    }) {
+        let message = Message::from_registry(
+            registry,
+            "ActionParameterMissing",
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![action.name.clone(), name.clone()],
+            vec![format!("#/{}", name)],
+        )
+        .expect("Base registry is missing ActionParameterMissing");
+        messages.push(message);
+    }
+    messages
+}
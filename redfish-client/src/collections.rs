@@ -0,0 +1,62 @@
+use redfish_data::Query;
+use serde_json::Value;
+
+use crate::{get_uri_with_query, Client, ClientError};
+
+// The members of a collection, read a page at a time by following the
+// Members@odata.nextLink of each page to the next, until a page has none.
+pub struct Members<'a> {
+    client: &'a Client,
+    // The members of the page read last that have not been returned yet.
+    page: std::vec::IntoIter<Value>,
+    // The URI of the page to read next, or None once the last has been read.
+    next_link: Option<String>,
+}
+
+impl Client {
+    // The members of the collection at the URI, with the query parameters, such as $filter,
+    // or $expand for the members to be whole resources rather than references. Nothing is
+    // sent until the first member is asked for.
+    pub fn members(&self, uri: &str, query: &Query) -> Members<'_> {
+        Members {
+            client: self,
+            page: Vec::new().into_iter(),
+            next_link: Some(get_uri_with_query(uri, query)),
+        }
+    }
+}
+
+impl Members<'_> {
+    // The next member, as the collection gives it, reading the next page if needed. None once
+    // every member has been returned, or after an error reading a page.
+    pub async fn next(&mut self) -> Option<Result<Value, ClientError>> {
+        loop {
+            if let Some(member) = self.page.next() {
+                return Some(Ok(member));
+            }
+            let link = self.next_link.take()?;
+            let mut body = match self.client.get(&link).await {
+                Ok(body) => body,
+                Err(error) => return Some(Err(error)),
+            };
+            let members = match body.get_mut("Members").map(Value::take) {
+                Some(Value::Array(members)) => members,
+                _ => return Some(Err(ClientError::InvalidBody)),
+            };
+            self.page = members.into_iter();
+            self.next_link = body
+                .get("Members@odata.nextLink")
+                .and_then(Value::as_str)
+                .map(String::from);
+        }
+    }
+
+    // Every member that has not been returned yet, from all the pages left.
+    pub async fn read_all(mut self) -> Result<Vec<Value>, ClientError> {
+        let mut members = Vec::new();
+        while let Some(member) = self.next().await {
+            members.push(member?);
+        }
+        Ok(members)
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::{HashSet, VecDeque};
+
+use redfish_data::{get_resource_links, Inventory};
+use serde_json::Value;
+
+use crate::{Client, ClientError};
+
+const SERVICE_ROOT_URI: &str = "/redfish/v1";
+
+impl Client {
+    // A normalized inventory of the systems, chassis and firmware of the service, as read with
+    // the client's credentials. The service is crawled from the ServiceRoot through the
+    // @odata.id links of each resource, and through the pages of collections by their
+    // Members@odata.nextLink. Resources that cannot be read, or are not JSON, are passed over,
+    // but the crawl stops if the service cannot be reached.
+    pub async fn get_inventory(&self) -> Result<Value, ClientError> {
+        let mut inventory = Inventory::new();
+        let mut seen = HashSet::from([String::from(SERVICE_ROOT_URI)]);
+        let mut queue = VecDeque::from([String::from(SERVICE_ROOT_URI)]);
+        while let Some(uri) = queue.pop_front() {
+            let body = match self.get(&uri).await {
+                Ok(body) => body,
+                Err(ClientError::Http(error)) => return Err(ClientError::Http(error)),
+                Err(_) => continue,
+            };
+            let next_page = body
+                .get("Members@odata.nextLink")
+                .and_then(Value::as_str)
+                .map(String::from);
+            for link in get_resource_links(&body).into_iter().chain(next_page) {
+                if seen.insert(link.clone()) {
+                    queue.push_back(link);
+                }
+            }
+            inventory.add(&uri, &body);
+        }
+        Ok(inventory.to_json())
+    }
+}
@@ -0,0 +1,741 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::{header, request, HeaderValue, Method, Request, StatusCode};
+use hyper::client::HttpConnector;
+use hyper::Body;
+use redfish_data::{Message, Query};
+use serde_json::Value;
+
+mod actions;
+mod collections;
+mod inventory;
+
+pub use actions::Action;
+pub use collections::Members;
+
+// How the client authenticates its requests.
+#[derive(Clone, Debug)]
+pub enum Auth {
+    None,
+    Basic { username: String, password: String },
+    // The X-Auth-Token of a session.
+    Token(String),
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    // The URI could not be made into a request.
+    InvalidUri,
+    // The request could not be sent, or its response read.
+    Http(hyper::Error),
+    // The service responded with an error, with the body it gave, if any.
+    Status(StatusCode, Option<Value>),
+    // The service responded with a body that is not JSON.
+    InvalidBody,
+    // The parameters given to an action do not fit those it declares, for the reasons the
+    // messages give. The action was not invoked.
+    InvalidParameters(Vec<Message>),
+    // The task carrying out a request did not complete within the task timeout. It may still
+    // complete later.
+    Timeout,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUri => write!(f, "invalid URI"),
+            Self::Http(error) => write!(f, "{}", error),
+            Self::Status(status, _) => write!(f, "service responded with {}", status),
+            Self::InvalidBody => write!(f, "service responded with a body that is not JSON"),
+            Self::InvalidParameters(messages) => {
+                write!(f, "{} invalid action parameters", messages.len())
+            }
+            Self::Timeout => write!(f, "task did not complete in time"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<hyper::Error> for ClientError {
+    fn from(error: hyper::Error) -> Self {
+        Self::Http(error)
+    }
+}
+
+fn get_uri_with_query(uri: &str, query: &Query) -> String {
+    match query.is_empty() {
+        true => String::from(uri),
+        false => format!("{}?{}", uri, query),
+    }
+}
+
+// A body the service gave with an ETag, to reuse while the resource has not changed.
+struct CachedBody {
+    etag: HeaderValue,
+    body: Value,
+}
+
+// A client of a Redfish service over HTTP.
+// Bodies the service gives with an ETag are cached by URI, and asked for again with
+// If-None-Match, so that the service may respond 304 Not Modified instead of sending the same
+// body again, which is most of the time when polling inventory. Services that do not give
+// ETags are asked for the full body each time.
+pub struct Client {
+    base_url: String,
+    auth: Auth,
+    http: hyper::Client<HttpConnector>,
+    cache: Mutex<HashMap<String, CachedBody>>,
+    poll_interval: Duration,
+    task_timeout: Duration,
+}
+
+impl Client {
+    // The base URL is the scheme and authority of the service, such as http://127.0.0.1:8000.
+    // Only http is supported, so HTTPS services need a proxy in front of them.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: String::from(base_url.trim_end_matches('/')),
+            auth: Auth::None,
+            http: hyper::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+            poll_interval: Duration::from_secs(1),
+            task_timeout: Duration::from_secs(600),
+        }
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    // How long to wait between polls of a task monitor, when the service does not say with
+    // Retry-After. One second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // How long to poll a task monitor for before giving up on the task with
+    // ClientError::Timeout. Ten minutes by default.
+    pub fn with_task_timeout(mut self, task_timeout: Duration) -> Self {
+        self.task_timeout = task_timeout;
+        self
+    }
+
+    // GET the resource at the URI, such as /redfish/v1/Systems/1, with any query it has.
+    pub async fn get(&self, uri: &str) -> Result<Value, ClientError> {
+        let mut builder = self.request(Method::GET, uri)?;
+        let cached_etag = self.cache.lock().unwrap().get(uri).map(|c| c.etag.clone());
+        if let Some(etag) = cached_etag {
+            builder = builder.header(header::IF_NONE_MATCH, etag);
+        }
+        let request = builder
+            .body(Body::empty())
+            .map_err(|_| ClientError::InvalidUri)?;
+        let response = self.http.request(request).await?;
+        let status = response.status();
+        let etag = response.headers().get(header::ETAG).cloned();
+
+        if status == StatusCode::NOT_MODIFIED {
+            // Only sent when there is a cached body, unless it was dropped meanwhile.
+            if let Some(cached) = self.cache.lock().unwrap().get(uri) {
+                return Ok(cached.body.clone());
+            }
+            return Err(ClientError::Status(status, None));
+        }
+        let body = read_body(response).await?;
+        if !status.is_success() {
+            return Err(ClientError::Status(status, body));
+        }
+        let body = body.ok_or(ClientError::InvalidBody)?;
+        let mut cache = self.cache.lock().unwrap();
+        match etag {
+            Some(etag) => {
+                let cached = CachedBody {
+                    etag,
+                    body: body.clone(),
+                };
+                cache.insert(String::from(uri), cached);
+            }
+            None => {
+                cache.remove(uri);
+            }
+        }
+        Ok(body)
+    }
+
+    // GET the resource at the URI, which has no query of its own, with the query parameters,
+    // such as $select or $filter.
+    pub async fn get_with_query(&self, uri: &str, query: &Query) -> Result<Value, ClientError> {
+        self.get(&get_uri_with_query(uri, query)).await
+    }
+
+    // PATCH the resource at the URI with the properties given, returning the body the service
+    // responds with, if any.
+    pub async fn patch(
+        &self,
+        uri: &str,
+        request_body: &Value,
+    ) -> Result<Option<Value>, ClientError> {
+        self.send(Method::PATCH, uri, request_body).await
+    }
+
+    // Forget the body cached for the URI, so that the next GET of it is sent in full.
+    pub fn invalidate(&self, uri: &str) {
+        self.cache.lock().unwrap().remove(uri);
+    }
+
+    fn request(&self, method: Method, uri: &str) -> Result<request::Builder, ClientError> {
+        if !uri.starts_with('/') {
+            return Err(ClientError::InvalidUri);
+        }
+        let builder = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base_url, uri))
+            .header(header::ACCEPT, "application/json");
+        Ok(match &self.auth {
+            Auth::None => builder,
+            Auth::Basic { username, password } => {
+                let credentials = STANDARD.encode(format!("{}:{}", username, password));
+                builder.header(header::AUTHORIZATION, format!("Basic {}", credentials))
+            }
+            Auth::Token(token) => builder.header("X-Auth-Token", token.as_str()),
+        })
+    }
+
+    // Send the body to the URI, and return the body of the response, if any. What was cached
+    // for the URI is dropped, as the request may change it.
+    async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        request_body: &Value,
+    ) -> Result<Option<Value>, ClientError> {
+        let response = self.send_request(method, uri, request_body).await?;
+        let status = response.status();
+        let body = read_body(response).await?;
+        if !status.is_success() {
+            return Err(ClientError::Status(status, body));
+        }
+        Ok(body)
+    }
+
+    async fn send_request(
+        &self,
+        method: Method,
+        uri: &str,
+        request_body: &Value,
+    ) -> Result<hyper::Response<Body>, ClientError> {
+        let request = self
+            .request(method, uri)?
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(request_body.to_string()))
+            .map_err(|_| ClientError::InvalidUri)?;
+        self.invalidate(uri);
+        Ok(self.http.request(request).await?)
+    }
+}
+
+// The JSON body of the response, or None if it has none.
+async fn read_body(response: hyper::Response<Body>) -> Result<Option<Value>, ClientError> {
+    let data = hyper::body::to_bytes(response.into_body()).await?;
+    if data.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|_| ClientError::InvalidBody)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use axum::Router;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::Server;
+    use redfish_axum::{
+        AppBuilder, Error, Node, NodeRef, Outcome, StaticTree, TaskService, Tree, TreeBuilder,
+    };
+    use redfish_data::{
+        ActionParameter, AllowedMethods, CollectionType, Health, PropertyType,
+        ResourceSchemaVersion, ResourceType,
+    };
+    use serde_json::{json, Map};
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use tower::{Service, ServiceExt};
+    use tower_http::normalize_path::NormalizePath;
+
+    // Serve the app on a free port, and return its base URL and the statuses of the responses
+    // it gives, in order.
+    fn serve(app: NormalizePath<Router>) -> (String, Arc<Mutex<Vec<StatusCode>>>) {
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let recorded = statuses.clone();
+        let make_service = make_service_fn(move |_| {
+            let app = app.clone();
+            let recorded = recorded.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let mut app = app.clone();
+                    let recorded = recorded.clone();
+                    async move {
+                        let response = app.ready().await?.call(request).await?;
+                        recorded.lock().unwrap().push(response.status());
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        (base_url, statuses)
+    }
+
+    fn admin_admin() -> Auth {
+        Auth::Basic {
+            username: String::from("admin"),
+            password: String::from("admin"),
+        }
+    }
+
+    fn get_system_tree() -> StaticTree {
+        TreeBuilder::new()
+            .resource(
+                "/redfish/v1/Systems/1",
+                "System",
+                ResourceType::new_dmtf(
+                    String::from("ComputerSystem"),
+                    ResourceSchemaVersion::new(1, 20, 0),
+                ),
+                AllowedMethods {
+                    delete: false,
+                    get: true,
+                    patch: true,
+                    post: false,
+                },
+                json!({"AssetTag": "A"}),
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn etag_caching() {
+        let system = "/redfish/v1/Systems/1";
+        let app = AppBuilder::new(get_system_tree())
+            .computed_etags(true)
+            .build();
+        let (base_url, statuses) = serve(app);
+        let client = Client::new(&base_url).with_auth(admin_admin());
+
+        // The body is sent once, then reused while it has not changed.
+        let body = client.get(system).await.unwrap();
+        assert_eq!(body["AssetTag"], "A");
+        let body = client.get(system).await.unwrap();
+        assert_eq!(body["AssetTag"], "A");
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![StatusCode::OK, StatusCode::NOT_MODIFIED]
+        );
+
+        // Changes are picked up, then cached in turn.
+        client
+            .patch(system, &json!({"AssetTag": "B"}))
+            .await
+            .unwrap();
+        let body = client.get(system).await.unwrap();
+        assert_eq!(body["AssetTag"], "B");
+        let body = client.get(system).await.unwrap();
+        assert_eq!(body["AssetTag"], "B");
+        assert_eq!(
+            statuses.lock().unwrap()[3..],
+            [StatusCode::OK, StatusCode::NOT_MODIFIED]
+        );
+
+        // Unless forgotten.
+        client.invalidate(system);
+        client.get(system).await.unwrap();
+        assert_eq!(statuses.lock().unwrap()[5], StatusCode::OK);
+
+        assert!(matches!(
+            client.get("/redfish/v1/Systems/2").await,
+            Err(ClientError::Status(StatusCode::NOT_FOUND, _))
+        ));
+        assert!(matches!(
+            client.get("redfish/v1").await,
+            Err(ClientError::InvalidUri)
+        ));
+    }
+
+    #[tokio::test]
+    async fn no_etags() {
+        let (base_url, statuses) = serve(redfish_axum::app(get_system_tree()));
+        let client = Client::new(&base_url).with_auth(admin_admin());
+
+        // Without ETags, bodies are sent in full each time.
+        for _ in 0..2 {
+            let body = client.get("/redfish/v1/Systems/1").await.unwrap();
+            assert_eq!(body["AssetTag"], "A");
+        }
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![StatusCode::OK, StatusCode::OK]
+        );
+    }
+
+    #[tokio::test]
+    async fn collection_pages() {
+        let uri = "/redfish/v1/Systems";
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let systems: Vec<String> = (1..=5).map(|i| format!("{}/{}", uri, i)).collect();
+        let mut builder = TreeBuilder::new().collection(
+            uri,
+            "Systems",
+            CollectionType::new_dmtf_v1(String::from("ComputerSystemCollection")),
+            systems.clone(),
+            None,
+        );
+        for (i, system) in systems.iter().enumerate() {
+            builder = builder.resource(
+                system,
+                "System",
+                ResourceType::new_dmtf(
+                    String::from("ComputerSystem"),
+                    ResourceSchemaVersion::new(1, 20, 0),
+                ),
+                allowed_methods,
+                json!({"AssetTag": format!("A{}", i + 1), "PowerState": "On"}),
+            );
+        }
+        let app = AppBuilder::new(builder.build()).page_size(Some(2)).build();
+        let (base_url, statuses) = serve(app);
+        let client = Client::new(&base_url).with_auth(admin_admin());
+
+        // Members are read a page at a time, as they are asked for.
+        let mut members = client.members(uri, &Query::default());
+        let first = members.next().await.unwrap().unwrap();
+        assert_eq!(first, json!({"@odata.id": "/redfish/v1/Systems/1"}));
+        assert_eq!(statuses.lock().unwrap().len(), 1);
+        let rest = members.read_all().await.unwrap();
+        let rest: Vec<&str> = rest
+            .iter()
+            .filter_map(|m| m["@odata.id"].as_str())
+            .collect();
+        assert_eq!(rest, systems[1..]);
+        assert_eq!(statuses.lock().unwrap().len(), 3);
+
+        // The query is sent with the first page, and carried on by the nextLinks.
+        let query = Query::parse("$expand=.&$filter=AssetTag%20ne%20'A2'").unwrap();
+        let members = client.members(uri, &query).read_all().await.unwrap();
+        let tags: Vec<&str> = members
+            .iter()
+            .filter_map(|m| m["AssetTag"].as_str())
+            .collect();
+        assert_eq!(tags, vec!["A1", "A3", "A4", "A5"]);
+
+        // As it is with single resources.
+        let query = Query::parse("$select=PowerState").unwrap();
+        let body = client.get_with_query(&systems[0], &query).await.unwrap();
+        assert_eq!(body["PowerState"], "On");
+        assert!(body.get("AssetTag").is_none());
+        let body = client
+            .get_with_query(&systems[0], &Query::default())
+            .await
+            .unwrap();
+        assert_eq!(body["AssetTag"], "A1");
+    }
+
+    // Decommissions systems as tasks, which complete a little later.
+    struct DecommissionTree {
+        tree: StaticTree,
+        tasks: TaskService,
+    }
+
+    #[async_trait]
+    impl Tree for DecommissionTree {
+        async fn get(&self, uri: &str, username: Option<&str>) -> Result<NodeRef<'_>, Error> {
+            self.tree.get(uri, username).await
+        }
+
+        async fn create(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.create(uri, request_body, username).await
+        }
+
+        async fn delete(&mut self, uri: &str, username: Option<&str>) -> Result<(), Error> {
+            self.tree.delete(uri, username).await
+        }
+
+        async fn patch(
+            &mut self,
+            uri: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<&dyn Node, Error> {
+            self.tree.patch(uri, request_body, username).await
+        }
+
+        async fn action(
+            &mut self,
+            uri: &str,
+            action: &str,
+            request_body: &Map<String, Value>,
+            username: Option<&str>,
+        ) -> Result<Outcome<Option<Value>>, Error> {
+            if action != "ComputerSystem.Decommission" {
+                return self.tree.action(uri, action, request_body, username).await;
+            }
+            let task = self.tasks.start("Decommission");
+            let completed = task.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                completed.complete(Health::OK, Some(json!({"Decommissioned": true})));
+            });
+            Ok(Outcome::Async(task))
+        }
+
+        fn get_collection_types(&self) -> &[CollectionType] {
+            self.tree.get_collection_types()
+        }
+
+        fn get_resource_types(&self) -> &[ResourceType] {
+            self.tree.get_resource_types()
+        }
+    }
+
+    #[tokio::test]
+    async fn actions() {
+        let system = "/redfish/v1/Systems/1";
+        let reset = "/redfish/v1/Systems/1/Actions/ComputerSystem.Reset";
+        let decommission = "/redfish/v1/Systems/1/Actions/ComputerSystem.Decommission";
+        let action_info = "/redfish/v1/Systems/1/DecommissionActionInfo";
+        let allowed_methods = AllowedMethods {
+            delete: false,
+            get: true,
+            patch: false,
+            post: false,
+        };
+        let tree = TreeBuilder::new()
+            .resource(
+                system,
+                "System",
+                ResourceType::new_dmtf(
+                    String::from("ComputerSystem"),
+                    ResourceSchemaVersion::new(1, 20, 0),
+                ),
+                allowed_methods,
+                json!({
+                    "PowerState": "On",
+                    "Actions": {
+                        "#ComputerSystem.Reset": {
+                            "target": reset,
+                            "ResetType@Redfish.AllowableValues": ["On", "ForceOff"],
+                        },
+                        "#ComputerSystem.Decommission": {
+                            "target": decommission,
+                            "@Redfish.ActionInfo": action_info,
+                        },
+                        "Oem": {
+                            "#Contoso.Blink": {
+                                "target": "/redfish/v1/Systems/1/Actions/Oem/Contoso.Blink",
+                            },
+                        },
+                    },
+                }),
+            )
+            .resource(
+                action_info,
+                "Decommission Action Info",
+                ResourceType::new_dmtf(
+                    String::from("ActionInfo"),
+                    ResourceSchemaVersion::new(1, 4, 2),
+                ),
+                allowed_methods,
+                json!({
+                    "Parameters": [
+                        {
+                            "Name": "DecommissionTypes",
+                            "Required": true,
+                            "DataType": "StringArray",
+                            "AllowableValues": ["All", "UserData"],
+                        },
+                        {
+                            "Name": "RequireSecureErase",
+                            "DataType": "Boolean",
+                        },
+                    ],
+                }),
+            )
+            .on_action(system, "ComputerSystem.Reset", move |resources, _| {
+                resources.set(system, "PowerState", json!("Off"))?;
+                Ok(None)
+            })
+            .build();
+        let tasks = TaskService::new();
+        let tree = DecommissionTree {
+            tree,
+            tasks: tasks.clone(),
+        };
+        let app = AppBuilder::new(tree).task_service(tasks).build();
+        let (base_url, statuses) = serve(app);
+        let client = Client::new(&base_url)
+            .with_auth(admin_admin())
+            .with_poll_interval(Duration::from_millis(10));
+
+        // Actions are found in Actions, OEM ones included.
+        let actions = client.get_actions(system).await.unwrap();
+        let names: Vec<&str> = actions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "ComputerSystem.Decommission",
+                "ComputerSystem.Reset",
+                "Contoso.Blink"
+            ]
+        );
+        let (decommission, reset) = (&actions[0], &actions[1]);
+        assert_eq!(
+            reset.target,
+            "/redfish/v1/Systems/1/Actions/ComputerSystem.Reset"
+        );
+        assert_eq!(decommission.action_info.as_deref(), Some(action_info));
+
+        // Their parameters are from their ActionInfo and AllowableValues annotations.
+        assert_eq!(
+            client.get_action_parameters(reset).await.unwrap(),
+            vec![ActionParameter::new(
+                "ResetType",
+                PropertyType::Enum(vec![String::from("On"), String::from("ForceOff")])
+            )]
+        );
+        let decommission_types = vec![String::from("All"), String::from("UserData")];
+        assert_eq!(
+            client.get_action_parameters(decommission).await.unwrap(),
+            vec![
+                ActionParameter::new(
+                    "DecommissionTypes",
+                    PropertyType::Array(Box::new(PropertyType::Enum(decommission_types)))
+                )
+                .required(),
+                ActionParameter::new("RequireSecureErase", PropertyType::Boolean),
+            ]
+        );
+
+        // Parameters that do not fit are refused without invoking the action.
+        let sent = statuses.lock().unwrap().len();
+        let get_message_ids = |result| match result {
+            Err(ClientError::InvalidParameters(messages)) => messages
+                .iter()
+                .map(|m: &Message| m.to_json()["MessageId"].as_str().unwrap().to_string())
+                .collect::<Vec<String>>(),
+            other => panic!("unexpected result {:?}", other),
+        };
+        let parameters = json!({"ResetType": "Nmi"});
+        let result = client
+            .invoke_action(reset, parameters.as_object().unwrap())
+            .await;
+        assert_eq!(
+            get_message_ids(result),
+            vec!["Base.1.16.ActionParameterValueNotInList"]
+        );
+        let parameters = json!({"RequireSecureErase": "Yes", "Force": true});
+        let result = client
+            .invoke_action(decommission, parameters.as_object().unwrap())
+            .await;
+        assert_eq!(
+            get_message_ids(result),
+            vec![
+                "Base.1.16.ActionParameterMissing",
+                "Base.1.16.ActionParameterValueTypeError",
+                "Base.1.16.ActionParameterUnknown",
+            ]
+        );
+        // Each element of an array is checked.
+        let parameters = json!({"DecommissionTypes": ["All", "Firmware", 3]});
+        let result = client
+            .invoke_action(decommission, parameters.as_object().unwrap())
+            .await;
+        let Err(ClientError::InvalidParameters(messages)) = result else {
+            panic!("unexpected result {:?}", result);
+        };
+        let messages: Vec<Value> = messages.iter().map(|m| Value::from(m.to_json())).collect();
+        assert_eq!(
+            messages[0]["MessageId"],
+            "Base.1.16.ActionParameterValueNotInList"
+        );
+        assert_eq!(
+            messages[0]["MessageArgs"],
+            json!([
+                "Firmware",
+                "DecommissionTypes",
+                "ComputerSystem.Decommission"
+            ])
+        );
+        assert_eq!(
+            messages[0]["RelatedProperties"],
+            json!(["#/DecommissionTypes/1"])
+        );
+        assert_eq!(
+            messages[1]["MessageId"],
+            "Base.1.16.ActionParameterValueTypeError"
+        );
+        assert_eq!(
+            messages[1]["RelatedProperties"],
+            json!(["#/DecommissionTypes/2"])
+        );
+        assert_eq!(messages.len(), 2);
+        let posted = statuses.lock().unwrap()[sent..]
+            .iter()
+            .any(|status| *status != StatusCode::OK && *status != StatusCode::NOT_MODIFIED);
+        assert!(!posted);
+
+        // Otherwise they are invoked.
+        let parameters = json!({"ResetType": "ForceOff"});
+        let response = client
+            .invoke_action(reset, parameters.as_object().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response, None);
+        assert_eq!(client.get(system).await.unwrap()["PowerState"], "Off");
+
+        // And tasks are followed until they complete.
+        let sent = statuses.lock().unwrap().len();
+        let parameters = json!({"DecommissionTypes": ["All"], "RequireSecureErase": true});
+        let response = client
+            .invoke_action(decommission, parameters.as_object().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response, Some(json!({"Decommissioned": true})));
+        {
+            let statuses = statuses.lock().unwrap();
+            assert!(statuses[sent..].contains(&StatusCode::ACCEPTED));
+            assert_eq!(statuses.last(), Some(&StatusCode::OK));
+        }
+
+        // Unless they take longer than the client waits for.
+        let impatient = Client::new(&base_url)
+            .with_auth(admin_admin())
+            .with_poll_interval(Duration::from_millis(10))
+            .with_task_timeout(Duration::from_millis(20));
+        let result = impatient
+            .invoke_action(decommission, parameters.as_object().unwrap())
+            .await;
+        assert!(matches!(result, Err(ClientError::Timeout)));
+    }
+}
@@ -1,6 +1,14 @@
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde::Serialize;
 use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::num::NonZeroU32;
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{collections::HashMap, fmt, fs};
 use strum::{Display, EnumString};
 
@@ -12,7 +20,7 @@ pub enum Health {
     Critical,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AllowedMethods {
     pub delete: bool,
     pub get: bool,
@@ -42,7 +50,7 @@ impl fmt::Display for AllowedMethods {
 
 pub trait SchemaVersion: fmt::Display {}
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ResourceSchemaVersion {
     major: u32,
     minor: u32,
@@ -72,12 +80,7 @@ pub fn get_resource_odata_type(
     schema_version: &ResourceSchemaVersion,
     term_name: &str,
 ) -> String {
-    format!(
-        "#{}.{}.{}",
-        schema_name,
-        schema_version.to_string(),
-        term_name
-    )
+    format!("#{}.{}.{}", schema_name, schema_version, term_name)
 }
 
 #[derive(Clone, PartialEq)]
@@ -99,12 +102,62 @@ impl fmt::Display for CollectionSchemaVersion {
     }
 }
 
+// The URL of a JSON schema, such as the one describing a type in Link headers. It is checked
+// when made, so that only absolute URLs, or absolute paths on the service, can end up in them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaUrl(String);
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidSchemaUrl(pub String);
+
+impl SchemaUrl {
+    pub fn new(url: &str) -> Result<Self, InvalidSchemaUrl> {
+        let after_scheme = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"));
+        let well_formed = match after_scheme {
+            // URLs need a host.
+            Some(rest) => !rest.is_empty() && !rest.starts_with('/'),
+            // Otherwise, it has to be an absolute path on the service.
+            None => url.starts_with('/'),
+        };
+        // Characters that URLs, or the Link header, do not allow unescaped.
+        let valid_chars = url
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"<>\"\\^`{|}".contains(c));
+        match well_formed && valid_chars {
+            true => Ok(Self(String::from(url))),
+            false => Err(InvalidSchemaUrl(String::from(url))),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for SchemaUrl {
+    type Err = InvalidSchemaUrl;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        Self::new(url)
+    }
+}
+
+impl fmt::Display for SchemaUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct ResourceType {
     pub name: String,
     pub version: ResourceSchemaVersion,
     pub xml_schema_uri: String,
-    pub described_by: String,
+    pub described_by: SchemaUrl,
+    // Types of the properties clients may write, for validating their values.
+    pub properties: HashMap<String, PropertyType>,
 }
 
 impl ResourceType {
@@ -115,16 +168,25 @@ impl ResourceType {
                 "http://redfish.dmtf.org/schemas/v1/{}_v{}.xml",
                 name, version.major
             ),
-            described_by: format!(
-                "https://redfish.dmtf.org/schemas/v1/{}.{}.json",
-                name,
-                version.to_string()
-            ),
+            described_by: get_dmtf_schema_url(&format!("{}.{}", name, version)),
             name,
             version,
+            properties: HashMap::new(),
         }
     }
 
+    pub fn with_property(mut self, name: &str, property_type: PropertyType) -> Self {
+        self.properties.insert(String::from(name), property_type);
+        self
+    }
+
+    // For types whose schema is not published by the DMTF, such as OEM extensions,
+    // or that the service hosts itself under /redfish/v1/JsonSchemas.
+    pub fn with_described_by(mut self, described_by: SchemaUrl) -> Self {
+        self.described_by = described_by;
+        self
+    }
+
     fn get_versioned_name(&self) -> String {
         get_versioned_name(&self.name, &self.version)
     }
@@ -135,12 +197,157 @@ impl ResourceType {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    // A string that must be one of the given enum members.
+    Enum(Vec<String>),
+    // An array whose elements are all of the given type.
+    Array(Box<PropertyType>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PropertyValueError {
+    TypeError,
+    NotInList,
+}
+
+impl PropertyType {
+    pub fn validate(&self, value: &Value) -> Result<(), PropertyValueError> {
+        let valid_type = match self {
+            PropertyType::String | PropertyType::Enum(_) => value.is_string(),
+            PropertyType::Number => value.is_number(),
+            PropertyType::Integer => value.is_i64() || value.is_u64(),
+            PropertyType::Boolean => value.is_boolean(),
+            PropertyType::Array(_) => value.is_array(),
+        };
+        if !valid_type {
+            return Err(PropertyValueError::TypeError);
+        }
+        if let PropertyType::Enum(members) = self {
+            if !members.iter().any(|member| value == member.as_str()) {
+                return Err(PropertyValueError::NotInList);
+            }
+        }
+        if let (PropertyType::Array(element_type), Value::Array(elements)) = (self, value) {
+            for element in elements {
+                element_type.validate(element)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A parameter of an action, as the ActionInfo of the action declares it. The members of Enum
+// types are the AllowableValues of the parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionParameter {
+    pub name: String,
+    pub parameter_type: PropertyType,
+    pub required: bool,
+}
+
+impl ActionParameter {
+    pub fn new(name: &str, parameter_type: PropertyType) -> Self {
+        Self {
+            name: String::from(name),
+            parameter_type,
+            required: false,
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+// Check the body of a request to carry out the action, such as ComputerSystem.Reset, against
+// the parameters it declares. Return a message from the given Base registry for each problem,
+// such as ActionParameterMissing or ActionParameterValueNotInList, or none if there is none.
+// Fails if there is a problem the registry has no message for, as when it is not a Base one.
+pub fn validate_action_parameters(
+    registry: &MessageRegistry,
+    action: &str,
+    parameters: &[ActionParameter],
+    request_body: &Map<String, Value>,
+) -> Result<Vec<Message>, RegistryError> {
+    let get_message = |key, message_args, property: String| {
+        Message::from_registry(
+            registry,
+            key,
+            ResourceSchemaVersion::new(1, 1, 2),
+            message_args,
+            vec![format!("#/{}", property)],
+        )
+    };
+    // The message about a value of the parameter, or an element of its value if it is an
+    // array, that is not of its type.
+    let get_value_message = |error, value: &Value, name: &str, property: String| {
+        let key = match error {
+            PropertyValueError::TypeError => "ActionParameterValueTypeError",
+            PropertyValueError::NotInList => "ActionParameterValueNotInList",
+        };
+        let value = match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        let args = vec![value, String::from(name), String::from(action)];
+        get_message(key, args, property)
+    };
+    let mut messages = Vec::new();
+    for parameter in parameters {
+        let name = parameter.name.as_str();
+        let value = match request_body.get(name) {
+            Some(value) => value,
+            None => {
+                if parameter.required {
+                    let args = vec![String::from(action), String::from(name)];
+                    messages.push(get_message("ActionParameterMissing", args, name.into())?);
+                }
+                continue;
+            }
+        };
+        // Each element of an array that is not of its type is a problem of its own.
+        if let (PropertyType::Array(element_type), Value::Array(elements)) =
+            (&parameter.parameter_type, value)
+        {
+            for (index, element) in elements.iter().enumerate() {
+                if let Err(error) = element_type.validate(element) {
+                    let property = format!("{}/{}", name, index);
+                    messages.push(get_value_message(error, element, name, property)?);
+                }
+            }
+            continue;
+        }
+        if let Err(error) = parameter.parameter_type.validate(value) {
+            messages.push(get_value_message(error, value, name, name.into())?);
+        }
+    }
+    for name in request_body.keys() {
+        if !parameters.iter().any(|parameter| &parameter.name == name) {
+            let args = vec![String::from(action), name.clone()];
+            messages.push(get_message("ActionParameterUnknown", args, name.clone())?);
+        }
+    }
+    Ok(messages)
+}
+
+// Schemas are named for their types, so a type whose name is not fit for a URL is a bug.
+fn get_dmtf_schema_url(schema: &str) -> SchemaUrl {
+    let url = format!("https://redfish.dmtf.org/schemas/v1/{}.json", schema);
+    SchemaUrl::new(&url).expect("Invalid schema name")
+}
+
 #[derive(Clone, PartialEq)]
 pub struct CollectionType {
     pub name: String,
     pub version: CollectionSchemaVersion,
     pub xml_schema_uri: String,
-    pub described_by: String,
+    pub described_by: SchemaUrl,
 }
 
 impl CollectionType {
@@ -148,10 +355,9 @@ impl CollectionType {
         Self {
             xml_schema_uri: format!(
                 "http://redfish.dmtf.org/schemas/v1/{}_{}.xml",
-                name,
-                version.to_string()
+                name, version
             ),
-            described_by: format!("https://redfish.dmtf.org/schemas/v1/{}.json", name),
+            described_by: get_dmtf_schema_url(&name),
             name,
             version,
         }
@@ -196,9 +402,7 @@ pub fn get_odata_service_document(service_root: &Map<String, Value>) -> Map<Stri
     values.push(ODataServiceValue::new("/redfish/v1"));
 
     for val in service_root.values() {
-        let val = val.as_object();
-        if val.is_some() {
-            let val = val.unwrap();
+        if let Some(val) = val.as_object() {
             if val.contains_key("@odata.id") {
                 values.push(ODataServiceValue::new(val["@odata.id"].as_str().unwrap()));
             }
@@ -218,6 +422,29 @@ pub fn get_odata_service_document(service_root: &Map<String, Value>) -> Map<Stri
     res
 }
 
+// The @odata.context of a resource or collection, given its @odata.type, such as
+// /redfish/v1/$metadata#Chassis.Chassis for #Chassis.v1_22_0.Chassis.
+pub fn get_odata_context(odata_type: &str) -> Option<String> {
+    let odata_type = odata_type.strip_prefix('#')?;
+    let (namespace, _) = odata_type.split_once('.')?;
+    let (_, term_name) = odata_type.rsplit_once('.')?;
+    Some(format!("/redfish/v1/$metadata#{}.{}", namespace, term_name))
+}
+
+// Add an @odata.context to a body that has an @odata.type, and does not have a context already.
+pub fn add_odata_context(body: &mut Map<String, Value>) {
+    if body.contains_key("@odata.context") {
+        return;
+    }
+    let context = match body.get("@odata.type").and_then(|t| t.as_str()) {
+        Some(odata_type) => get_odata_context(odata_type),
+        None => None,
+    };
+    if let Some(context) = context {
+        body.insert(String::from("@odata.context"), Value::String(context));
+    }
+}
+
 pub fn get_odata_metadata_document(
     collection_types: &[CollectionType],
     resource_types: &[ResourceType],
@@ -238,13 +465,13 @@ pub fn get_odata_metadata_document(
     );
     body.push_str("    <edmx:Include Namespace=\"RedfishExtensions.v1_0_0\" Alias=\"Redfish\"/>\n");
     body.push_str("  </edmx:Reference>\n");
-    if service_root_type.is_some() {
+    if let Some(service_root_type) = service_root_type {
         body.push_str("  <edmx:DataServices>\n");
         body.push_str("    <Schema xmlns=\"http://docs.oasis-open.org/odata/ns/edm\" Namespace=\"Service\">\n");
         body.push_str(
             format!(
                 "      <EntityContainer Name=\"Service\" Extends=\"{}.ServiceContainer\" />\n",
-                service_root_type.unwrap().get_versioned_name()
+                service_root_type.get_versioned_name()
             )
             .as_str(),
         );
@@ -267,21 +494,321 @@ pub fn get_uri_id(uri: &str) -> String {
     }
 }
 
+// The name of the type of the resource, such as ComputerSystem for a @odata.type of
+// #ComputerSystem.v1_20_0.ComputerSystem.
+pub fn get_type_name(body: &Value) -> Option<&str> {
+    let odata_type = body.get("@odata.type")?.as_str()?;
+    odata_type.strip_prefix('#')?.split('.').next()
+}
+
+// The URIs of the resources of the service the body links to with @odata.id, without
+// fragments, such as those of the members of arrays like Members. Each is given once, in the
+// order they are in the body.
+pub fn get_resource_links(body: &Value) -> Vec<String> {
+    let mut links = Vec::new();
+    add_resource_links(body, &mut links);
+    links
+}
+
+fn add_resource_links(body: &Value, links: &mut Vec<String>) {
+    match body {
+        Value::Object(object) => {
+            for (key, value) in object.iter() {
+                match (key.as_str(), value) {
+                    ("@odata.id", Value::String(link)) => {
+                        let link = link.split('#').next().unwrap_or_default();
+                        let link = link.trim_end_matches('/');
+                        if link.starts_with("/redfish/v1") && !links.iter().any(|l| l == link) {
+                            links.push(String::from(link));
+                        }
+                    }
+                    _ => add_resource_links(value, links),
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| add_resource_links(value, links)),
+        _ => (),
+    }
+}
+
+// The properties of each kind of resource that make it into an inventory, by the name they have
+// there. Those a resource lacks are null.
+const SYSTEM_PROPERTIES: [&str; 7] = [
+    "Name",
+    "Manufacturer",
+    "Model",
+    "SerialNumber",
+    "PowerState",
+    "BiosVersion",
+    "Status/Health",
+];
+const CHASSIS_PROPERTIES: [&str; 6] = [
+    "Name",
+    "ChassisType",
+    "Manufacturer",
+    "Model",
+    "SerialNumber",
+    "Status/Health",
+];
+
+// A normalized inventory of the systems, chassis and firmware of a service, for comparing
+// services with each other or over time. It is made of the resources read from the service,
+// however they are read. Firmware is that of SoftwareInventory resources, and of each Manager
+// itself.
+#[derive(Default)]
+pub struct Inventory {
+    systems: Vec<Value>,
+    chassis: Vec<Value>,
+    firmware: Vec<Value>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add the resource at the URI, if it is of a kind the inventory covers.
+    pub fn add(&mut self, uri: &str, body: &Value) {
+        match get_type_name(body) {
+            Some("ComputerSystem") => {
+                self.systems
+                    .push(get_inventory_entry(uri, body, &SYSTEM_PROPERTIES))
+            }
+            Some("Chassis") => {
+                self.chassis
+                    .push(get_inventory_entry(uri, body, &CHASSIS_PROPERTIES))
+            }
+            Some("SoftwareInventory") => {
+                self.firmware.push(get_firmware_entry(uri, body, "Version"))
+            }
+            Some("Manager") if body.get("FirmwareVersion").is_some() => self
+                .firmware
+                .push(get_firmware_entry(uri, body, "FirmwareVersion")),
+            _ => (),
+        }
+    }
+
+    // Each list is sorted by URI, whatever order the resources were added in.
+    pub fn to_json(&self) -> Value {
+        let sorted = |entries: &Vec<Value>| {
+            let mut entries = entries.clone();
+            entries.sort_by(|a, b| a["Uri"].as_str().cmp(&b["Uri"].as_str()));
+            entries
+        };
+        json!({
+            "Systems": sorted(&self.systems),
+            "Chassis": sorted(&self.chassis),
+            "Firmware": sorted(&self.firmware),
+        })
+    }
+}
+
+// Pick the properties out of the body, flattening paths such as Status/Health to Health.
+fn get_inventory_entry(uri: &str, body: &Value, properties: &[&str]) -> Value {
+    let mut entry = Map::new();
+    entry.insert(String::from("Uri"), json!(uri));
+    entry.insert(
+        String::from("Id"),
+        body.get("Id").cloned().unwrap_or(Value::Null),
+    );
+    for path in properties {
+        let value = path
+            .split('/')
+            .try_fold(body, |value, name| value.get(name))
+            .cloned()
+            .unwrap_or(Value::Null);
+        let name = path.rsplit('/').next().unwrap_or(path);
+        entry.insert(String::from(name), value);
+    }
+    Value::Object(entry)
+}
+
+fn get_firmware_entry(uri: &str, body: &Value, version: &str) -> Value {
+    json!({
+        "Uri": uri,
+        "Name": body.get("Name").cloned().unwrap_or(Value::Null),
+        "Version": body.get(version).cloned().unwrap_or(Value::Null),
+        "Updateable": body.get("Updateable").cloned().unwrap_or(Value::Null),
+    })
+}
+
 pub fn get_versioned_name(name: &str, version: &dyn SchemaVersion) -> String {
-    format!("{}.{}", name, version.to_string())
+    format!("{}.{}", name, version)
+}
+
+// Format a point in time as a Redfish DateTimeOffset string, in UTC.
+pub fn get_datetime_string(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Convert days since epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00:00",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+// A single record within an Event, wrapping a message from a registry.
+#[derive(Clone)]
+pub struct EventRecord {
+    event_id: String,
+    timestamp: SystemTime,
+    message: Message,
+    origin_of_condition: Option<String>,
+    log_entry: Option<String>,
+}
+
+impl EventRecord {
+    // The timestamp is when the event happened, as the clock of the service tells it.
+    pub fn new(
+        event_id: String,
+        timestamp: SystemTime,
+        message: Message,
+        origin_of_condition: Option<String>,
+    ) -> Self {
+        Self {
+            event_id,
+            timestamp,
+            message,
+            origin_of_condition,
+            log_entry: None,
+        }
+    }
+
+    // Link the record to the LogEntry it corresponds to.
+    pub fn with_log_entry(mut self, log_entry: String) -> Self {
+        self.log_entry = Some(log_entry);
+        self
+    }
+
+    pub fn get_message_id(&self) -> &str {
+        self.message.id.as_str()
+    }
+
+    pub fn get_origin_of_condition(&self) -> Option<&str> {
+        self.origin_of_condition.as_deref()
+    }
+
+    pub fn to_json(&self, member_id: usize) -> Map<String, Value> {
+        let mut res = Map::new();
+        res.insert(
+            String::from("MemberId"),
+            Value::String(member_id.to_string()),
+        );
+        res.insert(
+            String::from("EventId"),
+            Value::String(self.event_id.clone()),
+        );
+        res.insert(
+            String::from("EventTimestamp"),
+            Value::String(get_datetime_string(self.timestamp)),
+        );
+        res.insert(
+            String::from("MessageId"),
+            Value::String(self.message.id.clone()),
+        );
+        res.insert(
+            String::from("Message"),
+            Value::String(self.message.message.clone()),
+        );
+        res.insert(
+            String::from("MessageArgs"),
+            serde_json::to_value(self.message.message_args.clone()).unwrap(),
+        );
+        res.insert(
+            String::from("MessageSeverity"),
+            Value::String(self.message.severity.to_string()),
+        );
+        if let Some(origin) = &self.origin_of_condition {
+            res.insert(
+                String::from("OriginOfCondition"),
+                json!({ "@odata.id": origin }),
+            );
+        }
+        if let Some(log_entry) = &self.log_entry {
+            res.insert(String::from("LogEntry"), json!({ "@odata.id": log_entry }));
+        }
+        res
+    }
+}
+
+// An Event payload, as delivered to event subscribers.
+#[derive(Clone)]
+pub struct Event {
+    version: ResourceSchemaVersion,
+    id: String,
+    records: Vec<EventRecord>,
+}
+
+impl Event {
+    pub fn new(version: ResourceSchemaVersion, id: String, records: Vec<EventRecord>) -> Self {
+        Self {
+            version,
+            id,
+            records,
+        }
+    }
+
+    pub fn get_id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn get_records(&self) -> &[EventRecord] {
+        &self.records
+    }
+
+    // Keep only the records for which the function returns true, such as those a subscriber
+    // asked for.
+    pub fn retain_records<F: FnMut(&EventRecord) -> bool>(&mut self, f: F) {
+        self.records.retain(f);
+    }
+
+    pub fn to_json(&self) -> Map<String, Value> {
+        let mut records = Vec::new();
+        for (idx, record) in self.records.iter().enumerate() {
+            records.push(Value::Object(record.to_json(idx)));
+        }
+        let mut res = Map::new();
+        res.insert(
+            String::from("@odata.type"),
+            Value::String(get_resource_odata_type("Event", &self.version, "Event")),
+        );
+        res.insert(String::from("Id"), Value::String(self.id.clone()));
+        res.insert(String::from("Name"), Value::String(String::from("Event")));
+        res.insert(String::from("Events"), Value::Array(records));
+        res
+    }
 }
 
+#[derive(Clone, Debug)]
 pub struct ErrorResponse {
     code: String,
     message: String,
     extended_info: Vec<Message>,
+    // Added to the error object, besides the properties every error has.
+    properties: Map<String, Value>,
 }
 
 impl ErrorResponse {
     pub fn from_registry(
         registry: &MessageRegistry,
         key: &str,
-        message_args: &Vec<String>,
+        message_args: &[String],
         extended_info: Vec<Message>,
     ) -> Self {
         let message_definition = registry.get_message_definition(key).unwrap();
@@ -289,16 +816,43 @@ impl ErrorResponse {
             code: registry.get_message_id(key),
             message: message_definition.get_message(message_args),
             extended_info,
+            properties: Map::new(),
         }
     }
 
+    pub fn get_code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn add_extended_info(&mut self, message: Message) {
+        self.extended_info.push(message);
+    }
+
+    // Add a property to the error object, such as an OEM one. Properties named like the ones
+    // every error has are left out.
+    pub fn insert_property(&mut self, name: String, value: Value) {
+        self.properties.insert(name, value);
+    }
+
     pub fn to_json(&self) -> Map<String, Value> {
+        self.to_json_with(Message::to_json)
+    }
+
+    // Like to_json(), but with the Language of each message, for services that localize them.
+    pub fn to_localized_json(&self) -> Map<String, Value> {
+        self.to_json_with(Message::to_localized_json)
+    }
+
+    fn to_json_with(
+        &self,
+        message_to_json: fn(&Message) -> Map<String, Value>,
+    ) -> Map<String, Value> {
         let mut extended_info = Vec::new();
         for message in &self.extended_info {
-            extended_info.push(Value::Object(message.to_json()));
+            extended_info.push(Value::Object(message_to_json(message)));
         }
 
-        let mut error = Map::new();
+        let mut error = self.properties.clone();
         error.insert(String::from("code"), Value::String(self.code.clone()));
         error.insert(String::from("message"), Value::String(self.message.clone()));
         error.insert(
@@ -320,6 +874,7 @@ pub enum RegistryError {
 }
 
 // TODO: How to avoid implicit revlock to Message schema version at the time I write this?
+#[derive(Clone, Debug)]
 pub struct Message {
     // TODO: Allow OEM? How?
     version: ResourceSchemaVersion,
@@ -330,6 +885,8 @@ pub struct Message {
     message_args: Vec<String>,
     severity: Health,
     resolution: String,
+    // Language of the registry the message came from.
+    language: String,
 }
 
 impl Message {
@@ -353,9 +910,27 @@ impl Message {
             message_args,
             severity: message_definition.severity.clone(),
             resolution: message_definition.resolution.clone(),
+            language: String::from(registry.get_language()),
         })
     }
 
+    // Override the severity from the registry, for messages whose severity is configured by the user.
+    pub fn with_severity(mut self, severity: Health) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    // Override the resolution from the registry, for telling the client more about this case.
+    pub fn with_resolution(mut self, resolution: String) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    // The JSON pointers of the properties the message is about, such as #/SessionTimeout.
+    pub fn get_related_properties(&self) -> &[String] {
+        &self.related_properties
+    }
+
     //TODO: Give option to include deprecated Severity?
     //TODO: If I want to provide different variations of this, give more specific names?
     pub fn to_json(&self) -> Map<String, Value> {
@@ -384,6 +959,16 @@ impl Message {
         );
         res
     }
+
+    // Like to_json(), but with the Language of the message, for services that localize them.
+    pub fn to_localized_json(&self) -> Map<String, Value> {
+        let mut res = self.to_json();
+        res.insert(
+            String::from("Language"),
+            Value::String(self.language.clone()),
+        );
+        res
+    }
 }
 
 pub struct MessageDefinition {
@@ -404,9 +989,9 @@ impl MessageDefinition {
         }
     }
 
-    fn get_message(&self, message_args: &Vec<String>) -> String {
+    fn get_message(&self, message_args: &[String]) -> String {
+        debug_assert_eq!(message_args.len() as u64, self.number_of_args);
         let mut message = self.message.clone();
-        //FIXME: Assert right number of args
         for (idx, arg) in message_args.iter().enumerate() {
             //FIXME: Ensure this finds something?
             let from = format!("%{}", idx + 1);
@@ -419,14 +1004,20 @@ impl MessageDefinition {
 pub struct MessageRegistry {
     prefix: String,
     version: ResourceSchemaVersion,
+    language: String,
     message_definitions: HashMap<String, MessageDefinition>,
 }
 
 impl MessageRegistry {
     pub fn from_file(path: &str) -> Self {
         let data = fs::read_to_string(path).expect("Unable to read file");
+        Self::from_json(&data)
+    }
+
+    // Parse a message registry from the JSON text of a registry file.
+    pub fn from_json(data: &str) -> Self {
         let data: Map<String, Value> =
-            serde_json::from_str(&data).expect("Unable to parse message registry file");
+            serde_json::from_str(data).expect("Unable to parse message registry file");
         let version_str = data.get("RegistryVersion").unwrap().as_str().unwrap();
         let version_parts: Vec<&str> = version_str.split(".").collect();
         let mut message_definitions = HashMap::new();
@@ -443,6 +1034,7 @@ impl MessageRegistry {
                 version_parts[1].parse().unwrap(),
                 version_parts[2].parse().unwrap(),
             ),
+            language: String::from(data.get("Language").unwrap().as_str().unwrap()),
             message_definitions,
         }
     }
@@ -451,6 +1043,11 @@ impl MessageRegistry {
         self.message_definitions.get(key)
     }
 
+    // The RFC 5646 language tag of the registry, such as "en" or "de-DE".
+    pub fn get_language(&self) -> &str {
+        &self.language
+    }
+
     pub fn get_message_id(&self, key: &str) -> String {
         format!(
             "{}.{}.{}.{}",
@@ -459,63 +1056,2007 @@ impl MessageRegistry {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
+// The DMTF Base registry, for the standard messages of error and success responses, as a
+// service gives them and a client checks requests against them.
+pub fn base_registry() -> &'static MessageRegistry {
+    static REGISTRY: OnceLock<MessageRegistry> = OnceLock::new();
+    REGISTRY
+        .get_or_init(|| MessageRegistry::from_json(include_str!("../registries/Base.1.16.0.json")))
+}
 
-    fn get_base_registry() -> MessageRegistry {
-        let mut path = env::var("CARGO_MANIFEST_DIR").unwrap();
-        path.push_str("/../dmtf/Base.1.16.0.json");
-        MessageRegistry::from_file(&path)
+// For each operation, such as PATCH, the sets of privileges, any of which allows it.
+type OperationMap = HashMap<String, Vec<Vec<String>>>;
+
+fn parse_operation_map(value: &Value) -> OperationMap {
+    let mut operations = HashMap::new();
+    for (operation, sets) in value.as_object().unwrap() {
+        let sets = sets
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|set| {
+                set.get("Privilege")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|privilege| String::from(privilege.as_str().unwrap()))
+                    .collect()
+            })
+            .collect();
+        operations.insert(operation.clone(), sets);
     }
+    operations
+}
 
-    #[test]
-    fn message_registry() {
-        let registry = get_base_registry();
-        assert_eq!(registry.prefix, String::from("Base"));
-        assert_eq!(registry.version.major, 1);
-        assert_eq!(registry.version.minor, 16);
-        assert_eq!(registry.version.build, 0);
-        let success = registry.message_definitions.get("Success").unwrap();
-        assert_eq!(success.severity, Health::OK);
+// An override of the operations of an entity, for the properties, subordinate resources or
+// URIs that are its targets.
+struct MappingOverride {
+    targets: Vec<String>,
+    operations: OperationMap,
+}
+
+fn parse_overrides(mapping: &Value, name: &str) -> Vec<MappingOverride> {
+    let overrides = match mapping.get(name).and_then(Value::as_array) {
+        Some(overrides) => overrides,
+        None => return Vec::new(),
+    };
+    overrides
+        .iter()
+        .map(|value| MappingOverride {
+            targets: value
+                .get("Targets")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|target| String::from(target.as_str().unwrap()))
+                .collect(),
+            operations: parse_operation_map(value.get("OperationMap").unwrap()),
+        })
+        .collect()
+}
+
+// Whether the entities appear in the ancestors in the same order, if not next to each other.
+fn is_subordinate_to(ancestors: &[String], entities: &[String]) -> bool {
+    let mut ancestors = ancestors.iter();
+    entities
+        .iter()
+        .all(|entity| ancestors.any(|ancestor| ancestor == entity))
+}
+
+struct EntityMapping {
+    operations: OperationMap,
+    properties: Vec<MappingOverride>,
+    subordinates: Vec<MappingOverride>,
+    uris: Vec<MappingOverride>,
+}
+
+// What an operation is carried out on, for finding which of the mappings of a PrivilegeRegistry
+// apply to it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrivilegeTarget {
+    // The entity of the resource, such as ManagerAccount.
+    pub entity: String,
+    // The URI of the resource, for ResourceURIOverrides.
+    pub uri: String,
+    // The entities of the resources it is subordinate to, from the service root down, such as
+    // ServiceRoot, ManagerCollection, Manager and EthernetInterfaceCollection, for
+    // SubordinateOverrides.
+    pub ancestors: Vec<String>,
+    // The properties the operation writes, such as those in the body of a PATCH, for
+    // PropertyOverrides.
+    pub properties: Vec<String>,
+}
+
+impl PrivilegeTarget {
+    pub fn new(entity: &str, uri: &str) -> Self {
+        Self {
+            entity: String::from(entity),
+            uri: String::from(uri),
+            ..Default::default()
+        }
     }
 
-    #[test]
-    fn message() {
-        let registry = get_base_registry();
-        let message = Message::from_registry(
-            &registry,
-            "PropertyValueTypeError",
-            ResourceSchemaVersion::new(1, 1, 2),
-            vec![String::from("300"), String::from("SessionTimeout")],
-            vec![String::from("/SessionTimeout")],
-        )
-        .unwrap();
-        let jsonified = message.to_json();
-        assert_eq!(&jsonified, json!({
-            "@odata.type": "#Message.v1_1_2.Message",
-            "MessageId": "Base.1.16.PropertyValueTypeError",
-            "RelatedProperties": ["/SessionTimeout"],
-            "Message": "The value '300' for the property SessionTimeout is of a different type than the property can accept.",
-            "MessageArgs": ["300", "SessionTimeout"],
-            "MessageSeverity": "Warning",
-            "Resolution": "Correct the value for the property in the request body and resubmit the request if the operation failed."
-        }).as_object().unwrap());
+    pub fn with_ancestors(mut self, ancestors: Vec<String>) -> Self {
+        self.ancestors = ancestors;
+        self
     }
 
-    #[test]
-    fn error_response() {
-        let registry = get_base_registry();
-        let message = Message::from_registry(
-            &registry,
-            "PropertyValueTypeError",
-            ResourceSchemaVersion::new(1, 1, 2),
-            vec![String::from("300"), String::from("SessionTimeout")],
+    pub fn with_properties(mut self, properties: Vec<String>) -> Self {
+        self.properties = properties;
+        self
+    }
+}
+
+// Which privileges allow each operation, such as PATCH, on resources of each entity, such as
+// ComputerSystem, as a PrivilegeRegistry maps them, with the overrides of those mappings for
+// particular URIs, subordinate resources and properties.
+pub struct PrivilegeRegistry {
+    id: String,
+    body: Value,
+    mappings: HashMap<String, EntityMapping>,
+}
+
+impl PrivilegeRegistry {
+    // Parse a privilege registry from the JSON text of a registry file.
+    pub fn from_json(data: &str) -> Self {
+        let body: Value =
+            serde_json::from_str(data).expect("Unable to parse privilege registry file");
+        let mut mappings = HashMap::new();
+        for mapping in body.get("Mappings").unwrap().as_array().unwrap() {
+            let entity = mapping.get("Entity").unwrap().as_str().unwrap();
+            let entity_mapping = EntityMapping {
+                operations: parse_operation_map(mapping.get("OperationMap").unwrap()),
+                properties: parse_overrides(mapping, "PropertyOverrides"),
+                subordinates: parse_overrides(mapping, "SubordinateOverrides"),
+                uris: parse_overrides(mapping, "ResourceURIOverrides"),
+            };
+            mappings.insert(String::from(entity), entity_mapping);
+        }
+        Self {
+            id: String::from(body.get("Id").unwrap().as_str().unwrap()),
+            body,
+            mappings,
+        }
+    }
+
+    // Such as Redfish_1.5.0_PrivilegeRegistry.
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    // The registry as it was parsed, for serving to clients.
+    pub fn get_body(&self) -> &Value {
+        &self.body
+    }
+
+    // Whether any mapping has SubordinateOverrides, without which the ancestors of targets need
+    // not be found.
+    pub fn has_subordinate_overrides(&self) -> bool {
+        self.mappings
+            .values()
+            .any(|mapping| !mapping.subordinates.is_empty())
+    }
+
+    // The sets of privileges, any of which allows the operation on the resource, or None if the
+    // registry does not map it. A ResourceURIOverride for its URI takes precedence, then the
+    // SubordinateOverride with the most of its ancestors as targets, then the mapping of its
+    // entity. Overrides only apply to the operations they map.
+    pub fn get_required_privileges(
+        &self,
+        target: &PrivilegeTarget,
+        operation: &str,
+    ) -> Option<&[Vec<String>]> {
+        let mapping = self.mappings.get(&target.entity)?;
+        let uri_override = mapping
+            .uris
+            .iter()
+            .filter(|o| o.targets.contains(&target.uri))
+            .filter_map(|o| o.operations.get(operation))
+            .next();
+        let subordinate_override = mapping
+            .subordinates
+            .iter()
+            .filter(|o| is_subordinate_to(&target.ancestors, &o.targets))
+            .filter_map(|o| Some((o.targets.len(), o.operations.get(operation)?)))
+            .max_by_key(|(count, _)| *count)
+            .map(|(_, sets)| sets);
+        uri_override
+            .or(subordinate_override)
+            .or(mapping.operations.get(operation))
+            .map(|sets| sets.as_slice())
+    }
+
+    // The sets of privileges, any of which allows the operation to write the property of the
+    // resource, if a PropertyOverride maps it, such as PATCH of the Password of a
+    // ManagerAccount.
+    pub fn get_property_privileges(
+        &self,
+        target: &PrivilegeTarget,
+        property: &str,
+        operation: &str,
+    ) -> Option<&[Vec<String>]> {
+        self.mappings
+            .get(&target.entity)?
+            .properties
+            .iter()
+            .filter(|o| o.targets.iter().any(|target| target == property))
+            .find_map(|o| o.operations.get(operation))
+            .map(|sets| sets.as_slice())
+    }
+
+    // Whether a user with the privileges may carry out the operation on the target. Each of its
+    // properties that a PropertyOverride maps needs the privileges it says, and the operation
+    // needs those of the resource only if it writes others too, or none in particular.
+    // Operations the registry does not map need Login to read, by GET or HEAD, and
+    // ConfigureComponents otherwise, as most of those it maps do.
+    pub fn allows(&self, target: &PrivilegeTarget, operation: &str, privileges: &[String]) -> bool {
+        let has = |privilege: &String| privileges.contains(privilege);
+        let has_any = |sets: &[Vec<String>]| sets.iter().any(|set| set.iter().all(has));
+        let mut needs_resource = target.properties.is_empty();
+        for property in &target.properties {
+            match self.get_property_privileges(target, property, operation) {
+                Some(sets) if !has_any(sets) => return false,
+                Some(_) => {}
+                None => needs_resource = true,
+            }
+        }
+        if !needs_resource {
+            return true;
+        }
+        match self.get_required_privileges(target, operation) {
+            Some(sets) => has_any(sets),
+            None => match operation {
+                "GET" | "HEAD" => has(&String::from("Login")),
+                _ => has(&String::from("ConfigureComponents")),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticSeverity {
+    // The registry would fail to load, or produce broken messages.
+    Error,
+    // The registry would load, but does not follow Redfish conventions.
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegistryDiagnostic {
+    pub severity: DiagnosticSeverity,
+    // Key of the message the problem is in, if it is not about the registry as a whole.
+    pub message_key: Option<String>,
+    pub description: String,
+}
+
+impl RegistryDiagnostic {
+    fn new(severity: DiagnosticSeverity, message_key: Option<&str>, description: String) -> Self {
+        Self {
+            severity,
+            message_key: message_key.map(String::from),
+            description,
+        }
+    }
+}
+
+fn is_registry_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.is_ascii_alphabetic() && chars.all(|c| c.is_ascii_alphanumeric()),
+        None => false,
+    }
+}
+
+// Find the %N placeholders in a message string.
+fn get_message_placeholders(message: &str) -> Vec<u64> {
+    let mut placeholders = Vec::new();
+    let mut rest = message;
+    while let Some(start) = rest.find('%') {
+        rest = &rest[start + 1..];
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if let Ok(index) = digits.parse() {
+            placeholders.push(index);
+        }
+    }
+    placeholders
+}
+
+// Check the JSON text of a custom message registry for problems, before loading it with
+// MessageRegistry::from_json(), which panics on most of them.
+// A registry with no diagnostics of severity Error is safe to load.
+pub fn lint_message_registry(data: &str) -> Vec<RegistryDiagnostic> {
+    use DiagnosticSeverity::{Error, Warning};
+    let mut diagnostics = Vec::new();
+    let data: Map<String, Value> = match serde_json::from_str(data) {
+        Ok(data) => data,
+        Err(error) => {
+            let description = format!("Registry is not a JSON object: {}", error);
+            return vec![RegistryDiagnostic::new(Error, None, description)];
+        }
+    };
+
+    let prefix = data.get("RegistryPrefix").and_then(|v| v.as_str());
+    match prefix {
+        None => diagnostics.push(RegistryDiagnostic::new(
+            Error,
+            None,
+            String::from("RegistryPrefix is missing or not a string"),
+        )),
+        Some(prefix) if !is_registry_identifier(prefix) => {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                None,
+                format!("RegistryPrefix {} is not alphanumeric", prefix),
+            ))
+        }
+        Some(_) => (),
+    }
+    let version = data.get("RegistryVersion").and_then(|v| v.as_str());
+    let version_is_valid = version.is_some_and(|version| {
+        let parts: Vec<&str> = version.split('.').collect();
+        parts.len() == 3 && parts.iter().all(|part| part.parse::<u32>().is_ok())
+    });
+    if !version_is_valid {
+        diagnostics.push(RegistryDiagnostic::new(
+            Error,
+            None,
+            String::from("RegistryVersion is missing or not of the form major.minor.errata"),
+        ));
+    }
+    if let (Some(prefix), Some(version)) = (prefix, version) {
+        let expected = format!("{}.{}", prefix, version);
+        if data.get("Id").and_then(|v| v.as_str()) != Some(expected.as_str()) {
+            diagnostics.push(RegistryDiagnostic::new(
+                Warning,
+                None,
+                format!("Id should be {}", expected),
+            ));
+        }
+    }
+
+    let messages = match data.get("Messages").and_then(|v| v.as_object()) {
+        Some(messages) => messages,
+        None => {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                None,
+                String::from("Messages is missing or not an object"),
+            ));
+            return diagnostics;
+        }
+    };
+    for (name, message) in messages {
+        let key = Some(name.as_str());
+        if !is_registry_identifier(name) {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                key,
+                String::from("Message key is not alphanumeric"),
+            ));
+        }
+        let message = match message.as_object() {
+            Some(message) => message,
+            None => {
+                diagnostics.push(RegistryDiagnostic::new(
+                    Error,
+                    key,
+                    String::from("Message is not an object"),
+                ));
+                continue;
+            }
+        };
+        match message.get("MessageSeverity").and_then(|v| v.as_str()) {
+            Some(severity) if Health::from_str(severity).is_ok() => (),
+            _ => diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                key,
+                String::from("MessageSeverity is missing or not one of OK, Warning or Critical"),
+            )),
+        }
+        if message.get("Resolution").and_then(|v| v.as_str()).is_none() {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                key,
+                String::from("Resolution is missing or not a string"),
+            ));
+        }
+        let number_of_args = message.get("NumberOfArgs").and_then(|v| v.as_u64());
+        if number_of_args.is_none() {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                key,
+                String::from("NumberOfArgs is missing or not a non-negative integer"),
+            ));
+        }
+        let text = message.get("Message").and_then(|v| v.as_str());
+        if text.is_none() {
+            diagnostics.push(RegistryDiagnostic::new(
+                Error,
+                key,
+                String::from("Message is missing or not a string"),
+            ));
+        }
+        let (Some(number_of_args), Some(text)) = (number_of_args, text) else {
+            continue;
+        };
+        let placeholders = get_message_placeholders(text);
+        for placeholder in &placeholders {
+            if *placeholder == 0 || *placeholder > number_of_args {
+                diagnostics.push(RegistryDiagnostic::new(
+                    Error,
+                    key,
+                    format!(
+                        "Placeholder %{} is out of range for {} args",
+                        placeholder, number_of_args
+                    ),
+                ));
+            }
+        }
+        for arg in 1..=number_of_args {
+            if !placeholders.contains(&arg) {
+                diagnostics.push(RegistryDiagnostic::new(
+                    Warning,
+                    key,
+                    format!("Arg {} is not used in the message", arg),
+                ));
+            }
+        }
+        match message.get("ParamTypes").and_then(|v| v.as_array()) {
+            None if number_of_args > 0 => diagnostics.push(RegistryDiagnostic::new(
+                Warning,
+                key,
+                String::from("ParamTypes is missing"),
+            )),
+            None => (),
+            Some(types) => {
+                if types.len() as u64 != number_of_args {
+                    diagnostics.push(RegistryDiagnostic::new(
+                        Error,
+                        key,
+                        format!(
+                            "ParamTypes has {} entries but NumberOfArgs is {}",
+                            types.len(),
+                            number_of_args
+                        ),
+                    ));
+                }
+                if !types
+                    .iter()
+                    .all(|t| t.as_str() == Some("string") || t.as_str() == Some("number"))
+                {
+                    diagnostics.push(RegistryDiagnostic::new(
+                        Error,
+                        key,
+                        String::from("ParamTypes entries must be string or number"),
+                    ));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+// The format of a MAC address in Redfish, such as 00:1A:2B:3C:4D:5E. Dashes may separate the
+// octets instead of colons.
+pub fn is_mac_address(address: &str) -> bool {
+    let octets: Vec<&str> = address.split([':', '-']).collect();
+    octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// Whether the address is a subnet mask, such as 255.255.255.0, whose ones all come first.
+pub fn is_subnet_mask(mask: Ipv4Addr) -> bool {
+    let mask = u32::from(mask);
+    mask.leading_ones() + mask.trailing_zeros() == 32
+}
+
+// Why the properties of an EthernetInterface, or a patch of them, are not valid. Each names the
+// property, such as IPv4StaticAddresses/0/Address.
+#[derive(Debug, PartialEq)]
+pub enum EthernetInterfaceError {
+    TypeError(String),
+    NotInList(String),
+    // The property is an address, such as a MAC or IPv4 address, or a subnet mask, that is not
+    // in the format it should be.
+    InvalidAddress(String),
+}
+
+// How an address of an EthernetInterface was assigned, as its AddressOrigin says.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum IPv4AddressOrigin {
+    Static,
+    DHCP,
+    BOOTP,
+    IPv4LinkLocal,
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum IPv6AddressOrigin {
+    Static,
+    DHCPv6,
+    LinkLocal,
+    SLAAC,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IPv4Address {
+    pub address: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub gateway: Option<Ipv4Addr>,
+    pub origin: IPv4AddressOrigin,
+}
+
+impl IPv4Address {
+    fn to_json(&self) -> Value {
+        json!({
+            "Address": self.address.to_string(),
+            "SubnetMask": self.subnet_mask.to_string(),
+            "Gateway": self.gateway.map(|gateway| gateway.to_string()),
+            "AddressOrigin": self.origin.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IPv6Address {
+    pub address: Ipv6Addr,
+    pub prefix_length: u8,
+    pub origin: IPv6AddressOrigin,
+}
+
+impl IPv6Address {
+    fn to_json(&self) -> Value {
+        json!({
+            "Address": self.address.to_string(),
+            "PrefixLength": self.prefix_length,
+            "AddressOrigin": self.origin.to_string(),
+        })
+    }
+}
+
+// The DHCPv4 property of an EthernetInterface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DHCPv4Configuration {
+    pub dhcp_enabled: bool,
+    pub use_dns_servers: bool,
+    pub use_gateway: bool,
+    pub use_ntp_servers: bool,
+}
+
+impl Default for DHCPv4Configuration {
+    fn default() -> Self {
+        Self {
+            dhcp_enabled: true,
+            use_dns_servers: true,
+            use_gateway: true,
+            use_ntp_servers: true,
+        }
+    }
+}
+
+// The DHCPv6 property of an EthernetInterface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DHCPv6Configuration {
+    // Such as Enabled or Disabled.
+    pub operating_mode: DHCPv6OperatingMode,
+    pub use_dns_servers: bool,
+    pub use_ntp_servers: bool,
+}
+
+impl Default for DHCPv6Configuration {
+    fn default() -> Self {
+        Self {
+            operating_mode: DHCPv6OperatingMode::Enabled,
+            use_dns_servers: true,
+            use_ntp_servers: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum DHCPv6OperatingMode {
+    Enabled,
+    Disabled,
+}
+
+fn get_bool(value: &Value, path: &str) -> Result<bool, EthernetInterfaceError> {
+    value
+        .as_bool()
+        .ok_or_else(|| EthernetInterfaceError::TypeError(String::from(path)))
+}
+
+fn get_object<'a>(
+    value: &'a Value,
+    path: &str,
+) -> Result<&'a Map<String, Value>, EthernetInterfaceError> {
+    value
+        .as_object()
+        .ok_or_else(|| EthernetInterfaceError::TypeError(String::from(path)))
+}
+
+fn get_address<T: FromStr>(value: &Value, path: &str) -> Result<T, EthernetInterfaceError> {
+    let address = value
+        .as_str()
+        .ok_or_else(|| EthernetInterfaceError::TypeError(String::from(path)))?;
+    address
+        .parse()
+        .map_err(|_| EthernetInterfaceError::InvalidAddress(String::from(path)))
+}
+
+// Parse each object of an array, such as IPv4StaticAddresses, given its path.
+fn get_array_of<T>(
+    value: &Value,
+    path: &str,
+    parse: impl Fn(&Map<String, Value>, &str) -> Result<T, EthernetInterfaceError>,
+) -> Result<Vec<T>, EthernetInterfaceError> {
+    let elements = value
+        .as_array()
+        .ok_or_else(|| EthernetInterfaceError::TypeError(String::from(path)))?;
+    elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let path = format!("{}/{}", path, index);
+            parse(get_object(element, &path)?, &path)
+        })
+        .collect()
+}
+
+fn get_static_ipv4_address(
+    address: &Map<String, Value>,
+    path: &str,
+) -> Result<IPv4Address, EthernetInterfaceError> {
+    let property = |name: &str| (address.get(name), format!("{}/{}", path, name));
+    let (value, address_path) = property("Address");
+    let address_value = get_address(value.unwrap_or(&Value::Null), &address_path)?;
+    let (value, mask_path) = property("SubnetMask");
+    let subnet_mask = get_address(value.unwrap_or(&Value::Null), &mask_path)?;
+    if !is_subnet_mask(subnet_mask) {
+        return Err(EthernetInterfaceError::InvalidAddress(mask_path));
+    }
+    let gateway = match property("Gateway") {
+        (None | Some(Value::Null), _) => None,
+        (Some(value), gateway_path) => Some(get_address(value, &gateway_path)?),
+    };
+    Ok(IPv4Address {
+        address: address_value,
+        subnet_mask,
+        gateway,
+        origin: IPv4AddressOrigin::Static,
+    })
+}
+
+fn get_static_ipv6_address(
+    address: &Map<String, Value>,
+    path: &str,
+) -> Result<IPv6Address, EthernetInterfaceError> {
+    let address_path = format!("{}/Address", path);
+    let address_value = get_address(
+        address.get("Address").unwrap_or(&Value::Null),
+        &address_path,
+    )?;
+    let length_path = format!("{}/PrefixLength", path);
+    let prefix_length = address
+        .get("PrefixLength")
+        .and_then(|length| length.as_u64())
+        .ok_or_else(|| EthernetInterfaceError::TypeError(length_path.clone()))?;
+    if prefix_length > 128 {
+        return Err(EthernetInterfaceError::InvalidAddress(length_path));
+    }
+    Ok(IPv6Address {
+        address: address_value,
+        prefix_length: prefix_length as u8,
+        origin: IPv6AddressOrigin::Static,
+    })
+}
+
+// The properties of an EthernetInterface resource that nearly every manager and system has, for
+// trees to build their bodies from and to apply PATCH requests of them to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthernetInterface {
+    mac_address: String,
+    pub interface_enabled: bool,
+    pub ipv4_addresses: Vec<IPv4Address>,
+    pub ipv6_addresses: Vec<IPv6Address>,
+    pub dhcpv4: DHCPv4Configuration,
+    pub dhcpv6: DHCPv6Configuration,
+}
+
+impl EthernetInterface {
+    // The MAC address must be in the format of is_mac_address().
+    pub fn new(mac_address: &str) -> Result<Self, EthernetInterfaceError> {
+        if !is_mac_address(mac_address) {
+            return Err(EthernetInterfaceError::InvalidAddress(String::from(
+                "MACAddress",
+            )));
+        }
+        Ok(Self {
+            mac_address: String::from(mac_address),
+            interface_enabled: true,
+            ipv4_addresses: Vec::new(),
+            ipv6_addresses: Vec::new(),
+            dhcpv4: DHCPv4Configuration::default(),
+            dhcpv6: DHCPv6Configuration::default(),
+        })
+    }
+
+    pub fn get_mac_address(&self) -> &str {
+        &self.mac_address
+    }
+
+    // The properties for the body of the resource, to which the tree adds the likes of its Id.
+    // The static addresses are also those of its IPv4Addresses and IPv6Addresses whose
+    // AddressOrigin is Static.
+    pub fn get_properties(&self) -> Map<String, Value> {
+        let static_ipv4: Vec<Value> = self
+            .ipv4_addresses
+            .iter()
+            .filter(|address| address.origin == IPv4AddressOrigin::Static)
+            .map(|address| {
+                json!({
+                    "Address": address.address.to_string(),
+                    "SubnetMask": address.subnet_mask.to_string(),
+                    "Gateway": address.gateway.map(|gateway| gateway.to_string()),
+                })
+            })
+            .collect();
+        let static_ipv6: Vec<Value> = self
+            .ipv6_addresses
+            .iter()
+            .filter(|address| address.origin == IPv6AddressOrigin::Static)
+            .map(|address| {
+                json!({
+                    "Address": address.address.to_string(),
+                    "PrefixLength": address.prefix_length,
+                })
+            })
+            .collect();
+        let properties = json!({
+            "MACAddress": self.mac_address,
+            "InterfaceEnabled": self.interface_enabled,
+            "IPv4Addresses": self.ipv4_addresses.iter().map(IPv4Address::to_json).collect::<Vec<Value>>(),
+            "IPv4StaticAddresses": static_ipv4,
+            "IPv6Addresses": self.ipv6_addresses.iter().map(IPv6Address::to_json).collect::<Vec<Value>>(),
+            "IPv6StaticAddresses": static_ipv6,
+            "DHCPv4": {
+                "DHCPEnabled": self.dhcpv4.dhcp_enabled,
+                "UseDNSServers": self.dhcpv4.use_dns_servers,
+                "UseGateway": self.dhcpv4.use_gateway,
+                "UseNTPServers": self.dhcpv4.use_ntp_servers,
+            },
+            "DHCPv6": {
+                "OperatingMode": self.dhcpv6.operating_mode.to_string(),
+                "UseDNSServers": self.dhcpv6.use_dns_servers,
+                "UseNTPServers": self.dhcpv6.use_ntp_servers,
+            },
+        });
+        properties.as_object().cloned().unwrap_or_default()
+    }
+
+    // Apply a PATCH of the properties clients may write: MACAddress, InterfaceEnabled, the
+    // static addresses, which replace those there were, DHCPv4 and DHCPv6. Others are left to
+    // the tree. Nothing is applied if anything in the patch is not valid.
+    pub fn patch(&mut self, patch: &Map<String, Value>) -> Result<(), EthernetInterfaceError> {
+        let mut patched = self.clone();
+        for (name, value) in patch {
+            match name.as_str() {
+                "MACAddress" => {
+                    let mac_address = value
+                        .as_str()
+                        .ok_or_else(|| EthernetInterfaceError::TypeError(name.clone()))?;
+                    patched.mac_address = Self::new(mac_address)?.mac_address;
+                }
+                "InterfaceEnabled" => patched.interface_enabled = get_bool(value, name)?,
+                "IPv4StaticAddresses" => {
+                    let static_addresses = get_array_of(value, name, get_static_ipv4_address)?;
+                    patched
+                        .ipv4_addresses
+                        .retain(|address| address.origin != IPv4AddressOrigin::Static);
+                    patched.ipv4_addresses.extend(static_addresses);
+                }
+                "IPv6StaticAddresses" => {
+                    let static_addresses = get_array_of(value, name, get_static_ipv6_address)?;
+                    patched
+                        .ipv6_addresses
+                        .retain(|address| address.origin != IPv6AddressOrigin::Static);
+                    patched.ipv6_addresses.extend(static_addresses);
+                }
+                "DHCPv4" => {
+                    for (name, value) in get_object(value, name)? {
+                        let path = format!("DHCPv4/{}", name);
+                        let setting = match name.as_str() {
+                            "DHCPEnabled" => &mut patched.dhcpv4.dhcp_enabled,
+                            "UseDNSServers" => &mut patched.dhcpv4.use_dns_servers,
+                            "UseGateway" => &mut patched.dhcpv4.use_gateway,
+                            "UseNTPServers" => &mut patched.dhcpv4.use_ntp_servers,
+                            _ => continue,
+                        };
+                        *setting = get_bool(value, &path)?;
+                    }
+                }
+                "DHCPv6" => {
+                    for (name, value) in get_object(value, name)? {
+                        let path = format!("DHCPv6/{}", name);
+                        match name.as_str() {
+                            "OperatingMode" => {
+                                let mode = value.as_str().ok_or_else(|| {
+                                    EthernetInterfaceError::TypeError(path.clone())
+                                })?;
+                                patched.dhcpv6.operating_mode = mode
+                                    .parse()
+                                    .map_err(|_| EthernetInterfaceError::NotInList(path))?;
+                            }
+                            "UseDNSServers" => {
+                                patched.dhcpv6.use_dns_servers = get_bool(value, &path)?
+                            }
+                            "UseNTPServers" => {
+                                patched.dhcpv6.use_ntp_servers = get_bool(value, &path)?
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        *self = patched;
+        Ok(())
+    }
+}
+
+// Why a patch of the Boot or SecureBoot properties of a ComputerSystem is not valid. Each names
+// the property, such as Boot/BootSourceOverrideTarget.
+#[derive(Debug, PartialEq)]
+pub enum BootError {
+    TypeError(String),
+    // Including targets the system does not allow, which are not among its AllowableValues.
+    NotInList(String),
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum BootSourceOverrideTarget {
+    None,
+    Pxe,
+    Floppy,
+    Cd,
+    Usb,
+    Hdd,
+    BiosSetup,
+    Utilities,
+    Diags,
+    UefiShell,
+    UefiTarget,
+    SDCard,
+    UefiHttp,
+    RemoteDrive,
+    UefiBootNext,
+    Recovery,
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum BootSourceOverrideEnabled {
+    Disabled,
+    Once,
+    Continuous,
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum BootSourceOverrideMode {
+    Legacy,
+    UEFI,
+}
+
+fn get_enum<T: FromStr>(value: &Value, path: String) -> Result<T, BootError> {
+    let member = value
+        .as_str()
+        .ok_or_else(|| BootError::TypeError(path.clone()))?;
+    member.parse().map_err(|_| BootError::NotInList(path))
+}
+
+fn get_patch_object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>, BootError> {
+    value
+        .as_object()
+        .ok_or_else(|| BootError::TypeError(String::from(path)))
+}
+
+// The Boot property of a ComputerSystem: what the system boots from next, and in what order it
+// tries its boot options otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Boot {
+    pub target: BootSourceOverrideTarget,
+    pub enabled: BootSourceOverrideEnabled,
+    pub mode: Option<BootSourceOverrideMode>,
+    // The targets clients may override the boot source with, as its AllowableValues.
+    pub allowed_targets: Vec<BootSourceOverrideTarget>,
+    // The UEFI device path to boot from when the target is UefiTarget.
+    pub uefi_target: Option<String>,
+    // The BootOptionReference of each of the system's boot options, such as Boot0001.
+    pub boot_order: Vec<String>,
+}
+
+impl Boot {
+    pub fn new(allowed_targets: &[BootSourceOverrideTarget]) -> Self {
+        Self {
+            target: BootSourceOverrideTarget::None,
+            enabled: BootSourceOverrideEnabled::Disabled,
+            mode: None,
+            allowed_targets: Vec::from(allowed_targets),
+            uefi_target: None,
+            boot_order: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut boot = json!({
+            "BootSourceOverrideTarget": self.target.to_string(),
+            "BootSourceOverrideTarget@Redfish.AllowableValues": self
+                .allowed_targets
+                .iter()
+                .map(|target| target.to_string())
+                .collect::<Vec<String>>(),
+            "BootSourceOverrideEnabled": self.enabled.to_string(),
+            "BootOrder": self.boot_order,
+        });
+        if let Some(mode) = self.mode {
+            boot["BootSourceOverrideMode"] = json!(mode.to_string());
+        }
+        if let Some(uefi_target) = &self.uefi_target {
+            boot["UefiTargetBootSourceOverride"] = json!(uefi_target);
+        }
+        boot
+    }
+
+    // Apply a PATCH of the Boot property, such as {"BootSourceOverrideTarget": "Pxe"}. Nothing
+    // is applied if anything in the patch is not valid. Properties clients may not write, and
+    // the BootSourceOverrideMode of systems that do not have one, are left as they were.
+    pub fn patch(&mut self, patch: &Value) -> Result<(), BootError> {
+        let mut patched = self.clone();
+        for (name, value) in get_patch_object(patch, "Boot")? {
+            let path = format!("Boot/{}", name);
+            match name.as_str() {
+                "BootSourceOverrideTarget" => {
+                    let target = get_enum(value, path.clone())?;
+                    if !self.allowed_targets.contains(&target) {
+                        return Err(BootError::NotInList(path));
+                    }
+                    patched.target = target;
+                }
+                "BootSourceOverrideEnabled" => patched.enabled = get_enum(value, path)?,
+                "BootSourceOverrideMode" if self.mode.is_some() => {
+                    patched.mode = Some(get_enum(value, path)?)
+                }
+                "UefiTargetBootSourceOverride" => {
+                    let uefi_target = value
+                        .as_str()
+                        .ok_or_else(|| BootError::TypeError(path.clone()))?;
+                    patched.uefi_target = Some(String::from(uefi_target));
+                }
+                "BootOrder" => {
+                    patched.boot_order = value
+                        .as_array()
+                        .and_then(|order| {
+                            order
+                                .iter()
+                                .map(|reference| reference.as_str().map(String::from))
+                                .collect::<Option<Vec<String>>>()
+                        })
+                        .ok_or(BootError::TypeError(path))?;
+                }
+                _ => (),
+            }
+        }
+        *self = patched;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum SecureBootMode {
+    SetupMode,
+    UserMode,
+    AuditMode,
+    DeployedMode,
+}
+
+// The properties of the SecureBoot resource of a ComputerSystem. Whether secure boot is
+// enabled takes effect on the next reset, until which the current boot is as it was.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecureBoot {
+    pub enabled: bool,
+    pub current_boot_enabled: bool,
+    pub mode: SecureBootMode,
+}
+
+impl SecureBoot {
+    pub fn new(enabled: bool, mode: SecureBootMode) -> Self {
+        Self {
+            enabled,
+            current_boot_enabled: enabled,
+            mode,
+        }
+    }
+
+    pub fn get_properties(&self) -> Map<String, Value> {
+        let current_boot = match self.current_boot_enabled {
+            true => "Enabled",
+            false => "Disabled",
+        };
+        let properties = json!({
+            "SecureBootEnable": self.enabled,
+            "SecureBootCurrentBoot": current_boot,
+            "SecureBootMode": self.mode.to_string(),
+        });
+        properties.as_object().cloned().unwrap_or_default()
+    }
+
+    // Only SecureBootEnable is writable.
+    pub fn patch(&mut self, patch: &Map<String, Value>) -> Result<(), BootError> {
+        if let Some(enabled) = patch.get("SecureBootEnable") {
+            self.enabled = enabled
+                .as_bool()
+                .ok_or_else(|| BootError::TypeError(String::from("SecureBootEnable")))?;
+        }
+        Ok(())
+    }
+
+    // For when the system resets.
+    pub fn reset(&mut self) {
+        self.current_boot_enabled = self.enabled;
+    }
+}
+
+// The settings-resource pattern, for resources whose changes are staged rather than applied
+// when clients make them, such as the Boot of a system that only changes when it next resets.
+// Clients PATCH the settings object the @Redfish.Settings of the resource links to, which
+// shows the pending settings, and the resource keeps the current ones until they are applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StagedSettings<T> {
+    current: T,
+    pending: Option<T>,
+}
+
+impl<T: Clone> StagedSettings<T> {
+    pub fn new(current: T) -> Self {
+        Self {
+            current,
+            pending: None,
+        }
+    }
+
+    pub fn get_current(&self) -> &T {
+        &self.current
+    }
+
+    // What the settings object shows: the pending settings, or the current ones if none are.
+    pub fn get_pending(&self) -> &T {
+        self.pending.as_ref().unwrap_or(&self.current)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // Stage a change of the pending settings, such as by patching them, unless it fails.
+    pub fn stage<E>(&mut self, change: impl FnOnce(&mut T) -> Result<(), E>) -> Result<(), E> {
+        let mut pending = self.get_pending().clone();
+        change(&mut pending)?;
+        self.pending = Some(pending);
+        Ok(())
+    }
+
+    // Make the pending settings the current ones, such as when the system resets. Returns
+    // whether there were any.
+    pub fn apply(&mut self) -> bool {
+        match self.pending.take() {
+            Some(pending) => {
+                self.current = pending;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// The @Redfish.Settings annotation of a resource whose settings object is at the URI, with the
+// times changes may be applied at, such as OnReset.
+pub fn get_settings_annotation(settings_uri: &str, apply_times: &[&str]) -> Value {
+    json!({
+        "@odata.type": "#Settings.v1_3_5.Settings",
+        "SettingsObject": {"@odata.id": settings_uri},
+        "SupportedApplyTimes": apply_times,
+    })
+}
+
+// How a PasswordHash is derived from its password, by its identifier in the PHC string format.
+// Only PBKDF2 is supported, as ring provides it and the crate already depends on ring for its
+// other hashing. Memory-hard functions such as Argon2 and bcrypt would resist GPUs better, but
+// need crates of their own. Their PHC strings are refused as UnsupportedAlgorithm, so that
+// they can be added later without changing the format.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+pub enum PasswordAlgorithm {
+    #[strum(serialize = "pbkdf2-sha256")]
+    Pbkdf2Sha256,
+    #[strum(serialize = "pbkdf2-sha512")]
+    Pbkdf2Sha512,
+}
+
+impl PasswordAlgorithm {
+    fn get_pbkdf2(&self) -> ring::pbkdf2::Algorithm {
+        match self {
+            Self::Pbkdf2Sha256 => ring::pbkdf2::PBKDF2_HMAC_SHA256,
+            Self::Pbkdf2Sha512 => ring::pbkdf2::PBKDF2_HMAC_SHA512,
+        }
+    }
+
+    fn get_hash_len(&self) -> usize {
+        match self {
+            Self::Pbkdf2Sha256 => 32,
+            Self::Pbkdf2Sha512 => 64,
+        }
+    }
+}
+
+// How new passwords are hashed. Stored hashes keep the configuration they were made with, so
+// that it can be made stronger without invalidating them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordHashConfig {
+    pub algorithm: PasswordAlgorithm,
+    pub iterations: NonZeroU32,
+}
+
+impl Default for PasswordHashConfig {
+    // As OWASP recommends for PBKDF2-HMAC-SHA256.
+    fn default() -> Self {
+        Self {
+            algorithm: PasswordAlgorithm::Pbkdf2Sha256,
+            iterations: NonZeroU32::new(600_000).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordHashError {
+    // Not a hash in the PHC string format, such as $pbkdf2-sha256$i=600000$<salt>$<hash>.
+    InvalidFormat,
+    UnsupportedAlgorithm(String),
+    // More iterations than MAX_PASSWORD_ITERATIONS, which would take too long to verify.
+    TooManyIterations(u64),
+}
+
+const PASSWORD_SALT_LEN: usize = 16;
+
+// The most iterations a hash can be parsed with, so that a tampered store cannot make every
+// login take minutes. It is well over what OWASP recommends. Fewer than recommended are still
+// accepted, so that passwords hashed weakly before can be verified, and rehashed.
+pub const MAX_PASSWORD_ITERATIONS: u32 = 10_000_000;
+
+// A salted hash of an account's password, for AuthenticationProviders to keep instead of the
+// password. It converts to and from a string in the PHC string format, for persisting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordHash {
+    algorithm: PasswordAlgorithm,
+    iterations: NonZeroU32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl PasswordHash {
+    // Hash the password with a new random salt.
+    pub fn new(password: &str, config: &PasswordHashConfig) -> Self {
+        let mut salt = vec![0; PASSWORD_SALT_LEN];
+        SystemRandom::new().fill(&mut salt).unwrap();
+        let mut hash = vec![0; config.algorithm.get_hash_len()];
+        ring::pbkdf2::derive(
+            config.algorithm.get_pbkdf2(),
+            config.iterations,
+            &salt,
+            password.as_bytes(),
+            &mut hash,
+        );
+        Self {
+            algorithm: config.algorithm,
+            iterations: config.iterations,
+            salt,
+            hash,
+        }
+    }
+
+    // Whether the password is the one hashed, compared in constant time.
+    pub fn verify(&self, password: &str) -> bool {
+        ring::pbkdf2::verify(
+            self.algorithm.get_pbkdf2(),
+            self.iterations,
+            &self.salt,
+            password.as_bytes(),
+            &self.hash,
+        )
+        .is_ok()
+    }
+
+    // Whether the hash is weaker than the configuration would make it, so that it should be
+    // replaced, such as once the password has next been verified.
+    pub fn needs_rehash(&self, config: &PasswordHashConfig) -> bool {
+        self.algorithm != config.algorithm || self.iterations < config.iterations
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${}$i={}${}${}",
+            self.algorithm,
+            self.iterations,
+            STANDARD_NO_PAD.encode(&self.salt),
+            STANDARD_NO_PAD.encode(&self.hash)
+        )
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = PasswordHashError;
+
+    fn from_str(hash: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = hash.split('$').collect();
+        let (algorithm, params, salt, hash) = match parts[..] {
+            ["", algorithm, params, salt, hash] => (algorithm, params, salt, hash),
+            _ => return Err(PasswordHashError::InvalidFormat),
+        };
+        let algorithm = PasswordAlgorithm::from_str(algorithm)
+            .map_err(|_| PasswordHashError::UnsupportedAlgorithm(String::from(algorithm)))?;
+        let iterations: u64 = params
+            .strip_prefix("i=")
+            .and_then(|iterations| iterations.parse().ok())
+            .ok_or(PasswordHashError::InvalidFormat)?;
+        if iterations > u64::from(MAX_PASSWORD_ITERATIONS) {
+            return Err(PasswordHashError::TooManyIterations(iterations));
+        }
+        let iterations = u32::try_from(iterations)
+            .ok()
+            .and_then(NonZeroU32::new)
+            .ok_or(PasswordHashError::InvalidFormat)?;
+        let decode = |data| {
+            STANDARD_NO_PAD
+                .decode(data)
+                .map_err(|_| PasswordHashError::InvalidFormat)
+        };
+        let salt = decode(salt)?;
+        let hash = decode(hash)?;
+        if salt.is_empty() || hash.len() != algorithm.get_hash_len() {
+            return Err(PasswordHashError::InvalidFormat);
+        }
+        Ok(Self {
+            algorithm,
+            iterations,
+            salt,
+            hash,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+// A parsed $filter expression. Properties are paths such as Status/Health.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(String, FilterOp, Value),
+}
+
+impl Filter {
+    pub fn matches(&self, body: &Value) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(body) && right.matches(body),
+            Filter::Or(left, right) => left.matches(body) || right.matches(body),
+            Filter::Not(filter) => !filter.matches(body),
+            Filter::Compare(path, op, literal) => {
+                let pointer = format!("/{}", path);
+                let value = body.pointer(&pointer).unwrap_or(&Value::Null);
+                match (op, compare_filter_values(value, literal)) {
+                    (FilterOp::Eq, ordering) => ordering == Some(Ordering::Equal),
+                    (FilterOp::Ne, ordering) => ordering != Some(Ordering::Equal),
+                    (FilterOp::Gt, Some(ordering)) => ordering == Ordering::Greater,
+                    (FilterOp::Ge, Some(ordering)) => ordering != Ordering::Less,
+                    (FilterOp::Lt, Some(ordering)) => ordering == Ordering::Less,
+                    (FilterOp::Le, Some(ordering)) => ordering != Ordering::Greater,
+                    (_, None) => false,
+                }
+            }
+        }
+    }
+
+    // How tightly the expression binds, from or to comparisons.
+    fn get_binding(&self) -> u8 {
+        match self {
+            Filter::Or(..) => 0,
+            Filter::And(..) => 1,
+            Filter::Not(..) | Filter::Compare(..) => 2,
+        }
+    }
+
+    // Written as an operand of the given expression, in parentheses if it would otherwise be
+    // parsed differently. Operators bind to the left, so a right operand of the same kind needs
+    // them too.
+    fn fmt_operand(&self, f: &mut fmt::Formatter, of: &Filter, right: bool) -> fmt::Result {
+        let binding = self.get_binding();
+        match binding < of.get_binding() || (right && binding == of.get_binding() && binding < 2) {
+            true => write!(f, "({})", self),
+            false => write!(f, "{}", self),
+        }
+    }
+}
+
+// Filters are written in their canonical form, with single spaces and no more parentheses than
+// needed, such as Status/Health eq 'OK' and not (PowerState eq 'Off' or Id eq '1').
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                left.fmt_operand(f, self, false)?;
+                let op = match self {
+                    Filter::And(..) => "and",
+                    _ => "or",
+                };
+                write!(f, " {} ", op)?;
+                right.fmt_operand(f, self, true)
+            }
+            Filter::Not(filter) => {
+                write!(f, "not ")?;
+                filter.fmt_operand(f, self, false)
+            }
+            Filter::Compare(path, op, literal) => {
+                let literal = match literal {
+                    Value::String(string) => format!("'{}'", string.replace('\'', "''")),
+                    literal => literal.to_string(),
+                };
+                write!(f, "{} {} {}", path, op, literal)
+            }
+        }
+    }
+}
+
+// Values of different types do not compare, except that anything equals itself.
+fn compare_filter_values(value: &Value, literal: &Value) -> Option<Ordering> {
+    match (value, literal) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExpandKind {
+    // $expand=*, both subordinate resources and those under Links.
+    All,
+    // $expand=~, only the resources under Links.
+    Links,
+    // $expand=., only subordinate resources, such as the Members of a collection.
+    NoLinks,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Expand {
+    pub kind: ExpandKind,
+    pub levels: u32,
+}
+
+// Written as in $expand, with the $levels left out when it is 1, as it is by default.
+impl fmt::Display for Expand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            ExpandKind::All => "*",
+            ExpandKind::Links => "~",
+            ExpandKind::NoLinks => ".",
+        };
+        match self.levels {
+            1 => write!(f, "{}", kind),
+            levels => write!(f, "{}($levels={})", kind, levels),
+        }
+    }
+}
+
+// Why a query string could not be parsed, naming the parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryError {
+    // The value of the parameter is not one it can take, such as a $top that is not a number,
+    // or the query string is not encoded properly.
+    Malformed(String),
+    // The parameter starts with $, as those defined by the spec do, but is not one of them.
+    Unsupported(String),
+}
+
+// The query parameters of a request for a resource, as the Redfish spec defines them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub filter: Option<Filter>,
+    pub top: Option<usize>,
+    pub skip: Option<usize>,
+    pub expand: Option<Expand>,
+    // The properties to return, as paths such as Status/Health.
+    pub select: Option<Vec<String>>,
+    // Where in the members to carry on from, as given by the service in the nextLink of the
+    // page before. It is opaque to clients, so only the service that gave it can tell if it
+    // is one it would give.
+    pub skip_token: Option<String>,
+    // Return the member of the collection instead, if it has only one.
+    pub only: bool,
+    // Return only the properties the schema marks as part of excerpts of the resource.
+    pub excerpt: bool,
+}
+
+impl Query {
+    // Parse a query string as it is in a URI, without the ?, such as
+    // $top=2&$filter=Status/Health%20eq%20'OK'.
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        Self::from_pairs(&parse_query_pairs(query)?)
+    }
+
+    // Parse the decoded pairs of a query string. Parameters that do not start with $ and are
+    // not otherwise known, such as OEM ones, are ignored.
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<Self, QueryError> {
+        let mut query = Self::default();
+        for (key, value) in pairs {
+            let malformed = || QueryError::Malformed(key.clone());
+            match key.as_str() {
+                "$filter" => query.filter = Some(parse_filter(value).ok_or_else(malformed)?),
+                "$top" => query.top = Some(value.parse().map_err(|_| malformed())?),
+                "$skip" => query.skip = Some(value.parse().map_err(|_| malformed())?),
+                "$expand" => query.expand = Some(parse_expand(value).ok_or_else(malformed)?),
+                "$select" => query.select = Some(parse_select(value).ok_or_else(malformed)?),
+                "$skiptoken" if value.is_empty() => return Err(malformed()),
+                "$skiptoken" => query.skip_token = Some(value.clone()),
+                "only" | "excerpt" if !value.is_empty() => return Err(malformed()),
+                "only" => query.only = true,
+                "excerpt" => query.excerpt = true,
+                _ if key.starts_with('$') => return Err(QueryError::Unsupported(key.clone())),
+                _ => (),
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // The decoded pairs of the parameters, in their canonical form and order. Those without a
+    // value, such as only, have an empty one.
+    pub fn to_pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        let mut push = |key: &str, value: String| pairs.push((String::from(key), value));
+        if let Some(filter) = &self.filter {
+            push("$filter", filter.to_string());
+        }
+        if let Some(select) = &self.select {
+            push("$select", select.join(","));
+        }
+        if let Some(expand) = &self.expand {
+            push("$expand", expand.to_string());
+        }
+        if let Some(top) = self.top {
+            push("$top", top.to_string());
+        }
+        if let Some(skip) = self.skip {
+            push("$skip", skip.to_string());
+        }
+        if let Some(skip_token) = &self.skip_token {
+            push("$skiptoken", skip_token.clone());
+        }
+        if self.only {
+            push("only", String::new());
+        }
+        if self.excerpt {
+            push("excerpt", String::new());
+        }
+        pairs
+    }
+}
+
+// The query string of the parameters in their canonical form, percent-encoded, as it goes
+// after the ? of a URI. Parsing it gives the same parameters.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self
+            .to_pairs()
+            .into_iter()
+            .map(|(key, value)| match value.is_empty() {
+                true => encode_query_part(&key),
+                false => format!("{}={}", encode_query_part(&key), encode_query_part(&value)),
+            })
+            .collect();
+        write!(f, "{}", parts.join("&"))
+    }
+}
+
+// The decoded pairs of a query string, in the order they are in it. Parameters without a value,
+// such as only, have an empty one.
+pub fn parse_query_pairs(query: &str) -> Result<Vec<(String, String)>, QueryError> {
+    let mut pairs = Vec::new();
+    for part in query.split('&').filter(|part| !part.is_empty()) {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        let key = decode_query_part(key).ok_or_else(|| QueryError::Malformed(key.into()))?;
+        let value = decode_query_part(value).ok_or_else(|| QueryError::Malformed(key.clone()))?;
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+// Percent-encode what cannot be left as it is in a query string, keeping the characters that
+// show up in query parameters, such as $ and '.
+pub fn encode_query_part(part: &str) -> String {
+    let mut encoded = String::new();
+    for byte in part.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'$' | b'\'' | b'(' | b')' | b'*' | b',' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// Undo percent-encoding, and the + that forms encode spaces as. None if what is encoded is not
+// UTF-8, or a % is not followed by two hex digits.
+fn decode_query_part(part: &str) -> Option<String> {
+    let mut decoded = Vec::new();
+    let mut bytes = part.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                // Hex digits are ASCII.
+                let hex = std::str::from_utf8(&hex).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn parse_select(value: &str) -> Option<Vec<String>> {
+    let mut paths = Vec::new();
+    for path in value.split(',') {
+        let path = path.trim();
+        if path.is_empty() || path.split('/').any(|name| name.is_empty()) {
+            return None;
+        }
+        paths.push(String::from(path));
+    }
+    Some(paths)
+}
+
+fn parse_expand(value: &str) -> Option<Expand> {
+    let (kind, options) = match value.split_once('(') {
+        Some((kind, options)) => (kind, options.strip_suffix(')')?),
+        None => (value, ""),
+    };
+    let kind = match kind {
+        "*" => ExpandKind::All,
+        "~" => ExpandKind::Links,
+        "." => ExpandKind::NoLinks,
+        _ => return None,
+    };
+    let levels = match options {
+        "" => 1,
+        options => options.strip_prefix("$levels=")?.parse().ok()?,
+    };
+    Some(Expand { kind, levels })
+}
+
+fn tokenize_filter(filter: &str) -> Option<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => (),
+            '(' | ')' => tokens.push(c.to_string()),
+            '\'' => {
+                // Quotes within strings are doubled, as in 'Bob''s'.
+                let mut token = String::from("'");
+                loop {
+                    match chars.next()? {
+                        '\'' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            token.push('\'');
+                        }
+                        '\'' => break,
+                        c => token.push(c),
+                    }
+                }
+                tokens.push(token);
+            }
+            c => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c == ' ' || c == '(' || c == ')' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    Some(tokens)
+}
+
+// Parses filters with the grammar:
+// or := and ("or" and)*
+// and := unary ("and" unary)*
+// unary := "not" unary | "(" or ")" | property op literal
+struct FilterParser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl FilterParser {
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn peek_is(&self, token: &str) -> bool {
+        self.tokens.get(self.position).map(|t| t.as_str()) == Some(token)
+    }
+
+    fn parse_or(&mut self) -> Option<Filter> {
+        let mut filter = self.parse_and()?;
+        while self.peek_is("or") {
+            self.position += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Some(filter)
+    }
+
+    fn parse_and(&mut self) -> Option<Filter> {
+        let mut filter = self.parse_unary()?;
+        while self.peek_is("and") {
+            self.position += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+        Some(filter)
+    }
+
+    fn parse_unary(&mut self) -> Option<Filter> {
+        if self.peek_is("not") {
+            self.position += 1;
+            return Some(Filter::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.peek_is("(") {
+            self.position += 1;
+            let filter = self.parse_or()?;
+            return match self.next()? {
+                ")" => Some(filter),
+                _ => None,
+            };
+        }
+        let property = String::from(self.next()?);
+        let op = FilterOp::from_str(self.next()?).ok()?;
+        let literal = parse_filter_literal(self.next()?)?;
+        Some(Filter::Compare(property, op, literal))
+    }
+}
+
+fn parse_filter_literal(token: &str) -> Option<Value> {
+    if let Some(string) = token.strip_prefix('\'') {
+        return Some(Value::String(String::from(string)));
+    }
+    match token {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        "null" => Some(Value::Null),
+        number => serde_json::from_str::<serde_json::Number>(number)
+            .ok()
+            .map(Value::Number),
+    }
+}
+
+fn parse_filter(filter: &str) -> Option<Filter> {
+    let mut parser = FilterParser {
+        tokens: tokenize_filter(filter)?,
+        position: 0,
+    };
+    let filter = parser.parse_or()?;
+    match parser.position == parser.tokens.len() {
+        true => Some(filter),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn get_base_registry() -> MessageRegistry {
+        let mut path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        path.push_str("/registries/Base.1.16.0.json");
+        MessageRegistry::from_file(&path)
+    }
+
+    #[test]
+    fn odata_context() {
+        assert_eq!(
+            get_odata_context("#Chassis.v1_22_0.Chassis").as_deref(),
+            Some("/redfish/v1/$metadata#Chassis.Chassis")
+        );
+        assert_eq!(
+            get_odata_context("#ChassisCollection.ChassisCollection").as_deref(),
+            Some("/redfish/v1/$metadata#ChassisCollection.ChassisCollection")
+        );
+        assert_eq!(get_odata_context("Chassis"), None);
+        assert_eq!(get_odata_context("#Chassis"), None);
+
+        let mut body = json!({"@odata.type": "#Session.v1_6_0.Session"});
+        add_odata_context(body.as_object_mut().unwrap());
+        assert_eq!(
+            body["@odata.context"],
+            "/redfish/v1/$metadata#Session.Session"
+        );
+        let mut body = json!({"@odata.context": "/redfish/v1/$metadata#Other.Other"});
+        add_odata_context(body.as_object_mut().unwrap());
+        assert_eq!(body["@odata.context"], "/redfish/v1/$metadata#Other.Other");
+    }
+
+    #[test]
+    fn schema_url() {
+        let url = "https://redfish.dmtf.org/schemas/v1/Chassis.v1_22_0.json";
+        assert_eq!(SchemaUrl::new(url).unwrap().as_str(), url);
+        let path = "/redfish/v1/JsonSchemas/Contoso.v1_0_0.json";
+        assert_eq!(SchemaUrl::new(path).unwrap().to_string(), path);
+        for bad in [
+            "",
+            "redfish.dmtf.org/schemas/v1/Chassis.json",
+            "https://",
+            "https:///schemas",
+            "https://redfish.dmtf.org/schemas/v1/My Type.json",
+            "https://example.com/>; rel=next",
+            "ftp://example.com/schema.json",
+        ] {
+            assert_eq!(
+                SchemaUrl::new(bad),
+                Err(InvalidSchemaUrl(String::from(bad)))
+            );
+        }
+        assert_eq!(
+            ResourceType::new_dmtf(
+                String::from("Chassis"),
+                ResourceSchemaVersion::new(1, 22, 0)
+            )
+            .described_by
+            .as_str(),
+            url
+        );
+    }
+
+    #[test]
+    fn property_type() {
+        assert_eq!(PropertyType::String.validate(&json!("a")), Ok(()));
+        assert_eq!(
+            PropertyType::String.validate(&json!(1)),
+            Err(PropertyValueError::TypeError)
+        );
+        assert_eq!(PropertyType::Number.validate(&json!(1.5)), Ok(()));
+        assert_eq!(
+            PropertyType::Integer.validate(&json!(1.5)),
+            Err(PropertyValueError::TypeError)
+        );
+        assert_eq!(PropertyType::Boolean.validate(&json!(false)), Ok(()));
+        let enum_type = PropertyType::Enum(vec![String::from("On"), String::from("Off")]);
+        assert_eq!(enum_type.validate(&json!("Off")), Ok(()));
+        assert_eq!(
+            enum_type.validate(&json!("Dim")),
+            Err(PropertyValueError::NotInList)
+        );
+        assert_eq!(
+            enum_type.validate(&json!(true)),
+            Err(PropertyValueError::TypeError)
+        );
+        let array_type = PropertyType::Array(Box::new(enum_type));
+        assert_eq!(array_type.validate(&json!(["On", "Off"])), Ok(()));
+        assert_eq!(array_type.validate(&json!([])), Ok(()));
+        assert_eq!(
+            array_type.validate(&json!(["On", "Dim"])),
+            Err(PropertyValueError::NotInList)
+        );
+        assert_eq!(
+            array_type.validate(&json!("On")),
+            Err(PropertyValueError::TypeError)
+        );
+    }
+
+    #[test]
+    fn action_parameters() {
+        let registry = get_base_registry();
+        let reset_types = vec![String::from("On"), String::from("ForceOff")];
+        let parameters = [
+            ActionParameter::new("ResetType", PropertyType::Enum(reset_types)).required(),
+            ActionParameter::new("Delay", PropertyType::Integer),
+        ];
+        let validate = |body: Value| {
+            let body = body.as_object().unwrap().clone();
+            validate_action_parameters(&registry, "ComputerSystem.Reset", &parameters, &body)
+                .unwrap()
+                .iter()
+                .map(|message| {
+                    let message = message.to_json();
+                    (message["MessageId"].clone(), message["MessageArgs"].clone())
+                })
+                .collect::<Vec<(Value, Value)>>()
+        };
+        assert_eq!(validate(json!({"ResetType": "On"})), vec![]);
+        assert_eq!(validate(json!({"ResetType": "On", "Delay": 5})), vec![]);
+        assert_eq!(
+            validate(json!({"Delay": 5})),
+            vec![(
+                json!("Base.1.16.ActionParameterMissing"),
+                json!(["ComputerSystem.Reset", "ResetType"])
+            )]
+        );
+        assert_eq!(
+            validate(json!({"ResetType": "Nmi", "Delay": "soon", "Force": true})),
+            vec![
+                (
+                    json!("Base.1.16.ActionParameterValueNotInList"),
+                    json!(["Nmi", "ResetType", "ComputerSystem.Reset"])
+                ),
+                (
+                    json!("Base.1.16.ActionParameterValueTypeError"),
+                    json!(["soon", "Delay", "ComputerSystem.Reset"])
+                ),
+                (
+                    json!("Base.1.16.ActionParameterUnknown"),
+                    json!(["ComputerSystem.Reset", "Force"])
+                ),
+            ]
+        );
+
+        // Registries without the messages cannot say what the problems are
+        let registry = MessageRegistry::from_json(
+            &json!({
+                "Id": "Oem.1.0.0",
+                "RegistryPrefix": "Oem",
+                "RegistryVersion": "1.0.0",
+                "Language": "en",
+                "Messages": {},
+            })
+            .to_string(),
+        );
+        let body = json!({"Delay": 5}).as_object().unwrap().clone();
+        assert!(matches!(
+            validate_action_parameters(&registry, "ComputerSystem.Reset", &parameters, &body),
+            Err(RegistryError::MessageNotInRegistry)
+        ));
+        let body = json!({"ResetType": "On"}).as_object().unwrap().clone();
+        assert!(
+            validate_action_parameters(&registry, "ComputerSystem.Reset", &parameters, &body)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn lint_registry() {
+        let mut path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        path.push_str("/registries/Base.1.16.0.json");
+        let data = fs::read_to_string(path).unwrap();
+        assert_eq!(lint_message_registry(&data), vec![]);
+
+        let data = json!({
+            "Id": "Oem.1.0.0",
+            "RegistryPrefix": "Oem",
+            "RegistryVersion": "1.0",
+            "Messages": {
+                "Fan.Failed": {
+                    "Message": "Fan %1 failed with %3.",
+                    "MessageSeverity": "Bad",
+                    "NumberOfArgs": 2,
+                    "ParamTypes": ["string"],
+                    "Resolution": "None.",
+                },
+            },
+        })
+        .to_string();
+        let diagnostics = lint_message_registry(&data);
+        let descriptions: Vec<&str> = diagnostics.iter().map(|d| d.description.as_str()).collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "RegistryVersion is missing or not of the form major.minor.errata",
+                "Id should be Oem.1.0",
+                "Message key is not alphanumeric",
+                "MessageSeverity is missing or not one of OK, Warning or Critical",
+                "Placeholder %3 is out of range for 2 args",
+                "Arg 2 is not used in the message",
+                "ParamTypes has 1 entries but NumberOfArgs is 2",
+            ]
+        );
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[2].message_key, Some(String::from("Fan.Failed")));
+
+        assert_eq!(
+            lint_message_registry("not json")[0].severity,
+            DiagnosticSeverity::Error
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to parse message registry file")]
+    fn truncated_message_registry() {
+        let mut path = env::var("CARGO_MANIFEST_DIR").unwrap();
+        path.push_str("/registries/Base.1.16.0.json");
+        let data = fs::read_to_string(path).unwrap();
+        MessageRegistry::from_json(&data[..data.len() / 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to parse message registry file")]
+    fn message_registry_not_object() {
+        MessageRegistry::from_json(r#"[{"RegistryPrefix": "Oem", "RegistryVersion": "1.0.0"}]"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to parse privilege registry file")]
+    fn malformed_privilege_registry() {
+        PrivilegeRegistry::from_json(r#"{"Id": "Redfish_1.5.0_PrivilegeRegistry", "Mappings": ["#);
+    }
+
+    #[test]
+    fn message_registry() {
+        let registry = get_base_registry();
+        assert_eq!(registry.prefix, String::from("Base"));
+        assert_eq!(registry.version.major, 1);
+        assert_eq!(registry.version.minor, 16);
+        assert_eq!(registry.version.build, 0);
+        assert_eq!(registry.get_language(), "en");
+        let success = registry.message_definitions.get("Success").unwrap();
+        assert_eq!(success.severity, Health::OK);
+    }
+
+    #[test]
+    fn message() {
+        let registry = get_base_registry();
+        let message = Message::from_registry(
+            &registry,
+            "PropertyValueTypeError",
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![String::from("300"), String::from("SessionTimeout")],
+            vec![String::from("/SessionTimeout")],
+        )
+        .unwrap();
+        let jsonified = message.to_json();
+        assert_eq!(&jsonified, json!({
+            "@odata.type": "#Message.v1_1_2.Message",
+            "MessageId": "Base.1.16.PropertyValueTypeError",
+            "RelatedProperties": ["/SessionTimeout"],
+            "Message": "The value '300' for the property SessionTimeout is of a different type than the property can accept.",
+            "MessageArgs": ["300", "SessionTimeout"],
+            "MessageSeverity": "Warning",
+            "Resolution": "Correct the value for the property in the request body and resubmit the request if the operation failed."
+        }).as_object().unwrap());
+
+        let localized = message.to_localized_json();
+        assert_eq!(localized.get("Language"), Some(&json!("en")));
+        assert_eq!(localized.len(), jsonified.len() + 1);
+    }
+
+    #[test]
+    fn error_response() {
+        let registry = get_base_registry();
+        let message = Message::from_registry(
+            &registry,
+            "PropertyValueTypeError",
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![String::from("300"), String::from("SessionTimeout")],
             vec![String::from("/SessionTimeout")],
         )
         .unwrap();
-        let error = ErrorResponse::from_registry(&registry, "GeneralError", &vec![], vec![message]);
+        let error = ErrorResponse::from_registry(&registry, "GeneralError", &[], vec![message]);
         assert_eq!(&error.to_json(), json!({
             "error": {
                 "code": "Base.1.16.GeneralError",
@@ -535,6 +3076,85 @@ mod tests {
         }).as_object().unwrap());
     }
 
+    #[test]
+    fn extended_error_response() {
+        let registry = get_base_registry();
+        let mut error = ErrorResponse::from_registry(&registry, "GeneralError", &[], Vec::new());
+        assert_eq!(error.get_code(), "Base.1.16.GeneralError");
+        let message = Message::from_registry(
+            &registry,
+            "InternalError",
+            ResourceSchemaVersion::new(1, 1, 2),
+            Vec::new(),
+            Vec::new(),
+        )
+        .unwrap();
+        error.add_extended_info(message);
+        error.insert_property(String::from("Oem"), json!({"Contoso": {"Code": 17}}));
+        error.insert_property(String::from("code"), json!("Contoso.Overridden"));
+        let jsonified = error.to_json();
+        let object = jsonified["error"].as_object().unwrap();
+        assert_eq!(object["code"], "Base.1.16.GeneralError");
+        assert_eq!(object["Oem"], json!({"Contoso": {"Code": 17}}));
+        assert_eq!(
+            object["@Message.ExtendedInfo"][0]["MessageId"],
+            "Base.1.16.InternalError"
+        );
+    }
+
+    #[test]
+    fn datetime_string() {
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1685622896);
+        assert_eq!(get_datetime_string(time), "2023-06-01T12:34:56+00:00");
+        assert_eq!(get_datetime_string(UNIX_EPOCH), "1970-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn event() {
+        let registry = get_base_registry();
+        let message = Message::from_registry(
+            &registry,
+            "SessionTerminated",
+            ResourceSchemaVersion::new(1, 1, 2),
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        let record = EventRecord::new(
+            String::from("7"),
+            UNIX_EPOCH,
+            message,
+            Some(String::from("/redfish/v1/SessionService/Sessions/1")),
+        );
+        let event = Event::new(
+            ResourceSchemaVersion::new(1, 7, 0),
+            String::from("7"),
+            vec![record],
+        );
+        assert_eq!(
+            &event.to_json(),
+            json!({
+                "@odata.type": "#Event.v1_7_0.Event",
+                "Id": "7",
+                "Name": "Event",
+                "Events": [
+                    {
+                        "MemberId": "0",
+                        "EventId": "7",
+                        "EventTimestamp": "1970-01-01T00:00:00+00:00",
+                        "MessageId": "Base.1.16.SessionTerminated",
+                        "Message": "The session was successfully terminated.",
+                        "MessageArgs": [],
+                        "MessageSeverity": "OK",
+                        "OriginOfCondition": {"@odata.id": "/redfish/v1/SessionService/Sessions/1"},
+                    }
+                ]
+            })
+            .as_object()
+            .unwrap()
+        );
+    }
+
     #[test]
     fn uri_id() {
         assert_eq!(get_uri_id("/redfish/v1"), String::from("RootService"));
@@ -615,16 +3235,14 @@ mod tests {
 
     #[test]
     fn odata_metadata_document() {
-        let mut collection_types = Vec::new();
-        collection_types.push(CollectionType::new_dmtf_v1(String::from(
+        let collection_types = vec![CollectionType::new_dmtf_v1(String::from(
             "SessionCollection",
-        )));
+        ))];
 
-        let mut resource_types = Vec::new();
-        resource_types.push(ResourceType::new_dmtf(
+        let resource_types = vec![ResourceType::new_dmtf(
             String::from("ServiceRoot"),
             ResourceSchemaVersion::new(1, 15, 0),
-        ));
+        )];
 
         let exp_xml = String::from(
             r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -651,4 +3269,368 @@ mod tests {
             get_odata_metadata_document(collection_types.as_slice(), resource_types.as_slice());
         assert_eq!(doc, exp_xml);
     }
+
+    #[test]
+    fn ethernet_interface() {
+        assert!(is_mac_address("00:1A:2B:3C:4D:5E"));
+        assert!(is_mac_address("00-1a-2b-3c-4d-5e"));
+        assert!(!is_mac_address("00:1A:2B:3C:4D"));
+        assert!(!is_mac_address("00:1A:2B:3C:4D:5G"));
+        assert!(is_subnet_mask(Ipv4Addr::new(255, 255, 254, 0)));
+        assert!(!is_subnet_mask(Ipv4Addr::new(255, 0, 255, 0)));
+        assert_eq!(
+            EthernetInterface::new("001A2B3C4D5E"),
+            Err(EthernetInterfaceError::InvalidAddress(String::from(
+                "MACAddress"
+            )))
+        );
+
+        let mut interface = EthernetInterface::new("00:1A:2B:3C:4D:5E").unwrap();
+        interface.ipv4_addresses.push(IPv4Address {
+            address: Ipv4Addr::new(10, 0, 0, 5),
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 0),
+            gateway: Some(Ipv4Addr::new(10, 0, 0, 1)),
+            origin: IPv4AddressOrigin::DHCP,
+        });
+        let patch = json!({
+            "IPv4StaticAddresses": [{"Address": "192.168.0.10", "SubnetMask": "255.255.255.0"}],
+            "IPv6StaticAddresses": [{"Address": "fd00::10", "PrefixLength": 64}],
+            "DHCPv4": {"DHCPEnabled": false},
+            "DHCPv6": {"OperatingMode": "Disabled"},
+        });
+        interface.patch(patch.as_object().unwrap()).unwrap();
+        let properties = interface.get_properties();
+        assert_eq!(properties["MACAddress"], "00:1A:2B:3C:4D:5E");
+        assert_eq!(properties["IPv4Addresses"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            properties["IPv4StaticAddresses"],
+            json!([{"Address": "192.168.0.10", "SubnetMask": "255.255.255.0", "Gateway": null}])
+        );
+        assert_eq!(
+            properties["IPv6Addresses"],
+            json!([{"Address": "fd00::10", "PrefixLength": 64, "AddressOrigin": "Static"}])
+        );
+        assert_eq!(properties["DHCPv4"]["DHCPEnabled"], false);
+        assert_eq!(properties["DHCPv4"]["UseGateway"], true);
+        assert_eq!(properties["DHCPv6"]["OperatingMode"], "Disabled");
+
+        // Nothing is applied if anything is not valid
+        let before = interface.clone();
+        let patch = json!({
+            "InterfaceEnabled": false,
+            "IPv4StaticAddresses": [{"Address": "192.168.0.300", "SubnetMask": "255.255.255.0"}],
+        });
+        assert_eq!(
+            interface.patch(patch.as_object().unwrap()),
+            Err(EthernetInterfaceError::InvalidAddress(String::from(
+                "IPv4StaticAddresses/0/Address"
+            )))
+        );
+        let patch = json!({"IPv4StaticAddresses": [
+            {"Address": "192.168.0.10", "SubnetMask": "255.0.255.0"}
+        ]});
+        assert_eq!(
+            interface.patch(patch.as_object().unwrap()),
+            Err(EthernetInterfaceError::InvalidAddress(String::from(
+                "IPv4StaticAddresses/0/SubnetMask"
+            )))
+        );
+        let patch = json!({"DHCPv6": {"OperatingMode": "Sometimes"}});
+        assert_eq!(
+            interface.patch(patch.as_object().unwrap()),
+            Err(EthernetInterfaceError::NotInList(String::from(
+                "DHCPv6/OperatingMode"
+            )))
+        );
+        assert_eq!(interface, before);
+    }
+
+    #[test]
+    fn boot() {
+        let mut boot = Boot::new(&[
+            BootSourceOverrideTarget::None,
+            BootSourceOverrideTarget::Pxe,
+            BootSourceOverrideTarget::Hdd,
+        ]);
+        boot.mode = Some(BootSourceOverrideMode::UEFI);
+        assert_eq!(
+            boot.to_json()["BootSourceOverrideTarget@Redfish.AllowableValues"],
+            json!(["None", "Pxe", "Hdd"])
+        );
+        assert_eq!(
+            boot.patch(&json!({"BootSourceOverrideTarget": "Cd"})),
+            Err(BootError::NotInList(String::from(
+                "Boot/BootSourceOverrideTarget"
+            )))
+        );
+        assert_eq!(
+            boot.patch(&json!({"BootSourceOverrideEnabled": "Once", "BootOrder": [1]})),
+            Err(BootError::TypeError(String::from("Boot/BootOrder")))
+        );
+        assert_eq!(boot.enabled, BootSourceOverrideEnabled::Disabled);
+
+        let mut settings = StagedSettings::new(boot);
+        settings
+            .stage(|boot| {
+                boot.patch(&json!({
+                    "BootSourceOverrideTarget": "Pxe",
+                    "BootSourceOverrideEnabled": "Once",
+                    "BootSourceOverrideMode": "Legacy",
+                }))
+            })
+            .unwrap();
+        assert!(settings.has_pending());
+        assert_eq!(
+            settings.get_current().target,
+            BootSourceOverrideTarget::None
+        );
+        let pending = settings.get_pending().to_json();
+        assert_eq!(pending["BootSourceOverrideTarget"], "Pxe");
+        assert_eq!(pending["BootSourceOverrideMode"], "Legacy");
+        assert!(settings.apply());
+        assert!(!settings.apply());
+        assert_eq!(settings.get_current().target, BootSourceOverrideTarget::Pxe);
+        assert_eq!(
+            get_settings_annotation("/redfish/v1/Systems/1/Settings", &["OnReset"]),
+            json!({
+                "@odata.type": "#Settings.v1_3_5.Settings",
+                "SettingsObject": {"@odata.id": "/redfish/v1/Systems/1/Settings"},
+                "SupportedApplyTimes": ["OnReset"],
+            })
+        );
+
+        let mut secure_boot = SecureBoot::new(false, SecureBootMode::UserMode);
+        let patch = json!({"SecureBootEnable": true});
+        secure_boot.patch(patch.as_object().unwrap()).unwrap();
+        let properties = secure_boot.get_properties();
+        assert_eq!(properties["SecureBootEnable"], true);
+        assert_eq!(properties["SecureBootCurrentBoot"], "Disabled");
+        secure_boot.reset();
+        assert_eq!(
+            secure_boot.get_properties()["SecureBootCurrentBoot"],
+            "Enabled"
+        );
+    }
+
+    #[test]
+    fn privilege_registry() {
+        let registry = PrivilegeRegistry::from_json(
+            r#"{
+                "Id": "Test_PrivilegeRegistry",
+                "Mappings": [{
+                    "Entity": "Session",
+                    "OperationMap": {
+                        "GET": [{"Privilege": ["Login"]}],
+                        "DELETE": [
+                            {"Privilege": ["ConfigureManager"]},
+                            {"Privilege": ["Login", "ConfigureSelf"]}
+                        ]
+                    }
+                }, {
+                    "Entity": "ManagerAccount",
+                    "OperationMap": {
+                        "GET": [{"Privilege": ["ConfigureUsers"]}, {"Privilege": ["ConfigureSelf"]}],
+                        "PATCH": [{"Privilege": ["ConfigureUsers"]}]
+                    },
+                    "PropertyOverrides": [{
+                        "Targets": ["Password"],
+                        "OperationMap": {
+                            "PATCH": [{"Privilege": ["ConfigureUsers"]}, {"Privilege": ["ConfigureSelf"]}]
+                        }
+                    }]
+                }, {
+                    "Entity": "EthernetInterface",
+                    "OperationMap": {
+                        "PATCH": [{"Privilege": ["ConfigureComponents"]}]
+                    },
+                    "SubordinateOverrides": [{
+                        "Targets": ["Manager", "EthernetInterfaceCollection"],
+                        "OperationMap": {
+                            "PATCH": [{"Privilege": ["ConfigureManager"]}]
+                        }
+                    }],
+                    "ResourceURIOverrides": [{
+                        "Targets": ["/redfish/v1/Managers/1/EthernetInterfaces/Host"],
+                        "OperationMap": {
+                            "PATCH": [{"Privilege": ["ConfigureUsers"]}]
+                        }
+                    }]
+                }]
+            }"#,
+        );
+        assert_eq!(registry.get_id(), "Test_PrivilegeRegistry");
+        let privileges =
+            |names: &[&str]| names.iter().map(|n| String::from(*n)).collect::<Vec<_>>();
+        let session = PrivilegeTarget::new("Session", "/redfish/v1/SessionService/Sessions/1");
+        assert!(registry.allows(&session, "GET", &privileges(&["Login"])));
+        assert!(!registry.allows(&session, "DELETE", &privileges(&["Login"])));
+        assert!(registry.allows(&session, "DELETE", &privileges(&["Login", "ConfigureSelf"])));
+        assert!(registry.allows(&session, "DELETE", &privileges(&["ConfigureManager"])));
+        assert_eq!(registry.get_required_privileges(&session, "PATCH"), None);
+        // Unmapped entities need Login to read, and ConfigureComponents to write.
+        let chassis = PrivilegeTarget::new("Chassis", "/redfish/v1/Chassis/1");
+        assert!(registry.allows(&chassis, "HEAD", &privileges(&["Login"])));
+        assert!(!registry.allows(&chassis, "PATCH", &privileges(&["Login"])));
+        assert!(registry.allows(&chassis, "PATCH", &privileges(&["ConfigureComponents"])));
+
+        // Writing only overridden properties needs only what they override it with.
+        let account =
+            PrivilegeTarget::new("ManagerAccount", "/redfish/v1/AccountService/Accounts/1");
+        let own = privileges(&["Login", "ConfigureSelf"]);
+        assert!(!registry.allows(&account, "PATCH", &own));
+        let password = account.clone().with_properties(privileges(&["Password"]));
+        assert!(registry.allows(&password, "PATCH", &own));
+        let role = account.with_properties(privileges(&["Password", "RoleId"]));
+        assert!(!registry.allows(&role, "PATCH", &own));
+        assert!(registry.allows(&role, "PATCH", &privileges(&["ConfigureUsers"])));
+
+        // The URI takes precedence over where the resource is, which does over its entity.
+        let ancestors = privileges(&[
+            "ServiceRoot",
+            "ManagerCollection",
+            "Manager",
+            "EthernetInterfaceCollection",
+        ]);
+        let uri = "/redfish/v1/Managers/1/EthernetInterfaces/1";
+        let interface = PrivilegeTarget::new("EthernetInterface", uri);
+        let components = privileges(&["ConfigureComponents"]);
+        let manager = privileges(&["ConfigureManager"]);
+        assert!(registry.allows(&interface, "PATCH", &components));
+        let interface = interface.with_ancestors(ancestors.clone());
+        assert!(!registry.allows(&interface, "PATCH", &components));
+        assert!(registry.allows(&interface, "PATCH", &manager));
+        let uri = "/redfish/v1/Managers/1/EthernetInterfaces/Host";
+        let host = PrivilegeTarget::new("EthernetInterface", uri).with_ancestors(ancestors);
+        assert!(!registry.allows(&host, "PATCH", &manager));
+        assert!(registry.allows(&host, "PATCH", &privileges(&["ConfigureUsers"])));
+    }
+
+    #[test]
+    fn password_hash() {
+        let config = PasswordHashConfig {
+            algorithm: PasswordAlgorithm::Pbkdf2Sha256,
+            iterations: NonZeroU32::new(1000).unwrap(),
+        };
+        let hash = PasswordHash::new("hunter2", &config);
+        assert!(hash.verify("hunter2"));
+        assert!(!hash.verify("hunter3"));
+        // Salted, so the same password does not hash the same twice
+        assert_ne!(hash, PasswordHash::new("hunter2", &config));
+
+        // Persisted in the PHC string format
+        let persisted = hash.to_string();
+        assert!(persisted.starts_with("$pbkdf2-sha256$i=1000$"));
+        let reloaded = PasswordHash::from_str(&persisted).unwrap();
+        assert_eq!(reloaded, hash);
+        assert!(reloaded.verify("hunter2"));
+
+        assert!(!hash.needs_rehash(&config));
+        // Hashes made with fewer iterations, or another algorithm, should be replaced
+        let stronger = PasswordHashConfig {
+            iterations: NonZeroU32::new(2000).unwrap(),
+            ..config.clone()
+        };
+        assert!(hash.needs_rehash(&stronger));
+        assert!(!PasswordHash::new("hunter2", &stronger).needs_rehash(&config));
+        let sha512 = PasswordHashConfig {
+            algorithm: PasswordAlgorithm::Pbkdf2Sha512,
+            ..config.clone()
+        };
+        assert!(hash.needs_rehash(&sha512));
+        let hash = PasswordHash::new("hunter2", &sha512);
+        assert!(hash.verify("hunter2"));
+        assert!(PasswordHash::from_str(&hash.to_string())
+            .unwrap()
+            .verify("hunter2"));
+
+        assert_eq!(
+            PasswordHash::from_str("$2b$12$abcdefghijklmnopqrstuv"),
+            Err(PasswordHashError::InvalidFormat)
+        );
+        assert_eq!(
+            PasswordHash::from_str("$argon2id$i=3$c2FsdA$aGFzaA"),
+            Err(PasswordHashError::UnsupportedAlgorithm(String::from(
+                "argon2id"
+            )))
+        );
+        assert_eq!(
+            PasswordHash::from_str("$pbkdf2-sha256$i=1000$c2FsdA$aGFzaA"),
+            Err(PasswordHashError::InvalidFormat)
+        );
+        let hash = persisted.replace("i=1000", "i=4294967295");
+        assert_eq!(
+            PasswordHash::from_str(&hash),
+            Err(PasswordHashError::TooManyIterations(4294967295))
+        );
+        let hash = persisted.replace("i=1000", "i=0");
+        assert_eq!(
+            PasswordHash::from_str(&hash),
+            Err(PasswordHashError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn query() {
+        let query = Query::parse(
+            "$top=2&only&$filter=not%20(A%20eq%20'Bob''s'%20or%20B%20gt%201)%20and+C%20eq%20true\
+             &$select=Status/Health,Name&$expand=*($levels=2)&Oem=1",
+        )
+        .unwrap();
+        assert_eq!(query.top, Some(2));
+        assert!(query.only);
+        assert!(!query.excerpt);
+        assert_eq!(
+            query.select,
+            Some(vec![String::from("Status/Health"), String::from("Name")])
+        );
+        assert_eq!(
+            query.expand,
+            Some(Expand {
+                kind: ExpandKind::All,
+                levels: 2
+            })
+        );
+        let filter = query.filter.as_ref().unwrap();
+        assert!(filter.matches(&json!({"A": "Alice", "B": 0, "C": true})));
+        assert!(!filter.matches(&json!({"A": "Bob's", "B": 0, "C": true})));
+
+        // Written back in canonical form, which parses to the same query
+        assert_eq!(
+            filter.to_string(),
+            "not (A eq 'Bob''s' or B gt 1) and C eq true"
+        );
+        let canonical = query.to_string();
+        assert_eq!(
+            canonical,
+            "$filter=not%20(A%20eq%20'Bob''s'%20or%20B%20gt%201)%20and%20C%20eq%20true\
+             &$select=Status/Health,Name&$expand=*($levels%3D2)&$top=2&only"
+        );
+        assert_eq!(Query::parse(&canonical), Ok(query));
+        for filter in [
+            "A eq 1 or (B eq 2 or C eq 3)",
+            "(A eq 1 or B eq 2) and C eq 3",
+        ] {
+            let pairs = [(String::from("$filter"), String::from(filter))];
+            let query = Query::from_pairs(&pairs).unwrap();
+            assert_eq!(query.filter.unwrap().to_string(), filter);
+        }
+
+        assert_eq!(
+            Query::parse("$top=x"),
+            Err(QueryError::Malformed(String::from("$top")))
+        );
+        assert_eq!(
+            Query::parse("$filter=A%2"),
+            Err(QueryError::Malformed(String::from("$filter")))
+        );
+        assert_eq!(
+            Query::parse("excerpt=1"),
+            Err(QueryError::Malformed(String::from("excerpt")))
+        );
+        assert_eq!(
+            Query::parse("$levels=1"),
+            Err(QueryError::Unsupported(String::from("$levels")))
+        );
+        assert!(Query::parse("").unwrap().is_empty());
+    }
 }